@@ -0,0 +1,155 @@
+//! Hot write-path benchmarks: lineage computation, row (de)serialization and fork
+//! chunking against the in-memory storage backend.
+//!
+//! Run with: cargo bench --bench lineage [-- <filter>]
+
+mod support;
+
+use aigc_history::{
+    Settings,
+    config::AppConfig,
+    db::MessageRow,
+    domain::{ContentType, Message, MessageRole, TextContent},
+    object_store::S3ObjectStore,
+    repositories::Storage,
+    services::{ForkOptions, ForkService, ImageService, NotificationService, PreferenceService},
+    utils::compute_lineage,
+};
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::Arc;
+use support::Bencher;
+use uuid::Uuid;
+
+/// A single-path conversation of `depth` messages including the root
+fn conversation(depth: usize) -> Vec<Message> {
+    let conversation_id = Uuid::new_v4();
+    let root = Message::new_root(
+        conversation_id,
+        Uuid::new_v4(),
+        "Benchmark".to_string(),
+        "bench".to_string(),
+    );
+
+    let mut messages = vec![root];
+    for i in 1..depth {
+        let parent = &messages[i - 1];
+        let message_id = Uuid::new_v4();
+        let message = Message {
+            conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role: if i % 2 == 1 {
+                MessageRole::Human
+            } else {
+                MessageRole::Assistant
+            },
+            content: ContentType::Text(TextContent {
+                text: "The quick brown fox jumps over the lazy dog. ".repeat(8),
+            }),
+            content_metadata: HashMap::from([("model".to_string(), "bench".to_string())]),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: Utc::now(),
+            created_by: "bench".to_string(),
+        };
+        messages.push(message);
+    }
+    messages
+}
+
+fn bench_compute_lineage(bencher: &Bencher) {
+    for depth in [10, 100, 1000] {
+        let parent: Vec<Uuid> = (0..depth).map(|_| Uuid::new_v4()).collect();
+        let message_id = Uuid::new_v4();
+        bencher.bench(&format!("compute_lineage/depth={}", depth), || {
+            compute_lineage(&parent, message_id)
+        });
+    }
+}
+
+fn bench_message_row(bencher: &Bencher) {
+    for depth in [10, 1000] {
+        let message = conversation(depth).pop().unwrap();
+        bencher.bench(&format!("message_row/serialize/depth={}", depth), || {
+            MessageRow::from_message(&message).unwrap()
+        });
+
+        let row = MessageRow::from_message(&message).unwrap();
+        bencher.bench(&format!("message_row/deserialize/depth={}", depth), || {
+            row.clone().to_message().unwrap()
+        });
+    }
+}
+
+fn bench_fork(bencher: &Bencher) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .expect("Failed to build runtime");
+
+    for (messages, batch_size) in [(100, 100), (1000, 100), (1000, 10)] {
+        let name = format!(
+            "fork_conversation/messages={}/batch={}",
+            messages, batch_size
+        );
+        if !bencher.enabled(&name) {
+            continue;
+        }
+
+        let storage = Storage::memory();
+        let source = conversation(messages);
+        let source_id = source[0].conversation_id;
+        runtime
+            .block_on(storage.lineage.batch_insert_messages(&source))
+            .unwrap();
+
+        let fork_service = ForkService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            AppConfig {
+                max_lineage_depth: messages,
+                max_batch_size: batch_size,
+                ..Settings::default().app
+            },
+            Settings::default().fork,
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+        );
+
+        bencher.bench(&name, || {
+            runtime.block_on(async {
+                let fork = fork_service
+                    .fork_conversation(
+                        source_id,
+                        "Fork".to_string(),
+                        "bench".to_string(),
+                        ForkOptions::default(),
+                    )
+                    .await
+                    .unwrap();
+                // Keep the store at a constant size across iterations
+                storage
+                    .lineage
+                    .delete_conversation(fork.conversation_id)
+                    .await
+                    .unwrap();
+            })
+        });
+    }
+}
+
+fn main() {
+    let bencher = Bencher::from_args();
+
+    bench_compute_lineage(&bencher);
+    bench_message_row(&bencher);
+    bench_fork(&bencher);
+}
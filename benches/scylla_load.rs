@@ -0,0 +1,138 @@
+//! Load benchmark against a live ScyllaDB cluster. Appends messages to a set of
+//! conversations from concurrent writers and reports write throughput and latency.
+//!
+//! Skipped unless `BENCH_SCYLLA=1`. Connection settings come from the usual
+//! `SCYLLA_*` environment variables; point `SCYLLA_KEYSPACE` at a scratch keyspace.
+//!
+//! Run with: BENCH_SCYLLA=1 cargo bench --bench scylla_load
+//! Tune with BENCH_WRITERS (default 32) and BENCH_MESSAGES_PER_WRITER (default 200).
+
+// Only the reporting helper is used here
+#[allow(dead_code)]
+mod support;
+
+use aigc_history::{
+    Settings,
+    db::DbClient,
+    domain::{ContentType, MessageRole, TextContent},
+    object_store::S3ObjectStore,
+    repositories::Storage,
+    services::{
+        ChangeFeed, CollaborationHub, ConversationService, ImageService, NotificationService,
+        PreferenceService, ShareService,
+    },
+    utils::pii::PiiScrubber,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn env_usize(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::var("BENCH_SCYLLA").as_deref() != Ok("1") {
+        println!("scylla_load: skipped (set BENCH_SCYLLA=1 to run against a live cluster)");
+        return Ok(());
+    }
+
+    let writers = env_usize("BENCH_WRITERS", 32);
+    let messages_per_writer = env_usize("BENCH_MESSAGES_PER_WRITER", 200);
+
+    let settings = Settings::from_env()?;
+    let db_client = DbClient::new(&settings.scylla).await?;
+
+    let storage = Storage::scylla(db_client.clone());
+    let change_feed = ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new()));
+    let notifications = Arc::new(NotificationService::new(
+        storage.notifications.clone(),
+        Arc::new(PreferenceService::new(storage.preferences.clone())),
+    ));
+    let service = Arc::new(ConversationService::new(
+        storage.lineage.clone(),
+        change_feed.clone(),
+        settings.app.clone(),
+        notifications.clone(),
+        Arc::new(PiiScrubber::new(&settings.pii)),
+        Arc::new(ImageService::new(
+            storage.images.clone(),
+            storage.lineage.clone(),
+            Arc::new(S3ObjectStore::new(settings.s3.clone())),
+            settings.images.clone(),
+        )),
+        Arc::new(ShareService::new(
+            storage.shares.clone(),
+            change_feed,
+            notifications,
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        )),
+    ));
+
+    let start = Instant::now();
+    let mut tasks = Vec::with_capacity(writers);
+    for writer in 0..writers {
+        let service = service.clone();
+        tasks.push(tokio::spawn(async move {
+            let conversation = service
+                .create_conversation(format!("Load {}", writer), "bench".to_string())
+                .await?;
+            let mut parent = conversation.root_message.message_id;
+            let mut latencies = Vec::with_capacity(messages_per_writer);
+
+            for i in 0..messages_per_writer {
+                let started = Instant::now();
+                let message = service
+                    .append_message(
+                        conversation.conversation_id,
+                        parent,
+                        if i % 2 == 0 {
+                            MessageRole::Human
+                        } else {
+                            MessageRole::Assistant
+                        },
+                        ContentType::Text(TextContent {
+                            text: format!("Message {} from writer {}", i, writer),
+                        }),
+                        HashMap::new(),
+                        "bench".to_string(),
+                    )
+                    .await?;
+                latencies.push(started.elapsed());
+                parent = message.message_id;
+            }
+
+            service
+                .delete_conversation(conversation.conversation_id)
+                .await?;
+            Ok::<_, aigc_history::db::DbError>(latencies)
+        }));
+    }
+
+    let mut latencies: Vec<Duration> = Vec::with_capacity(writers * messages_per_writer);
+    for task in tasks {
+        latencies.extend(task.await??);
+    }
+    let elapsed = start.elapsed();
+
+    latencies.sort();
+    let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+    println!(
+        "scylla_load: {} appends from {} writers in {:.2?} ({:.0} appends/s)",
+        latencies.len(),
+        writers,
+        elapsed,
+        latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    support::report("scylla_load/append_message/p50", percentile(0.50), 1);
+    support::report("scylla_load/append_message/p99", percentile(0.99), 1);
+
+    drop(storage);
+    db_client.close();
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+//! Minimal timing harness shared by the `harness = false` benchmarks.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Time budget spent measuring each benchmark after warm-up
+const MEASUREMENT_TIME: Duration = Duration::from_secs(2);
+const WARM_UP_TIME: Duration = Duration::from_millis(500);
+
+/// Runs benchmarks whose name contains the filter passed on the command line
+/// (`cargo bench --bench lineage -- fork`)
+pub struct Bencher {
+    filter: Option<String>,
+}
+
+impl Bencher {
+    pub fn from_args() -> Self {
+        // cargo passes `--bench` to harness-less targets; ignore flags
+        let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+        Self { filter }
+    }
+
+    pub fn enabled(&self, name: &str) -> bool {
+        self.filter.as_deref().is_none_or(|f| name.contains(f))
+    }
+
+    /// Time a synchronous routine and print the mean time per iteration
+    pub fn bench<T>(&self, name: &str, mut routine: impl FnMut() -> T) {
+        if !self.enabled(name) {
+            return;
+        }
+
+        let warm_up = Instant::now();
+        while warm_up.elapsed() < WARM_UP_TIME {
+            black_box(routine());
+        }
+
+        let mut iterations: u64 = 0;
+        let start = Instant::now();
+        while start.elapsed() < MEASUREMENT_TIME {
+            black_box(routine());
+            iterations += 1;
+        }
+
+        report(name, start.elapsed(), iterations);
+    }
+}
+
+/// Print a result line in a stable, grep-friendly format
+pub fn report(name: &str, elapsed: Duration, iterations: u64) {
+    let per_iter = elapsed.as_nanos() as f64 / iterations.max(1) as f64;
+    let (value, unit) = if per_iter >= 1_000_000.0 {
+        (per_iter / 1_000_000.0, "ms")
+    } else if per_iter >= 1_000.0 {
+        (per_iter / 1_000.0, "µs")
+    } else {
+        (per_iter, "ns")
+    };
+    println!(
+        "{:<48} {:>10.2} {}/iter ({} iterations)",
+        name, value, unit, iterations
+    );
+}
@@ -0,0 +1,252 @@
+use reqwest::{Method, RequestBuilder, Response, Url, header};
+use serde::de::DeserializeOwned;
+
+use crate::error::{ClientError, ErrorBody};
+
+/// Header naming the calling user
+const USER_ID_HEADER: &str = "X-User-ID";
+
+/// Header carrying a service account's token
+const SERVICE_TOKEN_HEADER: &str = "X-Service-Token";
+
+/// Who requests are made as
+#[derive(Debug, Clone)]
+enum Identity {
+    Anonymous,
+    User(String),
+    Service(String),
+}
+
+/// Client of one aigc-history deployment. Cheap to clone; clones share the
+/// connection pool.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: Url,
+    identity: Identity,
+}
+
+impl Client {
+    /// A client of the server at `base_url`, e.g. `http://localhost:8080`,
+    /// making anonymous requests until given an identity
+    pub fn new(base_url: &str) -> Result<Self, ClientError> {
+        let base_url =
+            Url::parse(base_url).map_err(|_| ClientError::InvalidBaseUrl(base_url.to_string()))?;
+        if base_url.cannot_be_a_base() {
+            return Err(ClientError::InvalidBaseUrl(base_url.to_string()));
+        }
+
+        Ok(Self {
+            http: reqwest::Client::new(),
+            base_url,
+            identity: Identity::Anonymous,
+        })
+    }
+
+    /// Make requests as `user_id`, sent in `X-User-ID`
+    pub fn with_user(mut self, user_id: impl Into<String>) -> Self {
+        self.identity = Identity::User(user_id.into());
+        self
+    }
+
+    /// Make requests as the service account `token` belongs to, sent in
+    /// `X-Service-Token`
+    pub fn with_service_token(mut self, token: impl Into<String>) -> Self {
+        self.identity = Identity::Service(token.into());
+        self
+    }
+
+    /// Send requests through `http`, e.g. one built with timeouts or a proxy
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// A request to the path made of `segments`, each percent-encoded, with
+    /// the client's identity
+    pub(crate) fn request(&self, method: Method, segments: &[&str]) -> RequestBuilder {
+        let mut url = self.base_url.clone();
+        url.path_segments_mut()
+            .expect("checked in Client::new")
+            .pop_if_empty()
+            .extend(segments);
+
+        let request = self
+            .http
+            .request(method, url)
+            .header(header::ACCEPT, "application/json");
+        match &self.identity {
+            Identity::Anonymous => request,
+            Identity::User(user_id) => request.header(USER_ID_HEADER, user_id),
+            Identity::Service(token) => request.header(SERVICE_TOKEN_HEADER, token),
+        }
+    }
+
+    /// Send `request` and decode its JSON response
+    pub(crate) async fn send<T: DeserializeOwned>(
+        &self,
+        request: RequestBuilder,
+    ) -> Result<T, ClientError> {
+        Ok(self.execute(request).await?.json().await?)
+    }
+
+    /// Send `request`, discarding the response body
+    pub(crate) async fn send_empty(&self, request: RequestBuilder) -> Result<(), ClientError> {
+        self.execute(request).await?;
+        Ok(())
+    }
+
+    /// Send `request` and return its response body as text
+    pub(crate) async fn send_text(&self, request: RequestBuilder) -> Result<String, ClientError> {
+        Ok(self.execute(request).await?.text().await?)
+    }
+
+    /// Send `request`, turning error statuses into `ClientError::Api`
+    async fn execute(&self, request: RequestBuilder) -> Result<Response, ClientError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let retry_after = response
+            .headers()
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+        let text = response.text().await.unwrap_or_default();
+        let body: ErrorBody = serde_json::from_str(&text).unwrap_or_default();
+
+        Err(ClientError::Api {
+            status: status.as_u16(),
+            code: body.code.unwrap_or_default(),
+            message: body.error.or(body.detail).unwrap_or(text),
+            retry_after,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::dto::TrendingQuery;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    /// A server answering each connection with the next of `responses`,
+    /// returning the requests it got
+    async fn serve(responses: Vec<String>) -> (String, tokio::task::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests.push(String::from_utf8(request).unwrap());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+            requests
+        });
+
+        (base_url, handle)
+    }
+
+    fn response(status: &str, headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n{}\r\n{}",
+            status,
+            body.len(),
+            headers,
+            body
+        )
+    }
+
+    #[tokio::test]
+    async fn test_requests_carry_identity_path_and_query() {
+        let conversation_id = Uuid::new_v4();
+        let (base_url, server) = serve(vec![
+            response("200 OK", "", &format!("[\"{}\"]", conversation_id)),
+            response("200 OK", "", "[]"),
+        ])
+        .await;
+        let client = Client::new(&format!("{}/", base_url))
+            .unwrap()
+            .with_user("ann smith");
+
+        let conversations = client
+            .get_user_conversations("ann smith", None)
+            .await
+            .unwrap();
+        assert_eq!(conversations, vec![conversation_id]);
+        client
+            .with_service_token("indexer-token")
+            .get_trending(&TrendingQuery {
+                lang: Some("pt-BR".to_string()),
+                limit: Some(5),
+            })
+            .await
+            .unwrap();
+
+        let requests = server.await.unwrap();
+        assert!(
+            requests[0].starts_with("GET /api/v1/users/ann%20smith/conversations HTTP/1.1"),
+            "{}",
+            requests[0]
+        );
+        assert!(requests[0].contains("x-user-id: ann smith"));
+        assert!(
+            requests[1].starts_with("GET /api/v1/explore/trending?lang=pt-BR&limit=5 HTTP/1.1"),
+            "{}",
+            requests[1]
+        );
+        assert!(requests[1].contains("x-service-token: indexer-token"));
+        assert!(!requests[1].contains("x-user-id"));
+    }
+
+    #[tokio::test]
+    async fn test_error_responses_become_api_errors() {
+        let (base_url, server) = serve(vec![
+            response(
+                "404 Not Found",
+                "",
+                r#"{"error":"Conversation not found","code":"not_found"}"#,
+            ),
+            response(
+                "429 Too Many Requests",
+                "Retry-After: 7\r\n",
+                r#"{"type":"about:blank","title":"Too Many Requests","status":429,"detail":"Slow down","code":"rate_limited"}"#,
+            ),
+        ])
+        .await;
+        let client = Client::new(&base_url).unwrap().with_user("alice");
+
+        let err = client.get_conversation(Uuid::new_v4()).await.unwrap_err();
+        assert!(err.is_not_found());
+        assert_eq!(err.to_string(), "404 not_found: Conversation not found");
+        match client.get_lock(Uuid::new_v4()).await.unwrap_err() {
+            ClientError::Api {
+                status,
+                code,
+                message,
+                retry_after,
+            } => {
+                assert_eq!((status, code.as_str()), (429, "rate_limited"));
+                assert_eq!((message.as_str(), retry_after), ("Slow down", Some(7)));
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+        server.await.unwrap();
+
+        assert!(matches!(
+            Client::new("not a url"),
+            Err(ClientError::InvalidBaseUrl(_))
+        ));
+    }
+}
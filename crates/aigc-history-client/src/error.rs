@@ -0,0 +1,43 @@
+use serde::Deserialize;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ClientError {
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    /// The server answered with an error status
+    #[error("{status} {code}: {message}")]
+    Api {
+        status: u16,
+        /// Stable error code such as `not_found` or `conversation_locked`
+        code: String,
+        message: String,
+        /// Seconds to wait before retrying, for `429` and `503`
+        retry_after: Option<u64>,
+    },
+    #[error("`{0}` is not a valid base URL")]
+    InvalidBaseUrl(String),
+}
+
+impl ClientError {
+    /// HTTP status of an error response
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::Request(err) => err.status().map(|status| status.as_u16()),
+            ClientError::InvalidBaseUrl(_) => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404)
+    }
+}
+
+/// Body of an error response: `{"error", "code"}`, or problem details with
+/// `detail` and `code` when the server renders those
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct ErrorBody {
+    pub error: Option<String>,
+    pub detail: Option<String>,
+    pub code: Option<String>,
+}
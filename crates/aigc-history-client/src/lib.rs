@@ -0,0 +1,38 @@
+//! Typed async client for the aigc-history API.
+//!
+//! Requests and responses are the types the server itself uses, from
+//! `aigc-history-types`, re-exported here as [`types`]:
+//!
+//! ```no_run
+//! use aigc_history_client::Client;
+//! use aigc_history_client::types::dto::CreateConversationRequest;
+//!
+//! # async fn run() -> Result<(), aigc_history_client::ClientError> {
+//! let client = Client::new("http://localhost:8080")?.with_user("alice");
+//! let conversation = client
+//!     .create_conversation(&CreateConversationRequest {
+//!         title: "Sauces".to_string(),
+//!         created_by: None,
+//!         language: Some("en".to_string()),
+//!     })
+//!     .await?;
+//! let tree = client
+//!     .get_conversation_tree(conversation.conversation_id, &Default::default())
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Every endpoint of `/api/v1` and the health probes has a method on
+//! [`Client`]; `/api/v2` is reached through [`Client::v2`]. The live event
+//! stream (`/live`) is server-sent events and isn't covered.
+
+mod client;
+mod error;
+mod v1;
+mod v2;
+
+pub use aigc_history_types as types;
+pub use client::Client;
+pub use error::ClientError;
+pub use v2::V2;
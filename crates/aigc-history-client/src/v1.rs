@@ -0,0 +1,987 @@
+//! Methods for the endpoints of `/api/v1` and the health probes, in the
+//! order the README documents them
+
+use reqwest::Method;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::client::Client;
+use crate::error::ClientError;
+use crate::types::domain::NativeExport;
+use crate::types::dto::*;
+use crate::types::{ExportFormat, PresenceSignal};
+
+/// Answer of `POST /presence`
+#[derive(Deserialize)]
+struct PresenceAck {
+    subscribers: usize,
+}
+
+impl Client {
+    fn v1(&self, method: Method, path: &[&str]) -> reqwest::RequestBuilder {
+        let segments: Vec<&str> = ["api", "v1"].iter().chain(path).copied().collect();
+        self.request(method, &segments)
+    }
+
+    // Conversations
+
+    pub async fn create_conversation(
+        &self,
+        request: &CreateConversationRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        self.send(self.v1(Method::POST, &["conversations"]).json(request))
+            .await
+    }
+
+    pub async fn get_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<ConversationResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id]))
+            .await
+    }
+
+    pub async fn get_conversation_tree(
+        &self,
+        conversation_id: Uuid,
+        query: &TreeQuery,
+    ) -> Result<TreeResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "tree"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn update_conversation(
+        &self,
+        conversation_id: Uuid,
+        request: &UpdateConversationRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::PUT, &["conversations", &id]).json(request))
+            .await
+    }
+
+    pub async fn set_published_branch(
+        &self,
+        conversation_id: Uuid,
+        request: &PublishBranchRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::PUT, &["conversations", &id, "published-branch"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), ClientError> {
+        let id = conversation_id.to_string();
+        self.send_empty(self.v1(Method::DELETE, &["conversations", &id]))
+            .await
+    }
+
+    pub async fn get_duplicates(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<DuplicatesResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "duplicates"]))
+            .await
+    }
+
+    pub async fn scrub_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<ScrubResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::POST, &["conversations", &id, "scrub"]))
+            .await
+    }
+
+    pub async fn lock_conversation(
+        &self,
+        conversation_id: Uuid,
+        request: &LockConversationRequest,
+    ) -> Result<LockResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "lock"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_lock(&self, conversation_id: Uuid) -> Result<LockResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "lock"]))
+            .await
+    }
+
+    pub async fn unlock_conversation(&self, conversation_id: Uuid) -> Result<(), ClientError> {
+        let id = conversation_id.to_string();
+        self.send_empty(self.v1(Method::DELETE, &["conversations", &id, "lock"]))
+            .await
+    }
+
+    pub async fn get_changes(
+        &self,
+        conversation_id: Uuid,
+        query: &ChangesQuery,
+    ) -> Result<ChangesResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "changes"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn sync_conversation(
+        &self,
+        conversation_id: Uuid,
+        request: &SyncRequest,
+    ) -> Result<SyncResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "sync"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_events(
+        &self,
+        conversation_id: Uuid,
+        query: &EventsQuery,
+    ) -> Result<EventsResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "events"])
+                .query(query),
+        )
+        .await
+    }
+
+    /// Broadcast presence to the conversation's live followers; returns how
+    /// many there are
+    pub async fn post_presence(
+        &self,
+        conversation_id: Uuid,
+        signal: &PresenceSignal,
+    ) -> Result<usize, ClientError> {
+        let id = conversation_id.to_string();
+        let ack: PresenceAck = self
+            .send(
+                self.v1(Method::POST, &["conversations", &id, "presence"])
+                    .json(signal),
+            )
+            .await?;
+        Ok(ack.subscribers)
+    }
+
+    pub async fn get_access_log(
+        &self,
+        conversation_id: Uuid,
+        query: &AccessLogQuery,
+    ) -> Result<AccessLogResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "access-log"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn get_conversation_diff(
+        &self,
+        conversation_id: Uuid,
+        query: &DiffQuery,
+    ) -> Result<DiffResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "diff"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn search_conversation(
+        &self,
+        conversation_id: Uuid,
+        query: &MessageSearchQuery,
+    ) -> Result<MessageSearchResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "search"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn create_embed_token(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateEmbedTokenRequest,
+    ) -> Result<EmbedTokenResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "embed-token"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_conversation_analytics(
+        &self,
+        conversation_id: Uuid,
+        query: &AnalyticsQuery,
+    ) -> Result<AnalyticsResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "analytics"])
+                .query(query),
+        )
+        .await
+    }
+
+    // Messages
+
+    pub async fn create_message(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateMessageRequest,
+    ) -> Result<MessageResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "messages"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<MessageResponse, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(self.v1(
+            Method::GET,
+            &["conversations", &id, "messages", &message_id],
+        ))
+        .await
+    }
+
+    pub async fn get_message_children(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<Vec<MessageResponse>, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(self.v1(
+            Method::GET,
+            &["conversations", &id, "messages", &message_id, "children"],
+        ))
+        .await
+    }
+
+    pub async fn get_message_lineage(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<Vec<MessageResponse>, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(self.v1(
+            Method::GET,
+            &["conversations", &id, "messages", &message_id, "lineage"],
+        ))
+        .await
+    }
+
+    pub async fn get_message_ancestors(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        query: &AncestorsQuery,
+    ) -> Result<AncestorsResponse, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(
+            self.v1(
+                Method::GET,
+                &["conversations", &id, "messages", &message_id, "ancestors"],
+            )
+            .query(query),
+        )
+        .await
+    }
+
+    pub async fn get_conversation_context(
+        &self,
+        conversation_id: Uuid,
+        query: &ContextQuery,
+    ) -> Result<ContextResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "context"])
+                .query(query),
+        )
+        .await
+    }
+
+    /// Reparent a message with its replies; returns the moved messages
+    pub async fn move_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        request: &MoveMessageRequest,
+    ) -> Result<Vec<MessageResponse>, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(
+            self.v1(
+                Method::POST,
+                &["conversations", &id, "messages", &message_id, "move"],
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    /// Hide a message and its replies from everyone but the owner; returns
+    /// the conversation's private messages
+    pub async fn make_message_private(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<PrivateMessagesResponse, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(self.v1(
+            Method::PUT,
+            &["conversations", &id, "messages", &message_id, "private"],
+        ))
+        .await
+    }
+
+    pub async fn make_message_shared(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<PrivateMessagesResponse, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(self.v1(
+            Method::DELETE,
+            &["conversations", &id, "messages", &message_id, "private"],
+        ))
+        .await
+    }
+
+    // Branches
+
+    pub async fn create_branch(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateBranchRequest,
+    ) -> Result<BranchResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "branches"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_branches(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<BranchResponse>, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "branches"]))
+            .await
+    }
+
+    pub async fn get_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+    ) -> Result<BranchResponse, ClientError> {
+        let (id, branch_id) = (conversation_id.to_string(), branch_id.to_string());
+        self.send(self.v1(Method::GET, &["conversations", &id, "branches", &branch_id]))
+            .await
+    }
+
+    pub async fn get_branch_by_slug(
+        &self,
+        conversation_id: Uuid,
+        slug: &str,
+    ) -> Result<BranchResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(
+            Method::GET,
+            &["conversations", &id, "branches", "by-slug", slug],
+        ))
+        .await
+    }
+
+    pub async fn get_branch_messages(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        query: &BranchMessagesQuery,
+    ) -> Result<Vec<MessageResponse>, ClientError> {
+        let (id, branch_id) = (conversation_id.to_string(), branch_id.to_string());
+        self.send(
+            self.v1(
+                Method::GET,
+                &["conversations", &id, "branches", &branch_id, "messages"],
+            )
+            .query(query),
+        )
+        .await
+    }
+
+    pub async fn update_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        request: &UpdateBranchRequest,
+    ) -> Result<BranchResponse, ClientError> {
+        let (id, branch_id) = (conversation_id.to_string(), branch_id.to_string());
+        self.send(
+            self.v1(Method::PUT, &["conversations", &id, "branches", &branch_id])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn delete_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+    ) -> Result<(), ClientError> {
+        let (id, branch_id) = (conversation_id.to_string(), branch_id.to_string());
+        self.send_empty(self.v1(
+            Method::DELETE,
+            &["conversations", &id, "branches", &branch_id],
+        ))
+        .await
+    }
+
+    // Checkpoints
+
+    pub async fn create_checkpoint(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateCheckpointRequest,
+    ) -> Result<MessageResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "checkpoints"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_checkpoints(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<MessageResponse>, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "checkpoints"]))
+            .await
+    }
+
+    // Forking
+
+    pub async fn duplicate_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<ConversationResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::POST, &["conversations", &id, "duplicate"]))
+            .await
+    }
+
+    pub async fn fork_conversation(
+        &self,
+        conversation_id: Uuid,
+        request: &ForkConversationRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "fork"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn fork_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        request: &ForkConversationRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        let (id, branch_id) = (conversation_id.to_string(), branch_id.to_string());
+        self.send(
+            self.v1(
+                Method::POST,
+                &["conversations", &id, "branches", &branch_id, "fork"],
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    pub async fn fork_from_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        request: &ForkConversationRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.send(
+            self.v1(
+                Method::POST,
+                &["conversations", &id, "messages", &message_id, "fork"],
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_fork_graph(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<ForkGraphResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "fork-graph"]))
+            .await
+    }
+
+    // Exports and imports
+
+    /// The transcript in the requested format, as the server renders it
+    pub async fn export_conversation(
+        &self,
+        conversation_id: Uuid,
+        query: &ExportQuery,
+    ) -> Result<String, ClientError> {
+        let id = conversation_id.to_string();
+        self.send_text(
+            self.v1(Method::GET, &["conversations", &id, "export"])
+                .query(query),
+        )
+        .await
+    }
+
+    /// The whole conversation in the native format, for
+    /// [`import_native`](Self::import_native); shares are for the owner to
+    /// export
+    pub async fn export_native(
+        &self,
+        conversation_id: Uuid,
+        include_shares: bool,
+    ) -> Result<NativeExport, ClientError> {
+        let id = conversation_id.to_string();
+        let query = ExportQuery {
+            format: ExportFormat::Native,
+            branch_id: None,
+            leaf_message_id: None,
+            include_shares,
+        };
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "export"])
+                .query(&query),
+        )
+        .await
+    }
+
+    pub async fn create_export(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateExportRequest,
+    ) -> Result<ExportResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "exports"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_exports(
+        &self,
+        conversation_id: Uuid,
+        query: &ExportListQuery,
+    ) -> Result<Vec<ExportResponse>, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::GET, &["conversations", &id, "exports"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn get_export(
+        &self,
+        conversation_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<ExportResponse, ClientError> {
+        let (id, export_id) = (conversation_id.to_string(), export_id.to_string());
+        self.send(self.v1(Method::GET, &["conversations", &id, "exports", &export_id]))
+            .await
+    }
+
+    /// Import the conversations of a ChatGPT data export, the parsed
+    /// `conversations.json`
+    pub async fn import_chatgpt(
+        &self,
+        query: &ImportQuery,
+        conversations: &serde_json::Value,
+    ) -> Result<ImportResponse, ClientError> {
+        self.send(
+            self.v1(Method::POST, &["imports", "chatgpt"])
+                .query(query)
+                .json(conversations),
+        )
+        .await
+    }
+
+    /// Import a native export as a new conversation owned by the caller
+    pub async fn import_native(
+        &self,
+        export: &NativeExport,
+    ) -> Result<ImportedConversationResponse, ClientError> {
+        self.send(self.v1(Method::POST, &["imports", "native"]).json(export))
+            .await
+    }
+
+    // Sharing
+
+    pub async fn share_conversation(
+        &self,
+        conversation_id: Uuid,
+        request: &ShareConversationRequest,
+    ) -> Result<ShareResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "share"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn batch_update_shares(
+        &self,
+        conversation_id: Uuid,
+        request: &BatchShareRequest,
+    ) -> Result<BatchShareResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "shares", "batch"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_shares(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<ShareResponse>, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "shares"]))
+            .await
+    }
+
+    pub async fn revoke_share(
+        &self,
+        conversation_id: Uuid,
+        user_id: &str,
+    ) -> Result<(), ClientError> {
+        let id = conversation_id.to_string();
+        self.send_empty(self.v1(Method::DELETE, &["conversations", &id, "shares", user_id]))
+            .await
+    }
+
+    pub async fn create_invite(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateInviteRequest,
+    ) -> Result<InviteResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "invites"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn accept_invite(
+        &self,
+        request: &AcceptInviteRequest,
+    ) -> Result<ShareResponse, ClientError> {
+        self.send(self.v1(Method::POST, &["invites", "accept"]).json(request))
+            .await
+    }
+
+    pub async fn get_shared_with_me(
+        &self,
+        user_id: &str,
+        query: &SharedWithMeQuery,
+    ) -> Result<Vec<ShareResponse>, ClientError> {
+        self.send(
+            self.v1(Method::GET, &["users", user_id, "shared-with-me"])
+                .query(query),
+        )
+        .await
+    }
+
+    // Webhooks
+
+    pub async fn create_webhook(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateWebhookRequest,
+    ) -> Result<CreatedWebhookResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::POST, &["conversations", &id, "webhooks"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_webhooks(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<WebhookResponse>, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(self.v1(Method::GET, &["conversations", &id, "webhooks"]))
+            .await
+    }
+
+    pub async fn delete_webhook(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+    ) -> Result<(), ClientError> {
+        let (id, subscription_id) = (conversation_id.to_string(), subscription_id.to_string());
+        self.send_empty(self.v1(
+            Method::DELETE,
+            &["conversations", &id, "webhooks", &subscription_id],
+        ))
+        .await
+    }
+
+    pub async fn redeliver_webhook(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+        request: &RedeliverWebhookRequest,
+    ) -> Result<WebhookResponse, ClientError> {
+        let (id, subscription_id) = (conversation_id.to_string(), subscription_id.to_string());
+        self.send(
+            self.v1(
+                Method::POST,
+                &[
+                    "conversations",
+                    &id,
+                    "webhooks",
+                    &subscription_id,
+                    "redeliver",
+                ],
+            )
+            .json(request),
+        )
+        .await
+    }
+
+    // Users
+
+    /// IDs of the conversations the user has access to, most recently
+    /// active first
+    pub async fn get_user_conversations(
+        &self,
+        user_id: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<Uuid>, ClientError> {
+        let query = UserConversationsQuery {
+            limit,
+            ..Default::default()
+        };
+        self.send(
+            self.v1(Method::GET, &["users", user_id, "conversations"])
+                .query(&query),
+        )
+        .await
+    }
+
+    /// The user's conversations whose title matches `query.q`
+    pub async fn search_user_conversations(
+        &self,
+        user_id: &str,
+        query: &UserConversationsQuery,
+    ) -> Result<Vec<ConversationMatchResponse>, ClientError> {
+        self.send(
+            self.v1(Method::GET, &["users", user_id, "conversations"])
+                .query(query),
+        )
+        .await
+    }
+
+    /// Start deleting the user's conversations; returns the job doing it
+    pub async fn delete_user_conversations(
+        &self,
+        user_id: &str,
+        query: &DeleteUserConversationsQuery,
+    ) -> Result<JobResponse, ClientError> {
+        self.send(
+            self.v1(Method::DELETE, &["users", user_id, "conversations"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn get_preferences(&self, user_id: &str) -> Result<PreferencesResponse, ClientError> {
+        self.send(self.v1(Method::GET, &["users", user_id, "preferences"]))
+            .await
+    }
+
+    pub async fn update_preferences(
+        &self,
+        user_id: &str,
+        request: &UpdatePreferencesRequest,
+    ) -> Result<PreferencesResponse, ClientError> {
+        self.send(
+            self.v1(Method::PUT, &["users", user_id, "preferences"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_notifications(
+        &self,
+        user_id: &str,
+        query: &NotificationsQuery,
+    ) -> Result<Vec<NotificationResponse>, ClientError> {
+        self.send(
+            self.v1(Method::GET, &["users", user_id, "notifications"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn mark_notifications_read(
+        &self,
+        user_id: &str,
+        request: &MarkNotificationsReadRequest,
+    ) -> Result<(), ClientError> {
+        self.send_empty(
+            self.v1(Method::POST, &["users", user_id, "notifications", "read"])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn get_user_analytics(
+        &self,
+        user_id: &str,
+        query: &AnalyticsQuery,
+    ) -> Result<AnalyticsResponse, ClientError> {
+        self.send(
+            self.v1(Method::GET, &["users", user_id, "analytics"])
+                .query(query),
+        )
+        .await
+    }
+
+    // Jobs
+
+    pub async fn get_user_jobs(
+        &self,
+        user_id: &str,
+        query: &UserJobsQuery,
+    ) -> Result<Vec<JobResponse>, ClientError> {
+        self.send(
+            self.v1(Method::GET, &["users", user_id, "jobs"])
+                .query(query),
+        )
+        .await
+    }
+
+    pub async fn get_job(&self, job_id: Uuid) -> Result<JobResponse, ClientError> {
+        let job_id = job_id.to_string();
+        self.send(self.v1(Method::GET, &["jobs", &job_id])).await
+    }
+
+    pub async fn cancel_job(&self, job_id: Uuid) -> Result<JobResponse, ClientError> {
+        let job_id = job_id.to_string();
+        self.send(self.v1(Method::POST, &["jobs", &job_id, "cancel"]))
+            .await
+    }
+
+    // Explore
+
+    pub async fn get_trending(
+        &self,
+        query: &TrendingQuery,
+    ) -> Result<Vec<TrendingConversationResponse>, ClientError> {
+        self.send(self.v1(Method::GET, &["explore", "trending"]).query(query))
+            .await
+    }
+
+    // Administration
+
+    pub async fn get_usage(&self, query: &UsageQuery) -> Result<UsageResponse, ClientError> {
+        self.send(self.v1(Method::GET, &["admin", "usage"]).query(query))
+            .await
+    }
+
+    pub async fn get_legal_holds(&self) -> Result<Vec<LegalHoldResponse>, ClientError> {
+        self.send(self.v1(Method::GET, &["admin", "legal-holds"]))
+            .await
+    }
+
+    pub async fn place_legal_hold(
+        &self,
+        conversation_id: Uuid,
+        request: &PlaceLegalHoldRequest,
+    ) -> Result<LegalHoldResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.send(
+            self.v1(Method::PUT, &["admin", "legal-holds", &id])
+                .json(request),
+        )
+        .await
+    }
+
+    pub async fn release_legal_hold(&self, conversation_id: Uuid) -> Result<(), ClientError> {
+        let id = conversation_id.to_string();
+        self.send_empty(self.v1(Method::DELETE, &["admin", "legal-holds", &id]))
+            .await
+    }
+
+    // Health
+
+    pub async fn health(&self) -> Result<HealthResponse, ClientError> {
+        self.send(self.request(Method::GET, &["health"])).await
+    }
+
+    pub async fn liveness(&self) -> Result<ProbeResponse, ClientError> {
+        self.send(self.request(Method::GET, &["health", "live"]))
+            .await
+    }
+
+    pub async fn startup(&self) -> Result<ProbeResponse, ClientError> {
+        self.send(self.request(Method::GET, &["health", "startup"]))
+            .await
+    }
+
+    /// Fails with a `503` API error while the server isn't ready
+    pub async fn readiness(&self) -> Result<ReadinessResponse, ClientError> {
+        self.send(self.request(Method::GET, &["health", "ready"]))
+            .await
+    }
+}
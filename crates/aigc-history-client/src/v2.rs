@@ -0,0 +1,103 @@
+//! Methods for the endpoints of `/api/v2`
+
+use reqwest::Method;
+use uuid::Uuid;
+
+use crate::client::Client;
+use crate::error::ClientError;
+use crate::types::v2::*;
+
+/// The `/api/v2` endpoints of a [`Client`], from [`Client::v2`]
+#[derive(Debug, Clone, Copy)]
+pub struct V2<'a> {
+    client: &'a Client,
+}
+
+impl Client {
+    /// The `/api/v2` endpoints, made with this client's identity
+    pub fn v2(&self) -> V2<'_> {
+        V2 { client: self }
+    }
+}
+
+impl V2<'_> {
+    fn request(&self, method: Method, path: &[&str]) -> reqwest::RequestBuilder {
+        let segments: Vec<&str> = ["api", "v2"].iter().chain(path).copied().collect();
+        self.client.request(method, &segments)
+    }
+
+    pub async fn create_conversation(
+        &self,
+        request: &CreateConversationRequest,
+    ) -> Result<ConversationResponse, ClientError> {
+        self.client
+            .send(self.request(Method::POST, &["conversations"]).json(request))
+            .await
+    }
+
+    pub async fn get_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<ConversationResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.client
+            .send(self.request(Method::GET, &["conversations", &id]))
+            .await
+    }
+
+    pub async fn get_conversation_tree(
+        &self,
+        conversation_id: Uuid,
+        query: &TreeQuery,
+    ) -> Result<TreeResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.client
+            .send(
+                self.request(Method::GET, &["conversations", &id, "tree"])
+                    .query(query),
+            )
+            .await
+    }
+
+    pub async fn create_message(
+        &self,
+        conversation_id: Uuid,
+        request: &CreateMessageRequest,
+    ) -> Result<MessageResponse, ClientError> {
+        let id = conversation_id.to_string();
+        self.client
+            .send(
+                self.request(Method::POST, &["conversations", &id, "messages"])
+                    .json(request),
+            )
+            .await
+    }
+
+    pub async fn get_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<MessageResponse, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.client
+            .send(self.request(
+                Method::GET,
+                &["conversations", &id, "messages", &message_id],
+            ))
+            .await
+    }
+
+    pub async fn get_message_lineage(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<Vec<MessageResponse>, ClientError> {
+        let (id, message_id) = (conversation_id.to_string(), message_id.to_string());
+        self.client
+            .send(self.request(
+                Method::GET,
+                &["conversations", &id, "messages", &message_id, "lineage"],
+            ))
+            .await
+    }
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// How a context over the token budget is brought under it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ContextStrategy {
+    /// Drop the oldest turns
+    #[default]
+    TruncateOldest,
+    /// Replace the range covered by the deepest checkpoint on the path with
+    /// its summary, then drop the oldest turns if still needed
+    Summary,
+}
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A read of a conversation, as shown to its owner. Unlike the event log,
+/// which records changes, the access log records who looked and where.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccessLogEntry {
+    pub conversation_id: Uuid,
+    pub accessed_at: DateTime<Utc>,
+    pub entry_id: Uuid,
+    /// `None` for anonymous reads of public conversations and embeds
+    pub viewer: Option<String>,
+    /// Read through an embed token
+    pub via_embed: bool,
+    /// The branch read, for branch endpoints
+    pub branch_id: Option<Uuid>,
+    /// Endpoint relative to the conversation, e.g. `tree` or
+    /// `branches/{branch_id}/messages`; empty for the conversation itself
+    pub resource: String,
+}
+
+impl AccessLogEntry {
+    pub fn new(
+        conversation_id: Uuid,
+        viewer: Option<String>,
+        via_embed: bool,
+        resource: String,
+    ) -> Self {
+        let mut segments = resource.split('/');
+        let branch_id = match (segments.next(), segments.next()) {
+            (Some("branches"), Some(id)) => Uuid::parse_str(id).ok(),
+            _ => None,
+        };
+
+        AccessLogEntry {
+            conversation_id,
+            accessed_at: Utc::now(),
+            entry_id: Uuid::new_v4(),
+            viewer,
+            via_embed,
+            branch_id,
+            resource,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Branch {
+    pub conversation_id: Uuid,
+    pub branch_id: Uuid,
+    pub branch_name: String,
+    /// URL-safe handle, unique within the conversation; links using an
+    /// earlier slug keep resolving after a rename
+    pub slug: String,
+    pub leaf_message_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_updated: DateTime<Utc>,
+    pub created_by: String,
+    pub is_active: bool,
+}
+
+impl Branch {
+    pub fn new(
+        conversation_id: Uuid,
+        branch_name: String,
+        leaf_message_id: Uuid,
+        created_by: String,
+    ) -> Self {
+        let now = Utc::now();
+        Branch {
+            conversation_id,
+            branch_id: Uuid::new_v4(),
+            slug: slugify(&branch_name),
+            branch_name,
+            leaf_message_id,
+            created_at: now,
+            last_updated: now,
+            created_by,
+            is_active: true,
+        }
+    }
+
+    pub fn update_leaf(&mut self, new_leaf_id: Uuid) {
+        self.leaf_message_id = new_leaf_id;
+        self.last_updated = Utc::now();
+    }
+}
+
+/// A slug a branch has had, and the name it was derived from. A branch keeps
+/// every slug it was given, so the list doubles as its rename history.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchSlug {
+    pub conversation_id: Uuid,
+    pub slug: String,
+    pub branch_id: Uuid,
+    pub branch_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Lowercase ASCII letters and digits of `name`, with every other run of
+/// characters turned into a single `-`; `branch` when nothing is left
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "branch".to_string()
+    } else {
+        slug.to_string()
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    ConversationUpdated,
+    MessageCreated,
+    MessageUpdated,
+    CheckpointCreated,
+    BranchUpdated,
+    BranchDeleted,
+    ShareUpdated,
+    ShareRevoked,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ChangeKind::ConversationUpdated => "conversation_updated",
+            ChangeKind::MessageCreated => "message_created",
+            ChangeKind::MessageUpdated => "message_updated",
+            ChangeKind::CheckpointCreated => "checkpoint_created",
+            ChangeKind::BranchUpdated => "branch_updated",
+            ChangeKind::BranchDeleted => "branch_deleted",
+            ChangeKind::ShareUpdated => "share_updated",
+            ChangeKind::ShareRevoked => "share_revoked",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "conversation_updated" => Some(ChangeKind::ConversationUpdated),
+            "message_created" => Some(ChangeKind::MessageCreated),
+            "message_updated" => Some(ChangeKind::MessageUpdated),
+            "checkpoint_created" => Some(ChangeKind::CheckpointCreated),
+            "branch_updated" => Some(ChangeKind::BranchUpdated),
+            "branch_deleted" => Some(ChangeKind::BranchDeleted),
+            "share_updated" => Some(ChangeKind::ShareUpdated),
+            "share_revoked" => Some(ChangeKind::ShareRevoked),
+            _ => None,
+        }
+    }
+}
+
+/// A single entry of the per-conversation change feed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Change {
+    pub conversation_id: Uuid,
+    pub changed_at: DateTime<Utc>,
+    pub change_id: Uuid,
+    pub kind: ChangeKind,
+    pub entity_id: String,
+    pub payload: Option<serde_json::Value>,
+}
+
+impl Change {
+    pub fn new(
+        conversation_id: Uuid,
+        kind: ChangeKind,
+        entity_id: String,
+        payload: Option<serde_json::Value>,
+    ) -> Self {
+        Change {
+            conversation_id,
+            changed_at: Utc::now(),
+            change_id: Uuid::new_v4(),
+            kind,
+            entity_id,
+            payload,
+        }
+    }
+
+    /// Opaque cursor pointing right after this change
+    pub fn cursor(&self) -> String {
+        format!("{}:{}", self.changed_at.timestamp_millis(), self.change_id)
+    }
+}
+
+/// Parse a change feed position, either a cursor returned by the feed or an RFC 3339 timestamp
+pub fn parse_change_cursor(value: &str) -> Option<(DateTime<Utc>, Uuid)> {
+    if let Some((millis, change_id)) = value.split_once(':')
+        && let (Ok(millis), Ok(change_id)) = (millis.parse::<i64>(), Uuid::parse_str(change_id))
+    {
+        return Utc
+            .timestamp_millis_opt(millis)
+            .single()
+            .map(|at| (at, change_id));
+    }
+
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|at| (at.with_timezone(&Utc), Uuid::nil()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let change = Change::new(
+            Uuid::new_v4(),
+            ChangeKind::MessageCreated,
+            Uuid::new_v4().to_string(),
+            None,
+        );
+
+        let (at, change_id) = parse_change_cursor(&change.cursor()).unwrap();
+
+        assert_eq!(at.timestamp_millis(), change.changed_at.timestamp_millis());
+        assert_eq!(change_id, change.change_id);
+    }
+
+    #[test]
+    fn test_parse_timestamp_cursor() {
+        let (at, change_id) = parse_change_cursor("2025-01-02T03:04:05Z").unwrap();
+
+        assert_eq!(at.timestamp(), 1735787045);
+        assert!(change_id.is_nil());
+        assert!(parse_change_cursor("yesterday").is_none());
+    }
+}
@@ -0,0 +1,215 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Characters of an unknown content type repeated in its error; the type
+/// comes from stored rows and may be anything
+const MAX_ECHOED_TYPE_CHARS: usize = 64;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentType {
+    Text(TextContent),
+    Image(ImageContent),
+    ToolCall(ToolCallContent),
+    ToolResult(ToolResultContent),
+    ImageBatch(ImageBatchContent),
+    Metadata(MetadataContent),
+    Summary(SummaryContent),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TextContent {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageContent {
+    pub image_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolCallContent {
+    pub tool_name: String,
+    pub arguments: serde_json::Value,
+    pub tool_call_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolResultContent {
+    pub tool_call_id: String,
+    pub result: serde_json::Value,
+    pub success: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageBatchContent {
+    pub images: Vec<ImageBatchItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImageBatchItem {
+    pub image_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MetadataContent {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_public: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fork_from_conversation_id: Option<uuid::Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fork_from_message_id: Option<uuid::Uuid>,
+    /// Main language of the conversation, a tag such as `en` or `pt-BR`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Branch the owner curated, shown by default to viewers who can't write
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published_branch_id: Option<uuid::Uuid>,
+    /// Messages only the owner may read; their replies are hidden with them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub private_message_ids: Vec<uuid::Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SummaryContent {
+    pub text: String,
+    pub from_message_id: uuid::Uuid,
+    pub to_message_id: uuid::Uuid,
+    #[serde(default)]
+    pub message_count: usize,
+}
+
+pub type ContentMetadata = HashMap<String, String>;
+
+/// Every `type` a message's content can have
+pub const CONTENT_TYPES: &[&str] = &[
+    "text",
+    "image",
+    "tool_call",
+    "tool_result",
+    "image_batch",
+    "metadata",
+    "summary",
+];
+
+impl ContentType {
+    pub fn to_type_string(&self) -> &str {
+        match self {
+            ContentType::Text(_) => "text",
+            ContentType::Image(_) => "image",
+            ContentType::ToolCall(_) => "tool_call",
+            ContentType::ToolResult(_) => "tool_result",
+            ContentType::ImageBatch(_) => "image_batch",
+            ContentType::Metadata(_) => "metadata",
+            ContentType::Summary(_) => "summary",
+        }
+    }
+
+    /// Content from its stored parts, the type string and the JSON of the
+    /// variant
+    pub fn from_parts(content_type: &str, content_data: &str) -> Result<Self, ContentError> {
+        Ok(match content_type {
+            "text" => ContentType::Text(parse_part("text", content_data)?),
+            "image" => ContentType::Image(parse_part("image", content_data)?),
+            "tool_call" => ContentType::ToolCall(parse_part("tool_call", content_data)?),
+            "tool_result" => ContentType::ToolResult(parse_part("tool_result", content_data)?),
+            "image_batch" => ContentType::ImageBatch(parse_part("image_batch", content_data)?),
+            "metadata" => ContentType::Metadata(parse_part("metadata", content_data)?),
+            "summary" => ContentType::Summary(parse_part("summary", content_data)?),
+            _ => {
+                return Err(ContentError::UnknownType(
+                    content_type.chars().take(MAX_ECHOED_TYPE_CHARS).collect(),
+                ));
+            }
+        })
+    }
+
+    pub fn to_json_string(&self) -> Result<String, serde_json::Error> {
+        match self {
+            ContentType::Text(c) => serde_json::to_string(c),
+            ContentType::Image(c) => serde_json::to_string(c),
+            ContentType::ToolCall(c) => serde_json::to_string(c),
+            ContentType::ToolResult(c) => serde_json::to_string(c),
+            ContentType::ImageBatch(c) => serde_json::to_string(c),
+            ContentType::Metadata(c) => serde_json::to_string(c),
+            ContentType::Summary(c) => serde_json::to_string(c),
+        }
+    }
+}
+
+/// Why stored parts don't make a [`ContentType`]
+#[derive(Debug, thiserror::Error)]
+pub enum ContentError {
+    #[error("Unknown content type: {0}")]
+    UnknownType(String),
+    #[error("Failed to parse {content_type} content: {source}")]
+    Invalid {
+        content_type: &'static str,
+        #[source]
+        source: serde_json::Error,
+    },
+}
+
+fn parse_part<T: DeserializeOwned>(
+    content_type: &'static str,
+    content_data: &str,
+) -> Result<T, ContentError> {
+    serde_json::from_str(content_data).map_err(|source| ContentError::Invalid {
+        content_type,
+        source,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parts_round_trip_and_errors_are_typed() {
+        let content = ContentType::Text(TextContent {
+            text: "hi".to_string(),
+        });
+        let data = content.to_json_string().unwrap();
+        assert_eq!(
+            ContentType::from_parts(content.to_type_string(), &data).unwrap(),
+            content
+        );
+
+        let err = ContentType::from_parts("image", "{\"image_url\": 3}").unwrap_err();
+        assert!(matches!(
+            err,
+            ContentError::Invalid {
+                content_type: "image",
+                ..
+            }
+        ));
+        assert!(
+            err.to_string()
+                .starts_with("Failed to parse image content: ")
+        );
+
+        let long = "x".repeat(10_000);
+        match ContentType::from_parts(&long, "{}").unwrap_err() {
+            ContentError::UnknownType(echoed) => assert_eq!(echoed.len(), MAX_ECHOED_TYPE_CHARS),
+            err => panic!("unexpected error {:?}", err),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::message::Message;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub conversation_id: Uuid,
+    pub root_message: Message,
+}
+
+/// Marks a conversation read-only: its messages, metadata and branches can't
+/// change, and it can't be deleted, until it is unlocked
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationLock {
+    pub conversation_id: Uuid,
+    pub locked_by: String,
+    pub locked_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+/// Keeps a conversation from being deleted, by users or in bulk, and its
+/// change feed from expiring, until the hold is released
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalHold {
+    pub conversation_id: Uuid,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+/// A fork or duplicate being copied. Kept while its messages are inserted
+/// batch by batch and deleted once the copy is complete, so a record left
+/// behind names a partial copy to clean up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkProgress {
+    pub conversation_id: Uuid,
+    pub source_conversation_id: Uuid,
+    pub created_by: String,
+    pub total_chunks: i32,
+    pub completed_chunks: i32,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ForkProgress {
+    pub fn new(
+        conversation_id: Uuid,
+        source_conversation_id: Uuid,
+        created_by: String,
+        total_chunks: usize,
+    ) -> Self {
+        let now = Utc::now();
+        Self {
+            conversation_id,
+            source_conversation_id,
+            created_by,
+            total_chunks: total_chunks as i32,
+            completed_chunks: 0,
+            started_at: now,
+            updated_at: now,
+        }
+    }
+}
+
+impl Conversation {
+    pub fn new(title: String, created_by: String) -> Self {
+        let conversation_id = Uuid::new_v4();
+        // Time-ordered like every message ID
+        let root_message_id = Uuid::now_v7();
+
+        Conversation {
+            conversation_id,
+            root_message: Message::new_root(conversation_id, root_message_id, title, created_by),
+        }
+    }
+
+    /// Title, description, visibility and fork origin, kept in the root message
+    pub fn metadata(&self) -> Option<&super::content::MetadataContent> {
+        match &self.root_message.content {
+            super::content::ContentType::Metadata(m) => Some(m),
+            _ => None,
+        }
+    }
+
+    pub fn title(&self) -> Option<String> {
+        match &self.root_message.content {
+            super::content::ContentType::Metadata(m) => Some(m.title.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.root_message.created_at
+    }
+
+    pub fn created_by(&self) -> &str {
+        &self.root_message.created_by
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// An entry of a conversation's append-only event log. Unlike the change
+/// feed, events never expire and are written in the same batch as the state
+/// they describe, so replaying them rebuilds the conversation's messages and
+/// metadata.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationEvent {
+    pub conversation_id: Uuid,
+    /// Increases with every event of the conversation. Handed out by the
+    /// store as the event is written; 0 until then.
+    pub seq: i64,
+    pub event_id: Uuid,
+    pub kind: EventKind,
+    pub occurred_at: DateTime<Utc>,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    MessageCreated,
+    /// A stored message rewritten in place, e.g. scrubbed
+    MessageUpdated,
+    MessageMoved,
+    BranchMoved,
+    MetadataUpdated,
+    Forked,
+}
+
+/// Payload of `MessageMoved`. The lineages of the message and its replies
+/// follow from the new parent's.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageMoved {
+    pub message_id: Uuid,
+    pub from_parent_message_id: Option<Uuid>,
+    pub to_parent_message_id: Uuid,
+}
+
+/// Payload of `BranchMoved`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchMoved {
+    pub branch_id: Uuid,
+    pub from_leaf_message_id: Uuid,
+    pub to_leaf_message_id: Uuid,
+}
+
+/// Payload of `Forked`, recorded in the fork's log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Forked {
+    pub source_conversation_id: Uuid,
+    /// `None` when the whole conversation was forked
+    pub source_message_id: Option<Uuid>,
+    pub created_by: String,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            EventKind::MessageCreated => "message_created",
+            EventKind::MessageUpdated => "message_updated",
+            EventKind::MessageMoved => "message_moved",
+            EventKind::BranchMoved => "branch_moved",
+            EventKind::MetadataUpdated => "metadata_updated",
+            EventKind::Forked => "forked",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "message_created" => Some(EventKind::MessageCreated),
+            "message_updated" => Some(EventKind::MessageUpdated),
+            "message_moved" => Some(EventKind::MessageMoved),
+            "branch_moved" => Some(EventKind::BranchMoved),
+            "metadata_updated" => Some(EventKind::MetadataUpdated),
+            "forked" => Some(EventKind::Forked),
+            _ => None,
+        }
+    }
+}
+
+impl ConversationEvent {
+    /// Build an event, serializing `payload`. The store numbers it.
+    pub fn new<T: Serialize>(
+        conversation_id: Uuid,
+        kind: EventKind,
+        payload: &T,
+    ) -> Result<Self, serde_json::Error> {
+        Ok(ConversationEvent {
+            conversation_id,
+            seq: 0,
+            event_id: Uuid::now_v7(),
+            kind,
+            occurred_at: Utc::now(),
+            payload: serde_json::to_value(payload)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kinds_round_trip() {
+        for kind in [
+            EventKind::MessageCreated,
+            EventKind::MessageUpdated,
+            EventKind::MessageMoved,
+            EventKind::BranchMoved,
+            EventKind::MetadataUpdated,
+            EventKind::Forked,
+        ] {
+            assert_eq!(EventKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(EventKind::parse("unknown"), None);
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::branch::Branch;
+use super::message::Message;
+use super::permissions::Share;
+
+/// Version of the native export format this build writes; imports accept
+/// this version only
+pub const NATIVE_EXPORT_VERSION: u32 = 1;
+
+/// An export of a conversation written to the object store by a
+/// `conversation_export` job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationExport {
+    /// ID of the job writing the export
+    pub export_id: Uuid,
+    pub conversation_id: Uuid,
+    /// Requested format, e.g. `markdown`
+    pub format: String,
+    pub requested_by: String,
+    pub created_at: DateTime<Utc>,
+    /// Where the export was written; `None` until it is complete
+    pub object_key: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+/// A conversation in the service's own format: its whole tree, branches and,
+/// if asked for, shares. Importing it gives an equivalent conversation under
+/// fresh IDs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeExport {
+    /// [`NATIVE_EXPORT_VERSION`] at the time of export
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub conversation_id: Uuid,
+    /// Every message, the root included, in the order they were written.
+    /// Each names its parent, so the order doesn't matter on import.
+    pub messages: Vec<Message>,
+    pub branches: Vec<Branch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shares: Option<Vec<Share>>,
+}
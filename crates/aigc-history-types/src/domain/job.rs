@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// A unit of background work started by a user. Jobs are persisted, so their
+/// status survives restarts and can be read from any instance.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub job_id: Uuid,
+    pub kind: JobKind,
+    /// Who started the job; only they may read or cancel it
+    pub user_id: String,
+    pub status: JobStatus,
+    /// What the job was asked to do, as given to its runner
+    pub params: serde_json::Value,
+    /// Counters the runner reports, such as items done so far
+    pub progress: BTreeMap<String, i64>,
+    /// Set by a cancel request; the runner stops at its next check
+    pub cancel_requested: bool,
+    pub started_at: DateTime<Utc>,
+    /// Refreshed while the job runs; a running job that stops being updated
+    /// was interrupted and is resumed
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Why the job failed, if it did
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    /// Deletion of the conversations a user created
+    UserCleanup,
+    /// A conversation export written to the object store
+    ConversationExport,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl Job {
+    pub fn new(kind: JobKind, user_id: String, params: serde_json::Value) -> Self {
+        let now = Utc::now();
+        Job {
+            job_id: Uuid::new_v4(),
+            kind,
+            user_id,
+            status: JobStatus::Running,
+            params,
+            progress: BTreeMap::new(),
+            cancel_requested: false,
+            started_at: now,
+            updated_at: now,
+            finished_at: None,
+            error: None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.status != JobStatus::Running
+    }
+}
+
+impl JobKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            JobKind::UserCleanup => "user_cleanup",
+            JobKind::ConversationExport => "conversation_export",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "user_cleanup" => Some(JobKind::UserCleanup),
+            "conversation_export" => Some(JobKind::ConversationExport),
+            _ => None,
+        }
+    }
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            JobStatus::Running => "running",
+            JobStatus::Completed => "completed",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "running" => Some(JobStatus::Running),
+            "completed" => Some(JobStatus::Completed),
+            "failed" => Some(JobStatus::Failed),
+            "cancelled" => Some(JobStatus::Cancelled),
+            _ => None,
+        }
+    }
+}
@@ -4,19 +4,6 @@ use uuid::Uuid;
 
 use super::content::{ContentMetadata, ContentType};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Message {
-    pub conversation_id: Uuid,
-    pub message_id: Uuid,
-    pub parent_message_id: Option<Uuid>,
-    pub role: MessageRole,
-    pub content: ContentType,
-    pub content_metadata: ContentMetadata,
-    pub lineage: Vec<Uuid>,
-    pub created_at: DateTime<Utc>,
-    pub created_by: String,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum MessageRole {
@@ -50,6 +37,45 @@ impl MessageRole {
     }
 }
 
+/// `content_metadata` key holding the language of a message, a tag such as
+/// `en` or `pt-BR`
+pub const LANGUAGE_KEY: &str = "language";
+
+/// Prefix of the identities of service accounts, e.g. `service:generation-worker`
+pub const SERVICE_IDENTITY_PREFIX: &str = "service:";
+
+/// Whether a message was written by a person or by a pipeline acting as a
+/// service account
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthorKind {
+    Human,
+    Service,
+}
+
+impl AuthorKind {
+    pub fn of(user_id: &str) -> Self {
+        if user_id.starts_with(SERVICE_IDENTITY_PREFIX) {
+            AuthorKind::Service
+        } else {
+            AuthorKind::Human
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub conversation_id: Uuid,
+    pub message_id: Uuid,
+    pub parent_message_id: Option<Uuid>,
+    pub role: MessageRole,
+    pub content: ContentType,
+    pub content_metadata: ContentMetadata,
+    pub lineage: Vec<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub created_by: String,
+}
+
 impl Message {
     pub fn new_root(
         conversation_id: Uuid,
@@ -68,6 +94,9 @@ impl Message {
                 is_public: false,
                 fork_from_conversation_id: None,
                 fork_from_message_id: None,
+                language: None,
+                published_branch_id: None,
+                private_message_ids: Vec::new(),
             }),
             content_metadata: ContentMetadata::new(),
             lineage: vec![message_id],
@@ -76,6 +105,10 @@ impl Message {
         }
     }
 
+    pub fn author_kind(&self) -> AuthorKind {
+        AuthorKind::of(&self.created_by)
+    }
+
     pub fn is_root(&self) -> bool {
         self.role == MessageRole::Root
     }
@@ -83,4 +116,18 @@ impl Message {
     pub fn depth(&self) -> usize {
         self.lineage.len()
     }
+
+    /// Language tag of the message, if one was given or detected
+    pub fn language(&self) -> Option<&str> {
+        self.content_metadata.get(LANGUAGE_KEY).map(String::as_str)
+    }
+
+    /// Text the language of the message can be told from
+    pub fn language_sample(&self) -> Option<&str> {
+        match &self.content {
+            ContentType::Text(content) => Some(&content.text),
+            ContentType::Summary(content) => Some(&content.text),
+            _ => None,
+        }
+    }
 }
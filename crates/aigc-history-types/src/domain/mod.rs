@@ -0,0 +1,35 @@
+//! The domain model: conversations, their message trees and branches, and
+//! the records kept around them
+
+pub mod access_log;
+pub mod branch;
+pub mod change;
+pub mod content;
+pub mod conversation;
+pub mod event;
+pub mod export;
+pub mod job;
+pub mod message;
+pub mod notification;
+pub mod outbox;
+pub mod permissions;
+pub mod preferences;
+pub mod webhook;
+
+pub use access_log::AccessLogEntry;
+pub use branch::{Branch, BranchSlug, slugify};
+pub use change::{Change, ChangeKind};
+pub use content::{
+    CONTENT_TYPES, ContentError, ContentMetadata, ContentType, ImageBatchContent, ImageBatchItem,
+    ImageContent, MetadataContent, SummaryContent, TextContent, ToolCallContent, ToolResultContent,
+};
+pub use conversation::{Conversation, ConversationLock, ForkProgress, LegalHold};
+pub use event::{BranchMoved, ConversationEvent, EventKind, Forked, MessageMoved};
+pub use export::{ConversationExport, NATIVE_EXPORT_VERSION, NativeExport};
+pub use job::{Job, JobKind, JobStatus};
+pub use message::{AuthorKind, LANGUAGE_KEY, Message, MessageRole, SERVICE_IDENTITY_PREFIX};
+pub use notification::{Notification, NotificationKind};
+pub use outbox::{OutboxEntry, OutboxKind};
+pub use permissions::{EVERYONE, Invite, Permission, Share};
+pub use preferences::{BranchNaming, UserPreferences};
+pub use webhook::{WebhookFilter, WebhookSubscription};
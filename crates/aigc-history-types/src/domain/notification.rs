@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    ConversationShared,
+    ConversationForked,
+    MessageReplied,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 3] = [
+        NotificationKind::ConversationShared,
+        NotificationKind::ConversationForked,
+        NotificationKind::MessageReplied,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            NotificationKind::ConversationShared => "conversation_shared",
+            NotificationKind::ConversationForked => "conversation_forked",
+            NotificationKind::MessageReplied => "message_replied",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "conversation_shared" => Some(NotificationKind::ConversationShared),
+            "conversation_forked" => Some(NotificationKind::ConversationForked),
+            "message_replied" => Some(NotificationKind::MessageReplied),
+            _ => None,
+        }
+    }
+}
+
+/// Something that happened to a user's conversations or messages
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub notification_id: Uuid,
+    pub kind: NotificationKind,
+    pub conversation_id: Uuid,
+    /// User whose action caused the notification
+    pub actor: String,
+    /// The new fork for `ConversationForked`, the reply for `MessageReplied`
+    pub entity_id: Option<Uuid>,
+}
+
+impl Notification {
+    pub fn new(
+        user_id: String,
+        kind: NotificationKind,
+        conversation_id: Uuid,
+        actor: String,
+        entity_id: Option<Uuid>,
+    ) -> Self {
+        Notification {
+            user_id,
+            created_at: Utc::now(),
+            notification_id: Uuid::new_v4(),
+            kind,
+            conversation_id,
+            actor,
+            entity_id,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// A change to the lineage table, picked up from Scylla CDC and copied into
+/// the outbox for external consumers such as search indexers
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OutboxEntry {
+    pub changed_at: DateTime<Utc>,
+    /// `cdc$time` of the change; shared by the writes of one batch
+    pub change_id: Uuid,
+    /// Position of the write within its batch
+    pub batch_seq_no: i32,
+    pub conversation_id: Uuid,
+    /// `None` when the whole conversation was deleted
+    pub message_id: Option<Uuid>,
+    pub kind: OutboxKind,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxKind {
+    MessageUpserted,
+    MessageDeleted,
+    ConversationDeleted,
+}
+
+impl OutboxKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            OutboxKind::MessageUpserted => "message_upserted",
+            OutboxKind::MessageDeleted => "message_deleted",
+            OutboxKind::ConversationDeleted => "conversation_deleted",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "message_upserted" => Some(OutboxKind::MessageUpserted),
+            "message_deleted" => Some(OutboxKind::MessageDeleted),
+            "conversation_deleted" => Some(OutboxKind::ConversationDeleted),
+            _ => None,
+        }
+    }
+}
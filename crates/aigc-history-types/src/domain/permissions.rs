@@ -2,14 +2,8 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Share {
-    pub conversation_id: Uuid,
-    pub shared_with: String,
-    pub permission: Permission,
-    pub shared_at: DateTime<Utc>,
-    pub shared_by: String,
-}
+/// `shared_with` of the share publishing a conversation grants everyone
+pub const EVERYONE: &str = "*";
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -17,6 +11,8 @@ pub enum Permission {
     Read,
     Branch,
     Fork,
+    /// Everything `Fork` allows plus managing the conversation (update, delete, share)
+    Admin,
 }
 
 impl Permission {
@@ -25,6 +21,7 @@ impl Permission {
             Permission::Read => "read",
             Permission::Branch => "branch",
             Permission::Fork => "fork",
+            Permission::Admin => "admin",
         }
     }
 
@@ -33,6 +30,7 @@ impl Permission {
             "read" => Some(Permission::Read),
             "branch" => Some(Permission::Branch),
             "fork" => Some(Permission::Fork),
+            "admin" => Some(Permission::Admin),
             _ => None,
         }
     }
@@ -40,15 +38,43 @@ impl Permission {
     pub fn can_read(&self) -> bool {
         matches!(
             self,
-            Permission::Read | Permission::Branch | Permission::Fork
+            Permission::Read | Permission::Branch | Permission::Fork | Permission::Admin
         )
     }
 
     pub fn can_branch(&self) -> bool {
-        matches!(self, Permission::Branch | Permission::Fork)
+        matches!(
+            self,
+            Permission::Branch | Permission::Fork | Permission::Admin
+        )
     }
 
     pub fn can_fork(&self) -> bool {
-        matches!(self, Permission::Fork)
+        matches!(self, Permission::Fork | Permission::Admin)
     }
+
+    pub fn can_manage(&self) -> bool {
+        matches!(self, Permission::Admin)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Share {
+    pub conversation_id: Uuid,
+    pub shared_with: String,
+    pub permission: Permission,
+    pub shared_at: DateTime<Utc>,
+    pub shared_by: String,
+}
+
+/// A share offered to someone by email, turned into a `Share` once they accept it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    pub email: String,
+    pub token: String,
+    pub conversation_id: Uuid,
+    pub permission: Permission,
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
 }
@@ -0,0 +1,89 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use super::notification::NotificationKind;
+use super::permissions::Permission;
+
+/// Name given to branches created without one
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BranchNaming {
+    /// `Branch 3` for the conversation's third branch
+    #[default]
+    Numbered,
+    /// Creation time, `2025-01-31 14:05 UTC`
+    Timestamp,
+    /// The start of the leaf message's text
+    LeafExcerpt,
+}
+
+impl BranchNaming {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BranchNaming::Numbered => "numbered",
+            BranchNaming::Timestamp => "timestamp",
+            BranchNaming::LeafExcerpt => "leaf_excerpt",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "numbered" => Some(BranchNaming::Numbered),
+            "timestamp" => Some(BranchNaming::Timestamp),
+            "leaf_excerpt" => Some(BranchNaming::LeafExcerpt),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for BranchNaming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BranchNaming::parse(&s.to_ascii_lowercase()).ok_or_else(|| {
+            format!(
+                "unknown branch naming `{}` (expected numbered, timestamp or leaf_excerpt)",
+                s
+            )
+        })
+    }
+}
+
+/// Defaults a user applies to their own conversations, branches and requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPreferences {
+    pub user_id: String,
+    /// Shares granted on every conversation the user creates, by recipient
+    pub default_shares: HashMap<String, Permission>,
+    /// Whether others may fork the user's conversations once they are public
+    pub allow_public_forks: bool,
+    /// `limit` of the user's list requests that don't send one; the server
+    /// default when `None`
+    pub default_page_size: Option<usize>,
+    /// How branches created without a name are named; the deployment's
+    /// default when `None`
+    pub branch_naming: Option<BranchNaming>,
+    /// Notification kinds the user opted out of
+    pub muted_notifications: HashSet<NotificationKind>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UserPreferences {
+    /// What applies to users who never saved preferences
+    pub fn defaults(user_id: &str) -> Self {
+        UserPreferences {
+            user_id: user_id.to_string(),
+            default_shares: HashMap::new(),
+            allow_public_forks: true,
+            default_page_size: None,
+            branch_naming: None,
+            muted_notifications: HashSet::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    pub fn wants_notification(&self, kind: NotificationKind) -> bool {
+        !self.muted_notifications.contains(&kind)
+    }
+}
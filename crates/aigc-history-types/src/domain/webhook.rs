@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::change::{Change, ChangeKind};
+use super::message::{Message, MessageRole};
+
+/// An endpoint receiving a conversation's changes as they happen
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookSubscription {
+    pub conversation_id: Uuid,
+    pub subscription_id: Uuid,
+    pub url: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub filter: WebhookFilter,
+    /// Key signing deliveries; `None` for subscriptions created before
+    /// deliveries were signed
+    pub secret: Option<String>,
+    /// Change feed position delivered up to; starts at the subscription's creation
+    pub delivered_up_to: (DateTime<Utc>, Uuid),
+}
+
+/// Which changes a subscription receives. Empty lists don't filter.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct WebhookFilter {
+    pub event_types: Vec<ChangeKind>,
+    /// Only message changes whose message has one of these roles
+    pub roles: Vec<MessageRole>,
+    /// Only message changes whose content is one of these types, e.g. `text`
+    pub content_types: Vec<String>,
+}
+
+impl WebhookFilter {
+    pub fn matches(&self, change: &Change) -> bool {
+        if !self.event_types.is_empty() && !self.event_types.contains(&change.kind) {
+            return false;
+        }
+        if self.roles.is_empty() && self.content_types.is_empty() {
+            return true;
+        }
+
+        // Role and content filters only let messages through
+        let Some(message) = change
+            .payload
+            .clone()
+            .and_then(|payload| serde_json::from_value::<Message>(payload).ok())
+        else {
+            return false;
+        };
+        (self.roles.is_empty() || self.roles.contains(&message.role))
+            && (self.content_types.is_empty()
+                || self
+                    .content_types
+                    .iter()
+                    .any(|content_type| content_type == message.content.to_type_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::content::{ContentType, TextContent};
+
+    #[test]
+    fn test_filters_narrow_down_to_message_roles_and_types() {
+        let conversation_id = Uuid::new_v4();
+        let mut message = Message::new_root(
+            conversation_id,
+            Uuid::new_v4(),
+            "Test".to_string(),
+            "user_a".to_string(),
+        );
+        message.role = MessageRole::Assistant;
+        message.content = ContentType::Text(TextContent {
+            text: "Done".to_string(),
+        });
+        let change = |kind, message: &Message| {
+            Change::new(
+                conversation_id,
+                kind,
+                message.message_id.to_string(),
+                Some(serde_json::to_value(message).unwrap()),
+            )
+        };
+        let reply = change(ChangeKind::MessageCreated, &message);
+        let mut question = message.clone();
+        question.role = MessageRole::Human;
+        let question = change(ChangeKind::MessageCreated, &question);
+        let branch = Change::new(
+            conversation_id,
+            ChangeKind::BranchUpdated,
+            Uuid::new_v4().to_string(),
+            None,
+        );
+
+        assert!(WebhookFilter::default().matches(&branch));
+        let assistant_outputs = WebhookFilter {
+            event_types: vec![ChangeKind::MessageCreated],
+            roles: vec![MessageRole::Assistant],
+            content_types: Vec::new(),
+        };
+        assert!(assistant_outputs.matches(&reply));
+        assert!(!assistant_outputs.matches(&question));
+        assert!(!assistant_outputs.matches(&branch));
+
+        let images = WebhookFilter {
+            content_types: vec!["image".to_string()],
+            ..Default::default()
+        };
+        assert!(!images.matches(&reply));
+        assert!(
+            !WebhookFilter {
+                event_types: vec![ChangeKind::MessageUpdated],
+                ..Default::default()
+            }
+            .matches(&reply)
+        );
+    }
+}
@@ -0,0 +1,1058 @@
+//! Requests and responses of `/api/v1`
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use uuid::Uuid;
+
+use crate::{
+    AccessLogEntry, AuthorKind, Branch, BranchNaming, Change, ChangeKind, ContentType,
+    ContextStrategy, ConversationEvent, ConversationLock, ExportFormat, Job, LegalHold, Message,
+    MessageRole, Notification, NotificationKind, Permission, Share, UserPreferences,
+    WebhookSubscription,
+};
+
+// Request DTOs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateConversationRequest {
+    pub title: String,
+    /// Defaults to the caller; rejected unless it is the caller
+    pub created_by: Option<String>,
+    /// Language tag such as `en` or `pt-BR`; otherwise taken from the first
+    /// reply that has one
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateConversationRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Public conversations can appear in explore listings
+    pub is_public: Option<bool>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMessageRequest {
+    pub parent_message_id: Uuid,
+    pub role: MessageRole,
+    pub content: ContentType,
+    #[serde(default)]
+    pub content_metadata: HashMap<String, String>,
+    /// Defaults to the caller; rejected unless it is the caller
+    pub created_by: Option<String>,
+    pub branch_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveMessageRequest {
+    pub new_parent_message_id: Uuid,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateBranchRequest {
+    /// Named as the creator's preferences say when omitted
+    pub branch_name: Option<String>,
+    pub leaf_message_id: Uuid,
+    /// Defaults to the caller; rejected unless it is the caller
+    pub created_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateBranchRequest {
+    pub branch_name: Option<String>,
+    pub leaf_message_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateCheckpointRequest {
+    pub from_message_id: Option<Uuid>,
+    pub to_message_id: Uuid,
+    pub summary: String,
+    /// Defaults to the caller; rejected unless it is the caller
+    pub created_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhookRequest {
+    /// Receives a POST per matching change
+    pub url: String,
+    /// Only these kinds of changes, e.g. `message_created`; all when empty
+    #[serde(default)]
+    pub event_types: Vec<ChangeKind>,
+    /// Only messages with these roles
+    #[serde(default)]
+    pub roles: Vec<MessageRole>,
+    /// Only messages with these content types, e.g. `text`
+    #[serde(default)]
+    pub content_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedeliverWebhookRequest {
+    /// Change feed cursor or RFC 3339 timestamp to deliver again from
+    pub since: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LockConversationRequest {
+    /// Why the conversation must not change, e.g. a review ticket
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublishBranchRequest {
+    /// `null` stops publishing a branch
+    pub branch_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlaceLegalHoldRequest {
+    /// E.g. the matter or case the conversation is kept for
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreeQuery {
+    /// Only messages at least this deep; the root is at depth 1
+    pub min_depth: Option<usize>,
+    /// Only messages at most this deep
+    pub max_depth: Option<usize>,
+    /// Only messages written by people (`human`) or by service accounts (`service`)
+    pub author_kind: Option<AuthorKind>,
+    /// The whole tree even where viewers get the published branch by default
+    #[serde(default)]
+    pub full: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BranchMessagesQuery {
+    #[serde(default)]
+    pub compact: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AncestorsQuery {
+    /// How many of the nearest ancestors to return
+    pub last: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextQuery {
+    pub leaf: Uuid,
+    /// Token budget; unlimited when omitted
+    pub max_tokens: Option<usize>,
+    #[serde(default)]
+    pub strategy: ContextStrategy,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChangesQuery {
+    pub since: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AccessLogQuery {
+    /// Only reads before this time; `next_before` of the previous page
+    pub before: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncRequest {
+    /// Leaves the client already has, each with its whole lineage
+    #[serde(default)]
+    pub known_leaves: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventsQuery {
+    pub from_seq: Option<i64>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSearchQuery {
+    pub q: String,
+    /// Only messages in this language or a variant of it
+    pub lang: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffQuery {
+    pub from: DateTime<Utc>,
+    /// Defaults to now
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+    pub branch_id: Option<Uuid>,
+    pub leaf_message_id: Option<Uuid>,
+    /// Native exports only: include whom the conversation is shared with.
+    /// Only the owner may ask for them.
+    #[serde(default)]
+    pub include_shares: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateExportRequest {
+    pub format: ExportFormat,
+    pub branch_id: Option<Uuid>,
+    pub leaf_message_id: Option<Uuid>,
+    /// As in [`ExportQuery`]
+    #[serde(default)]
+    pub include_shares: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExportListQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ImportQuery {
+    /// Defaults to the caller; rejected unless it is the caller
+    pub created_by: Option<String>,
+    /// Store identical sibling messages (e.g. repeated generations) once
+    #[serde(default)]
+    pub dedup: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserConversationsQuery {
+    /// Title search; matches anywhere in the title, case-insensitive
+    pub q: Option<String>,
+    /// With `q`, only conversations in this language or a variant of it
+    pub lang: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DeleteUserConversationsQuery {
+    /// Only conversations created before this time
+    pub older_than: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserJobsQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrendingQuery {
+    /// Only conversations in this language or a variant of it
+    pub lang: Option<String>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateEmbedTokenRequest {
+    /// Validity in seconds; the server default when omitted
+    pub ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyticsQuery {
+    /// First UTC day, `YYYY-MM-DD`
+    pub from: Option<NaiveDate>,
+    /// Last UTC day, included
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageQuery {
+    /// Only `model` is supported
+    pub group_by: Option<String>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkConversationRequest {
+    pub title: String,
+    /// Defaults to the caller; rejected unless it is the caller
+    pub created_by: Option<String>,
+    /// Give copied messages fresh IDs (default true)
+    pub remap_ids: Option<bool>,
+    /// Recreate the copied branches in the fork (default true)
+    pub copy_branches: Option<bool>,
+    /// Give the forker their share on the source on the fork too (default false)
+    pub copy_share: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareConversationRequest {
+    pub shared_with: String,
+    pub permission: Permission,
+    /// Defaults to the caller; rejected unless it is the caller
+    pub shared_by: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareGrant {
+    pub shared_with: String,
+    pub permission: Permission,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BatchShareRequest {
+    /// New shares, or new permissions for existing ones
+    #[serde(default)]
+    pub grant: Vec<ShareGrant>,
+    #[serde(default)]
+    pub revoke: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdatePreferencesRequest {
+    /// Shares granted on every new conversation, by recipient
+    pub default_shares: Option<HashMap<String, Permission>>,
+    /// Let others fork the user's conversations once they are public
+    pub allow_public_forks: Option<bool>,
+    pub default_page_size: Option<usize>,
+    pub branch_naming: Option<BranchNaming>,
+    /// Whether to receive each kind of notification; kinds left out are unchanged
+    #[serde(default)]
+    pub notifications: HashMap<NotificationKind, bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SharedWithMeQuery {
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateInviteRequest {
+    pub email: String,
+    pub permission: Permission,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptInviteRequest {
+    pub email: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationsQuery {
+    pub limit: Option<usize>,
+    pub unread_only: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MarkNotificationsReadRequest {
+    /// Defaults to now, marking everything read
+    pub up_to: Option<DateTime<Utc>>,
+}
+
+// Response DTOs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationResponse {
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub created_by: String,
+    pub is_public: bool,
+    pub fork_from_conversation_id: Option<Uuid>,
+    pub fork_from_message_id: Option<Uuid>,
+    pub language: Option<String>,
+    pub published_branch_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageResponse {
+    pub conversation_id: Uuid,
+    pub message_id: Uuid,
+    pub parent_message_id: Option<Uuid>,
+    pub role: String,
+    pub content: ContentType,
+    pub content_metadata: HashMap<String, String>,
+    pub lineage: Vec<Uuid>,
+    pub depth: usize,
+    pub created_at: DateTime<Utc>,
+    pub created_by: String,
+    pub author_kind: AuthorKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AncestorsResponse {
+    pub conversation_id: Uuid,
+    pub message_id: Uuid,
+    /// Ancestors of the message, root included, of which the last are returned
+    pub total_ancestors: usize,
+    /// Oldest first, ending with the parent
+    pub ancestors: Vec<MessageResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchResponse {
+    pub conversation_id: Uuid,
+    pub branch_id: Uuid,
+    pub branch_name: String,
+    pub slug: String,
+    pub leaf_message_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub last_updated: DateTime<Utc>,
+    pub created_by: String,
+    pub is_active: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareResponse {
+    pub conversation_id: Uuid,
+    pub shared_with: String,
+    pub permission: String,
+    pub shared_at: DateTime<Utc>,
+    pub shared_by: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreferencesResponse {
+    pub user_id: String,
+    pub default_shares: BTreeMap<String, String>,
+    pub allow_public_forks: bool,
+    /// `null` when the server default applies
+    pub default_page_size: Option<usize>,
+    /// `null` when the server default applies
+    pub branch_naming: Option<BranchNaming>,
+    /// Whether the user receives each kind of notification
+    pub notifications: BTreeMap<String, bool>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchShareResponse {
+    pub granted: Vec<ShareResponse>,
+    pub revoked: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteResponse {
+    pub conversation_id: Uuid,
+    pub email: String,
+    pub token: String,
+    pub permission: String,
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationResponse {
+    pub notification_id: Uuid,
+    pub kind: String,
+    pub conversation_id: Uuid,
+    pub actor: String,
+    pub entity_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub read: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkGraphNodeResponse {
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub fork_from_conversation_id: Option<Uuid>,
+    pub fork_from_message_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkGraphEdgeResponse {
+    pub source_conversation_id: Uuid,
+    pub fork_conversation_id: Uuid,
+    pub fork_from_message_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkGraphResponse {
+    pub conversation_id: Uuid,
+    pub origin_conversation_id: Uuid,
+    pub nodes: Vec<ForkGraphNodeResponse>,
+    pub edges: Vec<ForkGraphEdgeResponse>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobResponse {
+    pub job_id: Uuid,
+    pub kind: String,
+    pub user_id: String,
+    pub status: String,
+    pub params: serde_json::Value,
+    pub progress: BTreeMap<String, i64>,
+    pub cancel_requested: bool,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportResponse {
+    pub export_id: Uuid,
+    pub conversation_id: Uuid,
+    pub format: String,
+    pub requested_by: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub size_bytes: Option<i64>,
+    /// Presigned, so valid without credentials until `download_expires_at`
+    pub download_url: Option<String>,
+    pub download_expires_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicateGroupResponse {
+    pub content_hash: String,
+    pub content_type: String,
+    pub message_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DuplicatesResponse {
+    pub conversation_id: Uuid,
+    pub groups: Vec<DuplicateGroupResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrubResponse {
+    pub conversation_id: Uuid,
+    pub scrubbed_message_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LockResponse {
+    pub conversation_id: Uuid,
+    pub locked_by: String,
+    pub locked_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookResponse {
+    pub conversation_id: Uuid,
+    pub subscription_id: Uuid,
+    pub url: String,
+    pub event_types: Vec<ChangeKind>,
+    pub roles: Vec<MessageRole>,
+    pub content_types: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Only returned on creation: the secret can't be read back later
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatedWebhookResponse {
+    #[serde(flatten)]
+    pub webhook: WebhookResponse,
+    /// Key of the deliveries' `X-Webhook-Signature`
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegalHoldResponse {
+    pub conversation_id: Uuid,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeResponse {
+    pub conversation_id: Uuid,
+    pub messages: Vec<MessageResponse>,
+    pub total_messages: usize,
+    /// Set when the messages are the lineage of the published branch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub published_branch_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrivateMessagesResponse {
+    pub conversation_id: Uuid,
+    /// Hidden with their replies from everyone but the owner
+    pub private_message_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeResponse {
+    pub change_id: Uuid,
+    pub changed_at: DateTime<Utc>,
+    pub kind: String,
+    pub entity_id: String,
+    pub payload: Option<serde_json::Value>,
+    pub cursor: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangesResponse {
+    pub conversation_id: Uuid,
+    pub changes: Vec<ChangeResponse>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessLogEntryResponse {
+    pub accessed_at: DateTime<Utc>,
+    pub viewer: Option<String>,
+    pub via_embed: bool,
+    pub branch_id: Option<Uuid>,
+    pub resource: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessLogResponse {
+    pub conversation_id: Uuid,
+    pub entries: Vec<AccessLogEntryResponse>,
+    pub next_before: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResponse {
+    pub conversation_id: Uuid,
+    pub messages: Vec<MessageResponse>,
+    pub branches: Vec<BranchResponse>,
+    pub branch_ids: Vec<Uuid>,
+    pub unknown_leaves: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventResponse {
+    pub seq: i64,
+    pub event_id: Uuid,
+    pub kind: String,
+    pub occurred_at: DateTime<Utc>,
+    pub payload: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventsResponse {
+    pub conversation_id: Uuid,
+    pub events: Vec<EventResponse>,
+    /// `from_seq` for the next page
+    pub next_seq: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageSearchResponse {
+    pub conversation_id: Uuid,
+    pub query: String,
+    pub matches: Vec<MessageMatchResponse>,
+    /// Matches before `limit` was applied
+    pub total: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageMatchResponse {
+    pub message_id: Uuid,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+    pub field: String,
+    pub snippet: String,
+    /// `[start, end)` character offsets into `snippet`
+    pub highlights: Vec<[usize; 2]>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffResponse {
+    pub conversation_id: Uuid,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub messages: MessagesDiff,
+    pub branches: BranchesDiff,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagesDiff {
+    pub added: Vec<MessageResponse>,
+    pub changed: Vec<MessageResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BranchesDiff {
+    pub added: Vec<BranchResponse>,
+    pub changed: Vec<BranchResponse>,
+    pub removed: Vec<BranchResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedConversationResponse {
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub message_count: usize,
+    pub branch_ids: Vec<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResponse {
+    pub conversations: Vec<ImportedConversationResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationMatchResponse {
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub updated_at: DateTime<Utc>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendingConversationResponse {
+    pub rank: i32,
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub score: f64,
+    pub view_count: i64,
+    pub fork_count: i64,
+    pub computed_at: DateTime<Utc>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AnalyticsCounts {
+    pub message_count: i64,
+    pub fork_count: i64,
+    pub messages_by_role: BTreeMap<String, i64>,
+    pub messages_by_model: BTreeMap<String, i64>,
+}
+
+impl AnalyticsCounts {
+    /// Add the counts of `other` to these
+    pub fn add(&mut self, other: &AnalyticsCounts) {
+        self.message_count += other.message_count;
+        self.fork_count += other.fork_count;
+        for (role, count) in &other.messages_by_role {
+            *self.messages_by_role.entry(role.clone()).or_default() += count;
+        }
+        for (model, count) in &other.messages_by_model {
+            *self.messages_by_model.entry(model.clone()).or_default() += count;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyticsDayResponse {
+    pub day: String,
+    #[serde(flatten)]
+    pub counts: AnalyticsCounts,
+    pub computed_at: DateTime<Utc>,
+}
+
+/// Daily rollups over a range of days, and their totals. Days without
+/// activity are left out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyticsResponse {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub totals: AnalyticsCounts,
+    pub days: Vec<AnalyticsDayResponse>,
+}
+
+impl AnalyticsResponse {
+    /// The response for `days`, totalling their counts
+    pub fn new(from: NaiveDate, to: NaiveDate, days: Vec<AnalyticsDayResponse>) -> Self {
+        let mut totals = AnalyticsCounts::default();
+        for day in &days {
+            totals.add(&day.counts);
+        }
+
+        AnalyticsResponse {
+            from,
+            to,
+            totals,
+            days,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelUsageResponse {
+    pub model: String,
+    pub message_count: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsageResponse {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub group_by: String,
+    pub groups: Vec<ModelUsageResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmbedTokenResponse {
+    pub conversation_id: Uuid,
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextMessageResponse {
+    pub message_id: Uuid,
+    pub role: String,
+    pub content: ContentType,
+    pub estimated_tokens: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextResponse {
+    pub conversation_id: Uuid,
+    pub leaf_message_id: Uuid,
+    pub strategy: ContextStrategy,
+    pub max_tokens: Option<usize>,
+    pub estimated_tokens: usize,
+    /// Messages replaced by a checkpoint summary
+    pub summarized_messages: usize,
+    /// Messages dropped to fit `max_tokens`
+    pub truncated_messages: usize,
+    pub messages: Vec<ContextMessageResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskHealthResponse {
+    pub name: String,
+    pub interval_secs: u64,
+    pub enabled: bool,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub timestamp: DateTime<Utc>,
+    /// Background tasks scheduled in this instance
+    pub tasks: Vec<TaskHealthResponse>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeResponse {
+    pub status: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Result of one readiness check; `error` is set when it failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessCheck {
+    pub name: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadinessResponse {
+    /// `ready` or `not_ready`
+    pub status: String,
+    pub timestamp: DateTime<Utc>,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+// Conversions from the domain model
+
+impl From<Message> for MessageResponse {
+    fn from(msg: Message) -> Self {
+        let depth = msg.depth();
+        let lineage = msg.lineage.clone();
+        let author_kind = msg.author_kind();
+        MessageResponse {
+            conversation_id: msg.conversation_id,
+            message_id: msg.message_id,
+            parent_message_id: msg.parent_message_id,
+            role: msg.role.as_str().to_string(),
+            content: msg.content,
+            content_metadata: msg.content_metadata,
+            lineage,
+            depth,
+            created_at: msg.created_at,
+            created_by: msg.created_by,
+            author_kind,
+        }
+    }
+}
+
+impl From<Branch> for BranchResponse {
+    fn from(branch: Branch) -> Self {
+        BranchResponse {
+            conversation_id: branch.conversation_id,
+            branch_id: branch.branch_id,
+            branch_name: branch.branch_name,
+            slug: branch.slug,
+            leaf_message_id: branch.leaf_message_id,
+            created_at: branch.created_at,
+            last_updated: branch.last_updated,
+            created_by: branch.created_by,
+            is_active: branch.is_active,
+        }
+    }
+}
+
+impl From<Share> for ShareResponse {
+    fn from(share: Share) -> Self {
+        ShareResponse {
+            conversation_id: share.conversation_id,
+            shared_with: share.shared_with,
+            permission: share.permission.as_str().to_string(),
+            shared_at: share.shared_at,
+            shared_by: share.shared_by,
+        }
+    }
+}
+
+impl From<UserPreferences> for PreferencesResponse {
+    fn from(preferences: UserPreferences) -> Self {
+        let notifications = NotificationKind::ALL
+            .into_iter()
+            .map(|kind| {
+                (
+                    kind.as_str().to_string(),
+                    preferences.wants_notification(kind),
+                )
+            })
+            .collect();
+        PreferencesResponse {
+            user_id: preferences.user_id,
+            default_shares: preferences
+                .default_shares
+                .into_iter()
+                .map(|(user_id, permission)| (user_id, permission.as_str().to_string()))
+                .collect(),
+            allow_public_forks: preferences.allow_public_forks,
+            default_page_size: preferences.default_page_size,
+            branch_naming: preferences.branch_naming,
+            notifications,
+            updated_at: preferences.updated_at,
+        }
+    }
+}
+
+impl NotificationResponse {
+    pub fn new(notification: Notification, read: bool) -> Self {
+        NotificationResponse {
+            notification_id: notification.notification_id,
+            kind: notification.kind.as_str().to_string(),
+            conversation_id: notification.conversation_id,
+            actor: notification.actor,
+            entity_id: notification.entity_id,
+            created_at: notification.created_at,
+            read,
+        }
+    }
+}
+
+impl From<Job> for JobResponse {
+    fn from(job: Job) -> Self {
+        JobResponse {
+            job_id: job.job_id,
+            kind: job.kind.as_str().to_string(),
+            user_id: job.user_id,
+            status: job.status.as_str().to_string(),
+            params: job.params,
+            progress: job.progress,
+            cancel_requested: job.cancel_requested,
+            started_at: job.started_at,
+            updated_at: job.updated_at,
+            finished_at: job.finished_at,
+            error: job.error,
+        }
+    }
+}
+
+impl From<ConversationLock> for LockResponse {
+    fn from(lock: ConversationLock) -> Self {
+        LockResponse {
+            conversation_id: lock.conversation_id,
+            locked_by: lock.locked_by,
+            locked_at: lock.locked_at,
+            reason: lock.reason,
+        }
+    }
+}
+
+impl From<WebhookSubscription> for CreatedWebhookResponse {
+    fn from(mut subscription: WebhookSubscription) -> Self {
+        CreatedWebhookResponse {
+            secret: subscription.secret.take(),
+            webhook: subscription.into(),
+        }
+    }
+}
+
+impl From<WebhookSubscription> for WebhookResponse {
+    fn from(subscription: WebhookSubscription) -> Self {
+        WebhookResponse {
+            conversation_id: subscription.conversation_id,
+            subscription_id: subscription.subscription_id,
+            url: subscription.url,
+            event_types: subscription.filter.event_types,
+            roles: subscription.filter.roles,
+            content_types: subscription.filter.content_types,
+            created_by: subscription.created_by,
+            created_at: subscription.created_at,
+        }
+    }
+}
+
+impl From<LegalHold> for LegalHoldResponse {
+    fn from(hold: LegalHold) -> Self {
+        LegalHoldResponse {
+            conversation_id: hold.conversation_id,
+            placed_by: hold.placed_by,
+            placed_at: hold.placed_at,
+            reason: hold.reason,
+        }
+    }
+}
+
+impl From<Change> for ChangeResponse {
+    fn from(change: Change) -> Self {
+        let cursor = change.cursor();
+        ChangeResponse {
+            change_id: change.change_id,
+            changed_at: change.changed_at,
+            kind: change.kind.as_str().to_string(),
+            entity_id: change.entity_id,
+            payload: change.payload,
+            cursor,
+        }
+    }
+}
+
+impl From<AccessLogEntry> for AccessLogEntryResponse {
+    fn from(entry: AccessLogEntry) -> Self {
+        AccessLogEntryResponse {
+            accessed_at: entry.accessed_at,
+            viewer: entry.viewer,
+            via_embed: entry.via_embed,
+            branch_id: entry.branch_id,
+            resource: entry.resource,
+        }
+    }
+}
+
+impl From<ConversationEvent> for EventResponse {
+    fn from(event: ConversationEvent) -> Self {
+        EventResponse {
+            seq: event.seq,
+            event_id: event.event_id,
+            kind: event.kind.as_str().to_string(),
+            occurred_at: event.occurred_at,
+            payload: event.payload,
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Ndjson,
+    Anthropic,
+    /// The whole conversation as a [`crate::domain::NativeExport`]
+    Native,
+}
+
+impl ExportFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "markdown",
+            ExportFormat::Html => "html",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Anthropic => "anthropic",
+            ExportFormat::Native => "native",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "text/markdown; charset=utf-8",
+            ExportFormat::Html => "text/html; charset=utf-8",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Anthropic | ExportFormat::Native => "application/json",
+        }
+    }
+
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Anthropic | ExportFormat::Native => "json",
+        }
+    }
+
+    /// Whether the format covers the entire tree and is streamed row by row
+    pub fn is_streamed(&self) -> bool {
+        matches!(self, ExportFormat::Ndjson)
+    }
+}
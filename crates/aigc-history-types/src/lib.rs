@@ -0,0 +1,21 @@
+//! Domain model and request and response types of the aigc-history API,
+//! shared by the server and its clients so both sides agree on the wire
+//! format.
+//!
+//! The crate only depends on serde, serde_json, thiserror, chrono and uuid,
+//! so frontends compiled to WebAssembly and other services can use it
+//! without the server stack.
+
+pub mod context;
+pub mod domain;
+pub mod dto;
+pub mod export;
+pub mod presence;
+#[cfg(feature = "tree")]
+pub mod tree;
+pub mod v2;
+
+pub use context::ContextStrategy;
+pub use domain::*;
+pub use export::ExportFormat;
+pub use presence::{PresenceSignal, PresenceState};
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresenceSignal {
+    /// Set by the server to the caller; requests may omit it
+    #[serde(default)]
+    pub user_id: String,
+    pub state: PresenceState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch_id: Option<Uuid>,
+    #[serde(default = "Utc::now")]
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceState {
+    Online,
+    Typing,
+    Idle,
+    Offline,
+}
@@ -0,0 +1,285 @@
+//! Tree math on lineages and messages: the same functions the server uses,
+//! so clients working on a tree they fetched get identical answers.
+//!
+//! Nothing here does I/O or reads the clock, so it compiles to WebAssembly;
+//! it needs only allocation. Enabled by the `tree` feature.
+
+use std::collections::BTreeMap;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::domain::Message;
+
+/// Compute lineage for a new message given parent's lineage
+pub fn compute_lineage(parent_lineage: &[Uuid], new_message_id: Uuid) -> Vec<Uuid> {
+    let mut lineage = parent_lineage.to_vec();
+    lineage.push(new_message_id);
+    lineage
+}
+
+/// A lineage longer than the deepest allowed
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error("Lineage depth {depth} exceeds maximum allowed depth {max}")]
+pub struct LineageTooDeep {
+    pub depth: usize,
+    pub max: usize,
+}
+
+/// Validate lineage depth
+pub fn validate_lineage_depth(lineage: &[Uuid], max_depth: usize) -> Result<(), LineageTooDeep> {
+    if lineage.len() > max_depth {
+        return Err(LineageTooDeep {
+            depth: lineage.len(),
+            max: max_depth,
+        });
+    }
+    Ok(())
+}
+
+/// Check if message A is an ancestor of message B by comparing lineages
+pub fn is_ancestor(ancestor_id: Uuid, descendant_lineage: &[Uuid]) -> bool {
+    descendant_lineage.contains(&ancestor_id)
+}
+
+/// Get the common ancestor path between two lineages
+pub fn common_ancestor_path(lineage_a: &[Uuid], lineage_b: &[Uuid]) -> Vec<Uuid> {
+    lineage_a
+        .iter()
+        .zip(lineage_b.iter())
+        .take_while(|(a, b)| a == b)
+        .map(|(a, _)| *a)
+        .collect()
+}
+
+/// Rebase a lineage so the subtree rooted at `subtree_root` hangs below a new parent.
+/// Returns `None` if `subtree_root` is not part of the lineage.
+pub fn rebase_lineage(
+    lineage: &[Uuid],
+    subtree_root: Uuid,
+    new_parent_lineage: &[Uuid],
+) -> Option<Vec<Uuid>> {
+    let position = lineage.iter().position(|id| *id == subtree_root)?;
+    let mut rebased = new_parent_lineage.to_vec();
+    rebased.extend_from_slice(&lineage[position..]);
+    Some(rebased)
+}
+
+/// Calculate the depth difference between two messages in the same tree
+pub fn depth_difference(lineage_a: &[Uuid], lineage_b: &[Uuid]) -> usize {
+    let common = common_ancestor_path(lineage_a, lineage_b).len();
+    (lineage_a.len() - common) + (lineage_b.len() - common)
+}
+
+/// How the paths of two branches relate, from the lineages of their leaves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchDiff {
+    /// Messages both branches include, from the root
+    pub common: Vec<Uuid>,
+    /// Messages only the first branch includes, from the fork point down
+    pub only_a: Vec<Uuid>,
+    /// Messages only the second branch includes, from the fork point down
+    pub only_b: Vec<Uuid>,
+}
+
+impl BranchDiff {
+    /// The last message both branches include; `None` if they share no
+    /// root, i.e. belong to different conversations
+    pub fn fork_point(&self) -> Option<Uuid> {
+        self.common.last().copied()
+    }
+
+    /// Whether the first branch is the second one extended, or the same
+    pub fn is_fast_forward(&self) -> bool {
+        self.only_b.is_empty()
+    }
+}
+
+/// Compare the branches whose leaves have lineages `lineage_a` and
+/// `lineage_b`
+pub fn diff_branches(lineage_a: &[Uuid], lineage_b: &[Uuid]) -> BranchDiff {
+    let common = common_ancestor_path(lineage_a, lineage_b);
+    BranchDiff {
+        only_a: lineage_a[common.len()..].to_vec(),
+        only_b: lineage_b[common.len()..].to_vec(),
+        common,
+    }
+}
+
+/// A message with its replies, oldest first
+#[derive(Debug, Clone)]
+pub struct MessageTree {
+    pub message: Message,
+    pub children: Vec<MessageTree>,
+}
+
+/// Nest messages under their parents, keeping their order among siblings;
+/// pass them oldest first, as the server returns them, for the server's
+/// order. Built without recursion, since paths can be thousands of messages
+/// deep. Messages whose parent is missing are left out.
+pub fn nest_messages(messages: Vec<Message>) -> Option<MessageTree> {
+    let index: BTreeMap<Uuid, usize> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, m)| (m.message_id, i))
+        .collect();
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); messages.len()];
+    let mut root = None;
+    for (i, message) in messages.iter().enumerate() {
+        match message.parent_message_id {
+            Some(parent_id) => {
+                if let Some(&parent) = index.get(&parent_id) {
+                    children[parent].push(i);
+                }
+            }
+            None if message.is_root() => root = Some(i),
+            None => {}
+        }
+    }
+    let root = root?;
+
+    // Parents come before their children in preorder, so building in
+    // reverse preorder finds every child's subtree already built
+    let mut preorder = Vec::with_capacity(messages.len());
+    let mut stack = vec![root];
+    while let Some(i) = stack.pop() {
+        preorder.push(i);
+        stack.extend(children[i].iter().rev());
+    }
+
+    let mut messages: Vec<Option<Message>> = messages.into_iter().map(Some).collect();
+    let mut built: Vec<Option<MessageTree>> = (0..messages.len()).map(|_| None).collect();
+    for &i in preorder.iter().rev() {
+        let subtrees = children[i]
+            .iter()
+            .filter_map(|&child| built[child].take())
+            .collect();
+        built[i] = Some(MessageTree {
+            message: messages[i].take()?,
+            children: subtrees,
+        });
+    }
+
+    built[root].take()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ContentType, MessageRole, TextContent};
+
+    #[test]
+    fn test_compute_lineage() {
+        let parent_lineage = vec![Uuid::new_v4(), Uuid::new_v4()];
+        let new_id = Uuid::new_v4();
+
+        let lineage = compute_lineage(&parent_lineage, new_id);
+
+        assert_eq!(lineage.len(), 3);
+        assert_eq!(lineage[2], new_id);
+    }
+
+    #[test]
+    fn test_is_ancestor() {
+        let ancestor = Uuid::new_v4();
+        let middle = Uuid::new_v4();
+        let leaf = Uuid::new_v4();
+        let lineage = vec![ancestor, middle, leaf];
+
+        assert!(is_ancestor(ancestor, &lineage));
+        assert!(is_ancestor(middle, &lineage));
+        assert!(!is_ancestor(Uuid::new_v4(), &lineage));
+    }
+
+    #[test]
+    fn test_common_ancestor_path() {
+        let root = Uuid::new_v4();
+        let common = Uuid::new_v4();
+        let branch_a = Uuid::new_v4();
+        let branch_b = Uuid::new_v4();
+
+        let lineage_a = vec![root, common, branch_a];
+        let lineage_b = vec![root, common, branch_b];
+
+        let common_path = common_ancestor_path(&lineage_a, &lineage_b);
+
+        assert_eq!(common_path, vec![root, common]);
+    }
+
+    #[test]
+    fn test_rebase_lineage() {
+        let root = Uuid::new_v4();
+        let old_parent = Uuid::new_v4();
+        let moved = Uuid::new_v4();
+        let child = Uuid::new_v4();
+        let new_parent = Uuid::new_v4();
+
+        let lineage = vec![root, old_parent, moved, child];
+        let rebased = rebase_lineage(&lineage, moved, &[root, new_parent]).unwrap();
+
+        assert_eq!(rebased, vec![root, new_parent, moved, child]);
+        assert!(rebase_lineage(&lineage, Uuid::new_v4(), &[root]).is_none());
+    }
+
+    #[test]
+    fn test_diff_branches() {
+        let [root, question, answer, retry, follow_up] = [(); 5].map(|_| Uuid::new_v4());
+
+        let diff = diff_branches(
+            &[root, question, answer, follow_up],
+            &[root, question, retry],
+        );
+        assert_eq!(diff.fork_point(), Some(question));
+        assert_eq!(diff.only_a, vec![answer, follow_up]);
+        assert_eq!(diff.only_b, vec![retry]);
+        assert!(!diff.is_fast_forward());
+
+        let ahead = diff_branches(&[root, question, answer], &[root, question]);
+        assert!(ahead.is_fast_forward() && ahead.only_a == vec![answer]);
+        assert_eq!(diff_branches(&[root], &[Uuid::new_v4()]).fork_point(), None);
+    }
+
+    #[test]
+    fn test_nest_messages_keeps_sibling_order() {
+        let root = crate::domain::Conversation::new("t".to_string(), "u".to_string()).root_message;
+        let reply = |parent: &Message, text: &str| {
+            let message_id = Uuid::new_v4();
+            Message {
+                conversation_id: parent.conversation_id,
+                message_id,
+                parent_message_id: Some(parent.message_id),
+                role: MessageRole::Human,
+                content: ContentType::Text(TextContent {
+                    text: text.to_string(),
+                }),
+                content_metadata: Default::default(),
+                lineage: compute_lineage(&parent.lineage, message_id),
+                created_at: parent.created_at,
+                created_by: "u".to_string(),
+            }
+        };
+        let first = reply(&root, "first");
+        let second = reply(&root, "second");
+        let nested = reply(&first, "nested");
+        let mut orphan = reply(&second, "orphan");
+        orphan.parent_message_id = Some(Uuid::new_v4());
+
+        let tree = nest_messages(vec![
+            root.clone(),
+            first.clone(),
+            second.clone(),
+            nested.clone(),
+            orphan,
+        ])
+        .unwrap();
+
+        assert_eq!(tree.message.message_id, root.message_id);
+        let ids: Vec<Uuid> = tree.children.iter().map(|c| c.message.message_id).collect();
+        assert_eq!(ids, vec![first.message_id, second.message_id]);
+        assert_eq!(
+            tree.children[0].children[0].message.message_id,
+            nested.message_id
+        );
+        assert!(tree.children[1].children.is_empty());
+        assert!(nest_messages(vec![first]).is_none());
+    }
+}
@@ -0,0 +1,177 @@
+//! Requests and responses of `/api/v2`
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{AuthorKind, ContentType, Conversation, Message, MessageRole};
+
+/// Role of a message author. The root message that v1 exposes with role
+/// `root` is the conversation itself in v2 and never appears as a message.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Human,
+    Assistant,
+    System,
+    Tool,
+}
+
+impl Role {
+    pub fn from_domain(role: MessageRole) -> Option<Self> {
+        match role {
+            MessageRole::Root => None,
+            MessageRole::Human => Some(Role::Human),
+            MessageRole::Assistant => Some(Role::Assistant),
+            MessageRole::System => Some(Role::System),
+            MessageRole::Tool => Some(Role::Tool),
+        }
+    }
+}
+
+impl From<Role> for MessageRole {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::Human => MessageRole::Human,
+            Role::Assistant => MessageRole::Assistant,
+            Role::System => MessageRole::System,
+            Role::Tool => MessageRole::Tool,
+        }
+    }
+}
+
+// Request DTOs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateConversationRequest {
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_public: bool,
+    /// Language tag such as `en` or `pt-BR`
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateMessageRequest {
+    /// Omit to start a new thread at the top of the conversation
+    pub parent_id: Option<Uuid>,
+    pub role: Role,
+    pub content: ContentType,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Branch whose leaf moves to the new message
+    pub branch_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TreeFormat {
+    #[default]
+    Nested,
+    Flat,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TreeQuery {
+    #[serde(default)]
+    pub format: TreeFormat,
+}
+
+// Response DTOs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForkOrigin {
+    pub conversation_id: Uuid,
+    pub message_id: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationResponse {
+    pub id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub owner: String,
+    pub is_public: bool,
+    pub created_at: DateTime<Utc>,
+    pub forked_from: Option<ForkOrigin>,
+    pub language: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageResponse {
+    pub id: Uuid,
+    pub conversation_id: Uuid,
+    /// `None` for messages at the top of the conversation
+    pub parent_id: Option<Uuid>,
+    pub role: Role,
+    pub content: ContentType,
+    pub metadata: HashMap<String, String>,
+    /// 1 for messages at the top of the conversation
+    pub depth: usize,
+    pub author: String,
+    pub author_kind: AuthorKind,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeNode {
+    #[serde(flatten)]
+    pub message: MessageResponse,
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeResponse {
+    pub conversation: ConversationResponse,
+    pub total_messages: usize,
+    /// Top-level messages with their replies; with `format=nested`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<Vec<TreeNode>>,
+    /// Every message, oldest first; with `format=flat`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<MessageResponse>>,
+}
+
+impl ConversationResponse {
+    /// `None` if the root message doesn't carry conversation metadata
+    pub fn from_domain(conversation: &Conversation) -> Option<Self> {
+        let metadata = conversation.metadata()?;
+        Some(ConversationResponse {
+            id: conversation.conversation_id,
+            title: metadata.title.clone(),
+            description: metadata.description.clone(),
+            owner: conversation.created_by().to_string(),
+            is_public: metadata.is_public,
+            created_at: conversation.created_at(),
+            forked_from: metadata
+                .fork_from_conversation_id
+                .map(|conversation_id| ForkOrigin {
+                    conversation_id,
+                    message_id: metadata.fork_from_message_id,
+                }),
+            language: metadata.language.clone(),
+        })
+    }
+}
+
+impl MessageResponse {
+    /// `None` for the root message, which v2 doesn't expose
+    pub fn from_domain(message: Message) -> Option<Self> {
+        let depth = message.depth().saturating_sub(1);
+        let role = Role::from_domain(message.role)?;
+        // Lineages start at the root
+        let root_id = message.lineage.first().copied();
+        Some(MessageResponse {
+            id: message.message_id,
+            conversation_id: message.conversation_id,
+            parent_id: message.parent_message_id.filter(|id| Some(*id) != root_id),
+            role,
+            content: message.content,
+            metadata: message.content_metadata,
+            depth,
+            author_kind: AuthorKind::of(&message.created_by),
+            author: message.created_by,
+            created_at: message.created_at,
+        })
+    }
+}
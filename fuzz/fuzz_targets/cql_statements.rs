@@ -0,0 +1,16 @@
+//! Migration files are semi-trusted input. Splitting them must never panic
+//! and must only produce trimmed, non-empty statements.
+
+#![no_main]
+
+use aigc_history::db::migration::parse_statements;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|sql: &str| {
+    if let Ok(statements) = parse_statements(sql, "fuzz") {
+        for statement in statements {
+            assert!(!statement.is_empty());
+            assert_eq!(statement.trim(), statement);
+        }
+    }
+});
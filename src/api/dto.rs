@@ -1,162 +1,201 @@
-use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use uuid::Uuid;
-
-use crate::domain::{Branch, ContentType, Message, MessageRole, Permission};
-
-// Request DTOs
-#[derive(Debug, Deserialize)]
-pub struct CreateConversationRequest {
-    pub title: String,
-    pub created_by: String,
-}
+//! The `/api/v1` DTOs live in `aigc-history-types` so clients can share
+//! them, along with their conversions from the domain model. Conversions
+//! from the server's own service types are here.
 
-#[derive(Debug, Deserialize)]
-pub struct UpdateConversationRequest {
-    pub title: Option<String>,
-    pub description: Option<String>,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct CreateMessageRequest {
-    pub parent_message_id: Uuid,
-    pub role: String,
-    pub content: ContentType,
-    #[serde(default)]
-    pub content_metadata: HashMap<String, String>,
-    pub created_by: String,
-    pub branch_id: Option<Uuid>,
-}
+pub use aigc_history_types::dto::*;
 
-#[derive(Debug, Deserialize)]
-pub struct CreateBranchRequest {
-    pub branch_name: String,
-    pub leaf_message_id: Uuid,
-    pub created_by: String,
-}
-
-#[derive(Debug, Deserialize)]
-pub struct UpdateBranchRequest {
-    pub branch_name: Option<String>,
-    pub leaf_message_id: Option<Uuid>,
-}
+use uuid::Uuid;
 
-#[derive(Debug, Deserialize)]
-pub struct ForkConversationRequest {
-    pub title: String,
-    pub created_by: String,
+use crate::db::AnalyticsRollupRow;
+use crate::scheduler::TaskHealth;
+use crate::services::{
+    ContextMessage, ConversationContext, DuplicateGroup, ExportStatus, ForkGraph, ForkGraphNode,
+    ForkOptions, MessageMatch, ModelUsage,
+};
+
+impl From<&ForkConversationRequest> for ForkOptions {
+    fn from(request: &ForkConversationRequest) -> Self {
+        let defaults = ForkOptions::default();
+        ForkOptions {
+            remap_ids: request.remap_ids.unwrap_or(defaults.remap_ids),
+            copy_branches: request.copy_branches.unwrap_or(defaults.copy_branches),
+            copy_share: request.copy_share.unwrap_or(defaults.copy_share),
+        }
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ShareConversationRequest {
-    pub shared_with: String,
-    pub permission: String,
-    pub shared_by: String,
+impl From<ForkGraphNode> for ForkGraphNodeResponse {
+    fn from(node: ForkGraphNode) -> Self {
+        ForkGraphNodeResponse {
+            conversation_id: node.conversation_id,
+            title: node.title,
+            created_by: node.created_by,
+            created_at: node.created_at,
+            fork_from_conversation_id: node.fork_from_conversation_id,
+            fork_from_message_id: node.fork_from_message_id,
+        }
+    }
 }
 
-// Response DTOs
-#[derive(Debug, Serialize)]
-pub struct ConversationResponse {
-    pub conversation_id: Uuid,
-    pub title: String,
-    pub description: Option<String>,
-    pub created_at: DateTime<Utc>,
-    pub created_by: String,
-    pub is_public: bool,
-    pub fork_from_conversation_id: Option<Uuid>,
-    pub fork_from_message_id: Option<Uuid>,
+impl From<ForkGraph> for ForkGraphResponse {
+    fn from(graph: ForkGraph) -> Self {
+        // Only edges between nodes in the graph; the origin's own source may be gone
+        let edges = graph
+            .nodes
+            .iter()
+            .filter_map(|node| {
+                let source = node.fork_from_conversation_id?;
+                graph
+                    .nodes
+                    .iter()
+                    .any(|n| n.conversation_id == source)
+                    .then_some(ForkGraphEdgeResponse {
+                        source_conversation_id: source,
+                        fork_conversation_id: node.conversation_id,
+                        fork_from_message_id: node.fork_from_message_id,
+                    })
+            })
+            .collect();
+
+        ForkGraphResponse {
+            conversation_id: graph.conversation_id,
+            origin_conversation_id: graph.origin_conversation_id,
+            nodes: graph.nodes.into_iter().map(Into::into).collect(),
+            edges,
+            truncated: graph.truncated,
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct MessageResponse {
-    pub conversation_id: Uuid,
-    pub message_id: Uuid,
-    pub parent_message_id: Option<Uuid>,
-    pub role: String,
-    pub content: ContentType,
-    pub content_metadata: HashMap<String, String>,
-    pub lineage: Vec<Uuid>,
-    pub depth: usize,
-    pub created_at: DateTime<Utc>,
-    pub created_by: String,
+impl From<ExportStatus> for ExportResponse {
+    fn from(status: ExportStatus) -> Self {
+        let export = status.export;
+        ExportResponse {
+            export_id: export.export_id,
+            conversation_id: export.conversation_id,
+            format: export.format,
+            requested_by: export.requested_by,
+            status: status.status.as_str().to_string(),
+            created_at: export.created_at,
+            completed_at: export.completed_at,
+            size_bytes: export.size_bytes,
+            download_url: status.download_url,
+            download_expires_at: status.download_expires_at,
+            error: status.error,
+        }
+    }
 }
 
-impl From<Message> for MessageResponse {
-    fn from(msg: Message) -> Self {
-        let depth = msg.depth();
-        let lineage = msg.lineage.clone();
-        MessageResponse {
-            conversation_id: msg.conversation_id,
-            message_id: msg.message_id,
-            parent_message_id: msg.parent_message_id,
-            role: msg.role.as_str().to_string(),
-            content: msg.content,
-            content_metadata: msg.content_metadata,
-            lineage,
-            depth,
-            created_at: msg.created_at,
-            created_by: msg.created_by,
+impl From<DuplicateGroup> for DuplicateGroupResponse {
+    fn from(group: DuplicateGroup) -> Self {
+        DuplicateGroupResponse {
+            content_hash: group.content_hash,
+            content_type: group.content_type,
+            message_ids: group.message_ids,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct BranchResponse {
-    pub conversation_id: Uuid,
-    pub branch_id: Uuid,
-    pub branch_name: String,
-    pub leaf_message_id: Uuid,
-    pub created_at: DateTime<Utc>,
-    pub last_updated: DateTime<Utc>,
-    pub created_by: String,
-    pub is_active: bool,
+impl From<MessageMatch> for MessageMatchResponse {
+    fn from(m: MessageMatch) -> Self {
+        MessageMatchResponse {
+            message_id: m.message_id,
+            role: m.role.as_str().to_string(),
+            created_at: m.created_at,
+            field: m.field.to_string(),
+            snippet: m.snippet,
+            highlights: m
+                .highlights
+                .into_iter()
+                .map(|(start, end)| [start, end])
+                .collect(),
+        }
+    }
 }
 
-impl From<Branch> for BranchResponse {
-    fn from(branch: Branch) -> Self {
-        BranchResponse {
-            conversation_id: branch.conversation_id,
-            branch_id: branch.branch_id,
-            branch_name: branch.branch_name,
-            leaf_message_id: branch.leaf_message_id,
-            created_at: branch.created_at,
-            last_updated: branch.last_updated,
-            created_by: branch.created_by,
-            is_active: branch.is_active,
+impl From<AnalyticsRollupRow> for AnalyticsDayResponse {
+    fn from(row: AnalyticsRollupRow) -> Self {
+        AnalyticsDayResponse {
+            day: row.day,
+            counts: AnalyticsCounts {
+                message_count: row.message_count,
+                fork_count: row.fork_count,
+                messages_by_role: row
+                    .messages_by_role
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+                messages_by_model: row
+                    .messages_by_model
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect(),
+            },
+            computed_at: row.computed_at,
         }
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct ShareResponse {
-    pub conversation_id: Uuid,
-    pub shared_with: String,
-    pub permission: String,
-    pub shared_at: DateTime<Utc>,
-    pub shared_by: String,
+impl From<ModelUsage> for ModelUsageResponse {
+    fn from(usage: ModelUsage) -> Self {
+        ModelUsageResponse {
+            model: usage.model,
+            message_count: usage.message_count,
+            input_tokens: usage.input_tokens,
+            output_tokens: usage.output_tokens,
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct TreeResponse {
-    pub conversation_id: Uuid,
-    pub messages: Vec<MessageResponse>,
-    pub total_messages: usize,
+impl From<ContextMessage> for ContextMessageResponse {
+    fn from(context_message: ContextMessage) -> Self {
+        ContextMessageResponse {
+            message_id: context_message.message.message_id,
+            role: context_message.message.role.as_str().to_string(),
+            content: context_message.message.content,
+            estimated_tokens: context_message.tokens,
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-pub struct HealthResponse {
-    pub status: String,
-    pub timestamp: DateTime<Utc>,
+pub fn context_response(
+    conversation_id: Uuid,
+    query: &ContextQuery,
+    context: ConversationContext,
+) -> ContextResponse {
+    ContextResponse {
+        conversation_id,
+        leaf_message_id: query.leaf,
+        strategy: query.strategy,
+        max_tokens: query.max_tokens,
+        estimated_tokens: context.tokens,
+        summarized_messages: context.summarized,
+        truncated_messages: context.truncated,
+        messages: context.messages.into_iter().map(Into::into).collect(),
+    }
 }
 
-// Helper to parse role from string
-pub fn parse_role(role_str: &str) -> Result<MessageRole, String> {
-    MessageRole::parse(role_str).ok_or_else(|| format!("Invalid role: {}", role_str))
+impl From<TaskHealth> for TaskHealthResponse {
+    fn from(task: TaskHealth) -> Self {
+        TaskHealthResponse {
+            name: task.name.to_string(),
+            interval_secs: task.interval.as_secs(),
+            enabled: task.enabled,
+            running: task.running,
+            runs: task.runs,
+            failures: task.failures,
+            last_started_at: task.last_started_at,
+            last_success_at: task.last_success_at,
+            last_error: task.last_error,
+        }
+    }
 }
 
-// Helper to parse permission from string
-pub fn parse_permission(permission_str: &str) -> Result<Permission, String> {
-    Permission::parse(permission_str)
-        .ok_or_else(|| format!("Invalid permission: {}", permission_str))
+// Helper to normalize an invitee email; only the shape is checked
+pub fn parse_email(email: &str) -> Result<String, String> {
+    let email = email.trim().to_lowercase();
+    match email.split_once('@') {
+        Some((local, domain)) if !local.is_empty() && domain.contains('.') => Ok(email),
+        _ => Err(format!("Invalid email: {}", email)),
+    }
 }
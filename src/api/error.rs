@@ -1,11 +1,12 @@
 use axum::{
     Json,
-    http::StatusCode,
+    extract::{FromRequest, rejection::JsonRejection},
+    http::{StatusCode, header},
     response::{IntoResponse, Response},
 };
 use serde_json::json;
 
-use crate::db::DbError;
+use crate::db::{DbError, ForkRejection};
 
 #[derive(Debug)]
 pub enum ApiError {
@@ -14,7 +15,71 @@ pub enum ApiError {
     BadRequest(String),
     Unauthorized(String),
     Forbidden(String),
+    Conflict(String),
     Internal(String),
+    /// The server is shedding load; the client should retry after the given number of seconds
+    Overloaded(u64),
+    /// The service account used up its requests for the minute; retry after the given number of seconds
+    RateLimited(u64),
+    /// The conversation reached a depth, width or size limit; forking it starts a fresh one
+    ConversationTooLarge(String),
+    /// A new, moved or imported message would be deeper than allowed; a
+    /// fork of the conversation starts from its root again
+    LineageTooDeep {
+        depth: usize,
+        max: usize,
+    },
+    Fork(ForkRejection),
+    /// The conversation is read-only until unlocked
+    Locked(String),
+    /// The conversation is under legal hold and can't be deleted
+    LegalHold(String),
+}
+
+/// What an error response was built from, kept in its extensions so the
+/// problem details middleware can re-render the body
+#[derive(Debug, Clone)]
+pub struct ErrorDetails {
+    pub code: &'static str,
+    pub detail: String,
+}
+
+impl ApiError {
+    /// Stable, machine-readable identifier of the kind of error
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::Database(DbError::RowParse { .. }) => "corrupt_row",
+            ApiError::Database(DbError::UnknownContentType { .. }) => "unknown_content_type",
+            ApiError::Database(_) => "database_error",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Internal(_) => "internal_error",
+            ApiError::Overloaded(_) => "overloaded",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::ConversationTooLarge(_) => "conversation_too_large",
+            ApiError::LineageTooDeep { .. } => "lineage_too_deep",
+            ApiError::Fork(ForkRejection::SourceTooLarge { .. }) => "fork_source_too_large",
+            ApiError::Fork(ForkRejection::RateLimited { .. }) => "fork_rate_limited",
+            ApiError::Fork(ForkRejection::NotAllowed(_)) => "fork_not_allowed",
+            ApiError::Locked(_) => "conversation_locked",
+            ApiError::LegalHold(_) => "legal_hold",
+        }
+    }
+}
+
+/// `Json` whose rejections are `400 Bad Request` API errors. serde's message
+/// names the offending field and, for enums, the allowed values.
+#[derive(Debug, FromRequest)]
+#[from_request(via(Json), rejection(ApiError))]
+pub struct ApiJson<T>(pub T);
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
 }
 
 impl From<DbError> for ApiError {
@@ -22,6 +87,15 @@ impl From<DbError> for ApiError {
         match err {
             DbError::NotFound => ApiError::NotFound("Resource not found".to_string()),
             DbError::InvalidData(msg) => ApiError::BadRequest(msg),
+            DbError::LimitExceeded(msg) => ApiError::ConversationTooLarge(format!(
+                "{}; fork the conversation to continue",
+                msg
+            )),
+            DbError::Conflict(msg) => ApiError::Conflict(msg),
+            DbError::LineageTooDeep { depth, max } => ApiError::LineageTooDeep { depth, max },
+            DbError::ForkRejected(rejection) => ApiError::Fork(rejection),
+            DbError::Locked(_) => ApiError::Locked(err.to_string()),
+            DbError::LegalHold(_) => ApiError::LegalHold(err.to_string()),
             _ => ApiError::Database(err),
         }
     }
@@ -29,6 +103,15 @@ impl From<DbError> for ApiError {
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
+        let retry_after = match &self {
+            ApiError::Overloaded(secs) | ApiError::RateLimited(secs) => Some(*secs),
+            ApiError::Fork(ForkRejection::RateLimited {
+                retry_after_secs, ..
+            }) => Some(*retry_after_secs),
+            _ => None,
+        };
+        let code = self.code();
+
         let (status, message) = match self {
             ApiError::Database(err) => (
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -38,13 +121,113 @@ impl IntoResponse for ApiError {
             ApiError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
             ApiError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
             ApiError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            ApiError::Conflict(msg) => (StatusCode::CONFLICT, msg),
             ApiError::Internal(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
+            ApiError::Overloaded(_) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Server is at capacity, retry later".to_string(),
+            ),
+            ApiError::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Service account rate limit exceeded, retry later".to_string(),
+            ),
+            ApiError::ConversationTooLarge(msg) => (StatusCode::UNPROCESSABLE_ENTITY, msg),
+            ApiError::LineageTooDeep { depth, max } => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!(
+                    "The message would be {} deep, more than the {} allowed; fork the conversation to continue",
+                    depth, max
+                ),
+            ),
+            ApiError::Fork(rejection) => {
+                let status = match rejection {
+                    ForkRejection::SourceTooLarge { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+                    ForkRejection::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+                    ForkRejection::NotAllowed(_) => StatusCode::FORBIDDEN,
+                };
+                (status, rejection.to_string())
+            }
+            ApiError::Locked(msg) | ApiError::LegalHold(msg) => (StatusCode::LOCKED, msg),
         };
 
         let body = Json(json!({
             "error": message,
+            "code": code,
         }));
 
-        (status, body).into_response()
+        let mut response = match retry_after {
+            Some(secs) => (status, [(header::RETRY_AFTER, secs.to_string())], body).into_response(),
+            None => (status, body).into_response(),
+        };
+        response.extensions_mut().insert(ErrorDetails {
+            code,
+            detail: message,
+        });
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::dto::ShareConversationRequest;
+    use axum::body::Body;
+    use axum::extract::Request;
+
+    #[tokio::test]
+    async fn test_invalid_enum_values_are_bad_requests() {
+        let req = Request::post("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                r#"{"shared_with": "bob", "permission": "owner", "shared_by": "alice"}"#,
+            ))
+            .unwrap();
+
+        let Err(ApiError::BadRequest(message)) =
+            ApiJson::<ShareConversationRequest>::from_request(req, &()).await
+        else {
+            panic!("expected a bad request");
+        };
+        assert!(message.contains("unknown variant `owner`"), "{}", message);
+        assert!(
+            message.contains("`read`, `branch`, `fork`, `admin`"),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn test_typed_db_errors_get_their_own_codes() {
+        let response = |err: DbError| ApiError::from(err).into_response();
+
+        let deep = response(DbError::LineageTooDeep { depth: 4, max: 3 });
+        assert_eq!(deep.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            deep.extensions().get::<ErrorDetails>().unwrap().code,
+            "lineage_too_deep"
+        );
+
+        let corrupt = response(DbError::RowParse {
+            table: "conversation_shares",
+            column: "permission",
+            reason: "unknown permission owner".to_string(),
+        });
+        assert_eq!(corrupt.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let details = corrupt.extensions().get::<ErrorDetails>().unwrap();
+        assert_eq!(details.code, "corrupt_row");
+        assert!(
+            details.detail.contains("permission in conversation_shares"),
+            "{}",
+            details.detail
+        );
+
+        let unknown = response(DbError::UnknownContentType {
+            value: "hologram".to_string(),
+        });
+        assert_eq!(unknown.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            unknown.extensions().get::<ErrorDetails>().unwrap().code,
+            "unknown_content_type"
+        );
     }
 }
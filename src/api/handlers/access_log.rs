@@ -0,0 +1,46 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{AccessLogQuery, AccessLogResponse},
+    error::ApiError,
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::middleware::AuthUser;
+use crate::services::{AccessLogService, ConversationService};
+use std::sync::Arc;
+
+/// Who read the conversation, newest first. Only for its owner.
+pub async fn get_access_log(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(service): State<Arc<AccessLogService>>,
+    State(config): State<Arc<AppConfig>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<AccessLogQuery>,
+) -> Result<(PageSize, Json<AccessLogResponse>), ApiError> {
+    let conversation = conv_service.get_conversation(conversation_id).await?;
+    if conversation.created_by() != user.0 {
+        return Err(ApiError::Forbidden(
+            "Only the owner can read the access log".to_string(),
+        ));
+    }
+    let page = PageSize::new(&config, query.limit);
+
+    let entries = service
+        .get_entries(conversation_id, &user.0, query.before, page.limit)
+        .await?;
+
+    Ok((
+        page,
+        Json(AccessLogResponse {
+            conversation_id,
+            entries: entries.entries.into_iter().map(Into::into).collect(),
+            next_before: entries.next_before,
+        }),
+    ))
+}
@@ -0,0 +1,108 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use chrono::{Duration, NaiveDate, Utc};
+use uuid::Uuid;
+
+use super::share::ensure_can_manage;
+use crate::api::{
+    dto::{AnalyticsQuery, AnalyticsResponse, UsageQuery, UsageResponse},
+    error::ApiError,
+};
+use crate::config::AdminConfig;
+use crate::middleware::AuthUser;
+use crate::services::{AnalyticsService, ConversationService, ShareService};
+use std::sync::Arc;
+
+/// Days covered when the query names no range
+const DEFAULT_ANALYTICS_DAYS: i64 = 30;
+
+/// Longest range a single request may cover
+const MAX_ANALYTICS_DAYS: i64 = 366;
+
+/// Daily rollups of the messages a user wrote and the forks they made
+pub async fn get_user_analytics(
+    State(service): State<Arc<AnalyticsService>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<AnalyticsResponse>, ApiError> {
+    user.ensure_is(&user_id)?;
+    let (from, to) = day_range(query.from, query.to)?;
+
+    let rollups = service.get_user_rollups(&user_id, from, to).await?;
+
+    let days = rollups.into_iter().map(Into::into).collect();
+    Ok(Json(AnalyticsResponse::new(from, to, days)))
+}
+
+/// Daily rollups of a conversation's messages and of the forks made from it
+pub async fn get_conversation_analytics(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(service): State<Arc<AnalyticsService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<AnalyticsQuery>,
+) -> Result<Json<AnalyticsResponse>, ApiError> {
+    ensure_can_manage(&conv_service, &share_service, conversation_id, &user).await?;
+    let (from, to) = day_range(query.from, query.to)?;
+
+    let rollups = service
+        .get_conversation_rollups(conversation_id, from, to)
+        .await?;
+
+    let days = rollups.into_iter().map(Into::into).collect();
+    Ok(Json(AnalyticsResponse::new(from, to, days)))
+}
+
+/// Messages and token usage across the deployment, grouped by model
+pub async fn get_usage(
+    State(service): State<Arc<AnalyticsService>>,
+    State(admin): State<Arc<AdminConfig>>,
+    user: AuthUser,
+    Query(query): Query<UsageQuery>,
+) -> Result<Json<UsageResponse>, ApiError> {
+    user.ensure_admin(&admin)?;
+    let group_by = query.group_by.unwrap_or_else(|| "model".to_string());
+    if group_by != "model" {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported group_by `{}`; expected `model`",
+            group_by
+        )));
+    }
+    let (from, to) = day_range(query.from, query.to)?;
+
+    let usage = service.get_usage_by_model(from, to).await?;
+
+    Ok(Json(UsageResponse {
+        from,
+        to,
+        group_by,
+        groups: usage.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// The requested days, defaulting to the last 30 up to today
+fn day_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<(NaiveDate, NaiveDate), ApiError> {
+    let to = to.unwrap_or_else(|| Utc::now().date_naive());
+    let from = from.unwrap_or(to - Duration::days(DEFAULT_ANALYTICS_DAYS - 1));
+
+    if from > to {
+        return Err(ApiError::BadRequest(
+            "`from` must not be after `to`".to_string(),
+        ));
+    }
+    if (to - from).num_days() >= MAX_ANALYTICS_DAYS {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} days can be requested at once",
+            MAX_ANALYTICS_DAYS
+        )));
+    }
+
+    Ok((from, to))
+}
@@ -1,91 +1,189 @@
 use axum::{
-    Json,
-    extract::{Path, State},
+    Extension, Json,
+    extract::{Path, Query, State},
 };
 use uuid::Uuid;
 
+use super::share::ensure_can_write;
 use crate::api::{
-    dto::{BranchResponse, CreateBranchRequest, MessageResponse, UpdateBranchRequest},
-    error::ApiError,
+    dto::{
+        BranchMessagesQuery, BranchResponse, CreateBranchRequest, MessageResponse,
+        UpdateBranchRequest,
+    },
+    error::{ApiError, ApiJson},
 };
-use crate::services::BranchService;
+use crate::db::DbError;
+use crate::domain::Branch;
+use crate::middleware::AuthUser;
+use crate::services::{BranchService, ConversationMailboxes, ConversationService, ImageService};
 use std::sync::Arc;
 
 pub async fn create_branch(
     State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    user: AuthUser,
     Path(conversation_id): Path<Uuid>,
-    Json(payload): Json<CreateBranchRequest>,
+    ApiJson(payload): ApiJson<CreateBranchRequest>,
 ) -> Result<Json<BranchResponse>, ApiError> {
+    ensure_can_write(&conv_service, conversation_id, &user).await?;
+    let created_by = user.attribute("created_by", payload.created_by)?;
     let branch = service
         .create_branch(
             conversation_id,
             payload.branch_name,
             payload.leaf_message_id,
-            payload.created_by,
+            created_by,
         )
         .await?;
 
     Ok(Json(branch.into()))
 }
 
+/// The branch, unless it ends at a message the viewer may not read
 pub async fn get_branch(
     State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path((conversation_id, branch_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<BranchResponse>, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
     let branch = service.get_branch(conversation_id, branch_id).await?;
+    let branch = visible_branch(&conv_service, viewer.as_deref(), branch).await?;
 
     Ok(Json(branch.into()))
 }
 
+/// The branch with the slug, unless it ends at a message the viewer may not
+/// read
+pub async fn get_branch_by_slug(
+    State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path((conversation_id, slug)): Path<(Uuid, String)>,
+) -> Result<Json<BranchResponse>, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    let branch = service.get_branch_by_slug(conversation_id, &slug).await?;
+    let branch = visible_branch(&conv_service, viewer.as_deref(), branch).await?;
+
+    Ok(Json(branch.into()))
+}
+
+/// The branches of the conversation, without those ending at a message the
+/// viewer may not read
 pub async fn get_branches(
     State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path(conversation_id): Path<Uuid>,
 ) -> Result<Json<Vec<BranchResponse>>, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    let mut private = conv_service
+        .private_messages(conversation_id, viewer.as_deref())
+        .await?;
     let branches = service.get_branches(conversation_id).await?;
+    let branches = conv_service
+        .visible_branches(conversation_id, branches, &mut private)
+        .await?;
 
     let responses = branches.into_iter().map(Into::into).collect();
 
     Ok(Json(responses))
 }
 
+/// `branch`, or not found if it ends at a message `viewer` may not read
+async fn visible_branch(
+    conv_service: &ConversationService,
+    viewer: Option<&str>,
+    branch: Branch,
+) -> Result<Branch, ApiError> {
+    let conversation_id = branch.conversation_id;
+    let mut private = conv_service
+        .private_messages(conversation_id, viewer)
+        .await?;
+    conv_service
+        .visible_branches(conversation_id, vec![branch], &mut private)
+        .await?
+        .pop()
+        .ok_or(DbError::NotFound.into())
+}
+
+/// The branch's lineage, without the messages the viewer may not read
 pub async fn get_branch_messages(
     State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path((conversation_id, branch_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<BranchMessagesQuery>,
 ) -> Result<Json<Vec<MessageResponse>>, ApiError> {
-    let messages = service
-        .get_branch_messages(conversation_id, branch_id)
+    let viewer = viewer.map(|Extension(user)| user.0);
+    let private = conv_service
+        .private_messages(conversation_id, viewer.as_deref())
         .await?;
+    let messages = if query.compact {
+        service
+            .get_compacted_branch_messages(conversation_id, branch_id)
+            .await?
+    } else {
+        service
+            .get_branch_messages(conversation_id, branch_id)
+            .await?
+    };
 
-    let responses = messages.into_iter().map(Into::into).collect();
+    let responses = messages
+        .into_iter()
+        .filter_map(|message| private.redact(message))
+        .map(|message| images.presign(message).into())
+        .collect();
 
     Ok(Json(responses))
 }
 
 pub async fn update_branch(
     State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    State(mailboxes): State<Arc<ConversationMailboxes>>,
+    user: AuthUser,
     Path((conversation_id, branch_id)): Path<(Uuid, Uuid)>,
-    Json(payload): Json<UpdateBranchRequest>,
+    ApiJson(payload): ApiJson<UpdateBranchRequest>,
 ) -> Result<Json<BranchResponse>, ApiError> {
-    if let Some(new_name) = payload.branch_name {
-        service
-            .update_branch_name(conversation_id, branch_id, new_name)
-            .await?;
-    }
-
-    if let Some(new_leaf_id) = payload.leaf_message_id {
-        service
-            .update_branch_leaf(conversation_id, branch_id, new_leaf_id)
-            .await?;
-    }
+    ensure_can_write(&conv_service, conversation_id, &user).await?;
+    let branches = service.clone();
+    mailboxes
+        .run(conversation_id, async move {
+            if let Some(new_name) = payload.branch_name {
+                branches
+                    .update_branch_name(conversation_id, branch_id, new_name)
+                    .await?;
+            }
+
+            if let Some(new_leaf_id) = payload.leaf_message_id {
+                branches
+                    .update_branch_leaf(conversation_id, branch_id, new_leaf_id)
+                    .await?;
+            }
+
+            Ok::<_, DbError>(())
+        })
+        .await?;
 
     // Fetch updated branch
-    get_branch(State(service), Path((conversation_id, branch_id))).await
+    get_branch(
+        State(service),
+        State(conv_service),
+        Some(Extension(user)),
+        Path((conversation_id, branch_id)),
+    )
+    .await
 }
 
 pub async fn delete_branch(
     State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    user: AuthUser,
     Path((conversation_id, branch_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_can_write(&conv_service, conversation_id, &user).await?;
     service.delete_branch(conversation_id, branch_id).await?;
 
     Ok(Json(serde_json::json!({
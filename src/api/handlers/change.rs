@@ -0,0 +1,93 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{ChangeResponse, ChangesQuery, ChangesResponse, SyncRequest, SyncResponse},
+    error::{ApiError, ApiJson},
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::domain::change::parse_change_cursor;
+use crate::middleware::AuthUser;
+use crate::services::{BranchService, ConversationService, ImageService, PrivateMessages};
+use std::sync::Arc;
+
+pub async fn get_changes(
+    State(service): State<Arc<ConversationService>>,
+    State(config): State<Arc<AppConfig>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<ChangesQuery>,
+) -> Result<(PageSize, Json<ChangesResponse>), ApiError> {
+    let since = query
+        .since
+        .as_deref()
+        .map(|since| {
+            parse_change_cursor(since)
+                .ok_or_else(|| ApiError::BadRequest(format!("Invalid since cursor: {}", since)))
+        })
+        .transpose()?;
+
+    let page = PageSize::new(&config, query.limit);
+    let mut private = super::message::private_messages(&service, conversation_id, viewer).await?;
+
+    let changes = service
+        .get_changes(conversation_id, since, page.limit as i32)
+        .await?;
+    let named = changes
+        .iter()
+        .filter_map(PrivateMessages::named_by_change)
+        .collect();
+    service
+        .resolve_private(conversation_id, &mut private, named)
+        .await?;
+
+    // The cursor moves past hidden changes too, so clients don't ask for them again
+    let next_cursor = changes.last().map(|change| change.cursor()).or(query.since);
+    let changes: Vec<ChangeResponse> = changes
+        .into_iter()
+        .filter_map(|change| private.redact_change(change))
+        .map(Into::into)
+        .collect();
+
+    Ok((
+        page,
+        Json(ChangesResponse {
+            conversation_id,
+            changes,
+            next_cursor,
+        }),
+    ))
+}
+
+/// Catch a client up from the leaves it has: the messages and branches it's
+/// missing, without replaying the change feed
+pub async fn sync_conversation(
+    State(service): State<Arc<BranchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<SyncRequest>,
+) -> Result<Json<SyncResponse>, ApiError> {
+    let private = super::message::private_messages(&conv_service, conversation_id, viewer).await?;
+    let sync = service
+        .sync(conversation_id, &payload.known_leaves, &private)
+        .await?;
+
+    Ok(Json(SyncResponse {
+        conversation_id,
+        messages: sync
+            .messages
+            .into_iter()
+            .filter_map(|message| private.redact(message))
+            .map(|message| images.presign(message).into())
+            .collect(),
+        branches: sync.branches.into_iter().map(Into::into).collect(),
+        branch_ids: sync.branch_ids,
+        unknown_leaves: sync.unknown_leaves,
+    }))
+}
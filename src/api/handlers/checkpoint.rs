@@ -0,0 +1,52 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+};
+use uuid::Uuid;
+
+use super::share::ensure_can_write;
+use crate::api::{
+    dto::{CreateCheckpointRequest, MessageResponse},
+    error::{ApiError, ApiJson},
+};
+use crate::middleware::AuthUser;
+use crate::services::ConversationService;
+use std::sync::Arc;
+
+pub async fn create_checkpoint(
+    State(service): State<Arc<ConversationService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<CreateCheckpointRequest>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    ensure_can_write(&service, conversation_id, &user).await?;
+    let created_by = user.attribute("created_by", payload.created_by)?;
+    let checkpoint = service
+        .create_checkpoint(
+            conversation_id,
+            payload.from_message_id,
+            payload.to_message_id,
+            payload.summary,
+            created_by,
+        )
+        .await?;
+
+    Ok(Json(checkpoint.into()))
+}
+
+pub async fn get_checkpoints(
+    State(service): State<Arc<ConversationService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let private = super::message::private_messages(&service, conversation_id, viewer).await?;
+    let checkpoints = service.get_checkpoints(conversation_id).await?;
+
+    let responses = checkpoints
+        .into_iter()
+        .filter_map(|checkpoint| private.redact(checkpoint))
+        .map(Into::into)
+        .collect();
+
+    Ok(Json(responses))
+}
@@ -0,0 +1,33 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+
+use crate::api::{
+    dto::{DeleteUserConversationsQuery, JobResponse},
+    error::ApiError,
+};
+use crate::db::DbError;
+use crate::domain::JobKind;
+use crate::middleware::AuthUser;
+use crate::services::{JobService, UserCleanupParams};
+use std::sync::Arc;
+
+/// Start deleting a user's conversations in the background; poll the returned job
+pub async fn delete_user_conversations(
+    State(jobs): State<Arc<JobService>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    Query(query): Query<DeleteUserConversationsQuery>,
+) -> Result<(StatusCode, Json<JobResponse>), ApiError> {
+    user.ensure_is(&user_id)?;
+
+    let params = serde_json::to_value(UserCleanupParams {
+        older_than: query.older_than,
+    })
+    .map_err(|e| DbError::SerializationError(e.to_string()))?;
+    let job = jobs.start(JobKind::UserCleanup, user_id, params).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(job.into())))
+}
@@ -0,0 +1,117 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::{Stream, stream};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+use uuid::Uuid;
+
+use super::share::ensure_can_read;
+use crate::api::error::{ApiError, ApiJson};
+use crate::domain::{ChangeKind, Message};
+use crate::middleware::AuthUser;
+use crate::services::{
+    CollaborationEvent, CollaborationHub, ConversationService, PresenceSignal, PrivateMessages,
+};
+use std::sync::Arc;
+
+/// Server-sent event stream of live changes and presence signals. Changes to
+/// messages the viewer may not see are left out; what is hidden follows the
+/// conversation updates going through the stream.
+pub async fn live_events(
+    State(hub): State<Arc<CollaborationHub>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    let receiver = hub.subscribe(conversation_id);
+    let private = conv_service
+        .private_messages(conversation_id, viewer.as_deref())
+        .await?;
+
+    let state = (receiver, private, viewer, conv_service);
+    let events = stream::unfold(
+        state,
+        move |(mut receiver, mut private, viewer, conv_service)| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        let event = match event {
+                            CollaborationEvent::Change(change) => {
+                                if change.kind == ChangeKind::ConversationUpdated
+                                    && let Some(payload) = &change.payload
+                                    && let Ok(root) =
+                                        serde_json::from_value::<Message>(payload.clone())
+                                {
+                                    private = PrivateMessages::of(&root, viewer.as_deref());
+                                }
+                                if let Some(leaf_id) = PrivateMessages::named_by_change(&change)
+                                    && let Err(err) = conv_service
+                                        .resolve_private(
+                                            conversation_id,
+                                            &mut private,
+                                            vec![leaf_id],
+                                        )
+                                        .await
+                                {
+                                    tracing::warn!(
+                                        "Couldn't tell whether branch leaf {} is private: {}",
+                                        leaf_id,
+                                        err
+                                    );
+                                    continue;
+                                }
+                                match private.redact_change(change) {
+                                    Some(change) => CollaborationEvent::Change(change),
+                                    None => continue,
+                                }
+                            }
+                            presence => presence,
+                        };
+                        let name = match &event {
+                            CollaborationEvent::Change(_) => "change",
+                            CollaborationEvent::Presence(_) => "presence",
+                        };
+                        let sse_event = Event::default()
+                            .event(name)
+                            .json_data(&event)
+                            .unwrap_or_else(|_| Event::default().event(name));
+                        return Some((Ok(sse_event), (receiver, private, viewer, conv_service)));
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            "Live subscriber of conversation {} lagged, skipped {} event(s)",
+                            conversation_id,
+                            skipped
+                        );
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        },
+    );
+
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
+
+/// Broadcast a lightweight presence/typing signal to the conversation's
+/// subscribers, on behalf of the caller, who must be able to read it
+pub async fn post_presence(
+    State(hub): State<Arc<CollaborationHub>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(mut payload): ApiJson<PresenceSignal>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_can_read(&conv_service, conversation_id, &user).await?;
+    let claimed = Some(std::mem::take(&mut payload.user_id)).filter(|id| !id.is_empty());
+    payload.user_id = user.attribute("user_id", claimed)?;
+    hub.publish(conversation_id, CollaborationEvent::Presence(payload));
+
+    Ok(Json(serde_json::json!({
+        "subscribers": hub.subscriber_count(conversation_id)
+    })))
+}
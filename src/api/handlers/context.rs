@@ -0,0 +1,45 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{ContextQuery, ContextResponse, context_response},
+    error::ApiError,
+};
+use crate::middleware::AuthUser;
+use crate::services::{ContextMessage, ContextService, ConversationService, ImageService};
+use std::sync::Arc;
+
+/// The path to a leaf message assembled as LLM context
+pub async fn get_conversation_context(
+    State(service): State<Arc<ContextService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<ContextQuery>,
+) -> Result<Json<ContextResponse>, ApiError> {
+    let private = super::message::private_messages(&conv_service, conversation_id, viewer).await?;
+    let mut context = service
+        .build_context(
+            conversation_id,
+            query.leaf,
+            query.max_tokens,
+            query.strategy,
+            &private,
+        )
+        .await?;
+
+    context.messages = context
+        .messages
+        .into_iter()
+        .map(|context_message| ContextMessage {
+            message: images.presign(context_message.message),
+            ..context_message
+        })
+        .collect();
+
+    Ok(Json(context_response(conversation_id, &query, context)))
+}
@@ -1,92 +1,99 @@
 use axum::{
-    Json,
-    extract::{Path, State},
+    Extension, Json,
+    extract::{Path, Query, State},
 };
 use uuid::Uuid;
 
 use crate::api::{
     dto::{
-        ConversationResponse, CreateConversationRequest, TreeResponse, UpdateConversationRequest,
+        ConversationResponse, CreateConversationRequest, DuplicatesResponse,
+        LockConversationRequest, LockResponse, MessageResponse, PrivateMessagesResponse,
+        PublishBranchRequest, ScrubResponse, TreeQuery, TreeResponse, UpdateConversationRequest,
     },
-    error::ApiError,
+    error::{ApiError, ApiJson},
 };
-use crate::domain::ContentType;
-use crate::services::ConversationService;
+use crate::db::DbError;
+use crate::domain::{Conversation, Message};
+use crate::middleware::AuthUser;
+use crate::services::{
+    BranchService, ConversationService, ForkService, ImageService, ShareService, TrendingService,
+    level_filter,
+};
+
+use super::share::ensure_can_manage;
 use std::sync::Arc;
 
 pub async fn create_conversation(
     State(service): State<Arc<ConversationService>>,
-    Json(payload): Json<CreateConversationRequest>,
+    user: AuthUser,
+    ApiJson(payload): ApiJson<CreateConversationRequest>,
 ) -> Result<Json<ConversationResponse>, ApiError> {
-    let conversation = service
-        .create_conversation(payload.title, payload.created_by)
+    let created_by = user.attribute("created_by", payload.created_by)?;
+    let mut conversation = service
+        .create_conversation(payload.title, created_by)
         .await?;
+    if payload.language.is_some() {
+        service
+            .update_conversation(
+                conversation.conversation_id,
+                None,
+                None,
+                None,
+                payload.language,
+            )
+            .await?;
+        conversation = service
+            .get_conversation(conversation.conversation_id)
+            .await?;
+    }
 
-    let response = match &conversation.root_message.content {
-        ContentType::Metadata(metadata) => ConversationResponse {
-            conversation_id: conversation.conversation_id,
-            title: metadata.title.clone(),
-            description: metadata.description.clone(),
-            created_at: conversation.root_message.created_at,
-            created_by: conversation.root_message.created_by.clone(),
-            is_public: metadata.is_public,
-            fork_from_conversation_id: metadata.fork_from_conversation_id,
-            fork_from_message_id: metadata.fork_from_message_id,
-        },
-        _ => {
-            return Err(ApiError::Internal(
-                "Invalid root message content".to_string(),
-            ));
-        }
-    };
-
-    Ok(Json(response))
+    Ok(Json(conversation_response(&conversation)?))
 }
 
 pub async fn get_conversation(
     State(service): State<Arc<ConversationService>>,
+    State(trending_service): State<Arc<TrendingService>>,
     Path(conversation_id): Path<Uuid>,
 ) -> Result<Json<ConversationResponse>, ApiError> {
     let conversation = service.get_conversation(conversation_id).await?;
+    trending_service.record_view(conversation_id).await;
 
-    let response = match &conversation.root_message.content {
-        ContentType::Metadata(metadata) => ConversationResponse {
-            conversation_id: conversation.conversation_id,
-            title: metadata.title.clone(),
-            description: metadata.description.clone(),
-            created_at: conversation.root_message.created_at,
-            created_by: conversation.root_message.created_by.clone(),
-            is_public: metadata.is_public,
-            fork_from_conversation_id: metadata.fork_from_conversation_id,
-            fork_from_message_id: metadata.fork_from_message_id,
-        },
-        _ => {
-            return Err(ApiError::Internal(
-                "Invalid root message content".to_string(),
-            ));
-        }
-    };
-
-    Ok(Json(response))
+    Ok(Json(conversation_response(&conversation)?))
 }
 
 pub async fn update_conversation(
     State(service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    user: AuthUser,
     Path(conversation_id): Path<Uuid>,
-    Json(payload): Json<UpdateConversationRequest>,
+    ApiJson(payload): ApiJson<UpdateConversationRequest>,
 ) -> Result<Json<ConversationResponse>, ApiError> {
+    ensure_can_manage(&service, &share_service, conversation_id, &user).await?;
+
     service
-        .update_conversation(conversation_id, payload.title, payload.description)
+        .update_conversation(
+            conversation_id,
+            payload.title,
+            payload.description,
+            payload.is_public,
+            payload.language,
+        )
         .await?;
 
     // Fetch updated conversation
-    get_conversation(State(service), Path(conversation_id)).await
+    let conversation = service.get_conversation(conversation_id).await?;
+
+    Ok(Json(conversation_response(&conversation)?))
 }
 
 pub async fn delete_conversation(
     State(service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    user: AuthUser,
     Path(conversation_id): Path<Uuid>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_can_manage(&service, &share_service, conversation_id, &user).await?;
+
     service.delete_conversation(conversation_id).await?;
 
     Ok(Json(serde_json::json!({
@@ -94,18 +101,280 @@ pub async fn delete_conversation(
     })))
 }
 
+/// Copy one of the caller's own conversations, as a lighter alternative to
+/// forking it
+pub async fn duplicate_conversation(
+    State(service): State<Arc<ConversationService>>,
+    State(fork_service): State<Arc<ForkService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<ConversationResponse>, ApiError> {
+    let conversation = service.get_conversation(conversation_id).await?;
+    if conversation.created_by() != user.0 {
+        return Err(ApiError::Forbidden(
+            "Only the owner can duplicate a conversation; fork it instead".to_string(),
+        ));
+    }
+
+    let copy = fork_service
+        .duplicate_conversation(conversation_id, &user.0)
+        .await?;
+
+    Ok(Json(conversation_response(&copy)?))
+}
+
+/// Groups of messages in the conversation with identical content, among
+/// those the viewer may read
+pub async fn get_duplicates(
+    State(service): State<Arc<ConversationService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<DuplicatesResponse>, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    let groups = service
+        .find_duplicates(conversation_id, viewer.as_deref())
+        .await?;
+
+    Ok(Json(DuplicatesResponse {
+        conversation_id,
+        groups: groups.into_iter().map(Into::into).collect(),
+    }))
+}
+
+/// Mask personal data in the conversation's stored message text
+pub async fn scrub_conversation(
+    State(service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(branch_service): State<Arc<BranchService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<ScrubResponse>, ApiError> {
+    ensure_can_manage(&service, &share_service, conversation_id, &user).await?;
+
+    let scrubbed = service.scrub_conversation(conversation_id).await?;
+    // Cached branches would keep serving the unmasked text until they expire
+    branch_service.forget_conversation(conversation_id);
+
+    Ok(Json(ScrubResponse {
+        conversation_id,
+        scrubbed_message_ids: scrubbed.into_iter().map(|m| m.message_id).collect(),
+    }))
+}
+
+/// Make the conversation read-only, e.g. once its transcript has been reviewed
+pub async fn lock_conversation(
+    State(service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    payload: Option<Json<LockConversationRequest>>,
+) -> Result<Json<LockResponse>, ApiError> {
+    ensure_can_manage(&service, &share_service, conversation_id, &user).await?;
+    let Json(payload) = payload.unwrap_or_default();
+
+    let lock = service
+        .lock_conversation(conversation_id, user.0, payload.reason)
+        .await?;
+
+    Ok(Json(lock.into()))
+}
+
+pub async fn unlock_conversation(
+    State(service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_can_manage(&service, &share_service, conversation_id, &user).await?;
+
+    service.unlock_conversation(conversation_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Conversation unlocked"
+    })))
+}
+
+pub async fn get_lock(
+    State(service): State<Arc<ConversationService>>,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<LockResponse>, ApiError> {
+    let lock = service.get_lock(conversation_id).await?;
+
+    Ok(Json(lock.into()))
+}
+
+/// The conversation's messages the viewer may read. Viewers who can't write
+/// get the lineage of the published branch instead, if there is one, unless
+/// they ask for the `full` tree.
 pub async fn get_conversation_tree(
     State(service): State<Arc<ConversationService>>,
+    State(branch_service): State<Arc<BranchService>>,
+    State(trending_service): State<Arc<TrendingService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path(conversation_id): Path<Uuid>,
+    Query(query): Query<TreeQuery>,
 ) -> Result<Json<TreeResponse>, ApiError> {
-    let messages = service.get_conversation_tree(conversation_id).await?;
+    let viewer = viewer.map(|Extension(user)| user);
+    let published = if query.full {
+        None
+    } else {
+        published_lineage(&service, &branch_service, conversation_id, viewer.as_ref()).await?
+    };
+    let (messages, published_branch_id) = match published {
+        Some((branch_id, mut messages)) => {
+            messages.retain(level_filter(
+                query.min_depth,
+                query.max_depth,
+                query.author_kind,
+            )?);
+            (messages, Some(branch_id))
+        }
+        None => (
+            service
+                .get_conversation_levels(
+                    conversation_id,
+                    query.min_depth,
+                    query.max_depth,
+                    query.author_kind,
+                )
+                .await?,
+            None,
+        ),
+    };
+    let private = service
+        .private_messages(conversation_id, viewer.as_ref().map(|user| user.0.as_str()))
+        .await?;
+    trending_service.record_view(conversation_id).await;
 
-    let total = messages.len();
-    let message_responses = messages.into_iter().map(Into::into).collect();
+    let message_responses: Vec<MessageResponse> = messages
+        .into_iter()
+        .filter_map(|message| private.redact(message))
+        .map(|message| images.presign(message).into())
+        .collect();
+    let total = message_responses.len();
 
     Ok(Json(TreeResponse {
         conversation_id,
         messages: message_responses,
         total_messages: total,
+        published_branch_id,
     }))
 }
+
+/// The published branch and its lineage, if the conversation has one and
+/// `viewer` can't write to it. Anonymous and embed viewers never can. A
+/// published branch that was since deleted is ignored.
+async fn published_lineage(
+    service: &ConversationService,
+    branch_service: &BranchService,
+    conversation_id: Uuid,
+    viewer: Option<&AuthUser>,
+) -> Result<Option<(Uuid, Vec<Message>)>, ApiError> {
+    let conversation = service.get_conversation(conversation_id).await?;
+    let Some(branch_id) = conversation.metadata().and_then(|m| m.published_branch_id) else {
+        return Ok(None);
+    };
+    if let Some(user) = viewer
+        && service.can_write(&conversation, &user.0).await?
+    {
+        return Ok(None);
+    }
+
+    match branch_service
+        .get_branch_messages(conversation_id, branch_id)
+        .await
+    {
+        Ok(messages) => Ok(Some((branch_id, messages))),
+        Err(DbError::NotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Make a message and its replies readable by the owner only
+pub async fn make_message_private(
+    State(service): State<Arc<ConversationService>>,
+    user: AuthUser,
+    Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<PrivateMessagesResponse>, ApiError> {
+    set_message_private(&service, &user, conversation_id, message_id, true).await
+}
+
+/// Make a private message readable by everyone who can read the conversation again
+pub async fn make_message_shared(
+    State(service): State<Arc<ConversationService>>,
+    user: AuthUser,
+    Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<PrivateMessagesResponse>, ApiError> {
+    set_message_private(&service, &user, conversation_id, message_id, false).await
+}
+
+async fn set_message_private(
+    service: &ConversationService,
+    user: &AuthUser,
+    conversation_id: Uuid,
+    message_id: Uuid,
+    private: bool,
+) -> Result<Json<PrivateMessagesResponse>, ApiError> {
+    let conversation = service.get_conversation(conversation_id).await?;
+    if conversation.created_by() != user.0 {
+        return Err(ApiError::Forbidden(
+            "Only the owner can make messages private".to_string(),
+        ));
+    }
+
+    let private_message_ids = service
+        .set_message_private(conversation_id, message_id, private)
+        .await?;
+
+    Ok(Json(PrivateMessagesResponse {
+        conversation_id,
+        private_message_ids,
+    }))
+}
+
+/// Only the owner may choose the branch viewers see by default
+pub async fn set_published_branch(
+    State(service): State<Arc<ConversationService>>,
+    State(branch_service): State<Arc<BranchService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<PublishBranchRequest>,
+) -> Result<Json<ConversationResponse>, ApiError> {
+    let conversation = service.get_conversation(conversation_id).await?;
+    if conversation.created_by() != user.0 {
+        return Err(ApiError::Forbidden(
+            "Only the owner can publish a branch".to_string(),
+        ));
+    }
+    if let Some(branch_id) = payload.branch_id {
+        branch_service
+            .get_branch(conversation_id, branch_id)
+            .await?;
+    }
+
+    let conversation = service
+        .set_published_branch(conversation_id, payload.branch_id)
+        .await?;
+
+    Ok(Json(conversation_response(&conversation)?))
+}
+
+fn conversation_response(conversation: &Conversation) -> Result<ConversationResponse, ApiError> {
+    let metadata = conversation
+        .metadata()
+        .ok_or_else(|| ApiError::Internal("Invalid root message content".to_string()))?;
+
+    Ok(ConversationResponse {
+        conversation_id: conversation.conversation_id,
+        title: metadata.title.clone(),
+        description: metadata.description.clone(),
+        created_at: conversation.root_message.created_at,
+        created_by: conversation.root_message.created_by.clone(),
+        is_public: metadata.is_public,
+        fork_from_conversation_id: metadata.fork_from_conversation_id,
+        fork_from_message_id: metadata.fork_from_message_id,
+        language: metadata.language.clone(),
+        published_branch_id: metadata.published_branch_id,
+    })
+}
@@ -0,0 +1,71 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{BranchesDiff, DiffQuery, DiffResponse, MessagesDiff},
+    error::ApiError,
+};
+use crate::domain::Branch;
+use crate::middleware::AuthUser;
+use crate::services::{ConversationService, DiffService, ImageService};
+use std::sync::Arc;
+
+/// Messages and branches added, changed or removed between two instants
+pub async fn get_conversation_diff(
+    State(service): State<Arc<DiffService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<DiffResponse>, ApiError> {
+    let to = query.to.unwrap_or_else(Utc::now);
+    let mut private =
+        super::message::private_messages(&conv_service, conversation_id, viewer).await?;
+    let diff = service.diff(conversation_id, query.from, to).await?;
+    let leaf_ids = diff
+        .branches_added
+        .iter()
+        .chain(&diff.branches_changed)
+        .chain(&diff.branches_removed)
+        .map(|branch| branch.leaf_message_id)
+        .collect();
+    conv_service
+        .resolve_private(conversation_id, &mut private, leaf_ids)
+        .await?;
+
+    let presigned = |messages: Vec<_>| {
+        messages
+            .into_iter()
+            .filter_map(|message| private.redact(message))
+            .map(|message| images.presign(message).into())
+            .collect()
+    };
+    // Branches ending at a hidden message are left out like the message
+    let converted = |branches: Vec<Branch>| {
+        branches
+            .into_iter()
+            .filter(|branch| !private.hides_branch(branch))
+            .map(Into::into)
+            .collect()
+    };
+
+    Ok(Json(DiffResponse {
+        conversation_id,
+        from: diff.from,
+        to: diff.to,
+        messages: MessagesDiff {
+            added: presigned(diff.messages_added),
+            changed: presigned(diff.messages_changed),
+        },
+        branches: BranchesDiff {
+            added: converted(diff.branches_added),
+            changed: converted(diff.branches_changed),
+            removed: converted(diff.branches_removed),
+        },
+    }))
+}
@@ -0,0 +1,36 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use chrono::Utc;
+use uuid::Uuid;
+
+use super::share::ensure_can_manage;
+use crate::api::{
+    dto::{CreateEmbedTokenRequest, EmbedTokenResponse},
+    error::{ApiError, ApiJson},
+};
+use crate::middleware::{AuthUser, EmbedTokens};
+use crate::services::{ConversationService, ShareService};
+use std::sync::Arc;
+
+/// A token that lets a viewer read this conversation, and nothing else,
+/// until it expires
+pub async fn create_embed_token(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(tokens): State<Arc<EmbedTokens>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<CreateEmbedTokenRequest>,
+) -> Result<Json<EmbedTokenResponse>, ApiError> {
+    ensure_can_manage(&conv_service, &share_service, conversation_id, &user).await?;
+
+    let (token, expires_at) = tokens.issue(conversation_id, payload.ttl_secs, Utc::now())?;
+
+    Ok(Json(EmbedTokenResponse {
+        conversation_id,
+        token,
+        expires_at,
+    }))
+}
@@ -0,0 +1,23 @@
+use axum::{Json, extract::State};
+use std::sync::Arc;
+
+use crate::api::error::ApiError;
+use crate::config::AdminConfig;
+use crate::middleware::AuthUser;
+use crate::services::ConversationService;
+
+/// Re-wrap every conversation's data key under the active master key. Run
+/// after changing `encryption.active_key_id`, before retiring the old key.
+pub async fn rotate_encryption_keys(
+    State(service): State<Arc<ConversationService>>,
+    State(admin): State<Arc<AdminConfig>>,
+    user: AuthUser,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    user.ensure_admin(&admin)?;
+
+    let rewrapped = service.rewrap_data_keys().await?;
+
+    Ok(Json(serde_json::json!({
+        "rewrapped_keys": rewrapped
+    })))
+}
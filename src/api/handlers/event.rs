@@ -0,0 +1,54 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{EventResponse, EventsQuery, EventsResponse},
+    error::ApiError,
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::middleware::AuthUser;
+use crate::services::{ConversationService, PrivateMessages};
+use std::sync::Arc;
+
+pub async fn get_events(
+    State(service): State<Arc<ConversationService>>,
+    State(config): State<Arc<AppConfig>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<EventsQuery>,
+) -> Result<(PageSize, Json<EventsResponse>), ApiError> {
+    let from_seq = query.from_seq.unwrap_or(0);
+    let page = PageSize::new(&config, query.limit);
+    let mut private = super::message::private_messages(&service, conversation_id, viewer).await?;
+
+    let events = service
+        .get_events(conversation_id, from_seq, page.limit as i32)
+        .await?;
+    let named = events
+        .iter()
+        .flat_map(PrivateMessages::named_by_event)
+        .collect();
+    service
+        .resolve_private(conversation_id, &mut private, named)
+        .await?;
+
+    let next_seq = events.last().map_or(from_seq, |event| event.seq + 1);
+    let events: Vec<EventResponse> = events
+        .into_iter()
+        .filter_map(|event| private.redact_event(event))
+        .map(Into::into)
+        .collect();
+
+    Ok((
+        page,
+        Json(EventsResponse {
+            conversation_id,
+            events,
+            next_seq,
+        }),
+    ))
+}
@@ -0,0 +1,40 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+
+use crate::api::{
+    dto::{TrendingConversationResponse, TrendingQuery},
+    error::ApiError,
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::services::TrendingService;
+use std::sync::Arc;
+
+pub async fn get_trending(
+    State(service): State<Arc<TrendingService>>,
+    State(config): State<Arc<AppConfig>>,
+    Query(query): Query<TrendingQuery>,
+) -> Result<(PageSize, Json<Vec<TrendingConversationResponse>>), ApiError> {
+    let page = PageSize::new(&config, query.limit);
+    let trending = service
+        .get_trending(page.limit, query.lang.as_deref())
+        .await?;
+
+    let responses = trending
+        .into_iter()
+        .map(|entry| TrendingConversationResponse {
+            rank: entry.rank + 1,
+            conversation_id: entry.conversation_id,
+            title: entry.title,
+            score: entry.score,
+            view_count: entry.view_count,
+            fork_count: entry.fork_count,
+            computed_at: entry.computed_at,
+            language: entry.language,
+        })
+        .collect();
+
+    Ok((page, Json(responses)))
+}
@@ -0,0 +1,166 @@
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query, State},
+    http::{StatusCode, header},
+    response::IntoResponse,
+};
+use futures::StreamExt;
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{CreateExportRequest, ExportListQuery, ExportQuery, ExportResponse, MessageResponse},
+    error::{ApiError, ApiJson},
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::db::DbError;
+use crate::domain::JobKind;
+use crate::middleware::AuthUser;
+use crate::services::{
+    ConversationExportParams, ExportFormat, ExportService, ImageService, JobService,
+};
+use std::sync::Arc;
+
+/// The conversation as a file, without the messages the viewer may not read
+pub async fn export_conversation(
+    State(service): State<Arc<ExportService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<ExportQuery>,
+) -> Result<impl IntoResponse, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    ensure_can_export_shares(
+        &service,
+        conversation_id,
+        viewer.as_deref(),
+        query.include_shares,
+    )
+    .await?;
+    let body = if query.format.is_streamed() {
+        let messages = service
+            .stream_messages(conversation_id, viewer.as_deref())
+            .await?;
+
+        // One JSON document per line, written as rows arrive from each page
+        let lines = messages.map(move |message| {
+            let response = MessageResponse::from(images.presign(message?));
+            let mut line = serde_json::to_vec(&response)
+                .map_err(|e| DbError::SerializationError(e.to_string()))?;
+            line.push(b'\n');
+            Ok::<_, DbError>(line)
+        });
+
+        Body::from_stream(lines)
+    } else if query.format == ExportFormat::Native {
+        let export = service
+            .export_native(conversation_id, viewer.as_deref(), query.include_shares)
+            .await?;
+        let body =
+            serde_json::to_vec(&export).map_err(|e| DbError::SerializationError(e.to_string()))?;
+
+        Body::from(body)
+    } else {
+        let transcript = service
+            .export_transcript(
+                conversation_id,
+                query.branch_id,
+                query.leaf_message_id,
+                query.format,
+                viewer.as_deref(),
+            )
+            .await?;
+
+        Body::from(transcript)
+    };
+
+    let disposition = format!(
+        "inline; filename=\"conversation-{}.{}\"",
+        conversation_id,
+        query.format.file_extension()
+    );
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                query.format.content_type().to_string(),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    ))
+}
+
+/// Start writing an export to the object store; it is listed with the
+/// conversation's exports, with a download link once complete
+pub async fn create_export(
+    State(service): State<Arc<ExportService>>,
+    State(jobs): State<Arc<JobService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(req): ApiJson<CreateExportRequest>,
+) -> Result<(StatusCode, Json<ExportResponse>), ApiError> {
+    service.ensure_exportable(conversation_id).await?;
+    ensure_can_export_shares(&service, conversation_id, Some(&user.0), req.include_shares).await?;
+
+    let params = serde_json::to_value(ConversationExportParams {
+        conversation_id,
+        format: req.format,
+        branch_id: req.branch_id,
+        leaf_message_id: req.leaf_message_id,
+        include_shares: req.include_shares,
+    })
+    .map_err(|e| DbError::SerializationError(e.to_string()))?;
+    let job = jobs
+        .start(JobKind::ConversationExport, user.0, params)
+        .await?;
+    let export = service.record_export(&job).await?;
+
+    Ok((StatusCode::ACCEPTED, Json(export.into())))
+}
+
+/// A conversation's exports, newest first
+pub async fn get_exports(
+    State(service): State<Arc<ExportService>>,
+    State(config): State<Arc<AppConfig>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<ExportListQuery>,
+) -> Result<(PageSize, Json<Vec<ExportResponse>>), ApiError> {
+    let page = PageSize::new(&config, query.limit);
+
+    let responses = service
+        .get_exports(conversation_id, page.limit)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok((page, Json(responses)))
+}
+
+pub async fn get_export(
+    State(service): State<Arc<ExportService>>,
+    Path((conversation_id, export_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ExportResponse>, ApiError> {
+    let export = service.get_export(conversation_id, export_id).await?;
+
+    Ok(Json(export.into()))
+}
+
+/// Whom a conversation is shared with is for its owner to export
+async fn ensure_can_export_shares(
+    service: &ExportService,
+    conversation_id: Uuid,
+    viewer: Option<&str>,
+    include_shares: bool,
+) -> Result<(), ApiError> {
+    if include_shares && !service.is_owner(conversation_id, viewer).await? {
+        return Err(ApiError::Forbidden(
+            "Only the owner can export shares".to_string(),
+        ));
+    }
+
+    Ok(())
+}
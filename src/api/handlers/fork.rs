@@ -5,21 +5,27 @@ use axum::{
 use uuid::Uuid;
 
 use crate::api::{
-    dto::{ConversationResponse, ForkConversationRequest},
-    error::ApiError,
+    dto::{ConversationResponse, ForkConversationRequest, ForkGraphResponse},
+    error::{ApiError, ApiJson},
 };
 use crate::domain::ContentType;
-use crate::services::ForkService;
+use crate::middleware::AuthUser;
+use crate::services::{ForkOptions, ForkService, TrendingService};
 use std::sync::Arc;
 
 pub async fn fork_conversation(
     State(service): State<Arc<ForkService>>,
+    State(trending_service): State<Arc<TrendingService>>,
     Path(conversation_id): Path<Uuid>,
-    Json(payload): Json<ForkConversationRequest>,
+    user: AuthUser,
+    ApiJson(payload): ApiJson<ForkConversationRequest>,
 ) -> Result<Json<ConversationResponse>, ApiError> {
+    let options = ForkOptions::from(&payload);
+    let created_by = user.attribute("created_by", payload.created_by)?;
     let conversation = service
-        .fork_conversation(conversation_id, payload.title, payload.created_by)
+        .fork_conversation(conversation_id, payload.title, created_by, options)
         .await?;
+    trending_service.record_fork(conversation_id).await;
 
     let response = match &conversation.root_message.content {
         ContentType::Metadata(metadata) => ConversationResponse {
@@ -31,6 +37,8 @@ pub async fn fork_conversation(
             is_public: metadata.is_public,
             fork_from_conversation_id: metadata.fork_from_conversation_id,
             fork_from_message_id: metadata.fork_from_message_id,
+            language: metadata.language.clone(),
+            published_branch_id: metadata.published_branch_id,
         },
         _ => {
             return Err(ApiError::Internal(
@@ -44,17 +52,23 @@ pub async fn fork_conversation(
 
 pub async fn fork_branch(
     State(service): State<Arc<ForkService>>,
+    State(trending_service): State<Arc<TrendingService>>,
     Path((conversation_id, branch_id)): Path<(Uuid, Uuid)>,
-    Json(payload): Json<ForkConversationRequest>,
+    user: AuthUser,
+    ApiJson(payload): ApiJson<ForkConversationRequest>,
 ) -> Result<Json<ConversationResponse>, ApiError> {
+    let options = ForkOptions::from(&payload);
+    let created_by = user.attribute("created_by", payload.created_by)?;
     let conversation = service
         .fork_branch(
             conversation_id,
             branch_id,
             payload.title,
-            payload.created_by,
+            created_by,
+            options,
         )
         .await?;
+    trending_service.record_fork(conversation_id).await;
 
     let response = match &conversation.root_message.content {
         ContentType::Metadata(metadata) => ConversationResponse {
@@ -66,6 +80,8 @@ pub async fn fork_branch(
             is_public: metadata.is_public,
             fork_from_conversation_id: metadata.fork_from_conversation_id,
             fork_from_message_id: metadata.fork_from_message_id,
+            language: metadata.language.clone(),
+            published_branch_id: metadata.published_branch_id,
         },
         _ => {
             return Err(ApiError::Internal(
@@ -79,17 +95,23 @@ pub async fn fork_branch(
 
 pub async fn fork_from_message(
     State(service): State<Arc<ForkService>>,
+    State(trending_service): State<Arc<TrendingService>>,
     Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
-    Json(payload): Json<ForkConversationRequest>,
+    user: AuthUser,
+    ApiJson(payload): ApiJson<ForkConversationRequest>,
 ) -> Result<Json<ConversationResponse>, ApiError> {
+    let options = ForkOptions::from(&payload);
+    let created_by = user.attribute("created_by", payload.created_by)?;
     let conversation = service
         .fork_from_message(
             conversation_id,
             message_id,
             payload.title,
-            payload.created_by,
+            created_by,
+            options,
         )
         .await?;
+    trending_service.record_fork(conversation_id).await;
 
     let response = match &conversation.root_message.content {
         ContentType::Metadata(metadata) => ConversationResponse {
@@ -101,6 +123,8 @@ pub async fn fork_from_message(
             is_public: metadata.is_public,
             fork_from_conversation_id: metadata.fork_from_conversation_id,
             fork_from_message_id: metadata.fork_from_message_id,
+            language: metadata.language.clone(),
+            published_branch_id: metadata.published_branch_id,
         },
         _ => {
             return Err(ApiError::Internal(
@@ -111,3 +135,12 @@ pub async fn fork_from_message(
 
     Ok(Json(response))
 }
+
+/// The fork family of a conversation: its ancestors and every fork of its original
+pub async fn get_fork_graph(
+    State(service): State<Arc<ForkService>>,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<ForkGraphResponse>, ApiError> {
+    let graph = service.get_fork_graph(conversation_id).await?;
+    Ok(Json(graph.into()))
+}
@@ -0,0 +1,52 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+
+use crate::api::{
+    dto::{ImportQuery, ImportResponse, ImportedConversationResponse},
+    error::{ApiError, ApiJson},
+};
+use crate::domain::NativeExport;
+use crate::middleware::AuthUser;
+use crate::services::ImportService;
+use crate::utils::chatgpt::{ChatGptConversation, ImportedConversation};
+use std::sync::Arc;
+
+/// Import the `conversations.json` file of a ChatGPT data export
+pub async fn import_chatgpt(
+    State(service): State<Arc<ImportService>>,
+    user: AuthUser,
+    Query(query): Query<ImportQuery>,
+    ApiJson(payload): ApiJson<Vec<ChatGptConversation>>,
+) -> Result<Json<ImportResponse>, ApiError> {
+    let created_by = user.attribute("created_by", query.created_by)?;
+    let imported = service
+        .import_chatgpt(&payload, created_by, query.dedup)
+        .await?;
+
+    let conversations = imported.iter().map(imported_response).collect();
+
+    Ok(Json(ImportResponse { conversations }))
+}
+
+/// Import a conversation exported in the native format, as a new
+/// conversation owned by the caller
+pub async fn import_native(
+    State(service): State<Arc<ImportService>>,
+    user: AuthUser,
+    ApiJson(payload): ApiJson<NativeExport>,
+) -> Result<Json<ImportedConversationResponse>, ApiError> {
+    let imported = service.import_native(&payload, user.0).await?;
+
+    Ok(Json(imported_response(&imported)))
+}
+
+fn imported_response(imported: &ImportedConversation) -> ImportedConversationResponse {
+    ImportedConversationResponse {
+        conversation_id: imported.conversation.conversation_id,
+        title: imported.conversation.title().unwrap_or_default(),
+        message_count: imported.messages.len(),
+        branch_ids: imported.branches.iter().map(|b| b.branch_id).collect(),
+    }
+}
@@ -0,0 +1,67 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{JobResponse, UserJobsQuery},
+    error::ApiError,
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::domain::Job;
+use crate::middleware::AuthUser;
+use crate::services::JobService;
+use std::sync::Arc;
+
+pub async fn get_job(
+    State(jobs): State<Arc<JobService>>,
+    user: AuthUser,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<JobResponse>, ApiError> {
+    let job = owned_job(&jobs, &user, job_id).await?;
+
+    Ok(Json(job.into()))
+}
+
+/// A user's jobs, newest first
+pub async fn get_user_jobs(
+    State(jobs): State<Arc<JobService>>,
+    State(config): State<Arc<AppConfig>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    Query(query): Query<UserJobsQuery>,
+) -> Result<(PageSize, Json<Vec<JobResponse>>), ApiError> {
+    user.ensure_is(&user_id)?;
+    let page = PageSize::new(&config, query.limit);
+
+    let responses = jobs
+        .get_jobs_for_user(&user_id, page.limit)
+        .await?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    Ok((page, Json(responses)))
+}
+
+/// Ask a running job to stop
+pub async fn cancel_job(
+    State(jobs): State<Arc<JobService>>,
+    user: AuthUser,
+    Path(job_id): Path<Uuid>,
+) -> Result<Json<JobResponse>, ApiError> {
+    owned_job(&jobs, &user, job_id).await?;
+    let job = jobs.cancel(job_id).await?;
+
+    Ok(Json(job.into()))
+}
+
+/// The job, if the caller started it
+async fn owned_job(jobs: &JobService, user: &AuthUser, job_id: Uuid) -> Result<Job, ApiError> {
+    let job = jobs.get_job(job_id).await?;
+    user.ensure_is(&job.user_id)?;
+
+    Ok(job)
+}
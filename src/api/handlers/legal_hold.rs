@@ -0,0 +1,60 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{LegalHoldResponse, PlaceLegalHoldRequest},
+    error::ApiError,
+};
+use crate::config::AdminConfig;
+use crate::middleware::AuthUser;
+use crate::services::LegalHoldService;
+use std::sync::Arc;
+
+/// Keep a conversation from being deleted or expiring, e.g. for litigation
+pub async fn place_legal_hold(
+    State(service): State<Arc<LegalHoldService>>,
+    State(admin): State<Arc<AdminConfig>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    payload: Option<Json<PlaceLegalHoldRequest>>,
+) -> Result<Json<LegalHoldResponse>, ApiError> {
+    user.ensure_admin(&admin)?;
+    let Json(payload) = payload.unwrap_or_default();
+
+    let hold = service
+        .place_hold(conversation_id, user.0, payload.reason)
+        .await?;
+
+    Ok(Json(hold.into()))
+}
+
+pub async fn release_legal_hold(
+    State(service): State<Arc<LegalHoldService>>,
+    State(admin): State<Arc<AdminConfig>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    user.ensure_admin(&admin)?;
+
+    service.release_hold(conversation_id).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Legal hold released"
+    })))
+}
+
+/// Every conversation under legal hold, most recent hold first
+pub async fn get_legal_holds(
+    State(service): State<Arc<LegalHoldService>>,
+    State(admin): State<Arc<AdminConfig>>,
+    user: AuthUser,
+) -> Result<Json<Vec<LegalHoldResponse>>, ApiError> {
+    user.ensure_admin(&admin)?;
+
+    let holds = service.get_holds().await?;
+
+    Ok(Json(holds.into_iter().map(Into::into).collect()))
+}
@@ -1,74 +1,223 @@
 use axum::{
-    Json,
-    extract::{Path, State},
+    Extension, Json,
+    extract::{Path, Query, State},
 };
 use uuid::Uuid;
 
+use super::share::ensure_can_write;
 use crate::api::{
-    dto::{CreateMessageRequest, MessageResponse, parse_role},
-    error::ApiError,
+    dto::{
+        AncestorsQuery, AncestorsResponse, CreateMessageRequest, MessageResponse,
+        MoveMessageRequest,
+    },
+    error::{ApiError, ApiJson},
+};
+use crate::db::DbError;
+use crate::middleware::AuthUser;
+use crate::services::{
+    BranchService, ConversationMailboxes, ConversationService, ImageService, PrivateMessages,
 };
-use crate::services::{BranchService, ConversationService};
 use std::sync::Arc;
 
+/// Ancestors returned when `last` is omitted
+const DEFAULT_ANCESTORS: usize = 20;
+
+/// Most ancestors returned at once; use the lineage endpoint for full paths
+const MAX_ANCESTORS: usize = 500;
+
 pub async fn create_message(
     State(conv_service): State<Arc<ConversationService>>,
     State(branch_service): State<Arc<BranchService>>,
+    State(images): State<Arc<ImageService>>,
+    State(mailboxes): State<Arc<ConversationMailboxes>>,
+    user: AuthUser,
     Path(conversation_id): Path<Uuid>,
-    Json(payload): Json<CreateMessageRequest>,
+    ApiJson(payload): ApiJson<CreateMessageRequest>,
 ) -> Result<Json<MessageResponse>, ApiError> {
-    let role = parse_role(&payload.role).map_err(ApiError::BadRequest)?;
-
-    let message = conv_service
-        .append_message(
-            conversation_id,
-            payload.parent_message_id,
-            role,
-            payload.content,
-            payload.content_metadata,
-            payload.created_by,
-        )
-        .await?;
+    ensure_can_write(&conv_service, conversation_id, &user).await?;
+    let created_by = user.attribute("created_by", payload.created_by)?;
+    let message = mailboxes
+        .run(conversation_id, async move {
+            let message = conv_service
+                .append_message(
+                    conversation_id,
+                    payload.parent_message_id,
+                    payload.role,
+                    payload.content,
+                    payload.content_metadata,
+                    created_by,
+                )
+                .await?;
 
-    // If a branch_id is provided, extend the branch
-    if let Some(branch_id) = payload.branch_id {
-        branch_service
-            .extend_branch_with_message(conversation_id, branch_id, message.message_id)
-            .await?;
-    }
+            // If a branch_id is provided, extend the branch
+            if let Some(branch_id) = payload.branch_id {
+                branch_service
+                    .extend_branch_with_message(conversation_id, branch_id, message.message_id)
+                    .await?;
+            }
 
-    Ok(Json(message.into()))
+            Ok::<_, DbError>(message)
+        })
+        .await?;
+
+    Ok(Json(images.presign(message).into()))
 }
 
 pub async fn get_message(
     State(service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<MessageResponse>, ApiError> {
+    let private = private_messages(&service, conversation_id, viewer).await?;
     let message = service.get_message(conversation_id, message_id).await?;
+    let message = private.redact(message).ok_or_else(not_found)?;
 
-    Ok(Json(message.into()))
+    Ok(Json(images.presign(message).into()))
 }
 
 pub async fn get_message_children(
     State(service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let private = private_messages(&service, conversation_id, viewer).await?;
     let children = service.get_children(conversation_id, message_id).await?;
 
-    let responses = children.into_iter().map(Into::into).collect();
+    let responses = children
+        .into_iter()
+        .filter_map(|message| private.redact(message))
+        .map(|message| images.presign(message).into())
+        .collect();
 
     Ok(Json(responses))
 }
 
 pub async fn get_message_lineage(
     State(service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
     Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let private = private_messages(&service, conversation_id, viewer).await?;
     let lineage = service
         .get_lineage_path(conversation_id, message_id)
         .await?;
+    // A hidden message hides everything below it, so only the last can be
+    if lineage.last().is_some_and(|message| private.hides(message)) {
+        return Err(not_found());
+    }
+
+    let responses = lineage
+        .into_iter()
+        .filter_map(|message| private.redact(message))
+        .map(|message| images.presign(message).into())
+        .collect();
+
+    Ok(Json(responses))
+}
+
+/// The nearest ancestors of a message, for bounded context windows and
+/// breadcrumbs in deep trees
+pub async fn get_message_ancestors(
+    State(service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<AncestorsQuery>,
+) -> Result<Json<AncestorsResponse>, ApiError> {
+    let last = query.last.unwrap_or(DEFAULT_ANCESTORS);
+    if last > MAX_ANCESTORS {
+        return Err(ApiError::BadRequest(format!(
+            "last must be at most {}",
+            MAX_ANCESTORS
+        )));
+    }
+
+    let private = private_messages(&service, conversation_id, viewer).await?;
+    let message = service.get_message(conversation_id, message_id).await?;
+    if private.hides(&message) {
+        return Err(not_found());
+    }
+
+    let (ancestors, total_ancestors) = service
+        .get_ancestors(conversation_id, message_id, last)
+        .await?;
 
-    let responses = lineage.into_iter().map(Into::into).collect();
+    Ok(Json(AncestorsResponse {
+        conversation_id,
+        message_id,
+        total_ancestors,
+        ancestors: ancestors
+            .into_iter()
+            .filter_map(|message| private.redact(message))
+            .map(|message| images.presign(message).into())
+            .collect(),
+    }))
+}
+
+/// Move a message and its replies below another message. The response
+/// leaves out what the caller may not see of the moved subtree.
+pub async fn move_message(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(branch_service): State<Arc<BranchService>>,
+    State(images): State<Arc<ImageService>>,
+    State(mailboxes): State<Arc<ConversationMailboxes>>,
+    user: AuthUser,
+    Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
+    ApiJson(payload): ApiJson<MoveMessageRequest>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    ensure_can_write(&conv_service, conversation_id, &user).await?;
+    let private = conv_service
+        .private_messages(conversation_id, Some(&user.0))
+        .await?;
+    // Writers can only move what they can see, and only below it
+    for id in [message_id, payload.new_parent_message_id] {
+        let message = conv_service.get_message(conversation_id, id).await?;
+        if private.hides(&message) {
+            return Err(not_found());
+        }
+    }
+
+    let moved = mailboxes
+        .run(conversation_id, async move {
+            let moved = conv_service
+                .move_message(conversation_id, message_id, payload.new_parent_message_id)
+                .await?;
+
+            // Branches ending inside the moved subtree now follow a different path
+            let moved_ids: Vec<Uuid> = moved.iter().map(|m| m.message_id).collect();
+            branch_service
+                .touch_branches_with_leaves(conversation_id, &moved_ids)
+                .await?;
+
+            Ok::<_, DbError>(moved)
+        })
+        .await?;
+
+    let responses = moved
+        .into_iter()
+        .filter_map(|message| private.redact(message))
+        .map(|message| images.presign(message).into())
+        .collect();
 
     Ok(Json(responses))
 }
+
+/// What the viewer may not see of the conversation
+pub(super) async fn private_messages(
+    service: &ConversationService,
+    conversation_id: Uuid,
+    viewer: Option<Extension<AuthUser>>,
+) -> Result<PrivateMessages, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    Ok(service
+        .private_messages(conversation_id, viewer.as_deref())
+        .await?)
+}
+
+/// What a message hidden from the viewer looks like to them
+pub(super) fn not_found() -> ApiError {
+    ApiError::NotFound("Message not found".to_string())
+}
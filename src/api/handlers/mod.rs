@@ -1,11 +1,51 @@
+pub mod access_log;
+pub mod analytics;
 pub mod branch;
+pub mod change;
+pub mod checkpoint;
+pub mod cleanup;
+pub mod collaboration;
+pub mod context;
 pub mod conversation;
+pub mod diff;
+pub mod embed;
+pub mod encryption;
+pub mod event;
+pub mod explore;
+pub mod export;
 pub mod fork;
+pub mod import;
+pub mod job;
+pub mod legal_hold;
 pub mod message;
+pub mod notification;
+pub mod preference;
+pub mod search;
 pub mod share;
+pub mod webhook;
 
+pub use access_log::*;
+pub use analytics::*;
 pub use branch::*;
+pub use change::*;
+pub use checkpoint::*;
+pub use cleanup::*;
+pub use collaboration::*;
+pub use context::*;
 pub use conversation::*;
+pub use diff::*;
+pub use embed::*;
+pub use encryption::*;
+pub use event::*;
+pub use explore::*;
+pub use export::*;
 pub use fork::*;
+pub use import::*;
+pub use job::*;
+pub use legal_hold::*;
 pub use message::*;
+pub use notification::*;
+pub use preference::*;
+pub use search::*;
 pub use share::*;
+pub use webhook::*;
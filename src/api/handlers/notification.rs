@@ -0,0 +1,56 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+
+use crate::api::{
+    dto::{MarkNotificationsReadRequest, NotificationResponse, NotificationsQuery},
+    error::ApiError,
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::middleware::AuthUser;
+use crate::services::{NotificationService, PreferenceService};
+use std::sync::Arc;
+
+pub async fn get_notifications(
+    State(service): State<Arc<NotificationService>>,
+    State(preferences): State<Arc<PreferenceService>>,
+    State(config): State<Arc<AppConfig>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    Query(query): Query<NotificationsQuery>,
+) -> Result<(PageSize, Json<Vec<NotificationResponse>>), ApiError> {
+    user.ensure_is(&user_id)?;
+    let preferences = preferences.get(&user_id).await?;
+    let page = PageSize::new(&config, query.limit.or(preferences.default_page_size));
+    let unread_only = query.unread_only.unwrap_or(false);
+
+    let notifications = service
+        .get_notifications(&user_id, page.limit as i32)
+        .await?
+        .into_iter()
+        .filter(|(_, read)| !unread_only || !read)
+        .map(|(notification, read)| NotificationResponse::new(notification, read))
+        .collect();
+
+    Ok((page, Json(notifications)))
+}
+
+pub async fn mark_notifications_read(
+    State(service): State<Arc<NotificationService>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    payload: Option<Json<MarkNotificationsReadRequest>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    user.ensure_is(&user_id)?;
+    let Json(payload) = payload.unwrap_or_default();
+    let up_to = payload.up_to.unwrap_or_else(chrono::Utc::now);
+
+    service.mark_read(&user_id, up_to).await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Notifications marked as read",
+        "read_up_to": up_to,
+    })))
+}
@@ -0,0 +1,66 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+
+use crate::api::{
+    dto::{PreferencesResponse, UpdatePreferencesRequest},
+    error::{ApiError, ApiJson},
+};
+use crate::middleware::AuthUser;
+use crate::services::PreferenceService;
+use std::sync::Arc;
+
+use super::share::MAX_SHARE_BATCH;
+
+pub async fn get_preferences(
+    State(service): State<Arc<PreferenceService>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+) -> Result<Json<PreferencesResponse>, ApiError> {
+    user.ensure_is(&user_id)?;
+    let preferences = service.get(&user_id).await?;
+
+    Ok(Json(preferences.into()))
+}
+
+/// Change the user's preferences; fields left out keep their current values
+pub async fn update_preferences(
+    State(service): State<Arc<PreferenceService>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    ApiJson(payload): ApiJson<UpdatePreferencesRequest>,
+) -> Result<Json<PreferencesResponse>, ApiError> {
+    user.ensure_is(&user_id)?;
+    let mut preferences = service.get(&user_id).await?;
+
+    if let Some(default_shares) = payload.default_shares {
+        if default_shares.len() > MAX_SHARE_BATCH {
+            return Err(ApiError::BadRequest(format!(
+                "At most {} default shares are allowed",
+                MAX_SHARE_BATCH
+            )));
+        }
+        preferences.default_shares = default_shares;
+    }
+    if let Some(allow_public_forks) = payload.allow_public_forks {
+        preferences.allow_public_forks = allow_public_forks;
+    }
+    if let Some(default_page_size) = payload.default_page_size {
+        preferences.default_page_size = Some(default_page_size);
+    }
+    if let Some(branch_naming) = payload.branch_naming {
+        preferences.branch_naming = Some(branch_naming);
+    }
+    for (kind, enabled) in payload.notifications {
+        if enabled {
+            preferences.muted_notifications.remove(&kind);
+        } else {
+            preferences.muted_notifications.insert(kind);
+        }
+    }
+
+    let preferences = service.save(preferences).await?;
+
+    Ok(Json(preferences.into()))
+}
@@ -0,0 +1,47 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{MessageSearchQuery, MessageSearchResponse},
+    error::ApiError,
+    pagination::PageSize,
+};
+use crate::config::AppConfig;
+use crate::middleware::AuthUser;
+use crate::services::{ConversationService, SearchService};
+use std::sync::Arc;
+
+/// Messages of one conversation containing a phrase, with highlighted snippets
+pub async fn search_conversation(
+    State(service): State<Arc<SearchService>>,
+    State(conv_service): State<Arc<ConversationService>>,
+    State(config): State<Arc<AppConfig>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<MessageSearchQuery>,
+) -> Result<(PageSize, Json<MessageSearchResponse>), ApiError> {
+    let page = PageSize::new(&config, query.limit);
+    let private = super::message::private_messages(&conv_service, conversation_id, viewer).await?;
+    let results = service
+        .search(
+            conversation_id,
+            &query.q,
+            query.lang.as_deref(),
+            &private,
+            page.limit,
+        )
+        .await?;
+
+    Ok((
+        page,
+        Json(MessageSearchResponse {
+            conversation_id,
+            query: query.q.trim().to_string(),
+            matches: results.matches.into_iter().map(Into::into).collect(),
+            total: results.total,
+        }),
+    ))
+}
@@ -1,65 +1,217 @@
 use axum::{
     Json,
-    extract::{Path, State},
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response},
 };
 use uuid::Uuid;
 
 use crate::api::{
-    dto::{ShareConversationRequest, ShareResponse, parse_permission},
-    error::ApiError,
+    dto::{
+        AcceptInviteRequest, BatchShareRequest, BatchShareResponse, ConversationMatchResponse,
+        CreateInviteRequest, InviteResponse, ShareConversationRequest, ShareResponse,
+        SharedWithMeQuery, UserConversationsQuery, parse_email,
+    },
+    error::{ApiError, ApiJson},
+    pagination::PageSize,
 };
-use crate::services::ShareService;
+use crate::config::AppConfig;
+use crate::domain::Permission;
+use crate::middleware::AuthUser;
+use crate::services::{ConversationService, PreferenceService, ShareService};
 use std::sync::Arc;
 
+/// Most users a single batch share request may touch
+pub(crate) const MAX_SHARE_BATCH: usize = 100;
+
+/// Only the conversation owner (its creator) or users with an admin share may
+/// update, delete or share a conversation
+pub(crate) async fn ensure_can_manage(
+    conv_service: &ConversationService,
+    share_service: &ShareService,
+    conversation_id: Uuid,
+    user: &AuthUser,
+) -> Result<(), ApiError> {
+    let conversation = conv_service.get_conversation(conversation_id).await?;
+    if conversation.root_message.created_by == user.0
+        || share_service
+            .check_permission(conversation_id, &user.0, Permission::Admin)
+            .await?
+    {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Only the owner or an admin can manage this conversation".to_string(),
+        ))
+    }
+}
+
+/// Only the owner, users with a share and, for public conversations, anyone
+/// may take part in the conversation
+pub(crate) async fn ensure_can_read(
+    conv_service: &ConversationService,
+    conversation_id: Uuid,
+    user: &AuthUser,
+) -> Result<(), ApiError> {
+    let conversation = conv_service.get_conversation(conversation_id).await?;
+    if conv_service.can_read(&conversation, &user.0).await? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Only the owner or users it is shared with can see this conversation".to_string(),
+        ))
+    }
+}
+
+/// Only the owner or users with a branch share, which admin shares include,
+/// may change the conversation's messages and branches
+pub(crate) async fn ensure_can_write(
+    conv_service: &ConversationService,
+    conversation_id: Uuid,
+    user: &AuthUser,
+) -> Result<(), ApiError> {
+    let conversation = conv_service.get_conversation(conversation_id).await?;
+    if conv_service.can_write(&conversation, &user.0).await? {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden(
+            "Only the owner or users with a branch share can change this conversation".to_string(),
+        ))
+    }
+}
+
 pub async fn share_conversation(
+    State(conv_service): State<Arc<ConversationService>>,
     State(service): State<Arc<ShareService>>,
+    user: AuthUser,
     Path(conversation_id): Path<Uuid>,
-    Json(payload): Json<ShareConversationRequest>,
+    ApiJson(payload): ApiJson<ShareConversationRequest>,
 ) -> Result<Json<ShareResponse>, ApiError> {
-    let permission = parse_permission(&payload.permission).map_err(ApiError::BadRequest)?;
+    ensure_can_manage(&conv_service, &service, conversation_id, &user).await?;
+    let shared_by = user.attribute("shared_by", payload.shared_by)?;
 
     let share = service
         .share_conversation(
             conversation_id,
             payload.shared_with,
-            permission,
-            payload.shared_by,
+            payload.permission,
+            shared_by,
         )
         .await?;
 
-    Ok(Json(ShareResponse {
-        conversation_id: share.conversation_id,
-        shared_with: share.shared_with,
-        permission: share.permission.as_str().to_string(),
-        shared_at: share.shared_at,
-        shared_by: share.shared_by,
+    Ok(Json(ShareResponse::from(share)))
+}
+
+pub async fn batch_update_shares(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(service): State<Arc<ShareService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<BatchShareRequest>,
+) -> Result<Json<BatchShareResponse>, ApiError> {
+    if payload.grant.len() + payload.revoke.len() > MAX_SHARE_BATCH {
+        return Err(ApiError::BadRequest(format!(
+            "At most {} users can be updated at once",
+            MAX_SHARE_BATCH
+        )));
+    }
+    let grants = payload
+        .grant
+        .into_iter()
+        .map(|grant| (grant.shared_with, grant.permission))
+        .collect();
+    ensure_can_manage(&conv_service, &service, conversation_id, &user).await?;
+
+    let (granted, revoked) = service
+        .batch_update_shares(conversation_id, grants, payload.revoke, user.0)
+        .await?;
+
+    Ok(Json(BatchShareResponse {
+        granted: granted.into_iter().map(ShareResponse::from).collect(),
+        revoked,
     }))
 }
 
+pub async fn get_shared_with_me(
+    State(service): State<Arc<ShareService>>,
+    State(preferences): State<Arc<PreferenceService>>,
+    State(config): State<Arc<AppConfig>>,
+    user: AuthUser,
+    Path(user_id): Path<String>,
+    Query(query): Query<SharedWithMeQuery>,
+) -> Result<(PageSize, Json<Vec<ShareResponse>>), ApiError> {
+    user.ensure_is(&user_id)?;
+    let preferences = preferences.get(&user_id).await?;
+    let page = PageSize::new(&config, query.limit.or(preferences.default_page_size));
+
+    let shares = service.get_shared_with_user(&user_id, page.limit).await?;
+
+    Ok((
+        page,
+        Json(shares.into_iter().map(ShareResponse::from).collect()),
+    ))
+}
+
+pub async fn create_invite(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(service): State<Arc<ShareService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<CreateInviteRequest>,
+) -> Result<Json<InviteResponse>, ApiError> {
+    let email = parse_email(&payload.email).map_err(ApiError::BadRequest)?;
+    ensure_can_manage(&conv_service, &service, conversation_id, &user).await?;
+
+    let invite = service
+        .create_invite(conversation_id, email, payload.permission, user.0)
+        .await?;
+
+    Ok(Json(InviteResponse {
+        conversation_id: invite.conversation_id,
+        email: invite.email,
+        token: invite.token,
+        permission: invite.permission.as_str().to_string(),
+        invited_by: invite.invited_by,
+        created_at: invite.created_at,
+        expires_at: invite.expires_at,
+    }))
+}
+
+pub async fn accept_invite(
+    State(service): State<Arc<ShareService>>,
+    user: AuthUser,
+    ApiJson(payload): ApiJson<AcceptInviteRequest>,
+) -> Result<Json<ShareResponse>, ApiError> {
+    let email = parse_email(&payload.email).map_err(ApiError::BadRequest)?;
+
+    let share = service
+        .accept_invite(&email, &payload.token, user.0)
+        .await
+        .map_err(|e| match ApiError::from(e) {
+            ApiError::NotFound(_) => ApiError::NotFound("Invite not found or expired".to_string()),
+            e => e,
+        })?;
+
+    Ok(Json(ShareResponse::from(share)))
+}
+
 pub async fn get_shares(
     State(service): State<Arc<ShareService>>,
     Path(conversation_id): Path<Uuid>,
 ) -> Result<Json<Vec<ShareResponse>>, ApiError> {
     let shares = service.get_conversation_shares(conversation_id).await?;
 
-    let responses = shares
-        .into_iter()
-        .map(|share| ShareResponse {
-            conversation_id: share.conversation_id,
-            shared_with: share.shared_with,
-            permission: share.permission.as_str().to_string(),
-            shared_at: share.shared_at,
-            shared_by: share.shared_by,
-        })
-        .collect();
+    let responses = shares.into_iter().map(ShareResponse::from).collect();
 
     Ok(Json(responses))
 }
 
 pub async fn revoke_share(
+    State(conv_service): State<Arc<ConversationService>>,
     State(service): State<Arc<ShareService>>,
+    user: AuthUser,
     Path((conversation_id, user_id)): Path<(Uuid, String)>,
 ) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_can_manage(&conv_service, &service, conversation_id, &user).await?;
     service.revoke_share(conversation_id, &user_id).await?;
 
     Ok(Json(serde_json::json!({
@@ -68,10 +220,33 @@ pub async fn revoke_share(
 }
 
 pub async fn get_user_conversations(
+    State(conv_service): State<Arc<ConversationService>>,
     State(service): State<Arc<ShareService>>,
+    State(config): State<Arc<AppConfig>>,
     Path(user_id): Path<String>,
-) -> Result<Json<Vec<Uuid>>, ApiError> {
-    let conversations = service.get_user_conversations(&user_id, 50).await?;
+    Query(query): Query<UserConversationsQuery>,
+) -> Result<Response, ApiError> {
+    let page = PageSize::new(&config, query.limit);
+
+    if let Some(q) = query.q.filter(|q| !q.trim().is_empty()) {
+        let matches: Vec<ConversationMatchResponse> = conv_service
+            .search_conversations(&user_id, &q, query.lang.as_deref(), page.limit)
+            .await?
+            .into_iter()
+            .map(|entry| ConversationMatchResponse {
+                conversation_id: entry.conversation_id,
+                title: entry.title,
+                updated_at: entry.updated_at,
+                language: entry.language,
+            })
+            .collect();
+
+        return Ok((page, Json(matches)).into_response());
+    }
+
+    let conversations = service
+        .get_user_conversations(&user_id, page.limit as i32)
+        .await?;
 
-    Ok(Json(conversations))
+    Ok((page, Json(conversations)).into_response())
 }
@@ -0,0 +1,94 @@
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use uuid::Uuid;
+
+use crate::api::{
+    dto::{CreateWebhookRequest, CreatedWebhookResponse, RedeliverWebhookRequest, WebhookResponse},
+    error::{ApiError, ApiJson},
+};
+use crate::domain::WebhookFilter;
+use crate::domain::change::parse_change_cursor;
+use crate::middleware::AuthUser;
+use crate::services::{ConversationService, ShareService, WebhookService};
+
+use super::share::ensure_can_manage;
+use std::sync::Arc;
+
+/// Have the conversation's changes, narrowed down by the request's
+/// filters, POSTed to a URL. The response holds the signing secret.
+pub async fn create_webhook(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(service): State<Arc<WebhookService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<CreateWebhookRequest>,
+) -> Result<Json<CreatedWebhookResponse>, ApiError> {
+    ensure_can_manage(&conv_service, &share_service, conversation_id, &user).await?;
+
+    let filter = WebhookFilter {
+        event_types: payload.event_types,
+        roles: payload.roles,
+        content_types: payload.content_types,
+    };
+    let subscription = service
+        .subscribe(conversation_id, payload.url, filter, user.0)
+        .await?;
+
+    Ok(Json(subscription.into()))
+}
+
+pub async fn get_webhooks(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(service): State<Arc<WebhookService>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<Vec<WebhookResponse>>, ApiError> {
+    ensure_can_manage(&conv_service, &share_service, conversation_id, &user).await?;
+
+    let subscriptions = service.get_subscriptions(conversation_id).await?;
+
+    Ok(Json(subscriptions.into_iter().map(Into::into).collect()))
+}
+
+pub async fn delete_webhook(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(service): State<Arc<WebhookService>>,
+    user: AuthUser,
+    Path((conversation_id, subscription_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    ensure_can_manage(&conv_service, &share_service, conversation_id, &user).await?;
+
+    service
+        .unsubscribe(conversation_id, subscription_id)
+        .await?;
+
+    Ok(Json(serde_json::json!({
+        "message": "Webhook deleted"
+    })))
+}
+
+/// Deliver the changes after a position again, e.g. after the receiver
+/// lost them
+pub async fn redeliver_webhook(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(share_service): State<Arc<ShareService>>,
+    State(service): State<Arc<WebhookService>>,
+    user: AuthUser,
+    Path((conversation_id, subscription_id)): Path<(Uuid, Uuid)>,
+    ApiJson(payload): ApiJson<RedeliverWebhookRequest>,
+) -> Result<Json<WebhookResponse>, ApiError> {
+    ensure_can_manage(&conv_service, &share_service, conversation_id, &user).await?;
+
+    let since = parse_change_cursor(&payload.since)
+        .ok_or_else(|| ApiError::BadRequest(format!("Invalid since cursor: {}", payload.since)))?;
+    let subscription = service
+        .redeliver(conversation_id, subscription_id, since)
+        .await?;
+
+    Ok(Json(subscription.into()))
+}
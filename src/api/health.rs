@@ -0,0 +1,241 @@
+use axum::{Json, extract::State, http::StatusCode};
+use chrono::Utc;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+
+use crate::db::DbClient;
+use crate::object_store::ObjectStore;
+use crate::scheduler::Scheduler;
+
+use super::dto::{HealthResponse, ProbeResponse, ReadinessCheck, ReadinessResponse};
+
+/// How long a single dependency check may take before readiness fails
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// State behind the startup, readiness and liveness probes
+pub struct Probes {
+    started: AtomicBool,
+    draining: AtomicBool,
+    /// `None` with the in-memory backend
+    db: Option<DbClient>,
+    object_store: Arc<dyn ObjectStore>,
+    /// Permits of the global concurrency limit; none left means requests are being shed
+    requests: Arc<Semaphore>,
+}
+
+impl Probes {
+    pub fn new(
+        db: Option<DbClient>,
+        object_store: Arc<dyn ObjectStore>,
+        max_concurrent_requests: usize,
+    ) -> Self {
+        Self {
+            started: AtomicBool::new(false),
+            draining: AtomicBool::new(false),
+            db,
+            object_store,
+            requests: Arc::new(Semaphore::new(max_concurrent_requests)),
+        }
+    }
+
+    /// Startup finished: migrations applied and the listener bound
+    pub fn mark_started(&self) {
+        self.started.store(true, Ordering::Release);
+    }
+
+    /// Shutdown began; readiness fails from now on so no new traffic arrives
+    pub fn start_draining(&self) {
+        self.draining.store(true, Ordering::Release);
+    }
+
+    pub fn is_started(&self) -> bool {
+        self.started.load(Ordering::Acquire)
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Acquire)
+    }
+
+    /// The semaphore the router's global concurrency limit draws from
+    pub fn request_permits(&self) -> Arc<Semaphore> {
+        self.requests.clone()
+    }
+
+    /// Run every readiness check; ready only if all of them pass
+    pub async fn readiness(&self) -> (bool, Vec<ReadinessCheck>) {
+        let mut checks = vec![
+            flag("started", self.is_started(), "startup has not finished"),
+            flag("draining", !self.is_draining(), "shutting down"),
+            flag(
+                "load_shedding",
+                self.requests.available_permits() > 0,
+                "concurrency limit reached",
+            ),
+        ];
+        if let Some(db) = &self.db {
+            checks.push(check("database", db.ping()).await);
+        }
+        checks.push(check("object_store", self.object_store.check()).await);
+
+        let ready = checks.iter().all(|check| check.ok);
+        (ready, checks)
+    }
+}
+
+fn flag(name: &str, ok: bool, error: &str) -> ReadinessCheck {
+    ReadinessCheck {
+        name: name.to_string(),
+        ok,
+        error: (!ok).then(|| error.to_string()),
+    }
+}
+
+async fn check<E: std::fmt::Display>(
+    name: &str,
+    probe: impl Future<Output = Result<(), E>>,
+) -> ReadinessCheck {
+    let error = match tokio::time::timeout(CHECK_TIMEOUT, probe).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!("timed out after {:?}", CHECK_TIMEOUT)),
+    };
+    ReadinessCheck {
+        name: name.to_string(),
+        ok: error.is_none(),
+        error,
+    }
+}
+
+fn probe(ok: bool, status: &str) -> (StatusCode, Json<ProbeResponse>) {
+    let code = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let response = ProbeResponse {
+        status: status.to_string(),
+        timestamp: Utc::now(),
+    };
+    (code, Json(response))
+}
+
+/// Always 200 while the process serves requests; a failing background task
+/// only marks the status `degraded`
+pub async fn health_check(State(scheduler): State<Arc<Scheduler>>) -> Json<HealthResponse> {
+    let tasks = scheduler.health();
+    let status = if tasks.iter().any(|task| task.is_failing()) {
+        "degraded"
+    } else {
+        "ok"
+    };
+
+    Json(HealthResponse {
+        status: status.to_string(),
+        timestamp: Utc::now(),
+        tasks: tasks.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// 200 whenever the process can answer at all; touches no dependency
+pub async fn liveness() -> (StatusCode, Json<ProbeResponse>) {
+    probe(true, "alive")
+}
+
+/// 200 once startup has finished. The listener only opens after migrations,
+/// so this mostly fails while the server is starting up.
+pub async fn startup(State(probes): State<Arc<Probes>>) -> (StatusCode, Json<ProbeResponse>) {
+    if probes.is_started() {
+        probe(true, "started")
+    } else {
+        probe(false, "starting")
+    }
+}
+
+/// 200 if this instance should receive traffic: started, not draining, not
+/// shedding load, and ScyllaDB and the object store reachable
+pub async fn readiness(State(probes): State<Arc<Probes>>) -> (StatusCode, Json<ReadinessResponse>) {
+    let (ready, checks) = probes.readiness().await;
+    let (code, status) = if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+
+    (
+        code,
+        Json(ReadinessResponse {
+            status: status.to_string(),
+            timestamp: Utc::now(),
+            checks,
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object_store::ObjectStoreError;
+    use async_trait::async_trait;
+
+    struct UnreachableStore;
+
+    #[async_trait]
+    impl ObjectStore for UnreachableStore {
+        fn key_for_url(&self, _url: &str) -> Option<String> {
+            None
+        }
+
+        fn presigned_get_url(&self, key: &str, _expires_in: std::time::Duration) -> String {
+            key.to_string()
+        }
+
+        async fn put_object(
+            &self,
+            _key: &str,
+            _content_type: &str,
+            _body: Vec<u8>,
+        ) -> Result<(), ObjectStoreError> {
+            Err(ObjectStoreError::Request("connection refused".to_string()))
+        }
+
+        async fn get_object(&self, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+            Err(ObjectStoreError::Request(format!("{} not found", key)))
+        }
+
+        async fn delete_object(&self, _key: &str) -> Result<(), ObjectStoreError> {
+            Ok(())
+        }
+
+        async fn check(&self) -> Result<(), ObjectStoreError> {
+            Err(ObjectStoreError::Request("connection refused".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_readiness_follows_startup_and_draining() {
+        let probes = Probes::new(None, Arc::new(UnreachableStore), 1);
+        let failing = |checks: &[ReadinessCheck]| -> Vec<String> {
+            checks
+                .iter()
+                .filter(|check| !check.ok)
+                .map(|check| check.name.clone())
+                .collect()
+        };
+
+        let (ready, checks) = probes.readiness().await;
+        assert!(!ready);
+        assert_eq!(failing(&checks), ["started", "object_store"]);
+
+        probes.mark_started();
+        let _permit = probes.request_permits().acquire_owned().await.unwrap();
+        probes.start_draining();
+        let (_, checks) = probes.readiness().await;
+        assert_eq!(
+            failing(&checks),
+            ["draining", "load_shedding", "object_store"]
+        );
+    }
+}
@@ -1,8 +1,13 @@
 pub mod dto;
 pub mod error;
 pub mod handlers;
+pub mod health;
+pub mod pagination;
 pub mod routes;
+pub mod tls;
+pub mod v2;
 
 pub use dto::*;
-pub use error::ApiError;
+pub use error::{ApiError, ApiJson};
+pub use pagination::PageSize;
 pub use routes::{AppState, create_router};
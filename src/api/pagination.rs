@@ -0,0 +1,80 @@
+use axum::{
+    http::{HeaderName, HeaderValue},
+    response::{IntoResponseParts, ResponseParts},
+};
+use std::convert::Infallible;
+
+use crate::config::AppConfig;
+
+/// Page size that was applied, sent with every list response
+pub const PAGE_LIMIT_HEADER: HeaderName = HeaderName::from_static("x-page-limit");
+
+/// Sent when the requested `limit` was outside `1..=MAX_PAGE_SIZE`
+pub const PAGE_LIMIT_CLAMPED_HEADER: HeaderName = HeaderName::from_static("x-page-limit-clamped");
+
+/// Page size of a list request: its `limit`, or the configured default,
+/// clamped to the configured maximum. Returned with the response to report
+/// the applied size in headers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PageSize {
+    pub limit: usize,
+    pub clamped: bool,
+}
+
+impl PageSize {
+    pub fn new(config: &AppConfig, requested: Option<usize>) -> Self {
+        let requested = requested.unwrap_or(config.default_page_size);
+        let limit = requested.clamp(1, config.max_page_size);
+        PageSize {
+            limit,
+            clamped: limit != requested,
+        }
+    }
+}
+
+impl IntoResponseParts for PageSize {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        res.headers_mut()
+            .insert(PAGE_LIMIT_HEADER, HeaderValue::from(self.limit));
+        if self.clamped {
+            res.headers_mut()
+                .insert(PAGE_LIMIT_CLAMPED_HEADER, HeaderValue::from_static("true"));
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use axum::{Json, response::IntoResponse};
+
+    #[test]
+    fn test_page_size_is_clamped_and_reported() {
+        let config = AppConfig {
+            default_page_size: 50,
+            max_page_size: 200,
+            ..Settings::default().app
+        };
+
+        assert_eq!(
+            PageSize::new(&config, None),
+            PageSize {
+                limit: 50,
+                clamped: false
+            }
+        );
+        assert_eq!(PageSize::new(&config, Some(0)).limit, 1);
+        let page = PageSize::new(&config, Some(5000));
+        assert_eq!(page.limit, 200);
+
+        let response = (page, Json(Vec::<u8>::new())).into_response();
+        assert_eq!(response.headers()[&PAGE_LIMIT_HEADER], "200");
+        assert_eq!(response.headers()[&PAGE_LIMIT_CLAMPED_HEADER], "true");
+        let response = (PageSize::new(&config, Some(10)), Json(())).into_response();
+        assert!(!response.headers().contains_key(&PAGE_LIMIT_CLAMPED_HEADER));
+    }
+}
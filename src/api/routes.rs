@@ -1,127 +1,1116 @@
 use axum::{
     Router,
-    routing::{delete, get, post},
+    error_handling::HandleErrorLayer,
+    extract::DefaultBodyLimit,
+    extract::FromRef,
+    routing::{delete, get, post, put},
 };
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+use tower::{ServiceBuilder, limit::GlobalConcurrencyLimitLayer};
 
-use crate::services::{BranchService, ConversationService, ForkService, ShareService};
+use crate::config::{AdminConfig, AppConfig, ErrorsConfig, Settings};
+use crate::db::DbClient;
+use crate::domain::JobKind;
+use crate::middleware::{
+    AuthPolicy, EmbedTokens, RequestLimits, RequestLogging, auth_middleware, handle_overload,
+    log_access, log_requests, problem_details, restrict_embed_tokens,
+};
+use crate::object_store::ObjectStore;
+use crate::repositories::Storage;
+use crate::scheduler::Scheduler;
+
+use crate::services::{
+    AccessLogService, AnalyticsService, BranchService, ChangeFeed, CleanupService,
+    CollaborationHub, ContextService, ConversationMailboxes, ConversationService, DiffService,
+    ExportService, ForkService, ImageService, ImportService, JobRunner, JobService,
+    LegalHoldService, NotificationService, PreferenceService, SearchService, ShareService,
+    TrendingService, WebhookService,
+};
+use crate::utils::content_pipeline::ContentPipeline;
+use crate::utils::pii::PiiScrubber;
 
 use super::handlers;
+use super::health::{self, Probes};
+
+/// Data exports are far larger than regular request bodies
+const IMPORT_BODY_LIMIT: usize = 256 * 1024 * 1024;
 
-#[derive(Clone)]
+/// Everything handlers take as `State`; each field is a substate they can
+/// extract on its own
+#[derive(Clone, FromRef)]
 pub struct AppState {
     pub conversation_service: Arc<ConversationService>,
+    /// Orders the writes handlers make to a conversation
+    pub conversation_mailboxes: Arc<ConversationMailboxes>,
     pub branch_service: Arc<BranchService>,
     pub fork_service: Arc<ForkService>,
     pub share_service: Arc<ShareService>,
+    pub export_service: Arc<ExportService>,
+    pub import_service: Arc<ImportService>,
+    pub trending_service: Arc<TrendingService>,
+    pub notification_service: Arc<NotificationService>,
+    pub preference_service: Arc<PreferenceService>,
+    pub collaboration_hub: Arc<CollaborationHub>,
+    pub job_service: Arc<JobService>,
+    pub image_service: Arc<ImageService>,
+    pub analytics_service: Arc<AnalyticsService>,
+    pub context_service: Arc<ContextService>,
+    pub diff_service: Arc<DiffService>,
+    pub search_service: Arc<SearchService>,
+    pub legal_hold_service: Arc<LegalHoldService>,
+    pub webhook_service: Arc<WebhookService>,
+    pub access_log_service: Arc<AccessLogService>,
+    pub admin: Arc<AdminConfig>,
+    /// Page sizes of list endpoints
+    pub app: Arc<AppConfig>,
+    pub auth: Arc<AuthPolicy>,
+    pub embed_tokens: Arc<EmbedTokens>,
+    pub scheduler: Arc<Scheduler>,
+    pub probes: Arc<Probes>,
+    pub limits: RequestLimits,
+    pub logging: RequestLogging,
+    pub errors: Arc<ErrorsConfig>,
+}
+
+impl AppState {
+    /// Every service wired over `storage` as `settings` configure them.
+    /// `db_client` is what the readiness probe pings, if any; the scheduler
+    /// stops when `shutdown` is cancelled. Nothing is started or registered
+    /// with the scheduler.
+    pub fn build(
+        storage: &Storage,
+        settings: &Settings,
+        object_store: Arc<dyn ObjectStore>,
+        db_client: Option<DbClient>,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let image_service = Arc::new(
+            ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                object_store.clone(),
+                settings.images.clone(),
+            )
+            .with_fetch_config(&settings.fetch),
+        );
+
+        // Live collaboration registry shared by the change feed and the API
+        let collaboration_hub = Arc::new(CollaborationHub::new());
+        let change_feed = ChangeFeed::new(storage.changes.clone(), collaboration_hub.clone());
+
+        let preference_service = Arc::new(PreferenceService::new(storage.preferences.clone()));
+        let notification_service = Arc::new(NotificationService::new(
+            storage.notifications.clone(),
+            preference_service.clone(),
+        ));
+        let pii_scrubber = Arc::new(PiiScrubber::new(&settings.pii));
+        let share_service = Arc::new(ShareService::new(
+            storage.shares.clone(),
+            change_feed.clone(),
+            notification_service.clone(),
+            preference_service.clone(),
+        ));
+        let conversation_service = Arc::new(
+            ConversationService::new(
+                storage.lineage.clone(),
+                change_feed.clone(),
+                settings.app.clone(),
+                notification_service.clone(),
+                pii_scrubber.clone(),
+                image_service.clone(),
+                share_service.clone(),
+            )
+            .with_content_pipeline(ContentPipeline::new(&settings.content)),
+        );
+        let branch_service = Arc::new(BranchService::new(
+            storage.branches.clone(),
+            storage.lineage.clone(),
+            change_feed,
+            settings.branch_cache.clone(),
+            settings.branches.default_naming,
+            preference_service.clone(),
+        ));
+        let fork_service = Arc::new(ForkService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            settings.app.clone(),
+            settings.fork.clone(),
+            notification_service.clone(),
+            image_service.clone(),
+        ));
+        let export_service = Arc::new(ExportService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.exports.clone(),
+            storage.jobs.clone(),
+            storage.shares.clone(),
+            object_store.clone(),
+            settings.exports.clone(),
+        ));
+        let import_service = Arc::new(ImportService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            settings.app.clone(),
+            pii_scrubber,
+            image_service.clone(),
+        ));
+        let cleanup_service: Arc<dyn JobRunner> = Arc::new(CleanupService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            conversation_service.clone(),
+        ));
+        let job_service = Arc::new(JobService::new(
+            storage.jobs.clone(),
+            HashMap::from([
+                (JobKind::UserCleanup, cleanup_service),
+                (
+                    JobKind::ConversationExport,
+                    export_service.clone() as Arc<dyn JobRunner>,
+                ),
+            ]),
+            settings.jobs.clone(),
+        ));
+
+        AppState {
+            conversation_service,
+            conversation_mailboxes: Arc::new(ConversationMailboxes::new(
+                settings.mailboxes.clone(),
+            )),
+            branch_service,
+            fork_service,
+            share_service,
+            export_service,
+            import_service,
+            trending_service: Arc::new(TrendingService::new(
+                storage.trending.clone(),
+                storage.lineage.clone(),
+                settings.trending.clone(),
+            )),
+            notification_service,
+            preference_service,
+            collaboration_hub,
+            job_service,
+            image_service,
+            analytics_service: Arc::new(AnalyticsService::new(
+                storage.analytics.clone(),
+                storage.lineage.clone(),
+                settings.analytics.clone(),
+            )),
+            context_service: Arc::new(ContextService::new(storage.lineage.clone())),
+            diff_service: Arc::new(DiffService::new(
+                storage.lineage.clone(),
+                storage.branches.clone(),
+                storage.changes.clone(),
+            )),
+            search_service: Arc::new(SearchService::new(storage.lineage.clone())),
+            legal_hold_service: Arc::new(LegalHoldService::new(
+                storage.lineage.clone(),
+                storage.changes.clone(),
+            )),
+            webhook_service: Arc::new(WebhookService::new(
+                storage.webhooks.clone(),
+                storage.changes.clone(),
+                storage.lineage.clone(),
+                settings.webhooks.clone(),
+                settings.fetch.clone(),
+            )),
+            access_log_service: Arc::new(AccessLogService::new(
+                storage.access_log.clone(),
+                settings.access_log.clone(),
+            )),
+            admin: Arc::new(settings.admin.clone()),
+            app: Arc::new(settings.app.clone()),
+            auth: Arc::new(AuthPolicy::new(
+                settings.auth.clone(),
+                storage.lineage.clone(),
+            )),
+            embed_tokens: Arc::new(EmbedTokens::new(settings.embed.clone())),
+            scheduler: Arc::new(Scheduler::new(settings.scheduler.clone(), shutdown)),
+            probes: Arc::new(Probes::new(
+                db_client,
+                object_store,
+                settings.server.max_concurrent_requests,
+            )),
+            limits: RequestLimits {
+                max_concurrent_expensive_requests: settings
+                    .server
+                    .max_concurrent_expensive_requests,
+            },
+            logging: RequestLogging {
+                sample_rate: settings.logging.sample_rate,
+                log_bodies: settings.logging.log_bodies,
+                max_body_bytes: settings.logging.max_body_bytes,
+            },
+            errors: Arc::new(settings.errors.clone()),
+        }
+    }
 }
 
 pub fn create_router(state: AppState) -> Router {
-    Router::new()
-        // Health check
-        .route("/health", get(health_check))
+    // Requests beyond a limit are shed with 503 + Retry-After instead of queueing.
+    // The global layers share one semaphore across all routes they wrap.
+    let limited = |permits: Arc<Semaphore>| {
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(handle_overload))
+            .load_shed()
+            .layer(GlobalConcurrencyLimitLayer::with_semaphore(permits))
+    };
+    let expensive = limited(Arc::new(Semaphore::new(
+        state.limits.max_concurrent_expensive_requests,
+    )));
+
+    let api = Router::new()
         // Conversations
-        .route(
-            "/api/v1/conversations",
-            post(handlers::create_conversation).with_state(state.conversation_service.clone()),
-        )
+        .route("/api/v1/conversations", post(handlers::create_conversation))
         .route(
             "/api/v1/conversations/{id}",
             get(handlers::get_conversation)
-                .with_state(state.conversation_service.clone())
                 .put(handlers::update_conversation)
-                .with_state(state.conversation_service.clone())
-                .delete(handlers::delete_conversation)
-                .with_state(state.conversation_service.clone()),
+                .delete(handlers::delete_conversation),
+        )
+        .route(
+            "/api/v1/conversations/{id}/duplicates",
+            get(handlers::get_duplicates).layer(expensive.clone()),
+        )
+        .route(
+            "/api/v1/conversations/{id}/scrub",
+            post(handlers::scrub_conversation).layer(expensive.clone()),
+        )
+        .route(
+            "/api/v1/conversations/{id}/lock",
+            post(handlers::lock_conversation)
+                .delete(handlers::unlock_conversation)
+                .get(handlers::get_lock),
         )
         .route(
             "/api/v1/conversations/{id}/tree",
-            get(handlers::get_conversation_tree).with_state(state.conversation_service.clone()),
+            get(handlers::get_conversation_tree).layer(expensive.clone()),
+        )
+        .route(
+            "/api/v1/conversations/{id}/published-branch",
+            put(handlers::set_published_branch),
+        )
+        .route(
+            "/api/v1/conversations/{id}/changes",
+            get(handlers::get_changes),
+        )
+        .route(
+            "/api/v1/conversations/{id}/access-log",
+            get(handlers::get_access_log),
+        )
+        .route(
+            "/api/v1/conversations/{id}/sync",
+            post(handlers::sync_conversation).layer(expensive.clone()),
+        )
+        .route(
+            "/api/v1/conversations/{id}/events",
+            get(handlers::get_events),
+        )
+        // Live collaboration
+        .route(
+            "/api/v1/conversations/{id}/live",
+            get(handlers::live_events),
+        )
+        .route(
+            "/api/v1/conversations/{id}/presence",
+            post(handlers::post_presence),
         )
         // Messages
         .route(
             "/api/v1/conversations/{id}/messages",
-            post({
-                let conv_service = state.conversation_service.clone();
-                let branch_service = state.branch_service.clone();
-                move |path, json| {
-                    handlers::create_message(
-                        axum::extract::State(conv_service.clone()),
-                        axum::extract::State(branch_service.clone()),
-                        path,
-                        json,
-                    )
-                }
-            }),
+            post(handlers::create_message),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/messages/{message_id}",
-            get(handlers::get_message).with_state(state.conversation_service.clone()),
+            get(handlers::get_message),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/messages/{message_id}/children",
-            get(handlers::get_message_children).with_state(state.conversation_service.clone()),
+            get(handlers::get_message_children),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/messages/{message_id}/lineage",
-            get(handlers::get_message_lineage).with_state(state.conversation_service.clone()),
+            get(handlers::get_message_lineage),
+        )
+        .route(
+            "/api/v1/conversations/{conversation_id}/messages/{message_id}/ancestors",
+            get(handlers::get_message_ancestors),
+        )
+        .route(
+            "/api/v1/conversations/{conversation_id}/messages/{message_id}/move",
+            post(handlers::move_message),
+        )
+        .route(
+            "/api/v1/conversations/{conversation_id}/messages/{message_id}/private",
+            put(handlers::make_message_private).delete(handlers::make_message_shared),
+        )
+        .route(
+            "/api/v1/conversations/{id}/context",
+            get(handlers::get_conversation_context),
+        )
+        .route(
+            "/api/v1/conversations/{id}/diff",
+            get(handlers::get_conversation_diff),
+        )
+        .route(
+            "/api/v1/conversations/{id}/search",
+            get(handlers::search_conversation),
+        )
+        .route(
+            "/api/v1/conversations/{id}/embed-token",
+            post(handlers::create_embed_token),
+        )
+        // Checkpoints
+        .route(
+            "/api/v1/conversations/{id}/checkpoints",
+            post(handlers::create_checkpoint).get(handlers::get_checkpoints),
         )
         // Branches
         .route(
             "/api/v1/conversations/{id}/branches",
-            post(handlers::create_branch)
-                .with_state(state.branch_service.clone())
-                .get(handlers::get_branches)
-                .with_state(state.branch_service.clone()),
+            post(handlers::create_branch).get(handlers::get_branches),
+        )
+        .route(
+            "/api/v1/conversations/{conversation_id}/branches/by-slug/{slug}",
+            get(handlers::get_branch_by_slug),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/branches/{branch_id}",
             get(handlers::get_branch)
-                .with_state(state.branch_service.clone())
                 .put(handlers::update_branch)
-                .with_state(state.branch_service.clone())
-                .delete(handlers::delete_branch)
-                .with_state(state.branch_service.clone()),
+                .delete(handlers::delete_branch),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/branches/{branch_id}/messages",
-            get(handlers::get_branch_messages).with_state(state.branch_service.clone()),
+            get(handlers::get_branch_messages),
         )
         // Forking
+        .route(
+            "/api/v1/conversations/{id}/duplicate",
+            post(handlers::duplicate_conversation).layer(expensive.clone()),
+        )
         .route(
             "/api/v1/conversations/{id}/fork",
-            post(handlers::fork_conversation).with_state(state.fork_service.clone()),
+            post(handlers::fork_conversation).layer(expensive.clone()),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/branches/{branch_id}/fork",
-            post(handlers::fork_branch).with_state(state.fork_service.clone()),
+            post(handlers::fork_branch).layer(expensive.clone()),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/messages/{message_id}/fork",
-            post(handlers::fork_from_message).with_state(state.fork_service.clone()),
+            post(handlers::fork_from_message).layer(expensive.clone()),
+        )
+        .route(
+            "/api/v1/conversations/{id}/fork-graph",
+            get(handlers::get_fork_graph).layer(expensive.clone()),
+        )
+        // Export
+        .route(
+            "/api/v1/conversations/{id}/export",
+            get(handlers::export_conversation).layer(expensive.clone()),
+        )
+        .route(
+            "/api/v1/conversations/{id}/exports",
+            post(handlers::create_export).get(handlers::get_exports),
+        )
+        .route(
+            "/api/v1/conversations/{id}/exports/{export_id}",
+            get(handlers::get_export),
+        )
+        // Import
+        .route(
+            "/api/v1/imports/chatgpt",
+            post(handlers::import_chatgpt).layer(
+                expensive
+                    .clone()
+                    .layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+            ),
+        )
+        .route(
+            "/api/v1/imports/native",
+            post(handlers::import_native).layer(
+                expensive
+                    .clone()
+                    .layer(DefaultBodyLimit::max(IMPORT_BODY_LIMIT)),
+            ),
         )
+        // Notifications
+        .route(
+            "/api/v1/users/{user_id}/notifications",
+            get(handlers::get_notifications),
+        )
+        .route(
+            "/api/v1/users/{user_id}/notifications/read",
+            post(handlers::mark_notifications_read),
+        )
+        // Analytics
+        .route(
+            "/api/v1/users/{user_id}/analytics",
+            get(handlers::get_user_analytics),
+        )
+        .route(
+            "/api/v1/conversations/{id}/analytics",
+            get(handlers::get_conversation_analytics),
+        )
+        .route("/api/v1/admin/usage", get(handlers::get_usage))
+        .route("/api/v1/admin/legal-holds", get(handlers::get_legal_holds))
+        .route(
+            "/api/v1/admin/legal-holds/{conversation_id}",
+            put(handlers::place_legal_hold).delete(handlers::release_legal_hold),
+        )
+        .route(
+            "/api/v1/admin/encryption/rotate",
+            post(handlers::rotate_encryption_keys),
+        )
+        // Explore
+        .route("/api/v1/explore/trending", get(handlers::get_trending))
         // Sharing
         .route(
             "/api/v1/conversations/{id}/share",
-            post(handlers::share_conversation).with_state(state.share_service.clone()),
+            post(handlers::share_conversation),
+        )
+        .route(
+            "/api/v1/conversations/{id}/shares/batch",
+            post(handlers::batch_update_shares),
+        )
+        .route(
+            "/api/v1/users/{user_id}/shared-with-me",
+            get(handlers::get_shared_with_me),
         )
+        .route(
+            "/api/v1/users/{user_id}/preferences",
+            get(handlers::get_preferences).put(handlers::update_preferences),
+        )
+        .route(
+            "/api/v1/conversations/{id}/invites",
+            post(handlers::create_invite),
+        )
+        .route("/api/v1/invites/accept", post(handlers::accept_invite))
         .route(
             "/api/v1/conversations/{id}/shares",
-            get(handlers::get_shares).with_state(state.share_service.clone()),
+            get(handlers::get_shares),
+        )
+        .route(
+            "/api/v1/conversations/{id}/webhooks",
+            post(handlers::create_webhook).get(handlers::get_webhooks),
+        )
+        .route(
+            "/api/v1/conversations/{conversation_id}/webhooks/{subscription_id}",
+            delete(handlers::delete_webhook),
+        )
+        .route(
+            "/api/v1/conversations/{conversation_id}/webhooks/{subscription_id}/redeliver",
+            post(handlers::redeliver_webhook),
         )
         .route(
             "/api/v1/conversations/{conversation_id}/shares/{user_id}",
-            delete(handlers::revoke_share).with_state(state.share_service.clone()),
+            delete(handlers::revoke_share),
         )
         .route(
             "/api/v1/users/{user_id}/conversations",
-            get(handlers::get_user_conversations).with_state(state.share_service.clone()),
+            get(handlers::get_user_conversations).delete(handlers::delete_user_conversations),
         )
+        .route("/api/v1/users/{user_id}/jobs", get(handlers::get_user_jobs))
+        .route("/api/v1/jobs/{job_id}", get(handlers::get_job))
+        .route("/api/v1/jobs/{job_id}/cancel", post(handlers::cancel_job))
+        .merge(super::v2::routes(|route| route.layer(expensive.clone())))
+        // Inside authentication, to know who is reading
+        .layer(axum::middleware::from_fn_with_state(
+            state.access_log_service.clone(),
+            log_access,
+        ))
+        // Inside the embed check, which strips the identity of embed requests
+        .layer(axum::middleware::from_fn_with_state(
+            state.auth.clone(),
+            auth_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            state.embed_tokens.clone(),
+            restrict_embed_tokens,
+        ))
+        // Readiness watches this semaphore to report shedding
+        .layer(limited(state.probes.request_permits()))
+        // Outside the limits so shed requests are logged too
+        .layer(axum::middleware::from_fn_with_state(
+            state.logging,
+            log_requests,
+        ))
+        // Outermost so every error, shed requests included, gets the negotiated format
+        .layer(axum::middleware::from_fn_with_state(
+            state.errors.clone(),
+            problem_details,
+        ));
+
+    // Health checks stay outside the limits and logging so probes keep working under load
+    api.route("/health", get(health::health_check))
+        .route("/health/live", get(health::liveness))
+        .route("/health/startup", get(health::startup))
+        .route("/health/ready", get(health::readiness))
+        .with_state(state)
 }
 
-async fn health_check() -> axum::Json<crate::api::dto::HealthResponse> {
-    axum::Json(crate::api::dto::HealthResponse {
-        status: "ok".to_string(),
-        timestamp: chrono::Utc::now(),
-    })
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ContentType, Message, MessageRole, Permission, TextContent};
+    use crate::repositories::Storage;
+    use crate::services::CollaborationEvent;
+    use crate::test_support;
+    use axum::body::Body;
+    use axum::http::Request;
+    use futures::StreamExt;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    /// Text of every message the owner keeps to themselves
+    const SECRET: &str = "secret";
+
+    async fn call(
+        app: &Router,
+        method: &str,
+        path: &str,
+        user_id: Option<&str>,
+        body: Option<&str>,
+    ) -> (u16, String) {
+        let mut req = Request::builder().method(method).uri(path);
+        if let Some(user_id) = user_id {
+            req = req.header("X-User-ID", user_id);
+        }
+        let body = match body {
+            Some(body) => {
+                req = req.header("Content-Type", "application/json");
+                Body::from(body.to_string())
+            }
+            None => Body::empty(),
+        };
+        let response = app.clone().oneshot(req.body(body).unwrap()).await.unwrap();
+        let status = response.status().as_u16();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn reply(state: &AppState, parent: &Message, text: &str) -> Message {
+        state
+            .conversation_service
+            .append_message(
+                parent.conversation_id,
+                parent.message_id,
+                MessageRole::Human,
+                ContentType::Text(TextContent {
+                    text: text.to_string(),
+                }),
+                HashMap::new(),
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap()
+    }
+
+    /// A public conversation of `user_a` with a visible reply, and below it
+    /// a private message with a reply, a copy, a checkpoint and a branch of
+    /// its own
+    async fn private_conversation() -> (AppState, Message, Message, Message) {
+        let storage = Storage::memory();
+        let mut state = test_support::app_state(&storage);
+        let mut auth = crate::config::Settings::default().auth;
+        auth.anonymous_public_reads = true;
+        state.auth = Arc::new(AuthPolicy::new(auth, storage.lineage.clone()));
+
+        let service = &state.conversation_service;
+        let conversation = service
+            .create_conversation("Logo".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        service
+            .update_conversation(cid, None, None, Some(true), None)
+            .await
+            .unwrap();
+        let visible = reply(&state, &conversation.root_message, "Draw a fox").await;
+        let private = reply(&state, &visible, "A secret draft").await;
+        let below = reply(&state, &private, "A secret reply").await;
+        service
+            .create_checkpoint(
+                cid,
+                Some(private.message_id),
+                below.message_id,
+                "A secret summary".to_string(),
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        let copy = reply(&state, &private, "A secret draft").await;
+        let branch = state
+            .branch_service
+            .create_branch(
+                cid,
+                Some("Secret path".to_string()),
+                below.message_id,
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        state
+            .branch_service
+            .update_branch_leaf(cid, branch.branch_id, copy.message_id)
+            .await
+            .unwrap();
+        service
+            .set_message_private(cid, private.message_id, true)
+            .await
+            .unwrap();
+
+        (state, visible, private, below)
+    }
+
+    #[tokio::test]
+    async fn test_private_messages_are_hidden_from_every_read_endpoint() {
+        let (state, visible, private, below) = private_conversation().await;
+        let cid = visible.conversation_id;
+        let branch = state
+            .branch_service
+            .get_branches(cid)
+            .await
+            .unwrap()
+            .remove(0);
+        let app = create_router(state);
+        let v1 = format!("/api/v1/conversations/{}", cid);
+        let v2 = format!("/api/v2/conversations/{}", cid);
+        let hidden = |body: &str| {
+            !body.contains(SECRET)
+                && !body.contains(&private.message_id.to_string())
+                && !body.contains(&below.message_id.to_string())
+        };
+
+        let readable = [
+            ("GET", format!("{}/tree", v1), None),
+            (
+                "GET",
+                format!("{}/messages/{}", v1, visible.message_id),
+                None,
+            ),
+            (
+                "GET",
+                format!("{}/messages/{}/children", v1, visible.message_id),
+                None,
+            ),
+            (
+                "GET",
+                format!("{}/messages/{}/lineage", v1, visible.message_id),
+                None,
+            ),
+            (
+                "GET",
+                format!("{}/messages/{}/ancestors", v1, visible.message_id),
+                None,
+            ),
+            (
+                "GET",
+                format!("{}/context?leaf={}", v1, visible.message_id),
+                None,
+            ),
+            ("GET", format!("{}/checkpoints", v1), None),
+            ("GET", format!("{}/branches", v1), None),
+            ("GET", format!("{}/duplicates", v1), None),
+            ("GET", format!("{}/search?q=draft", v1), None),
+            (
+                "GET",
+                format!("{}/diff?from=2000-01-01T00:00:00Z", v1),
+                None,
+            ),
+            ("GET", format!("{}/changes", v1), None),
+            ("GET", format!("{}/events", v1), None),
+            ("POST", format!("{}/sync", v1), Some("{}")),
+            ("GET", format!("{}/tree", v2), None),
+            ("GET", format!("{}/tree?format=flat", v2), None),
+            (
+                "GET",
+                format!("{}/messages/{}", v2, visible.message_id),
+                None,
+            ),
+            (
+                "GET",
+                format!("{}/messages/{}/lineage", v2, visible.message_id),
+                None,
+            ),
+        ];
+        for (method, path, body) in &readable {
+            let (status, response) = call(&app, method, path, Some("user_b"), *body).await;
+            assert_eq!(status, 200, "{} {}: {}", method, path, response);
+            assert!(hidden(&response), "{} {} leaks: {}", method, path, response);
+            // Anonymous readers get the same, where they may read at all
+            let (status, response) = call(&app, method, path, None, *body).await;
+            assert!(status == 200 || status == 401, "{} {}", method, path);
+            assert!(hidden(&response), "{} {} leaks: {}", method, path, response);
+        }
+        let (_, found) = call(
+            &app,
+            "GET",
+            &format!("{}/search?q=draft", v1),
+            Some("user_b"),
+            None,
+        )
+        .await;
+        assert!(found.contains("\"total\":0"), "{}", found);
+
+        let not_found = [
+            format!("{}/messages/{}", v1, private.message_id),
+            format!("{}/messages/{}", v1, below.message_id),
+            format!("{}/messages/{}/lineage", v1, below.message_id),
+            format!("{}/messages/{}/ancestors", v1, below.message_id),
+            format!("{}/context?leaf={}", v1, below.message_id),
+            format!("{}/branches/{}", v1, branch.branch_id),
+            format!("{}/branches/by-slug/{}", v1, branch.slug),
+            format!("{}/messages/{}", v2, private.message_id),
+            format!("{}/messages/{}/lineage", v2, below.message_id),
+        ];
+        for path in &not_found {
+            for viewer in [Some("user_b"), None] {
+                let (status, response) = call(&app, "GET", path, viewer, None).await;
+                // Anonymous readers can't reach v2 at all
+                assert!(
+                    status == 404 || (viewer.is_none() && path.starts_with(&v2) && status == 401),
+                    "GET {} as {:?}: {} {}",
+                    path,
+                    viewer,
+                    status,
+                    response
+                );
+            }
+            // The owner still reads everything
+            let (status, response) = call(&app, "GET", path, Some("user_a"), None).await;
+            assert_eq!(status, 200, "GET {} as the owner: {}", path, response);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_moving_a_subtree_leaves_out_private_messages() {
+        let (state, visible, private, below) = private_conversation().await;
+        let cid = visible.conversation_id;
+        let root = state
+            .conversation_service
+            .get_conversation(cid)
+            .await
+            .unwrap()
+            .root_message;
+        let other = reply(&state, &root, "Draw a cat").await;
+        state
+            .share_service
+            .share_conversation(
+                cid,
+                "user_c".to_string(),
+                Permission::Branch,
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        let app = create_router(state);
+        let move_path = |message: &Message| {
+            format!(
+                "/api/v1/conversations/{}/messages/{}/move",
+                cid, message.message_id
+            )
+        };
+        let below_other = format!(r#"{{"new_parent_message_id":"{}"}}"#, other.message_id);
+
+        let (status, response) = call(
+            &app,
+            "POST",
+            &move_path(&visible),
+            Some("user_c"),
+            Some(&below_other),
+        )
+        .await;
+        assert_eq!(status, 200, "{}", response);
+        assert!(response.contains(&visible.message_id.to_string()));
+        assert!(
+            !response.contains(SECRET)
+                && !response.contains(&private.message_id.to_string())
+                && !response.contains(&below.message_id.to_string()),
+            "{}",
+            response
+        );
+
+        // Nor can hidden messages be moved, or moved below
+        let below_hidden = format!(r#"{{"new_parent_message_id":"{}"}}"#, below.message_id);
+        for (path, body) in [
+            (move_path(&below), &below_other),
+            (move_path(&other), &below_hidden),
+        ] {
+            let (status, response) = call(&app, "POST", &path, Some("user_c"), Some(body)).await;
+            assert_eq!(status, 404, "{}", response);
+        }
+
+        // The owner gets the whole subtree
+        let below_root = format!(r#"{{"new_parent_message_id":"{}"}}"#, root.message_id);
+        let (status, response) = call(
+            &app,
+            "POST",
+            &move_path(&visible),
+            Some("user_a"),
+            Some(&below_root),
+        )
+        .await;
+        assert_eq!(status, 200);
+        assert!(response.contains(&below.message_id.to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_owners_duplicate_their_private_messages() {
+        let (state, visible, _, _) = private_conversation().await;
+        let app = create_router(state);
+        let path = format!(
+            "/api/v1/conversations/{}/duplicate",
+            visible.conversation_id
+        );
+
+        let (status, response) = call(&app, "POST", &path, Some("user_a"), None).await;
+        assert_eq!(status, 200, "{}", response);
+        let copy: serde_json::Value = serde_json::from_str(&response).unwrap();
+        let tree = format!(
+            "/api/v1/conversations/{}/tree",
+            copy["conversation_id"].as_str().unwrap()
+        );
+
+        // The copy has every message, and keeps the private ones private
+        let (_, owned) = call(&app, "GET", &tree, Some("user_a"), None).await;
+        assert!(owned.contains(SECRET), "{}", owned);
+        let (_, shown) = call(&app, "GET", &tree, Some("user_b"), None).await;
+        assert!(shown.contains("Draw a fox"), "{}", shown);
+        assert!(!shown.contains(SECRET), "{}", shown);
+    }
+
+    #[tokio::test]
+    async fn test_live_stream_leaves_out_private_messages() {
+        let (state, visible, private, _) = private_conversation().await;
+        let app = create_router(state.clone());
+        let path = format!("/api/v1/conversations/{}/live", visible.conversation_id);
+        let response = app
+            .oneshot(
+                Request::get(path)
+                    .header("X-User-ID", "user_b")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let mut body = response.into_body().into_data_stream();
+
+        reply(&state, &private, "Another secret").await;
+        let shown = reply(&state, &visible, "Make it red").await;
+
+        let mut received = String::new();
+        while !received.contains(&shown.message_id.to_string()) {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+                .await
+                .expect("the visible reply is streamed")
+                .unwrap()
+                .unwrap();
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        assert!(!received.contains(SECRET), "{}", received);
+    }
+
+    #[tokio::test]
+    async fn test_only_writers_move_messages_and_change_branches() {
+        let state = test_support::app_state(&Storage::memory());
+        let conversation = state
+            .conversation_service
+            .create_conversation("Logo".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let first = reply(&state, &conversation.root_message, "Draw a fox").await;
+        let second = reply(&state, &conversation.root_message, "Draw a cat").await;
+        let branch = state
+            .branch_service
+            .create_branch(cid, None, first.message_id, "user_a".to_string())
+            .await
+            .unwrap();
+        state
+            .share_service
+            .share_conversation(
+                cid,
+                "user_c".to_string(),
+                Permission::Branch,
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        let app = create_router(state);
+
+        let move_to =
+            |parent: &Message| format!(r#"{{"new_parent_message_id":"{}"}}"#, parent.message_id);
+        let move_path = format!(
+            "/api/v1/conversations/{}/messages/{}/move",
+            cid, second.message_id
+        );
+        let branch_path = format!(
+            "/api/v1/conversations/{}/branches/{}",
+            cid, branch.branch_id
+        );
+        let rename = r#"{"branch_name":"renamed"}"#;
+        // Appending with a branch_id moves that branch's leaf too
+        let messages_path = format!("/api/v1/conversations/{}/messages", cid);
+        let append = format!(
+            r#"{{"parent_message_id":"{}","role":"human","content":{{"type":"text","text":"Red"}},"branch_id":"{}"}}"#,
+            first.message_id, branch.branch_id
+        );
+        let v2_messages_path = format!("/api/v2/conversations/{}/messages", cid);
+        let v2_append = format!(
+            r#"{{"parent_id":"{}","role":"human","content":{{"type":"text","text":"Red"}},"branch_id":"{}"}}"#,
+            first.message_id, branch.branch_id
+        );
+        let branches_path = format!("/api/v1/conversations/{}/branches", cid);
+        let new_branch = format!(r#"{{"leaf_message_id":"{}"}}"#, second.message_id);
+        let checkpoints_path = format!("/api/v1/conversations/{}/checkpoints", cid);
+        let checkpoint = format!(
+            r#"{{"to_message_id":"{}","summary":"A fox"}}"#,
+            first.message_id
+        );
+
+        // Strangers can't write, whatever their request
+        let writes = [
+            ("POST", move_path.as_str(), Some(move_to(&first))),
+            ("PUT", branch_path.as_str(), Some(rename.to_string())),
+            ("DELETE", branch_path.as_str(), None),
+            ("POST", messages_path.as_str(), Some(append.clone())),
+            ("POST", v2_messages_path.as_str(), Some(v2_append)),
+            ("POST", branches_path.as_str(), Some(new_branch.clone())),
+            ("POST", checkpoints_path.as_str(), Some(checkpoint.clone())),
+        ];
+        for (method, path, body) in &writes {
+            let (status, response) =
+                call(&app, method, path, Some("user_b"), body.as_deref()).await;
+            assert_eq!(status, 403, "{} {}: {}", method, path, response);
+        }
+        let (_, unchanged) = call(&app, "GET", &branch_path, Some("user_a"), None).await;
+        assert!(
+            unchanged.contains(&first.message_id.to_string()),
+            "{}",
+            unchanged
+        );
+
+        // Malformed bodies are API errors like any other
+        for (method, path) in [
+            ("POST", move_path.as_str()),
+            (
+                "POST",
+                format!("/api/v1/conversations/{}/fork", cid).as_str(),
+            ),
+            (
+                "PUT",
+                format!("/api/v1/conversations/{}/published-branch", cid).as_str(),
+            ),
+        ] {
+            let (status, response) =
+                call(&app, method, path, Some("user_c"), Some(r#"{"x":"#)).await;
+            assert_eq!(status, 400, "{} {}: {}", method, path, response);
+            assert!(response.contains(r#""code":"bad_request""#), "{}", response);
+        }
+
+        // Branch shares and the owner can
+        let (status, _) = call(
+            &app,
+            "POST",
+            &move_path,
+            Some("user_c"),
+            Some(&move_to(&first)),
+        )
+        .await;
+        assert_eq!(status, 200);
+        let (status, _) = call(&app, "PUT", &branch_path, Some("user_c"), Some(rename)).await;
+        assert_eq!(status, 200);
+        for (path, body) in [
+            (&messages_path, &append),
+            (&branches_path, &new_branch),
+            (&checkpoints_path, &checkpoint),
+        ] {
+            let (status, response) = call(&app, "POST", path, Some("user_c"), Some(body)).await;
+            assert_eq!(status, 200, "POST {}: {}", path, response);
+        }
+        let (status, _) = call(&app, "DELETE", &branch_path, Some("user_a"), None).await;
+        assert_eq!(status, 200);
+    }
+
+    #[tokio::test]
+    async fn test_presence_is_signalled_as_the_caller() {
+        let state = test_support::app_state(&Storage::memory());
+        let conversation = state
+            .conversation_service
+            .create_conversation("Logo".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        state
+            .share_service
+            .share_conversation(
+                cid,
+                "user_b".to_string(),
+                Permission::Read,
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        let mut events = state.collaboration_hub.subscribe(cid);
+        let app = create_router(state);
+        let path = format!("/api/v1/conversations/{}/presence", cid);
+
+        // Only those who can read the conversation are present in it
+        let (status, _) = call(
+            &app,
+            "POST",
+            &path,
+            Some("user_c"),
+            Some(r#"{"state":"typing"}"#),
+        )
+        .await;
+        assert_eq!(status, 403);
+        let (status, _) = call(
+            &app,
+            "POST",
+            &format!("/api/v1/conversations/{}/presence", Uuid::new_v4()),
+            Some("user_b"),
+            Some(r#"{"state":"typing"}"#),
+        )
+        .await;
+        assert_eq!(status, 404);
+
+        let (status, _) = call(
+            &app,
+            "POST",
+            &path,
+            Some("user_b"),
+            Some(r#"{"user_id":"user_a","state":"typing"}"#),
+        )
+        .await;
+        assert_eq!(status, 403);
+        let (status, _) = call(
+            &app,
+            "POST",
+            &path,
+            Some("user_b"),
+            Some(r#"{"state":"typing"}"#),
+        )
+        .await;
+        assert_eq!(status, 200);
+
+        match events.try_recv().unwrap() {
+            CollaborationEvent::Presence(signal) => assert_eq!(signal.user_id, "user_b"),
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
 }
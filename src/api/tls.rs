@@ -0,0 +1,196 @@
+//! HTTPS termination for deployments without a fronting load balancer. The
+//! listener hands axum connections whose TLS handshake is done; handshakes
+//! run concurrently, so a slow client doesn't hold up the others.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig, crypto};
+use tokio_rustls::server::TlsStream;
+
+use crate::config::ServerConfig as ServerSettings;
+
+/// How long a client may take to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Handshaken connections waiting for the server to take them
+const ACCEPT_BACKLOG: usize = 128;
+
+/// TLS configuration of the server from `server.tls_*`, `None` if HTTPS
+/// isn't served directly. With `server.tls_client_ca_path`, clients must
+/// present a certificate issued by one of its CAs.
+pub fn server_config(settings: &ServerSettings) -> Result<Option<Arc<ServerConfig>>, String> {
+    let (Some(cert_path), Some(key_path)) = (&settings.tls_cert_path, &settings.tls_key_path)
+    else {
+        return Ok(None);
+    };
+
+    let provider = Arc::new(crypto::ring::default_provider());
+    let certs = certificates(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path).map_err(|e| format!("{}: {}", key_path, e))?;
+    let builder = ServerConfig::builder_with_provider(provider.clone())
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?;
+    let builder = match &settings.tls_client_ca_path {
+        Some(ca_path) => {
+            let mut roots = RootCertStore::empty();
+            for cert in certificates(ca_path)? {
+                roots.add(cert).map_err(|e| format!("{}: {}", ca_path, e))?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                .build()
+                .map_err(|e| format!("{}: {}", ca_path, e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder
+        .with_single_cert(certs, key)
+        .map_err(|e| format!("{}: {}", cert_path, e))?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+
+    Ok(Some(Arc::new(config)))
+}
+
+/// The certificates of a PEM file, at least one
+fn certificates(path: &str) -> Result<Vec<CertificateDer<'static>>, String> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("{}: {}", path, e))?;
+    if certs.is_empty() {
+        return Err(format!("{}: no certificate found", path));
+    }
+
+    Ok(certs)
+}
+
+/// Listener serving HTTPS on a bound TCP listener
+pub struct TlsListener {
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    /// Accept connections on `listener` and handshake them in the
+    /// background, until the listener is dropped
+    pub fn new(listener: TcpListener, config: Arc<ServerConfig>) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let acceptor = TlsAcceptor::from(config);
+        let (tx, connections) = mpsc::channel(ACCEPT_BACKLOG);
+
+        tokio::spawn(async move {
+            loop {
+                let (stream, peer) = tokio::select! {
+                    _ = tx.closed() => break,
+                    accepted = listener.accept() => match accepted {
+                        Ok(accepted) => accepted,
+                        Err(e) => {
+                            // Usually out of file descriptors; give some back
+                            tracing::warn!("Failed to accept a connection: {}", e);
+                            tokio::time::sleep(Duration::from_secs(1)).await;
+                            continue;
+                        }
+                    },
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, peer)).await;
+                        }
+                        Ok(Err(e)) => tracing::debug!("TLS handshake with {} failed: {}", peer, e),
+                        Err(_) => tracing::debug!("TLS handshake with {} timed out", peer),
+                    }
+                });
+            }
+        });
+
+        Ok(Self {
+            connections,
+            local_addr,
+        })
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accepting task only stops once this listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, routing::get};
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/tls/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    /// Serve `config` on a local port and return its URL
+    async fn serve(config: Arc<ServerConfig>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "https://localhost:{}/",
+            listener.local_addr().unwrap().port()
+        );
+        let app = Router::new().route("/", get(|| async { "ok" }));
+        let listener = TlsListener::new(listener, config).unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        url
+    }
+
+    fn client(identity: bool) -> reqwest::Client {
+        let ca = std::fs::read(fixture("ca.pem")).unwrap();
+        let mut builder = reqwest::Client::builder()
+            .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+            .no_proxy();
+        if identity {
+            let mut pem = std::fs::read(fixture("client.pem")).unwrap();
+            pem.extend(std::fs::read(fixture("client.key")).unwrap());
+            builder = builder.identity(reqwest::Identity::from_pem(&pem).unwrap());
+        }
+        builder.build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_https_is_served_and_client_certificates_are_checked() {
+        let mut settings = crate::config::Settings::default().server;
+        assert!(server_config(&settings).unwrap().is_none());
+
+        settings.tls_cert_path = Some(fixture("server.pem"));
+        settings.tls_key_path = Some(fixture("server.key"));
+        let url = serve(server_config(&settings).unwrap().unwrap()).await;
+        let response = client(false).get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        settings.tls_client_ca_path = Some(fixture("ca.pem"));
+        let url = serve(server_config(&settings).unwrap().unwrap()).await;
+        assert!(client(false).get(&url).send().await.is_err());
+        let response = client(true).get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        settings.tls_key_path = Some(fixture("ca.pem"));
+        assert!(server_config(&settings).is_err());
+    }
+}
@@ -0,0 +1,24 @@
+//! The `/api/v2` DTOs live in `aigc-history-types` so clients can share
+//! them, along with their conversions from the domain model
+
+pub use aigc_history_types::v2::*;
+
+use crate::domain::Message;
+use crate::services::MessageTree;
+
+/// Nodes for `trees` and their replies, converting each message with
+/// `convert`; a message it turns into `None` is left out with its replies
+pub fn tree_nodes(
+    trees: Vec<MessageTree>,
+    convert: &impl Fn(Message) -> Option<Message>,
+) -> Vec<TreeNode> {
+    trees
+        .into_iter()
+        .filter_map(|tree| {
+            Some(TreeNode {
+                message: MessageResponse::from_domain(convert(tree.message)?)?,
+                children: tree_nodes(tree.children, convert),
+            })
+        })
+        .collect()
+}
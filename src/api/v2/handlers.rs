@@ -0,0 +1,239 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::error::{ApiError, ApiJson};
+use crate::api::handlers::ensure_can_write;
+use crate::db::DbError;
+use crate::domain::{Conversation, Message};
+use crate::middleware::AuthUser;
+use crate::services::{
+    BranchService, ConversationMailboxes, ConversationService, ImageService, PrivateMessages,
+    TrendingService,
+};
+
+use super::dto::{
+    self, ConversationResponse, CreateConversationRequest, CreateMessageRequest, MessageResponse,
+    TreeFormat, TreeNode, TreeQuery, TreeResponse,
+};
+
+pub async fn create_conversation(
+    State(service): State<Arc<ConversationService>>,
+    user: AuthUser,
+    Json(payload): Json<CreateConversationRequest>,
+) -> Result<(StatusCode, Json<ConversationResponse>), ApiError> {
+    let mut conversation = service.create_conversation(payload.title, user.0).await?;
+    if payload.description.is_some() || payload.is_public || payload.language.is_some() {
+        service
+            .update_conversation(
+                conversation.conversation_id,
+                None,
+                payload.description,
+                Some(payload.is_public),
+                payload.language,
+            )
+            .await?;
+        conversation = service
+            .get_conversation(conversation.conversation_id)
+            .await?;
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(conversation_response(&conversation)?),
+    ))
+}
+
+pub async fn get_conversation(
+    State(service): State<Arc<ConversationService>>,
+    State(trending_service): State<Arc<TrendingService>>,
+    Path(conversation_id): Path<Uuid>,
+) -> Result<Json<ConversationResponse>, ApiError> {
+    let conversation = service.get_conversation(conversation_id).await?;
+    trending_service.record_view(conversation_id).await;
+
+    Ok(Json(conversation_response(&conversation)?))
+}
+
+/// The conversation with its messages, nested under their parents unless
+/// `format=flat`, without those the viewer may not see
+pub async fn get_conversation_tree(
+    State(service): State<Arc<ConversationService>>,
+    State(trending_service): State<Arc<TrendingService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path(conversation_id): Path<Uuid>,
+    Query(query): Query<TreeQuery>,
+) -> Result<Json<TreeResponse>, ApiError> {
+    let private = private_messages(&service, conversation_id, viewer).await?;
+    let response = match query.format {
+        TreeFormat::Nested => {
+            let tree = service.get_message_tree(conversation_id).await?;
+            let conversation = Conversation {
+                conversation_id,
+                root_message: tree.message,
+            };
+            let nodes = dto::tree_nodes(tree.children, &|m| {
+                private.redact(m).map(|m| images.presign(m))
+            });
+            TreeResponse {
+                conversation: conversation_response(&conversation)?,
+                total_messages: count_nodes(&nodes),
+                tree: Some(nodes),
+                messages: None,
+            }
+        }
+        TreeFormat::Flat => {
+            let mut messages = service.get_conversation_tree(conversation_id).await?;
+            let root = messages
+                .iter()
+                .position(Message::is_root)
+                .ok_or(ApiError::NotFound("Conversation not found".to_string()))?;
+            let conversation = Conversation {
+                conversation_id,
+                root_message: messages.remove(root),
+            };
+            let messages: Vec<MessageResponse> = messages
+                .into_iter()
+                .filter_map(|m| private.redact(m))
+                .filter_map(|m| MessageResponse::from_domain(images.presign(m)))
+                .collect();
+            TreeResponse {
+                conversation: conversation_response(&conversation)?,
+                total_messages: messages.len(),
+                tree: None,
+                messages: Some(messages),
+            }
+        }
+    };
+    trending_service.record_view(conversation_id).await;
+
+    Ok(Json(response))
+}
+
+pub async fn create_message(
+    State(conv_service): State<Arc<ConversationService>>,
+    State(branch_service): State<Arc<BranchService>>,
+    State(images): State<Arc<ImageService>>,
+    State(mailboxes): State<Arc<ConversationMailboxes>>,
+    user: AuthUser,
+    Path(conversation_id): Path<Uuid>,
+    ApiJson(payload): ApiJson<CreateMessageRequest>,
+) -> Result<(StatusCode, Json<MessageResponse>), ApiError> {
+    ensure_can_write(&conv_service, conversation_id, &user).await?;
+    let parent_id = match payload.parent_id {
+        Some(parent_id) => parent_id,
+        None => {
+            conv_service
+                .get_conversation(conversation_id)
+                .await?
+                .root_message
+                .message_id
+        }
+    };
+
+    let message = mailboxes
+        .run(conversation_id, async move {
+            let message = conv_service
+                .append_message(
+                    conversation_id,
+                    parent_id,
+                    payload.role.into(),
+                    payload.content,
+                    payload.metadata,
+                    user.0,
+                )
+                .await?;
+
+            if let Some(branch_id) = payload.branch_id {
+                branch_service
+                    .extend_branch_with_message(conversation_id, branch_id, message.message_id)
+                    .await?;
+            }
+
+            Ok::<_, DbError>(message)
+        })
+        .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(message_response(&images, message)?),
+    ))
+}
+
+pub async fn get_message(
+    State(service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<MessageResponse>, ApiError> {
+    let private = private_messages(&service, conversation_id, viewer).await?;
+    let message = service.get_message(conversation_id, message_id).await?;
+    let message = private.redact(message).ok_or_else(message_not_found)?;
+
+    Ok(Json(message_response(&images, message)?))
+}
+
+/// The path from the top of the conversation to the message
+pub async fn get_message_lineage(
+    State(service): State<Arc<ConversationService>>,
+    State(images): State<Arc<ImageService>>,
+    viewer: Option<Extension<AuthUser>>,
+    Path((conversation_id, message_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<MessageResponse>>, ApiError> {
+    let private = private_messages(&service, conversation_id, viewer).await?;
+    let lineage = service
+        .get_lineage_path(conversation_id, message_id)
+        .await?;
+    if lineage.last().is_some_and(|m| private.hides(m)) {
+        return Err(message_not_found());
+    }
+
+    Ok(Json(
+        lineage
+            .into_iter()
+            .filter_map(|m| private.redact(m))
+            .filter_map(|m| MessageResponse::from_domain(images.presign(m)))
+            .collect(),
+    ))
+}
+
+/// What the viewer may not see of the conversation
+async fn private_messages(
+    service: &ConversationService,
+    conversation_id: Uuid,
+    viewer: Option<Extension<AuthUser>>,
+) -> Result<PrivateMessages, ApiError> {
+    let viewer = viewer.map(|Extension(user)| user.0);
+    Ok(service
+        .private_messages(conversation_id, viewer.as_deref())
+        .await?)
+}
+
+fn message_not_found() -> ApiError {
+    ApiError::NotFound("Message not found".to_string())
+}
+
+fn conversation_response(conversation: &Conversation) -> Result<ConversationResponse, ApiError> {
+    ConversationResponse::from_domain(conversation)
+        .ok_or_else(|| ApiError::Internal("Invalid root message content".to_string()))
+}
+
+/// The root message is the conversation in v2, not a message
+fn message_response(images: &ImageService, message: Message) -> Result<MessageResponse, ApiError> {
+    MessageResponse::from_domain(images.presign(message)).ok_or_else(message_not_found)
+}
+
+fn count_nodes(nodes: &[TreeNode]) -> usize {
+    let mut count = 0;
+    let mut stack: Vec<&TreeNode> = nodes.iter().collect();
+    while let Some(node) = stack.pop() {
+        count += 1;
+        stack.extend(&node.children);
+    }
+    count
+}
@@ -0,0 +1,9 @@
+//! `/api/v2`: DTOs that break compatibility with v1 (typed roles, the root
+//! message folded into the conversation, nested trees, `owner`) while both
+//! versions share the services underneath. v1 keeps its shapes unchanged.
+
+pub mod dto;
+pub mod handlers;
+pub mod routes;
+
+pub use routes::routes;
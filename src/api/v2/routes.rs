@@ -0,0 +1,38 @@
+use axum::{
+    Router,
+    routing::{MethodRouter, get, post},
+};
+
+use crate::api::AppState;
+
+use super::handlers;
+
+/// Routes of `/api/v2`, served by the same services as v1. The router
+/// mounting them adds the shared limits and middleware; `expensive` wraps
+/// routes in the limit for expensive endpoints.
+pub fn routes(
+    expensive: impl Fn(MethodRouter<AppState>) -> MethodRouter<AppState>,
+) -> Router<AppState> {
+    Router::new()
+        .route("/api/v2/conversations", post(handlers::create_conversation))
+        .route(
+            "/api/v2/conversations/{id}",
+            get(handlers::get_conversation),
+        )
+        .route(
+            "/api/v2/conversations/{id}/tree",
+            expensive(get(handlers::get_conversation_tree)),
+        )
+        .route(
+            "/api/v2/conversations/{id}/messages",
+            post(handlers::create_message),
+        )
+        .route(
+            "/api/v2/conversations/{id}/messages/{message_id}",
+            get(handlers::get_message),
+        )
+        .route(
+            "/api/v2/conversations/{id}/messages/{message_id}/lineage",
+            get(handlers::get_message_lineage),
+        )
+}
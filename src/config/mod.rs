@@ -1,3 +1,14 @@
+pub mod secrets;
 pub mod settings;
 
-pub use settings::{AppConfig, ScyllaConfig, Settings};
+pub use secrets::{SecretsError, SecretsProvider};
+pub use settings::{
+    AccessLogConfig, AdminConfig, AnalyticsConfig, AppConfig, AuthConfig, AzureConfig,
+    BatchStrategy, BlobsConfig, BranchCacheConfig, BranchesConfig, CdcConfig, ConfigError,
+    ContentConfig, ContentProcessorKind, EmbedConfig, EncryptionConfig, ErrorFormat, ErrorsConfig,
+    ExecutionProfiles, ExportsConfig, FetchConfig, ForkConfig, GcsConfig, ImagesConfig, JobsConfig,
+    LegalHoldsConfig, LogFormat, LoggingConfig, MailboxesConfig, ObjectStoreConfig,
+    ObjectStoreProvider, OidcIdentityClaim, PiiConfig, ProfileOverrides, S3Config, SchedulerConfig,
+    ScyllaConfig, SecretsConfig, SecretsProviderKind, ServerConfig, Settings, StorageBackend,
+    StorageConfig, TrendingConfig, WebhooksConfig,
+};
@@ -0,0 +1,188 @@
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use crate::utils::http::{HttpClient, HttpError};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Secret names looked up in a provider and the env vars used by the env provider
+pub const SECRET_NAMES: &[(&str, &str)] = &[
+    ("scylla_username", "SCYLLA_USERNAME"),
+    ("scylla_password", "SCYLLA_PASSWORD"),
+    ("s3_access_key", "S3_ACCESS_KEY"),
+    ("s3_secret_key", "S3_SECRET_KEY"),
+    ("embed_token_secret", "EMBED_TOKEN_SECRET"),
+];
+
+#[derive(Debug, thiserror::Error)]
+pub enum SecretsError {
+    #[error("secrets request failed: {0}")]
+    Request(String),
+
+    #[error("unexpected secrets response: {0}")]
+    InvalidResponse(String),
+}
+
+/// Source of the credentials that should not be injected as plain env vars
+pub trait SecretsProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Fetch the available secrets, keyed by the names in [`SECRET_NAMES`]
+    fn fetch(&self) -> BoxFuture<'_, Result<HashMap<String, String>, SecretsError>>;
+}
+
+/// Reads secrets from env vars (the historical behaviour)
+pub struct EnvSecrets;
+
+impl SecretsProvider for EnvSecrets {
+    fn name(&self) -> &'static str {
+        "env"
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<HashMap<String, String>, SecretsError>> {
+        Box::pin(async {
+            Ok(SECRET_NAMES
+                .iter()
+                .filter_map(|(name, var)| env::var(var).ok().map(|v| (name.to_string(), v)))
+                .collect())
+        })
+    }
+}
+
+/// Reads a KV v2 secret from HashiCorp Vault, directly or through a Vault Agent listener
+pub struct VaultSecrets {
+    pub addr: String,
+    pub token: String,
+    pub mount: String,
+    pub path: String,
+}
+
+impl SecretsProvider for VaultSecrets {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<HashMap<String, String>, SecretsError>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/v1/{}/data/{}",
+                self.addr.trim_end_matches('/'),
+                self.mount.trim_matches('/'),
+                self.path.trim_matches('/')
+            );
+            let response = get_json(&url, &[("X-Vault-Token", &self.token)]).await?;
+            vault_values(&response)
+        })
+    }
+}
+
+/// Reads a secret from AWS Secrets Manager through the Secrets Manager Agent's local endpoint
+pub struct AwsSecretsManager {
+    pub endpoint: String,
+    pub secret_id: String,
+    pub token: String,
+}
+
+impl SecretsProvider for AwsSecretsManager {
+    fn name(&self) -> &'static str {
+        "aws"
+    }
+
+    fn fetch(&self) -> BoxFuture<'_, Result<HashMap<String, String>, SecretsError>> {
+        Box::pin(async move {
+            let url = format!(
+                "{}/secretsmanager/get?secretId={}",
+                self.endpoint.trim_end_matches('/'),
+                percent_encode(&self.secret_id)
+            );
+            let response =
+                get_json(&url, &[("X-Aws-Parameters-Secrets-Token", &self.token)]).await?;
+            aws_values(&response)
+        })
+    }
+}
+
+/// Extract the key/value pairs of a Vault KV v2 read response
+fn vault_values(response: &serde_json::Value) -> Result<HashMap<String, String>, SecretsError> {
+    let data = response
+        .pointer("/data/data")
+        .and_then(|v| v.as_object())
+        .ok_or_else(|| SecretsError::InvalidResponse("missing data.data".to_string()))?;
+
+    Ok(string_values(data))
+}
+
+/// Extract the key/value pairs of a JSON `SecretString` returned by Secrets Manager
+fn aws_values(response: &serde_json::Value) -> Result<HashMap<String, String>, SecretsError> {
+    let secret = response
+        .get("SecretString")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| SecretsError::InvalidResponse("missing SecretString".to_string()))?;
+    let secret: serde_json::Value = serde_json::from_str(secret)
+        .map_err(|e| SecretsError::InvalidResponse(format!("SecretString is not JSON: {}", e)))?;
+    let data = secret.as_object().ok_or_else(|| {
+        SecretsError::InvalidResponse("SecretString is not a JSON object".to_string())
+    })?;
+
+    Ok(string_values(data))
+}
+
+fn string_values(data: &serde_json::Map<String, serde_json::Value>) -> HashMap<String, String> {
+    data.iter()
+        .map(|(k, v)| {
+            let value = v
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| v.to_string());
+            (k.clone(), value)
+        })
+        .collect()
+}
+
+/// GET a secrets agent's JSON response
+async fn get_json(url: &str, headers: &[(&str, &str)]) -> Result<serde_json::Value, SecretsError> {
+    HttpClient::configured()
+        .get_json(url, headers, REQUEST_TIMEOUT)
+        .await
+        .map_err(|e| match e {
+            HttpError::InvalidResponse(_, e) => SecretsError::InvalidResponse(e),
+            e => SecretsError::Request(e.to_string()),
+        })
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_responses() {
+        let vault = serde_json::json!({
+            "data": { "data": { "scylla_password": "hunter2" }, "metadata": { "version": 3 } }
+        });
+        assert_eq!(vault_values(&vault).unwrap()["scylla_password"], "hunter2");
+
+        let aws = serde_json::json!({
+            "Name": "aigc-history",
+            "SecretString": "{\"s3_secret_key\":\"abc\",\"port\":9042}"
+        });
+        let values = aws_values(&aws).unwrap();
+        assert_eq!(values["s3_secret_key"], "abc");
+        assert_eq!(values["port"], "9042");
+
+        assert_eq!(percent_encode("arn:aws:x/y"), "arn%3Aaws%3Ax%2Fy");
+    }
+}
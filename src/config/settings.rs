@@ -1,17 +1,118 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::collections::HashMap;
 use std::env;
+use std::path::Path;
+
+use super::secrets::{AwsSecretsManager, EnvSecrets, SecretsProvider, VaultSecrets};
+use crate::domain::BranchNaming;
 
 #[derive(Debug, Clone)]
 pub struct Settings {
     pub server: ServerConfig,
     pub scylla: ScyllaConfig,
+    pub object_store: ObjectStoreConfig,
     pub s3: S3Config,
+    pub gcs: GcsConfig,
+    pub azure: AzureConfig,
     pub app: AppConfig,
+    pub branch_cache: BranchCacheConfig,
+    pub branches: BranchesConfig,
+    pub fork: ForkConfig,
+    pub jobs: JobsConfig,
+    pub exports: ExportsConfig,
+    pub legal_holds: LegalHoldsConfig,
+    pub webhooks: WebhooksConfig,
+    pub fetch: FetchConfig,
+    pub access_log: AccessLogConfig,
+    pub secrets: SecretsConfig,
+    pub storage: StorageConfig,
+    pub logging: LoggingConfig,
+    pub trending: TrendingConfig,
+    pub scheduler: SchedulerConfig,
+    pub pii: PiiConfig,
+    pub content: ContentConfig,
+    pub images: ImagesConfig,
+    pub analytics: AnalyticsConfig,
+    pub cdc: CdcConfig,
+    pub blobs: BlobsConfig,
+    pub encryption: EncryptionConfig,
+    pub mailboxes: MailboxesConfig,
+    pub admin: AdminConfig,
+    pub auth: AuthConfig,
+    pub embed: EmbedConfig,
+    pub errors: ErrorsConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageConfig {
+    pub backend: StorageBackend,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StorageBackend {
+    Scylla,
+    /// Process-local storage; nothing survives a restart
+    Memory,
+}
+
+impl std::str::FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "scylla" => Ok(StorageBackend::Scylla),
+            "memory" => Ok(StorageBackend::Memory),
+            other => Err(format!(
+                "unknown storage backend `{}` (expected scylla or memory)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable, colored output
+    Pretty,
+    /// One JSON object per line, with span and trace ids
+    Json,
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!(
+                "unknown log format `{}` (expected pretty or json)",
+                other
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// How long in-flight requests may take to finish once shutdown starts
+    pub shutdown_grace_period_secs: u64,
+    /// How long readiness reports failure after a shutdown signal before
+    /// draining starts, so load balancers stop routing here first
+    pub shutdown_ready_delay_secs: u64,
+    /// Requests handled at once before new ones are shed with 503
+    pub max_concurrent_requests: usize,
+    /// Separate, lower limit for expensive endpoints (tree, fork, export, import)
+    pub max_concurrent_expensive_requests: usize,
+    /// PEM certificate chain; with `tls_key_path`, HTTPS is served directly
+    pub tls_cert_path: Option<String>,
+    /// PEM private key of the certificate
+    pub tls_key_path: Option<String>,
+    /// PEM CA certificates client certificates must chain to; clients
+    /// without one are refused (mTLS)
+    pub tls_client_ca_path: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -20,6 +121,190 @@ pub struct ScyllaConfig {
     pub keyspace: String,
     pub username: Option<String>,
     pub password: Option<String>,
+    /// Datacenter preferred by the load balancer; other DCs are only used for failover
+    pub local_dc: Option<String>,
+    /// Consistency level name, e.g. `local_quorum`
+    pub consistency: String,
+    pub request_timeout_ms: u64,
+    /// Timeout for batches and full-conversation scans
+    pub bulk_timeout_ms: u64,
+    /// Rows fetched per page by paged reads
+    pub page_size: i32,
+    pub connections_per_shard: usize,
+    /// Timeout for opening a connection to a node
+    pub connect_timeout_ms: u64,
+    /// Extra executions of a slow interactive read sent to other replicas,
+    /// each `speculative_delay_ms` after the previous one; 0 disables them
+    pub speculative_retries: usize,
+    pub speculative_delay_ms: u64,
+    /// Replication map of the keyspace, substituted for `{{replication}}` in
+    /// migrations, e.g. `{'class': 'NetworkTopologyStrategy', 'dc1': 3}`
+    pub replication: String,
+    /// Compaction map substituted for `{{compaction}}` in migrations
+    pub compaction: String,
+    /// Default TTL of the change feed table (`{{change_ttl}}`); 0 keeps entries forever
+    pub change_ttl_secs: u64,
+    /// Default TTL of the notifications table (`{{notification_ttl}}`)
+    pub notification_ttl_secs: u64,
+    /// Default TTL of the access log table (`{{access_log_ttl}}`)
+    pub access_log_ttl_secs: u64,
+    /// How the messages and events copied by forks and duplicates are written
+    pub fork_batch_strategy: BatchStrategy,
+    /// Inserts in flight at once with the `concurrent` batch strategy
+    pub write_parallelism: usize,
+    /// Per-class overrides of consistency, timeout and retries
+    pub profiles: ExecutionProfiles,
+    /// How long startup keeps retrying to connect and migrate while Scylla is
+    /// unreachable or not ready; 0 fails on the first error
+    pub startup_max_wait_secs: u64,
+    /// First wait between startup attempts; doubles after each attempt
+    pub startup_backoff_ms: u64,
+    pub startup_max_backoff_ms: u64,
+}
+
+/// How a group of rows is written
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatchStrategy {
+    /// One logged batch: the rows are applied together or not at all
+    Logged,
+    /// One unlogged batch: no batchlog round trip, but a failure may leave
+    /// some of the rows written
+    Unlogged,
+    /// Separate inserts, `write_parallelism` at a time: the highest
+    /// throughput, with each row written on its own
+    Concurrent,
+}
+
+impl std::str::FromStr for BatchStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "logged" => Ok(BatchStrategy::Logged),
+            "unlogged" => Ok(BatchStrategy::Unlogged),
+            "concurrent" => Ok(BatchStrategy::Concurrent),
+            other => Err(format!(
+                "unknown batch strategy `{}` (expected logged, unlogged or concurrent)",
+                other
+            )),
+        }
+    }
+}
+
+/// Overrides for the execution profile of one class of statements
+#[derive(Debug, Clone, Default)]
+pub struct ProfileOverrides {
+    /// Falls back to `consistency`
+    pub consistency: Option<String>,
+    /// Falls back to `request_timeout_ms` for interactive statements and
+    /// `bulk_timeout_ms` for bulk ones
+    pub timeout_ms: Option<u64>,
+    /// Retries per statement at most, for errors the driver deems retryable;
+    /// the driver's default policy when unset, none when 0
+    pub retries: Option<u32>,
+}
+
+/// The classes of statements repositories choose from
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionProfiles {
+    /// Single-partition reads on the request path
+    pub interactive_read: ProfileOverrides,
+    /// Single-row writes on the request path
+    pub interactive_write: ProfileOverrides,
+    /// Full-conversation and paged scans
+    pub bulk_read: ProfileOverrides,
+    /// Batches, such as the copies made by forks
+    pub bulk_write: ProfileOverrides,
+}
+
+impl ExecutionProfiles {
+    /// Profile names as used in setting keys, with their overrides
+    pub fn iter(&self) -> [(&'static str, &ProfileOverrides); 4] {
+        [
+            ("interactive_read", &self.interactive_read),
+            ("interactive_write", &self.interactive_write),
+            ("bulk_read", &self.bulk_read),
+            ("bulk_write", &self.bulk_write),
+        ]
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut ProfileOverrides> {
+        match name {
+            "interactive_read" => Some(&mut self.interactive_read),
+            "interactive_write" => Some(&mut self.interactive_write),
+            "bulk_read" => Some(&mut self.bulk_read),
+            "bulk_write" => Some(&mut self.bulk_write),
+            _ => None,
+        }
+    }
+}
+
+impl ScyllaConfig {
+    pub fn consistency(&self) -> Result<scylla::statement::Consistency, String> {
+        parse_consistency(&self.consistency)
+    }
+
+    /// Consistency of a class of statements, `consistency` unless overridden
+    pub fn profile_consistency(
+        &self,
+        overrides: &ProfileOverrides,
+    ) -> Result<scylla::statement::Consistency, String> {
+        parse_consistency(
+            overrides
+                .consistency
+                .as_deref()
+                .unwrap_or(&self.consistency),
+        )
+    }
+}
+
+fn parse_consistency(name: &str) -> Result<scylla::statement::Consistency, String> {
+    use scylla::statement::Consistency;
+
+    match name.to_ascii_lowercase().as_str() {
+        "any" => Ok(Consistency::Any),
+        "one" => Ok(Consistency::One),
+        "two" => Ok(Consistency::Two),
+        "three" => Ok(Consistency::Three),
+        "quorum" => Ok(Consistency::Quorum),
+        "all" => Ok(Consistency::All),
+        "local_quorum" => Ok(Consistency::LocalQuorum),
+        "each_quorum" => Ok(Consistency::EachQuorum),
+        "local_one" => Ok(Consistency::LocalOne),
+        other => Err(format!("unknown consistency level `{}`", other)),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectStoreConfig {
+    pub provider: ObjectStoreProvider,
+}
+
+/// Where images, exports and offloaded contents are stored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectStoreProvider {
+    /// S3 or a compatible store such as MinIO (`s3.*`)
+    S3,
+    /// Google Cloud Storage with an HMAC key (`gcs.*`)
+    Gcs,
+    /// Azure Blob Storage with the account key (`azure.*`)
+    Azure,
+}
+
+impl std::str::FromStr for ObjectStoreProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "s3" | "minio" => Ok(ObjectStoreProvider::S3),
+            "gcs" => Ok(ObjectStoreProvider::Gcs),
+            "azure" => Ok(ObjectStoreProvider::Azure),
+            other => Err(format!(
+                "unknown object store provider `{}` (expected s3, gcs or azure)",
+                other
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -31,51 +316,1798 @@ pub struct S3Config {
     pub region: String,
 }
 
+#[derive(Debug, Clone)]
+pub struct GcsConfig {
+    /// XML API endpoint
+    pub endpoint: String,
+    /// HMAC key of a service account
+    pub access_key: String,
+    pub secret_key: String,
+    pub bucket: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct AzureConfig {
+    /// Blob service endpoint; Azurite's includes the account
+    pub endpoint: String,
+    pub account: String,
+    /// Base64 account key
+    pub access_key: String,
+    pub container: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// Fraction of successful requests logged (0.0-1.0); failed requests are always logged
+    pub sample_rate: f64,
+    /// Log redacted JSON request and response bodies; for debugging only
+    pub log_bodies: bool,
+    pub max_body_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct TrendingConfig {
+    /// How often the trending ranking is recomputed
+    pub interval_secs: u64,
+    /// Days of activity considered; each older day counts half as much as the next
+    pub window_days: u32,
+    /// Number of conversations kept in the ranking
+    pub size: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct AppConfig {
     pub max_lineage_depth: usize,
     pub max_batch_size: usize,
+    /// Replies a single message may have
+    pub max_children_per_message: usize,
+    /// Messages a conversation may hold, the root included
+    pub max_messages_per_conversation: usize,
+    /// Items list endpoints return when the request has no `limit`
+    pub default_page_size: usize,
+    /// Largest `limit` list endpoints honour; larger ones are clamped
+    pub max_page_size: usize,
 }
 
-impl Settings {
-    pub fn from_env() -> Result<Self, String> {
-        Ok(Settings {
+#[derive(Debug, Clone)]
+pub struct BranchCacheConfig {
+    /// Branches whose message lists are kept in memory; 0 disables the cache
+    pub capacity: usize,
+    /// How long a cached list may be served. Leaf moves show up at once;
+    /// this bounds how stale edits to the messages themselves can get.
+    pub ttl_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Run recurring background tasks in this instance; with several replicas,
+    /// enabling it on one is enough
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BranchesConfig {
+    /// How branches created without a name are named, for users who didn't
+    /// pick a scheme in their preferences
+    pub default_naming: BranchNaming,
+}
+
+#[derive(Debug, Clone)]
+pub struct ForkConfig {
+    /// Most messages a fork may copy; forks are copied within the request
+    pub max_source_messages: usize,
+    /// Most forks a user may create per hour, counted by each instance
+    pub max_per_user_per_hour: usize,
+    /// Batches of copied messages inserted at once
+    pub chunk_parallelism: usize,
+    /// Retries of a batch of copied messages that failed to insert
+    pub chunk_retries: u32,
+    /// Wait before the first retry, doubled for each further one
+    pub chunk_retry_backoff_ms: u64,
+    /// An unfinished fork not updated for this long is taken for abandoned,
+    /// e.g. by a crashed instance, and deleted
+    pub stale_after_secs: u64,
+    /// How often to look for abandoned forks
+    pub recovery_interval_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct JobsConfig {
+    /// A running job not updated for this long is taken for interrupted and
+    /// resumed; running jobs refresh themselves three times as often
+    pub stale_after_secs: u64,
+    /// How often to look for interrupted jobs
+    pub recovery_interval_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExportsConfig {
+    /// Validity of the download URL of a stored export
+    pub download_expiry_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhooksConfig {
+    /// How often new changes are delivered to subscribers
+    pub interval_secs: u64,
+    /// Changes read per subscription and run
+    pub batch_size: usize,
+    /// How long a subscriber may take to answer a delivery
+    pub timeout_secs: u64,
+}
+
+/// Limits on requests the service makes to URLs it was given, such as
+/// webhook deliveries
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    /// Let URLs point at loopback, private and link-local addresses. Only
+    /// for development, or receivers on a trusted network.
+    pub allow_private_networks: bool,
+    /// Bytes of a response read at most; the rest is dropped
+    pub max_response_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessLogConfig {
+    /// Record reads of conversations for their owners
+    pub enabled: bool,
+    /// Fraction of reads recorded (0.0-1.0)
+    pub sample_rate: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct LegalHoldsConfig {
+    /// How often the change feeds of held conversations are rewritten without
+    /// an expiry, catching changes recorded since; keep it well below
+    /// `scylla.change_ttl_secs`
+    pub retention_interval_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct PiiConfig {
+    /// Mask emails, phone numbers and card numbers in message text before it is stored
+    pub scrub_on_write: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ContentConfig {
+    /// Processors new messages go through before they are stored, in order
+    pub processors: Vec<ContentProcessorKind>,
+    /// Schemes image URLs and Markdown links may use, checked by `url_schemes`
+    pub allowed_url_schemes: Vec<String>,
+    /// Detect the language of new messages that don't give one
+    pub detect_language: bool,
+}
+
+/// A built-in step of the content pipeline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentProcessorKind {
+    /// Trim whitespace around text and summaries
+    TrimWhitespace,
+    /// Unix line endings, no trailing whitespace but hard breaks, and at
+    /// most one blank line in a row, outside code blocks
+    NormalizeMarkdown,
+    /// Drop `utm_*`, `fbclid` and other tracking parameters from image URLs
+    StripTrackingParams,
+    /// Refuse image URLs and Markdown links with a scheme not in
+    /// `allowed_url_schemes`
+    UrlSchemes,
+}
+
+impl std::str::FromStr for ContentProcessorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "trim_whitespace" => Ok(ContentProcessorKind::TrimWhitespace),
+            "normalize_markdown" => Ok(ContentProcessorKind::NormalizeMarkdown),
+            "strip_tracking_params" => Ok(ContentProcessorKind::StripTrackingParams),
+            "url_schemes" => Ok(ContentProcessorKind::UrlSchemes),
+            other => Err(format!(
+                "unknown content processor `{}` (expected trim_whitespace, normalize_markdown, strip_tracking_params or url_schemes)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ImagesConfig {
+    /// Delete images from the bucket once no conversation references them.
+    /// Enable only after `--backfill-image-refs` has indexed existing messages.
+    pub gc_enabled: bool,
+    pub gc_interval_secs: u64,
+    /// Objects deleted per run at most
+    pub gc_batch_size: usize,
+    /// Serve links to images in the bucket as presigned URLs, so the bucket
+    /// can stay private
+    pub presign_urls: bool,
+    /// How long a presigned URL stays valid; at most 7 days
+    pub presign_expiry_secs: u64,
+    /// Schemes image URLs of new messages may use. Links into the bucket
+    /// are always accepted.
+    pub allowed_url_schemes: Vec<String>,
+    /// Longest image URL accepted, in bytes
+    pub max_url_length: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnalyticsConfig {
+    /// Compute daily rollups in the background. Each run scans every message.
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Days recomputed per run, today included; older rollups are final
+    pub window_days: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct CdcConfig {
+    /// Enable Scylla CDC on the lineage table and copy its changes into the
+    /// outbox. Needs the Scylla backend.
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Changes younger than this are left for the next run, since CDC rows
+    /// may show up slightly out of order
+    pub lag_secs: u64,
+    /// Longest stretch of the CDC log read per run, so a consumer that fell
+    /// behind catches up in steps
+    pub max_window_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlobsConfig {
+    /// Store message contents larger than `offload_threshold_bytes` in the
+    /// S3 bucket, keyed by their hash, instead of in the lineage table. Needs
+    /// the Scylla backend. Offloaded contents are read back either way.
+    pub offload_enabled: bool,
+    pub offload_threshold_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncryptionConfig {
+    /// Base64 AES-256 master keys by key ID. While any is set, message
+    /// contents and event payloads are encrypted with per-conversation data
+    /// keys wrapped by the active one. Needs the Scylla backend. Keep a
+    /// retired key until a rotation has re-wrapped every data key it wraps.
+    pub master_keys: HashMap<String, String>,
+    /// Master key new data keys are wrapped with, and rotations re-wrap to
+    pub active_key_id: Option<String>,
+    /// Unwrapped data keys kept in memory
+    pub key_cache_capacity: usize,
+}
+
+impl EncryptionConfig {
+    pub fn enabled(&self) -> bool {
+        !self.master_keys.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MailboxesConfig {
+    /// Run appends, moves and branch updates of a conversation one at a
+    /// time, in arrival order, on one task per conversation. Only orders
+    /// writes made through this instance.
+    pub enabled: bool,
+    /// Writes queued per conversation before further ones wait
+    pub capacity: usize,
+    /// How long a conversation's task outlives its last write
+    pub idle_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdminConfig {
+    /// Users allowed to read deployment-wide data, such as usage per model
+    pub users: Vec<String>,
+}
+
+impl AdminConfig {
+    pub fn is_admin(&self, user_id: &str) -> bool {
+        self.users.iter().any(|admin| admin == user_id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// Let requests without an identity `GET` the read endpoints of public
+    /// conversations; everything else still requires one
+    pub anonymous_public_reads: bool,
+    /// Tokens of the service accounts by account name. A request with one
+    /// in `X-Service-Token` acts as `service:<name>`.
+    pub service_tokens: HashMap<String, String>,
+    /// Requests a service account may make per minute, per instance
+    pub service_requests_per_minute: u32,
+    /// Per-account overrides of `service_requests_per_minute`
+    pub service_rate_limits: HashMap<String, u32>,
+    /// OpenID Connect issuer whose ID tokens, in `Authorization: Bearer`,
+    /// identify callers instead of `X-User-ID`
+    pub oidc_issuer: Option<String>,
+    /// Client ID ID tokens must be issued to (their `aud`)
+    pub oidc_client_id: Option<String>,
+    /// Claim the caller's identity is taken from
+    pub oidc_identity_claim: OidcIdentityClaim,
+    /// How long the issuer's signing keys are used before being fetched again
+    pub oidc_jwks_cache_secs: u64,
+}
+
+/// ID token claim that becomes the caller's identity, e.g. in `created_by`
+/// and shares
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OidcIdentityClaim {
+    /// `sub`, stable for the user at the issuer
+    Subject,
+    /// `email`, only taken when `email_verified` is true
+    Email,
+}
+
+impl std::str::FromStr for OidcIdentityClaim {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sub" => Ok(OidcIdentityClaim::Subject),
+            "email" => Ok(OidcIdentityClaim::Email),
+            other => Err(format!(
+                "unknown identity claim `{}` (expected sub or email)",
+                other
+            )),
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn service_rate_limit(&self, name: &str) -> u32 {
+        self.service_rate_limits
+            .get(name)
+            .copied()
+            .unwrap_or(self.service_requests_per_minute)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbedConfig {
+    /// Key signing embed tokens; embed tokens are disabled while unset.
+    /// Rotating it invalidates every token issued.
+    pub secret: Option<String>,
+    /// Validity of a token when the request names none
+    pub default_ttl_secs: u64,
+    /// Longest validity a request may ask for
+    pub max_ttl_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    /// `{"error": "..."}`
+    Json,
+    /// RFC 7807 `application/problem+json`
+    Problem,
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(ErrorFormat::Json),
+            "problem" => Ok(ErrorFormat::Problem),
+            other => Err(format!(
+                "unknown error format `{}` (expected json or problem)",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ErrorsConfig {
+    /// Format of error bodies for clients that don't ask for
+    /// `application/problem+json` in `Accept`
+    pub format: ErrorFormat,
+    /// Problem `type` URIs are this followed by the error code; `about:blank` while unset
+    pub problem_type_base_url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SecretsConfig {
+    pub provider: SecretsProviderKind,
+    pub vault_addr: Option<String>,
+    pub vault_token: Option<String>,
+    pub vault_mount: String,
+    pub vault_path: String,
+    pub aws_endpoint: String,
+    pub aws_secret_id: String,
+    pub aws_token: Option<String>,
+}
+
+/// Where the credentials kept out of plain env vars come from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SecretsProviderKind {
+    /// Env vars, as before secrets providers existed
+    Env,
+    /// A HashiCorp Vault KV v2 secret (`secrets.vault_*`)
+    Vault,
+    /// An AWS Secrets Manager secret through its agent (`secrets.aws_*`)
+    Aws,
+}
+
+impl std::str::FromStr for SecretsProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "env" => Ok(SecretsProviderKind::Env),
+            "vault" => Ok(SecretsProviderKind::Vault),
+            "aws" => Ok(SecretsProviderKind::Aws),
+            other => Err(format!(
+                "unknown secrets provider `{}` (expected env, vault or aws)",
+                other
+            )),
+        }
+    }
+}
+
+/// Longest validity S3 accepts for a presigned URL (7 days)
+const MAX_PRESIGN_EXPIRY_SECS: u64 = 604_800;
+
+/// Shortest embed token signing key accepted
+const MIN_EMBED_SECRET_LEN: usize = 32;
+
+/// Shortest service account token accepted
+const MIN_SERVICE_TOKEN_LEN: usize = 32;
+
+/// Every problem found while loading the configuration, reported together
+#[derive(Debug, thiserror::Error)]
+#[error("invalid configuration: {}", .errors.join("; "))]
+pub struct ConfigError {
+    pub errors: Vec<String>,
+}
+
+/// Setting keys (`section.name` in the config file) and the env vars overriding them
+const SETTINGS: &[(&str, &str)] = &[
+    ("server.host", "SERVER_HOST"),
+    ("server.port", "SERVER_PORT"),
+    (
+        "server.shutdown_grace_period_secs",
+        "SHUTDOWN_GRACE_PERIOD_SECS",
+    ),
+    (
+        "server.shutdown_ready_delay_secs",
+        "SHUTDOWN_READY_DELAY_SECS",
+    ),
+    ("server.max_concurrent_requests", "MAX_CONCURRENT_REQUESTS"),
+    (
+        "server.max_concurrent_expensive_requests",
+        "MAX_CONCURRENT_EXPENSIVE_REQUESTS",
+    ),
+    ("server.tls_cert_path", "TLS_CERT_PATH"),
+    ("server.tls_key_path", "TLS_KEY_PATH"),
+    ("server.tls_client_ca_path", "TLS_CLIENT_CA_PATH"),
+    ("scylla.nodes", "SCYLLA_NODES"),
+    ("scylla.keyspace", "SCYLLA_KEYSPACE"),
+    ("scylla.username", "SCYLLA_USERNAME"),
+    ("scylla.password", "SCYLLA_PASSWORD"),
+    ("scylla.local_dc", "SCYLLA_LOCAL_DC"),
+    ("scylla.consistency", "SCYLLA_CONSISTENCY"),
+    ("scylla.request_timeout_ms", "SCYLLA_REQUEST_TIMEOUT_MS"),
+    ("scylla.bulk_timeout_ms", "SCYLLA_BULK_TIMEOUT_MS"),
+    ("scylla.page_size", "SCYLLA_PAGE_SIZE"),
+    (
+        "scylla.connections_per_shard",
+        "SCYLLA_CONNECTIONS_PER_SHARD",
+    ),
+    ("scylla.connect_timeout_ms", "SCYLLA_CONNECT_TIMEOUT_MS"),
+    ("scylla.speculative_retries", "SCYLLA_SPECULATIVE_RETRIES"),
+    ("scylla.speculative_delay_ms", "SCYLLA_SPECULATIVE_DELAY_MS"),
+    ("scylla.replication", "SCYLLA_REPLICATION"),
+    ("scylla.compaction", "SCYLLA_COMPACTION"),
+    ("scylla.change_ttl_secs", "SCYLLA_CHANGE_TTL_SECS"),
+    (
+        "scylla.notification_ttl_secs",
+        "SCYLLA_NOTIFICATION_TTL_SECS",
+    ),
+    ("scylla.access_log_ttl_secs", "SCYLLA_ACCESS_LOG_TTL_SECS"),
+    ("scylla.fork_batch_strategy", "SCYLLA_FORK_BATCH_STRATEGY"),
+    ("scylla.write_parallelism", "SCYLLA_WRITE_PARALLELISM"),
+    (
+        "scylla.startup_max_wait_secs",
+        "SCYLLA_STARTUP_MAX_WAIT_SECS",
+    ),
+    ("scylla.startup_backoff_ms", "SCYLLA_STARTUP_BACKOFF_MS"),
+    (
+        "scylla.startup_max_backoff_ms",
+        "SCYLLA_STARTUP_MAX_BACKOFF_MS",
+    ),
+    (
+        "scylla.interactive_read_consistency",
+        "SCYLLA_INTERACTIVE_READ_CONSISTENCY",
+    ),
+    (
+        "scylla.interactive_read_timeout_ms",
+        "SCYLLA_INTERACTIVE_READ_TIMEOUT_MS",
+    ),
+    (
+        "scylla.interactive_read_retries",
+        "SCYLLA_INTERACTIVE_READ_RETRIES",
+    ),
+    (
+        "scylla.interactive_write_consistency",
+        "SCYLLA_INTERACTIVE_WRITE_CONSISTENCY",
+    ),
+    (
+        "scylla.interactive_write_timeout_ms",
+        "SCYLLA_INTERACTIVE_WRITE_TIMEOUT_MS",
+    ),
+    (
+        "scylla.interactive_write_retries",
+        "SCYLLA_INTERACTIVE_WRITE_RETRIES",
+    ),
+    (
+        "scylla.bulk_read_consistency",
+        "SCYLLA_BULK_READ_CONSISTENCY",
+    ),
+    ("scylla.bulk_read_timeout_ms", "SCYLLA_BULK_READ_TIMEOUT_MS"),
+    ("scylla.bulk_read_retries", "SCYLLA_BULK_READ_RETRIES"),
+    (
+        "scylla.bulk_write_consistency",
+        "SCYLLA_BULK_WRITE_CONSISTENCY",
+    ),
+    (
+        "scylla.bulk_write_timeout_ms",
+        "SCYLLA_BULK_WRITE_TIMEOUT_MS",
+    ),
+    ("scylla.bulk_write_retries", "SCYLLA_BULK_WRITE_RETRIES"),
+    ("object_store.provider", "OBJECT_STORE_PROVIDER"),
+    ("s3.endpoint", "S3_ENDPOINT"),
+    ("s3.access_key", "S3_ACCESS_KEY"),
+    ("s3.secret_key", "S3_SECRET_KEY"),
+    ("s3.bucket", "S3_BUCKET"),
+    ("s3.region", "S3_REGION"),
+    ("gcs.endpoint", "GCS_ENDPOINT"),
+    ("gcs.access_key", "GCS_ACCESS_KEY"),
+    ("gcs.secret_key", "GCS_SECRET_KEY"),
+    ("gcs.bucket", "GCS_BUCKET"),
+    ("azure.endpoint", "AZURE_STORAGE_ENDPOINT"),
+    ("azure.account", "AZURE_STORAGE_ACCOUNT"),
+    ("azure.access_key", "AZURE_STORAGE_KEY"),
+    ("azure.container", "AZURE_STORAGE_CONTAINER"),
+    ("app.max_lineage_depth", "MAX_LINEAGE_DEPTH"),
+    ("app.max_batch_size", "MAX_BATCH_SIZE"),
+    ("app.max_children_per_message", "MAX_CHILDREN_PER_MESSAGE"),
+    (
+        "app.max_messages_per_conversation",
+        "MAX_MESSAGES_PER_CONVERSATION",
+    ),
+    ("app.default_page_size", "DEFAULT_PAGE_SIZE"),
+    ("app.max_page_size", "MAX_PAGE_SIZE"),
+    ("branch_cache.capacity", "BRANCH_CACHE_CAPACITY"),
+    ("branch_cache.ttl_secs", "BRANCH_CACHE_TTL_SECS"),
+    ("branches.default_naming", "BRANCH_NAMING"),
+    ("fork.max_source_messages", "FORK_MAX_SOURCE_MESSAGES"),
+    ("fork.max_per_user_per_hour", "FORK_MAX_PER_USER_PER_HOUR"),
+    ("fork.chunk_parallelism", "FORK_CHUNK_PARALLELISM"),
+    ("fork.chunk_retries", "FORK_CHUNK_RETRIES"),
+    ("fork.chunk_retry_backoff_ms", "FORK_CHUNK_RETRY_BACKOFF_MS"),
+    ("fork.stale_after_secs", "FORK_STALE_AFTER_SECS"),
+    ("fork.recovery_interval_secs", "FORK_RECOVERY_INTERVAL_SECS"),
+    ("jobs.stale_after_secs", "JOBS_STALE_AFTER_SECS"),
+    ("jobs.recovery_interval_secs", "JOBS_RECOVERY_INTERVAL_SECS"),
+    (
+        "exports.download_expiry_secs",
+        "EXPORT_DOWNLOAD_EXPIRY_SECS",
+    ),
+    (
+        "legal_holds.retention_interval_secs",
+        "LEGAL_HOLD_RETENTION_INTERVAL_SECS",
+    ),
+    ("webhooks.interval_secs", "WEBHOOK_INTERVAL_SECS"),
+    ("webhooks.batch_size", "WEBHOOK_BATCH_SIZE"),
+    ("webhooks.timeout_secs", "WEBHOOK_TIMEOUT_SECS"),
+    (
+        "fetch.allow_private_networks",
+        "FETCH_ALLOW_PRIVATE_NETWORKS",
+    ),
+    ("fetch.max_response_bytes", "FETCH_MAX_RESPONSE_BYTES"),
+    ("access_log.enabled", "ACCESS_LOG_ENABLED"),
+    ("access_log.sample_rate", "ACCESS_LOG_SAMPLE_RATE"),
+    ("storage.backend", "STORAGE_BACKEND"),
+    ("logging.format", "LOG_FORMAT"),
+    ("logging.sample_rate", "LOG_SAMPLE_RATE"),
+    ("logging.log_bodies", "LOG_BODIES"),
+    ("logging.max_body_bytes", "LOG_MAX_BODY_BYTES"),
+    ("trending.interval_secs", "TRENDING_INTERVAL_SECS"),
+    ("trending.window_days", "TRENDING_WINDOW_DAYS"),
+    ("trending.size", "TRENDING_SIZE"),
+    ("scheduler.enabled", "SCHEDULER_ENABLED"),
+    ("pii.scrub_on_write", "PII_SCRUB_ON_WRITE"),
+    ("content.processors", "CONTENT_PROCESSORS"),
+    ("content.allowed_url_schemes", "CONTENT_ALLOWED_URL_SCHEMES"),
+    ("content.detect_language", "CONTENT_DETECT_LANGUAGE"),
+    ("images.gc_enabled", "IMAGE_GC_ENABLED"),
+    ("images.gc_interval_secs", "IMAGE_GC_INTERVAL_SECS"),
+    ("images.gc_batch_size", "IMAGE_GC_BATCH_SIZE"),
+    ("images.presign_urls", "IMAGE_PRESIGN_URLS"),
+    ("images.presign_expiry_secs", "IMAGE_PRESIGN_EXPIRY_SECS"),
+    ("images.allowed_url_schemes", "IMAGE_ALLOWED_URL_SCHEMES"),
+    ("images.max_url_length", "IMAGE_MAX_URL_LENGTH"),
+    ("analytics.enabled", "ANALYTICS_ENABLED"),
+    ("analytics.interval_secs", "ANALYTICS_INTERVAL_SECS"),
+    ("analytics.window_days", "ANALYTICS_WINDOW_DAYS"),
+    ("cdc.enabled", "CDC_ENABLED"),
+    ("cdc.interval_secs", "CDC_INTERVAL_SECS"),
+    ("cdc.lag_secs", "CDC_LAG_SECS"),
+    ("cdc.max_window_secs", "CDC_MAX_WINDOW_SECS"),
+    ("blobs.offload_enabled", "BLOB_OFFLOAD_ENABLED"),
+    (
+        "blobs.offload_threshold_bytes",
+        "BLOB_OFFLOAD_THRESHOLD_BYTES",
+    ),
+    ("encryption.master_keys", "ENCRYPTION_MASTER_KEYS"),
+    ("encryption.active_key_id", "ENCRYPTION_ACTIVE_KEY_ID"),
+    (
+        "encryption.key_cache_capacity",
+        "ENCRYPTION_KEY_CACHE_CAPACITY",
+    ),
+    ("mailboxes.enabled", "CONVERSATION_MAILBOXES_ENABLED"),
+    ("mailboxes.capacity", "CONVERSATION_MAILBOX_CAPACITY"),
+    ("mailboxes.idle_secs", "CONVERSATION_MAILBOX_IDLE_SECS"),
+    ("admin.users", "ADMIN_USERS"),
+    ("auth.anonymous_public_reads", "AUTH_ANONYMOUS_PUBLIC_READS"),
+    ("auth.service_tokens", "SERVICE_ACCOUNT_TOKENS"),
+    (
+        "auth.service_requests_per_minute",
+        "SERVICE_ACCOUNT_REQUESTS_PER_MINUTE",
+    ),
+    ("auth.service_rate_limits", "SERVICE_ACCOUNT_RATE_LIMITS"),
+    ("auth.oidc_issuer", "OIDC_ISSUER"),
+    ("auth.oidc_client_id", "OIDC_CLIENT_ID"),
+    ("auth.oidc_identity_claim", "OIDC_IDENTITY_CLAIM"),
+    ("auth.oidc_jwks_cache_secs", "OIDC_JWKS_CACHE_SECS"),
+    ("embed.secret", "EMBED_TOKEN_SECRET"),
+    ("embed.default_ttl_secs", "EMBED_TOKEN_TTL_SECS"),
+    ("embed.max_ttl_secs", "EMBED_TOKEN_MAX_TTL_SECS"),
+    ("errors.format", "ERROR_FORMAT"),
+    ("errors.problem_type_base_url", "PROBLEM_TYPE_BASE_URL"),
+    ("secrets.provider", "SECRETS_PROVIDER"),
+    ("secrets.vault_addr", "VAULT_ADDR"),
+    ("secrets.vault_token", "VAULT_TOKEN"),
+    ("secrets.vault_mount", "VAULT_MOUNT"),
+    ("secrets.vault_path", "VAULT_SECRET_PATH"),
+    ("secrets.aws_endpoint", "AWS_SECRETS_AGENT_ENDPOINT"),
+    ("secrets.aws_secret_id", "AWS_SECRET_ID"),
+    ("secrets.aws_token", "AWS_TOKEN"),
+];
+
+/// Secret names served by a secrets provider and the settings they fill in
+const SECRET_SETTINGS: &[(&str, &str)] = &[
+    ("scylla_username", "scylla.username"),
+    ("scylla_password", "scylla.password"),
+    ("s3_access_key", "s3.access_key"),
+    ("s3_secret_key", "s3.secret_key"),
+    ("gcs_access_key", "gcs.access_key"),
+    ("gcs_secret_key", "gcs.secret_key"),
+    ("azure_storage_key", "azure.access_key"),
+    ("embed_token_secret", "embed.secret"),
+    ("encryption_master_keys", "encryption.master_keys"),
+    ("service_account_tokens", "auth.service_tokens"),
+];
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
             server: ServerConfig {
-                host: env::var("SERVER_HOST").unwrap_or_else(|_| "0.0.0.0".to_string()),
-                port: env::var("SERVER_PORT")
-                    .unwrap_or_else(|_| "8080".to_string())
-                    .parse()
-                    .map_err(|e| format!("Invalid SERVER_PORT: {}", e))?,
+                host: "0.0.0.0".to_string(),
+                port: 8080,
+                shutdown_grace_period_secs: 30,
+                shutdown_ready_delay_secs: 5,
+                max_concurrent_requests: 1024,
+                max_concurrent_expensive_requests: 16,
+                tls_cert_path: None,
+                tls_key_path: None,
+                tls_client_ca_path: None,
             },
             scylla: ScyllaConfig {
-                nodes: env::var("SCYLLA_NODES")
-                    .unwrap_or_else(|_| "localhost:9042".to_string())
-                    .split(',')
-                    .map(|s| s.trim().to_string())
-                    .collect(),
-                keyspace: env::var("SCYLLA_KEYSPACE")
-                    .unwrap_or_else(|_| "aigc_history".to_string()),
-                username: env::var("SCYLLA_USERNAME").ok(),
-                password: env::var("SCYLLA_PASSWORD").ok(),
+                nodes: vec!["localhost:9042".to_string()],
+                keyspace: "aigc_history".to_string(),
+                username: None,
+                password: None,
+                local_dc: None,
+                consistency: "local_quorum".to_string(),
+                request_timeout_ms: 5_000,
+                bulk_timeout_ms: 60_000,
+                page_size: 1000,
+                connections_per_shard: 1,
+                connect_timeout_ms: 5_000,
+                speculative_retries: 0,
+                speculative_delay_ms: 100,
+                replication: "{'class': 'SimpleStrategy', 'replication_factor': 1}".to_string(),
+                compaction: "{'class': 'SizeTieredCompactionStrategy'}".to_string(),
+                change_ttl_secs: 30 * 24 * 3600,
+                notification_ttl_secs: 90 * 24 * 3600,
+                access_log_ttl_secs: 90 * 24 * 3600,
+                fork_batch_strategy: BatchStrategy::Logged,
+                write_parallelism: 16,
+                profiles: ExecutionProfiles::default(),
+                startup_max_wait_secs: 60,
+                startup_backoff_ms: 500,
+                startup_max_backoff_ms: 10_000,
+            },
+            object_store: ObjectStoreConfig {
+                provider: ObjectStoreProvider::S3,
             },
             s3: S3Config {
-                endpoint: env::var("S3_ENDPOINT")
-                    .unwrap_or_else(|_| "http://localhost:9000".to_string()),
-                access_key: env::var("S3_ACCESS_KEY").unwrap_or_else(|_| "minioadmin".to_string()),
-                secret_key: env::var("S3_SECRET_KEY").unwrap_or_else(|_| "minioadmin".to_string()),
-                bucket: env::var("S3_BUCKET").unwrap_or_else(|_| "aigc-images".to_string()),
-                region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                endpoint: "http://localhost:9000".to_string(),
+                access_key: "minioadmin".to_string(),
+                secret_key: "minioadmin".to_string(),
+                bucket: "aigc-images".to_string(),
+                region: "us-east-1".to_string(),
+            },
+            gcs: GcsConfig {
+                endpoint: "https://storage.googleapis.com".to_string(),
+                access_key: String::new(),
+                secret_key: String::new(),
+                bucket: "aigc-images".to_string(),
+            },
+            // Azurite's well-known development account
+            azure: AzureConfig {
+                endpoint: "http://localhost:10000/devstoreaccount1".to_string(),
+                account: "devstoreaccount1".to_string(),
+                access_key: "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==".to_string(),
+                container: "aigc-images".to_string(),
             },
             app: AppConfig {
-                max_lineage_depth: env::var("MAX_LINEAGE_DEPTH")
-                    .unwrap_or_else(|_| "1000".to_string())
-                    .parse()
-                    .unwrap_or(1000),
-                max_batch_size: env::var("MAX_BATCH_SIZE")
-                    .unwrap_or_else(|_| "100".to_string())
-                    .parse()
-                    .unwrap_or(100),
+                max_lineage_depth: 1000,
+                max_batch_size: 100,
+                max_children_per_message: 1000,
+                max_messages_per_conversation: 50_000,
+                default_page_size: 50,
+                max_page_size: 200,
+            },
+            branch_cache: BranchCacheConfig {
+                capacity: 10_000,
+                ttl_secs: 60,
+            },
+            branches: BranchesConfig {
+                default_naming: BranchNaming::Numbered,
+            },
+            fork: ForkConfig {
+                max_source_messages: 10_000,
+                max_per_user_per_hour: 30,
+                chunk_parallelism: 4,
+                chunk_retries: 3,
+                chunk_retry_backoff_ms: 100,
+                stale_after_secs: 900,
+                recovery_interval_secs: 300,
+            },
+            jobs: JobsConfig {
+                stale_after_secs: 300,
+                recovery_interval_secs: 60,
+            },
+            exports: ExportsConfig {
+                download_expiry_secs: 3600,
+            },
+            legal_holds: LegalHoldsConfig {
+                retention_interval_secs: 86_400,
+            },
+            webhooks: WebhooksConfig {
+                interval_secs: 5,
+                batch_size: 100,
+                timeout_secs: 10,
+            },
+            fetch: FetchConfig {
+                allow_private_networks: false,
+                max_response_bytes: 64 * 1024,
+            },
+            access_log: AccessLogConfig {
+                enabled: false,
+                sample_rate: 1.0,
+            },
+            secrets: SecretsConfig {
+                provider: SecretsProviderKind::Env,
+                vault_addr: None,
+                vault_token: None,
+                vault_mount: "secret".to_string(),
+                vault_path: "aigc-history".to_string(),
+                aws_endpoint: "http://localhost:2773".to_string(),
+                aws_secret_id: "aigc-history".to_string(),
+                aws_token: None,
             },
+            storage: StorageConfig {
+                backend: StorageBackend::Scylla,
+            },
+            logging: LoggingConfig {
+                format: LogFormat::Pretty,
+                sample_rate: 1.0,
+                log_bodies: false,
+                max_body_bytes: 4096,
+            },
+            trending: TrendingConfig {
+                interval_secs: 900,
+                window_days: 7,
+                size: 100,
+            },
+            scheduler: SchedulerConfig { enabled: true },
+            pii: PiiConfig {
+                scrub_on_write: false,
+            },
+            content: ContentConfig {
+                processors: Vec::new(),
+                allowed_url_schemes: ["https", "http", "s3", "gs"]
+                    .map(String::from)
+                    .to_vec(),
+                detect_language: false,
+            },
+            images: ImagesConfig {
+                gc_enabled: false,
+                gc_interval_secs: 3600,
+                gc_batch_size: 500,
+                presign_urls: false,
+                presign_expiry_secs: 3600,
+                allowed_url_schemes: ["https", "http", "s3", "gs"]
+                    .map(String::from)
+                    .to_vec(),
+                max_url_length: 2048,
+            },
+            analytics: AnalyticsConfig {
+                enabled: false,
+                interval_secs: 3600,
+                window_days: 2,
+            },
+            cdc: CdcConfig {
+                enabled: false,
+                interval_secs: 5,
+                lag_secs: 30,
+                max_window_secs: 300,
+            },
+            blobs: BlobsConfig {
+                offload_enabled: false,
+                offload_threshold_bytes: 65_536,
+            },
+            encryption: EncryptionConfig {
+                master_keys: HashMap::new(),
+                active_key_id: None,
+                key_cache_capacity: 10_000,
+            },
+            mailboxes: MailboxesConfig {
+                enabled: false,
+                capacity: 64,
+                idle_secs: 30,
+            },
+            admin: AdminConfig { users: Vec::new() },
+            auth: AuthConfig {
+                anonymous_public_reads: false,
+                service_tokens: HashMap::new(),
+                service_requests_per_minute: 600,
+                service_rate_limits: HashMap::new(),
+                oidc_issuer: None,
+                oidc_client_id: None,
+                oidc_identity_claim: OidcIdentityClaim::Subject,
+                oidc_jwks_cache_secs: 3_600,
+            },
+            embed: EmbedConfig {
+                secret: None,
+                default_ttl_secs: 86_400,
+                max_ttl_secs: 2_592_000,
+            },
+            errors: ErrorsConfig {
+                format: ErrorFormat::Json,
+                problem_type_base_url: None,
+            },
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::load(None)
+    }
+
+    /// Load defaults, then the TOML config file (if any), then env var overrides
+    pub fn load(config_path: Option<&Path>) -> Result<Self, ConfigError> {
+        let file = match config_path {
+            Some(path) => Some(std::fs::read_to_string(path).map_err(|e| ConfigError {
+                errors: vec![format!("cannot read {}: {}", path.display(), e)],
+            })?),
+            None => None,
+        };
+
+        Self::build(file.as_deref(), |name| env::var(name).ok())
+    }
+
+    /// The secrets provider selected by `secrets.provider`, or the settings
+    /// it is missing
+    pub fn secrets_provider(&self) -> Result<Box<dyn SecretsProvider>, String> {
+        let secrets = &self.secrets;
+        match secrets.provider {
+            SecretsProviderKind::Env => Ok(Box::new(EnvSecrets)),
+            SecretsProviderKind::Vault => match (
+                secrets.vault_addr.as_deref().filter(|v| !v.is_empty()),
+                secrets.vault_token.as_deref().filter(|v| !v.is_empty()),
+            ) {
+                (Some(addr), Some(token)) => Ok(Box::new(VaultSecrets {
+                    addr: addr.to_string(),
+                    token: token.to_string(),
+                    mount: secrets.vault_mount.clone(),
+                    path: secrets.vault_path.clone(),
+                })),
+                _ => Err(
+                    "the vault secrets provider needs `secrets.vault_addr` and `secrets.vault_token`"
+                        .to_string(),
+                ),
+            },
+            SecretsProviderKind::Aws => match secrets.aws_token.as_deref().filter(|v| !v.is_empty())
+            {
+                Some(token) if !secrets.aws_endpoint.is_empty() => {
+                    Ok(Box::new(AwsSecretsManager {
+                        endpoint: secrets.aws_endpoint.clone(),
+                        secret_id: secrets.aws_secret_id.clone(),
+                        token: token.to_string(),
+                    }))
+                }
+                _ => Err(
+                    "the aws secrets provider needs `secrets.aws_endpoint` and `secrets.aws_token`"
+                        .to_string(),
+                ),
+            },
+        }
+    }
+
+    /// Fill in Scylla credentials and S3 keys from the configured secrets provider
+    pub async fn resolve_secrets(&mut self) -> Result<(), ConfigError> {
+        let provider = self
+            .secrets_provider()
+            .map_err(|e| ConfigError { errors: vec![e] })?;
+        let secrets = provider.fetch().await.map_err(|e| ConfigError {
+            errors: vec![format!("{} secrets provider: {}", provider.name(), e)],
+        })?;
+
+        let mut errors = Vec::new();
+        for (name, key) in SECRET_SETTINGS {
+            if let Some(value) = secrets.get(*name)
+                && let Err(e) = self.set(key, value)
+            {
+                errors.push(e);
+            }
+        }
+        self.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+
+    fn build(
+        file: Option<&str>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        let mut settings = Settings::default();
+        let mut errors = Vec::new();
+
+        if let Some(file) = file {
+            match file.parse::<toml_edit::Document>() {
+                Ok(document) => settings.apply_document(&document, &mut errors),
+                Err(e) => errors.push(format!("config file is not valid TOML: {}", e)),
+            }
+        }
+
+        for (key, var) in SETTINGS {
+            if let Some(value) = env(var)
+                && let Err(e) = settings.set(key, &value)
+            {
+                errors.push(format!("{} ({})", e, var));
+            }
+        }
+
+        settings.validate(&mut errors);
+
+        if errors.is_empty() {
+            Ok(settings)
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+
+    fn apply_document(&mut self, document: &toml_edit::Document, errors: &mut Vec<String>) {
+        for (section, item) in document.iter() {
+            let Some(table) = item.as_table_like() else {
+                errors.push(format!("`{}` must be a table", section));
+                continue;
+            };
+
+            for (name, item) in table.iter() {
+                let key = format!("{}.{}", section, name);
+                let value = match item.as_value() {
+                    Some(toml_edit::Value::String(s)) => s.value().clone(),
+                    Some(toml_edit::Value::Integer(i)) => i.value().to_string(),
+                    Some(toml_edit::Value::Boolean(b)) => b.value().to_string(),
+                    Some(toml_edit::Value::Array(array)) => array
+                        .iter()
+                        .map(|v| {
+                            v.as_str()
+                                .map(str::to_string)
+                                .unwrap_or_else(|| v.to_string())
+                        })
+                        .collect::<Vec<_>>()
+                        .join(","),
+                    _ => {
+                        errors.push(format!("unsupported value for `{}`", key));
+                        continue;
+                    }
+                };
+
+                if let Err(e) = self.set(&key, &value) {
+                    errors.push(e);
+                }
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        if let Some(rest) = key.strip_prefix("scylla.")
+            && let Some((profile, field)) = ["_consistency", "_timeout_ms", "_retries"]
+                .iter()
+                .find_map(|field| Some((rest.strip_suffix(field)?, *field)))
+            && let Some(overrides) = self.scylla.profiles.get_mut(profile)
+        {
+            match field {
+                "_consistency" => overrides.consistency = Some(value.to_string()),
+                "_timeout_ms" => overrides.timeout_ms = Some(parse(key, value)?),
+                _ => overrides.retries = Some(parse(key, value)?),
+            }
+            return Ok(());
+        }
+
+        match key {
+            "server.host" => self.server.host = value.to_string(),
+            "server.port" => self.server.port = parse(key, value)?,
+            "server.shutdown_grace_period_secs" => {
+                self.server.shutdown_grace_period_secs = parse(key, value)?
+            }
+            "server.shutdown_ready_delay_secs" => {
+                self.server.shutdown_ready_delay_secs = parse(key, value)?
+            }
+            "server.max_concurrent_requests" => {
+                self.server.max_concurrent_requests = parse(key, value)?
+            }
+            "server.max_concurrent_expensive_requests" => {
+                self.server.max_concurrent_expensive_requests = parse(key, value)?
+            }
+            "server.tls_cert_path" => self.server.tls_cert_path = Some(value.to_string()),
+            "server.tls_key_path" => self.server.tls_key_path = Some(value.to_string()),
+            "server.tls_client_ca_path" => self.server.tls_client_ca_path = Some(value.to_string()),
+            "scylla.nodes" => {
+                self.scylla.nodes = value
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            }
+            "scylla.keyspace" => self.scylla.keyspace = value.to_string(),
+            "scylla.username" => self.scylla.username = Some(value.to_string()),
+            "scylla.password" => self.scylla.password = Some(value.to_string()),
+            "scylla.local_dc" => self.scylla.local_dc = Some(value.to_string()),
+            "scylla.consistency" => self.scylla.consistency = value.to_string(),
+            "scylla.request_timeout_ms" => self.scylla.request_timeout_ms = parse(key, value)?,
+            "scylla.bulk_timeout_ms" => self.scylla.bulk_timeout_ms = parse(key, value)?,
+            "scylla.startup_max_wait_secs" => {
+                self.scylla.startup_max_wait_secs = parse(key, value)?
+            }
+            "scylla.startup_backoff_ms" => self.scylla.startup_backoff_ms = parse(key, value)?,
+            "scylla.startup_max_backoff_ms" => {
+                self.scylla.startup_max_backoff_ms = parse(key, value)?
+            }
+            "scylla.page_size" => self.scylla.page_size = parse(key, value)?,
+            "scylla.connections_per_shard" => {
+                self.scylla.connections_per_shard = parse(key, value)?
+            }
+            "scylla.connect_timeout_ms" => self.scylla.connect_timeout_ms = parse(key, value)?,
+            "scylla.speculative_retries" => self.scylla.speculative_retries = parse(key, value)?,
+            "scylla.speculative_delay_ms" => self.scylla.speculative_delay_ms = parse(key, value)?,
+            "scylla.replication" => self.scylla.replication = value.to_string(),
+            "scylla.compaction" => self.scylla.compaction = value.to_string(),
+            "scylla.change_ttl_secs" => self.scylla.change_ttl_secs = parse(key, value)?,
+            "scylla.notification_ttl_secs" => {
+                self.scylla.notification_ttl_secs = parse(key, value)?
+            }
+            "scylla.access_log_ttl_secs" => self.scylla.access_log_ttl_secs = parse(key, value)?,
+            "scylla.fork_batch_strategy" => self.scylla.fork_batch_strategy = value.parse()?,
+            "scylla.write_parallelism" => self.scylla.write_parallelism = parse(key, value)?,
+            "object_store.provider" => self.object_store.provider = value.parse()?,
+            "s3.endpoint" => self.s3.endpoint = value.to_string(),
+            "s3.access_key" => self.s3.access_key = value.to_string(),
+            "s3.secret_key" => self.s3.secret_key = value.to_string(),
+            "s3.bucket" => self.s3.bucket = value.to_string(),
+            "s3.region" => self.s3.region = value.to_string(),
+            "gcs.endpoint" => self.gcs.endpoint = value.to_string(),
+            "gcs.access_key" => self.gcs.access_key = value.to_string(),
+            "gcs.secret_key" => self.gcs.secret_key = value.to_string(),
+            "gcs.bucket" => self.gcs.bucket = value.to_string(),
+            "azure.endpoint" => self.azure.endpoint = value.to_string(),
+            "azure.account" => self.azure.account = value.to_string(),
+            "azure.access_key" => self.azure.access_key = value.to_string(),
+            "azure.container" => self.azure.container = value.to_string(),
+            "app.max_lineage_depth" => self.app.max_lineage_depth = parse(key, value)?,
+            "app.max_batch_size" => self.app.max_batch_size = parse(key, value)?,
+            "app.max_children_per_message" => {
+                self.app.max_children_per_message = parse(key, value)?
+            }
+            "app.max_messages_per_conversation" => {
+                self.app.max_messages_per_conversation = parse(key, value)?
+            }
+            "app.default_page_size" => self.app.default_page_size = parse(key, value)?,
+            "app.max_page_size" => self.app.max_page_size = parse(key, value)?,
+            "branch_cache.capacity" => self.branch_cache.capacity = parse(key, value)?,
+            "branch_cache.ttl_secs" => self.branch_cache.ttl_secs = parse(key, value)?,
+            "storage.backend" => self.storage.backend = value.parse()?,
+            "logging.format" => self.logging.format = value.parse()?,
+            "logging.sample_rate" => self.logging.sample_rate = parse(key, value)?,
+            "logging.log_bodies" => self.logging.log_bodies = parse(key, value)?,
+            "logging.max_body_bytes" => self.logging.max_body_bytes = parse(key, value)?,
+            "trending.interval_secs" => self.trending.interval_secs = parse(key, value)?,
+            "trending.window_days" => self.trending.window_days = parse(key, value)?,
+            "trending.size" => self.trending.size = parse(key, value)?,
+            "scheduler.enabled" => self.scheduler.enabled = parse(key, value)?,
+            "pii.scrub_on_write" => self.pii.scrub_on_write = parse(key, value)?,
+            "content.processors" => self.content.processors = parse_list(key, value)?,
+            "content.allowed_url_schemes" => {
+                self.content.allowed_url_schemes = parse_list::<String>(key, value)?
+                    .iter()
+                    .map(|scheme| scheme.to_ascii_lowercase())
+                    .collect()
+            }
+            "content.detect_language" => self.content.detect_language = parse(key, value)?,
+            "images.gc_enabled" => self.images.gc_enabled = parse(key, value)?,
+            "images.gc_interval_secs" => self.images.gc_interval_secs = parse(key, value)?,
+            "images.gc_batch_size" => self.images.gc_batch_size = parse(key, value)?,
+            "images.presign_urls" => self.images.presign_urls = parse(key, value)?,
+            "images.presign_expiry_secs" => self.images.presign_expiry_secs = parse(key, value)?,
+            "images.allowed_url_schemes" => {
+                self.images.allowed_url_schemes = parse_list::<String>(key, value)?
+                    .iter()
+                    .map(|scheme| scheme.to_ascii_lowercase())
+                    .collect()
+            }
+            "images.max_url_length" => self.images.max_url_length = parse(key, value)?,
+            "analytics.enabled" => self.analytics.enabled = parse(key, value)?,
+            "analytics.interval_secs" => self.analytics.interval_secs = parse(key, value)?,
+            "analytics.window_days" => self.analytics.window_days = parse(key, value)?,
+            "branches.default_naming" => self.branches.default_naming = value.parse()?,
+            "fork.max_source_messages" => self.fork.max_source_messages = parse(key, value)?,
+            "fork.max_per_user_per_hour" => self.fork.max_per_user_per_hour = parse(key, value)?,
+            "fork.chunk_parallelism" => self.fork.chunk_parallelism = parse(key, value)?,
+            "fork.chunk_retries" => self.fork.chunk_retries = parse(key, value)?,
+            "fork.chunk_retry_backoff_ms" => self.fork.chunk_retry_backoff_ms = parse(key, value)?,
+            "fork.stale_after_secs" => self.fork.stale_after_secs = parse(key, value)?,
+            "fork.recovery_interval_secs" => self.fork.recovery_interval_secs = parse(key, value)?,
+            "jobs.stale_after_secs" => self.jobs.stale_after_secs = parse(key, value)?,
+            "jobs.recovery_interval_secs" => self.jobs.recovery_interval_secs = parse(key, value)?,
+            "exports.download_expiry_secs" => {
+                self.exports.download_expiry_secs = parse(key, value)?
+            }
+            "legal_holds.retention_interval_secs" => {
+                self.legal_holds.retention_interval_secs = parse(key, value)?
+            }
+            "webhooks.interval_secs" => self.webhooks.interval_secs = parse(key, value)?,
+            "webhooks.batch_size" => self.webhooks.batch_size = parse(key, value)?,
+            "webhooks.timeout_secs" => self.webhooks.timeout_secs = parse(key, value)?,
+            "fetch.allow_private_networks" => {
+                self.fetch.allow_private_networks = parse(key, value)?
+            }
+            "fetch.max_response_bytes" => self.fetch.max_response_bytes = parse(key, value)?,
+            "access_log.enabled" => self.access_log.enabled = parse(key, value)?,
+            "access_log.sample_rate" => self.access_log.sample_rate = parse(key, value)?,
+            "cdc.enabled" => self.cdc.enabled = parse(key, value)?,
+            "cdc.interval_secs" => self.cdc.interval_secs = parse(key, value)?,
+            "cdc.lag_secs" => self.cdc.lag_secs = parse(key, value)?,
+            "cdc.max_window_secs" => self.cdc.max_window_secs = parse(key, value)?,
+            "blobs.offload_enabled" => self.blobs.offload_enabled = parse(key, value)?,
+            "blobs.offload_threshold_bytes" => {
+                self.blobs.offload_threshold_bytes = parse(key, value)?
+            }
+            "encryption.master_keys" => self.encryption.master_keys = parse_pairs(key, value)?,
+            "encryption.active_key_id" => self.encryption.active_key_id = Some(value.to_string()),
+            "encryption.key_cache_capacity" => {
+                self.encryption.key_cache_capacity = parse(key, value)?
+            }
+            "mailboxes.enabled" => self.mailboxes.enabled = parse(key, value)?,
+            "mailboxes.capacity" => self.mailboxes.capacity = parse(key, value)?,
+            "mailboxes.idle_secs" => self.mailboxes.idle_secs = parse(key, value)?,
+            "admin.users" => {
+                self.admin.users = value
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect()
+            }
+            "auth.anonymous_public_reads" => self.auth.anonymous_public_reads = parse(key, value)?,
+            "auth.service_tokens" => self.auth.service_tokens = parse_pairs(key, value)?,
+            "auth.service_requests_per_minute" => {
+                self.auth.service_requests_per_minute = parse(key, value)?
+            }
+            "auth.service_rate_limits" => self.auth.service_rate_limits = parse_pairs(key, value)?,
+            "auth.oidc_issuer" => self.auth.oidc_issuer = Some(value.to_string()),
+            "auth.oidc_client_id" => self.auth.oidc_client_id = Some(value.to_string()),
+            "auth.oidc_identity_claim" => self.auth.oidc_identity_claim = value.parse()?,
+            "auth.oidc_jwks_cache_secs" => self.auth.oidc_jwks_cache_secs = parse(key, value)?,
+            "embed.secret" => self.embed.secret = Some(value.to_string()),
+            "embed.default_ttl_secs" => self.embed.default_ttl_secs = parse(key, value)?,
+            "embed.max_ttl_secs" => self.embed.max_ttl_secs = parse(key, value)?,
+            "errors.format" => self.errors.format = value.parse()?,
+            "errors.problem_type_base_url" => {
+                self.errors.problem_type_base_url = Some(value.to_string())
+            }
+            "secrets.provider" => self.secrets.provider = value.parse()?,
+            "secrets.vault_addr" => self.secrets.vault_addr = Some(value.to_string()),
+            "secrets.vault_token" => self.secrets.vault_token = Some(value.to_string()),
+            "secrets.vault_mount" => self.secrets.vault_mount = value.to_string(),
+            "secrets.vault_path" => self.secrets.vault_path = value.to_string(),
+            "secrets.aws_endpoint" => self.secrets.aws_endpoint = value.to_string(),
+            "secrets.aws_secret_id" => self.secrets.aws_secret_id = value.to_string(),
+            "secrets.aws_token" => self.secrets.aws_token = Some(value.to_string()),
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+        Ok(())
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.server.port == 0 {
+            errors.push("`server.port` must not be 0".to_string());
+        }
+        if self.server.max_concurrent_requests == 0 {
+            errors.push("`server.max_concurrent_requests` must be positive".to_string());
+        }
+        if self.server.max_concurrent_expensive_requests == 0 {
+            errors.push("`server.max_concurrent_expensive_requests` must be positive".to_string());
+        }
+        if self.server.tls_cert_path.is_some() != self.server.tls_key_path.is_some() {
+            errors.push(
+                "`server.tls_cert_path` and `server.tls_key_path` must be set together".to_string(),
+            );
+        }
+        if self.server.tls_client_ca_path.is_some() && self.server.tls_cert_path.is_none() {
+            errors.push(
+                "`server.tls_client_ca_path` needs `server.tls_cert_path` and `server.tls_key_path`"
+                    .to_string(),
+            );
+        }
+        for (key, path) in [
+            ("server.tls_cert_path", &self.server.tls_cert_path),
+            ("server.tls_key_path", &self.server.tls_key_path),
+            ("server.tls_client_ca_path", &self.server.tls_client_ca_path),
+        ] {
+            if let Some(path) = path
+                && !Path::new(path).is_file()
+            {
+                errors.push(format!("`{}`: {} is not a file", key, path));
+            }
+        }
+        if self.scylla.nodes.is_empty() {
+            errors.push("`scylla.nodes` must list at least one node".to_string());
+        }
+        if self.scylla.keyspace.is_empty() {
+            errors.push("`scylla.keyspace` must not be empty".to_string());
+        }
+        for (key, map) in [
+            ("scylla.replication", &self.scylla.replication),
+            ("scylla.compaction", &self.scylla.compaction),
+        ] {
+            let map = map.trim();
+            if !(map.starts_with('{') && map.ends_with('}')) {
+                errors.push(format!(
+                    "`{}` must be a CQL map such as {{'class': ...}}",
+                    key
+                ));
+            }
+        }
+        if let Err(e) = self.scylla.consistency() {
+            errors.push(format!("`scylla.consistency`: {}", e));
+        }
+        for (name, overrides) in self.scylla.profiles.iter() {
+            if let Err(e) = self.scylla.profile_consistency(overrides) {
+                errors.push(format!("`scylla.{}_consistency`: {}", name, e));
+            }
+            if overrides.timeout_ms == Some(0) {
+                errors.push(format!("`scylla.{}_timeout_ms` must be positive", name));
+            }
+        }
+        if self.scylla.request_timeout_ms == 0
+            || self.scylla.bulk_timeout_ms == 0
+            || self.scylla.connect_timeout_ms == 0
+        {
+            errors.push("Scylla timeouts must be positive".to_string());
+        }
+        if self.scylla.startup_backoff_ms == 0
+            || self.scylla.startup_backoff_ms > self.scylla.startup_max_backoff_ms
+        {
+            errors.push(
+                "`scylla.startup_backoff_ms` must be positive and at most `scylla.startup_max_backoff_ms`"
+                    .to_string(),
+            );
+        }
+        if self.scylla.page_size <= 0 {
+            errors.push("`scylla.page_size` must be positive".to_string());
+        }
+        if self.scylla.connections_per_shard == 0 {
+            errors.push("`scylla.connections_per_shard` must be positive".to_string());
+        }
+        if self.scylla.speculative_retries > 0 && self.scylla.speculative_delay_ms == 0 {
+            errors.push(
+                "`scylla.speculative_delay_ms` must be positive with speculative retries"
+                    .to_string(),
+            );
+        }
+        if self.scylla.write_parallelism == 0 {
+            errors.push("`scylla.write_parallelism` must be positive".to_string());
+        }
+        if self.scylla.username.is_some() != self.scylla.password.is_some() {
+            errors.push("`scylla.username` and `scylla.password` must be set together".to_string());
+        }
+        if self.app.max_lineage_depth == 0 {
+            errors.push("`app.max_lineage_depth` must be positive".to_string());
+        }
+        if self.app.max_batch_size == 0 {
+            errors.push("`app.max_batch_size` must be positive".to_string());
+        }
+        if self.app.max_children_per_message == 0 || self.app.max_messages_per_conversation == 0 {
+            errors.push(
+                "`app.max_children_per_message` and `app.max_messages_per_conversation` must be \
+                 positive"
+                    .to_string(),
+            );
+        }
+        if self.app.default_page_size == 0 || self.app.max_page_size < self.app.default_page_size {
+            errors.push(
+                "`app.default_page_size` must be positive and at most `app.max_page_size`"
+                    .to_string(),
+            );
+        }
+        if self.trending.interval_secs == 0
+            || self.trending.window_days == 0
+            || self.trending.size == 0
+        {
+            errors.push("`trending` settings must be positive".to_string());
+        }
+        if self.images.gc_interval_secs == 0 || self.images.gc_batch_size == 0 {
+            errors.push("`images` GC settings must be positive".to_string());
+        }
+        if self.images.allowed_url_schemes.is_empty() || self.images.max_url_length == 0 {
+            errors.push(
+                "`images.allowed_url_schemes` must not be empty and `images.max_url_length` must be positive"
+                    .to_string(),
+            );
+        }
+        match self.object_store.provider {
+            ObjectStoreProvider::S3 => {}
+            ObjectStoreProvider::Gcs => {
+                if self.gcs.access_key.is_empty() || self.gcs.secret_key.is_empty() {
+                    errors.push(
+                        "the gcs object store needs `gcs.access_key` and `gcs.secret_key`"
+                            .to_string(),
+                    );
+                }
+            }
+            ObjectStoreProvider::Azure => {
+                if self.azure.account.is_empty() || self.azure.container.is_empty() {
+                    errors.push(
+                        "the azure object store needs `azure.account` and `azure.container`"
+                            .to_string(),
+                    );
+                }
+                if STANDARD
+                    .decode(&self.azure.access_key)
+                    .map_or(true, |key| key.is_empty())
+                {
+                    errors.push("`azure.access_key` must be a base64 account key".to_string());
+                }
+            }
+        }
+        if !(1..=MAX_PRESIGN_EXPIRY_SECS).contains(&self.images.presign_expiry_secs) {
+            errors.push(format!(
+                "`images.presign_expiry_secs` must be between 1 and {}",
+                MAX_PRESIGN_EXPIRY_SECS
+            ));
+        }
+        if !(1..=MAX_PRESIGN_EXPIRY_SECS).contains(&self.exports.download_expiry_secs) {
+            errors.push(format!(
+                "`exports.download_expiry_secs` must be between 1 and {}",
+                MAX_PRESIGN_EXPIRY_SECS
+            ));
+        }
+        if self.analytics.interval_secs == 0 || self.analytics.window_days == 0 {
+            errors.push("`analytics` settings must be positive".to_string());
+        }
+        if self.fork.max_source_messages == 0
+            || self.fork.max_per_user_per_hour == 0
+            || self.fork.chunk_parallelism == 0
+        {
+            errors.push(
+                "`fork.max_source_messages`, `fork.max_per_user_per_hour` and `fork.chunk_parallelism` must be positive"
+                    .to_string(),
+            );
+        }
+        if self.fork.stale_after_secs == 0 || self.fork.recovery_interval_secs == 0 {
+            errors.push(
+                "`fork.stale_after_secs` and `fork.recovery_interval_secs` must be positive"
+                    .to_string(),
+            );
+        }
+        if self.content.allowed_url_schemes.is_empty()
+            && self
+                .content
+                .processors
+                .contains(&ContentProcessorKind::UrlSchemes)
+        {
+            errors.push(
+                "`content.allowed_url_schemes` must not be empty with the `url_schemes` processor"
+                    .to_string(),
+            );
+        }
+        if self.jobs.stale_after_secs == 0 || self.jobs.recovery_interval_secs == 0 {
+            errors.push(
+                "`jobs.stale_after_secs` and `jobs.recovery_interval_secs` must be positive"
+                    .to_string(),
+            );
+        }
+        if self.legal_holds.retention_interval_secs == 0 {
+            errors.push("`legal_holds.retention_interval_secs` must be positive".to_string());
+        }
+        if self.webhooks.interval_secs == 0
+            || self.webhooks.batch_size == 0
+            || self.webhooks.timeout_secs == 0
+        {
+            errors.push(
+                "`webhooks.interval_secs`, `webhooks.batch_size` and `webhooks.timeout_secs` must be positive"
+                    .to_string(),
+            );
+        }
+        if self.fetch.max_response_bytes == 0 {
+            errors.push("`fetch.max_response_bytes` must be positive".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.access_log.sample_rate) {
+            errors.push("`access_log.sample_rate` must be between 0 and 1".to_string());
+        }
+        if self.cdc.interval_secs == 0 || self.cdc.max_window_secs == 0 {
+            errors
+                .push("`cdc.interval_secs` and `cdc.max_window_secs` must be positive".to_string());
+        }
+        if self.cdc.enabled && self.storage.backend != StorageBackend::Scylla {
+            errors.push("`cdc.enabled` needs the scylla storage backend".to_string());
+        }
+        if self.blobs.offload_enabled && self.storage.backend != StorageBackend::Scylla {
+            errors.push("`blobs.offload_enabled` needs the scylla storage backend".to_string());
+        }
+        if self.blobs.offload_threshold_bytes == 0 {
+            errors.push("`blobs.offload_threshold_bytes` must be positive".to_string());
+        }
+        if self.encryption.enabled() {
+            if self.storage.backend != StorageBackend::Scylla {
+                errors
+                    .push("`encryption.master_keys` needs the scylla storage backend".to_string());
+            }
+            match &self.encryption.active_key_id {
+                Some(id) if self.encryption.master_keys.contains_key(id) => {}
+                Some(id) => errors.push(format!(
+                    "`encryption.active_key_id` `{}` is not in `encryption.master_keys`",
+                    id
+                )),
+                None => errors.push(
+                    "`encryption.active_key_id` must name one of `encryption.master_keys`"
+                        .to_string(),
+                ),
+            }
+            for (id, key) in &self.encryption.master_keys {
+                if STANDARD.decode(key).map_or(true, |key| key.len() != 32) {
+                    errors.push(format!(
+                        "master key `{}` must be 32 bytes, base64-encoded",
+                        id
+                    ));
+                }
+            }
+        }
+        if self.mailboxes.capacity == 0 || self.mailboxes.idle_secs == 0 {
+            errors.push(
+                "`mailboxes.capacity` and `mailboxes.idle_secs` must be positive".to_string(),
+            );
+        }
+        if let Some(secret) = &self.embed.secret
+            && secret.len() < MIN_EMBED_SECRET_LEN
+        {
+            errors.push(format!(
+                "`embed.secret` must be at least {} bytes",
+                MIN_EMBED_SECRET_LEN
+            ));
+        }
+        for (name, token) in &self.auth.service_tokens {
+            if !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                errors.push(format!(
+                    "service account name `{}` may only contain letters, digits, `-` and `_`",
+                    name
+                ));
+            }
+            if token.len() < MIN_SERVICE_TOKEN_LEN {
+                errors.push(format!(
+                    "the token of service account `{}` must be at least {} bytes",
+                    name, MIN_SERVICE_TOKEN_LEN
+                ));
+            }
+        }
+        if self.auth.service_requests_per_minute == 0 {
+            errors.push("`auth.service_requests_per_minute` must be positive".to_string());
+        }
+        for (name, limit) in &self.auth.service_rate_limits {
+            if !self.auth.service_tokens.contains_key(name) {
+                errors.push(format!(
+                    "`auth.service_rate_limits` names unknown service account `{}`",
+                    name
+                ));
+            } else if *limit == 0 {
+                errors.push(format!(
+                    "the rate limit of service account `{}` must be positive",
+                    name
+                ));
+            }
+        }
+        match (&self.auth.oidc_issuer, &self.auth.oidc_client_id) {
+            (Some(issuer), Some(_)) => {
+                if crate::utils::url::scheme(issuer) != Some("https") {
+                    errors.push("`auth.oidc_issuer` must be an https:// URL".to_string());
+                }
+            }
+            (None, None) => {}
+            _ => errors.push(
+                "`auth.oidc_issuer` and `auth.oidc_client_id` must be set together".to_string(),
+            ),
+        }
+        if self.auth.oidc_jwks_cache_secs == 0 {
+            errors.push("`auth.oidc_jwks_cache_secs` must be positive".to_string());
+        }
+        if self.embed.default_ttl_secs == 0 || self.embed.default_ttl_secs > self.embed.max_ttl_secs
+        {
+            errors.push(
+                "`embed.default_ttl_secs` must be positive and at most `embed.max_ttl_secs`"
+                    .to_string(),
+            );
+        }
+        if !(0.0..=1.0).contains(&self.logging.sample_rate) {
+            errors.push("`logging.sample_rate` must be between 0 and 1".to_string());
+        }
+        if let Err(e) = self.secrets_provider() {
+            errors.push(e);
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid value `{}` for `{}`: {}", value, key, e))
+}
+
+/// Comma-separated values, such as `trim_whitespace,url_schemes`
+fn parse_list<T: std::str::FromStr>(key: &str, value: &str) -> Result<Vec<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| parse(key, item))
+        .collect()
+}
+
+/// Comma-separated `name=value` pairs, such as `indexer=60,summarizer=120`
+fn parse_pairs<T: std::str::FromStr>(key: &str, value: &str) -> Result<HashMap<String, T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("expected `name=value` pairs for `{}`", key))?;
+            Ok((name.trim().to_string(), parse(key, value)?))
         })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env_overrides_file() {
+        let file = r#"
+            [server]
+            port = 9000
+
+            [scylla]
+            nodes = ["db1:9042", "db2:9042"]
+            bulk_write_timeout_ms = 120000
+            fork_batch_strategy = "concurrent"
+            speculative_retries = 2
+        "#;
+        let env: HashMap<&str, &str> = [
+            ("SERVER_PORT", "9100"),
+            ("SCYLLA_BULK_WRITE_CONSISTENCY", "local_one"),
+            ("SCYLLA_CONNECT_TIMEOUT_MS", "2000"),
+        ]
+        .into();
+
+        let settings =
+            Settings::build(Some(file), |name| env.get(name).map(|v| v.to_string())).unwrap();
+
+        assert_eq!(settings.server.port, 9100);
+        assert_eq!(settings.scylla.nodes, vec!["db1:9042", "db2:9042"]);
+        assert_eq!(settings.app.max_batch_size, 100);
+        assert_eq!(settings.scylla.connect_timeout_ms, 2_000);
+        assert_eq!(settings.scylla.speculative_retries, 2);
+        assert_eq!(settings.scylla.speculative_delay_ms, 100);
+        assert_eq!(
+            settings.scylla.fork_batch_strategy,
+            BatchStrategy::Concurrent
+        );
+        let bulk_write = &settings.scylla.profiles.bulk_write;
+        assert_eq!(bulk_write.timeout_ms, Some(120_000));
+        assert_eq!(bulk_write.consistency.as_deref(), Some("local_one"));
+        assert!(
+            settings
+                .scylla
+                .profiles
+                .interactive_read
+                .timeout_ms
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_errors_are_aggregated() {
+        let file = r#"
+            [server]
+            prot = 9000
+
+            [app]
+            max_batch_size = 0
+        "#;
+        let env: HashMap<&str, &str> = [("MAX_LINEAGE_DEPTH", "deep")].into();
+
+        let err =
+            Settings::build(Some(file), |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+
+        assert_eq!(err.errors.len(), 3);
+        assert!(err.errors[0].contains("server.prot"));
+        assert!(err.errors[1].contains("MAX_LINEAGE_DEPTH"));
+        assert!(err.errors[2].contains("max_batch_size"));
+    }
+
+    #[test]
+    fn test_service_accounts_are_parsed_and_checked() {
+        let token = "t".repeat(MIN_SERVICE_TOKEN_LEN);
+        let tokens = format!("generation-worker={}, indexer={}", token, token);
+        let env: HashMap<&str, &str> = [
+            ("SERVICE_ACCOUNT_TOKENS", tokens.as_str()),
+            ("SERVICE_ACCOUNT_RATE_LIMITS", "indexer=60"),
+        ]
+        .into();
+
+        let settings = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap();
+
+        assert_eq!(settings.auth.service_tokens["generation-worker"], token);
+        assert_eq!(settings.auth.service_rate_limit("indexer"), 60);
+        assert_eq!(settings.auth.service_rate_limit("generation-worker"), 600);
+
+        let env: HashMap<&str, &str> = [
+            ("SERVICE_ACCOUNT_TOKENS", "indexer=short"),
+            ("SERVICE_ACCOUNT_RATE_LIMITS", "summarizer=60"),
+        ]
+        .into();
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(err.errors.len(), 2);
+        assert!(err.errors[0].contains("indexer"));
+        assert!(err.errors[1].contains("summarizer"));
+    }
+
+    #[test]
+    fn test_tls_paths_are_checked() {
+        let env: HashMap<&str, &str> = [
+            ("TLS_CERT_PATH", "Cargo.toml"),
+            ("TLS_CLIENT_CA_PATH", "missing.pem"),
+        ]
+        .into();
+
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+
+        assert_eq!(err.errors.len(), 2);
+        assert!(err.errors[0].contains("must be set together"));
+        assert!(err.errors[1].contains("missing.pem is not a file"));
+
+        let env: HashMap<&str, &str> = [("TLS_CLIENT_CA_PATH", "Cargo.toml")].into();
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(err.errors.len(), 1);
+        assert!(err.errors[0].contains("tls_client_ca_path` needs"));
+    }
+
+    #[test]
+    fn test_oidc_settings_are_checked() {
+        let env: HashMap<&str, &str> = [
+            ("OIDC_ISSUER", "https://login.example.com"),
+            ("OIDC_CLIENT_ID", "aigc-history"),
+            ("OIDC_IDENTITY_CLAIM", "email"),
+        ]
+        .into();
+        let settings = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(settings.auth.oidc_identity_claim, OidcIdentityClaim::Email);
+
+        let env: HashMap<&str, &str> = [
+            ("OIDC_ISSUER", "http://login.example.com"),
+            ("OIDC_IDENTITY_CLAIM", "name"),
+        ]
+        .into();
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(err.errors.len(), 2);
+        assert!(err.errors[0].contains("unknown identity claim `name`"));
+        assert!(err.errors[1].contains("must be set together"));
+    }
+
+    #[test]
+    fn test_encryption_keys_are_parsed_and_checked() {
+        let key = STANDARD.encode([7u8; 32]);
+        let keys = format!("2025-01={}, 2026-01={}", key, key);
+        let env: HashMap<&str, &str> = [
+            ("ENCRYPTION_MASTER_KEYS", keys.as_str()),
+            ("ENCRYPTION_ACTIVE_KEY_ID", "2026-01"),
+        ]
+        .into();
+        let settings = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert!(settings.encryption.enabled());
+        assert_eq!(settings.encryption.master_keys["2025-01"], key);
+
+        let short = STANDARD.encode([7u8; 16]);
+        let keys = format!("2025-01={}", short);
+        let env: HashMap<&str, &str> = [
+            ("ENCRYPTION_MASTER_KEYS", keys.as_str()),
+            ("ENCRYPTION_ACTIVE_KEY_ID", "2026-01"),
+            ("STORAGE_BACKEND", "memory"),
+        ]
+        .into();
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(err.errors.len(), 3);
+        assert!(err.errors[0].contains("scylla storage backend"));
+        assert!(err.errors[1].contains("`2026-01` is not in"));
+        assert!(err.errors[2].contains("master key `2025-01` must be 32 bytes"));
+    }
+
+    #[test]
+    fn test_secrets_provider_is_parsed_and_checked() {
+        let env: HashMap<&str, &str> = [
+            ("SECRETS_PROVIDER", "Vault"),
+            ("VAULT_ADDR", "http://127.0.0.1:8200"),
+            ("VAULT_TOKEN", "s.token"),
+        ]
+        .into();
+        let settings = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(settings.secrets.provider, SecretsProviderKind::Vault);
+        assert_eq!(settings.secrets_provider().unwrap().name(), "vault");
+
+        let env: HashMap<&str, &str> = [("SECRETS_PROVIDER", "vualt")].into();
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(err.errors.len(), 1);
+        assert!(err.errors[0].contains("unknown secrets provider `vualt`"));
+
+        let env: HashMap<&str, &str> = [("SECRETS_PROVIDER", "vault"), ("VAULT_TOKEN", "")].into();
+        let err = Settings::build(None, |name| env.get(name).map(|v| v.to_string())).unwrap_err();
+        assert_eq!(err.errors.len(), 1);
+        assert!(err.errors[0].contains("secrets.vault_addr"));
     }
 }
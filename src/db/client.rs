@@ -1,10 +1,47 @@
-use scylla::{Session, SessionBuilder};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use scylla::batch::Batch;
+use scylla::cql_to_rust::FromRowError;
+use scylla::execution_profile::ExecutionProfileHandle;
+use scylla::frame::response::result::CqlValue;
+use scylla::load_balancing::DefaultPolicy;
+use scylla::query::Query;
+use scylla::serialize::batch::BatchValues;
+use scylla::serialize::row::SerializeRow;
+use scylla::speculative_execution::{SimpleSpeculativeExecutionPolicy, SpeculativeExecutionPolicy};
+use scylla::transport::iterator::NextRowError;
+use scylla::transport::query_result::MaybeFirstRowTypedError;
+use scylla::transport::session::PoolSize;
+use scylla::{CachingSession, ExecutionProfile, FromRow, QueryResult, Session, SessionBuilder};
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
-use crate::config::ScyllaConfig;
+use crate::config::{BatchStrategy, ProfileOverrides, ScyllaConfig};
+use crate::utils::LineageTooDeep;
 
 use super::migration;
+use super::queries;
+use super::retry::BoundedRetryPolicy;
+use super::startup::retry_startup;
+
+/// Prepared statements kept in the client-side cache
+const STATEMENT_CACHE_SIZE: usize = 256;
+
+/// Why a fork was refused
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ForkRejection {
+    #[error("The source has {messages} messages, more than the {max} a fork may copy")]
+    SourceTooLarge { messages: usize, max: usize },
+
+    #[error("At most {max} forks per hour are allowed")]
+    RateLimited { max: usize, retry_after_secs: u64 },
+
+    /// The caller has no fork permission on the source
+    #[error("{0}")]
+    NotAllowed(String),
+}
 
 #[derive(Error, Debug)]
 pub enum DbError {
@@ -20,29 +57,127 @@ pub enum DbError {
     #[error("Not found")]
     NotFound,
 
+    /// The request carries a value the operation refuses
     #[error("Invalid data: {0}")]
     InvalidData(String),
 
+    /// A stored row holds a value that doesn't map to the domain model
+    #[error("Invalid {column} in {table}: {reason}")]
+    RowParse {
+        table: &'static str,
+        column: &'static str,
+        reason: String,
+    },
+
+    /// A stored message has a content type this build doesn't know
+    #[error("Unknown content type: {value}")]
+    UnknownContentType { value: String },
+
+    /// A new or moved message would be deeper than `max_lineage_depth`
+    #[error("Lineage depth {depth} exceeds maximum allowed depth {max}")]
+    LineageTooDeep { depth: usize, max: usize },
+
+    /// A conversation size limit would be exceeded
+    #[error("Limit exceeded: {0}")]
+    LimitExceeded(String),
+
+    /// The write clashes with existing data, such as a name already in use
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// A fork guard refused the fork
+    #[error("Fork rejected: {0}")]
+    ForkRejected(#[from] ForkRejection),
+
     #[error("Migration error: {0}")]
     MigrationError(String),
+
+    /// The conversation is locked read-only
+    #[error("Conversation {0} is locked")]
+    Locked(uuid::Uuid),
+
+    /// The conversation is under legal hold and must be kept
+    #[error("Conversation {0} is under legal hold")]
+    LegalHold(uuid::Uuid),
+
+    /// Reading or writing a stored file failed
+    #[error("Object store error: {0}")]
+    ObjectStore(#[from] crate::object_store::ObjectStoreError),
+}
+
+impl DbError {
+    /// The driver couldn't decode a row of type `R`. Its error names the
+    /// column by index only, so `column` is `*`.
+    fn row_decode<R>(err: FromRowError) -> Self {
+        let type_name = std::any::type_name::<R>();
+        DbError::RowParse {
+            table: type_name.rsplit("::").next().unwrap_or(type_name),
+            column: "*",
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl From<LineageTooDeep> for DbError {
+    fn from(err: LineageTooDeep) -> Self {
+        DbError::LineageTooDeep {
+            depth: err.depth,
+            max: err.max,
+        }
+    }
+}
+
+impl From<serde_json::Error> for DbError {
+    fn from(err: serde_json::Error) -> Self {
+        DbError::SerializationError(err.to_string())
+    }
+}
+
+/// Execution profile a statement runs with. Each can be tuned separately in
+/// `ScyllaConfig::profiles`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StatementProfile {
+    /// Single-partition reads on the request path
+    InteractiveRead,
+    /// Single-row writes on the request path
+    InteractiveWrite,
+    /// Full-partition scans, which get a longer timeout
+    BulkRead,
+    /// Batches, which get a longer timeout
+    BulkWrite,
+}
+
+/// Handles of the execution profiles, one per `StatementProfile`
+#[derive(Clone)]
+struct ProfileHandles {
+    interactive_read: ExecutionProfileHandle,
+    interactive_write: ExecutionProfileHandle,
+    bulk_read: ExecutionProfileHandle,
+    bulk_write: ExecutionProfileHandle,
 }
 
 #[derive(Clone)]
 pub struct DbClient {
-    session: Arc<Session>,
+    session: Arc<CachingSession>,
+    profiles: ProfileHandles,
+    page_size: i32,
     keyspace: String,
+    fork_batch_strategy: BatchStrategy,
+    write_parallelism: usize,
 }
 
 impl DbClient {
+    /// Connect and apply the migrations, retrying while Scylla is not ready
+    /// for up to `startup_max_wait_secs`
     pub async fn new(config: &ScyllaConfig) -> Result<Self, DbError> {
-        tracing::info!("Initializing Scylla session with nodes {:?}", config.nodes);
-        let session = SessionBuilder::new()
-            .known_nodes(&config.nodes)
-            .build()
-            .await?;
+        let (session, profiles) =
+            retry_startup(config, "Connecting to Scylla", || Self::connect(config)).await?;
 
         tracing::info!("Scylla session established, starting migrations");
-        migration::run_migrations(&session, config).await?;
+        retry_startup(config, "Applying migrations", || {
+            migration::run_migrations(&session, config, None)
+        })
+        .await?;
 
         tracing::info!(
             "Migrations complete, selecting keyspace '{}'",
@@ -53,16 +188,285 @@ impl DbClient {
         tracing::info!("Keyspace '{}' selected", config.keyspace);
 
         Ok(DbClient {
-            session: Arc::new(session),
+            session: Arc::new(CachingSession::from(session, STATEMENT_CACHE_SIZE)),
+            profiles,
+            page_size: config.page_size,
             keyspace: config.keyspace.clone(),
+            fork_batch_strategy: config.fork_batch_strategy,
+            write_parallelism: config.write_parallelism,
         })
     }
 
+    /// Apply the migrations up to and including `target`, without opening a
+    /// client for queries
+    pub async fn migrate(config: &ScyllaConfig, target: Option<u32>) -> Result<(), DbError> {
+        let (session, _) =
+            retry_startup(config, "Connecting to Scylla", || Self::connect(config)).await?;
+        retry_startup(config, "Applying migrations", || {
+            migration::run_migrations(&session, config, target)
+        })
+        .await
+    }
+
+    /// Versions recorded in the migration ledger, or `None` if the cluster
+    /// can't be reached. Connects once, without the startup retries.
+    pub async fn applied_migrations(config: &ScyllaConfig) -> Option<HashSet<u32>> {
+        let applied = match Self::connect(config).await {
+            Ok((session, _)) => migration::applied_versions(&session, &config.keyspace).await,
+            Err(err) => Err(err),
+        };
+        applied
+            .inspect_err(|err| tracing::warn!("Couldn't read the migration ledger: {}", err))
+            .ok()
+    }
+
+    /// Open a session, returning it with the handles of its execution profiles
+    async fn connect(config: &ScyllaConfig) -> Result<(Session, ProfileHandles), DbError> {
+        tracing::info!("Initializing Scylla session with nodes {:?}", config.nodes);
+
+        // Token-aware routing only works for prepared statements, which is why every
+        // statement goes through the caching session below
+        let mut policy = DefaultPolicy::builder().token_aware(true);
+        if let Some(local_dc) = &config.local_dc {
+            policy = policy
+                .prefer_datacenter(local_dc.clone())
+                .permit_dc_failover(true);
+        }
+        let policy = policy.build();
+
+        // Speculating is only safe for idempotent statements, which are the
+        // interactive reads (see `statement`); scans would just double the load
+        let speculative = (config.speculative_retries > 0).then(|| {
+            Arc::new(SimpleSpeculativeExecutionPolicy {
+                max_retry_count: config.speculative_retries,
+                retry_interval: Duration::from_millis(config.speculative_delay_ms),
+            }) as Arc<dyn SpeculativeExecutionPolicy>
+        });
+
+        let profile = |name: &str, overrides: &ProfileOverrides, default_timeout_ms: u64| {
+            let consistency = config
+                .profile_consistency(overrides)
+                .map_err(DbError::InvalidData)?;
+            let timeout_ms = overrides.timeout_ms.unwrap_or(default_timeout_ms);
+            let mut profile = ExecutionProfile::builder()
+                .consistency(consistency)
+                .request_timeout(Some(Duration::from_millis(timeout_ms)))
+                .load_balancing_policy(policy.clone());
+            if let Some(retries) = overrides.retries {
+                profile = profile.retry_policy(Box::new(BoundedRetryPolicy::new(retries)));
+            }
+            if name == "interactive_read" {
+                profile = profile.speculative_execution_policy(speculative.clone());
+            }
+            Ok::<_, DbError>(profile.build().into_handle_with_label(name.to_string()))
+        };
+        let profiles = &config.profiles;
+        let profiles = ProfileHandles {
+            interactive_read: profile(
+                "interactive_read",
+                &profiles.interactive_read,
+                config.request_timeout_ms,
+            )?,
+            interactive_write: profile(
+                "interactive_write",
+                &profiles.interactive_write,
+                config.request_timeout_ms,
+            )?,
+            bulk_read: profile("bulk_read", &profiles.bulk_read, config.bulk_timeout_ms)?,
+            bulk_write: profile("bulk_write", &profiles.bulk_write, config.bulk_timeout_ms)?,
+        };
+
+        let connections_per_shard = NonZeroUsize::new(config.connections_per_shard)
+            .ok_or_else(|| DbError::InvalidData("connections_per_shard must be positive".into()))?;
+
+        let mut builder = SessionBuilder::new()
+            .known_nodes(&config.nodes)
+            .default_execution_profile_handle(profiles.interactive_read.clone())
+            .pool_size(PoolSize::PerShard(connections_per_shard))
+            .connection_timeout(Duration::from_millis(config.connect_timeout_ms));
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.user(username, password);
+        }
+        let session = builder.build().await?;
+
+        Ok((session, profiles))
+    }
+
     pub fn session(&self) -> &Session {
-        &self.session
+        self.session.get_session()
     }
 
     pub fn keyspace(&self) -> &str {
         &self.keyspace
     }
+
+    /// Build a statement bound to the given execution profile and the configured page size.
+    /// Interactive reads are marked idempotent, so they may be speculated.
+    pub fn statement(&self, cql: &str, profile: StatementProfile) -> Query {
+        let mut query = Query::new(cql);
+        query.set_execution_profile_handle(Some(self.profile_handle(profile)));
+        query.set_is_idempotent(profile == StatementProfile::InteractiveRead);
+        query.set_page_size(self.page_size);
+        query
+    }
+
+    /// Build an unlogged batch bound to the bulk write profile
+    pub fn batch(&self) -> Batch {
+        let mut batch = Batch::new(scylla::batch::BatchType::Unlogged);
+        batch.set_execution_profile_handle(Some(self.profiles.bulk_write.clone()));
+        batch
+    }
+
+    /// Build a logged batch bound to the bulk write profile, for writes that
+    /// must apply together across tables
+    pub fn logged_batch(&self) -> Batch {
+        let mut batch = Batch::new(scylla::batch::BatchType::Logged);
+        batch.set_execution_profile_handle(Some(self.profiles.bulk_write.clone()));
+        batch
+    }
+
+    /// Build a counter batch bound to the bulk write profile
+    pub fn counter_batch(&self) -> Batch {
+        let mut batch = Batch::new(scylla::batch::BatchType::Counter);
+        batch.set_execution_profile_handle(Some(self.profiles.bulk_write.clone()));
+        batch
+    }
+
+    /// How forks and duplicates write the rows they copy
+    pub fn fork_batch_strategy(&self) -> BatchStrategy {
+        self.fork_batch_strategy
+    }
+
+    /// Write rows, each a statement with its values, the way `strategy`
+    /// says. Batches and separate inserts alike run with the bulk write
+    /// profile.
+    pub async fn write_rows(
+        &self,
+        strategy: BatchStrategy,
+        rows: Vec<(&'static str, Vec<Option<CqlValue>>)>,
+    ) -> Result<(), DbError> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = match strategy {
+            BatchStrategy::Logged => self.logged_batch(),
+            BatchStrategy::Unlogged => self.batch(),
+            BatchStrategy::Concurrent => {
+                let inserts: Vec<_> = rows
+                    .into_iter()
+                    .map(|(cql, values)| (self.statement(cql, StatementProfile::BulkWrite), values))
+                    .collect();
+                return stream::iter(inserts)
+                    .map(|(query, values)| self.execute(query, values))
+                    .buffer_unordered(self.write_parallelism)
+                    .try_for_each(|_| async { Ok(()) })
+                    .await;
+            }
+        };
+        let mut values_list = Vec::with_capacity(rows.len());
+        for (cql, values) in rows {
+            batch.append_statement(cql);
+            values_list.push(values);
+        }
+
+        self.execute_batch(&batch, values_list).await
+    }
+
+    /// Run a trivial query against the cluster, for readiness checks
+    pub async fn ping(&self) -> Result<(), DbError> {
+        self.execute(
+            self.statement(queries::PING, StatementProfile::InteractiveRead),
+            (),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Execute a statement (prepared and cached) and return the first page
+    pub async fn execute(
+        &self,
+        query: Query,
+        values: impl SerializeRow,
+    ) -> Result<QueryResult, DbError> {
+        Ok(self.session.execute(query, values).await?)
+    }
+
+    /// Execute a batch, preparing its statements through the cache
+    pub async fn execute_batch(
+        &self,
+        batch: &Batch,
+        values: impl BatchValues,
+    ) -> Result<(), DbError> {
+        self.session.batch(batch, values).await?;
+        Ok(())
+    }
+
+    /// Fetch every row of a query, transparently following all pages
+    pub async fn fetch_all<R: FromRow + Send + 'static>(
+        &self,
+        query: Query,
+        values: impl SerializeRow,
+    ) -> Result<Vec<R>, DbError> {
+        self.fetch_stream(query, values).await?.try_collect().await
+    }
+
+    /// Fetch the first row of a query, or `NotFound`
+    pub async fn fetch_one<R: FromRow>(
+        &self,
+        query: Query,
+        values: impl SerializeRow,
+    ) -> Result<R, DbError> {
+        self.execute(query, values)
+            .await?
+            .maybe_first_row_typed::<R>()
+            .map_err(|e| match e {
+                MaybeFirstRowTypedError::FromRowError(e) => DbError::row_decode::<R>(e),
+                e => DbError::InvalidData(format!("Failed to parse row: {}", e)),
+            })?
+            .ok_or(DbError::NotFound)
+    }
+
+    /// Stream the rows of a query, fetching one page at a time
+    pub async fn fetch_stream<R: FromRow + Send + 'static>(
+        &self,
+        query: Query,
+        values: impl SerializeRow,
+    ) -> Result<BoxStream<'static, Result<R, DbError>>, DbError> {
+        let rows = self.session.execute_iter(query, values).await?;
+
+        Ok(rows
+            .into_typed::<R>()
+            .map(|row| {
+                row.map_err(|e| match e {
+                    NextRowError::QueryError(e) => DbError::QueryError(e),
+                    NextRowError::FromRowError(e) => DbError::row_decode::<R>(e),
+                })
+            })
+            .boxed())
+    }
+
+    fn profile_handle(&self, profile: StatementProfile) -> ExecutionProfileHandle {
+        match profile {
+            StatementProfile::InteractiveRead => self.profiles.interactive_read.clone(),
+            StatementProfile::InteractiveWrite => self.profiles.interactive_write.clone(),
+            StatementProfile::BulkRead => self.profiles.bulk_read.clone(),
+            StatementProfile::BulkWrite => self.profiles.bulk_write.clone(),
+        }
+    }
+
+    /// Close the session's connections. Only takes effect once every other
+    /// clone (held by the repositories) has been dropped.
+    pub fn close(self) {
+        match Arc::try_unwrap(self.session) {
+            Ok(session) => {
+                drop(session);
+                tracing::info!("Scylla session closed");
+            }
+            Err(session) => tracing::warn!(
+                "Scylla session still has {} other reference(s); leaving it to be dropped",
+                Arc::strong_count(&session) - 1
+            ),
+        }
+    }
 }
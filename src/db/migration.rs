@@ -1,5 +1,9 @@
 use scylla::Session;
+use scylla::transport::errors::{DbError as ScyllaDbError, QueryError};
+use std::collections::HashSet;
+use std::iter::Peekable;
 use std::path::PathBuf;
+use std::str::CharIndices;
 use tokio::{
     fs,
     time::{Duration, sleep},
@@ -9,10 +13,25 @@ use tracing::{debug, error, info, warn};
 use crate::config::ScyllaConfig;
 
 use super::DbError;
+use super::startup::is_transient_query_error;
 
-/// Run all `.cql` migrations using the provided session.
-/// Migrations are executed sequentially in lexicographic order.
-pub async fn run_migrations(session: &Session, config: &ScyllaConfig) -> Result<(), DbError> {
+/// A migration file, parsed into the statements it runs
+#[derive(Debug, Clone)]
+pub struct Migration {
+    /// Number the file name starts with, e.g. 3 for `003_change_feed.cql`
+    pub version: u32,
+    /// File name
+    pub name: String,
+    /// Statements in execution order, keyspace name substituted
+    pub statements: Vec<String>,
+}
+
+/// Read and parse the `.cql` migrations in lexicographic order, up to and
+/// including `target` if given. Nothing is executed.
+pub async fn load_migrations(
+    config: &ScyllaConfig,
+    target: Option<u32>,
+) -> Result<Vec<Migration>, DbError> {
     let migrations_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("migrations");
     let migrations_dir_display = migrations_dir.to_str().unwrap_or("migrations directory");
 
@@ -43,57 +62,130 @@ pub async fn run_migrations(session: &Session, config: &ScyllaConfig) -> Result<
             "No migrations found in '{}'; skipping migration step",
             migrations_dir_display
         );
-        return Ok(());
+        return Ok(Vec::new());
     }
 
-    info!("Applying {} migration file(s)", files.len());
+    let mut migrations = Vec::new();
+    for path in files {
+        let name = path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        let version = name
+            .split('_')
+            .next()
+            .and_then(|prefix| prefix.parse().ok())
+            .ok_or_else(|| {
+                DbError::MigrationError(format!(
+                    "Migration file {} must start with a version number",
+                    name
+                ))
+            })?;
+        if target.is_some_and(|target| version > target) {
+            continue;
+        }
 
-    let mut keyspace_ready = false;
+        let migration_sql = fs::read_to_string(&path)
+            .await
+            .map_err(|e| DbError::MigrationError(format!("Failed to read {}: {}", name, e)))?;
 
-    for path in files {
-        let display_path = path
-            .to_str()
-            .map(|s| s.to_string())
-            .or_else(|| {
-                path.file_name()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.to_string())
-            })
-            .unwrap_or_else(|| "unknown".to_string());
-        info!("Running migration file: {}", display_path);
+        let migration_sql = substitute_placeholders(&migration_sql, config)
+            .map_err(|e| DbError::MigrationError(format!("{}: {}", name, e)))?;
 
-        let migration_sql = fs::read_to_string(&path).await.map_err(|e| {
-            DbError::MigrationError(format!("Failed to read {}: {}", display_path, e))
-        })?;
-
-        // Replace default keyspace name with configured keyspace, if present.
-        let migration_sql = migration_sql.replace("aigc_history", &config.keyspace);
-
-        let statements: Vec<String> = migration_sql
-            .split(';')
-            .filter_map(|chunk| {
-                let cleaned = chunk
-                    .lines()
-                    .filter_map(|line| {
-                        let trimmed = line.trim();
-                        if trimmed.is_empty() || trimmed.starts_with("--") {
-                            None
-                        } else {
-                            Some(trimmed)
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .join(" ");
+        let statements = parse_statements(&migration_sql, &config.keyspace)
+            .map_err(|e| DbError::MigrationError(format!("{}: {}", name, e)))?;
 
-                let statement = cleaned.trim().to_string();
-                if statement.is_empty() {
-                    None
-                } else {
-                    Some(statement)
-                }
-            })
-            .collect();
+        migrations.push(Migration {
+            version,
+            name,
+            statements,
+        });
+    }
+
+    if let Some(target) = target
+        && !migrations.iter().any(|m| m.version == target)
+    {
+        return Err(DbError::MigrationError(format!(
+            "No migration has version {}",
+            target
+        )));
+    }
+
+    Ok(migrations)
+}
+
+/// The statements `run_migrations` would execute, with what each one does,
+/// for review before applying them
+pub fn format_plan<'a>(migrations: impl IntoIterator<Item = &'a Migration>) -> String {
+    let mut plan = String::new();
+    for migration in migrations {
+        plan.push_str(&format!(
+            "-- {} (version {})\n",
+            migration.name, migration.version
+        ));
+        for (index, statement) in migration.statements.iter().enumerate() {
+            plan.push_str(&format!(
+                "-- {}: {}\n{};\n",
+                index + 1,
+                describe(statement),
+                statement
+            ));
+        }
+        plan.push('\n');
+    }
+    plan
+}
+
+/// Table of the keyspace recording the migrations applied to it
+const LEDGER_TABLE: &str = "schema_migrations";
+
+/// The migrations not applied yet, in order
+pub fn pending<'a>(migrations: &'a [Migration], applied: &HashSet<u32>) -> Vec<&'a Migration> {
+    migrations
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect()
+}
+
+/// Whether a statement failed only because what it creates is already
+/// there, as when a migration applied before the ledger existed runs again
+fn already_applied(err: &QueryError) -> bool {
+    match err {
+        QueryError::DbError(ScyllaDbError::AlreadyExists { .. }, _) => true,
+        QueryError::DbError(ScyllaDbError::Invalid, msg) => {
+            msg.contains("conflicts with an existing column")
+        }
+        _ => false,
+    }
+}
+
+/// Run the `.cql` migrations using the provided session, up to and including
+/// `target` if given. Migrations are executed sequentially in lexicographic
+/// order, and each one is recorded in `schema_migrations` once applied, so
+/// later runs skip it.
+pub async fn run_migrations(
+    session: &Session,
+    config: &ScyllaConfig,
+    target: Option<u32>,
+) -> Result<(), DbError> {
+    let migrations = load_migrations(config, target).await?;
+    let applied = applied_versions(session, &config.keyspace).await?;
+    let migrations = pending(&migrations, &applied);
+    if migrations.is_empty() {
+        info!("Schema is up to date");
+        return Ok(());
+    }
 
+    info!("Applying {} migration file(s)", migrations.len());
+
+    let mut keyspace_ready = false;
+    let mut ledger_ready = false;
+
+    for migration in migrations {
+        let display_path = &migration.name;
+        let statements = &migration.statements;
+        info!("Running migration file: {}", display_path);
         info!("Executing {} statement(s)", statements.len());
 
         for (index, statement) in statements.iter().enumerate() {
@@ -104,27 +196,15 @@ pub async fn run_migrations(session: &Session, config: &ScyllaConfig) -> Result<
                     index + 1,
                     config.keyspace
                 );
-                ensure_keyspace_selected(session, &config.keyspace, index + 1, &display_path)
+                ensure_keyspace_selected(session, &config.keyspace, index + 1, display_path)
                     .await?;
                 keyspace_ready = true;
                 continue;
-            } else if upper.contains("CREATE KEYSPACE") {
-                info!("Statement {}: creating keyspace if absent", index + 1);
-            } else if upper.contains("CREATE TABLE") {
-                let table_name = statement
-                    .split_whitespace()
-                    .skip_while(|&s| s.to_uppercase() != "TABLE")
-                    .nth(1)
-                    .unwrap_or("unknown");
-                info!("Statement {}: creating table {}", index + 1, table_name);
-            } else if upper.contains("CREATE INDEX") {
-                info!("Statement {}: creating index", index + 1);
-            } else {
-                info!("Statement {}: executing migration statement", index + 1);
             }
+            info!("Statement {}: {}", index + 1, describe(statement));
 
             if !keyspace_ready && !upper.contains("CREATE KEYSPACE") {
-                ensure_keyspace_selected(session, &config.keyspace, index + 1, &display_path)
+                ensure_keyspace_selected(session, &config.keyspace, index + 1, display_path)
                     .await?;
                 keyspace_ready = true;
             }
@@ -140,9 +220,9 @@ pub async fn run_migrations(session: &Session, config: &ScyllaConfig) -> Result<
                 Err(err) => {
                     // Allow idempotent migrations.
                     let error_msg = err.to_string();
-                    if error_msg.contains("already exists") {
+                    if already_applied(&err) {
                         warn!(
-                            "Statement {} skipped: object already exists ({}).",
+                            "Statement {} skipped: already applied ({}).",
                             index + 1,
                             error_msg
                         );
@@ -157,6 +237,10 @@ pub async fn run_migrations(session: &Session, config: &ScyllaConfig) -> Result<
                     );
                     debug!("Statement {} content: {}", index + 1, statement);
 
+                    // Keep errors the cluster may recover from retryable at startup
+                    if is_transient_query_error(&err) {
+                        return Err(DbError::QueryError(err));
+                    }
                     return Err(DbError::MigrationError(format!(
                         "Failed to execute statement {} from {}: {}",
                         index + 1,
@@ -179,17 +263,279 @@ pub async fn run_migrations(session: &Session, config: &ScyllaConfig) -> Result<
                         config.keyspace, err
                     );
                 }
-                ensure_keyspace_selected(session, &config.keyspace, index + 1, &display_path)
+                ensure_keyspace_selected(session, &config.keyspace, index + 1, display_path)
                     .await?;
                 keyspace_ready = true;
             }
         }
+
+        if !ledger_ready {
+            create_ledger(session, &config.keyspace).await?;
+            ledger_ready = true;
+        }
+        record_applied(session, &config.keyspace, migration).await?;
     }
 
     info!("Database migrations applied successfully");
     Ok(())
 }
 
+/// Versions recorded in the ledger; none before the keyspace or the ledger
+/// exist
+pub async fn applied_versions(session: &Session, keyspace: &str) -> Result<HashSet<u32>, DbError> {
+    let query = format!("SELECT version FROM {}.{}", keyspace, LEDGER_TABLE);
+    match session.query(query, &[]).await {
+        Ok(result) => Ok(result
+            .rows_typed_or_empty::<(i32,)>()
+            .filter_map(Result::ok)
+            .map(|(version,)| version as u32)
+            .collect()),
+        Err(err) if is_transient_query_error(&err) => Err(DbError::QueryError(err)),
+        Err(err) => {
+            info!(
+                "No migration ledger yet ({}); applying every migration",
+                err
+            );
+            Ok(HashSet::new())
+        }
+    }
+}
+
+async fn create_ledger(session: &Session, keyspace: &str) -> Result<(), DbError> {
+    let statement = format!(
+        "CREATE TABLE IF NOT EXISTS {}.{} (version INT PRIMARY KEY, name TEXT, applied_at TIMESTAMP)",
+        keyspace, LEDGER_TABLE
+    );
+    session.query(statement, &[]).await?;
+    if let Err(err) = session.await_schema_agreement().await {
+        warn!(
+            "Schema agreement wait after creating the migration ledger failed: {}",
+            err
+        );
+    }
+    Ok(())
+}
+
+async fn record_applied(
+    session: &Session,
+    keyspace: &str,
+    migration: &Migration,
+) -> Result<(), DbError> {
+    let statement = format!(
+        "INSERT INTO {}.{} (version, name, applied_at) VALUES (?, ?, ?)",
+        keyspace, LEDGER_TABLE
+    );
+    session
+        .query(
+            statement,
+            (
+                migration.version as i32,
+                migration.name.as_str(),
+                chrono::Utc::now(),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Replace the `{{name}}` placeholders of a migration with table and keyspace
+/// options from the configuration, so every environment runs the same files.
+/// Unknown placeholders are an error rather than reaching the cluster.
+fn substitute_placeholders(migration_sql: &str, config: &ScyllaConfig) -> Result<String, String> {
+    let mut substituted = String::with_capacity(migration_sql.len());
+    let mut rest = migration_sql;
+
+    while let Some(start) = rest.find("{{") {
+        substituted.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "unterminated `{{` placeholder".to_string())?;
+        let value = match after[..end].trim() {
+            "replication" => config.replication.clone(),
+            "compaction" => config.compaction.clone(),
+            "change_ttl" => config.change_ttl_secs.to_string(),
+            "notification_ttl" => config.notification_ttl_secs.to_string(),
+            "access_log_ttl" => config.access_log_ttl_secs.to_string(),
+            other => return Err(format!("unknown placeholder `{{{{{}}}}}`", other)),
+        };
+        substituted.push_str(&value);
+        rest = &after[end + 2..];
+    }
+    substituted.push_str(rest);
+
+    Ok(substituted)
+}
+
+/// Why a migration file couldn't be split into statements
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SplitError {
+    #[error("unterminated {kind} starting on line {line}")]
+    Unterminated { kind: &'static str, line: usize },
+}
+
+/// Split a migration file into statements, replacing the default keyspace name
+/// with the configured one. Aware of CQL syntax: `;` ends a statement only
+/// outside quoted strings, identifiers and `$$` literals and outside
+/// `BEGIN BATCH ... APPLY BATCH`, and `--`, `//` and `/* */` comments are
+/// dropped. Whitespace outside literals is collapsed to single spaces.
+///
+/// A literal, block comment or batch still open at the end of the file is
+/// an error rather than swallowing the statements after it.
+pub fn parse_statements(migration_sql: &str, keyspace: &str) -> Result<Vec<String>, SplitError> {
+    let sql = migration_sql.replace("aigc_history", keyspace);
+    let unterminated = |kind, offset: usize| SplitError::Unterminated {
+        kind,
+        line: sql[..offset].matches('\n').count() + 1,
+    };
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut statement_start = 0;
+    let mut chars = sql.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        match c {
+            // Strings and quoted identifiers; a doubled quote escapes itself
+            '\'' | '"' => {
+                current.push(c);
+                let mut closed = false;
+                while let Some((_, next)) = chars.next() {
+                    current.push(next);
+                    if next == c {
+                        if next_is(&mut chars, c) {
+                            current.push(c);
+                            chars.next();
+                        } else {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+                if !closed {
+                    let kind = if c == '\'' {
+                        "string"
+                    } else {
+                        "quoted identifier"
+                    };
+                    return Err(unterminated(kind, offset));
+                }
+            }
+            '$' if next_is(&mut chars, '$') => {
+                chars.next();
+                current.push_str("$$");
+                let mut closed = false;
+                while let Some((_, next)) = chars.next() {
+                    current.push(next);
+                    if next == '$' && next_is(&mut chars, '$') {
+                        current.push('$');
+                        chars.next();
+                        closed = true;
+                        break;
+                    }
+                }
+                if !closed {
+                    return Err(unterminated("`$$` literal", offset));
+                }
+            }
+            '-' | '/' if next_is(&mut chars, c) => {
+                for (_, next) in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+                push_space(&mut current);
+            }
+            '/' if next_is(&mut chars, '*') => {
+                chars.next();
+                let mut previous = '\0';
+                let mut closed = false;
+                for (_, next) in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        closed = true;
+                        break;
+                    }
+                    previous = next;
+                }
+                if !closed {
+                    return Err(unterminated("block comment", offset));
+                }
+                push_space(&mut current);
+            }
+            ';' if is_open_batch(&current) => current.push(';'),
+            ';' => finish_statement(&mut current, &mut statements),
+            c if c.is_whitespace() => push_space(&mut current),
+            c => {
+                if current.is_empty() {
+                    statement_start = offset;
+                }
+                current.push(c);
+            }
+        }
+    }
+    if is_open_batch(&current) {
+        return Err(unterminated("batch", statement_start));
+    }
+    finish_statement(&mut current, &mut statements);
+
+    Ok(statements)
+}
+
+fn next_is(chars: &mut Peekable<CharIndices<'_>>, c: char) -> bool {
+    chars.peek().is_some_and(|&(_, next)| next == c)
+}
+
+fn push_space(statement: &mut String) {
+    if !statement.is_empty() && !statement.ends_with(' ') {
+        statement.push(' ');
+    }
+}
+
+fn finish_statement(current: &mut String, statements: &mut Vec<String>) {
+    let statement = current.trim();
+    if !statement.is_empty() {
+        statements.push(statement.to_string());
+    }
+    current.clear();
+}
+
+/// Whether a statement is a batch whose `APPLY BATCH` has not been reached,
+/// so a `;` separates statements inside it
+fn is_open_batch(statement: &str) -> bool {
+    let upper = statement.to_uppercase();
+    let mut words = upper.split_whitespace();
+    words.next() == Some("BEGIN")
+        && words.take(2).any(|word| word == "BATCH")
+        && !upper.trim_end().ends_with("APPLY BATCH")
+}
+
+/// What a statement does, for logs and plans
+fn describe(statement: &str) -> String {
+    let upper = statement.to_uppercase();
+    let object_name = |kind: &str| {
+        statement
+            .split_whitespace()
+            .skip_while(|&s| s.to_uppercase() != kind)
+            .skip(1)
+            .find(|s| !matches!(s.to_uppercase().as_str(), "IF" | "NOT" | "EXISTS"))
+            .unwrap_or("unknown")
+            .to_string()
+    };
+
+    if upper.starts_with("USE ") {
+        "switching keyspace".to_string()
+    } else if upper.contains("CREATE KEYSPACE") {
+        "creating keyspace if absent".to_string()
+    } else if upper.contains("CREATE TABLE") {
+        format!("creating table {}", object_name("TABLE"))
+    } else if upper.contains("ALTER TABLE") {
+        format!("altering table {}", object_name("TABLE"))
+    } else if upper.contains("CREATE INDEX") {
+        "creating index".to_string()
+    } else {
+        "executing migration statement".to_string()
+    }
+}
+
 async fn ensure_keyspace_selected(
     session: &Session,
     keyspace: &str,
@@ -247,3 +593,147 @@ async fn ensure_keyspace_selected(
         keyspace, MAX_ATTEMPTS, display_path
     )))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+
+    #[tokio::test]
+    async fn test_plan_stops_at_target() {
+        let mut config = Settings::default().scylla;
+        config.keyspace = "history_staging".to_string();
+
+        let migrations = load_migrations(&config, Some(2)).await.unwrap();
+        let versions: Vec<u32> = migrations.iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![1, 2]);
+
+        let plan = format_plan(&migrations);
+        assert!(plan.starts_with("-- 001_initial_schema.cql (version 1)\n"));
+        assert!(plan.contains("USE history_staging;"));
+        assert!(plan.contains("creating table conversation_checkpoints"));
+        assert!(!plan.contains("aigc_history"));
+
+        assert!(load_migrations(&config, Some(999)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_a_second_run_applies_nothing() {
+        let config = Settings::default().scylla;
+        let migrations = load_migrations(&config, None).await.unwrap();
+
+        let first = pending(&migrations, &HashSet::new());
+        assert_eq!(first.len(), migrations.len());
+        let applied: HashSet<u32> = first.iter().map(|m| m.version).collect();
+        assert!(pending(&migrations, &applied).is_empty());
+
+        // Clusters migrated before the ledger existed run everything once
+        // more, so each statement must find its work already done
+        for migration in &migrations {
+            for statement in &migration.statements {
+                let upper = statement.to_uppercase();
+                assert!(
+                    upper.starts_with("USE ")
+                        || upper.contains(" IF NOT EXISTS ")
+                        || (upper.starts_with("ALTER TABLE ") && upper.contains(" ADD ")),
+                    "{} can't run twice: {}",
+                    migration.name,
+                    statement
+                );
+            }
+        }
+        let invalid = |msg: &str| QueryError::DbError(ScyllaDbError::Invalid, msg.to_string());
+        assert!(already_applied(&invalid(
+            "Invalid column name content_hash because it conflicts with an existing column"
+        )));
+        assert!(already_applied(&QueryError::DbError(
+            ScyllaDbError::AlreadyExists {
+                keyspace: "aigc_history".to_string(),
+                table: "jobs".to_string(),
+            },
+            "Cannot add already existing table".to_string(),
+        )));
+        assert!(!already_applied(&invalid(
+            "Undefined column name content_hash"
+        )));
+    }
+
+    #[tokio::test]
+    async fn test_placeholders_take_configured_options() {
+        let mut config = Settings::default().scylla;
+        config.replication = "{'class': 'NetworkTopologyStrategy', 'dc1': 3}".to_string();
+        config.change_ttl_secs = 86400;
+
+        let plan = format_plan(&load_migrations(&config, None).await.unwrap());
+        assert!(
+            plan.contains("WITH replication = {'class': 'NetworkTopologyStrategy', 'dc1': 3};")
+        );
+        assert!(plan.contains("default_time_to_live = 86400;"));
+        assert!(!plan.contains("{{"));
+
+        assert!(substitute_placeholders("WITH x = {{replicaton}}", &config).is_err());
+    }
+    #[test]
+    fn test_statements_split_outside_literals_and_batches() {
+        let sql = r#"
+            -- A table; with a comment
+            CREATE TABLE IF NOT EXISTS aigc_history.notes (
+                id INT PRIMARY KEY, -- key
+                body TEXT /* multi-line;
+                comment */
+            );
+            INSERT INTO notes (id, body) VALUES (1, 'semi; colon -- and ''quotes''');
+            CREATE FUNCTION f(x INT) RETURNS NULL ON NULL INPUT RETURNS INT
+                LANGUAGE lua AS $$ return x; $$;
+            BEGIN UNLOGGED BATCH
+                INSERT INTO notes (id, body) VALUES (2, 'a');
+                INSERT INTO "Odd;Name" (id) VALUES (3);
+            APPLY BATCH;
+            // trailing comment
+        "#;
+
+        assert_eq!(
+            parse_statements(sql, "history").unwrap(),
+            vec![
+                "CREATE TABLE IF NOT EXISTS history.notes ( id INT PRIMARY KEY, body TEXT )",
+                "INSERT INTO notes (id, body) VALUES (1, 'semi; colon -- and ''quotes''')",
+                "CREATE FUNCTION f(x INT) RETURNS NULL ON NULL INPUT RETURNS INT LANGUAGE lua AS $$ return x; $$",
+                "BEGIN UNLOGGED BATCH INSERT INTO notes (id, body) VALUES (2, 'a'); INSERT INTO \"Odd;Name\" (id) VALUES (3); APPLY BATCH",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unterminated_literals_are_errors() {
+        for (sql, kind, line) in [
+            (
+                "SELECT 1;\nINSERT INTO t (a) VALUES ('oops);\nSELECT 2;",
+                "string",
+                2,
+            ),
+            ("CREATE TABLE \"odd (a INT);", "quoted identifier", 1),
+            (
+                "SELECT 1;\n\nCREATE FUNCTION f() AS $$ return 1;",
+                "`$$` literal",
+                3,
+            ),
+            ("SELECT 1; /* never closed", "block comment", 1),
+            (
+                "SELECT 1;\nBEGIN BATCH INSERT INTO t (a) VALUES (1);",
+                "batch",
+                2,
+            ),
+        ] {
+            assert_eq!(
+                parse_statements(sql, "history"),
+                Err(SplitError::Unterminated { kind, line }),
+                "{}",
+                sql
+            );
+        }
+        assert_eq!(
+            parse_statements("SELECT 1; -- no newline at the end", "history").unwrap(),
+            vec!["SELECT 1"]
+        );
+    }
+}
@@ -2,6 +2,8 @@ pub mod client;
 pub mod migration;
 pub mod models;
 pub mod queries;
+pub mod retry;
+pub mod startup;
 
-pub use client::{DbClient, DbError};
+pub use client::{DbClient, DbError, ForkRejection, StatementProfile};
 pub use models::*;
@@ -1,9 +1,28 @@
 use chrono::{DateTime, Utc};
 use scylla::FromRow;
+use scylla::frame::response::result::CqlValue;
+use scylla::frame::value::{Counter, CqlTimeuuid};
 use std::collections::HashMap;
 use uuid::Uuid;
 
-use crate::domain::{Branch, Message, MessageRole, Permission, Share};
+use crate::domain::{
+    AccessLogEntry, Branch, BranchNaming, BranchSlug, Change, ChangeKind, ContentError,
+    Conversation, ConversationEvent, ConversationExport, ConversationLock, EventKind, ForkProgress,
+    Invite, Job, JobKind, JobStatus, LegalHold, Message, MessageRole, Notification,
+    NotificationKind, OutboxEntry, OutboxKind, Permission, Share, UserPreferences, WebhookFilter,
+    WebhookSubscription, slugify,
+};
+
+use super::DbError;
+
+/// `column` of a `table` row holds a value the domain model doesn't accept
+fn row_parse(table: &'static str, column: &'static str, reason: impl ToString) -> DbError {
+    DbError::RowParse {
+        table,
+        column,
+        reason: reason.to_string(),
+    }
+}
 
 // Database row model for conversation_lineage table
 #[derive(Debug, Clone, FromRow)]
@@ -18,15 +37,20 @@ pub struct MessageRow {
     pub lineage: Vec<Uuid>,
     pub created_at: DateTime<Utc>,
     pub created_by: String,
+    /// See `utils::content_hash`; missing on rows written before it existed
+    /// and on encrypted rows
+    pub content_hash: Option<String>,
+    /// Data key `content_data` is encrypted with (see
+    /// `repositories::content_cipher`); `None` if it is stored in the clear
+    pub key_id: Option<Uuid>,
 }
 
 impl MessageRow {
-    pub fn from_message(message: &Message) -> Result<Self, String> {
+    pub fn from_message(message: &Message) -> Result<Self, DbError> {
         let content_type = message.content.to_type_string().to_string();
-        let content_data = message
-            .content
-            .to_json_string()
-            .map_err(|e| format!("Failed to serialize content: {}", e))?;
+        let content_data = message.content.to_json_string()?;
+
+        let content_hash = Some(crate::utils::content_hash::content_hash(&content_data));
 
         Ok(MessageRow {
             conversation_id: message.conversation_id,
@@ -39,16 +63,75 @@ impl MessageRow {
             lineage: message.lineage.clone(),
             created_at: message.created_at,
             created_by: message.created_by.clone(),
+            content_hash,
+            key_id: None,
         })
     }
 
-    pub fn to_message(self) -> Result<Message, String> {
-        let role =
-            MessageRole::parse(&self.role).ok_or_else(|| format!("Invalid role: {}", self.role))?;
+    /// Values bound to `INSERT_MESSAGE`, for batches that mix statements
+    pub fn insert_values(self) -> Vec<Option<CqlValue>> {
+        vec![
+            Some(CqlValue::Uuid(self.conversation_id)),
+            Some(CqlValue::Uuid(self.message_id)),
+            self.parent_message_id.map(CqlValue::Uuid),
+            Some(CqlValue::Text(self.role)),
+            Some(CqlValue::Text(self.content_type)),
+            Some(CqlValue::Text(self.content_data)),
+            self.content_metadata.map(|metadata| {
+                CqlValue::Map(
+                    metadata
+                        .into_iter()
+                        .map(|(k, v)| (CqlValue::Text(k), CqlValue::Text(v)))
+                        .collect(),
+                )
+            }),
+            Some(CqlValue::List(
+                self.lineage.into_iter().map(CqlValue::Uuid).collect(),
+            )),
+            Some(CqlValue::Timestamp(self.created_at.into())),
+            Some(CqlValue::Text(self.created_by)),
+            self.content_hash.map(CqlValue::Text),
+            self.key_id.map(CqlValue::Uuid),
+        ]
+    }
+
+    /// SHA-256 of the content if it was offloaded to the object store (see
+    /// `repositories::content_blobs`), in which case `content_data` only
+    /// references it
+    pub fn blob_hash(&self) -> Option<&str> {
+        self.content_data
+            .strip_prefix(crate::repositories::content_blobs::BLOB_REF_PREFIX)
+    }
+
+    /// Offloaded content must have been loaded back into the row first
+    pub fn to_message(self) -> Result<Message, DbError> {
+        const TABLE: &str = "conversation_lineage";
+        let role = MessageRole::parse(&self.role)
+            .ok_or_else(|| row_parse(TABLE, "role", format!("unknown role {}", self.role)))?;
+        if let Some(hash) = self.blob_hash() {
+            return Err(row_parse(
+                TABLE,
+                "content_data",
+                format!("content is in blob {}, which was not loaded", hash),
+            ));
+        }
+        if let Some(key_id) = self.key_id {
+            return Err(row_parse(
+                TABLE,
+                "content_data",
+                format!(
+                    "content is encrypted with data key {} and was not decrypted",
+                    key_id
+                ),
+            ));
+        }
 
         let content =
             crate::domain::ContentType::from_parts(&self.content_type, &self.content_data)
-                .map_err(|e| format!("Failed to deserialize content: {}", e))?;
+                .map_err(|e| match e {
+                    ContentError::UnknownType(value) => DbError::UnknownContentType { value },
+                    e => row_parse(TABLE, "content_data", e),
+                })?;
 
         Ok(Message {
             conversation_id: self.conversation_id,
@@ -75,6 +158,8 @@ pub struct BranchRow {
     pub last_updated: DateTime<Utc>,
     pub created_by: String,
     pub is_active: bool,
+    /// `None` for branches created before slugs existed
+    pub slug: Option<String>,
 }
 
 impl BranchRow {
@@ -88,6 +173,7 @@ impl BranchRow {
             last_updated: branch.last_updated,
             created_by: branch.created_by.clone(),
             is_active: branch.is_active,
+            slug: Some(branch.slug.clone()),
         }
     }
 
@@ -95,6 +181,7 @@ impl BranchRow {
         Branch {
             conversation_id: self.conversation_id,
             branch_id: self.branch_id,
+            slug: self.slug.unwrap_or_else(|| slugify(&self.branch_name)),
             branch_name: self.branch_name,
             leaf_message_id: self.leaf_message_id,
             created_at: self.created_at,
@@ -126,9 +213,14 @@ impl ShareRow {
         }
     }
 
-    pub fn to_share(self) -> Result<Share, String> {
-        let permission = Permission::parse(&self.permission)
-            .ok_or_else(|| format!("Invalid permission: {}", self.permission))?;
+    pub fn to_share(self) -> Result<Share, DbError> {
+        let permission = Permission::parse(&self.permission).ok_or_else(|| {
+            row_parse(
+                "conversation_shares",
+                "permission",
+                format!("unknown permission {}", self.permission),
+            )
+        })?;
 
         Ok(Share {
             conversation_id: self.conversation_id,
@@ -140,6 +232,149 @@ impl ShareRow {
     }
 }
 
+// Database row model for user_preferences table
+#[derive(Debug, Clone, FromRow)]
+pub struct UserPreferencesRow {
+    pub user_id: String,
+    /// Recipient to permission; null when empty
+    pub default_shares: Option<HashMap<String, String>>,
+    pub allow_public_forks: bool,
+    pub default_page_size: Option<i32>,
+    /// Null when the deployment default applies
+    pub branch_naming: Option<String>,
+    /// Null when empty
+    pub muted_notifications: Option<Vec<String>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl UserPreferencesRow {
+    pub fn from_preferences(preferences: &UserPreferences) -> Self {
+        UserPreferencesRow {
+            user_id: preferences.user_id.clone(),
+            default_shares: Some(
+                preferences
+                    .default_shares
+                    .iter()
+                    .map(|(user_id, permission)| (user_id.clone(), permission.as_str().to_string()))
+                    .collect(),
+            ),
+            allow_public_forks: preferences.allow_public_forks,
+            default_page_size: preferences.default_page_size.map(|size| size as i32),
+            branch_naming: preferences
+                .branch_naming
+                .map(|naming| naming.as_str().to_string()),
+            muted_notifications: Some(
+                preferences
+                    .muted_notifications
+                    .iter()
+                    .map(|kind| kind.as_str().to_string())
+                    .collect(),
+            ),
+            updated_at: preferences.updated_at,
+        }
+    }
+
+    pub fn to_preferences(self) -> Result<UserPreferences, DbError> {
+        const TABLE: &str = "user_preferences";
+        let default_shares = self
+            .default_shares
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(user_id, permission)| {
+                Permission::parse(&permission)
+                    .map(|permission| (user_id, permission))
+                    .ok_or_else(|| {
+                        row_parse(
+                            TABLE,
+                            "default_shares",
+                            format!("unknown permission {}", permission),
+                        )
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+        let branch_naming = match self.branch_naming {
+            Some(naming) => Some(BranchNaming::parse(&naming).ok_or_else(|| {
+                row_parse(
+                    TABLE,
+                    "branch_naming",
+                    format!("unknown branch naming {}", naming),
+                )
+            })?),
+            None => None,
+        };
+        let muted_notifications = self
+            .muted_notifications
+            .unwrap_or_default()
+            .into_iter()
+            .map(|kind| {
+                NotificationKind::parse(&kind).ok_or_else(|| {
+                    row_parse(
+                        TABLE,
+                        "muted_notifications",
+                        format!("unknown notification kind {}", kind),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(UserPreferences {
+            user_id: self.user_id,
+            default_shares,
+            allow_public_forks: self.allow_public_forks,
+            default_page_size: self.default_page_size.map(|size| size as usize),
+            branch_naming,
+            muted_notifications,
+            updated_at: self.updated_at,
+        })
+    }
+}
+
+// Database row model for share_invites table
+#[derive(Debug, Clone, FromRow)]
+pub struct InviteRow {
+    pub email: String,
+    pub token: String,
+    pub conversation_id: Uuid,
+    pub permission: String,
+    pub invited_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl InviteRow {
+    pub fn from_invite(invite: &Invite) -> Self {
+        InviteRow {
+            email: invite.email.clone(),
+            token: invite.token.clone(),
+            conversation_id: invite.conversation_id,
+            permission: invite.permission.as_str().to_string(),
+            invited_by: invite.invited_by.clone(),
+            created_at: invite.created_at,
+            expires_at: invite.expires_at,
+        }
+    }
+
+    pub fn to_invite(self) -> Result<Invite, DbError> {
+        let permission = Permission::parse(&self.permission).ok_or_else(|| {
+            row_parse(
+                "share_invites",
+                "permission",
+                format!("unknown permission {}", self.permission),
+            )
+        })?;
+
+        Ok(Invite {
+            email: self.email,
+            token: self.token,
+            conversation_id: self.conversation_id,
+            permission,
+            invited_by: self.invited_by,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        })
+    }
+}
+
 // Database row model for user_conversations table
 #[derive(Debug, Clone, FromRow)]
 pub struct UserConversationRow {
@@ -149,6 +384,227 @@ pub struct UserConversationRow {
     pub active_branch_id: Option<Uuid>,
 }
 
+// Database row model for conversation_titles_by_user table
+#[derive(Debug, Clone, FromRow)]
+pub struct ConversationTitleRow {
+    pub user_id: String,
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub updated_at: DateTime<Utc>,
+    pub language: Option<String>,
+}
+
+impl ConversationTitleRow {
+    /// Index entry for a conversation under its creator; `None` if the root carries no title
+    pub fn from_conversation(conversation: &Conversation) -> Option<Self> {
+        Some(ConversationTitleRow {
+            user_id: conversation.created_by().to_string(),
+            conversation_id: conversation.conversation_id,
+            title: conversation.title()?,
+            updated_at: Utc::now(),
+            language: conversation.metadata()?.language.clone(),
+        })
+    }
+}
+
+// Database row model for conversation_locks table
+#[derive(Debug, Clone, FromRow)]
+pub struct LockRow {
+    pub conversation_id: Uuid,
+    pub locked_by: String,
+    pub locked_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+impl LockRow {
+    pub fn to_lock(self) -> ConversationLock {
+        ConversationLock {
+            conversation_id: self.conversation_id,
+            locked_by: self.locked_by,
+            locked_at: self.locked_at,
+            reason: self.reason,
+        }
+    }
+}
+
+// Database row model for legal_holds table
+#[derive(Debug, Clone, FromRow)]
+pub struct LegalHoldRow {
+    pub conversation_id: Uuid,
+    pub placed_by: String,
+    pub placed_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+impl LegalHoldRow {
+    pub fn to_hold(self) -> LegalHold {
+        LegalHold {
+            conversation_id: self.conversation_id,
+            placed_by: self.placed_by,
+            placed_at: self.placed_at,
+            reason: self.reason,
+        }
+    }
+}
+
+// Database row model for conversation_keys table
+#[derive(Debug, Clone, FromRow)]
+pub struct ConversationKeyRow {
+    pub conversation_id: Uuid,
+    pub key_id: Uuid,
+    /// Master key `wrapped_key` is sealed with
+    pub master_key_id: String,
+    pub wrapped_key: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Database row model for fork_progress table
+#[derive(Debug, Clone, FromRow)]
+pub struct ForkProgressRow {
+    pub conversation_id: Uuid,
+    pub source_conversation_id: Uuid,
+    pub created_by: String,
+    pub total_chunks: i32,
+    pub completed_chunks: i32,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ForkProgressRow {
+    pub fn to_progress(self) -> ForkProgress {
+        ForkProgress {
+            conversation_id: self.conversation_id,
+            source_conversation_id: self.source_conversation_id,
+            created_by: self.created_by,
+            total_chunks: self.total_chunks,
+            completed_chunks: self.completed_chunks,
+            started_at: self.started_at,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
+// Database row model for webhook_subscriptions table
+#[derive(Debug, Clone, FromRow)]
+pub struct WebhookSubscriptionRow {
+    pub conversation_id: Uuid,
+    pub subscription_id: Uuid,
+    pub url: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    /// Null when empty
+    pub event_types: Option<Vec<String>>,
+    pub roles: Option<Vec<String>>,
+    pub content_types: Option<Vec<String>>,
+    pub secret: Option<String>,
+    pub delivered_at: DateTime<Utc>,
+    pub delivered_change_id: Uuid,
+}
+
+impl WebhookSubscriptionRow {
+    pub fn from_subscription(subscription: &WebhookSubscription) -> Self {
+        let filter = &subscription.filter;
+        let (delivered_at, delivered_change_id) = subscription.delivered_up_to;
+        WebhookSubscriptionRow {
+            conversation_id: subscription.conversation_id,
+            subscription_id: subscription.subscription_id,
+            url: subscription.url.clone(),
+            created_by: subscription.created_by.clone(),
+            created_at: subscription.created_at,
+            event_types: Some(
+                filter
+                    .event_types
+                    .iter()
+                    .map(|kind| kind.as_str().to_string())
+                    .collect(),
+            ),
+            roles: Some(
+                filter
+                    .roles
+                    .iter()
+                    .map(|role| role.as_str().to_string())
+                    .collect(),
+            ),
+            content_types: Some(filter.content_types.clone()),
+            secret: subscription.secret.clone(),
+            delivered_at,
+            delivered_change_id,
+        }
+    }
+
+    pub fn to_subscription(self) -> Result<WebhookSubscription, DbError> {
+        const TABLE: &str = "webhook_subscriptions";
+        let event_types = self
+            .event_types
+            .unwrap_or_default()
+            .into_iter()
+            .map(|kind| {
+                ChangeKind::parse(&kind).ok_or_else(|| {
+                    row_parse(
+                        TABLE,
+                        "event_types",
+                        format!("unknown change kind {}", kind),
+                    )
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let roles = self
+            .roles
+            .unwrap_or_default()
+            .into_iter()
+            .map(|role| {
+                MessageRole::parse(&role)
+                    .ok_or_else(|| row_parse(TABLE, "roles", format!("unknown role {}", role)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(WebhookSubscription {
+            conversation_id: self.conversation_id,
+            subscription_id: self.subscription_id,
+            url: self.url,
+            created_by: self.created_by,
+            created_at: self.created_at,
+            filter: WebhookFilter {
+                event_types,
+                roles,
+                content_types: self.content_types.unwrap_or_default(),
+            },
+            secret: self.secret,
+            delivered_up_to: (self.delivered_at, self.delivered_change_id),
+        })
+    }
+}
+
+// Database row model for conversation_forks table
+#[derive(Debug, Clone, FromRow)]
+pub struct ForkLinkRow {
+    pub source_conversation_id: Uuid,
+    pub fork_conversation_id: Uuid,
+    pub fork_from_message_id: Option<Uuid>,
+    pub title: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ForkLinkRow {
+    /// Index entry linking a fork to its source; `None` if the conversation is not a fork
+    pub fn from_conversation(conversation: &Conversation) -> Option<Self> {
+        let crate::domain::ContentType::Metadata(metadata) = &conversation.root_message.content
+        else {
+            return None;
+        };
+
+        Some(ForkLinkRow {
+            source_conversation_id: metadata.fork_from_conversation_id?,
+            fork_conversation_id: conversation.conversation_id,
+            fork_from_message_id: metadata.fork_from_message_id,
+            title: metadata.title.clone(),
+            created_by: conversation.created_by().to_string(),
+            created_at: conversation.created_at(),
+        })
+    }
+}
+
 // Database row model for branch_by_leaf table
 #[derive(Debug, Clone, FromRow)]
 pub struct BranchByLeafRow {
@@ -156,3 +612,450 @@ pub struct BranchByLeafRow {
     pub conversation_id: Uuid,
     pub branch_id: Uuid,
 }
+
+// Database row model for branch_slugs table
+#[derive(Debug, Clone, FromRow)]
+pub struct BranchSlugRow {
+    pub conversation_id: Uuid,
+    pub slug: String,
+    pub branch_id: Uuid,
+    pub branch_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl BranchSlugRow {
+    pub fn from_slug(slug: &BranchSlug) -> Self {
+        BranchSlugRow {
+            conversation_id: slug.conversation_id,
+            slug: slug.slug.clone(),
+            branch_id: slug.branch_id,
+            branch_name: slug.branch_name.clone(),
+            created_at: slug.created_at,
+        }
+    }
+
+    pub fn to_slug(self) -> BranchSlug {
+        BranchSlug {
+            conversation_id: self.conversation_id,
+            slug: self.slug,
+            branch_id: self.branch_id,
+            branch_name: self.branch_name,
+            created_at: self.created_at,
+        }
+    }
+}
+
+// Database row model for jobs table
+#[derive(Debug, Clone, FromRow)]
+pub struct JobRow {
+    pub job_id: Uuid,
+    pub kind: String,
+    pub user_id: String,
+    pub status: String,
+    /// JSON
+    pub params: String,
+    /// Null when empty
+    pub progress: Option<HashMap<String, i64>>,
+    /// Null until a cancel was requested
+    pub cancel_requested: Option<bool>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+impl JobRow {
+    pub fn from_job(job: &Job) -> Result<Self, DbError> {
+        Ok(JobRow {
+            job_id: job.job_id,
+            kind: job.kind.as_str().to_string(),
+            user_id: job.user_id.clone(),
+            status: job.status.as_str().to_string(),
+            params: serde_json::to_string(&job.params)?,
+            progress: Some(job.progress.clone().into_iter().collect()),
+            cancel_requested: Some(job.cancel_requested),
+            started_at: job.started_at,
+            updated_at: job.updated_at,
+            finished_at: job.finished_at,
+            error: job.error.clone(),
+        })
+    }
+
+    pub fn to_job(self) -> Result<Job, DbError> {
+        const TABLE: &str = "jobs";
+        Ok(Job {
+            job_id: self.job_id,
+            kind: JobKind::parse(&self.kind).ok_or_else(|| {
+                row_parse(TABLE, "kind", format!("unknown job kind {}", self.kind))
+            })?,
+            user_id: self.user_id,
+            status: JobStatus::parse(&self.status).ok_or_else(|| {
+                row_parse(
+                    TABLE,
+                    "status",
+                    format!("unknown job status {}", self.status),
+                )
+            })?,
+            params: serde_json::from_str(&self.params)
+                .map_err(|e| row_parse(TABLE, "params", e))?,
+            progress: self.progress.unwrap_or_default().into_iter().collect(),
+            cancel_requested: self.cancel_requested.unwrap_or(false),
+            started_at: self.started_at,
+            updated_at: self.updated_at,
+            finished_at: self.finished_at,
+            error: self.error,
+        })
+    }
+}
+
+// Database row model for jobs_by_user and running_jobs lookups
+#[derive(Debug, Clone, FromRow)]
+pub struct JobIdRow {
+    pub job_id: Uuid,
+}
+
+// Database row model for conversation_exports table
+#[derive(Debug, Clone, FromRow)]
+pub struct ExportRow {
+    pub conversation_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub export_id: Uuid,
+    pub format: Option<String>,
+    pub requested_by: Option<String>,
+    pub object_key: Option<String>,
+    pub size_bytes: Option<i64>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl ExportRow {
+    pub fn to_export(self) -> ConversationExport {
+        ConversationExport {
+            export_id: self.export_id,
+            conversation_id: self.conversation_id,
+            format: self.format.unwrap_or_default(),
+            requested_by: self.requested_by.unwrap_or_default(),
+            created_at: self.created_at,
+            object_key: self.object_key,
+            size_bytes: self.size_bytes,
+            completed_at: self.completed_at,
+        }
+    }
+}
+
+// Database row model for conversation_changes table
+#[derive(Debug, Clone, FromRow)]
+pub struct ChangeRow {
+    pub conversation_id: Uuid,
+    pub changed_at: DateTime<Utc>,
+    pub change_id: Uuid,
+    pub kind: String,
+    pub entity_id: String,
+    pub payload: Option<String>,
+}
+
+impl ChangeRow {
+    pub fn from_change(change: &Change) -> Result<Self, DbError> {
+        let payload = change
+            .payload
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+
+        Ok(ChangeRow {
+            conversation_id: change.conversation_id,
+            changed_at: change.changed_at,
+            change_id: change.change_id,
+            kind: change.kind.as_str().to_string(),
+            entity_id: change.entity_id.clone(),
+            payload,
+        })
+    }
+
+    pub fn to_change(self) -> Result<Change, DbError> {
+        const TABLE: &str = "conversation_changes";
+        let kind = ChangeKind::parse(&self.kind).ok_or_else(|| {
+            row_parse(TABLE, "kind", format!("unknown change kind {}", self.kind))
+        })?;
+
+        let payload = self
+            .payload
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|e| row_parse(TABLE, "payload", e))?;
+
+        Ok(Change {
+            conversation_id: self.conversation_id,
+            changed_at: self.changed_at,
+            change_id: self.change_id,
+            kind,
+            entity_id: self.entity_id,
+            payload,
+        })
+    }
+}
+
+// Database row model for conversation_events table
+#[derive(Debug, Clone, FromRow)]
+pub struct EventRow {
+    pub conversation_id: Uuid,
+    pub seq: i64,
+    pub event_id: Uuid,
+    pub kind: String,
+    pub occurred_at: DateTime<Utc>,
+    pub payload: String,
+    /// Data key `payload` is encrypted with, like `MessageRow::key_id`
+    pub key_id: Option<Uuid>,
+}
+
+impl EventRow {
+    pub fn from_event(event: &ConversationEvent) -> Result<Self, DbError> {
+        let payload = serde_json::to_string(&event.payload)?;
+
+        Ok(EventRow {
+            conversation_id: event.conversation_id,
+            seq: event.seq,
+            event_id: event.event_id,
+            kind: event.kind.as_str().to_string(),
+            occurred_at: event.occurred_at,
+            payload,
+            key_id: None,
+        })
+    }
+
+    /// Values bound to `INSERT_EVENT`, for batches that mix statements
+    pub fn insert_values(self) -> Vec<Option<CqlValue>> {
+        vec![
+            Some(CqlValue::Uuid(self.conversation_id)),
+            Some(CqlValue::BigInt(self.seq)),
+            Some(CqlValue::Uuid(self.event_id)),
+            Some(CqlValue::Text(self.kind)),
+            Some(CqlValue::Timestamp(self.occurred_at.into())),
+            Some(CqlValue::Text(self.payload)),
+            self.key_id.map(CqlValue::Uuid),
+        ]
+    }
+
+    pub fn to_event(self) -> Result<ConversationEvent, DbError> {
+        const TABLE: &str = "conversation_events";
+        let kind = EventKind::parse(&self.kind)
+            .ok_or_else(|| row_parse(TABLE, "kind", format!("unknown event kind {}", self.kind)))?;
+        if let Some(key_id) = self.key_id {
+            return Err(row_parse(
+                TABLE,
+                "payload",
+                format!(
+                    "payload is encrypted with data key {} and was not decrypted",
+                    key_id
+                ),
+            ));
+        }
+        let payload =
+            serde_json::from_str(&self.payload).map_err(|e| row_parse(TABLE, "payload", e))?;
+
+        Ok(ConversationEvent {
+            conversation_id: self.conversation_id,
+            seq: self.seq,
+            event_id: self.event_id,
+            kind,
+            occurred_at: self.occurred_at,
+            payload,
+        })
+    }
+}
+
+// Row of the CDC log of the conversation_lineage table
+#[derive(Debug, Clone, FromRow)]
+pub struct LineageCdcRow {
+    pub cdc_time: CqlTimeuuid,
+    pub changed_at: DateTime<Utc>,
+    pub batch_seq_no: i32,
+    pub operation: i8,
+    pub conversation_id: Uuid,
+    pub message_id: Option<Uuid>,
+}
+
+impl LineageCdcRow {
+    /// `cdc$operation` values, see the Scylla CDC docs
+    const UPDATE: i8 = 1;
+    const INSERT: i8 = 2;
+    const ROW_DELETE: i8 = 3;
+    const PARTITION_DELETE: i8 = 4;
+
+    /// `None` for pre- and post-images and range deletes, which the lineage
+    /// table never sees
+    pub fn to_outbox_entry(self) -> Option<OutboxEntry> {
+        let kind = match self.operation {
+            Self::UPDATE | Self::INSERT => OutboxKind::MessageUpserted,
+            Self::ROW_DELETE => OutboxKind::MessageDeleted,
+            Self::PARTITION_DELETE => OutboxKind::ConversationDeleted,
+            _ => return None,
+        };
+
+        Some(OutboxEntry {
+            changed_at: self.changed_at,
+            change_id: self.cdc_time.into(),
+            batch_seq_no: self.batch_seq_no,
+            conversation_id: self.conversation_id,
+            message_id: self
+                .message_id
+                .filter(|_| kind != OutboxKind::ConversationDeleted),
+            kind,
+        })
+    }
+}
+
+// Database row model for notifications table
+#[derive(Debug, Clone, FromRow)]
+pub struct NotificationRow {
+    pub user_id: String,
+    pub created_at: DateTime<Utc>,
+    pub notification_id: Uuid,
+    pub kind: String,
+    pub conversation_id: Uuid,
+    pub actor: String,
+    pub entity_id: Option<Uuid>,
+}
+
+impl NotificationRow {
+    pub fn from_notification(notification: &Notification) -> Self {
+        NotificationRow {
+            user_id: notification.user_id.clone(),
+            created_at: notification.created_at,
+            notification_id: notification.notification_id,
+            kind: notification.kind.as_str().to_string(),
+            conversation_id: notification.conversation_id,
+            actor: notification.actor.clone(),
+            entity_id: notification.entity_id,
+        }
+    }
+
+    pub fn to_notification(self) -> Result<Notification, DbError> {
+        let kind = NotificationKind::parse(&self.kind).ok_or_else(|| {
+            row_parse(
+                "notifications",
+                "kind",
+                format!("unknown notification kind {}", self.kind),
+            )
+        })?;
+
+        Ok(Notification {
+            user_id: self.user_id,
+            created_at: self.created_at,
+            notification_id: self.notification_id,
+            kind,
+            conversation_id: self.conversation_id,
+            actor: self.actor,
+            entity_id: self.entity_id,
+        })
+    }
+}
+
+// Database row model for conversation_access_log table
+#[derive(Debug, Clone, FromRow)]
+pub struct AccessLogRow {
+    pub conversation_id: Uuid,
+    pub accessed_at: DateTime<Utc>,
+    pub entry_id: Uuid,
+    pub viewer: Option<String>,
+    pub via_embed: bool,
+    pub branch_id: Option<Uuid>,
+    pub resource: String,
+}
+
+impl AccessLogRow {
+    pub fn from_entry(entry: &AccessLogEntry) -> Self {
+        AccessLogRow {
+            conversation_id: entry.conversation_id,
+            accessed_at: entry.accessed_at,
+            entry_id: entry.entry_id,
+            viewer: entry.viewer.clone(),
+            via_embed: entry.via_embed,
+            branch_id: entry.branch_id,
+            resource: entry.resource.clone(),
+        }
+    }
+
+    pub fn to_entry(self) -> AccessLogEntry {
+        AccessLogEntry {
+            conversation_id: self.conversation_id,
+            accessed_at: self.accessed_at,
+            entry_id: self.entry_id,
+            viewer: self.viewer,
+            via_embed: self.via_embed,
+            branch_id: self.branch_id,
+            resource: self.resource,
+        }
+    }
+}
+
+// Database row model for notification_reads table
+#[derive(Debug, Clone, FromRow)]
+pub struct NotificationReadRow {
+    pub user_id: String,
+    pub read_up_to: DateTime<Utc>,
+}
+
+// Database row model for conversation_activity_daily table
+#[derive(Debug, Clone, FromRow)]
+pub struct ActivityRow {
+    /// UTC date, `YYYY-MM-DD`
+    pub day: String,
+    pub conversation_id: Uuid,
+    pub view_count: Option<Counter>,
+    pub fork_count: Option<Counter>,
+}
+
+impl ActivityRow {
+    pub fn views(&self) -> i64 {
+        self.view_count.map_or(0, |c| c.0)
+    }
+
+    pub fn forks(&self) -> i64 {
+        self.fork_count.map_or(0, |c| c.0)
+    }
+}
+
+// Database row model for trending_conversations table
+#[derive(Debug, Clone, FromRow)]
+pub struct TrendingRow {
+    pub bucket: String,
+    pub rank: i32,
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub score: f64,
+    pub view_count: i64,
+    pub fork_count: i64,
+    pub computed_at: DateTime<Utc>,
+    pub language: Option<String>,
+}
+
+// Database row model for conversation_images and image_references tables
+#[derive(Debug, Clone, FromRow)]
+pub struct ImageRefRow {
+    pub conversation_id: Uuid,
+    pub storage_key: String,
+}
+
+// Database row model for pending_image_deletions table
+#[derive(Debug, Clone, FromRow)]
+pub struct PendingImageDeletionRow {
+    pub storage_key: String,
+    pub queued_at: DateTime<Utc>,
+}
+
+// Database row model for analytics_daily table
+#[derive(Debug, Clone, FromRow)]
+pub struct AnalyticsRollupRow {
+    /// `global`, `user:<user_id>` or `conversation:<conversation_id>`
+    pub scope: String,
+    /// UTC date, `YYYY-MM-DD`
+    pub day: String,
+    pub message_count: i64,
+    pub fork_count: i64,
+    pub messages_by_role: Option<HashMap<String, i64>>,
+    pub messages_by_model: Option<HashMap<String, i64>>,
+    pub input_tokens_by_model: Option<HashMap<String, i64>>,
+    pub output_tokens_by_model: Option<HashMap<String, i64>>,
+    pub computed_at: DateTime<Utc>,
+}
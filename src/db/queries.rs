@@ -5,14 +5,14 @@ pub const INSERT_MESSAGE: &str = r#"
     INSERT INTO conversation_lineage (
         conversation_id, message_id, parent_message_id, role,
         content_type, content_data, content_metadata, lineage,
-        created_at, created_by
-    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        created_at, created_by, content_hash, key_id
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
 "#;
 
 pub const SELECT_MESSAGE: &str = r#"
     SELECT conversation_id, message_id, parent_message_id, role,
            content_type, content_data, content_metadata, lineage,
-           created_at, created_by
+           created_at, created_by, content_hash, key_id
     FROM conversation_lineage
     WHERE conversation_id = ? AND message_id = ?
 "#;
@@ -20,16 +20,50 @@ pub const SELECT_MESSAGE: &str = r#"
 pub const SELECT_MESSAGE_CHILDREN: &str = r#"
     SELECT conversation_id, message_id, parent_message_id, role,
            content_type, content_data, content_metadata, lineage,
-           created_at, created_by
+           created_at, created_by, content_hash, key_id
     FROM conversation_lineage
     WHERE conversation_id = ? AND parent_message_id = ?
     ALLOW FILTERING
 "#;
 
+pub const COUNT_MESSAGES: &str = r#"
+    SELECT COUNT(*) FROM conversation_lineage WHERE conversation_id = ?
+"#;
+
+// conversation_message_counts and message_reply_counts queries
+pub const SELECT_MESSAGE_COUNT: &str = r#"
+    SELECT message_count FROM conversation_message_counts WHERE conversation_id = ?
+"#;
+
+pub const SELECT_REPLY_COUNT: &str = r#"
+    SELECT reply_count FROM message_reply_counts
+    WHERE conversation_id = ? AND message_id = ?
+"#;
+
+pub const INCREMENT_MESSAGE_COUNT: &str = r#"
+    UPDATE conversation_message_counts
+    SET message_count = message_count + ?
+    WHERE conversation_id = ?
+"#;
+
+pub const INCREMENT_REPLY_COUNT: &str = r#"
+    UPDATE message_reply_counts
+    SET reply_count = reply_count + ?
+    WHERE conversation_id = ? AND message_id = ?
+"#;
+
+pub const DELETE_MESSAGE_COUNT: &str = r#"
+    DELETE FROM conversation_message_counts WHERE conversation_id = ?
+"#;
+
+pub const DELETE_REPLY_COUNTS: &str = r#"
+    DELETE FROM message_reply_counts WHERE conversation_id = ?
+"#;
+
 pub const SELECT_MESSAGES_BY_IDS: &str = r#"
     SELECT conversation_id, message_id, parent_message_id, role,
            content_type, content_data, content_metadata, lineage,
-           created_at, created_by
+           created_at, created_by, content_hash, key_id
     FROM conversation_lineage
     WHERE conversation_id = ? AND message_id IN ?
 "#;
@@ -37,33 +71,62 @@ pub const SELECT_MESSAGES_BY_IDS: &str = r#"
 pub const SELECT_ALL_MESSAGES: &str = r#"
     SELECT conversation_id, message_id, parent_message_id, role,
            content_type, content_data, content_metadata, lineage,
-           created_at, created_by
+           created_at, created_by, content_hash, key_id
     FROM conversation_lineage
     WHERE conversation_id = ?
 "#;
 
+/// Scans every message of every conversation; only for one-off backfills
+pub const SELECT_EVERY_MESSAGE: &str = r#"
+    SELECT conversation_id, message_id, parent_message_id, role,
+           content_type, content_data, content_metadata, lineage,
+           created_at, created_by, content_hash, key_id
+    FROM conversation_lineage
+"#;
+
 pub const DELETE_CONVERSATION: &str = r#"
     DELETE FROM conversation_lineage WHERE conversation_id = ?
 "#;
 
+// conversation_checkpoints queries
+pub const INSERT_CHECKPOINT: &str = r#"
+    INSERT INTO conversation_checkpoints (
+        conversation_id, message_id, parent_message_id, role,
+        content_type, content_data, content_metadata, lineage,
+        created_at, created_by, content_hash, key_id
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_CHECKPOINTS_BY_CONVERSATION: &str = r#"
+    SELECT conversation_id, message_id, parent_message_id, role,
+           content_type, content_data, content_metadata, lineage,
+           created_at, created_by, content_hash, key_id
+    FROM conversation_checkpoints
+    WHERE conversation_id = ?
+"#;
+
+pub const DELETE_CHECKPOINTS: &str = r#"
+    DELETE FROM conversation_checkpoints WHERE conversation_id = ?
+"#;
+
 // conversation_branches queries
 pub const INSERT_BRANCH: &str = r#"
     INSERT INTO conversation_branches (
         conversation_id, branch_id, branch_name, leaf_message_id,
-        created_at, last_updated, created_by, is_active
-    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        created_at, last_updated, created_by, is_active, slug
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
 "#;
 
 pub const SELECT_BRANCH: &str = r#"
     SELECT conversation_id, branch_id, branch_name, leaf_message_id,
-           created_at, last_updated, created_by, is_active
+           created_at, last_updated, created_by, is_active, slug
     FROM conversation_branches
     WHERE conversation_id = ? AND branch_id = ?
 "#;
 
 pub const SELECT_BRANCHES_BY_CONVERSATION: &str = r#"
     SELECT conversation_id, branch_id, branch_name, leaf_message_id,
-           created_at, last_updated, created_by, is_active
+           created_at, last_updated, created_by, is_active, slug
     FROM conversation_branches
     WHERE conversation_id = ?
 "#;
@@ -76,7 +139,7 @@ pub const UPDATE_BRANCH_LEAF: &str = r#"
 
 pub const UPDATE_BRANCH_NAME: &str = r#"
     UPDATE conversation_branches
-    SET branch_name = ?, last_updated = ?
+    SET branch_name = ?, slug = ?, last_updated = ?
     WHERE conversation_id = ? AND branch_id = ?
 "#;
 
@@ -109,6 +172,132 @@ pub const DELETE_SHARE: &str = r#"
     WHERE conversation_id = ? AND shared_with = ?
 "#;
 
+pub const SELECT_ALL_SHARES: &str = r#"
+    SELECT conversation_id, shared_with, permission, shared_at, shared_by
+    FROM conversation_shares
+"#;
+
+pub const INSERT_SHARE_BY_USER: &str = r#"
+    INSERT INTO shares_by_user (
+        shared_with, conversation_id, permission, shared_at, shared_by
+    ) VALUES (?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_SHARES_BY_USER: &str = r#"
+    SELECT conversation_id, shared_with, permission, shared_at, shared_by
+    FROM shares_by_user
+    WHERE shared_with = ?
+"#;
+
+pub const DELETE_SHARE_BY_USER: &str = r#"
+    DELETE FROM shares_by_user
+    WHERE shared_with = ? AND conversation_id = ?
+"#;
+
+// share_invites queries
+pub const INSERT_INVITE: &str = r#"
+    INSERT INTO share_invites (
+        email, token, conversation_id, permission, invited_by, created_at, expires_at
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+    USING TTL ?
+"#;
+
+pub const SELECT_INVITE: &str = r#"
+    SELECT email, token, conversation_id, permission, invited_by, created_at, expires_at
+    FROM share_invites
+    WHERE email = ? AND token = ?
+"#;
+
+pub const DELETE_INVITE: &str = r#"
+    DELETE FROM share_invites
+    WHERE email = ? AND token = ?
+"#;
+
+// user_preferences queries
+pub const UPSERT_USER_PREFERENCES: &str = r#"
+    INSERT INTO user_preferences (
+        user_id, default_shares, allow_public_forks, default_page_size, branch_naming,
+        muted_notifications, updated_at
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_USER_PREFERENCES: &str = r#"
+    SELECT user_id, default_shares, allow_public_forks, default_page_size, branch_naming,
+        muted_notifications, updated_at
+    FROM user_preferences
+    WHERE user_id = ?
+"#;
+
+// jobs queries
+// cancel_requested is left out, so saving progress never clears a cancel request
+pub const UPSERT_JOB: &str = r#"
+    INSERT INTO jobs (
+        job_id, kind, user_id, status, params, progress,
+        started_at, updated_at, finished_at, error
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_JOB: &str = r#"
+    SELECT job_id, kind, user_id, status, params, progress, cancel_requested,
+           started_at, updated_at, finished_at, error
+    FROM jobs
+    WHERE job_id = ?
+"#;
+
+pub const REQUEST_JOB_CANCEL: &str = r#"
+    UPDATE jobs SET cancel_requested = true WHERE job_id = ?
+"#;
+
+pub const INSERT_JOB_BY_USER: &str = r#"
+    INSERT INTO jobs_by_user (user_id, started_at, job_id) VALUES (?, ?, ?)
+"#;
+
+pub const SELECT_JOBS_BY_USER: &str = r#"
+    SELECT job_id FROM jobs_by_user WHERE user_id = ? LIMIT ?
+"#;
+
+pub const INSERT_RUNNING_JOB: &str = r#"
+    INSERT INTO running_jobs (bucket, job_id) VALUES (0, ?)
+"#;
+
+pub const DELETE_RUNNING_JOB: &str = r#"
+    DELETE FROM running_jobs WHERE bucket = 0 AND job_id = ?
+"#;
+
+pub const SELECT_RUNNING_JOBS: &str = r#"
+    SELECT job_id FROM running_jobs WHERE bucket = 0
+"#;
+
+// conversation_exports queries
+// The result columns are left out, so recording an export after its job
+// finished doesn't clear them
+pub const INSERT_CONVERSATION_EXPORT: &str = r#"
+    INSERT INTO conversation_exports (
+        conversation_id, created_at, export_id, format, requested_by
+    ) VALUES (?, ?, ?, ?, ?)
+"#;
+
+pub const COMPLETE_CONVERSATION_EXPORT: &str = r#"
+    UPDATE conversation_exports
+    SET object_key = ?, size_bytes = ?, completed_at = ?
+    WHERE conversation_id = ? AND created_at = ? AND export_id = ?
+"#;
+
+pub const SELECT_CONVERSATION_EXPORT: &str = r#"
+    SELECT conversation_id, created_at, export_id, format, requested_by,
+           object_key, size_bytes, completed_at
+    FROM conversation_exports
+    WHERE conversation_id = ? AND created_at = ? AND export_id = ?
+"#;
+
+pub const SELECT_CONVERSATION_EXPORTS: &str = r#"
+    SELECT conversation_id, created_at, export_id, format, requested_by,
+           object_key, size_bytes, completed_at
+    FROM conversation_exports
+    WHERE conversation_id = ?
+    LIMIT ?
+"#;
+
 // user_conversations queries
 pub const INSERT_USER_CONVERSATION: &str = r#"
     INSERT INTO user_conversations (
@@ -129,6 +318,99 @@ pub const UPDATE_USER_CONVERSATION_ACTIVITY: &str = r#"
     ) VALUES (?, ?, ?, ?)
 "#;
 
+// conversation_locks queries
+pub const INSERT_CONVERSATION_LOCK: &str = r#"
+    INSERT INTO conversation_locks (conversation_id, locked_by, locked_at, reason)
+    VALUES (?, ?, ?, ?)
+"#;
+
+pub const SELECT_CONVERSATION_LOCK: &str = r#"
+    SELECT conversation_id, locked_by, locked_at, reason
+    FROM conversation_locks
+    WHERE conversation_id = ?
+"#;
+
+pub const DELETE_CONVERSATION_LOCK: &str = r#"
+    DELETE FROM conversation_locks WHERE conversation_id = ?
+"#;
+
+// legal_holds queries
+pub const INSERT_LEGAL_HOLD: &str = r#"
+    INSERT INTO legal_holds (bucket, conversation_id, placed_by, placed_at, reason)
+    VALUES (0, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_LEGAL_HOLD: &str = r#"
+    SELECT conversation_id, placed_by, placed_at, reason
+    FROM legal_holds
+    WHERE bucket = 0 AND conversation_id = ?
+"#;
+
+pub const SELECT_LEGAL_HOLDS: &str = r#"
+    SELECT conversation_id, placed_by, placed_at, reason
+    FROM legal_holds
+    WHERE bucket = 0
+"#;
+
+pub const DELETE_LEGAL_HOLD: &str = r#"
+    DELETE FROM legal_holds WHERE bucket = 0 AND conversation_id = ?
+"#;
+
+// fork_progress queries
+pub const UPSERT_FORK_PROGRESS: &str = r#"
+    INSERT INTO fork_progress (bucket, conversation_id, source_conversation_id, created_by,
+                               total_chunks, completed_chunks, started_at, updated_at)
+    VALUES (0, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_FORK_PROGRESS: &str = r#"
+    SELECT conversation_id, source_conversation_id, created_by, total_chunks,
+           completed_chunks, started_at, updated_at
+    FROM fork_progress
+    WHERE bucket = 0
+"#;
+
+pub const DELETE_FORK_PROGRESS: &str = r#"
+    DELETE FROM fork_progress WHERE bucket = 0 AND conversation_id = ?
+"#;
+
+// conversation_titles_by_user queries
+pub const UPSERT_CONVERSATION_TITLE: &str = r#"
+    INSERT INTO conversation_titles_by_user (user_id, conversation_id, title, updated_at, language)
+    VALUES (?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_CONVERSATION_TITLES: &str = r#"
+    SELECT user_id, conversation_id, title, updated_at, language
+    FROM conversation_titles_by_user
+    WHERE user_id = ?
+"#;
+
+pub const DELETE_CONVERSATION_TITLE: &str = r#"
+    DELETE FROM conversation_titles_by_user
+    WHERE user_id = ? AND conversation_id = ?
+"#;
+
+// conversation_forks queries
+pub const UPSERT_FORK_LINK: &str = r#"
+    INSERT INTO conversation_forks (
+        source_conversation_id, fork_conversation_id, fork_from_message_id,
+        title, created_by, created_at
+    ) VALUES (?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_FORK_LINKS: &str = r#"
+    SELECT source_conversation_id, fork_conversation_id, fork_from_message_id,
+        title, created_by, created_at
+    FROM conversation_forks
+    WHERE source_conversation_id = ?
+"#;
+
+pub const DELETE_FORK_LINK: &str = r#"
+    DELETE FROM conversation_forks
+    WHERE source_conversation_id = ? AND fork_conversation_id = ?
+"#;
+
 // branch_by_leaf queries
 pub const INSERT_BRANCH_BY_LEAF: &str = r#"
     INSERT INTO branch_by_leaf (leaf_message_id, conversation_id, branch_id)
@@ -144,3 +426,341 @@ pub const SELECT_BRANCH_BY_LEAF: &str = r#"
 pub const DELETE_BRANCH_BY_LEAF: &str = r#"
     DELETE FROM branch_by_leaf WHERE leaf_message_id = ?
 "#;
+
+// branch_slugs queries
+pub const INSERT_BRANCH_SLUG: &str = r#"
+    INSERT INTO branch_slugs (conversation_id, slug, branch_id, branch_name, created_at)
+    VALUES (?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_BRANCH_SLUG: &str = r#"
+    SELECT conversation_id, slug, branch_id, branch_name, created_at
+    FROM branch_slugs
+    WHERE conversation_id = ? AND slug = ?
+"#;
+
+pub const SELECT_BRANCH_SLUGS: &str = r#"
+    SELECT conversation_id, slug, branch_id, branch_name, created_at
+    FROM branch_slugs
+    WHERE conversation_id = ?
+"#;
+
+pub const DELETE_BRANCH_SLUG: &str = r#"
+    DELETE FROM branch_slugs WHERE conversation_id = ? AND slug = ?
+"#;
+
+// conversation_changes queries
+pub const INSERT_CHANGE: &str = r#"
+    INSERT INTO conversation_changes (
+        conversation_id, changed_at, change_id, kind, entity_id, payload
+    ) VALUES (?, ?, ?, ?, ?, ?)
+"#;
+
+/// Rewrites a change without its expiry
+pub const INSERT_RETAINED_CHANGE: &str = r#"
+    INSERT INTO conversation_changes (
+        conversation_id, changed_at, change_id, kind, entity_id, payload
+    ) VALUES (?, ?, ?, ?, ?, ?)
+    USING TTL 0
+"#;
+
+pub const SELECT_CHANGES_SINCE: &str = r#"
+    SELECT conversation_id, changed_at, change_id, kind, entity_id, payload
+    FROM conversation_changes
+    WHERE conversation_id = ? AND (changed_at, change_id) > (?, ?)
+    LIMIT ?
+"#;
+
+pub const SELECT_ALL_CHANGES: &str = r#"
+    SELECT conversation_id, changed_at, change_id, kind, entity_id, payload
+    FROM conversation_changes
+    WHERE conversation_id = ?
+"#;
+
+pub const DELETE_CHANGES: &str = r#"
+    DELETE FROM conversation_changes WHERE conversation_id = ?
+"#;
+
+// conversation_events queries
+pub const INSERT_EVENT: &str = r#"
+    INSERT INTO conversation_events (
+        conversation_id, seq, event_id, kind, occurred_at, payload, key_id
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_EVENTS_FROM: &str = r#"
+    SELECT conversation_id, seq, event_id, kind, occurred_at, payload, key_id
+    FROM conversation_events
+    WHERE conversation_id = ? AND seq >= ?
+    LIMIT ?
+"#;
+
+pub const DELETE_EVENTS: &str = r#"
+    DELETE FROM conversation_events WHERE conversation_id = ?
+"#;
+
+pub const SELECT_LAST_EVENT_SEQ: &str = r#"
+    SELECT seq FROM conversation_events
+    WHERE conversation_id = ?
+    ORDER BY seq DESC
+    LIMIT 1
+"#;
+
+// conversation_event_seqs queries
+pub const SELECT_EVENT_SEQ: &str = r#"
+    SELECT last_seq FROM conversation_event_seqs WHERE conversation_id = ?
+"#;
+
+pub const INSERT_EVENT_SEQ: &str = r#"
+    INSERT INTO conversation_event_seqs (conversation_id, last_seq)
+    VALUES (?, ?)
+    IF NOT EXISTS
+"#;
+
+pub const UPDATE_EVENT_SEQ: &str = r#"
+    UPDATE conversation_event_seqs
+    SET last_seq = ?
+    WHERE conversation_id = ?
+    IF last_seq = ?
+"#;
+
+pub const DELETE_EVENT_SEQ: &str = r#"
+    DELETE FROM conversation_event_seqs WHERE conversation_id = ?
+    IF EXISTS
+"#;
+
+// conversation_keys queries
+pub const INSERT_CONVERSATION_KEY: &str = r#"
+    INSERT INTO conversation_keys (
+        conversation_id, key_id, master_key_id, wrapped_key, created_at
+    ) VALUES (?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_CURRENT_CONVERSATION_KEY: &str = r#"
+    SELECT conversation_id, key_id, master_key_id, wrapped_key, created_at
+    FROM conversation_keys
+    WHERE conversation_id = ?
+    LIMIT 1
+"#;
+
+pub const SELECT_CONVERSATION_KEY: &str = r#"
+    SELECT conversation_id, key_id, master_key_id, wrapped_key, created_at
+    FROM conversation_keys
+    WHERE conversation_id = ? AND key_id = ?
+"#;
+
+/// Every data key of every conversation; only for re-wrapping them
+pub const SELECT_EVERY_CONVERSATION_KEY: &str = r#"
+    SELECT conversation_id, key_id, master_key_id, wrapped_key, created_at
+    FROM conversation_keys
+"#;
+
+pub const UPDATE_CONVERSATION_KEY_WRAPPING: &str = r#"
+    UPDATE conversation_keys
+    SET master_key_id = ?, wrapped_key = ?
+    WHERE conversation_id = ? AND key_id = ?
+"#;
+
+pub const DELETE_CONVERSATION_KEYS: &str = r#"
+    DELETE FROM conversation_keys WHERE conversation_id = ?
+"#;
+
+// CDC and outbox queries
+pub const ENABLE_LINEAGE_CDC: &str = r#"
+    ALTER TABLE conversation_lineage WITH cdc = {'enabled': true}
+"#;
+
+pub const SELECT_CDC_GENERATIONS: &str = r#"
+    SELECT time FROM system_distributed.cdc_generation_timestamps WHERE key = 'timestamps'
+"#;
+
+pub const SELECT_CDC_STREAMS: &str = r#"
+    SELECT streams FROM system_distributed.cdc_streams_descriptions_v2 WHERE time = ?
+"#;
+
+pub const SELECT_LINEAGE_CDC: &str = r#"
+    SELECT "cdc$time", toTimestamp("cdc$time"), "cdc$batch_seq_no", "cdc$operation",
+        conversation_id, message_id
+    FROM conversation_lineage_scylla_cdc_log
+    WHERE "cdc$stream_id" IN ? AND "cdc$time" > maxTimeuuid(?) AND "cdc$time" <= maxTimeuuid(?)
+"#;
+
+pub const INSERT_OUTBOX: &str = r#"
+    INSERT INTO lineage_outbox (
+        day, change_id, batch_seq_no, changed_at, conversation_id, message_id, kind
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_CDC_CHECKPOINT: &str = r#"
+    SELECT processed_up_to FROM cdc_checkpoints WHERE consumer = ?
+"#;
+
+pub const UPSERT_CDC_CHECKPOINT: &str = r#"
+    INSERT INTO cdc_checkpoints (consumer, processed_up_to) VALUES (?, ?)
+"#;
+
+// notifications queries
+pub const INSERT_NOTIFICATION: &str = r#"
+    INSERT INTO notifications (
+        user_id, created_at, notification_id, kind, conversation_id, actor, entity_id
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_NOTIFICATIONS: &str = r#"
+    SELECT user_id, created_at, notification_id, kind, conversation_id, actor, entity_id
+    FROM notifications
+    WHERE user_id = ?
+    LIMIT ?
+"#;
+
+pub const UPSERT_NOTIFICATIONS_READ: &str = r#"
+    INSERT INTO notification_reads (user_id, read_up_to) VALUES (?, ?)
+"#;
+
+pub const SELECT_NOTIFICATIONS_READ: &str = r#"
+    SELECT user_id, read_up_to FROM notification_reads WHERE user_id = ?
+"#;
+
+// conversation_access_log queries
+pub const INSERT_ACCESS_LOG_ENTRY: &str = r#"
+    INSERT INTO conversation_access_log (
+        conversation_id, accessed_at, entry_id, viewer, via_embed, branch_id, resource
+    ) VALUES (?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_ACCESS_LOG: &str = r#"
+    SELECT conversation_id, accessed_at, entry_id, viewer, via_embed, branch_id, resource
+    FROM conversation_access_log
+    WHERE conversation_id = ? AND accessed_at < ?
+    LIMIT ?
+"#;
+
+// conversation_activity_daily queries
+pub const INCREMENT_ACTIVITY: &str = r#"
+    UPDATE conversation_activity_daily
+    SET view_count = view_count + ?, fork_count = fork_count + ?
+    WHERE day = ? AND conversation_id = ?
+"#;
+
+pub const SELECT_ACTIVITY_BY_DAY: &str = r#"
+    SELECT day, conversation_id, view_count, fork_count
+    FROM conversation_activity_daily
+    WHERE day = ?
+"#;
+
+// trending_conversations queries
+pub const INSERT_TRENDING: &str = r#"
+    INSERT INTO trending_conversations (
+        bucket, rank, conversation_id, title, score, view_count, fork_count, computed_at,
+        language
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const DELETE_TRENDING_FROM_RANK: &str = r#"
+    DELETE FROM trending_conversations WHERE bucket = ? AND rank >= ?
+"#;
+
+pub const SELECT_TRENDING: &str = r#"
+    SELECT bucket, rank, conversation_id, title, score, view_count, fork_count, computed_at,
+        language
+    FROM trending_conversations
+    WHERE bucket = ?
+    LIMIT ?
+"#;
+
+// analytics_daily queries
+pub const INSERT_ANALYTICS_ROLLUP: &str = r#"
+    INSERT INTO analytics_daily (
+        scope, day, message_count, fork_count, messages_by_role, messages_by_model,
+        input_tokens_by_model, output_tokens_by_model, computed_at
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_ANALYTICS_ROLLUPS: &str = r#"
+    SELECT scope, day, message_count, fork_count, messages_by_role, messages_by_model,
+        input_tokens_by_model, output_tokens_by_model, computed_at
+    FROM analytics_daily
+    WHERE scope = ? AND day >= ? AND day <= ?
+"#;
+
+// conversation_images / image_references queries
+pub const INSERT_CONVERSATION_IMAGE: &str = r#"
+    INSERT INTO conversation_images (conversation_id, storage_key) VALUES (?, ?)
+"#;
+
+pub const INSERT_IMAGE_REFERENCE: &str = r#"
+    INSERT INTO image_references (storage_key, conversation_id) VALUES (?, ?)
+"#;
+
+pub const SELECT_CONVERSATION_IMAGES: &str = r#"
+    SELECT conversation_id, storage_key
+    FROM conversation_images
+    WHERE conversation_id = ?
+"#;
+
+pub const DELETE_CONVERSATION_IMAGES: &str = r#"
+    DELETE FROM conversation_images WHERE conversation_id = ?
+"#;
+
+pub const DELETE_IMAGE_REFERENCE: &str = r#"
+    DELETE FROM image_references WHERE storage_key = ? AND conversation_id = ?
+"#;
+
+pub const SELECT_IMAGE_REFERENCE: &str = r#"
+    SELECT conversation_id, storage_key
+    FROM image_references
+    WHERE storage_key = ?
+    LIMIT 1
+"#;
+
+// pending_image_deletions queries
+pub const INSERT_PENDING_IMAGE_DELETION: &str = r#"
+    INSERT INTO pending_image_deletions (storage_key, queued_at) VALUES (?, ?)
+"#;
+
+pub const SELECT_PENDING_IMAGE_DELETIONS: &str = r#"
+    SELECT storage_key, queued_at
+    FROM pending_image_deletions
+    LIMIT ?
+"#;
+
+pub const DELETE_PENDING_IMAGE_DELETION: &str = r#"
+    DELETE FROM pending_image_deletions WHERE storage_key = ?
+"#;
+
+// webhook_subscriptions queries
+pub const INSERT_WEBHOOK_SUBSCRIPTION: &str = r#"
+    INSERT INTO webhook_subscriptions (
+        conversation_id, subscription_id, url, created_by, created_at,
+        event_types, roles, content_types, secret, delivered_at, delivered_change_id
+    ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+"#;
+
+pub const SELECT_WEBHOOK_SUBSCRIPTIONS: &str = r#"
+    SELECT conversation_id, subscription_id, url, created_by, created_at,
+           event_types, roles, content_types, secret, delivered_at, delivered_change_id
+    FROM webhook_subscriptions
+    WHERE conversation_id = ?
+"#;
+
+pub const SELECT_ALL_WEBHOOK_SUBSCRIPTIONS: &str = r#"
+    SELECT conversation_id, subscription_id, url, created_by, created_at,
+           event_types, roles, content_types, secret, delivered_at, delivered_change_id
+    FROM webhook_subscriptions
+"#;
+
+pub const UPDATE_WEBHOOK_DELIVERED: &str = r#"
+    UPDATE webhook_subscriptions
+    SET delivered_at = ?, delivered_change_id = ?
+    WHERE conversation_id = ? AND subscription_id = ?
+    IF EXISTS
+"#;
+
+pub const DELETE_WEBHOOK_SUBSCRIPTION: &str = r#"
+    DELETE FROM webhook_subscriptions WHERE conversation_id = ? AND subscription_id = ?
+"#;
+
+// health queries
+pub const PING: &str = r#"
+    SELECT now() FROM system.local
+"#;
@@ -0,0 +1,97 @@
+use scylla::retry_policy::{
+    DefaultRetryPolicy, QueryInfo, RetryDecision, RetryPolicy, RetrySession,
+};
+
+/// The driver's default retry decisions, capped at a number of retries per
+/// statement
+#[derive(Debug, Clone)]
+pub struct BoundedRetryPolicy {
+    max_retries: u32,
+}
+
+impl BoundedRetryPolicy {
+    pub fn new(max_retries: u32) -> Self {
+        Self { max_retries }
+    }
+}
+
+impl RetryPolicy for BoundedRetryPolicy {
+    fn new_session(&self) -> Box<dyn RetrySession> {
+        Box::new(BoundedRetrySession {
+            inner: DefaultRetryPolicy::new().new_session(),
+            max_retries: self.max_retries,
+            retries: 0,
+        })
+    }
+
+    fn clone_boxed(&self) -> Box<dyn RetryPolicy> {
+        Box::new(self.clone())
+    }
+}
+
+struct BoundedRetrySession {
+    inner: Box<dyn RetrySession>,
+    max_retries: u32,
+    retries: u32,
+}
+
+impl RetrySession for BoundedRetrySession {
+    fn decide_should_retry(&mut self, query_info: QueryInfo) -> RetryDecision {
+        match self.inner.decide_should_retry(query_info) {
+            RetryDecision::RetrySameNode(_) | RetryDecision::RetryNextNode(_)
+                if self.retries >= self.max_retries =>
+            {
+                RetryDecision::DontRetry
+            }
+            decision @ (RetryDecision::RetrySameNode(_) | RetryDecision::RetryNextNode(_)) => {
+                self.retries += 1;
+                decision
+            }
+            decision => decision,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.retries = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scylla::statement::Consistency;
+    use scylla::transport::errors::QueryError;
+    use std::io;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_retries_are_capped() {
+        let error = QueryError::IoError(Arc::new(io::Error::other("connection reset")));
+        let info = || QueryInfo {
+            error: &error,
+            is_idempotent: true,
+            consistency: Consistency::LocalQuorum,
+        };
+
+        let mut session = BoundedRetryPolicy::new(2).new_session();
+        assert_eq!(
+            session.decide_should_retry(info()),
+            RetryDecision::RetryNextNode(None)
+        );
+        assert_eq!(
+            session.decide_should_retry(info()),
+            RetryDecision::RetryNextNode(None)
+        );
+        assert_eq!(
+            session.decide_should_retry(info()),
+            RetryDecision::DontRetry
+        );
+
+        session.reset();
+        assert_eq!(
+            session.decide_should_retry(info()),
+            RetryDecision::RetryNextNode(None)
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use scylla::transport::errors::{DbError as ScyllaDbError, NewSessionError, QueryError};
+use std::future::Future;
+use tokio::time::{Duration, Instant, sleep};
+
+use crate::config::ScyllaConfig;
+
+use super::DbError;
+
+/// Whether an error may go away on its own while the cluster starts up, as
+/// opposed to a misconfiguration or a broken migration
+pub fn is_transient(err: &DbError) -> bool {
+    match err {
+        DbError::ConnectionError(err) => match err {
+            NewSessionError::EmptyKnownNodesList | NewSessionError::BadQuery(_) => false,
+            NewSessionError::DbError(err, _) => is_transient_db_error(err),
+            _ => true,
+        },
+        DbError::QueryError(err) => is_transient_query_error(err),
+        _ => false,
+    }
+}
+
+pub fn is_transient_query_error(err: &QueryError) -> bool {
+    match err {
+        QueryError::IoError(_) | QueryError::TimeoutError | QueryError::RequestTimeout(_) => true,
+        QueryError::DbError(err, _) => is_transient_db_error(err),
+        _ => false,
+    }
+}
+
+fn is_transient_db_error(err: &ScyllaDbError) -> bool {
+    matches!(
+        err,
+        ScyllaDbError::Unavailable { .. }
+            | ScyllaDbError::Overloaded
+            | ScyllaDbError::IsBootstrapping
+            | ScyllaDbError::ReadTimeout { .. }
+            | ScyllaDbError::WriteTimeout { .. }
+            | ScyllaDbError::ServerError
+    )
+}
+
+/// Run `attempt` until it succeeds, fails with an error that isn't
+/// transient, or `startup_max_wait_secs` have passed. Waits between attempts
+/// start at `startup_backoff_ms` and double up to `startup_max_backoff_ms`.
+pub async fn retry_startup<T, F, Fut>(
+    config: &ScyllaConfig,
+    what: &str,
+    mut attempt: F,
+) -> Result<T, DbError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, DbError>>,
+{
+    let deadline = Instant::now() + Duration::from_secs(config.startup_max_wait_secs);
+    let max_backoff = Duration::from_millis(config.startup_max_backoff_ms);
+    let mut backoff = Duration::from_millis(config.startup_backoff_ms).min(max_backoff);
+
+    for attempts in 1.. {
+        let err = match attempt().await {
+            Ok(value) => {
+                if attempts > 1 {
+                    tracing::info!("{} succeeded after {} attempts", what, attempts);
+                }
+                return Ok(value);
+            }
+            Err(err) => err,
+        };
+
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if !is_transient(&err) || remaining.is_zero() {
+            return Err(err);
+        }
+
+        let wait = backoff.min(remaining);
+        tracing::warn!(
+            "{} failed (attempt {}): {}; retrying in {} ms, giving up in {} s",
+            what,
+            attempts,
+            err,
+            wait.as_millis(),
+            remaining.as_secs()
+        );
+        sleep(wait).await;
+        backoff = (backoff * 2).min(max_backoff);
+    }
+
+    unreachable!("the attempts range is unbounded")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use std::cell::Cell;
+    use std::io;
+    use std::sync::Arc;
+
+    fn config(max_wait_secs: u64) -> ScyllaConfig {
+        ScyllaConfig {
+            startup_max_wait_secs: max_wait_secs,
+            startup_backoff_ms: 1,
+            startup_max_backoff_ms: 2,
+            ..Settings::default().scylla
+        }
+    }
+
+    fn refused() -> DbError {
+        DbError::ConnectionError(NewSessionError::IoError(Arc::new(io::Error::from(
+            io::ErrorKind::ConnectionRefused,
+        ))))
+    }
+
+    #[tokio::test]
+    async fn test_transient_errors_are_retried() {
+        let calls = Cell::new(0);
+        let result = retry_startup(&config(5), "connect", || {
+            calls.set(calls.get() + 1);
+            let calls = calls.get();
+            async move { if calls < 3 { Err(refused()) } else { Ok(calls) } }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+
+        let calls = Cell::new(0);
+        let result: Result<(), _> = retry_startup(&config(5), "migrate", || {
+            calls.set(calls.get() + 1);
+            async { Err(DbError::MigrationError("syntax error".to_string())) }
+        })
+        .await;
+        assert!(matches!(result, Err(DbError::MigrationError(_))));
+        assert_eq!(calls.get(), 1);
+
+        let result: Result<(), _> =
+            retry_startup(&config(0), "connect", || async { Err(refused()) }).await;
+        assert!(matches!(result, Err(DbError::ConnectionError(_))));
+    }
+}
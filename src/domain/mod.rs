@@ -1,14 +1,4 @@
-pub mod branch;
-pub mod content;
-pub mod conversation;
-pub mod message;
-pub mod permissions;
+//! The domain model lives in `aigc-history-types`, shared with clients;
+//! it is re-exported here under its usual path
 
-pub use branch::Branch;
-pub use content::{
-    ContentMetadata, ContentType, ImageBatchContent, ImageContent, MetadataContent, TextContent,
-    ToolCallContent, ToolResultContent,
-};
-pub use conversation::Conversation;
-pub use message::{Message, MessageRole};
-pub use permissions::{Permission, Share};
+pub use aigc_history_types::domain::*;
@@ -3,8 +3,12 @@ pub mod config;
 pub mod db;
 pub mod domain;
 pub mod middleware;
+pub mod object_store;
 pub mod repositories;
+pub mod scheduler;
 pub mod services;
+#[cfg(any(test, feature = "test_support"))]
+pub mod test_support;
 pub mod utils;
 
 pub use config::Settings;
@@ -1,92 +1,385 @@
 use aigc_history::{
-    api::{AppState, create_router},
-    config::Settings,
-    db::DbClient,
-    repositories::{BranchRepository, LineageRepository, ShareRepository},
-    services::{BranchService, ConversationService, ForkService, ShareService},
+    api::{AppState, create_router, tls},
+    config::{LogFormat, Settings, StorageBackend},
+    db::{DbClient, migration},
+    object_store,
+    repositories::{CdcRepository, ContentBlobs, ContentCipher, Storage},
+    services::{CdcConsumer, DemoSeeder},
+    utils::json_log::{JsonFields, JsonFormat},
 };
+use clap::Parser;
+use futures::future::BoxFuture;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[cfg(unix)]
 use tokio::signal::unix::{SignalKind, signal};
 
+#[derive(Parser)]
+#[command(version, about = "AIGC history management service")]
+struct Cli {
+    /// TOML config file; environment variables override its values
+    #[arg(long, env = "CONFIG_PATH")]
+    config: Option<PathBuf>,
+
+    /// Rebuild the shares_by_user index from conversation_shares, then exit
+    #[arg(long)]
+    backfill_share_index: bool,
+
+    /// Record the images referenced by existing messages, then exit. Run once
+    /// before enabling image garbage collection.
+    #[arg(long)]
+    backfill_image_refs: bool,
+
+    /// Detect the language of existing messages and conversations that have
+    /// none, then exit
+    #[arg(long)]
+    backfill_languages: bool,
+
+    /// Count the messages and replies of existing conversations, then exit.
+    /// Run once, while no messages are being written, so size limits apply
+    /// to conversations created before the counters existed.
+    #[arg(long)]
+    backfill_message_counts: bool,
+
+    /// Apply the schema migrations, then exit
+    #[arg(long)]
+    migrate: bool,
+
+    /// With --migrate, print the statements of the migrations the database
+    /// hasn't applied yet instead of running them, or of every migration if
+    /// it can't be reached
+    #[arg(long, requires = "migrate")]
+    dry_run: bool,
+
+    /// With --migrate, stop after the migration with this version (the
+    /// number its file name starts with)
+    #[arg(long, requires = "migrate")]
+    target: Option<u32>,
+
+    /// Create demo conversations through the services at startup, then keep
+    /// serving. Every run creates new data.
+    #[arg(long)]
+    seed: bool,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Load configuration
+    let cli = Cli::parse();
+    let mut settings = Settings::load(cli.config.as_deref())
+        .map_err(|e| format!("Failed to load settings: {}", e))?;
+
     // Initialize tracing
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "aigc_history=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| "aigc_history=info".into());
+    match settings.logging.format {
+        LogFormat::Pretty => tracing_subscriber::registry()
+            .with(filter)
+            .with(tracing_subscriber::fmt::layer())
+            .init(),
+        LogFormat::Json => tracing_subscriber::registry()
+            .with(filter)
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .event_format(JsonFormat)
+                    .fmt_fields(JsonFields),
+            )
+            .init(),
+    }
+    settings
+        .resolve_secrets()
+        .await
+        .map_err(|e| format!("Failed to load secrets: {}", e))?;
 
-    // Load configuration
-    let settings = Settings::from_env().map_err(|e| format!("Failed to load settings: {}", e))?;
+    if cli.migrate {
+        if cli.dry_run {
+            let migrations = migration::load_migrations(&settings.scylla, cli.target)
+                .await
+                .map_err(|e| format!("Failed to load migrations: {}", e))?;
+            match DbClient::applied_migrations(&settings.scylla).await {
+                Some(applied) => {
+                    let pending = migration::pending(&migrations, &applied);
+                    println!(
+                        "-- {} of {} migration(s) pending; the rest are recorded in schema_migrations\n",
+                        pending.len(),
+                        migrations.len()
+                    );
+                    print!("{}", migration::format_plan(pending));
+                }
+                None => {
+                    println!(
+                        "-- Full plan: the database couldn't be reached, so migrations it has already applied are included\n"
+                    );
+                    print!("{}", migration::format_plan(&migrations));
+                }
+            }
+        } else {
+            DbClient::migrate(&settings.scylla, cli.target)
+                .await
+                .map_err(|e| format!("Failed to apply migrations: {}", e))?;
+        }
+        return Ok(());
+    }
 
     tracing::info!("Starting AIGC History Service");
-    tracing::info!("Connecting to ScyllaDB at: {:?}", settings.scylla.nodes);
 
-    // Initialize database client
-    let db_client = DbClient::new(&settings.scylla)
+    let object_store = object_store::from_settings(&settings);
+
+    // Initialize storage backend
+    let (storage, db_client) = match settings.storage.backend {
+        StorageBackend::Scylla => {
+            tracing::info!("Connecting to ScyllaDB at: {:?}", settings.scylla.nodes);
+
+            let db_client = DbClient::new(&settings.scylla)
+                .await
+                .map_err(|e| format!("Failed to connect to ScyllaDB: {}", e))?;
+
+            tracing::info!("Successfully connected to ScyllaDB");
+            let blobs = Arc::new(ContentBlobs::new(object_store.clone(), &settings.blobs));
+            let cipher = ContentCipher::new(db_client.clone(), &settings.encryption)
+                .map_err(|e| format!("Failed to load the master keys: {}", e))?
+                .map(Arc::new);
+            (
+                Storage::scylla_with_content(db_client.clone(), blobs, cipher),
+                Some(db_client),
+            )
+        }
+        StorageBackend::Memory => {
+            tracing::warn!("Using the in-memory storage backend; data is lost on restart");
+            (Storage::memory(), None)
+        }
+    };
+
+    let shutdown = CancellationToken::new();
+    let app_state = AppState::build(
+        &storage,
+        &settings,
+        object_store,
+        db_client.clone(),
+        shutdown.clone(),
+    );
+
+    if cli.backfill_share_index
+        || cli.backfill_image_refs
+        || cli.backfill_languages
+        || cli.backfill_message_counts
+    {
+        if cli.backfill_share_index {
+            let indexed = storage
+                .shares
+                .rebuild_user_index()
+                .await
+                .map_err(|e| format!("Failed to backfill shares_by_user: {}", e))?;
+            tracing::info!("Indexed {} shares by user", indexed);
+        }
+        if cli.backfill_image_refs {
+            let indexed = app_state
+                .image_service
+                .backfill_refs(settings.scylla.page_size)
+                .await
+                .map_err(|e| format!("Failed to backfill image references: {}", e))?;
+            tracing::info!("Recorded {} image references", indexed);
+        }
+        if cli.backfill_languages {
+            let (messages, conversations) = app_state
+                .conversation_service
+                .backfill_languages(settings.scylla.page_size)
+                .await
+                .map_err(|e| format!("Failed to backfill languages: {}", e))?;
+            tracing::info!(
+                "Recorded the language of {} messages and {} conversations",
+                messages,
+                conversations
+            );
+        }
+        if cli.backfill_message_counts {
+            let counted = app_state
+                .conversation_service
+                .backfill_message_counts(settings.scylla.page_size)
+                .await
+                .map_err(|e| format!("Failed to backfill message counts: {}", e))?;
+            tracing::info!("Counted the messages of {} conversations", counted);
+        }
+
+        drop(app_state);
+        drop(storage);
+        if let Some(db_client) = db_client {
+            db_client.close();
+        }
+        return Ok(());
+    }
+
+    if cli.seed {
+        let report = DemoSeeder::new(
+            app_state.conversation_service.clone(),
+            app_state.branch_service.clone(),
+            app_state.share_service.clone(),
+            app_state.fork_service.clone(),
+        )
+        .seed()
         .await
-        .map_err(|e| format!("Failed to connect to ScyllaDB: {}", e))?;
-
-    tracing::info!("Successfully connected to ScyllaDB");
-
-    // Initialize repositories
-    let lineage_repo = LineageRepository::new(db_client.clone());
-    let branch_repo = BranchRepository::new(db_client.clone());
-    let share_repo = ShareRepository::new(db_client.clone());
-
-    // Initialize services
-    let conversation_service = Arc::new(ConversationService::new(
-        lineage_repo.clone(),
-        settings.app.clone(),
-    ));
-
-    let branch_service = Arc::new(BranchService::new(
-        branch_repo.clone(),
-        lineage_repo.clone(),
-    ));
-
-    let fork_service = Arc::new(ForkService::new(
-        lineage_repo.clone(),
-        branch_repo.clone(),
-        settings.app.clone(),
-    ));
-
-    let share_service = Arc::new(ShareService::new(share_repo.clone()));
-
-    // Create application state
-    let app_state = AppState {
-        conversation_service,
-        branch_service,
-        fork_service,
-        share_service,
+        .map_err(|e| format!("Failed to seed demo data: {}", e))?;
+        tracing::info!(
+            "Seeded {} messages: conversation {} (branches {:?}), public conversation {}, fork {}",
+            report.messages,
+            report.conversation_id,
+            report.branch_ids,
+            report.public_conversation_id,
+            report.fork_conversation_id
+        );
+    }
+
+    // Settings validation guarantees the Scylla backend when CDC is enabled
+    let cdc_consumer = match &db_client {
+        Some(db_client) if settings.cdc.enabled => {
+            let consumer = Arc::new(CdcConsumer::new(
+                Arc::new(CdcRepository::new(db_client.clone())),
+                settings.cdc.clone(),
+                settings.app.max_batch_size,
+            ));
+            consumer
+                .enable()
+                .await
+                .map_err(|e| format!("Failed to enable CDC on the lineage table: {}", e))?;
+            Some(consumer)
+        }
+        _ => None,
     };
 
+    let scheduler = app_state.scheduler.clone();
+    let probes = app_state.probes.clone();
+    let collaboration_hub = app_state.collaboration_hub.clone();
+    // Registered once the server is up
+    let services = app_state.clone();
+
     // Build router
     let app = create_router(app_state)
         .layer(CorsLayer::permissive())
         .layer(tower_http::catch_panic::CatchPanicLayer::new());
 
     // Start server
+    let tls_config = tls::server_config(&settings.server)
+        .map_err(|e| format!("Failed to load the TLS certificate: {}", e))?;
+    let scheme = if tls_config.is_some() {
+        "https"
+    } else {
+        "http"
+    };
     let addr = format!("{}:{}", settings.server.host, settings.server.port);
     let listener = tokio::net::TcpListener::bind(&addr)
         .await
         .map_err(|e| format!("Failed to bind to {}: {}", addr, e))?;
 
     tracing::info!("Server listening on {}", addr);
-    tracing::info!("Health check available at: http://{}/health", addr);
-    tracing::info!("API endpoints available at: http://{}/api/v1/", addr);
+    tracing::info!("Health check available at: {}://{}/health", scheme, addr);
+    probes.mark_started();
+    tracing::info!("API endpoints available at: {}://{}/api/v1/", scheme, addr);
 
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(|e| format!("Server error: {}", e))?;
+    scheduler.register(
+        services.trending_service.clone(),
+        Duration::from_secs(settings.trending.interval_secs),
+    );
+    if settings.images.gc_enabled {
+        scheduler.register(
+            services.image_service.clone(),
+            Duration::from_secs(settings.images.gc_interval_secs),
+        );
+    }
+    if settings.analytics.enabled {
+        scheduler.register(
+            services.analytics_service.clone(),
+            Duration::from_secs(settings.analytics.interval_secs),
+        );
+    }
+    scheduler.register(
+        services.job_service.clone(),
+        Duration::from_secs(settings.jobs.recovery_interval_secs),
+    );
+    scheduler.register(
+        services.fork_service.clone(),
+        Duration::from_secs(settings.fork.recovery_interval_secs),
+    );
+    scheduler.register(
+        services.legal_hold_service.clone(),
+        Duration::from_secs(settings.legal_holds.retention_interval_secs),
+    );
+    scheduler.register(
+        services.webhook_service.clone(),
+        Duration::from_secs(settings.webhooks.interval_secs),
+    );
+    if let Some(cdc_consumer) = cdc_consumer {
+        scheduler.register(
+            cdc_consumer,
+            Duration::from_secs(settings.cdc.interval_secs),
+        );
+    }
+    // Don't hold the stores of unregistered services past shutdown, or the
+    // Scylla session can't close
+    drop(services);
+
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        let probes = probes.clone();
+        let ready_delay = Duration::from_secs(settings.server.shutdown_ready_delay_secs);
+        async move {
+            shutdown_signal().await;
+            // Fail readiness first and keep serving, so load balancers stop
+            // routing here before the listener closes
+            probes.start_draining();
+            if !ready_delay.is_zero() {
+                tracing::info!("Readiness now failing; draining in {:?}", ready_delay);
+                tokio::time::sleep(ready_delay).await;
+            }
+            // Live streams never finish on their own; end them so they don't hold up draining
+            collaboration_hub.close_all();
+            shutdown.cancel();
+        }
+    });
+
+    // Stop accepting connections on shutdown and let in-flight requests drain,
+    // up to the configured grace period
+    let grace_period = Duration::from_secs(settings.server.shutdown_grace_period_secs);
+    let stopped = shutdown.clone().cancelled_owned();
+    let server: BoxFuture<'_, std::io::Result<()>> = match tls_config {
+        Some(config) => {
+            let listener = tls::TlsListener::new(listener, config)?;
+            Box::pin(
+                axum::serve(listener, app)
+                    .with_graceful_shutdown(stopped)
+                    .into_future(),
+            )
+        }
+        None => Box::pin(
+            axum::serve(listener, app)
+                .with_graceful_shutdown(stopped)
+                .into_future(),
+        ),
+    };
+
+    tokio::select! {
+        result = server => result.map_err(|e| format!("Server error: {}", e))?,
+        _ = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(grace_period).await;
+        } => {
+            tracing::warn!(
+                "In-flight requests did not finish within {:?}, aborting them",
+                grace_period
+            );
+        }
+    }
+
+    scheduler.join().await;
+    drop(probes);
+    drop(storage);
+    if let Some(db_client) = db_client {
+        db_client.close();
+    }
 
     tracing::info!("Server shutdown complete");
 
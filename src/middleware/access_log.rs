@@ -0,0 +1,49 @@
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+use super::AuthUser;
+use super::embed::{CONVERSATIONS_PATH, EmbedAccess, readable_conversation};
+use crate::domain::AccessLogEntry;
+use crate::services::AccessLogService;
+
+/// Record successful reads of a conversation's read endpoints in its access
+/// log, sampled. Runs inside authentication, so it sees who is reading.
+pub async fn log_access(
+    State(access_log): State<Arc<AccessLogService>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let conversation_id = match readable_conversation(req.uri().path()) {
+        Some(conversation_id) if req.method() == Method::GET && access_log.should_record() => {
+            conversation_id
+        }
+        _ => return next.run(req).await,
+    };
+    let resource = req
+        .uri()
+        .path()
+        .strip_prefix(CONVERSATIONS_PATH)
+        .and_then(|rest| rest.strip_prefix(conversation_id.to_string().as_str()))
+        .unwrap_or_default()
+        .trim_start_matches('/')
+        .to_string();
+    let viewer = req
+        .extensions()
+        .get::<AuthUser>()
+        .map(|user| user.0.clone());
+    let via_embed = req.extensions().get::<EmbedAccess>().is_some();
+
+    let response = next.run(req).await;
+    if response.status().is_success() {
+        let entry = AccessLogEntry::new(conversation_id, viewer, via_embed, resource);
+        // Off the request path; reads shouldn't wait for the log
+        tokio::spawn(async move { access_log.record(&entry).await });
+    }
+
+    response
+}
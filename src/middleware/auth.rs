@@ -1,44 +1,498 @@
-// Simple authentication middleware placeholder
-// In production, implement JWT validation or similar
-
 use axum::{
     body::Body,
-    http::{Request, StatusCode},
+    extract::{FromRequestParts, State},
+    http::{HeaderMap, Method, Request, request::Parts},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::api::error::ApiError;
+use crate::config::{AdminConfig, AuthConfig};
+use crate::db::DbError;
+use crate::domain::{ContentType, SERVICE_IDENTITY_PREFIX};
+use crate::repositories::LineageStore;
+
+use super::embed::{EmbedAccess, constant_time_eq, readable_conversation};
+use super::oidc::OidcVerifier;
+
+/// Header carrying a service account's token
+pub const SERVICE_TOKEN_HEADER: &str = "X-Service-Token";
+
+/// Identity of the caller, required by handlers that act on behalf of a user
+#[derive(Debug, Clone)]
+pub struct AuthUser(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or_else(|| ApiError::Unauthorized("Missing X-User-ID header".to_string()))
+    }
+}
+
+impl AuthUser {
+    /// Allow only the user the resource belongs to
+    pub fn ensure_is(&self, user_id: &str) -> Result<(), ApiError> {
+        if self.0 == user_id {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(format!(
+                "Only {} can access this resource",
+                user_id
+            )))
+        }
+    }
+
+    /// The caller as the author of what the request creates. Bodies may
+    /// still name the author in `field`, but only as the caller.
+    pub fn attribute(self, field: &str, claimed: Option<String>) -> Result<String, ApiError> {
+        match claimed {
+            Some(claimed) if claimed != self.0 => Err(ApiError::Forbidden(format!(
+                "{} must be the authenticated user",
+                field
+            ))),
+            _ => Ok(self.0),
+        }
+    }
+
+    /// Whether the caller is a service account rather than a person
+    pub fn is_service(&self) -> bool {
+        self.0.starts_with(SERVICE_IDENTITY_PREFIX)
+    }
+
+    /// Allow only the deployment's administrators
+    pub fn ensure_admin(&self, admin: &AdminConfig) -> Result<(), ApiError> {
+        if admin.is_admin(&self.0) {
+            Ok(())
+        } else {
+            Err(ApiError::Forbidden(
+                "Only administrators can access this resource".to_string(),
+            ))
+        }
+    }
+}
+
+/// Decides which requests may go without an identity, which service
+/// account a service token stands for and, with OIDC, who an ID token
+/// identifies
+pub struct AuthPolicy {
+    config: AuthConfig,
+    lineage_repo: Arc<dyn LineageStore>,
+    oidc: Option<OidcVerifier>,
+    /// When each service account made requests within the last minute, oldest first
+    recent_requests: Mutex<HashMap<String, VecDeque<DateTime<Utc>>>>,
+}
+
+impl AuthPolicy {
+    pub fn new(config: AuthConfig, lineage_repo: Arc<dyn LineageStore>) -> Self {
+        Self {
+            oidc: OidcVerifier::new(&config),
+            config,
+            lineage_repo,
+            recent_requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Name of the service account `token` belongs to
+    fn service_account(&self, token: &str) -> Option<&str> {
+        self.config
+            .service_tokens
+            .iter()
+            .find(|(_, expected)| constant_time_eq(expected.as_bytes(), token.as_bytes()))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Count a request of the service account against its per-minute
+    /// limit. Fails with the seconds until it may retry.
+    fn record_service_request(&self, name: &str, now: DateTime<Utc>) -> Result<(), u64> {
+        let window_start = now - Duration::minutes(1);
+        let max = self.config.service_rate_limit(name) as usize;
+        let mut recent_requests = self.recent_requests.lock().unwrap();
 
-/// Simple authentication middleware (placeholder)
-/// In production, validate JWT tokens here
-pub async fn auth_middleware(req: Request<Body>, next: Next) -> Result<Response, StatusCode> {
-    // TODO: Implement proper authentication
-    // For now, allow all requests
-
-    // Example JWT validation logic (commented out):
-    // let auth_header = req.headers()
-    //     .get(axum::http::header::AUTHORIZATION)
-    //     .and_then(|header| header.to_str().ok());
-    //
-    // if let Some(auth_header) = auth_header {
-    //     if auth_header.starts_with("Bearer ") {
-    //         let token = &auth_header[7..];
-    //         // Validate token here
-    //         return Ok(next.run(req).await);
-    //     }
-    // }
-    //
-    // Err(StatusCode::UNAUTHORIZED)
-
-    Ok(next.run(req).await)
-}
-
-/// Extract user ID from request headers
-/// This is a helper function for when authentication is implemented
+        let requests = recent_requests.entry(name.to_string()).or_default();
+        while requests.front().is_some_and(|at| *at <= window_start) {
+            requests.pop_front();
+        }
+        if let Some(oldest) = requests.front()
+            && requests.len() >= max
+        {
+            return Err((*oldest - window_start).num_seconds().max(1) as u64);
+        }
+        requests.push_back(now);
+        Ok(())
+    }
+
+    /// Whether the request reads a public conversation, when anonymous
+    /// public reads are enabled
+    async fn allows_anonymous(&self, method: &Method, path: &str) -> bool {
+        if !self.config.anonymous_public_reads || method != Method::GET {
+            return false;
+        }
+        let Some(conversation_id) = readable_conversation(path) else {
+            return false;
+        };
+
+        match self.lineage_repo.get_root_message(conversation_id).await {
+            Ok(root) => {
+                matches!(&root.content, ContentType::Metadata(metadata) if metadata.is_public)
+            }
+            Err(DbError::NotFound) => false,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to check visibility of conversation {}: {}",
+                    conversation_id,
+                    e
+                );
+                false
+            }
+        }
+    }
+}
+
+/// Authentication middleware: attaches the caller's identity to the
+/// request for the `AuthUser` extractor and rejects anonymous requests,
+/// except those carrying an embed token and, if enabled, reads of public
+/// conversations. The identity comes from `X-User-ID`, set by a trusted
+/// gateway, or with OIDC configured from an ID token in
+/// `Authorization: Bearer`, and then only from one. Service accounts
+/// authenticate with their token instead and are rate limited per account.
+pub async fn auth_middleware(
+    State(policy): State<Arc<AuthPolicy>>,
+    mut req: Request<Body>,
+    next: Next,
+) -> Response {
+    let user_id = user_id_from_headers(req.headers());
+    // An embed request's bearer token is the embed token, already checked
+    let id_token = policy
+        .oidc
+        .as_ref()
+        .filter(|_| req.extensions().get::<EmbedAccess>().is_none())
+        .zip(bearer_token(req.headers()));
+    if let Some(token) = req
+        .headers()
+        .get(SERVICE_TOKEN_HEADER)
+        .and_then(|header| header.to_str().ok())
+    {
+        if user_id.is_some() || id_token.is_some() {
+            return ApiError::BadRequest(format!(
+                "Send either a user identity or {}, not both",
+                SERVICE_TOKEN_HEADER
+            ))
+            .into_response();
+        }
+        let Some(name) = policy.service_account(token) else {
+            return ApiError::Unauthorized("Unknown service token".to_string()).into_response();
+        };
+        if let Err(retry_after_secs) = policy.record_service_request(name, Utc::now()) {
+            return ApiError::RateLimited(retry_after_secs).into_response();
+        }
+        let identity = format!("{}{}", SERVICE_IDENTITY_PREFIX, name);
+        req.extensions_mut().insert(AuthUser(identity));
+    } else if let Some((oidc, token)) = id_token {
+        if user_id.is_some() {
+            return ApiError::BadRequest(
+                "Send either X-User-ID or an ID token, not both".to_string(),
+            )
+            .into_response();
+        }
+        let identity = match oidc.verify(&token).await {
+            Ok(identity) => identity,
+            Err(e) => return ApiError::from(e).into_response(),
+        };
+        if identity.starts_with(SERVICE_IDENTITY_PREFIX) {
+            return ApiError::Forbidden(format!(
+                "Service accounts authenticate with {}",
+                SERVICE_TOKEN_HEADER
+            ))
+            .into_response();
+        }
+        req.extensions_mut().insert(AuthUser(identity));
+    } else if let Some(user_id) = user_id {
+        if policy.oidc.is_some() {
+            return ApiError::Unauthorized(
+                "Authenticate with an ID token in Authorization: Bearer, not X-User-ID".to_string(),
+            )
+            .into_response();
+        }
+        if user_id.starts_with(SERVICE_IDENTITY_PREFIX) {
+            return ApiError::Forbidden(format!(
+                "Service accounts authenticate with {}",
+                SERVICE_TOKEN_HEADER
+            ))
+            .into_response();
+        }
+        req.extensions_mut().insert(AuthUser(user_id));
+    } else if req.extensions().get::<EmbedAccess>().is_none()
+        && !policy
+            .allows_anonymous(req.method(), req.uri().path())
+            .await
+    {
+        let missing = if policy.oidc.is_some() {
+            "Missing ID token"
+        } else {
+            "Missing X-User-ID header"
+        };
+        return ApiError::Unauthorized(missing.to_string()).into_response();
+    }
+    next.run(req).await
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+}
+
+/// The user ID of the `X-User-ID` header, unverified
 pub fn extract_user_id(req: &Request<Body>) -> Option<String> {
-    // TODO: Extract from validated JWT token
-    // For now, check for a simple X-User-ID header
-    req.headers()
+    user_id_from_headers(req.headers())
+}
+
+fn user_id_from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
         .get("X-User-ID")
         .and_then(|header| header.to_str().ok())
+        .filter(|s| !s.is_empty())
         .map(|s| s.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Conversation, MetadataContent};
+    use crate::repositories::Storage;
+    use axum::{Router, routing::get};
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    const INDEXER_TOKEN: &str = "0123456789abcdef0123456789abcdef";
+
+    async fn call(app: Router, path: &str, user_id: Option<&str>) -> (u16, String) {
+        let headers: Vec<_> = user_id.map(|id| ("X-User-ID", id)).into_iter().collect();
+        call_with(app, path, &headers).await
+    }
+
+    async fn call_with(app: Router, path: &str, headers: &[(&str, &str)]) -> (u16, String) {
+        let mut req = Request::get(path);
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+        let response = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let status = response.status().as_u16();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    async fn conversation(storage: &Storage, is_public: bool) -> Uuid {
+        let mut conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        if let ContentType::Metadata(MetadataContent {
+            is_public: public, ..
+        }) = &mut conversation.root_message.content
+        {
+            *public = is_public;
+        }
+        storage
+            .lineage
+            .insert_message(&conversation.root_message)
+            .await
+            .unwrap();
+        conversation.conversation_id
+    }
+
+    fn app(storage: &Storage, anonymous_public_reads: bool) -> Router {
+        let policy = Arc::new(AuthPolicy::new(
+            AuthConfig {
+                anonymous_public_reads,
+                service_tokens: [("indexer".to_string(), INDEXER_TOKEN.to_string())].into(),
+                service_requests_per_minute: 2,
+                service_rate_limits: HashMap::new(),
+                ..crate::config::Settings::default().auth
+            },
+            storage.lineage.clone(),
+        ));
+        Router::new()
+            .route("/whoami", get(|user: AuthUser| async move { user.0 }))
+            .route(
+                "/api/v1/conversations/{id}/tree",
+                get(|user: Option<axum::Extension<AuthUser>>| async move {
+                    user.map(|user| user.0.0).unwrap_or_default()
+                }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                policy,
+                auth_middleware,
+            ))
+    }
+
+    #[tokio::test]
+    async fn test_identity_comes_from_the_middleware() {
+        let storage = Storage::memory();
+        let app = app(&storage, false);
+        assert_eq!(
+            call(app.clone(), "/whoami", Some("user_a")).await,
+            (200, "user_a".to_string())
+        );
+        assert_eq!(call(app, "/whoami", None).await.0, 401);
+        // Without the middleware the header alone isn't trusted
+        let bare = Router::new().route("/whoami", get(|user: AuthUser| async move { user.0 }));
+        assert_eq!(call(bare, "/whoami", Some("user_a")).await.0, 401);
+
+        let user = AuthUser("user_a".to_string());
+        assert_eq!(
+            user.clone().attribute("created_by", None).unwrap(),
+            "user_a"
+        );
+        assert!(
+            user.clone()
+                .attribute("created_by", Some("user_a".to_string()))
+                .is_ok()
+        );
+        assert!(matches!(
+            user.attribute("created_by", Some("user_b".to_string())),
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_reads_reach_public_conversations_only() {
+        let storage = Storage::memory();
+        let public = conversation(&storage, true).await;
+        let private = conversation(&storage, false).await;
+        let tree = |id: Uuid| format!("/api/v1/conversations/{}/tree", id);
+
+        assert_eq!(call(app(&storage, false), &tree(public), None).await.0, 401);
+
+        let app = app(&storage, true);
+        assert_eq!(
+            call(app.clone(), &tree(public), None).await,
+            (200, String::new())
+        );
+        assert_eq!(call(app.clone(), &tree(private), None).await.0, 401);
+        assert_eq!(call(app.clone(), &tree(Uuid::new_v4()), None).await.0, 401);
+        assert_eq!(call(app.clone(), "/whoami", None).await.0, 401);
+        assert_eq!(
+            call(app, &tree(private), Some("user_b")).await,
+            (200, "user_b".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_service_accounts_authenticate_by_token_and_are_rate_limited() {
+        let storage = Storage::memory();
+        let app = app(&storage, false);
+        let service = [(SERVICE_TOKEN_HEADER, INDEXER_TOKEN)];
+
+        assert_eq!(
+            call_with(app.clone(), "/whoami", &service).await,
+            (200, "service:indexer".to_string())
+        );
+        assert!(AuthUser("service:indexer".to_string()).is_service());
+        assert_eq!(
+            call_with(app.clone(), "/whoami", &[(SERVICE_TOKEN_HEADER, "guess")])
+                .await
+                .0,
+            401
+        );
+        assert_eq!(
+            call_with(
+                app.clone(),
+                "/whoami",
+                &[
+                    (SERVICE_TOKEN_HEADER, INDEXER_TOKEN),
+                    ("X-User-ID", "user_a")
+                ]
+            )
+            .await
+            .0,
+            400
+        );
+        // People can't pass themselves off as a service account
+        assert_eq!(
+            call(app.clone(), "/whoami", Some("service:indexer"))
+                .await
+                .0,
+            403
+        );
+
+        assert_eq!(call_with(app.clone(), "/whoami", &service).await.0, 200);
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/whoami")
+                    .header(SERVICE_TOKEN_HEADER, INDEXER_TOKEN)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 429);
+        assert!(response.headers().contains_key("retry-after"));
+        // Only the service account is limited
+        assert_eq!(call(app, "/whoami", Some("user_a")).await.0, 200);
+    }
+
+    #[tokio::test]
+    async fn test_oidc_replaces_the_user_id_header() {
+        let storage = Storage::memory();
+        let mut config = crate::config::Settings::default().auth;
+        config.oidc_issuer = Some("https://login.example.com".to_string());
+        config.oidc_client_id = Some("aigc-history".to_string());
+        config.service_tokens = [("indexer".to_string(), INDEXER_TOKEN.to_string())].into();
+        let policy = Arc::new(AuthPolicy::new(config, storage.lineage.clone()));
+        let app = Router::new()
+            .route("/whoami", get(|user: AuthUser| async move { user.0 }))
+            .layer(axum::middleware::from_fn_with_state(
+                policy,
+                auth_middleware,
+            ));
+
+        // The gateway header can't be trusted any more
+        let (status, body) = call(app.clone(), "/whoami", Some("user_a")).await;
+        assert_eq!(status, 401);
+        assert!(body.contains("ID token"));
+        let (status, body) = call(app.clone(), "/whoami", None).await;
+        assert_eq!(status, 401);
+        assert!(body.contains("Missing ID token"));
+        assert_eq!(
+            call_with(
+                app.clone(),
+                "/whoami",
+                &[("Authorization", "Bearer not-a-jwt")]
+            )
+            .await
+            .0,
+            401
+        );
+        assert_eq!(
+            call_with(
+                app.clone(),
+                "/whoami",
+                &[
+                    ("Authorization", "Bearer not-a-jwt"),
+                    (SERVICE_TOKEN_HEADER, INDEXER_TOKEN)
+                ]
+            )
+            .await
+            .0,
+            400
+        );
+        assert_eq!(
+            call_with(app, "/whoami", &[(SERVICE_TOKEN_HEADER, INDEXER_TOKEN)])
+                .await
+                .0,
+            200
+        );
+    }
+}
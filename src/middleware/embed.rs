@@ -0,0 +1,275 @@
+use axum::{
+    extract::{Request, State},
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use ring::hmac;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::error::ApiError;
+use crate::config::EmbedConfig;
+use crate::utils::hex::hex;
+
+/// Prefix and version of embed tokens, so they can't be mistaken for other bearer tokens
+const TOKEN_PREFIX: &str = "emb1";
+
+/// Query parameter carrying the token, for viewers that can't set headers
+const TOKEN_PARAM: &str = "embed_token";
+
+pub(super) const CONVERSATIONS_PATH: &str = "/api/v1/conversations/";
+
+/// Set on requests let through by a valid embed token, naming its conversation
+#[derive(Debug, Clone, Copy)]
+pub struct EmbedAccess(pub Uuid);
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum EmbedTokenError {
+    #[error("Embed tokens are not enabled on this server")]
+    Disabled,
+
+    #[error("Token validity must be between 1 and {0} seconds")]
+    InvalidTtl(u64),
+
+    #[error("Invalid embed token")]
+    Invalid,
+
+    #[error("Embed token expired")]
+    Expired,
+}
+
+/// Issues and verifies signed tokens granting read access to a single
+/// conversation until they expire. Tokens are stateless: `emb1.<conversation
+/// id>.<expiry as unix seconds>.<HMAC-SHA256 of the preceding part>`.
+pub struct EmbedTokens {
+    config: EmbedConfig,
+}
+
+impl EmbedTokens {
+    pub fn new(config: EmbedConfig) -> Self {
+        Self { config }
+    }
+
+    /// A token for `conversation_id`, valid for `ttl_secs` or the configured
+    /// default, and when it expires
+    pub fn issue(
+        &self,
+        conversation_id: Uuid,
+        ttl_secs: Option<u64>,
+        now: DateTime<Utc>,
+    ) -> Result<(String, DateTime<Utc>), EmbedTokenError> {
+        let secret = self.secret()?;
+        let ttl_secs = ttl_secs.unwrap_or(self.config.default_ttl_secs);
+        if !(1..=self.config.max_ttl_secs).contains(&ttl_secs) {
+            return Err(EmbedTokenError::InvalidTtl(self.config.max_ttl_secs));
+        }
+
+        let expires_at = now + Duration::seconds(ttl_secs as i64);
+        let payload = format!(
+            "{}.{}.{}",
+            TOKEN_PREFIX,
+            conversation_id,
+            expires_at.timestamp()
+        );
+        let signature = hex(sign(secret, &payload));
+
+        Ok((format!("{}.{}", payload, signature), expires_at))
+    }
+
+    /// The conversation a token grants access to
+    pub fn verify(&self, token: &str, now: DateTime<Utc>) -> Result<Uuid, EmbedTokenError> {
+        let secret = self.secret()?;
+        let (payload, signature) = token.rsplit_once('.').ok_or(EmbedTokenError::Invalid)?;
+        let expected = hex(sign(secret, payload));
+        if !constant_time_eq(expected.as_bytes(), signature.as_bytes()) {
+            return Err(EmbedTokenError::Invalid);
+        }
+
+        let mut parts = payload.split('.');
+        let (Some(TOKEN_PREFIX), Some(conversation_id), Some(expires_at), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(EmbedTokenError::Invalid);
+        };
+        let conversation_id =
+            Uuid::parse_str(conversation_id).map_err(|_| EmbedTokenError::Invalid)?;
+        let expires_at: i64 = expires_at.parse().map_err(|_| EmbedTokenError::Invalid)?;
+        if now.timestamp() >= expires_at {
+            return Err(EmbedTokenError::Expired);
+        }
+
+        Ok(conversation_id)
+    }
+
+    fn secret(&self) -> Result<&[u8], EmbedTokenError> {
+        self.config
+            .secret
+            .as_deref()
+            .map(str::as_bytes)
+            .ok_or(EmbedTokenError::Disabled)
+    }
+}
+
+impl From<EmbedTokenError> for ApiError {
+    fn from(err: EmbedTokenError) -> Self {
+        match err {
+            EmbedTokenError::Disabled => ApiError::NotFound(err.to_string()),
+            EmbedTokenError::InvalidTtl(_) => ApiError::BadRequest(err.to_string()),
+            EmbedTokenError::Invalid | EmbedTokenError::Expired => {
+                ApiError::Unauthorized(err.to_string())
+            }
+        }
+    }
+}
+
+/// Confine requests carrying an embed token, in an `Authorization: Bearer`
+/// header or the `embed_token` query parameter, to the read endpoints of the
+/// token's conversation. They act as an anonymous caller. Requests without
+/// one pass through unchanged.
+pub async fn restrict_embed_tokens(
+    State(tokens): State<Arc<EmbedTokens>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = embed_token(&req) else {
+        return next.run(req).await;
+    };
+
+    let conversation_id = match tokens.verify(&token, Utc::now()) {
+        Ok(conversation_id) => conversation_id,
+        Err(e) => return ApiError::from(e).into_response(),
+    };
+    if req.method() != Method::GET || !is_embed_readable(req.uri().path(), conversation_id) {
+        return ApiError::Forbidden(
+            "Embed tokens only grant read access to their conversation".to_string(),
+        )
+        .into_response();
+    }
+
+    req.headers_mut().remove("X-User-ID");
+    req.extensions_mut().insert(EmbedAccess(conversation_id));
+    next.run(req).await
+}
+
+fn embed_token(req: &Request) -> Option<String> {
+    let bearer = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .filter(|token| token.starts_with(TOKEN_PREFIX));
+    let param = req.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix(TOKEN_PARAM)?.strip_prefix('='))
+    });
+
+    bearer.or(param).map(str::to_string)
+}
+
+/// The conversation `path` is a read endpoint of, if any
+pub(crate) fn readable_conversation(path: &str) -> Option<Uuid> {
+    let rest = path.strip_prefix(CONVERSATIONS_PATH)?;
+    let id = rest.split('/').next()?;
+    let conversation_id = Uuid::parse_str(id).ok()?;
+    is_embed_readable(path, conversation_id).then_some(conversation_id)
+}
+
+/// Whether `path` is one of the read endpoints of the conversation: the
+/// conversation itself, its tree, messages (with children, lineage and
+/// ancestors), branches, checkpoints and context
+fn is_embed_readable(path: &str, conversation_id: Uuid) -> bool {
+    let Some(rest) = path
+        .strip_prefix(CONVERSATIONS_PATH)
+        .and_then(|rest| rest.strip_prefix(conversation_id.to_string().as_str()))
+    else {
+        return false;
+    };
+
+    let segments: Vec<&str> = match rest.strip_prefix('/') {
+        Some(rest) => rest.split('/').collect(),
+        None if rest.is_empty() => Vec::new(),
+        None => return false,
+    };
+    matches!(
+        segments.as_slice(),
+        [] | ["tree"]
+            | ["context"]
+            | ["checkpoints"]
+            | ["branches"]
+            | ["branches", _]
+            | ["branches", "by-slug", _]
+            | ["branches", _, "messages"]
+            | ["messages", _]
+            | ["messages", _, "children" | "lineage" | "ancestors"]
+    ) && segments.iter().all(|segment| !segment.is_empty())
+}
+
+/// HMAC-SHA256 of a token's payload
+fn sign(secret: &[u8], payload: &str) -> hmac::Tag {
+    hmac::sign(
+        &hmac::Key::new(hmac::HMAC_SHA256, secret),
+        payload.as_bytes(),
+    )
+}
+
+pub(super) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens() -> EmbedTokens {
+        EmbedTokens::new(EmbedConfig {
+            secret: Some("0123456789abcdef0123456789abcdef".to_string()),
+            default_ttl_secs: 60,
+            max_ttl_secs: 3600,
+        })
+    }
+
+    #[test]
+    fn test_embed_token_round_trip() {
+        let tokens = tokens();
+        let conversation_id = Uuid::new_v4();
+        let now = Utc::now();
+
+        let (token, expires_at) = tokens.issue(conversation_id, None, now).unwrap();
+        assert_eq!(expires_at.timestamp(), now.timestamp() + 60);
+        assert_eq!(tokens.verify(&token, now), Ok(conversation_id));
+        assert_eq!(
+            tokens.verify(&token, expires_at),
+            Err(EmbedTokenError::Expired)
+        );
+
+        let other = Uuid::new_v4().to_string();
+        let forged = token.replace(&conversation_id.to_string(), &other);
+        assert_eq!(tokens.verify(&forged, now), Err(EmbedTokenError::Invalid));
+        assert_eq!(
+            tokens.issue(conversation_id, Some(7200), now),
+            Err(EmbedTokenError::InvalidTtl(3600))
+        );
+    }
+
+    #[test]
+    fn test_embed_tokens_reach_read_endpoints_only() {
+        let id = Uuid::new_v4();
+        let path = |suffix: &str| format!("{}{}{}", CONVERSATIONS_PATH, id, suffix);
+
+        assert!(is_embed_readable(&path(""), id));
+        assert!(is_embed_readable(&path("/tree"), id));
+        assert!(is_embed_readable(&path("/messages/m1/lineage"), id));
+        assert!(is_embed_readable(&path("/messages/m1/ancestors"), id));
+        assert!(is_embed_readable(&path("/branches/b1/messages"), id));
+        assert!(!is_embed_readable(&path("/shares"), id));
+        assert!(!is_embed_readable(&path("/export"), id));
+        assert!(!is_embed_readable(&path("/messages//lineage"), id));
+        assert!(!is_embed_readable(&path("x"), id));
+        assert!(!is_embed_readable(&path("/tree"), Uuid::new_v4()));
+        assert_eq!(readable_conversation(&path("/branches")), Some(id));
+        assert_eq!(readable_conversation(&path("/shares")), None);
+    }
+}
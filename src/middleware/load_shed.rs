@@ -0,0 +1,26 @@
+use axum::{
+    BoxError,
+    response::{IntoResponse, Response},
+};
+
+use crate::api::error::ApiError;
+
+/// Seconds clients are asked to wait before retrying a shed request
+const RETRY_AFTER_SECS: u64 = 1;
+
+/// Concurrency limits enforced by the router. The global limit draws from
+/// the semaphore in `Probes`, so readiness can report shedding.
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// Requests handled at once across expensive endpoints (tree, fork, export, import)
+    pub max_concurrent_expensive_requests: usize,
+}
+
+/// Turn errors of the load-shedding stack into responses; shed requests get 503 with Retry-After
+pub async fn handle_overload(err: BoxError) -> Response {
+    if err.is::<tower::load_shed::error::Overloaded>() {
+        ApiError::Overloaded(RETRY_AFTER_SECS).into_response()
+    } else {
+        ApiError::Internal(format!("Unhandled middleware error: {}", err)).into_response()
+    }
+}
@@ -1,3 +1,15 @@
+pub mod access_log;
 pub mod auth;
+pub mod embed;
+pub mod load_shed;
+pub mod oidc;
+pub mod problem;
+pub mod request_log;
 
+pub use access_log::*;
 pub use auth::*;
+pub use embed::*;
+pub use load_shed::*;
+pub use oidc::*;
+pub use problem::*;
+pub use request_log::*;
@@ -0,0 +1,434 @@
+//! OpenID Connect ID tokens as the caller's identity. Tokens are JWTs signed
+//! with RS256 or ES256 by the configured issuer; its signing keys are found
+//! through its discovery document and cached.
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::api::error::ApiError;
+use crate::config::{AuthConfig, OidcIdentityClaim};
+use crate::utils::http::{HttpClient, HttpError};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Shortest time between two fetches of the keys, so tokens naming unknown
+/// keys can't make every request hit the issuer
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Clock skew tolerated on `exp` and `nbf`, in seconds
+const LEEWAY_SECS: i64 = 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("Invalid ID token: {0}")]
+    InvalidToken(String),
+    #[error("Expired ID token")]
+    Expired,
+    #[error("Couldn't fetch the identity provider's keys: {0}")]
+    Provider(String),
+}
+
+impl From<OidcError> for ApiError {
+    fn from(err: OidcError) -> Self {
+        match err {
+            OidcError::InvalidToken(_) | OidcError::Expired => {
+                ApiError::Unauthorized(err.to_string())
+            }
+            OidcError::Provider(_) => ApiError::Internal(err.to_string()),
+        }
+    }
+}
+
+/// A signing key of the issuer
+#[derive(Debug, Clone)]
+enum SigningKey {
+    /// Modulus and exponent, big-endian
+    Rsa { n: Vec<u8>, e: Vec<u8> },
+    /// Uncompressed P-256 point
+    P256(Vec<u8>),
+}
+
+struct Keys {
+    by_id: HashMap<String, SigningKey>,
+    fetched_at: Instant,
+}
+
+/// Verifies ID tokens of the configured issuer and client
+pub struct OidcVerifier {
+    issuer: String,
+    client_id: String,
+    identity_claim: OidcIdentityClaim,
+    cache_for: Duration,
+    /// Held while fetching, so concurrent requests wait for one fetch
+    keys: Mutex<Option<Keys>>,
+}
+
+impl OidcVerifier {
+    /// `None` unless `auth.oidc_issuer` and `auth.oidc_client_id` are set
+    pub fn new(config: &AuthConfig) -> Option<Self> {
+        Some(Self {
+            issuer: config.oidc_issuer.clone()?,
+            client_id: config.oidc_client_id.clone()?,
+            identity_claim: config.oidc_identity_claim,
+            cache_for: Duration::from_secs(config.oidc_jwks_cache_secs),
+            keys: Mutex::new(None),
+        })
+    }
+
+    /// The identity an ID token stands for, once its signature, issuer,
+    /// audience and validity period are checked
+    pub async fn verify(&self, token: &str) -> Result<String, OidcError> {
+        let invalid = |reason: &str| OidcError::InvalidToken(reason.to_string());
+        let mut parts = token.split('.');
+        let (Some(header), Some(payload), Some(signature), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid("not a signed JWT"));
+        };
+        let header = decode_json(header).ok_or_else(|| invalid("malformed header"))?;
+        let claims = decode_json(payload).ok_or_else(|| invalid("malformed claims"))?;
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| invalid("malformed signature"))?;
+
+        let alg = header["alg"].as_str().unwrap_or_default();
+        if !matches!(alg, "RS256" | "ES256") {
+            return Err(invalid("unsupported algorithm"));
+        }
+        let kid = header["kid"].as_str().unwrap_or_default();
+        let key = self.key(kid).await?;
+        let signed = &token[..header_and_payload_len(token)];
+        let verified = match (alg, &key) {
+            ("RS256", SigningKey::Rsa { n, e }) => RsaPublicKeyComponents { n, e }
+                .verify(
+                    &signature::RSA_PKCS1_2048_8192_SHA256,
+                    signed.as_bytes(),
+                    &signature,
+                )
+                .is_ok(),
+            ("ES256", SigningKey::P256(point)) => {
+                UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                    .verify(signed.as_bytes(), &signature)
+                    .is_ok()
+            }
+            _ => return Err(invalid("algorithm doesn't match the key")),
+        };
+        if !verified {
+            return Err(invalid("bad signature"));
+        }
+
+        self.check_claims(&claims, Utc::now().timestamp())?;
+        self.identity(&claims)
+    }
+
+    fn check_claims(&self, claims: &serde_json::Value, now: i64) -> Result<(), OidcError> {
+        let invalid = |reason: &str| OidcError::InvalidToken(reason.to_string());
+        if claims["iss"].as_str() != Some(self.issuer.as_str()) {
+            return Err(invalid("issued by another issuer"));
+        }
+        let audience = &claims["aud"];
+        let for_us = audience.as_str() == Some(self.client_id.as_str())
+            || audience
+                .as_array()
+                .is_some_and(|aud| aud.iter().any(|a| a.as_str() == Some(&self.client_id)));
+        if !for_us {
+            return Err(invalid("issued to another client"));
+        }
+        let expires = claims["exp"].as_i64().ok_or_else(|| invalid("no expiry"))?;
+        if expires + LEEWAY_SECS <= now {
+            return Err(OidcError::Expired);
+        }
+        if claims["nbf"]
+            .as_i64()
+            .is_some_and(|not_before| not_before - LEEWAY_SECS > now)
+        {
+            return Err(invalid("not valid yet"));
+        }
+
+        Ok(())
+    }
+
+    fn identity(&self, claims: &serde_json::Value) -> Result<String, OidcError> {
+        let identity = match self.identity_claim {
+            OidcIdentityClaim::Subject => claims["sub"].as_str(),
+            OidcIdentityClaim::Email if claims["email_verified"].as_bool() == Some(true) => {
+                claims["email"].as_str()
+            }
+            OidcIdentityClaim::Email => None,
+        };
+
+        identity
+            .filter(|identity| !identity.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                OidcError::InvalidToken(match self.identity_claim {
+                    OidcIdentityClaim::Subject => "no subject".to_string(),
+                    OidcIdentityClaim::Email => "no verified email".to_string(),
+                })
+            })
+    }
+
+    /// The signing key `kid`, fetching the keys again once they are stale or
+    /// when the key is new to us
+    async fn key(&self, kid: &str) -> Result<SigningKey, OidcError> {
+        let mut keys = self.keys.lock().await;
+        let (known, fresh, may_refresh) = match keys.as_ref() {
+            Some(keys) => (
+                keys.by_id.get(kid).cloned(),
+                keys.fetched_at.elapsed() < self.cache_for,
+                keys.fetched_at.elapsed() >= MIN_REFRESH_INTERVAL,
+            ),
+            None => (None, false, true),
+        };
+        match known {
+            Some(key) if fresh => return Ok(key),
+            None if !may_refresh => {
+                return Err(OidcError::InvalidToken("unknown signing key".to_string()));
+            }
+            _ => {}
+        }
+
+        let by_id = self.fetch_keys().await?;
+        let key = by_id.get(kid).cloned();
+        *keys = Some(Keys {
+            by_id,
+            fetched_at: Instant::now(),
+        });
+
+        key.ok_or_else(|| OidcError::InvalidToken("unknown signing key".to_string()))
+    }
+
+    /// The issuer's signing keys, by key ID, from the JWKS its discovery
+    /// document points to
+    async fn fetch_keys(&self) -> Result<HashMap<String, SigningKey>, OidcError> {
+        let client = HttpClient::configured();
+        let provider = |e: HttpError| OidcError::Provider(e.to_string());
+        let discovery = client
+            .get_json(
+                &format!(
+                    "{}/.well-known/openid-configuration",
+                    self.issuer.trim_end_matches('/')
+                ),
+                &[],
+                REQUEST_TIMEOUT,
+            )
+            .await
+            .map_err(provider)?;
+        if discovery["issuer"].as_str() != Some(self.issuer.as_str()) {
+            return Err(OidcError::Provider(
+                "the discovery document names another issuer".to_string(),
+            ));
+        }
+        let jwks_uri = discovery["jwks_uri"].as_str().ok_or_else(|| {
+            OidcError::Provider("the discovery document has no jwks_uri".to_string())
+        })?;
+        let jwks = client
+            .get_json(jwks_uri, &[], REQUEST_TIMEOUT)
+            .await
+            .map_err(provider)?;
+
+        Ok(signing_keys(&jwks))
+    }
+}
+
+/// Length of the `header.payload` part of a token, which the signature is over
+fn header_and_payload_len(token: &str) -> usize {
+    token.rfind('.').unwrap_or(token.len())
+}
+
+fn decode_json(part: &str) -> Option<serde_json::Value> {
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).ok()?).ok()
+}
+
+/// The RSA and P-256 signature keys of a JWKS, by key ID; other keys are
+/// skipped
+fn signing_keys(jwks: &serde_json::Value) -> HashMap<String, SigningKey> {
+    let field = |key: &serde_json::Value, name: &str| {
+        key[name]
+            .as_str()
+            .and_then(|value| URL_SAFE_NO_PAD.decode(value).ok())
+    };
+
+    jwks["keys"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|key| key["use"].as_str().is_none_or(|usage| usage == "sig"))
+        .filter_map(|key| {
+            let kid = key["kid"].as_str().unwrap_or_default().to_string();
+            let signing_key = match (key["kty"].as_str(), key["crv"].as_str()) {
+                (Some("RSA"), _) => SigningKey::Rsa {
+                    n: field(key, "n")?,
+                    e: field(key, "e")?,
+                },
+                (Some("EC"), Some("P-256")) => {
+                    let mut point = vec![0x04];
+                    point.extend(field(key, "x")?);
+                    point.extend(field(key, "y")?);
+                    SigningKey::P256(point)
+                }
+                _ => return None,
+            };
+            Some((kid, signing_key))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, routing::get};
+    use ring::rand::SystemRandom;
+    use ring::signature::{EcdsaKeyPair, KeyPair, RsaKeyPair};
+    use serde_json::json;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const RSA_KEY: &[u8] = include_bytes!("../../tests/fixtures/oidc/rsa_key.pk8");
+
+    fn encode(data: &[u8]) -> String {
+        URL_SAFE_NO_PAD.encode(data)
+    }
+
+    /// Serve a discovery document and `jwks` on a local port, counting the
+    /// JWKS fetches, and return the issuer
+    async fn issuer(jwks: serde_json::Value, fetches: Arc<AtomicUsize>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let discovery = json!({ "issuer": issuer, "jwks_uri": format!("{}/jwks", issuer) });
+        let app = Router::new()
+            .route(
+                "/.well-known/openid-configuration",
+                get(move || async move { Json(discovery) }),
+            )
+            .route(
+                "/jwks",
+                get(move || async move {
+                    fetches.fetch_add(1, Ordering::SeqCst);
+                    Json(jwks)
+                }),
+            );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        issuer
+    }
+
+    fn verifier_for(issuer: &str, identity_claim: OidcIdentityClaim) -> OidcVerifier {
+        let mut config = crate::config::Settings::default().auth;
+        config.oidc_issuer = Some(issuer.to_string());
+        config.oidc_client_id = Some("aigc-history".to_string());
+        config.oidc_identity_claim = identity_claim;
+        OidcVerifier::new(&config).unwrap()
+    }
+
+    fn token(
+        alg: &str,
+        kid: &str,
+        claims: &serde_json::Value,
+        sign: impl Fn(&[u8]) -> Vec<u8>,
+    ) -> String {
+        let header = json!({ "alg": alg, "kid": kid, "typ": "JWT" });
+        let signed = format!(
+            "{}.{}",
+            encode(header.to_string().as_bytes()),
+            encode(claims.to_string().as_bytes())
+        );
+        format!("{}.{}", signed, encode(&sign(signed.as_bytes())))
+    }
+
+    #[tokio::test]
+    async fn test_id_tokens_are_verified_against_the_issuer_keys() {
+        let rng = SystemRandom::new();
+        let rsa = RsaKeyPair::from_pkcs8(RSA_KEY).unwrap();
+        let rsa_public = RsaPublicKeyComponents::<Vec<u8>>::from(rsa.public());
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(&signature::ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+            .unwrap();
+        let ec = EcdsaKeyPair::from_pkcs8(
+            &signature::ECDSA_P256_SHA256_FIXED_SIGNING,
+            pkcs8.as_ref(),
+            &rng,
+        )
+        .unwrap();
+        let point = ec.public_key().as_ref();
+        let jwks = json!({ "keys": [
+            { "kty": "RSA", "kid": "rsa", "use": "sig", "n": encode(&rsa_public.n), "e": encode(&rsa_public.e) },
+            { "kty": "EC", "kid": "ec", "crv": "P-256", "x": encode(&point[1..33]), "y": encode(&point[33..]) },
+        ]});
+        let fetches = Arc::new(AtomicUsize::new(0));
+        let issuer = issuer(jwks, fetches.clone()).await;
+        let verifier = verifier_for(&issuer, OidcIdentityClaim::Subject);
+
+        let now = Utc::now().timestamp();
+        let claims = json!({
+            "iss": issuer, "aud": ["other", "aigc-history"], "sub": "user_a",
+            "email": "a@example.com", "email_verified": true, "iat": now, "exp": now + 300,
+        });
+        let rs256 = |claims: &serde_json::Value| {
+            token("RS256", "rsa", claims, |message| {
+                let mut signature = vec![0; rsa.public().modulus_len()];
+                rsa.sign(&signature::RSA_PKCS1_SHA256, &rng, message, &mut signature)
+                    .unwrap();
+                signature
+            })
+        };
+        let es256 = |claims: &serde_json::Value| {
+            token("ES256", "ec", claims, |message| {
+                ec.sign(&rng, message).unwrap().as_ref().to_vec()
+            })
+        };
+
+        assert_eq!(verifier.verify(&rs256(&claims)).await.unwrap(), "user_a");
+        assert_eq!(verifier.verify(&es256(&claims)).await.unwrap(), "user_a");
+        // The keys are cached
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        let by_email = verifier_for(&issuer, OidcIdentityClaim::Email);
+        assert_eq!(
+            by_email.verify(&es256(&claims)).await.unwrap(),
+            "a@example.com"
+        );
+
+        let mut impostor = claims.clone();
+        impostor["sub"] = json!("user_b");
+        let mut parts: Vec<String> = rs256(&claims).split('.').map(str::to_string).collect();
+        parts[1] = encode(impostor.to_string().as_bytes());
+        assert!(matches!(
+            verifier.verify(&parts.join(".")).await,
+            Err(OidcError::InvalidToken(_))
+        ));
+        let mut forged = es256(&claims);
+        forged.truncate(forged.rfind('.').unwrap());
+        forged.push_str(&format!(".{}", encode(&[0; 64])));
+        assert!(matches!(
+            verifier.verify(&forged).await,
+            Err(OidcError::InvalidToken(_))
+        ));
+        for (field, value) in [
+            ("iss", json!("https://elsewhere.example.com")),
+            ("aud", json!("other")),
+            ("exp", json!(now - 120)),
+            ("nbf", json!(now + 600)),
+            ("sub", json!("")),
+        ] {
+            let mut claims = claims.clone();
+            claims[field] = value;
+            assert!(verifier.verify(&rs256(&claims)).await.is_err(), "{}", field);
+        }
+        let mut unverified = claims.clone();
+        unverified["email_verified"] = json!(false);
+        assert!(by_email.verify(&es256(&unverified)).await.is_err());
+        let unsigned = token("none", "rsa", &claims, |_| Vec::new());
+        assert!(verifier.verify(&unsigned).await.is_err());
+
+        // Unknown keys make the keys be fetched again, at most once a minute
+        let fetched = fetches.load(Ordering::SeqCst);
+        let unknown = token("ES256", "rotated", &claims, |message| {
+            ec.sign(&rng, message).unwrap().as_ref().to_vec()
+        });
+        assert!(verifier.verify(&unknown).await.is_err());
+        assert!(verifier.verify(&unknown).await.is_err());
+        assert_eq!(fetches.load(Ordering::SeqCst), fetched);
+    }
+}
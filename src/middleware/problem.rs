@@ -0,0 +1,197 @@
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::api::error::ErrorDetails;
+use crate::config::{ErrorFormat, ErrorsConfig};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const PROBLEM_JSON: &str = "application/problem+json";
+
+/// Longest client-supplied request ID that is kept; longer ones are replaced
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// ID of the current request, taken from `X-Request-ID` or generated
+#[derive(Debug, Clone)]
+pub struct RequestId(pub String);
+
+/// RFC 7807 problem details, with our error code and the request ID as
+/// extension members
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: String,
+    pub status: u16,
+    pub detail: String,
+    pub instance: String,
+    pub code: String,
+    pub request_id: String,
+}
+
+/// Tag each request with a request ID, echoed in `X-Request-ID`, and render
+/// API errors as `application/problem+json` when the client accepts it or
+/// the server is configured to
+pub async fn problem_details(
+    State(config): State<Arc<ErrorsConfig>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let request_id = req
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    let wants_problem = config.format == ErrorFormat::Problem || accepts_problem(&req);
+    let instance = req.uri().path().to_string();
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let mut response = next.run(req).await;
+    if wants_problem && let Some(details) = response.extensions_mut().remove::<ErrorDetails>() {
+        let problem = problem(
+            &config,
+            &details,
+            response.status().as_u16(),
+            instance,
+            &request_id,
+        );
+        let (mut parts, _) = response.into_parts();
+        let (new_parts, body) = Json(problem).into_response().into_parts();
+        parts.headers.extend(new_parts.headers);
+        parts
+            .headers
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(PROBLEM_JSON));
+        response = Response::from_parts(parts, body);
+    }
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn accepts_problem(req: &Request) -> bool {
+    req.headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| {
+            let mut params = media_type.split(';').map(str::trim);
+            params
+                .next()
+                .is_some_and(|essence| essence.eq_ignore_ascii_case(PROBLEM_JSON))
+                && !params.any(|param| param.replace(' ', "") == "q=0")
+        })
+}
+
+fn problem(
+    config: &ErrorsConfig,
+    details: &ErrorDetails,
+    status: u16,
+    instance: String,
+    request_id: &str,
+) -> Problem {
+    let title = axum::http::StatusCode::from_u16(status)
+        .ok()
+        .and_then(|status| status.canonical_reason())
+        .unwrap_or("Error");
+    let problem_type = match &config.problem_type_base_url {
+        Some(base) => format!("{}{}", base, details.code),
+        None => "about:blank".to_string(),
+    };
+
+    Problem {
+        problem_type,
+        title: title.to_string(),
+        status,
+        detail: details.detail.clone(),
+        instance,
+        code: details.code.to_string(),
+        request_id: request_id.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiError;
+    use axum::{Router, body::Body, routing::get};
+    use tower::ServiceExt;
+
+    fn app(format: ErrorFormat) -> Router {
+        let config = Arc::new(ErrorsConfig {
+            format,
+            problem_type_base_url: Some("https://errors.example.com/".to_string()),
+        });
+        Router::new()
+            .route(
+                "/missing",
+                get(|| async { ApiError::NotFound("Conversation not found".to_string()) }),
+            )
+            .layer(axum::middleware::from_fn_with_state(
+                config,
+                problem_details,
+            ))
+    }
+
+    async fn get_json(app: Router, accept: Option<&str>) -> (Response, serde_json::Value) {
+        let mut req = Request::get("/missing").header(&REQUEST_ID_HEADER, "req-1");
+        if let Some(accept) = accept {
+            req = req.header(header::ACCEPT, accept);
+        }
+        let response = app.oneshot(req.body(Body::empty()).unwrap()).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let bytes = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (
+            Response::from_parts(parts, Body::empty()),
+            serde_json::from_slice(&bytes).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_problem_details_are_negotiated() {
+        let (response, body) = get_json(app(ErrorFormat::Json), None).await;
+        assert_eq!(
+            body,
+            serde_json::json!({"error": "Conversation not found", "code": "not_found"})
+        );
+        assert_eq!(response.headers()[&REQUEST_ID_HEADER], "req-1");
+
+        let (response, body) =
+            get_json(app(ErrorFormat::Json), Some("application/problem+json")).await;
+        assert_eq!(response.status(), 404);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], PROBLEM_JSON);
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "type": "https://errors.example.com/not_found",
+                "title": "Not Found",
+                "status": 404,
+                "detail": "Conversation not found",
+                "instance": "/missing",
+                "code": "not_found",
+                "request_id": "req-1",
+            })
+        );
+
+        let (_, body) = get_json(app(ErrorFormat::Problem), Some("application/json")).await;
+        assert_eq!(body["code"], "not_found");
+        let (_, body) = get_json(
+            app(ErrorFormat::Json),
+            Some("application/problem+json;q=0, application/json"),
+        )
+        .await;
+        assert!(body.get("type").is_none());
+    }
+}
@@ -0,0 +1,199 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{MatchedPath, Request, State},
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use tracing::Instrument;
+
+use super::{RequestId, extract_user_id};
+
+/// JSON fields whose values are replaced before bodies are logged
+const REDACTED_FIELDS: &[&str] = &[
+    "content",
+    "text",
+    "summary",
+    "title",
+    "description",
+    "arguments",
+    "result",
+    "password",
+    "token",
+    "secret",
+];
+
+const REDACTED: &str = "[REDACTED]";
+
+/// What the request logging middleware records
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLogging {
+    /// Fraction of successful requests that are logged; errors are always logged
+    pub sample_rate: f64,
+    /// Also log (redacted) JSON request and response bodies
+    pub log_bodies: bool,
+    /// Bodies larger than this are not buffered for logging
+    pub max_body_bytes: usize,
+}
+
+/// Log method, route template, status, latency and user of each request
+pub async fn log_requests(
+    State(logging): State<RequestLogging>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let start = Instant::now();
+    let method = req.method().clone();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "<unmatched>".to_string());
+    let user_id = extract_user_id(&req).unwrap_or_else(|| "-".to_string());
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .map(|id| id.0.clone())
+        .unwrap_or_else(|| "-".to_string());
+
+    let (req, request_body) = if logging.log_bodies {
+        let (parts, body) = req.into_parts();
+        let (body, logged) = capture_body(&parts.headers, body, logging.max_body_bytes).await;
+        (Request::from_parts(parts, body), logged)
+    } else {
+        (req, None)
+    };
+
+    // Everything logged while handling the request shares this span (and its trace id)
+    let span = tracing::info_span!("request", %method, %route, %request_id);
+    let response = next.run(req).instrument(span.clone()).await;
+    let latency_ms = start.elapsed().as_secs_f64() * 1000.0;
+    let status = response.status();
+
+    let sampled = status.is_client_error()
+        || status.is_server_error()
+        || logging.sample_rate >= 1.0
+        || rand::random::<f64>() < logging.sample_rate;
+    if !sampled {
+        return response;
+    }
+
+    let (response, response_body) = if logging.log_bodies {
+        let (parts, body) = response.into_parts();
+        let (body, logged) = capture_body(&parts.headers, body, logging.max_body_bytes).await;
+        (Response::from_parts(parts, body), logged)
+    } else {
+        (response, None)
+    };
+
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!(
+                target: "aigc_history::http",
+                %method,
+                %route,
+                status = status.as_u16(),
+                latency_ms,
+                %user_id,
+                request_body,
+                response_body,
+                "request failed"
+            );
+        } else {
+            tracing::info!(
+                target: "aigc_history::http",
+                %method,
+                %route,
+                status = status.as_u16(),
+                latency_ms,
+                %user_id,
+                request_body,
+                response_body,
+                "request completed"
+            );
+        }
+    });
+
+    response
+}
+
+/// Buffer a small JSON body for logging and hand back an equivalent body.
+/// Streams and large or non-JSON bodies are passed through untouched.
+async fn capture_body(
+    headers: &HeaderMap,
+    body: Body,
+    max_body_bytes: usize,
+) -> (Body, Option<String>) {
+    let is_json = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    let small = body
+        .size_hint()
+        .exact()
+        .is_some_and(|len| len as usize <= max_body_bytes);
+    if !is_json || !small {
+        return (body, None);
+    }
+
+    match axum::body::to_bytes(body, max_body_bytes).await {
+        Ok(bytes) => {
+            let logged = redact_body(&bytes);
+            (Body::from(bytes), Some(logged))
+        }
+        Err(e) => {
+            tracing::warn!("Failed to buffer body for logging: {}", e);
+            (Body::empty(), None)
+        }
+    }
+}
+
+fn redact_body(bytes: &Bytes) -> String {
+    match serde_json::from_slice::<serde_json::Value>(bytes) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => format!("<{} bytes of invalid JSON>", bytes.len()),
+    }
+}
+
+/// Replace the values of content-bearing fields anywhere in a JSON document
+pub fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if REDACTED_FIELDS.contains(&key.as_str()) {
+                    *field = serde_json::Value::String(REDACTED.to_string());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_nested_content_fields() {
+        let mut value = serde_json::json!({
+            "parent_message_id": "8f0e",
+            "role": "human",
+            "content": { "type": "text", "text": "my secret prompt" },
+            "messages": [{ "title": "Private", "created_by": "user_a" }],
+        });
+
+        redact(&mut value);
+
+        assert_eq!(value["content"], REDACTED);
+        assert_eq!(value["role"], "human");
+        assert_eq!(value["messages"][0]["title"], REDACTED);
+        assert_eq!(value["messages"][0]["created_by"], "user_a");
+    }
+}
@@ -0,0 +1,318 @@
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use ring::hmac;
+use std::time::Duration;
+
+use super::http::{self, key_below, uri_encode};
+use super::{ObjectStore, ObjectStoreError};
+use crate::config::AzureConfig;
+
+/// Storage service version requests and SAS tokens are made for
+const API_VERSION: &str = "2021-08-06";
+
+/// The configured Azure Blob Storage container, authorized with the
+/// account's Shared Key. `azure.endpoint` is the blob service's URL, with
+/// the account in the path for Azurite
+/// (`http://azurite:10000/devstoreaccount1`).
+pub struct AzureObjectStore {
+    config: AzureConfig,
+}
+
+impl AzureObjectStore {
+    pub fn new(config: AzureConfig) -> Self {
+        Self { config }
+    }
+
+    /// Request path of the container, below the endpoint's path
+    fn container_path(&self) -> Result<String, ObjectStoreError> {
+        let (_, prefix) = http::split_endpoint(&self.config.endpoint)?;
+        Ok(format!("{}/{}", prefix, self.config.container))
+    }
+
+    fn blob_path(&self, key: &str) -> Result<String, ObjectStoreError> {
+        Ok(format!(
+            "{}/{}",
+            self.container_path()?,
+            uri_encode(key, false)
+        ))
+    }
+}
+
+#[async_trait]
+impl ObjectStore for AzureObjectStore {
+    /// Understands `{endpoint}/container/key` and
+    /// `https://{account}.blob.core.windows.net/container/key` URLs. Query
+    /// strings (e.g. SAS tokens) are ignored.
+    fn key_for_url(&self, url: &str) -> Option<String> {
+        let url = url.split(['?', '#']).next()?;
+        let container = format!("/{}", self.config.container);
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        let public = format!("https://{}.blob.core.windows.net", self.config.account);
+
+        let path = url
+            .strip_prefix(endpoint)
+            .or_else(|| url.strip_prefix(&public))?;
+
+        key_below(path, &container)
+    }
+
+    fn presigned_get_url(&self, key: &str, expires_in: Duration) -> String {
+        let endpoint = self.config.endpoint.trim_end_matches('/');
+        let expiry = Utc::now() + chrono::Duration::seconds(expires_in.as_secs() as i64);
+
+        format!(
+            "{}/{}/{}?{}",
+            endpoint,
+            self.config.container,
+            uri_encode(key, false),
+            sas_query(&self.config, key, expiry)
+        )
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        let path = self.blob_path(key)?;
+        let status = self
+            .send(
+                "PUT",
+                &path,
+                None,
+                &[("x-ms-blob-type", "BlockBlob")],
+                Some((content_type, body.as_slice())),
+            )
+            .await?
+            .0;
+
+        match status {
+            201 => Ok(()),
+            _ => Err(ObjectStoreError::Request(format!(
+                "PUT {} returned {}",
+                path, status
+            ))),
+        }
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        let path = self.blob_path(key)?;
+
+        match self.send("GET", &path, None, &[], None).await? {
+            (200, body) => Ok(body),
+            (status, _) => Err(ObjectStoreError::Request(format!(
+                "GET {} returned {}",
+                path, status
+            ))),
+        }
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), ObjectStoreError> {
+        let path = self.blob_path(key)?;
+        let (status, _) = self.send("DELETE", &path, None, &[], None).await?;
+
+        match status {
+            202 | 404 => Ok(()),
+            _ => Err(ObjectStoreError::Request(format!(
+                "DELETE {} returned {}",
+                path, status
+            ))),
+        }
+    }
+
+    async fn check(&self) -> Result<(), ObjectStoreError> {
+        let path = self.container_path()?;
+        match self
+            .send("HEAD", &path, Some(("restype", "container")), &[], None)
+            .await?
+        {
+            (200, _) => Ok(()),
+            (status, _) => Err(ObjectStoreError::Request(format!(
+                "HEAD {} returned {}",
+                path, status
+            ))),
+        }
+    }
+}
+
+impl AzureObjectStore {
+    /// Send a request signed with the Shared Key, with a query parameter,
+    /// extra `x-ms-*` headers (sorted by name) and a body and its content
+    /// type if given, and return the response status and body
+    async fn send(
+        &self,
+        method: &str,
+        path: &str,
+        query: Option<(&str, &str)>,
+        ms_headers: &[(&str, &str)],
+        body: Option<(&str, &[u8])>,
+    ) -> Result<(u16, Vec<u8>), ObjectStoreError> {
+        let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let payload = body.map_or(&[][..], |(_, payload)| payload);
+
+        let mut signed_headers = ms_headers.to_vec();
+        signed_headers.extend([("x-ms-date", date.as_str()), ("x-ms-version", API_VERSION)]);
+        let mut resource = format!("/{}{}", self.config.account, path);
+        if let Some((name, value)) = query {
+            resource.push_str(&format!("\n{}:{}", name, value));
+        }
+        let string_to_sign = string_to_sign(
+            method,
+            payload.len(),
+            body.map(|(content_type, _)| content_type),
+            &signed_headers,
+            &resource,
+        );
+        let authorization = format!(
+            "SharedKey {}:{}",
+            self.config.account,
+            sign(&self.config, &string_to_sign)
+        );
+
+        let mut headers = signed_headers;
+        if let Some((content_type, _)) = body {
+            headers.push(("content-type", content_type));
+        }
+        headers.push(("authorization", authorization.as_str()));
+        let target = match query {
+            Some((name, value)) => format!("{}?{}={}", path, name, value),
+            None => path.to_string(),
+        };
+
+        http::send(&self.config.endpoint, method, &target, &headers, payload).await
+    }
+}
+
+/// Shared Key string to sign of a request without conditional headers.
+/// `ms_headers` are the `x-ms-*` headers, lowercase and sorted.
+fn string_to_sign(
+    method: &str,
+    content_length: usize,
+    content_type: Option<&str>,
+    ms_headers: &[(&str, &str)],
+    canonical_resource: &str,
+) -> String {
+    let content_length = match content_length {
+        0 => String::new(),
+        n => n.to_string(),
+    };
+    let canonical_headers: String = ms_headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+
+    // Content-Encoding, -Language, -Length, -MD5, -Type, Date, the four
+    // If-* headers and Range, then the x-ms-* headers and the resource
+    format!(
+        "{}\n\n\n{}\n\n{}\n\n\n\n\n\n\n{}{}",
+        method,
+        content_length,
+        content_type.unwrap_or(""),
+        canonical_headers,
+        canonical_resource
+    )
+}
+
+/// Base64 HMAC-SHA256 of `string_to_sign` keyed with the account key
+fn sign(config: &AzureConfig, string_to_sign: &str) -> String {
+    // Settings validation rejects keys that aren't base64
+    let key = STANDARD.decode(&config.access_key).unwrap_or_default();
+    let key = hmac::Key::new(hmac::HMAC_SHA256, &key);
+    STANDARD.encode(hmac::sign(&key, string_to_sign.as_bytes()))
+}
+
+/// Query string of a service SAS allowing to read the blob until `expiry`
+fn sas_query(config: &AzureConfig, key: &str, expiry: DateTime<Utc>) -> String {
+    let expiry = expiry.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let resource = format!("/blob/{}/{}/{}", config.account, config.container, key);
+    // Permissions, start, expiry, resource, identifier, IP, protocol,
+    // version, resource type, snapshot time, encryption scope and the five
+    // response header overrides
+    let string_to_sign = format!(
+        "r\n\n{}\n{}\n\n\n\n{}\nb\n\n\n\n\n\n\n",
+        expiry, resource, API_VERSION
+    );
+
+    format!(
+        "sv={}&se={}&sr=b&sp=r&sig={}",
+        API_VERSION,
+        uri_encode(&expiry, true),
+        uri_encode(&sign(config, &string_to_sign), true)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> AzureConfig {
+        AzureConfig {
+            endpoint: "http://azurite:10000/devstoreaccount1".to_string(),
+            account: "devstoreaccount1".to_string(),
+            access_key: "Eby8vdM02xNOcqFlqUwJPLlmEtlCDXJ1OUzFT50uSRZ6IFsuFq2UVErCz4I6tq/K1SZFPTOtr/KBHBeksoGMGw==".to_string(),
+            container: "aigc-images".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_key_for_url() {
+        let store = AzureObjectStore::new(config());
+
+        for url in [
+            "http://azurite:10000/devstoreaccount1/aigc-images/gen/cat%201.png?sv=2021-08-06&sig=abc",
+            "https://devstoreaccount1.blob.core.windows.net/aigc-images/gen/cat%201.png",
+        ] {
+            assert_eq!(
+                store.key_for_url(url).as_deref(),
+                Some("gen/cat 1.png"),
+                "{}",
+                url
+            );
+        }
+        for url in [
+            "http://azurite:10000/devstoreaccount1/other/cat.png",
+            "https://other.blob.core.windows.net/aigc-images/cat.png",
+            "s3://aigc-images/cat.png",
+        ] {
+            assert_eq!(store.key_for_url(url), None, "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_string_to_sign_layout() {
+        let string_to_sign = string_to_sign(
+            "PUT",
+            5,
+            Some("image/png"),
+            &[
+                ("x-ms-blob-type", "BlockBlob"),
+                ("x-ms-date", "Fri, 26 Jun 2015 23:39:12 GMT"),
+                ("x-ms-version", API_VERSION),
+            ],
+            "/devstoreaccount1/devstoreaccount1/aigc-images/cat.png",
+        );
+
+        assert_eq!(
+            string_to_sign,
+            "PUT\n\n\n5\n\nimage/png\n\n\n\n\n\n\n\
+             x-ms-blob-type:BlockBlob\n\
+             x-ms-date:Fri, 26 Jun 2015 23:39:12 GMT\n\
+             x-ms-version:2021-08-06\n\
+             /devstoreaccount1/devstoreaccount1/aigc-images/cat.png"
+        );
+        assert_eq!(string_to_sign.split('\n').count(), 12 + 3 + 1);
+    }
+
+    #[test]
+    fn test_presigned_url() {
+        let url = AzureObjectStore::new(config())
+            .presigned_get_url("gen/cat 1.png", Duration::from_secs(60));
+
+        assert!(url.starts_with(
+            "http://azurite:10000/devstoreaccount1/aigc-images/gen/cat%201.png?sv=2021-08-06&se="
+        ));
+        assert!(url.contains("&sr=b&sp=r&sig="));
+    }
+}
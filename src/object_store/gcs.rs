@@ -0,0 +1,123 @@
+use async_trait::async_trait;
+use std::time::Duration;
+
+use super::http::key_below;
+use super::{ObjectStore, ObjectStoreError, S3ObjectStore};
+use crate::config::{GcsConfig, S3Config};
+
+/// Public hosts Cloud Storage serves objects from, path-style
+const PUBLIC_HOSTS: &[&str] = &["storage.googleapis.com", "storage.cloud.google.com"];
+
+/// A Google Cloud Storage bucket, reached through the XML API's
+/// S3 interoperability with an HMAC key: requests are signed like S3's, with
+/// the `auto` region.
+pub struct GcsObjectStore {
+    bucket: String,
+    inner: S3ObjectStore,
+}
+
+impl GcsObjectStore {
+    pub fn new(config: GcsConfig) -> Self {
+        Self {
+            inner: S3ObjectStore::new(S3Config {
+                endpoint: config.endpoint,
+                access_key: config.access_key,
+                secret_key: config.secret_key,
+                bucket: config.bucket.clone(),
+                region: "auto".to_string(),
+            }),
+            bucket: config.bucket,
+        }
+    }
+}
+
+#[async_trait]
+impl ObjectStore for GcsObjectStore {
+    /// Understands `gs://bucket/key` and public `https://storage.googleapis.com/bucket/key`
+    /// URLs on top of the endpoint's URLs
+    fn key_for_url(&self, url: &str) -> Option<String> {
+        let path = url.split(['?', '#']).next()?;
+        let bucket = format!("/{}", self.bucket);
+
+        if let Some(rest) = path.strip_prefix("gs:/") {
+            return key_below(rest, &bucket);
+        }
+        for host in PUBLIC_HOSTS {
+            if let Some(rest) = path.strip_prefix(&format!("https://{}", host)) {
+                return key_below(rest, &bucket);
+            }
+        }
+
+        // `s3://` links name an S3 bucket, whatever it's called
+        if url.starts_with("s3://") {
+            return None;
+        }
+        self.inner.key_for_url(url)
+    }
+
+    fn presigned_get_url(&self, key: &str, expires_in: Duration) -> String {
+        self.inner.presigned_get_url(key, expires_in)
+    }
+
+    async fn put_object(
+        &self,
+        key: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<(), ObjectStoreError> {
+        self.inner.put_object(key, content_type, body).await
+    }
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+        self.inner.get_object(key).await
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), ObjectStoreError> {
+        self.inner.delete_object(key).await
+    }
+
+    async fn check(&self) -> Result<(), ObjectStoreError> {
+        self.inner.check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_for_url() {
+        let store = GcsObjectStore::new(GcsConfig {
+            endpoint: "http://storage.googleapis.com".to_string(),
+            access_key: "GOOGTS7C7FUP3AIRVJTE2BCD".to_string(),
+            secret_key: "bGoa+V7g/yqDXvKRqq+JTFn4uQZbPiQJo4pf9RzJ".to_string(),
+            bucket: "aigc-images".to_string(),
+        });
+
+        for url in [
+            "gs://aigc-images/gen/cat%201.png",
+            "https://storage.googleapis.com/aigc-images/gen/cat%201.png",
+            "https://storage.cloud.google.com/aigc-images/gen/cat%201.png?authuser=1",
+            "http://storage.googleapis.com/aigc-images/gen/cat%201.png?X-Amz-Signature=abc",
+        ] {
+            assert_eq!(
+                store.key_for_url(url).as_deref(),
+                Some("gen/cat 1.png"),
+                "{}",
+                url
+            );
+        }
+        for url in [
+            "gs://other-bucket/cat.png",
+            "https://storage.googleapis.com/aigc-images-2/cat.png",
+            "s3://aigc-images/cat.png",
+        ] {
+            assert_eq!(store.key_for_url(url), None, "{}", url);
+        }
+        assert!(
+            store
+                .presigned_get_url("gen/cat 1.png", Duration::from_secs(60))
+                .starts_with("http://storage.googleapis.com/aigc-images/gen/cat%201.png?")
+        );
+    }
+}
@@ -0,0 +1,130 @@
+//! Transport and URL encoding shared by the providers. Endpoints are
+//! `https://` URLs of the cloud services or `http://` ones, e.g. an emulator
+//! on the local network.
+
+use std::time::Duration;
+
+use super::ObjectStoreError;
+use crate::utils::http::HttpClient;
+
+pub(super) const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Uploads and downloads may carry large exports or message contents
+pub(super) const TRANSFER_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Authority and path prefix of an `http://` or `https://` endpoint,
+/// without a trailing slash on the path
+pub(super) fn split_endpoint(endpoint: &str) -> Result<(&str, &str), ObjectStoreError> {
+    let endpoint = endpoint.trim_end_matches('/');
+    let rest = endpoint
+        .strip_prefix("https://")
+        .or_else(|| endpoint.strip_prefix("http://"))
+        .ok_or_else(|| {
+            ObjectStoreError::Request(format!(
+                "{} is not an http:// or https:// endpoint",
+                endpoint
+            ))
+        })?;
+
+    Ok(match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, ""),
+    })
+}
+
+/// Timeout of a request: transfers of object contents get longer
+pub(super) fn timeout_for(method: &str, has_body: bool) -> Duration {
+    if has_body || method == "GET" {
+        TRANSFER_TIMEOUT
+    } else {
+        REQUEST_TIMEOUT
+    }
+}
+
+/// Send a request to the endpoint's host with the given headers and return
+/// the response status and body
+pub(super) async fn send(
+    endpoint: &str,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: &[u8],
+) -> Result<(u16, Vec<u8>), ObjectStoreError> {
+    let (scheme, _) = endpoint.split_once("://").unwrap_or(("http", endpoint));
+    let (authority, _) = split_endpoint(endpoint)?;
+    let url = format!("{}://{}{}", scheme, authority, path);
+
+    let response = HttpClient::configured()
+        .send(
+            method,
+            &url,
+            headers,
+            body.to_vec(),
+            timeout_for(method, !body.is_empty()),
+        )
+        .await
+        .map_err(|e| ObjectStoreError::Request(e.to_string()))?;
+
+    Ok((response.status, response.body))
+}
+
+/// Percent-encode everything but unreserved characters, and `/` unless
+/// `encode_slash` (query values encode it, paths don't)
+pub(super) fn uri_encode(value: &str, encode_slash: bool) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' if !encode_slash => "/".to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes; `None` if an escape or the result is malformed
+pub(super) fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Key of the object at `path` below `prefix`, e.g. `/bucket` for `/bucket/key`
+pub(super) fn key_below(path: &str, prefix: &str) -> Option<String> {
+    let rest = path.strip_prefix(prefix)?.strip_prefix('/')?;
+    percent_decode(rest).filter(|key| !key.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_endpoint() {
+        assert_eq!(
+            split_endpoint("http://minio:9000/").unwrap(),
+            ("minio:9000", "")
+        );
+        assert_eq!(
+            split_endpoint("http://azurite:10000/devstoreaccount1").unwrap(),
+            ("azurite:10000", "/devstoreaccount1")
+        );
+        assert_eq!(
+            split_endpoint("https://storage.googleapis.com").unwrap(),
+            ("storage.googleapis.com", "")
+        );
+        assert!(split_endpoint("ftp://minio:9000").is_err());
+    }
+}
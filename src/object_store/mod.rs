@@ -0,0 +1,63 @@
+//! Object storage holding the image files that messages link to,
+//! conversation exports and offloaded message contents
+
+pub mod azure;
+pub mod gcs;
+mod http;
+#[cfg(any(test, feature = "test_support"))]
+pub mod memory;
+pub mod s3;
+
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{ObjectStoreProvider, Settings};
+
+pub use azure::AzureObjectStore;
+pub use gcs::GcsObjectStore;
+pub use s3::S3ObjectStore;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ObjectStoreError {
+    #[error("object store request failed: {0}")]
+    Request(String),
+
+    #[error("unexpected object store response: {0}")]
+    InvalidResponse(String),
+}
+
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// The key of the object a URL points at, or `None` if the URL is not
+    /// served by this store (e.g. an image hosted elsewhere)
+    fn key_for_url(&self, url: &str) -> Option<String>;
+
+    /// A URL anyone holding it can download the object from until it expires
+    fn presigned_get_url(&self, key: &str, expires_in: Duration) -> String;
+
+    /// Create or replace an object
+    async fn put_object(
+        &self,
+        key: &str,
+        content_type: &str,
+        body: Vec<u8>,
+    ) -> Result<(), ObjectStoreError>;
+
+    async fn get_object(&self, key: &str) -> Result<Vec<u8>, ObjectStoreError>;
+
+    /// Delete an object; deleting one that does not exist succeeds
+    async fn delete_object(&self, key: &str) -> Result<(), ObjectStoreError>;
+
+    /// Succeeds if the store is reachable and the bucket accessible
+    async fn check(&self) -> Result<(), ObjectStoreError>;
+}
+
+/// The store selected by `object_store.provider`
+pub fn from_settings(settings: &Settings) -> Arc<dyn ObjectStore> {
+    match settings.object_store.provider {
+        ObjectStoreProvider::S3 => Arc::new(S3ObjectStore::new(settings.s3.clone())),
+        ObjectStoreProvider::Gcs => Arc::new(GcsObjectStore::new(settings.gcs.clone())),
+        ObjectStoreProvider::Azure => Arc::new(AzureObjectStore::new(settings.azure.clone())),
+    }
+}
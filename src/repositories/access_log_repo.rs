@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::store::AccessLogStore;
+use crate::db::{AccessLogRow, DbClient, DbError, StatementProfile};
+use crate::domain::AccessLogEntry;
+
+#[derive(Clone)]
+pub struct AccessLogRepository {
+    client: DbClient,
+}
+
+impl AccessLogRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl AccessLogStore for AccessLogRepository {
+    async fn insert_entry(&self, entry: &AccessLogEntry) -> Result<(), DbError> {
+        let row = AccessLogRow::from_entry(entry);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_ACCESS_LOG_ENTRY,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    row.conversation_id,
+                    row.accessed_at,
+                    row.entry_id,
+                    row.viewer,
+                    row.via_embed,
+                    row.branch_id,
+                    row.resource,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_entries(
+        &self,
+        conversation_id: Uuid,
+        before: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<AccessLogEntry>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_ACCESS_LOG,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<AccessLogRow> = self
+            .client
+            .fetch_all(query, (conversation_id, before, limit))
+            .await?;
+
+        Ok(rows.into_iter().map(AccessLogRow::to_entry).collect())
+    }
+}
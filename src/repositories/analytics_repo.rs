@@ -0,0 +1,64 @@
+use async_trait::async_trait;
+
+use super::store::AnalyticsStore;
+use crate::db::{AnalyticsRollupRow, DbClient, DbError, StatementProfile};
+
+#[derive(Clone)]
+pub struct AnalyticsRepository {
+    client: DbClient,
+}
+
+impl AnalyticsRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl AnalyticsStore for AnalyticsRepository {
+    /// Rollups span many partitions, so they are written one by one rather
+    /// than in a batch
+    async fn upsert_rollups(&self, rollups: &[AnalyticsRollupRow]) -> Result<(), DbError> {
+        for rollup in rollups {
+            let query = self.client.statement(
+                crate::db::queries::INSERT_ANALYTICS_ROLLUP,
+                StatementProfile::BulkWrite,
+            );
+            self.client
+                .execute(
+                    query,
+                    (
+                        &rollup.scope,
+                        &rollup.day,
+                        rollup.message_count,
+                        rollup.fork_count,
+                        &rollup.messages_by_role,
+                        &rollup.messages_by_model,
+                        &rollup.input_tokens_by_model,
+                        &rollup.output_tokens_by_model,
+                        rollup.computed_at,
+                    ),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get a range of days of one scope (one partition)
+    async fn get_rollups(
+        &self,
+        scope: &str,
+        from_day: &str,
+        to_day: &str,
+    ) -> Result<Vec<AnalyticsRollupRow>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_ANALYTICS_ROLLUPS,
+            StatementProfile::InteractiveRead,
+        );
+
+        self.client
+            .fetch_all(query, (scope, from_day, to_day))
+            .await
+    }
+}
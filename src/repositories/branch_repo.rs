@@ -1,10 +1,12 @@
+use async_trait::async_trait;
 use chrono::Utc;
-use scylla::IntoTypedRows;
-use scylla::query::Query;
+use scylla::frame::response::result::CqlValue;
 use uuid::Uuid;
 
-use crate::db::{BranchByLeafRow, BranchRow, DbClient, DbError};
-use crate::domain::Branch;
+use super::event_seqs;
+use super::store::BranchStore;
+use crate::db::{BranchByLeafRow, BranchRow, BranchSlugRow, DbClient, DbError, StatementProfile};
+use crate::domain::{Branch, BranchSlug, ConversationEvent};
 
 #[derive(Clone)]
 pub struct BranchRepository {
@@ -15,15 +17,20 @@ impl BranchRepository {
     pub fn new(client: DbClient) -> Self {
         Self { client }
     }
+}
 
+#[async_trait]
+impl BranchStore for BranchRepository {
     /// Insert a new branch
-    pub async fn insert_branch(&self, branch: &Branch) -> Result<(), DbError> {
+    async fn insert_branch(&self, branch: &Branch) -> Result<(), DbError> {
         let row = BranchRow::from_branch(branch);
-        let query = Query::new(crate::db::queries::INSERT_BRANCH);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_BRANCH,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(
+            .execute(
                 query,
                 (
                     row.conversation_id,
@@ -34,9 +41,18 @@ impl BranchRepository {
                     row.last_updated,
                     row.created_by,
                     row.is_active,
+                    row.slug,
                 ),
             )
             .await?;
+        self.insert_branch_slug(&BranchSlug {
+            conversation_id: branch.conversation_id,
+            slug: branch.slug.clone(),
+            branch_id: branch.branch_id,
+            branch_name: branch.branch_name.clone(),
+            created_at: branch.created_at,
+        })
+        .await?;
 
         // Also insert into branch_by_leaf index
         self.insert_branch_by_leaf(
@@ -50,70 +66,60 @@ impl BranchRepository {
     }
 
     /// Get a specific branch
-    pub async fn get_branch(
-        &self,
-        conversation_id: Uuid,
-        branch_id: Uuid,
-    ) -> Result<Branch, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_BRANCH);
+    async fn get_branch(&self, conversation_id: Uuid, branch_id: Uuid) -> Result<Branch, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_BRANCH,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
+        let row: BranchRow = self
             .client
-            .session()
-            .query(query, (conversation_id, branch_id))
+            .fetch_one(query, (conversation_id, branch_id))
             .await?;
 
-        let row = result
-            .rows
-            .ok_or(DbError::NotFound)?
-            .into_typed::<BranchRow>()
-            .next()
-            .ok_or(DbError::NotFound)?
-            .map_err(|e| DbError::InvalidData(format!("Failed to parse branch row: {}", e)))?;
-
         Ok(row.to_branch())
     }
 
     /// Get all branches for a conversation
-    pub async fn get_branches_by_conversation(
+    async fn get_branches_by_conversation(
         &self,
         conversation_id: Uuid,
     ) -> Result<Vec<Branch>, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_BRANCHES_BY_CONVERSATION);
+        let query = self.client.statement(
+            crate::db::queries::SELECT_BRANCHES_BY_CONVERSATION,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
-            .client
-            .session()
-            .query(query, (conversation_id,))
-            .await?;
-
-        let rows = result.rows.unwrap_or_default();
-        let mut branches = Vec::new();
-
-        for row in rows.into_typed::<BranchRow>() {
-            let row =
-                row.map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-            branches.push(row.to_branch());
-        }
+        let rows: Vec<BranchRow> = self.client.fetch_all(query, (conversation_id,)).await?;
+        let branches = rows.into_iter().map(|row| row.to_branch()).collect();
 
         Ok(branches)
     }
 
     /// Update branch leaf message
-    pub async fn update_branch_leaf(
+    async fn update_branch_leaf(
         &self,
         conversation_id: Uuid,
         branch_id: Uuid,
         old_leaf_id: Uuid,
         new_leaf_id: Uuid,
+        events: &[ConversationEvent],
     ) -> Result<(), DbError> {
-        let now = Utc::now();
-        let query = Query::new(crate::db::queries::UPDATE_BRANCH_LEAF);
+        // The branch and its events in one logged batch
+        let mut batch = self.client.logged_batch();
+        batch.append_statement(crate::db::queries::UPDATE_BRANCH_LEAF);
+        let mut values_list = vec![vec![
+            Some(CqlValue::Uuid(new_leaf_id)),
+            Some(CqlValue::Timestamp(Utc::now().into())),
+            Some(CqlValue::Uuid(conversation_id)),
+            Some(CqlValue::Uuid(branch_id)),
+        ]];
+        for row in event_seqs::numbered_event_rows(&self.client, events).await? {
+            batch.append_statement(crate::db::queries::INSERT_EVENT);
+            values_list.push(row.insert_values());
+        }
 
-        self.client
-            .session()
-            .query(query, (new_leaf_id, now, conversation_id, branch_id))
-            .await?;
+        self.client.execute_batch(&batch, values_list).await?;
 
         // Update branch_by_leaf index
         self.delete_branch_by_leaf(old_leaf_id).await?;
@@ -124,64 +130,116 @@ impl BranchRepository {
     }
 
     /// Update branch name
-    pub async fn update_branch_name(
+    async fn update_branch_name(
         &self,
         conversation_id: Uuid,
         branch_id: Uuid,
         new_name: String,
+        new_slug: String,
     ) -> Result<(), DbError> {
         let now = Utc::now();
-        let query = Query::new(crate::db::queries::UPDATE_BRANCH_NAME);
+        // Claim the slug first, so the branch never carries one that doesn't resolve
+        self.insert_branch_slug(&BranchSlug {
+            conversation_id,
+            slug: new_slug.clone(),
+            branch_id,
+            branch_name: new_name.clone(),
+            created_at: now,
+        })
+        .await?;
+
+        let query = self.client.statement(
+            crate::db::queries::UPDATE_BRANCH_NAME,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (new_name, now, conversation_id, branch_id))
+            .execute(query, (new_name, new_slug, now, conversation_id, branch_id))
             .await?;
 
         Ok(())
     }
 
     /// Delete a branch
-    pub async fn delete_branch(
+    async fn delete_branch(
         &self,
         conversation_id: Uuid,
         branch_id: Uuid,
         leaf_message_id: Uuid,
     ) -> Result<(), DbError> {
-        let query = Query::new(crate::db::queries::DELETE_BRANCH);
+        let query = self.client.statement(
+            crate::db::queries::DELETE_BRANCH,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (conversation_id, branch_id))
+            .execute(query, (conversation_id, branch_id))
             .await?;
 
         // Also delete from branch_by_leaf index
         self.delete_branch_by_leaf(leaf_message_id).await?;
 
+        // Release the branch's slugs, including those of earlier names
+        let query = self.client.statement(
+            crate::db::queries::DELETE_BRANCH_SLUG,
+            StatementProfile::InteractiveWrite,
+        );
+        for slug in self.get_branch_slugs(conversation_id).await? {
+            if slug.branch_id == branch_id {
+                self.client
+                    .execute(query.clone(), (conversation_id, slug.slug))
+                    .await?;
+            }
+        }
+
         Ok(())
     }
 
     /// Get branch by leaf message ID
-    pub async fn get_branch_by_leaf(&self, leaf_message_id: Uuid) -> Result<(Uuid, Uuid), DbError> {
-        let query = Query::new(crate::db::queries::SELECT_BRANCH_BY_LEAF);
+    async fn get_branch_by_leaf(&self, leaf_message_id: Uuid) -> Result<(Uuid, Uuid), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_BRANCH_BY_LEAF,
+            StatementProfile::InteractiveRead,
+        );
+
+        let row: BranchByLeafRow = self.client.fetch_one(query, (leaf_message_id,)).await?;
+
+        Ok((row.conversation_id, row.branch_id))
+    }
+
+    /// Get every slug claimed in a conversation
+    async fn get_branch_slugs(&self, conversation_id: Uuid) -> Result<Vec<BranchSlug>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_BRANCH_SLUGS,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
+        let rows: Vec<BranchSlugRow> = self.client.fetch_all(query, (conversation_id,)).await?;
+
+        Ok(rows.into_iter().map(BranchSlugRow::to_slug).collect())
+    }
+
+    /// Get the branch a slug resolves to
+    async fn get_branch_slug(
+        &self,
+        conversation_id: Uuid,
+        slug: &str,
+    ) -> Result<BranchSlug, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_BRANCH_SLUG,
+            StatementProfile::InteractiveRead,
+        );
+
+        let row: BranchSlugRow = self
             .client
-            .session()
-            .query(query, (leaf_message_id,))
+            .fetch_one(query, (conversation_id, slug))
             .await?;
 
-        let row = result
-            .rows
-            .ok_or(DbError::NotFound)?
-            .into_typed::<BranchByLeafRow>()
-            .next()
-            .ok_or(DbError::NotFound)?
-            .map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-
-        Ok((row.conversation_id, row.branch_id))
+        Ok(row.to_slug())
     }
+}
 
+impl BranchRepository {
     // Helper methods for branch_by_leaf index
     async fn insert_branch_by_leaf(
         &self,
@@ -189,24 +247,49 @@ impl BranchRepository {
         conversation_id: Uuid,
         branch_id: Uuid,
     ) -> Result<(), DbError> {
-        let query = Query::new(crate::db::queries::INSERT_BRANCH_BY_LEAF);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_BRANCH_BY_LEAF,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (leaf_message_id, conversation_id, branch_id))
+            .execute(query, (leaf_message_id, conversation_id, branch_id))
             .await?;
 
         Ok(())
     }
 
-    async fn delete_branch_by_leaf(&self, leaf_message_id: Uuid) -> Result<(), DbError> {
-        let query = Query::new(crate::db::queries::DELETE_BRANCH_BY_LEAF);
+    async fn insert_branch_slug(&self, slug: &BranchSlug) -> Result<(), DbError> {
+        let row = BranchSlugRow::from_slug(slug);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_BRANCH_SLUG,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (leaf_message_id,))
+            .execute(
+                query,
+                (
+                    row.conversation_id,
+                    row.slug,
+                    row.branch_id,
+                    row.branch_name,
+                    row.created_at,
+                ),
+            )
             .await?;
 
         Ok(())
     }
+
+    async fn delete_branch_by_leaf(&self, leaf_message_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_BRANCH_BY_LEAF,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (leaf_message_id,)).await?;
+
+        Ok(())
+    }
 }
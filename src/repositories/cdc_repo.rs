@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use scylla::frame::value::CqlTimeuuid;
+
+use super::store::CdcStore;
+use crate::db::{DbClient, DbError, LineageCdcRow, StatementProfile};
+use crate::domain::OutboxEntry;
+
+/// CDC streams bound per query of the CDC log
+const STREAMS_PER_QUERY: usize = 100;
+
+#[derive(Clone)]
+pub struct CdcRepository {
+    client: DbClient,
+}
+
+impl CdcRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+
+    /// Streams of every CDC generation in effect during `(from, to]`
+    async fn get_streams(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Vec<u8>>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CDC_GENERATIONS,
+            StatementProfile::InteractiveRead,
+        );
+        let rows: Vec<(DateTime<Utc>,)> = self.client.fetch_all(query, ()).await?;
+        let mut generations: Vec<DateTime<Utc>> = rows.into_iter().map(|(time,)| time).collect();
+        generations.sort();
+
+        // The generation current at `from`, and those that replaced it before `to`
+        let first = generations
+            .iter()
+            .rposition(|time| *time <= from)
+            .unwrap_or(0);
+        let mut streams = Vec::new();
+        for time in generations[first..].iter().filter(|time| **time <= to) {
+            let query = self.client.statement(
+                crate::db::queries::SELECT_CDC_STREAMS,
+                StatementProfile::InteractiveRead,
+            );
+            let rows: Vec<(Vec<Vec<u8>>,)> = self.client.fetch_all(query, (time,)).await?;
+            streams.extend(rows.into_iter().flat_map(|(ids,)| ids));
+        }
+
+        Ok(streams)
+    }
+}
+
+#[async_trait]
+impl CdcStore for CdcRepository {
+    /// Turn on CDC for the lineage table; a no-op when it already is
+    async fn enable_lineage_cdc(&self) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::ENABLE_LINEAGE_CDC,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, ()).await?;
+
+        Ok(())
+    }
+
+    /// Read the CDC log stream by stream, a batch of streams per query
+    async fn get_lineage_changes(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEntry>, DbError> {
+        let streams = self.get_streams(from, to).await?;
+
+        let mut entries = Vec::new();
+        for chunk in streams.chunks(STREAMS_PER_QUERY) {
+            let query = self.client.statement(
+                crate::db::queries::SELECT_LINEAGE_CDC,
+                StatementProfile::BulkRead,
+            );
+            let rows: Vec<LineageCdcRow> = self.client.fetch_all(query, (chunk, from, to)).await?;
+            entries.extend(rows.into_iter().filter_map(LineageCdcRow::to_outbox_entry));
+        }
+        entries.sort_by_key(|entry| (entry.changed_at, entry.batch_seq_no));
+
+        Ok(entries)
+    }
+
+    /// Write entries into their day's outbox partition
+    async fn insert_outbox_entries(&self, entries: &[OutboxEntry]) -> Result<(), DbError> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = self.client.batch();
+        let mut values_list = Vec::new();
+
+        for entry in entries {
+            batch.append_statement(crate::db::queries::INSERT_OUTBOX);
+            values_list.push((
+                entry.changed_at.format("%Y-%m-%d").to_string(),
+                CqlTimeuuid::from(entry.change_id),
+                entry.batch_seq_no,
+                entry.changed_at,
+                entry.conversation_id,
+                entry.message_id,
+                entry.kind.as_str(),
+            ));
+        }
+
+        self.client.execute_batch(&batch, values_list).await
+    }
+
+    async fn get_checkpoint(&self, consumer: &str) -> Result<Option<DateTime<Utc>>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CDC_CHECKPOINT,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<(Option<DateTime<Utc>>,)>(query, (consumer,))
+            .await
+        {
+            Ok((processed_up_to,)) => Ok(processed_up_to),
+            Err(DbError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn set_checkpoint(
+        &self,
+        consumer: &str,
+        processed_up_to: DateTime<Utc>,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_CDC_CHECKPOINT,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(query, (consumer, processed_up_to))
+            .await?;
+
+        Ok(())
+    }
+}
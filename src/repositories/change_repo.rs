@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use uuid::Uuid;
+
+use super::store::ChangeStore;
+use crate::db::{ChangeRow, DbClient, DbError, StatementProfile};
+use crate::domain::Change;
+
+#[derive(Clone)]
+pub struct ChangeRepository {
+    client: DbClient,
+}
+
+impl ChangeRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ChangeStore for ChangeRepository {
+    /// Append a change to the conversation's change feed
+    async fn insert_change(&self, change: &Change) -> Result<(), DbError> {
+        let row = ChangeRow::from_change(change)?;
+        let query = self.client.statement(
+            crate::db::queries::INSERT_CHANGE,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    row.conversation_id,
+                    row.changed_at,
+                    row.change_id,
+                    row.kind,
+                    row.entity_id,
+                    row.payload,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get changes strictly after the given position (oldest first)
+    async fn get_changes_since(
+        &self,
+        conversation_id: Uuid,
+        since: DateTime<Utc>,
+        after_change_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<Change>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CHANGES_SINCE,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<ChangeRow> = self
+            .client
+            .fetch_all(query, (conversation_id, since, after_change_id, limit))
+            .await?;
+        let changes = rows
+            .into_iter()
+            .map(|row| row.to_change())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(changes)
+    }
+
+    /// Delete the whole change feed of a conversation
+    async fn delete_changes(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CHANGES,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        Ok(())
+    }
+
+    /// Rewrite every change of the conversation with no TTL, overriding the
+    /// table default
+    async fn retain_changes(&self, conversation_id: Uuid) -> Result<usize, DbError> {
+        let select = self.client.statement(
+            crate::db::queries::SELECT_ALL_CHANGES,
+            StatementProfile::BulkRead,
+        );
+        let mut rows = self
+            .client
+            .fetch_stream::<ChangeRow>(select, (conversation_id,))
+            .await?;
+
+        let mut retained = 0;
+        while let Some(row) = rows.try_next().await? {
+            let insert = self.client.statement(
+                crate::db::queries::INSERT_RETAINED_CHANGE,
+                StatementProfile::InteractiveWrite,
+            );
+            self.client
+                .execute(
+                    insert,
+                    (
+                        row.conversation_id,
+                        row.changed_at,
+                        row.change_id,
+                        row.kind,
+                        row.entity_id,
+                        row.payload,
+                    ),
+                )
+                .await?;
+            retained += 1;
+        }
+
+        Ok(retained)
+    }
+}
@@ -0,0 +1,153 @@
+//! Message contents too large to keep in the lineage table, stored in the
+//! object store under their SHA-256 so identical contents are stored once
+
+use ring::digest;
+use std::sync::Arc;
+
+use crate::config::BlobsConfig;
+use crate::db::{DbError, MessageRow};
+use crate::object_store::ObjectStore;
+use crate::utils::hex::hex;
+
+/// Start of a `content_data` value whose content was offloaded; the SHA-256
+/// of the content follows. Contents are JSON objects, so none starts with it.
+pub const BLOB_REF_PREFIX: &str = "blob:sha256:";
+
+/// Moves large message contents out of lineage rows on write and puts them
+/// back on read
+pub struct ContentBlobs {
+    object_store: Arc<dyn ObjectStore>,
+    /// Offload contents larger than this; `None` only reads them back
+    offload_above: Option<usize>,
+}
+
+impl ContentBlobs {
+    pub fn new(object_store: Arc<dyn ObjectStore>, config: &BlobsConfig) -> Self {
+        Self {
+            object_store,
+            offload_above: config
+                .offload_enabled
+                .then_some(config.offload_threshold_bytes),
+        }
+    }
+
+    /// Replace the row's content with a reference if it is over the threshold
+    pub async fn offload(&self, row: &mut MessageRow) -> Result<(), DbError> {
+        if self
+            .offload_above
+            .is_none_or(|threshold| row.content_data.len() <= threshold)
+        {
+            return Ok(());
+        }
+
+        let hash = hex(digest::digest(&digest::SHA256, row.content_data.as_bytes()));
+        let content = std::mem::take(&mut row.content_data).into_bytes();
+        self.object_store
+            .put_object(&blob_key(&hash), "application/json", content)
+            .await?;
+        row.content_data = format!("{}{}", BLOB_REF_PREFIX, hash);
+
+        Ok(())
+    }
+
+    /// Load the row's content back if it was offloaded
+    pub async fn hydrate(&self, row: &mut MessageRow) -> Result<(), DbError> {
+        let Some(hash) = row.blob_hash() else {
+            return Ok(());
+        };
+
+        let content = self.object_store.get_object(&blob_key(hash)).await?;
+        if hex(digest::digest(&digest::SHA256, &content)) != hash {
+            return Err(DbError::RowParse {
+                table: "conversation_lineage",
+                column: "content_data",
+                reason: format!(
+                    "content blob {} of message {} is corrupt",
+                    hash, row.message_id
+                ),
+            });
+        }
+        row.content_data = String::from_utf8(content).map_err(|_| DbError::RowParse {
+            table: "conversation_lineage",
+            column: "content_data",
+            reason: format!(
+                "content blob {} of message {} is not UTF-8",
+                hash, row.message_id
+            ),
+        })?;
+
+        Ok(())
+    }
+}
+
+fn blob_key(hash: &str) -> String {
+    format!("blobs/sha256/{}", hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ContentType, Conversation, TextContent};
+    use crate::object_store::memory::MemoryObjectStore;
+
+    fn row_with_text(text: &str) -> MessageRow {
+        let mut message = Conversation::new("Logs".to_string(), "user_a".to_string()).root_message;
+        message.content = ContentType::Text(TextContent {
+            text: text.to_string(),
+        });
+        MessageRow::from_message(&message).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_large_contents_round_trip_through_the_object_store() {
+        let object_store = Arc::new(MemoryObjectStore::default());
+        let blobs = ContentBlobs::new(
+            object_store.clone(),
+            &BlobsConfig {
+                offload_enabled: true,
+                offload_threshold_bytes: 100,
+            },
+        );
+
+        let mut small = row_with_text("short");
+        blobs.offload(&mut small).await.unwrap();
+        assert!(small.blob_hash().is_none());
+
+        let text = "tool output ".repeat(50);
+        let mut large = row_with_text(&text);
+        let original = large.content_data.clone();
+        blobs.offload(&mut large).await.unwrap();
+        let hash = large.blob_hash().unwrap().to_string();
+        assert_eq!(object_store.objects.lock().unwrap().len(), 1);
+        assert!(large.clone().to_message().is_err());
+
+        // Offloaded contents are read back even once offloading is turned off
+        let reader = ContentBlobs::new(
+            object_store.clone(),
+            &BlobsConfig {
+                offload_enabled: false,
+                offload_threshold_bytes: 100,
+            },
+        );
+        let mut loaded = large.clone();
+        reader.hydrate(&mut loaded).await.unwrap();
+        assert_eq!(loaded.content_data, original);
+        let ContentType::Text(content) = loaded.to_message().unwrap().content else {
+            panic!("expected text");
+        };
+        assert_eq!(content.text, text);
+
+        object_store
+            .objects
+            .lock()
+            .unwrap()
+            .insert(blob_key(&hash), b"{}".to_vec());
+        assert!(matches!(
+            reader.hydrate(&mut large).await,
+            Err(DbError::RowParse {
+                column: "content_data",
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,631 @@
+//! Message contents and event payloads encrypted at rest. Each conversation
+//! has its own AES-256-GCM data key, stored in `conversation_keys` wrapped
+//! by a master key, so a leaked data key exposes one conversation and
+//! rotating the master key only re-wraps data keys, not message rows.
+
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::Utc;
+use futures::TryStreamExt;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use crate::config::EncryptionConfig;
+use crate::db::{ConversationKeyRow, DbClient, DbError, EventRow, MessageRow, StatementProfile};
+
+/// Length of data and master keys
+const KEY_LEN: usize = 32;
+
+/// Data keys fetched at a time while re-wrapping them
+const REWRAP_PAGE_SIZE: i32 = 500;
+
+/// Encrypts rows on write and decrypts them on read
+pub struct ContentCipher {
+    client: DbClient,
+    master_keys: HashMap<String, LessSafeKey>,
+    active_key_id: String,
+    rng: SystemRandom,
+    cache: Mutex<DataKeyCache>,
+}
+
+/// Unwrapped data keys, dropped all at once when full
+struct DataKeyCache {
+    capacity: usize,
+    /// Key new rows of a conversation are encrypted with
+    current: HashMap<Uuid, Uuid>,
+    keys: HashMap<(Uuid, Uuid), Arc<LessSafeKey>>,
+}
+
+impl DataKeyCache {
+    fn insert(&mut self, conversation_id: Uuid, key_id: Uuid, key: Arc<LessSafeKey>) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.keys.len() >= self.capacity {
+            self.keys.clear();
+            self.current.clear();
+        }
+        self.keys.insert((conversation_id, key_id), key);
+    }
+}
+
+impl ContentCipher {
+    /// `None` while no master key is configured
+    pub fn new(client: DbClient, config: &EncryptionConfig) -> Result<Option<Self>, DbError> {
+        if !config.enabled() {
+            return Ok(None);
+        }
+
+        let master_keys = config
+            .master_keys
+            .iter()
+            .map(|(id, key)| {
+                let key = STANDARD
+                    .decode(key)
+                    .ok()
+                    .and_then(|key| aead_key(&key))
+                    .ok_or_else(|| {
+                        DbError::InvalidData(format!("master key `{}` is not a 32-byte key", id))
+                    })?;
+                Ok((id.clone(), key))
+            })
+            .collect::<Result<HashMap<_, _>, DbError>>()?;
+        let active_key_id = config
+            .active_key_id
+            .clone()
+            .filter(|id| master_keys.contains_key(id))
+            .ok_or_else(|| {
+                DbError::InvalidData(
+                    "`encryption.active_key_id` must name one of the master keys".to_string(),
+                )
+            })?;
+
+        Ok(Some(Self {
+            client,
+            master_keys,
+            active_key_id,
+            rng: SystemRandom::new(),
+            cache: Mutex::new(DataKeyCache {
+                capacity: config.key_cache_capacity,
+                current: HashMap::new(),
+                keys: HashMap::new(),
+            }),
+        }))
+    }
+
+    /// Master key data keys are wrapped with from now on
+    pub fn active_key_id(&self) -> &str {
+        &self.active_key_id
+    }
+
+    /// Encrypt the row's content with its conversation's current data key
+    pub async fn encrypt(&self, row: &mut MessageRow) -> Result<(), DbError> {
+        let (key_id, key) = self.current_key(row.conversation_id).await?;
+        seal_message(&self.rng, &key, key_id, row)
+    }
+
+    /// Decrypt the row's content if it was encrypted
+    pub async fn decrypt(&self, row: &mut MessageRow) -> Result<(), DbError> {
+        let Some(key_id) = row.key_id else {
+            return Ok(());
+        };
+
+        let key = self.key(row.conversation_id, key_id).await?;
+        open_message(&key, row)
+    }
+
+    /// Encrypt the event's payload with its conversation's current data key
+    pub async fn encrypt_event(&self, row: &mut EventRow) -> Result<(), DbError> {
+        let (key_id, key) = self.current_key(row.conversation_id).await?;
+        seal_event(&self.rng, &key, key_id, row)
+    }
+
+    /// Decrypt the event's payload if it was encrypted
+    pub async fn decrypt_event(&self, row: &mut EventRow) -> Result<(), DbError> {
+        let Some(key_id) = row.key_id else {
+            return Ok(());
+        };
+
+        let key = self.key(row.conversation_id, key_id).await?;
+        open_event(&key, row)
+    }
+
+    /// Re-wrap every data key not wrapped by the active master key with it.
+    /// Returns how many were re-wrapped.
+    pub async fn rewrap_data_keys(&self) -> Result<usize, DbError> {
+        let active = &self.master_keys[&self.active_key_id];
+        let mut query = self.client.statement(
+            crate::db::queries::SELECT_EVERY_CONVERSATION_KEY,
+            StatementProfile::BulkRead,
+        );
+        query.set_page_size(REWRAP_PAGE_SIZE);
+        let mut rows = self
+            .client
+            .fetch_stream::<ConversationKeyRow>(query, ())
+            .await?;
+
+        let mut rewrapped = 0;
+        while let Some(row) = rows.try_next().await? {
+            if row.master_key_id == self.active_key_id {
+                continue;
+            }
+
+            let data_key = self.unwrap_row(&row)?;
+            let wrapped_key = wrap(
+                &self.rng,
+                active,
+                row.conversation_id,
+                row.key_id,
+                &data_key,
+            )?;
+            let query = self.client.statement(
+                crate::db::queries::UPDATE_CONVERSATION_KEY_WRAPPING,
+                StatementProfile::BulkWrite,
+            );
+            self.client
+                .execute(
+                    query,
+                    (
+                        &self.active_key_id,
+                        wrapped_key,
+                        row.conversation_id,
+                        row.key_id,
+                    ),
+                )
+                .await?;
+            rewrapped += 1;
+        }
+
+        Ok(rewrapped)
+    }
+
+    /// Forget the data keys of a conversation being deleted, leaving
+    /// whatever copies of its rows remain unreadable
+    pub async fn delete_keys(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CONVERSATION_KEYS,
+            StatementProfile::InteractiveWrite,
+        );
+        self.client.execute(query, (conversation_id,)).await?;
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.current.remove(&conversation_id);
+        cache
+            .keys
+            .retain(|(cached_conversation_id, _), _| *cached_conversation_id != conversation_id);
+
+        Ok(())
+    }
+
+    /// The newest data key of a conversation, created on its first write.
+    /// Writers racing to create the first one may each create one; every
+    /// row names the key it used, so all of them stay readable.
+    async fn current_key(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<(Uuid, Arc<LessSafeKey>), DbError> {
+        {
+            let cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(key_id) = cache.current.get(&conversation_id)
+                && let Some(key) = cache.keys.get(&(conversation_id, *key_id))
+            {
+                return Ok((*key_id, key.clone()));
+            }
+        }
+
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CURRENT_CONVERSATION_KEY,
+            StatementProfile::InteractiveRead,
+        );
+        let (key_id, key) = match self
+            .client
+            .fetch_one::<ConversationKeyRow>(query, (conversation_id,))
+            .await
+        {
+            Ok(row) => (row.key_id, Arc::new(self.unwrap_key(&row)?)),
+            Err(DbError::NotFound) => self.create_key(conversation_id).await?,
+            Err(e) => return Err(e),
+        };
+
+        let mut cache = self.cache.lock().unwrap_or_else(|e| e.into_inner());
+        cache.insert(conversation_id, key_id, key.clone());
+        cache.current.insert(conversation_id, key_id);
+
+        Ok((key_id, key))
+    }
+
+    async fn create_key(&self, conversation_id: Uuid) -> Result<(Uuid, Arc<LessSafeKey>), DbError> {
+        let mut data_key = [0u8; KEY_LEN];
+        self.rng
+            .fill(&mut data_key)
+            .map_err(|_| DbError::InvalidData("no randomness for a data key".to_string()))?;
+        let key_id = Uuid::now_v7();
+        let wrapped_key = wrap(
+            &self.rng,
+            &self.master_keys[&self.active_key_id],
+            conversation_id,
+            key_id,
+            &data_key,
+        )?;
+
+        let query = self.client.statement(
+            crate::db::queries::INSERT_CONVERSATION_KEY,
+            StatementProfile::InteractiveWrite,
+        );
+        self.client
+            .execute(
+                query,
+                (
+                    conversation_id,
+                    key_id,
+                    &self.active_key_id,
+                    wrapped_key,
+                    Utc::now(),
+                ),
+            )
+            .await?;
+
+        let key = aead_key(&data_key).expect("data keys are KEY_LEN bytes");
+        Ok((key_id, Arc::new(key)))
+    }
+
+    /// A data key rows name, whether or not it is still the current one
+    async fn key(&self, conversation_id: Uuid, key_id: Uuid) -> Result<Arc<LessSafeKey>, DbError> {
+        if let Some(key) = self
+            .cache
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys
+            .get(&(conversation_id, key_id))
+        {
+            return Ok(key.clone());
+        }
+
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CONVERSATION_KEY,
+            StatementProfile::InteractiveRead,
+        );
+        let row: ConversationKeyRow = match self
+            .client
+            .fetch_one(query, (conversation_id, key_id))
+            .await
+        {
+            Err(DbError::NotFound) => {
+                return Err(key_parse(format!(
+                    "data key {} of conversation {} does not exist",
+                    key_id, conversation_id
+                )));
+            }
+            result => result?,
+        };
+        let key = Arc::new(self.unwrap_key(&row)?);
+
+        self.cache.lock().unwrap_or_else(|e| e.into_inner()).insert(
+            conversation_id,
+            key_id,
+            key.clone(),
+        );
+
+        Ok(key)
+    }
+
+    fn unwrap_key(&self, row: &ConversationKeyRow) -> Result<LessSafeKey, DbError> {
+        let data_key = self.unwrap_row(row)?;
+        aead_key(&data_key).ok_or_else(|| {
+            key_parse(format!(
+                "data key {} of conversation {} is not {} bytes",
+                row.key_id, row.conversation_id, KEY_LEN
+            ))
+        })
+    }
+
+    fn unwrap_row(&self, row: &ConversationKeyRow) -> Result<Vec<u8>, DbError> {
+        let master_key = self.master_keys.get(&row.master_key_id).ok_or_else(|| {
+            key_parse(format!(
+                "data key {} of conversation {} is wrapped by master key `{}`, which is not configured",
+                row.key_id, row.conversation_id, row.master_key_id
+            ))
+        })?;
+
+        unwrap(
+            master_key,
+            row.conversation_id,
+            row.key_id,
+            &row.wrapped_key,
+        )
+    }
+}
+
+/// `None` unless `bytes` is `KEY_LEN` long
+fn aead_key(bytes: &[u8]) -> Option<LessSafeKey> {
+    UnboundKey::new(&AES_256_GCM, bytes)
+        .ok()
+        .map(LessSafeKey::new)
+}
+
+fn key_parse(reason: String) -> DbError {
+    DbError::RowParse {
+        table: "conversation_keys",
+        column: "wrapped_key",
+        reason,
+    }
+}
+
+/// Binds each ciphertext to the row it was written for, so one can't be
+/// copied into another
+fn associated_data(conversation_id: Uuid, row_id: Uuid) -> [u8; 32] {
+    let mut aad = [0u8; 32];
+    aad[..16].copy_from_slice(conversation_id.as_bytes());
+    aad[16..].copy_from_slice(row_id.as_bytes());
+    aad
+}
+
+/// A random nonce followed by the ciphertext and its tag
+fn seal(
+    rng: &SystemRandom,
+    key: &LessSafeKey,
+    aad: &[u8],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, DbError> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut nonce)
+        .map_err(|_| DbError::InvalidData("no randomness for a nonce".to_string()))?;
+
+    let mut sealed = Vec::with_capacity(NONCE_LEN + plaintext.len() + AES_256_GCM.tag_len());
+    sealed.extend_from_slice(&nonce);
+    sealed.extend_from_slice(plaintext);
+    let mut in_out = sealed.split_off(NONCE_LEN);
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad),
+        &mut in_out,
+    )
+    .map_err(|_| DbError::InvalidData("plaintext too long to encrypt".to_string()))?;
+    sealed.append(&mut in_out);
+
+    Ok(sealed)
+}
+
+/// The plaintext of `seal`'s output, `None` if it was not sealed with this
+/// key and `aad` or has been altered
+fn open(key: &LessSafeKey, aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(nonce).ok()?;
+    let mut in_out = ciphertext.to_vec();
+    let plaintext_len = key
+        .open_in_place(nonce, Aad::from(aad), &mut in_out)
+        .ok()?
+        .len();
+    in_out.truncate(plaintext_len);
+
+    Some(in_out)
+}
+
+fn wrap(
+    rng: &SystemRandom,
+    master_key: &LessSafeKey,
+    conversation_id: Uuid,
+    key_id: Uuid,
+    data_key: &[u8],
+) -> Result<Vec<u8>, DbError> {
+    seal(
+        rng,
+        master_key,
+        &associated_data(conversation_id, key_id),
+        data_key,
+    )
+}
+
+fn unwrap(
+    master_key: &LessSafeKey,
+    conversation_id: Uuid,
+    key_id: Uuid,
+    wrapped_key: &[u8],
+) -> Result<Vec<u8>, DbError> {
+    open(
+        master_key,
+        &associated_data(conversation_id, key_id),
+        wrapped_key,
+    )
+    .ok_or_else(|| {
+        key_parse(format!(
+            "data key {} of conversation {} does not unwrap with its master key",
+            key_id, conversation_id
+        ))
+    })
+}
+
+/// The plaintext hash would let short contents be guessed, so it is dropped
+fn seal_message(
+    rng: &SystemRandom,
+    key: &LessSafeKey,
+    key_id: Uuid,
+    row: &mut MessageRow,
+) -> Result<(), DbError> {
+    let sealed = seal(
+        rng,
+        key,
+        &associated_data(row.conversation_id, row.message_id),
+        row.content_data.as_bytes(),
+    )?;
+    row.content_data = STANDARD.encode(sealed);
+    row.content_hash = None;
+    row.key_id = Some(key_id);
+
+    Ok(())
+}
+
+fn open_message(key: &LessSafeKey, row: &mut MessageRow) -> Result<(), DbError> {
+    let content = STANDARD
+        .decode(&row.content_data)
+        .ok()
+        .and_then(|sealed| {
+            open(
+                key,
+                &associated_data(row.conversation_id, row.message_id),
+                &sealed,
+            )
+        })
+        .and_then(|content| String::from_utf8(content).ok())
+        .ok_or_else(|| DbError::RowParse {
+            table: "conversation_lineage",
+            column: "content_data",
+            reason: format!("content of message {} does not decrypt", row.message_id),
+        })?;
+    row.content_data = content;
+    row.key_id = None;
+
+    Ok(())
+}
+
+fn seal_event(
+    rng: &SystemRandom,
+    key: &LessSafeKey,
+    key_id: Uuid,
+    row: &mut EventRow,
+) -> Result<(), DbError> {
+    let sealed = seal(
+        rng,
+        key,
+        &associated_data(row.conversation_id, row.event_id),
+        row.payload.as_bytes(),
+    )?;
+    row.payload = STANDARD.encode(sealed);
+    row.key_id = Some(key_id);
+
+    Ok(())
+}
+
+fn open_event(key: &LessSafeKey, row: &mut EventRow) -> Result<(), DbError> {
+    let payload = STANDARD
+        .decode(&row.payload)
+        .ok()
+        .and_then(|sealed| {
+            open(
+                key,
+                &associated_data(row.conversation_id, row.event_id),
+                &sealed,
+            )
+        })
+        .and_then(|payload| String::from_utf8(payload).ok())
+        .ok_or_else(|| DbError::RowParse {
+            table: "conversation_events",
+            column: "payload",
+            reason: format!("payload of event {} does not decrypt", row.event_id),
+        })?;
+    row.payload = payload;
+    row.key_id = None;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ContentType, Conversation, ConversationEvent, EventKind, TextContent};
+
+    fn key(byte: u8) -> LessSafeKey {
+        aead_key(&[byte; KEY_LEN]).unwrap()
+    }
+
+    #[test]
+    fn test_contents_round_trip_and_stay_bound_to_their_row() {
+        let rng = SystemRandom::new();
+        let data_key = key(1);
+        let key_id = Uuid::now_v7();
+        let mut message = Conversation::new("Notes".to_string(), "user_a".to_string()).root_message;
+        message.content = ContentType::Text(TextContent {
+            text: "my account number is 1234".to_string(),
+        });
+        let plain = MessageRow::from_message(&message).unwrap();
+
+        let mut row = plain.clone();
+        seal_message(&rng, &data_key, key_id, &mut row).unwrap();
+        assert_eq!(row.key_id, Some(key_id));
+        assert!(row.content_hash.is_none());
+        assert!(!row.content_data.contains("1234"));
+        assert!(row.clone().to_message().is_err());
+
+        // Each write draws a fresh nonce
+        let mut again = plain.clone();
+        seal_message(&rng, &data_key, key_id, &mut again).unwrap();
+        assert_ne!(again.content_data, row.content_data);
+
+        let mut opened = row.clone();
+        open_message(&data_key, &mut opened).unwrap();
+        assert_eq!(opened.content_data, plain.content_data);
+        let ContentType::Text(content) = opened.to_message().unwrap().content else {
+            panic!("expected text");
+        };
+        assert_eq!(content.text, "my account number is 1234");
+
+        // Another conversation's key, or the ciphertext of another row, fails
+        assert!(open_message(&key(2), &mut row.clone()).is_err());
+        let mut moved = row.clone();
+        moved.message_id = Uuid::now_v7();
+        assert!(matches!(
+            open_message(&data_key, &mut moved),
+            Err(DbError::RowParse {
+                column: "content_data",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_event_payloads_round_trip() {
+        let rng = SystemRandom::new();
+        let data_key = key(3);
+        let event = ConversationEvent::new(
+            Uuid::now_v7(),
+            EventKind::MetadataUpdated,
+            &serde_json::json!({"title": "Salary review"}),
+        )
+        .unwrap();
+        let plain = EventRow::from_event(&event).unwrap();
+
+        let mut row = plain.clone();
+        seal_event(&rng, &data_key, Uuid::now_v7(), &mut row).unwrap();
+        assert!(!row.payload.contains("Salary"));
+        assert!(row.clone().to_event().is_err());
+
+        open_event(&data_key, &mut row).unwrap();
+        assert_eq!(row.payload, plain.payload);
+        assert_eq!(row.to_event().unwrap().payload, event.payload);
+    }
+
+    #[test]
+    fn test_data_keys_unwrap_only_with_their_master_key() {
+        let rng = SystemRandom::new();
+        let (conversation_id, key_id) = (Uuid::now_v7(), Uuid::now_v7());
+        let data_key = [7u8; KEY_LEN];
+
+        let wrapped = wrap(&rng, &key(10), conversation_id, key_id, &data_key).unwrap();
+        assert_eq!(
+            unwrap(&key(10), conversation_id, key_id, &wrapped).unwrap(),
+            data_key
+        );
+        assert!(unwrap(&key(11), conversation_id, key_id, &wrapped).is_err());
+        assert!(unwrap(&key(10), Uuid::now_v7(), key_id, &wrapped).is_err());
+
+        // Re-wrapping changes the wrapping, not the data key
+        let rewrapped = wrap(
+            &rng,
+            &key(11),
+            conversation_id,
+            key_id,
+            &unwrap(&key(10), conversation_id, key_id, &wrapped).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            unwrap(&key(11), conversation_id, key_id, &rewrapped).unwrap(),
+            data_key
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Sequence numbers of conversation events. Each conversation has one row
+//! holding the last number handed out; writers reserve the next ones with a
+//! lightweight transaction, so numbers increase in the order events are
+//! written, whichever instance writes them.
+
+use scylla::QueryResult;
+use scylla::frame::response::result::CqlValue;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::db::{DbClient, DbError, EventRow, StatementProfile};
+use crate::domain::ConversationEvent;
+
+/// Reservations that lose to concurrent writers this many times in a row
+/// give up
+const MAX_ATTEMPTS: usize = 32;
+
+/// Rows of `events`, numbered after the events already written to their
+/// conversations, in the order given
+pub(crate) async fn numbered_event_rows(
+    client: &DbClient,
+    events: &[ConversationEvent],
+) -> Result<Vec<EventRow>, DbError> {
+    let mut counts: Vec<(Uuid, i64)> = Vec::new();
+    for event in events {
+        match counts
+            .iter_mut()
+            .find(|(id, _)| *id == event.conversation_id)
+        {
+            Some((_, count)) => *count += 1,
+            None => counts.push((event.conversation_id, 1)),
+        }
+    }
+    let mut next: HashMap<Uuid, i64> = HashMap::with_capacity(counts.len());
+    for (conversation_id, count) in counts {
+        next.insert(
+            conversation_id,
+            reserve(client, conversation_id, count).await?,
+        );
+    }
+
+    events
+        .iter()
+        .map(|event| {
+            let mut row = EventRow::from_event(event)?;
+            let seq = next
+                .get_mut(&event.conversation_id)
+                .expect("reserved above");
+            row.seq = *seq;
+            *seq += 1;
+            Ok(row)
+        })
+        .collect()
+}
+
+/// Forget the numbers handed out for a conversation being deleted
+pub(crate) async fn delete(client: &DbClient, conversation_id: Uuid) -> Result<(), DbError> {
+    let query = client.statement(
+        crate::db::queries::DELETE_EVENT_SEQ,
+        StatementProfile::InteractiveWrite,
+    );
+    client.execute(query, (conversation_id,)).await?;
+
+    Ok(())
+}
+
+/// Reserve `count` numbers and return the first
+async fn reserve(client: &DbClient, conversation_id: Uuid, count: i64) -> Result<i64, DbError> {
+    let query = client.statement(
+        crate::db::queries::SELECT_EVENT_SEQ,
+        StatementProfile::InteractiveRead,
+    );
+    let mut last = match client
+        .fetch_one::<(Option<i64>,)>(query, (conversation_id,))
+        .await
+    {
+        Ok((last,)) => last.unwrap_or_default(),
+        // Logs written before the counter existed were numbered by the clock;
+        // carry on after their last event
+        Err(DbError::NotFound) => {
+            let last = last_written_seq(client, conversation_id).await?;
+            let query = client.statement(
+                crate::db::queries::INSERT_EVENT_SEQ,
+                StatementProfile::InteractiveWrite,
+            );
+            let result = client
+                .execute(query, (conversation_id, last + count))
+                .await?;
+            match outcome(&result)? {
+                None => return Ok(last + 1),
+                Some(current) => current,
+            }
+        }
+        Err(e) => return Err(e),
+    };
+
+    for _ in 0..MAX_ATTEMPTS {
+        let query = client.statement(
+            crate::db::queries::UPDATE_EVENT_SEQ,
+            StatementProfile::InteractiveWrite,
+        );
+        let result = client
+            .execute(query, (last + count, conversation_id, last))
+            .await?;
+        match outcome(&result)? {
+            None => return Ok(last + 1),
+            Some(current) => last = current,
+        }
+    }
+
+    Err(DbError::Conflict(format!(
+        "Too many concurrent writes to the event log of conversation {}",
+        conversation_id
+    )))
+}
+
+/// Sequence number of the last event written to a conversation, or 0
+async fn last_written_seq(client: &DbClient, conversation_id: Uuid) -> Result<i64, DbError> {
+    let query = client.statement(
+        crate::db::queries::SELECT_LAST_EVENT_SEQ,
+        StatementProfile::InteractiveRead,
+    );
+    match client.fetch_one::<(i64,)>(query, (conversation_id,)).await {
+        Ok((seq,)) => Ok(seq),
+        Err(DbError::NotFound) => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+/// `None` if the transaction applied, otherwise the `last_seq` it found
+fn outcome(result: &QueryResult) -> Result<Option<i64>, DbError> {
+    let invalid = || DbError::InvalidData("Unexpected lightweight transaction result".to_string());
+    let row = result
+        .rows
+        .as_ref()
+        .and_then(|rows| rows.first())
+        .ok_or_else(invalid)?;
+    let column = |name: &str| {
+        result
+            .get_column_spec(name)
+            .and_then(|(index, _)| row.columns.get(index))
+            .and_then(Option::as_ref)
+    };
+
+    match column("[applied]") {
+        Some(CqlValue::Boolean(true)) => Ok(None),
+        Some(CqlValue::Boolean(false)) => match column("last_seq") {
+            Some(CqlValue::BigInt(last)) => Ok(Some(*last)),
+            _ => Ok(Some(0)),
+        },
+        _ => Err(invalid()),
+    }
+}
@@ -0,0 +1,114 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::store::ExportStore;
+use crate::db::{DbClient, DbError, ExportRow, StatementProfile};
+use crate::domain::ConversationExport;
+
+#[derive(Clone)]
+pub struct ExportRepository {
+    client: DbClient,
+}
+
+impl ExportRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ExportStore for ExportRepository {
+    /// Record a requested export, leaving its result alone
+    async fn insert_export(&self, export: &ConversationExport) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::INSERT_CONVERSATION_EXPORT,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    export.conversation_id,
+                    export.created_at,
+                    export.export_id,
+                    &export.format,
+                    &export.requested_by,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Record where a finished export was written
+    async fn complete_export(
+        &self,
+        conversation_id: Uuid,
+        created_at: DateTime<Utc>,
+        export_id: Uuid,
+        object_key: &str,
+        size_bytes: i64,
+        completed_at: DateTime<Utc>,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::COMPLETE_CONVERSATION_EXPORT,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    object_key,
+                    size_bytes,
+                    completed_at,
+                    conversation_id,
+                    created_at,
+                    export_id,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_export(
+        &self,
+        conversation_id: Uuid,
+        created_at: DateTime<Utc>,
+        export_id: Uuid,
+    ) -> Result<ConversationExport, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CONVERSATION_EXPORT,
+            StatementProfile::InteractiveRead,
+        );
+
+        let row: ExportRow = self
+            .client
+            .fetch_one(query, (conversation_id, created_at, export_id))
+            .await?;
+
+        Ok(row.to_export())
+    }
+
+    /// Get a conversation's most recent exports, newest first
+    async fn get_exports(
+        &self,
+        conversation_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<ConversationExport>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CONVERSATION_EXPORTS,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<ExportRow> = self
+            .client
+            .fetch_all(query, (conversation_id, limit))
+            .await?;
+
+        Ok(rows.into_iter().map(ExportRow::to_export).collect())
+    }
+}
@@ -0,0 +1,138 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use scylla::frame::response::result::CqlValue;
+use uuid::Uuid;
+
+use super::store::ImageStore;
+use crate::db::{DbClient, DbError, ImageRefRow, PendingImageDeletionRow, StatementProfile};
+
+#[derive(Clone)]
+pub struct ImageRepository {
+    client: DbClient,
+}
+
+impl ImageRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl ImageStore for ImageRepository {
+    /// Write both directions of the references in one logged batch
+    async fn add_image_refs(
+        &self,
+        conversation_id: Uuid,
+        storage_keys: &[String],
+    ) -> Result<(), DbError> {
+        if storage_keys.is_empty() {
+            return Ok(());
+        }
+
+        let mut batch = self.client.logged_batch();
+        let mut values_list: Vec<Vec<CqlValue>> = Vec::new();
+
+        for storage_key in storage_keys {
+            batch.append_statement(crate::db::queries::INSERT_CONVERSATION_IMAGE);
+            values_list.push(vec![
+                CqlValue::Uuid(conversation_id),
+                CqlValue::Text(storage_key.clone()),
+            ]);
+
+            batch.append_statement(crate::db::queries::INSERT_IMAGE_REFERENCE);
+            values_list.push(vec![
+                CqlValue::Text(storage_key.clone()),
+                CqlValue::Uuid(conversation_id),
+            ]);
+        }
+
+        self.client.execute_batch(&batch, values_list).await
+    }
+
+    /// Get the keys a conversation references (one partition)
+    async fn get_image_keys(&self, conversation_id: Uuid) -> Result<Vec<String>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CONVERSATION_IMAGES,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<ImageRefRow> = self.client.fetch_all(query, (conversation_id,)).await?;
+
+        Ok(rows.into_iter().map(|row| row.storage_key).collect())
+    }
+
+    /// Drop the reverse references first and the conversation's partition
+    /// last, so an interrupted call can be repeated
+    async fn delete_image_refs(
+        &self,
+        conversation_id: Uuid,
+        storage_keys: &[String],
+    ) -> Result<(), DbError> {
+        for storage_key in storage_keys {
+            let query = self.client.statement(
+                crate::db::queries::DELETE_IMAGE_REFERENCE,
+                StatementProfile::InteractiveWrite,
+            );
+            self.client
+                .execute(query, (storage_key, conversation_id))
+                .await?;
+        }
+
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CONVERSATION_IMAGES,
+            StatementProfile::InteractiveWrite,
+        );
+        self.client.execute(query, (conversation_id,)).await?;
+
+        Ok(())
+    }
+
+    /// Check whether a key's partition has any row left
+    async fn is_image_referenced(&self, storage_key: &str) -> Result<bool, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_IMAGE_REFERENCE,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<ImageRefRow> = self.client.fetch_all(query, (storage_key,)).await?;
+
+        Ok(!rows.is_empty())
+    }
+
+    async fn queue_image_deletions(&self, storage_keys: &[String]) -> Result<(), DbError> {
+        let queued_at = Utc::now();
+        for storage_key in storage_keys {
+            let query = self.client.statement(
+                crate::db::queries::INSERT_PENDING_IMAGE_DELETION,
+                StatementProfile::InteractiveWrite,
+            );
+            self.client.execute(query, (storage_key, queued_at)).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the first `limit` queued keys in token order; the queue is expected
+    /// to stay small, as the GC task drains it
+    async fn get_pending_image_deletions(&self, limit: i32) -> Result<Vec<String>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_PENDING_IMAGE_DELETIONS,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<PendingImageDeletionRow> = self.client.fetch_all(query, (limit,)).await?;
+
+        Ok(rows.into_iter().map(|row| row.storage_key).collect())
+    }
+
+    async fn remove_pending_image_deletion(&self, storage_key: &str) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_PENDING_IMAGE_DELETION,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (storage_key,)).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,131 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use super::store::JobStore;
+use crate::db::{DbClient, DbError, JobIdRow, JobRow, StatementProfile};
+use crate::domain::Job;
+
+#[derive(Clone)]
+pub struct JobRepository {
+    client: DbClient,
+}
+
+impl JobRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+
+    async fn get_jobs(&self, job_ids: Vec<Uuid>) -> Result<Vec<Job>, DbError> {
+        let mut jobs = Vec::with_capacity(job_ids.len());
+        for job_id in job_ids {
+            match self.get_job(job_id).await {
+                Ok(job) => jobs.push(job),
+                // The index outlived the expired job row
+                Err(DbError::NotFound) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(jobs)
+    }
+}
+
+#[async_trait]
+impl JobStore for JobRepository {
+    /// Write a job, keeping the per-user and running indexes in step
+    async fn save_job(&self, job: &Job) -> Result<(), DbError> {
+        let row = JobRow::from_job(job)?;
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_JOB,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    row.job_id,
+                    row.kind,
+                    row.user_id,
+                    row.status,
+                    row.params,
+                    row.progress,
+                    row.started_at,
+                    row.updated_at,
+                    row.finished_at,
+                    row.error,
+                ),
+            )
+            .await?;
+
+        let query = self.client.statement(
+            crate::db::queries::INSERT_JOB_BY_USER,
+            StatementProfile::InteractiveWrite,
+        );
+        self.client
+            .execute(query, (&job.user_id, job.started_at, job.job_id))
+            .await?;
+
+        let cql = if job.is_finished() {
+            crate::db::queries::DELETE_RUNNING_JOB
+        } else {
+            crate::db::queries::INSERT_RUNNING_JOB
+        };
+        let query = self
+            .client
+            .statement(cql, StatementProfile::InteractiveWrite);
+        self.client.execute(query, (job.job_id,)).await?;
+
+        Ok(())
+    }
+
+    /// Get a job by ID
+    async fn get_job(&self, job_id: Uuid) -> Result<Job, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_JOB,
+            StatementProfile::InteractiveRead,
+        );
+
+        let row: JobRow = self.client.fetch_one(query, (job_id,)).await?;
+
+        row.to_job()
+    }
+
+    /// Get a user's most recent jobs, newest first
+    async fn get_jobs_for_user(&self, user_id: &str, limit: i32) -> Result<Vec<Job>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_JOBS_BY_USER,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<JobIdRow> = self.client.fetch_all(query, (user_id, limit)).await?;
+
+        self.get_jobs(rows.into_iter().map(|row| row.job_id).collect())
+            .await
+    }
+
+    /// Get every job still marked as running
+    async fn get_running_jobs(&self) -> Result<Vec<Job>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_RUNNING_JOBS,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<JobIdRow> = self.client.fetch_all(query, ()).await?;
+
+        self.get_jobs(rows.into_iter().map(|row| row.job_id).collect())
+            .await
+    }
+
+    /// Flag a job for cancellation
+    async fn request_cancel(&self, job_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::REQUEST_JOB_CANCEL,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (job_id,)).await?;
+
+        Ok(())
+    }
+}
@@ -1,29 +1,122 @@
-use scylla::IntoTypedRows;
-use scylla::query::Query;
+use async_trait::async_trait;
+use futures::stream::{BoxStream, StreamExt};
+use scylla::frame::response::result::CqlValue;
+use scylla::frame::value::Counter;
+use std::sync::Arc;
 use uuid::Uuid;
 
-use crate::db::{DbClient, DbError, MessageRow};
-use crate::domain::Message;
+use super::content_blobs::ContentBlobs;
+use super::content_cipher::ContentCipher;
+use super::event_seqs;
+use super::store::LineageStore;
+use crate::config::BatchStrategy;
+use crate::db::{
+    ConversationTitleRow, DbClient, DbError, EventRow, ForkLinkRow, ForkProgressRow, LegalHoldRow,
+    LockRow, MessageRow, StatementProfile,
+};
+use crate::domain::{ConversationEvent, ConversationLock, ForkProgress, LegalHold, Message};
 
 #[derive(Clone)]
 pub struct LineageRepository {
     client: DbClient,
+    /// Where large contents are offloaded; without it, offloaded rows can't be read
+    blobs: Option<Arc<ContentBlobs>>,
+    /// Encrypts contents and event payloads; without it, encrypted rows can't be read
+    cipher: Option<Arc<ContentCipher>>,
 }
 
 impl LineageRepository {
     pub fn new(client: DbClient) -> Self {
-        Self { client }
+        Self {
+            client,
+            blobs: None,
+            cipher: None,
+        }
+    }
+
+    pub fn with_blobs(mut self, blobs: Arc<ContentBlobs>) -> Self {
+        self.blobs = Some(blobs);
+        self
+    }
+
+    pub fn with_cipher(mut self, cipher: Arc<ContentCipher>) -> Self {
+        self.cipher = Some(cipher);
+        self
     }
 
+    /// The row to write for a message, its content encrypted if enabled and
+    /// then offloaded if large, so the object store only sees ciphertext
+    async fn to_row(&self, message: &Message) -> Result<MessageRow, DbError> {
+        let mut row = MessageRow::from_message(message)?;
+        if let Some(cipher) = &self.cipher {
+            cipher.encrypt(&mut row).await?;
+        }
+        if let Some(blobs) = &self.blobs {
+            blobs.offload(&mut row).await?;
+        }
+
+        Ok(row)
+    }
+
+    /// Insert statements and values of messages and events
+    async fn message_and_event_rows(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<Vec<(&'static str, Vec<Option<CqlValue>>)>, DbError> {
+        let mut rows = Vec::with_capacity(messages.len() + events.len());
+        for message in messages {
+            let row = self.to_row(message).await?;
+            rows.push((crate::db::queries::INSERT_MESSAGE, row.insert_values()));
+        }
+        for mut row in event_seqs::numbered_event_rows(&self.client, events).await? {
+            if let Some(cipher) = &self.cipher {
+                cipher.encrypt_event(&mut row).await?;
+            }
+            rows.push((crate::db::queries::INSERT_EVENT, row.insert_values()));
+        }
+
+        Ok(rows)
+    }
+
+    async fn to_messages(&self, rows: Vec<MessageRow>) -> Result<Vec<Message>, DbError> {
+        let mut messages = Vec::with_capacity(rows.len());
+        for row in rows {
+            messages.push(to_message(self.blobs.as_deref(), self.cipher.as_deref(), row).await?);
+        }
+
+        Ok(messages)
+    }
+
+    /// `rows` as messages, loading offloaded contents as they go by
+    fn stream_messages(
+        &self,
+        rows: BoxStream<'static, Result<MessageRow, DbError>>,
+    ) -> BoxStream<'static, Result<Message, DbError>> {
+        let blobs = self.blobs.clone();
+        let cipher = self.cipher.clone();
+        rows.then(move |row| {
+            let blobs = blobs.clone();
+            let cipher = cipher.clone();
+            async move { to_message(blobs.as_deref(), cipher.as_deref(), row?).await }
+        })
+        .boxed()
+    }
+}
+
+#[async_trait]
+impl LineageStore for LineageRepository {
     /// Insert a new message into the conversation lineage
-    pub async fn insert_message(&self, message: &Message) -> Result<(), DbError> {
-        let row = MessageRow::from_message(message).map_err(DbError::SerializationError)?;
+    async fn insert_message(&self, message: &Message) -> Result<(), DbError> {
+        let row = self.to_row(message).await?;
 
-        let query = Query::new(crate::db::queries::INSERT_MESSAGE);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_MESSAGE,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(
+            .execute(
                 query,
                 (
                     row.conversation_id,
@@ -36,6 +129,8 @@ impl LineageRepository {
                     row.lineage,
                     row.created_at,
                     row.created_by,
+                    row.content_hash,
+                    row.key_id,
                 ),
             )
             .await?;
@@ -44,59 +139,126 @@ impl LineageRepository {
     }
 
     /// Get a specific message by conversation_id and message_id
-    pub async fn get_message(
+    async fn get_message(
         &self,
         conversation_id: Uuid,
         message_id: Uuid,
     ) -> Result<Message, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_MESSAGE);
+        let query = self.client.statement(
+            crate::db::queries::SELECT_MESSAGE,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
+        let row: MessageRow = self
             .client
-            .session()
-            .query(query, (conversation_id, message_id))
+            .fetch_one(query, (conversation_id, message_id))
             .await?;
 
-        let row = result
-            .rows
-            .ok_or(DbError::NotFound)?
-            .into_typed::<MessageRow>()
-            .next()
-            .ok_or(DbError::NotFound)?
-            .map_err(|e| DbError::InvalidData(format!("Failed to parse message row: {}", e)))?;
-
-        row.to_message().map_err(DbError::InvalidData)
+        to_message(self.blobs.as_deref(), self.cipher.as_deref(), row).await
     }
 
     /// Get all child messages of a given message (branches from this point)
-    pub async fn get_children(
+    async fn get_children(
         &self,
         conversation_id: Uuid,
         parent_message_id: Uuid,
     ) -> Result<Vec<Message>, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_MESSAGE_CHILDREN);
+        let query = self.client.statement(
+            crate::db::queries::SELECT_MESSAGE_CHILDREN,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
+        let rows: Vec<MessageRow> = self
             .client
-            .session()
-            .query(query, (conversation_id, parent_message_id))
+            .fetch_all(query, (conversation_id, parent_message_id))
             .await?;
 
-        let rows = result.rows.unwrap_or_default();
-        let mut messages = Vec::new();
+        self.to_messages(rows).await
+    }
+
+    /// Counted server-side, but still reads the whole partition
+    async fn count_messages(&self, conversation_id: Uuid) -> Result<usize, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::COUNT_MESSAGES,
+            StatementProfile::InteractiveRead,
+        );
 
-        for row in rows.into_typed::<MessageRow>() {
-            let row =
-                row.map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-            let message = row.to_message().map_err(DbError::InvalidData)?;
-            messages.push(message);
+        let (count,): (i64,) = self.client.fetch_one(query, (conversation_id,)).await?;
+
+        Ok(count as usize)
+    }
+
+    async fn get_message_count(&self, conversation_id: Uuid) -> Result<usize, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_MESSAGE_COUNT,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<(Option<Counter>,)>(query, (conversation_id,))
+            .await
+        {
+            Ok((count,)) => Ok(count.map_or(0, |c| c.0.max(0) as usize)),
+            Err(DbError::NotFound) => Ok(0),
+            Err(e) => Err(e),
         }
+    }
 
-        Ok(messages)
+    async fn get_reply_count(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<usize, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_REPLY_COUNT,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<(Option<Counter>,)>(query, (conversation_id, message_id))
+            .await
+        {
+            Ok((count,)) => Ok(count.map_or(0, |c| c.0.max(0) as usize)),
+            Err(DbError::NotFound) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Both counter tables in one counter batch
+    async fn add_message_counts(
+        &self,
+        conversation_id: Uuid,
+        messages: i64,
+        replies: &[(Uuid, i64)],
+    ) -> Result<(), DbError> {
+        let mut batch = self.client.counter_batch();
+        let mut values_list: Vec<Vec<CqlValue>> = Vec::new();
+        if messages != 0 {
+            batch.append_statement(crate::db::queries::INCREMENT_MESSAGE_COUNT);
+            values_list.push(vec![
+                CqlValue::Counter(Counter(messages)),
+                CqlValue::Uuid(conversation_id),
+            ]);
+        }
+        for &(message_id, count) in replies.iter().filter(|(_, count)| *count != 0) {
+            batch.append_statement(crate::db::queries::INCREMENT_REPLY_COUNT);
+            values_list.push(vec![
+                CqlValue::Counter(Counter(count)),
+                CqlValue::Uuid(conversation_id),
+                CqlValue::Uuid(message_id),
+            ]);
+        }
+        if values_list.is_empty() {
+            return Ok(());
+        }
+
+        self.client.execute_batch(&batch, values_list).await
     }
 
     /// Get multiple messages by their IDs (useful for fetching a lineage path)
-    pub async fn get_messages_by_ids(
+    async fn get_messages_by_ids(
         &self,
         conversation_id: Uuid,
         message_ids: &[Uuid],
@@ -105,23 +267,16 @@ impl LineageRepository {
             return Ok(Vec::new());
         }
 
-        let query = Query::new(crate::db::queries::SELECT_MESSAGES_BY_IDS);
+        let query = self.client.statement(
+            crate::db::queries::SELECT_MESSAGES_BY_IDS,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
+        let rows: Vec<MessageRow> = self
             .client
-            .session()
-            .query(query, (conversation_id, message_ids))
+            .fetch_all(query, (conversation_id, message_ids))
             .await?;
-
-        let rows = result.rows.unwrap_or_default();
-        let mut messages = Vec::new();
-
-        for row in rows.into_typed::<MessageRow>() {
-            let row =
-                row.map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-            let message = row.to_message().map_err(DbError::InvalidData)?;
-            messages.push(message);
-        }
+        let mut messages = self.to_messages(rows).await?;
 
         // Sort by lineage depth to maintain order
         messages.sort_by_key(|m| m.lineage.len());
@@ -129,55 +284,128 @@ impl LineageRepository {
         Ok(messages)
     }
 
-    /// Get all messages in a conversation (entire tree)
-    pub async fn get_all_messages(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_ALL_MESSAGES);
+    /// Stream all messages in a conversation, fetching a page at a time
+    /// instead of buffering the entire tree in memory
+    async fn stream_all_messages(
+        &self,
+        conversation_id: Uuid,
+        page_size: Option<i32>,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError> {
+        let mut query = self.client.statement(
+            crate::db::queries::SELECT_ALL_MESSAGES,
+            StatementProfile::BulkRead,
+        );
+        if let Some(page_size) = page_size {
+            query.set_page_size(page_size);
+        }
 
-        let result = self
+        let rows = self
             .client
-            .session()
-            .query(query, (conversation_id,))
+            .fetch_stream::<MessageRow>(query, (conversation_id,))
             .await?;
 
-        let rows = result.rows.unwrap_or_default();
-        let mut messages = Vec::new();
+        Ok(self.stream_messages(rows))
+    }
 
-        for row in rows.into_typed::<MessageRow>() {
-            let row =
-                row.map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-            let message = row.to_message().map_err(DbError::InvalidData)?;
-            messages.push(message);
+    /// Stream every message in the table, fetching `page_size` rows at a time
+    async fn scan_messages(
+        &self,
+        page_size: i32,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError> {
+        let mut query = self.client.statement(
+            crate::db::queries::SELECT_EVERY_MESSAGE,
+            StatementProfile::BulkRead,
+        );
+        query.set_page_size(page_size);
+
+        let rows = self.client.fetch_stream::<MessageRow>(query, ()).await?;
+
+        Ok(self.stream_messages(rows))
+    }
+
+    /// Delete an entire conversation (all messages and checkpoints)
+    async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CONVERSATION,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CHECKPOINTS,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        // Counter rows can't share a batch with the others
+        for cql in [
+            crate::db::queries::DELETE_MESSAGE_COUNT,
+            crate::db::queries::DELETE_REPLY_COUNTS,
+        ] {
+            let query = self
+                .client
+                .statement(cql, StatementProfile::InteractiveWrite);
+            self.client.execute(query, (conversation_id,)).await?;
         }
 
-        Ok(messages)
+        Ok(())
     }
 
-    /// Delete an entire conversation (all messages)
-    pub async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
-        let query = Query::new(crate::db::queries::DELETE_CONVERSATION);
+    /// Insert a checkpoint (summary message covering a lineage range)
+    async fn insert_checkpoint(&self, checkpoint: &Message) -> Result<(), DbError> {
+        let row = self.to_row(checkpoint).await?;
+
+        let query = self.client.statement(
+            crate::db::queries::INSERT_CHECKPOINT,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (conversation_id,))
+            .execute(
+                query,
+                (
+                    row.conversation_id,
+                    row.message_id,
+                    row.parent_message_id,
+                    row.role,
+                    row.content_type,
+                    row.content_data,
+                    row.content_metadata,
+                    row.lineage,
+                    row.created_at,
+                    row.created_by,
+                    row.content_hash,
+                    row.key_id,
+                ),
+            )
             .await?;
 
         Ok(())
     }
 
-    /// Batch insert multiple messages (useful for forking)
-    pub async fn batch_insert_messages(&self, messages: &[Message]) -> Result<(), DbError> {
-        use scylla::batch::Batch;
-        use scylla::batch::BatchType;
+    /// Get all checkpoints of a conversation
+    async fn get_checkpoints(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CHECKPOINTS_BY_CONVERSATION,
+            StatementProfile::InteractiveRead,
+        );
 
-        let mut batch = Batch::new(BatchType::Unlogged);
-        let query_str = crate::db::queries::INSERT_MESSAGE;
+        let rows: Vec<MessageRow> = self.client.fetch_all(query, (conversation_id,)).await?;
+
+        self.to_messages(rows).await
+    }
 
+    /// Batch insert multiple messages (useful for forking)
+    async fn batch_insert_messages(&self, messages: &[Message]) -> Result<(), DbError> {
+        let mut batch = self.client.batch();
         let mut values_list = Vec::new();
 
         for message in messages {
-            let row = MessageRow::from_message(message).map_err(DbError::SerializationError)?;
+            let row = self.to_row(message).await?;
 
-            batch.append_statement(query_str);
+            batch.append_statement(crate::db::queries::INSERT_MESSAGE);
             values_list.push((
                 row.conversation_id,
                 row.message_id,
@@ -189,11 +417,372 @@ impl LineageRepository {
                 row.lineage,
                 row.created_at,
                 row.created_by,
+                row.content_hash,
+                row.key_id,
             ));
         }
 
-        self.client.session().batch(&batch, values_list).await?;
+        self.client.execute_batch(&batch, values_list).await
+    }
+
+    /// Insert messages and append events to the event log in one logged batch
+    async fn insert_messages_with_events(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError> {
+        let rows = self.message_and_event_rows(messages, events).await?;
+        self.client.write_rows(BatchStrategy::Logged, rows).await
+    }
+
+    /// Like `insert_messages_with_events`, written with
+    /// `scylla.fork_batch_strategy`
+    async fn insert_copied_messages(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError> {
+        let rows = self.message_and_event_rows(messages, events).await?;
+        self.client
+            .write_rows(self.client.fork_batch_strategy(), rows)
+            .await
+    }
+
+    /// Events with a sequence number of at least `from_seq`, oldest first
+    async fn get_events(
+        &self,
+        conversation_id: Uuid,
+        from_seq: i64,
+        limit: i32,
+    ) -> Result<Vec<ConversationEvent>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_EVENTS_FROM,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<EventRow> = self
+            .client
+            .fetch_all(query, (conversation_id, from_seq, limit))
+            .await?;
+
+        let mut events = Vec::with_capacity(rows.len());
+        for mut row in rows {
+            if let Some(cipher) = &self.cipher {
+                cipher.decrypt_event(&mut row).await?;
+            }
+            events.push(row.to_event()?);
+        }
+
+        Ok(events)
+    }
+
+    /// Delete the whole event log of a conversation
+    async fn delete_events(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_EVENTS,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        event_seqs::delete(&self.client, conversation_id).await?;
+
+        // Callers delete the messages first, so nothing is left to read with them
+        if let Some(cipher) = &self.cipher {
+            cipher.delete_keys(conversation_id).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn rewrap_data_keys(&self) -> Result<usize, DbError> {
+        match &self.cipher {
+            Some(cipher) => cipher.rewrap_data_keys().await,
+            None => Err(DbError::InvalidData(
+                "Encryption at rest is not enabled".to_string(),
+            )),
+        }
+    }
+
+    /// Add or retitle a conversation in its creator's title index
+    async fn upsert_conversation_title(&self, entry: &ConversationTitleRow) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_CONVERSATION_TITLE,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    &entry.user_id,
+                    entry.conversation_id,
+                    &entry.title,
+                    entry.updated_at,
+                    &entry.language,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove a conversation from its creator's title index
+    async fn delete_conversation_title(
+        &self,
+        user_id: &str,
+        conversation_id: Uuid,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CONVERSATION_TITLE,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(query, (user_id, conversation_id))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the title index of a user (one partition)
+    async fn get_conversation_titles(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<ConversationTitleRow>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CONVERSATION_TITLES,
+            StatementProfile::InteractiveRead,
+        );
+
+        self.client.fetch_all(query, (user_id,)).await
+    }
+
+    /// Record a fork in its source conversation's fork index
+    async fn upsert_fork_link(&self, link: &ForkLinkRow) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_FORK_LINK,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    link.source_conversation_id,
+                    link.fork_conversation_id,
+                    link.fork_from_message_id,
+                    &link.title,
+                    &link.created_by,
+                    link.created_at,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Remove a fork from its source conversation's fork index
+    async fn delete_fork_link(
+        &self,
+        source_conversation_id: Uuid,
+        fork_conversation_id: Uuid,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_FORK_LINK,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(query, (source_conversation_id, fork_conversation_id))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the direct forks of a conversation (one partition)
+    async fn get_fork_links(
+        &self,
+        source_conversation_id: Uuid,
+    ) -> Result<Vec<ForkLinkRow>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_FORK_LINKS,
+            StatementProfile::InteractiveRead,
+        );
+
+        self.client
+            .fetch_all(query, (source_conversation_id,))
+            .await
+    }
+
+    /// Mark a conversation read-only
+    async fn insert_lock(&self, lock: &ConversationLock) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::INSERT_CONVERSATION_LOCK,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    lock.conversation_id,
+                    &lock.locked_by,
+                    lock.locked_at,
+                    &lock.reason,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_lock(&self, conversation_id: Uuid) -> Result<Option<ConversationLock>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_CONVERSATION_LOCK,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<LockRow>(query, (conversation_id,))
+            .await
+        {
+            Ok(row) => Ok(Some(row.to_lock())),
+            Err(DbError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn delete_lock(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_CONVERSATION_LOCK,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        Ok(())
+    }
+
+    async fn insert_hold(&self, hold: &LegalHold) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::INSERT_LEGAL_HOLD,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    hold.conversation_id,
+                    &hold.placed_by,
+                    hold.placed_at,
+                    &hold.reason,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_hold(&self, conversation_id: Uuid) -> Result<Option<LegalHold>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_LEGAL_HOLD,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<LegalHoldRow>(query, (conversation_id,))
+            .await
+        {
+            Ok(row) => Ok(Some(row.to_hold())),
+            Err(DbError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn get_holds(&self) -> Result<Vec<LegalHold>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_LEGAL_HOLDS,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<LegalHoldRow> = self.client.fetch_all(query, ()).await?;
+
+        Ok(rows.into_iter().map(LegalHoldRow::to_hold).collect())
+    }
+
+    async fn delete_hold(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_LEGAL_HOLD,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        Ok(())
+    }
+
+    async fn upsert_fork_progress(&self, progress: &ForkProgress) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_FORK_PROGRESS,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    progress.conversation_id,
+                    progress.source_conversation_id,
+                    &progress.created_by,
+                    progress.total_chunks,
+                    progress.completed_chunks,
+                    progress.started_at,
+                    progress.updated_at,
+                ),
+            )
+            .await?;
 
         Ok(())
     }
+
+    async fn get_fork_progress(&self) -> Result<Vec<ForkProgress>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_FORK_PROGRESS,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<ForkProgressRow> = self.client.fetch_all(query, ()).await?;
+
+        Ok(rows.into_iter().map(ForkProgressRow::to_progress).collect())
+    }
+
+    async fn delete_fork_progress(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_FORK_PROGRESS,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (conversation_id,)).await?;
+
+        Ok(())
+    }
+}
+
+async fn to_message(
+    blobs: Option<&ContentBlobs>,
+    cipher: Option<&ContentCipher>,
+    mut row: MessageRow,
+) -> Result<Message, DbError> {
+    if let Some(blobs) = blobs {
+        blobs.hydrate(&mut row).await?;
+    }
+    if let Some(cipher) = cipher {
+        cipher.decrypt(&mut row).await?;
+    }
+
+    row.to_message()
 }
@@ -0,0 +1,1207 @@
+//! In-memory implementations of the store traits. State lives for the lifetime
+//! of the process; intended for development, tests and benchmarks.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt};
+use scylla::frame::value::Counter;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use uuid::Uuid;
+
+use super::store::{
+    AccessLogStore, AnalyticsStore, BranchStore, ChangeStore, ExportStore, ImageStore, JobStore,
+    LineageStore, NotificationStore, PreferenceStore, ShareStore, TrendingStore, WebhookStore,
+};
+use crate::db::{
+    ActivityRow, AnalyticsRollupRow, ConversationTitleRow, DbError, ForkLinkRow, TrendingRow,
+    UserConversationRow,
+};
+use crate::domain::{
+    AccessLogEntry, Branch, BranchSlug, Change, ConversationEvent, ConversationExport,
+    ConversationLock, ForkProgress, Invite, Job, LegalHold, Message, Notification, Share,
+    UserPreferences, WebhookSubscription,
+};
+
+/// One conversation's events keyed by `(seq, event_id)`
+type EventLog = BTreeMap<(i64, Uuid), ConversationEvent>;
+
+/// Event logs keyed by conversation, shared by the stores that append to them
+#[derive(Clone, Default)]
+pub struct MemoryEventLog(Arc<Mutex<HashMap<Uuid, EventLog>>>);
+
+impl MemoryEventLog {
+    /// Number the events after the last ones of their conversations
+    fn append(&self, events: &[ConversationEvent]) {
+        let mut logs = lock(&self.0);
+        for event in events {
+            let log = logs.entry(event.conversation_id).or_default();
+            let seq = log.last_key_value().map_or(1, |((seq, _), _)| seq + 1);
+            let event = ConversationEvent {
+                seq,
+                ..event.clone()
+            };
+            log.insert((seq, event.event_id), event);
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryLineageStore {
+    messages: Mutex<HashMap<Uuid, HashMap<Uuid, Message>>>,
+    checkpoints: Mutex<HashMap<Uuid, Vec<Message>>>,
+    message_counts: Mutex<HashMap<Uuid, i64>>,
+    reply_counts: Mutex<HashMap<Uuid, HashMap<Uuid, i64>>>,
+    titles: Mutex<HashMap<String, HashMap<Uuid, ConversationTitleRow>>>,
+    forks: Mutex<HashMap<Uuid, HashMap<Uuid, ForkLinkRow>>>,
+    locks: Mutex<HashMap<Uuid, ConversationLock>>,
+    holds: Mutex<HashMap<Uuid, LegalHold>>,
+    fork_progress: Mutex<HashMap<Uuid, ForkProgress>>,
+    events: MemoryEventLog,
+}
+
+impl MemoryLineageStore {
+    pub fn with_events(events: MemoryEventLog) -> Self {
+        Self {
+            events,
+            ..Self::default()
+        }
+    }
+}
+
+#[async_trait]
+impl LineageStore for MemoryLineageStore {
+    async fn insert_message(&self, message: &Message) -> Result<(), DbError> {
+        self.batch_insert_messages(std::slice::from_ref(message))
+            .await
+    }
+
+    async fn get_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<Message, DbError> {
+        lock(&self.messages)
+            .get(&conversation_id)
+            .and_then(|messages| messages.get(&message_id))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_children(
+        &self,
+        conversation_id: Uuid,
+        parent_message_id: Uuid,
+    ) -> Result<Vec<Message>, DbError> {
+        let mut children: Vec<Message> = lock(&self.messages)
+            .get(&conversation_id)
+            .map(|messages| {
+                messages
+                    .values()
+                    .filter(|m| m.parent_message_id == Some(parent_message_id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        children.sort_by_key(|m| m.message_id);
+
+        Ok(children)
+    }
+
+    async fn count_messages(&self, conversation_id: Uuid) -> Result<usize, DbError> {
+        Ok(lock(&self.messages)
+            .get(&conversation_id)
+            .map(|messages| messages.len())
+            .unwrap_or_default())
+    }
+
+    async fn get_message_count(&self, conversation_id: Uuid) -> Result<usize, DbError> {
+        let count = lock(&self.message_counts)
+            .get(&conversation_id)
+            .copied()
+            .unwrap_or_default();
+        Ok(count.max(0) as usize)
+    }
+
+    async fn get_reply_count(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<usize, DbError> {
+        let count = lock(&self.reply_counts)
+            .get(&conversation_id)
+            .and_then(|counts| counts.get(&message_id))
+            .copied()
+            .unwrap_or_default();
+        Ok(count.max(0) as usize)
+    }
+
+    async fn add_message_counts(
+        &self,
+        conversation_id: Uuid,
+        messages: i64,
+        replies: &[(Uuid, i64)],
+    ) -> Result<(), DbError> {
+        *lock(&self.message_counts)
+            .entry(conversation_id)
+            .or_default() += messages;
+        let mut reply_counts = lock(&self.reply_counts);
+        let reply_counts = reply_counts.entry(conversation_id).or_default();
+        for &(message_id, count) in replies {
+            *reply_counts.entry(message_id).or_default() += count;
+        }
+
+        Ok(())
+    }
+
+    async fn get_messages_by_ids(
+        &self,
+        conversation_id: Uuid,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<Message>, DbError> {
+        let mut found: Vec<Message> = lock(&self.messages)
+            .get(&conversation_id)
+            .map(|messages| {
+                message_ids
+                    .iter()
+                    .filter_map(|id| messages.get(id))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        found.sort_by_key(|m| m.lineage.len());
+
+        Ok(found)
+    }
+
+    async fn stream_all_messages(
+        &self,
+        conversation_id: Uuid,
+        _page_size: Option<i32>,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError> {
+        let mut all: Vec<Message> = lock(&self.messages)
+            .get(&conversation_id)
+            .map(|messages| messages.values().cloned().collect())
+            .unwrap_or_default();
+        all.sort_by_key(|m| m.message_id);
+
+        Ok(stream::iter(all.into_iter().map(Ok)).boxed())
+    }
+
+    async fn scan_messages(
+        &self,
+        _page_size: i32,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError> {
+        let all: Vec<Message> = lock(&self.messages)
+            .values()
+            .flat_map(|messages| messages.values().cloned())
+            .collect();
+
+        Ok(stream::iter(all.into_iter().map(Ok)).boxed())
+    }
+
+    async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        lock(&self.messages).remove(&conversation_id);
+        lock(&self.checkpoints).remove(&conversation_id);
+        lock(&self.message_counts).remove(&conversation_id);
+        lock(&self.reply_counts).remove(&conversation_id);
+
+        Ok(())
+    }
+
+    async fn insert_checkpoint(&self, checkpoint: &Message) -> Result<(), DbError> {
+        let mut checkpoints = lock(&self.checkpoints);
+        let checkpoints = checkpoints.entry(checkpoint.conversation_id).or_default();
+        checkpoints.retain(|c| c.message_id != checkpoint.message_id);
+        checkpoints.push(checkpoint.clone());
+
+        Ok(())
+    }
+
+    async fn get_checkpoints(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError> {
+        Ok(lock(&self.checkpoints)
+            .get(&conversation_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn batch_insert_messages(&self, messages: &[Message]) -> Result<(), DbError> {
+        let mut stored = lock(&self.messages);
+        for message in messages {
+            stored
+                .entry(message.conversation_id)
+                .or_default()
+                .insert(message.message_id, message.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn insert_messages_with_events(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError> {
+        self.batch_insert_messages(messages).await?;
+        self.events.append(events);
+
+        Ok(())
+    }
+
+    async fn insert_copied_messages(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError> {
+        self.insert_messages_with_events(messages, events).await
+    }
+
+    async fn get_events(
+        &self,
+        conversation_id: Uuid,
+        from_seq: i64,
+        limit: i32,
+    ) -> Result<Vec<ConversationEvent>, DbError> {
+        Ok(lock(&self.events.0)
+            .get(&conversation_id)
+            .map(|log| {
+                log.range((from_seq, Uuid::nil())..)
+                    .take(limit.max(0) as usize)
+                    .map(|(_, event)| event.clone())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn delete_events(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        lock(&self.events.0).remove(&conversation_id);
+
+        Ok(())
+    }
+
+    /// Nothing is encrypted in memory
+    async fn rewrap_data_keys(&self) -> Result<usize, DbError> {
+        Err(DbError::InvalidData(
+            "Encryption at rest is not enabled".to_string(),
+        ))
+    }
+
+    async fn upsert_conversation_title(&self, entry: &ConversationTitleRow) -> Result<(), DbError> {
+        lock(&self.titles)
+            .entry(entry.user_id.clone())
+            .or_default()
+            .insert(entry.conversation_id, entry.clone());
+
+        Ok(())
+    }
+
+    async fn delete_conversation_title(
+        &self,
+        user_id: &str,
+        conversation_id: Uuid,
+    ) -> Result<(), DbError> {
+        if let Some(titles) = lock(&self.titles).get_mut(user_id) {
+            titles.remove(&conversation_id);
+        }
+
+        Ok(())
+    }
+
+    async fn get_conversation_titles(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<ConversationTitleRow>, DbError> {
+        Ok(lock(&self.titles)
+            .get(user_id)
+            .map(|titles| titles.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn upsert_fork_link(&self, link: &ForkLinkRow) -> Result<(), DbError> {
+        lock(&self.forks)
+            .entry(link.source_conversation_id)
+            .or_default()
+            .insert(link.fork_conversation_id, link.clone());
+
+        Ok(())
+    }
+
+    async fn delete_fork_link(
+        &self,
+        source_conversation_id: Uuid,
+        fork_conversation_id: Uuid,
+    ) -> Result<(), DbError> {
+        if let Some(forks) = lock(&self.forks).get_mut(&source_conversation_id) {
+            forks.remove(&fork_conversation_id);
+        }
+
+        Ok(())
+    }
+
+    async fn get_fork_links(
+        &self,
+        source_conversation_id: Uuid,
+    ) -> Result<Vec<ForkLinkRow>, DbError> {
+        Ok(lock(&self.forks)
+            .get(&source_conversation_id)
+            .map(|forks| forks.values().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn insert_lock(&self, conversation_lock: &ConversationLock) -> Result<(), DbError> {
+        lock(&self.locks).insert(conversation_lock.conversation_id, conversation_lock.clone());
+        Ok(())
+    }
+
+    async fn get_lock(&self, conversation_id: Uuid) -> Result<Option<ConversationLock>, DbError> {
+        Ok(lock(&self.locks).get(&conversation_id).cloned())
+    }
+
+    async fn delete_lock(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        lock(&self.locks).remove(&conversation_id);
+        Ok(())
+    }
+
+    async fn insert_hold(&self, hold: &LegalHold) -> Result<(), DbError> {
+        lock(&self.holds).insert(hold.conversation_id, hold.clone());
+        Ok(())
+    }
+
+    async fn get_hold(&self, conversation_id: Uuid) -> Result<Option<LegalHold>, DbError> {
+        Ok(lock(&self.holds).get(&conversation_id).cloned())
+    }
+
+    async fn get_holds(&self) -> Result<Vec<LegalHold>, DbError> {
+        Ok(lock(&self.holds).values().cloned().collect())
+    }
+
+    async fn delete_hold(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        lock(&self.holds).remove(&conversation_id);
+        Ok(())
+    }
+
+    async fn upsert_fork_progress(&self, progress: &ForkProgress) -> Result<(), DbError> {
+        lock(&self.fork_progress).insert(progress.conversation_id, progress.clone());
+        Ok(())
+    }
+
+    async fn get_fork_progress(&self) -> Result<Vec<ForkProgress>, DbError> {
+        Ok(lock(&self.fork_progress).values().cloned().collect())
+    }
+
+    async fn delete_fork_progress(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        lock(&self.fork_progress).remove(&conversation_id);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryBranchStore {
+    branches: Mutex<HashMap<Uuid, HashMap<Uuid, Branch>>>,
+    by_leaf: Mutex<HashMap<Uuid, (Uuid, Uuid)>>,
+    slugs: Mutex<HashMap<(Uuid, String), BranchSlug>>,
+    events: MemoryEventLog,
+}
+
+impl MemoryBranchStore {
+    pub fn with_events(events: MemoryEventLog) -> Self {
+        Self {
+            events,
+            ..Self::default()
+        }
+    }
+}
+
+#[async_trait]
+impl BranchStore for MemoryBranchStore {
+    async fn insert_branch(&self, branch: &Branch) -> Result<(), DbError> {
+        lock(&self.branches)
+            .entry(branch.conversation_id)
+            .or_default()
+            .insert(branch.branch_id, branch.clone());
+        lock(&self.by_leaf).insert(
+            branch.leaf_message_id,
+            (branch.conversation_id, branch.branch_id),
+        );
+        self.claim_slug(
+            branch.conversation_id,
+            branch.branch_id,
+            &branch.branch_name,
+            &branch.slug,
+        );
+
+        Ok(())
+    }
+
+    async fn get_branch(&self, conversation_id: Uuid, branch_id: Uuid) -> Result<Branch, DbError> {
+        lock(&self.branches)
+            .get(&conversation_id)
+            .and_then(|branches| branches.get(&branch_id))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_branches_by_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<Branch>, DbError> {
+        let mut branches: Vec<Branch> = lock(&self.branches)
+            .get(&conversation_id)
+            .map(|branches| branches.values().cloned().collect())
+            .unwrap_or_default();
+        branches.sort_by_key(|b| b.branch_id);
+
+        Ok(branches)
+    }
+
+    async fn update_branch_leaf(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        old_leaf_id: Uuid,
+        new_leaf_id: Uuid,
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError> {
+        self.events.append(events);
+        if let Some(branch) = lock(&self.branches)
+            .get_mut(&conversation_id)
+            .and_then(|branches| branches.get_mut(&branch_id))
+        {
+            branch.leaf_message_id = new_leaf_id;
+            branch.last_updated = Utc::now();
+        }
+
+        let mut by_leaf = lock(&self.by_leaf);
+        by_leaf.remove(&old_leaf_id);
+        by_leaf.insert(new_leaf_id, (conversation_id, branch_id));
+
+        Ok(())
+    }
+
+    async fn update_branch_name(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        new_name: String,
+        new_slug: String,
+    ) -> Result<(), DbError> {
+        self.claim_slug(conversation_id, branch_id, &new_name, &new_slug);
+        if let Some(branch) = lock(&self.branches)
+            .get_mut(&conversation_id)
+            .and_then(|branches| branches.get_mut(&branch_id))
+        {
+            branch.branch_name = new_name;
+            branch.slug = new_slug;
+            branch.last_updated = Utc::now();
+        }
+
+        Ok(())
+    }
+
+    async fn delete_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        leaf_message_id: Uuid,
+    ) -> Result<(), DbError> {
+        if let Some(branches) = lock(&self.branches).get_mut(&conversation_id) {
+            branches.remove(&branch_id);
+        }
+        lock(&self.by_leaf).remove(&leaf_message_id);
+        lock(&self.slugs).retain(|(conversation, _), slug| {
+            *conversation != conversation_id || slug.branch_id != branch_id
+        });
+
+        Ok(())
+    }
+
+    async fn get_branch_by_leaf(&self, leaf_message_id: Uuid) -> Result<(Uuid, Uuid), DbError> {
+        lock(&self.by_leaf)
+            .get(&leaf_message_id)
+            .copied()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_branch_slugs(&self, conversation_id: Uuid) -> Result<Vec<BranchSlug>, DbError> {
+        let mut slugs: Vec<BranchSlug> = lock(&self.slugs)
+            .values()
+            .filter(|slug| slug.conversation_id == conversation_id)
+            .cloned()
+            .collect();
+        slugs.sort_by(|a, b| a.slug.cmp(&b.slug));
+
+        Ok(slugs)
+    }
+
+    async fn get_branch_slug(
+        &self,
+        conversation_id: Uuid,
+        slug: &str,
+    ) -> Result<BranchSlug, DbError> {
+        lock(&self.slugs)
+            .get(&(conversation_id, slug.to_string()))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+}
+
+impl MemoryBranchStore {
+    fn claim_slug(&self, conversation_id: Uuid, branch_id: Uuid, branch_name: &str, slug: &str) {
+        lock(&self.slugs).insert(
+            (conversation_id, slug.to_string()),
+            BranchSlug {
+                conversation_id,
+                slug: slug.to_string(),
+                branch_id,
+                branch_name: branch_name.to_string(),
+                created_at: Utc::now(),
+            },
+        );
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryShareStore {
+    shares: Mutex<HashMap<Uuid, HashMap<String, Share>>>,
+    invites: Mutex<HashMap<(String, String), Invite>>,
+    user_conversations: Mutex<HashMap<String, Vec<UserConversationRow>>>,
+}
+
+#[async_trait]
+impl ShareStore for MemoryShareStore {
+    async fn insert_share(&self, share: &Share) -> Result<(), DbError> {
+        lock(&self.shares)
+            .entry(share.conversation_id)
+            .or_default()
+            .insert(share.shared_with.clone(), share.clone());
+
+        Ok(())
+    }
+
+    async fn get_share(&self, conversation_id: Uuid, shared_with: &str) -> Result<Share, DbError> {
+        lock(&self.shares)
+            .get(&conversation_id)
+            .and_then(|shares| shares.get(shared_with))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_shares_by_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<Share>, DbError> {
+        let mut shares: Vec<Share> = lock(&self.shares)
+            .get(&conversation_id)
+            .map(|shares| shares.values().cloned().collect())
+            .unwrap_or_default();
+        shares.sort_by(|a, b| a.shared_with.cmp(&b.shared_with));
+
+        Ok(shares)
+    }
+
+    async fn delete_share(&self, conversation_id: Uuid, shared_with: &str) -> Result<(), DbError> {
+        if let Some(shares) = lock(&self.shares).get_mut(&conversation_id) {
+            shares.remove(shared_with);
+        }
+
+        Ok(())
+    }
+
+    async fn apply_share_changes(
+        &self,
+        conversation_id: Uuid,
+        grants: &[Share],
+        revokes: &[String],
+    ) -> Result<(), DbError> {
+        let mut shares = lock(&self.shares);
+        let conversation_shares = shares.entry(conversation_id).or_default();
+        for share in grants {
+            conversation_shares.insert(share.shared_with.clone(), share.clone());
+        }
+        for shared_with in revokes {
+            conversation_shares.remove(shared_with);
+        }
+
+        Ok(())
+    }
+
+    async fn get_shares_for_user(&self, shared_with: &str) -> Result<Vec<Share>, DbError> {
+        Ok(lock(&self.shares)
+            .values()
+            .filter_map(|shares| shares.get(shared_with).cloned())
+            .collect())
+    }
+
+    /// Shares are looked up by user directly, so there is no index to rebuild
+    async fn rebuild_user_index(&self) -> Result<usize, DbError> {
+        Ok(lock(&self.shares).values().map(|shares| shares.len()).sum())
+    }
+
+    async fn insert_invite(&self, invite: &Invite) -> Result<(), DbError> {
+        lock(&self.invites).insert((invite.email.clone(), invite.token.clone()), invite.clone());
+
+        Ok(())
+    }
+
+    async fn get_invite(&self, email: &str, token: &str) -> Result<Invite, DbError> {
+        lock(&self.invites)
+            .get(&(email.to_string(), token.to_string()))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn delete_invite(&self, email: &str, token: &str) -> Result<(), DbError> {
+        lock(&self.invites).remove(&(email.to_string(), token.to_string()));
+
+        Ok(())
+    }
+
+    async fn upsert_user_conversation(
+        &self,
+        user_id: &str,
+        conversation_id: Uuid,
+        active_branch_id: Option<Uuid>,
+    ) -> Result<(), DbError> {
+        lock(&self.user_conversations)
+            .entry(user_id.to_string())
+            .or_default()
+            .push(UserConversationRow {
+                user_id: user_id.to_string(),
+                last_activity: Utc::now(),
+                conversation_id,
+                active_branch_id,
+            });
+
+        Ok(())
+    }
+
+    async fn get_user_conversations(
+        &self,
+        user_id: &str,
+        limit: i32,
+    ) -> Result<Vec<UserConversationRow>, DbError> {
+        let mut rows = lock(&self.user_conversations)
+            .get(user_id)
+            .cloned()
+            .unwrap_or_default();
+        rows.sort_by_key(|row| std::cmp::Reverse(row.last_activity));
+        rows.truncate(limit.max(0) as usize);
+
+        Ok(rows)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryPreferenceStore {
+    preferences: Mutex<HashMap<String, UserPreferences>>,
+}
+
+#[async_trait]
+impl PreferenceStore for MemoryPreferenceStore {
+    async fn get_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>, DbError> {
+        Ok(lock(&self.preferences).get(user_id).cloned())
+    }
+
+    async fn upsert_preferences(&self, preferences: &UserPreferences) -> Result<(), DbError> {
+        lock(&self.preferences).insert(preferences.user_id.clone(), preferences.clone());
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryExportStore {
+    /// Keyed by conversation, then export
+    exports: Mutex<HashMap<Uuid, HashMap<Uuid, ConversationExport>>>,
+}
+
+#[async_trait]
+impl ExportStore for MemoryExportStore {
+    async fn insert_export(&self, export: &ConversationExport) -> Result<(), DbError> {
+        let mut exports = lock(&self.exports);
+        let saved = exports
+            .entry(export.conversation_id)
+            .or_default()
+            .entry(export.export_id)
+            .or_insert_with(|| export.clone());
+        saved.format = export.format.clone();
+        saved.requested_by = export.requested_by.clone();
+
+        Ok(())
+    }
+
+    async fn complete_export(
+        &self,
+        conversation_id: Uuid,
+        created_at: DateTime<Utc>,
+        export_id: Uuid,
+        object_key: &str,
+        size_bytes: i64,
+        completed_at: DateTime<Utc>,
+    ) -> Result<(), DbError> {
+        let mut exports = lock(&self.exports);
+        let saved = exports
+            .entry(conversation_id)
+            .or_default()
+            .entry(export_id)
+            .or_insert_with(|| ConversationExport {
+                export_id,
+                conversation_id,
+                format: String::new(),
+                requested_by: String::new(),
+                created_at,
+                object_key: None,
+                size_bytes: None,
+                completed_at: None,
+            });
+        saved.object_key = Some(object_key.to_string());
+        saved.size_bytes = Some(size_bytes);
+        saved.completed_at = Some(completed_at);
+
+        Ok(())
+    }
+
+    async fn get_export(
+        &self,
+        conversation_id: Uuid,
+        created_at: DateTime<Utc>,
+        export_id: Uuid,
+    ) -> Result<ConversationExport, DbError> {
+        lock(&self.exports)
+            .get(&conversation_id)
+            .and_then(|exports| exports.get(&export_id))
+            .filter(|export| export.created_at == created_at)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_exports(
+        &self,
+        conversation_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<ConversationExport>, DbError> {
+        let mut exports: Vec<ConversationExport> = lock(&self.exports)
+            .get(&conversation_id)
+            .map(|exports| exports.values().cloned().collect())
+            .unwrap_or_default();
+        exports.sort_by_key(|export| std::cmp::Reverse(export.created_at));
+        exports.truncate(limit.max(0) as usize);
+
+        Ok(exports)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryJobStore {
+    jobs: Mutex<HashMap<Uuid, Job>>,
+}
+
+#[async_trait]
+impl JobStore for MemoryJobStore {
+    async fn save_job(&self, job: &Job) -> Result<(), DbError> {
+        let mut jobs = lock(&self.jobs);
+        let cancel_requested = jobs
+            .get(&job.job_id)
+            .is_some_and(|saved| saved.cancel_requested);
+        let mut job = job.clone();
+        job.cancel_requested |= cancel_requested;
+        jobs.insert(job.job_id, job);
+
+        Ok(())
+    }
+
+    async fn get_job(&self, job_id: Uuid) -> Result<Job, DbError> {
+        lock(&self.jobs)
+            .get(&job_id)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_jobs_for_user(&self, user_id: &str, limit: i32) -> Result<Vec<Job>, DbError> {
+        let mut jobs: Vec<Job> = lock(&self.jobs)
+            .values()
+            .filter(|job| job.user_id == user_id)
+            .cloned()
+            .collect();
+        jobs.sort_by_key(|job| std::cmp::Reverse(job.started_at));
+        jobs.truncate(limit.max(0) as usize);
+
+        Ok(jobs)
+    }
+
+    async fn get_running_jobs(&self) -> Result<Vec<Job>, DbError> {
+        Ok(lock(&self.jobs)
+            .values()
+            .filter(|job| !job.is_finished())
+            .cloned()
+            .collect())
+    }
+
+    async fn request_cancel(&self, job_id: Uuid) -> Result<(), DbError> {
+        if let Some(job) = lock(&self.jobs).get_mut(&job_id) {
+            job.cancel_requested = true;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryChangeStore {
+    changes: Mutex<HashMap<Uuid, Vec<Change>>>,
+}
+
+#[async_trait]
+impl ChangeStore for MemoryChangeStore {
+    async fn insert_change(&self, change: &Change) -> Result<(), DbError> {
+        let mut changes = lock(&self.changes);
+        let changes = changes.entry(change.conversation_id).or_default();
+        changes.push(change.clone());
+        changes.sort_by_key(|c| (c.changed_at, c.change_id));
+
+        Ok(())
+    }
+
+    async fn get_changes_since(
+        &self,
+        conversation_id: Uuid,
+        since: DateTime<Utc>,
+        after_change_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<Change>, DbError> {
+        Ok(lock(&self.changes)
+            .get(&conversation_id)
+            .map(|changes| {
+                changes
+                    .iter()
+                    .filter(|c| (c.changed_at, c.change_id) > (since, after_change_id))
+                    .take(limit.max(0) as usize)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn delete_changes(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        lock(&self.changes).remove(&conversation_id);
+
+        Ok(())
+    }
+
+    /// Nothing expires in memory
+    async fn retain_changes(&self, conversation_id: Uuid) -> Result<usize, DbError> {
+        Ok(lock(&self.changes)
+            .get(&conversation_id)
+            .map_or(0, |changes| changes.len()))
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryTrendingStore {
+    /// (day, conversation_id) -> (views, forks)
+    activity: Mutex<HashMap<(String, Uuid), (i64, i64)>>,
+    rankings: Mutex<HashMap<String, Vec<TrendingRow>>>,
+}
+
+#[async_trait]
+impl TrendingStore for MemoryTrendingStore {
+    async fn increment_activity(
+        &self,
+        day: &str,
+        conversation_id: Uuid,
+        views: i64,
+        forks: i64,
+    ) -> Result<(), DbError> {
+        let mut activity = lock(&self.activity);
+        let counters = activity
+            .entry((day.to_string(), conversation_id))
+            .or_default();
+        counters.0 += views;
+        counters.1 += forks;
+
+        Ok(())
+    }
+
+    async fn get_activity(&self, day: &str) -> Result<Vec<ActivityRow>, DbError> {
+        Ok(lock(&self.activity)
+            .iter()
+            .filter(|((d, _), _)| d == day)
+            .map(|((day, conversation_id), (views, forks))| ActivityRow {
+                day: day.clone(),
+                conversation_id: *conversation_id,
+                view_count: Some(Counter(*views)),
+                fork_count: Some(Counter(*forks)),
+            })
+            .collect())
+    }
+
+    async fn replace_trending(&self, bucket: &str, entries: &[TrendingRow]) -> Result<(), DbError> {
+        lock(&self.rankings).insert(bucket.to_string(), entries.to_vec());
+
+        Ok(())
+    }
+
+    async fn get_trending(&self, bucket: &str, limit: i32) -> Result<Vec<TrendingRow>, DbError> {
+        Ok(lock(&self.rankings)
+            .get(bucket)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .take(limit.max(0) as usize)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryNotificationStore {
+    notifications: Mutex<HashMap<String, Vec<Notification>>>,
+    read_up_to: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+#[async_trait]
+impl NotificationStore for MemoryNotificationStore {
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), DbError> {
+        lock(&self.notifications)
+            .entry(notification.user_id.clone())
+            .or_default()
+            .push(notification.clone());
+
+        Ok(())
+    }
+
+    async fn get_notifications(
+        &self,
+        user_id: &str,
+        limit: i32,
+    ) -> Result<Vec<Notification>, DbError> {
+        let mut notifications = lock(&self.notifications)
+            .get(user_id)
+            .cloned()
+            .unwrap_or_default();
+        notifications.sort_by_key(|n| std::cmp::Reverse(n.created_at));
+        notifications.truncate(limit.max(0) as usize);
+
+        Ok(notifications)
+    }
+
+    async fn set_read_up_to(
+        &self,
+        user_id: &str,
+        read_up_to: DateTime<Utc>,
+    ) -> Result<(), DbError> {
+        lock(&self.read_up_to).insert(user_id.to_string(), read_up_to);
+
+        Ok(())
+    }
+
+    async fn get_read_up_to(&self, user_id: &str) -> Result<Option<DateTime<Utc>>, DbError> {
+        Ok(lock(&self.read_up_to).get(user_id).copied())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryAccessLogStore {
+    entries: Mutex<HashMap<Uuid, Vec<AccessLogEntry>>>,
+}
+
+#[async_trait]
+impl AccessLogStore for MemoryAccessLogStore {
+    async fn insert_entry(&self, entry: &AccessLogEntry) -> Result<(), DbError> {
+        lock(&self.entries)
+            .entry(entry.conversation_id)
+            .or_default()
+            .push(entry.clone());
+
+        Ok(())
+    }
+
+    async fn get_entries(
+        &self,
+        conversation_id: Uuid,
+        before: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<AccessLogEntry>, DbError> {
+        let mut entries: Vec<AccessLogEntry> = lock(&self.entries)
+            .get(&conversation_id)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.accessed_at < before)
+            .cloned()
+            .collect();
+        entries.sort_by_key(|entry| (std::cmp::Reverse(entry.accessed_at), entry.entry_id));
+        entries.truncate(limit.max(0) as usize);
+
+        Ok(entries)
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryWebhookStore {
+    /// Keyed by `(conversation_id, subscription_id)`
+    subscriptions: Mutex<BTreeMap<(Uuid, Uuid), WebhookSubscription>>,
+}
+
+#[async_trait]
+impl WebhookStore for MemoryWebhookStore {
+    async fn insert_subscription(&self, subscription: &WebhookSubscription) -> Result<(), DbError> {
+        lock(&self.subscriptions).insert(
+            (subscription.conversation_id, subscription.subscription_id),
+            subscription.clone(),
+        );
+
+        Ok(())
+    }
+
+    async fn get_subscriptions(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<WebhookSubscription>, DbError> {
+        Ok(lock(&self.subscriptions)
+            .range((conversation_id, Uuid::nil())..=(conversation_id, Uuid::max()))
+            .map(|(_, subscription)| subscription.clone())
+            .collect())
+    }
+
+    async fn get_all_subscriptions(&self) -> Result<Vec<WebhookSubscription>, DbError> {
+        Ok(lock(&self.subscriptions).values().cloned().collect())
+    }
+
+    async fn set_delivered_up_to(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+        delivered_up_to: (DateTime<Utc>, Uuid),
+    ) -> Result<(), DbError> {
+        if let Some(subscription) =
+            lock(&self.subscriptions).get_mut(&(conversation_id, subscription_id))
+        {
+            subscription.delivered_up_to = delivered_up_to;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_subscription(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+    ) -> Result<(), DbError> {
+        lock(&self.subscriptions).remove(&(conversation_id, subscription_id));
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryImageStore {
+    /// Keys referenced by each conversation
+    refs: Mutex<HashMap<Uuid, BTreeSet<String>>>,
+    pending_deletions: Mutex<BTreeSet<String>>,
+}
+
+#[async_trait]
+impl ImageStore for MemoryImageStore {
+    async fn add_image_refs(
+        &self,
+        conversation_id: Uuid,
+        storage_keys: &[String],
+    ) -> Result<(), DbError> {
+        lock(&self.refs)
+            .entry(conversation_id)
+            .or_default()
+            .extend(storage_keys.iter().cloned());
+
+        Ok(())
+    }
+
+    async fn get_image_keys(&self, conversation_id: Uuid) -> Result<Vec<String>, DbError> {
+        Ok(lock(&self.refs)
+            .get(&conversation_id)
+            .map(|keys| keys.iter().cloned().collect())
+            .unwrap_or_default())
+    }
+
+    async fn delete_image_refs(
+        &self,
+        conversation_id: Uuid,
+        _storage_keys: &[String],
+    ) -> Result<(), DbError> {
+        lock(&self.refs).remove(&conversation_id);
+
+        Ok(())
+    }
+
+    async fn is_image_referenced(&self, storage_key: &str) -> Result<bool, DbError> {
+        Ok(lock(&self.refs)
+            .values()
+            .any(|keys| keys.contains(storage_key)))
+    }
+
+    async fn queue_image_deletions(&self, storage_keys: &[String]) -> Result<(), DbError> {
+        lock(&self.pending_deletions).extend(storage_keys.iter().cloned());
+
+        Ok(())
+    }
+
+    async fn get_pending_image_deletions(&self, limit: i32) -> Result<Vec<String>, DbError> {
+        Ok(lock(&self.pending_deletions)
+            .iter()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_pending_image_deletion(&self, storage_key: &str) -> Result<(), DbError> {
+        lock(&self.pending_deletions).remove(storage_key);
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryAnalyticsStore {
+    /// scope -> day -> rollup
+    rollups: Mutex<HashMap<String, BTreeMap<String, AnalyticsRollupRow>>>,
+}
+
+#[async_trait]
+impl AnalyticsStore for MemoryAnalyticsStore {
+    async fn upsert_rollups(&self, rollups: &[AnalyticsRollupRow]) -> Result<(), DbError> {
+        let mut stored = lock(&self.rollups);
+        for rollup in rollups {
+            stored
+                .entry(rollup.scope.clone())
+                .or_default()
+                .insert(rollup.day.clone(), rollup.clone());
+        }
+
+        Ok(())
+    }
+
+    async fn get_rollups(
+        &self,
+        scope: &str,
+        from_day: &str,
+        to_day: &str,
+    ) -> Result<Vec<AnalyticsRollupRow>, DbError> {
+        if from_day > to_day {
+            return Ok(Vec::new());
+        }
+
+        Ok(lock(&self.rollups)
+            .get(scope)
+            .map(|days| {
+                days.range(from_day.to_string()..=to_day.to_string())
+                    .map(|(_, rollup)| rollup.clone())
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().expect("memory store lock poisoned")
+}
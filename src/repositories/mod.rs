@@ -1,7 +1,41 @@
+pub mod access_log_repo;
+pub mod analytics_repo;
 pub mod branch_repo;
+pub mod cdc_repo;
+pub mod change_repo;
+pub mod content_blobs;
+pub mod content_cipher;
+mod event_seqs;
+pub mod export_repo;
+pub mod image_repo;
+pub mod job_repo;
 pub mod lineage_repo;
+pub mod memory;
+pub mod notification_repo;
+pub mod preference_repo;
 pub mod share_repo;
+pub mod store;
+pub mod trending_repo;
+pub mod webhook_repo;
 
+pub use access_log_repo::AccessLogRepository;
+pub use analytics_repo::AnalyticsRepository;
 pub use branch_repo::BranchRepository;
+pub use cdc_repo::CdcRepository;
+pub use change_repo::ChangeRepository;
+pub use content_blobs::ContentBlobs;
+pub use content_cipher::ContentCipher;
+pub use export_repo::ExportRepository;
+pub use image_repo::ImageRepository;
+pub use job_repo::JobRepository;
 pub use lineage_repo::LineageRepository;
+pub use notification_repo::NotificationRepository;
+pub use preference_repo::PreferenceRepository;
 pub use share_repo::ShareRepository;
+pub use store::{
+    AccessLogStore, AnalyticsStore, BranchStore, CdcStore, ChangeStore, ExportStore, ImageStore,
+    JobStore, LineageStore, NotificationStore, PreferenceStore, ShareStore, Storage, TrendingStore,
+    WebhookStore,
+};
+pub use trending_repo::TrendingRepository;
+pub use webhook_repo::WebhookRepository;
@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::store::NotificationStore;
+use crate::db::{DbClient, DbError, NotificationReadRow, NotificationRow, StatementProfile};
+use crate::domain::Notification;
+
+#[derive(Clone)]
+pub struct NotificationRepository {
+    client: DbClient,
+}
+
+impl NotificationRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl NotificationStore for NotificationRepository {
+    /// Add a notification to a user's inbox
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), DbError> {
+        let row = NotificationRow::from_notification(notification);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_NOTIFICATION,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    row.user_id,
+                    row.created_at,
+                    row.notification_id,
+                    row.kind,
+                    row.conversation_id,
+                    row.actor,
+                    row.entity_id,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get a user's notifications (newest first)
+    async fn get_notifications(
+        &self,
+        user_id: &str,
+        limit: i32,
+    ) -> Result<Vec<Notification>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_NOTIFICATIONS,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<NotificationRow> = self.client.fetch_all(query, (user_id, limit)).await?;
+        rows.into_iter().map(|row| row.to_notification()).collect()
+    }
+
+    /// Record that everything up to the given time has been read
+    async fn set_read_up_to(
+        &self,
+        user_id: &str,
+        read_up_to: DateTime<Utc>,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_NOTIFICATIONS_READ,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (user_id, read_up_to)).await?;
+
+        Ok(())
+    }
+
+    /// How far the user has read, if they ever marked anything as read
+    async fn get_read_up_to(&self, user_id: &str) -> Result<Option<DateTime<Utc>>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_NOTIFICATIONS_READ,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<NotificationReadRow>(query, (user_id,))
+            .await
+        {
+            Ok(row) => Ok(Some(row.read_up_to)),
+            Err(DbError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use async_trait::async_trait;
+
+use super::store::PreferenceStore;
+use crate::db::{DbClient, DbError, StatementProfile, UserPreferencesRow};
+use crate::domain::UserPreferences;
+
+#[derive(Clone)]
+pub struct PreferenceRepository {
+    client: DbClient,
+}
+
+impl PreferenceRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl PreferenceStore for PreferenceRepository {
+    /// Get a user's preferences
+    async fn get_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_USER_PREFERENCES,
+            StatementProfile::InteractiveRead,
+        );
+
+        match self
+            .client
+            .fetch_one::<UserPreferencesRow>(query, (user_id,))
+            .await
+        {
+            Ok(row) => row.to_preferences().map(Some),
+            Err(DbError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Replace a user's preferences
+    async fn upsert_preferences(&self, preferences: &UserPreferences) -> Result<(), DbError> {
+        let row = UserPreferencesRow::from_preferences(preferences);
+        let query = self.client.statement(
+            crate::db::queries::UPSERT_USER_PREFERENCES,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    row.user_id,
+                    row.default_shares,
+                    row.allow_public_forks,
+                    row.default_page_size,
+                    row.branch_naming,
+                    row.muted_notifications,
+                    row.updated_at,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
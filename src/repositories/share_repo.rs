@@ -1,10 +1,12 @@
+use async_trait::async_trait;
 use chrono::Utc;
-use scylla::IntoTypedRows;
-use scylla::query::Query;
+use futures::StreamExt;
+use scylla::frame::response::result::CqlValue;
 use uuid::Uuid;
 
-use crate::db::{DbClient, DbError, ShareRow, UserConversationRow};
-use crate::domain::Share;
+use super::store::ShareStore;
+use crate::db::{DbClient, DbError, InviteRow, ShareRow, StatementProfile, UserConversationRow};
+use crate::domain::{Invite, Share};
 
 #[derive(Clone)]
 pub struct ShareRepository {
@@ -15,132 +17,243 @@ impl ShareRepository {
     pub fn new(client: DbClient) -> Self {
         Self { client }
     }
+}
 
+#[async_trait]
+impl ShareStore for ShareRepository {
     /// Insert a new share
-    pub async fn insert_share(&self, share: &Share) -> Result<(), DbError> {
-        let row = ShareRow::from_share(share);
-        let query = Query::new(crate::db::queries::INSERT_SHARE);
-
-        self.client
-            .session()
-            .query(
-                query,
-                (
-                    row.conversation_id,
-                    row.shared_with,
-                    row.permission,
-                    row.shared_at,
-                    row.shared_by,
-                ),
-            )
-            .await?;
-
-        Ok(())
+    async fn insert_share(&self, share: &Share) -> Result<(), DbError> {
+        self.apply_share_changes(share.conversation_id, std::slice::from_ref(share), &[])
+            .await
     }
 
     /// Get a specific share
-    pub async fn get_share(
-        &self,
-        conversation_id: Uuid,
-        shared_with: &str,
-    ) -> Result<Share, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_SHARE);
+    async fn get_share(&self, conversation_id: Uuid, shared_with: &str) -> Result<Share, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_SHARE,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
+        let row: ShareRow = self
             .client
-            .session()
-            .query(query, (conversation_id, shared_with))
+            .fetch_one(query, (conversation_id, shared_with))
             .await?;
 
-        let row = result
-            .rows
-            .ok_or(DbError::NotFound)?
-            .into_typed::<ShareRow>()
-            .next()
-            .ok_or(DbError::NotFound)?
-            .map_err(|e| DbError::InvalidData(format!("Failed to parse share row: {}", e)))?;
-
-        row.to_share().map_err(DbError::InvalidData)
+        row.to_share()
     }
 
     /// Get all shares for a conversation
-    pub async fn get_shares_by_conversation(
+    async fn get_shares_by_conversation(
         &self,
         conversation_id: Uuid,
     ) -> Result<Vec<Share>, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_SHARES_BY_CONVERSATION);
+        let query = self.client.statement(
+            crate::db::queries::SELECT_SHARES_BY_CONVERSATION,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self
-            .client
-            .session()
-            .query(query, (conversation_id,))
-            .await?;
-
-        let rows = result.rows.unwrap_or_default();
-        let mut shares = Vec::new();
-
-        for row in rows.into_typed::<ShareRow>() {
-            let row =
-                row.map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-            let share = row.to_share().map_err(DbError::InvalidData)?;
-            shares.push(share);
-        }
+        let rows: Vec<ShareRow> = self.client.fetch_all(query, (conversation_id,)).await?;
+        let shares = rows
+            .into_iter()
+            .map(|row| row.to_share())
+            .collect::<Result<Vec<_>, _>>()?;
 
         Ok(shares)
     }
 
     /// Delete a share
-    pub async fn delete_share(
+    async fn delete_share(&self, conversation_id: Uuid, shared_with: &str) -> Result<(), DbError> {
+        self.apply_share_changes(conversation_id, &[], &[shared_with.to_string()])
+            .await
+    }
+
+    /// Write grants and revocations to both share tables in one logged batch
+    async fn apply_share_changes(
         &self,
         conversation_id: Uuid,
-        shared_with: &str,
+        grants: &[Share],
+        revokes: &[String],
     ) -> Result<(), DbError> {
-        let query = Query::new(crate::db::queries::DELETE_SHARE);
+        let mut batch = self.client.logged_batch();
+        let mut values_list: Vec<Vec<CqlValue>> = Vec::new();
+
+        for share in grants {
+            let row = ShareRow::from_share(share);
+            let conversation_id = CqlValue::Uuid(row.conversation_id);
+            let shared_with = CqlValue::Text(row.shared_with);
+            let permission = CqlValue::Text(row.permission);
+            let shared_at = CqlValue::Timestamp(row.shared_at.into());
+            let shared_by = CqlValue::Text(row.shared_by);
+
+            batch.append_statement(crate::db::queries::INSERT_SHARE);
+            values_list.push(vec![
+                conversation_id.clone(),
+                shared_with.clone(),
+                permission.clone(),
+                shared_at.clone(),
+                shared_by.clone(),
+            ]);
+
+            batch.append_statement(crate::db::queries::INSERT_SHARE_BY_USER);
+            values_list.push(vec![
+                shared_with,
+                conversation_id,
+                permission,
+                shared_at,
+                shared_by,
+            ]);
+        }
+
+        for shared_with in revokes {
+            batch.append_statement(crate::db::queries::DELETE_SHARE);
+            values_list.push(vec![
+                CqlValue::Uuid(conversation_id),
+                CqlValue::Text(shared_with.clone()),
+            ]);
+
+            batch.append_statement(crate::db::queries::DELETE_SHARE_BY_USER);
+            values_list.push(vec![
+                CqlValue::Text(shared_with.clone()),
+                CqlValue::Uuid(conversation_id),
+            ]);
+        }
+
+        if values_list.is_empty() {
+            return Ok(());
+        }
+
+        self.client.execute_batch(&batch, values_list).await
+    }
+
+    /// Get every share granted to a user
+    async fn get_shares_for_user(&self, shared_with: &str) -> Result<Vec<Share>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_SHARES_BY_USER,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<ShareRow> = self.client.fetch_all(query, (shared_with,)).await?;
+        rows.into_iter().map(|row| row.to_share()).collect()
+    }
+
+    /// Copy every share into shares_by_user. Idempotent; scans the whole table.
+    async fn rebuild_user_index(&self) -> Result<usize, DbError> {
+        let scan = self.client.statement(
+            crate::db::queries::SELECT_ALL_SHARES,
+            StatementProfile::BulkRead,
+        );
+        let mut rows = self.client.fetch_stream::<ShareRow>(scan, ()).await?;
+
+        let mut indexed = 0;
+        while let Some(row) = rows.next().await {
+            let row = row?;
+            let query = self.client.statement(
+                crate::db::queries::INSERT_SHARE_BY_USER,
+                StatementProfile::BulkWrite,
+            );
+            self.client
+                .execute(
+                    query,
+                    (
+                        row.shared_with,
+                        row.conversation_id,
+                        row.permission,
+                        row.shared_at,
+                        row.shared_by,
+                    ),
+                )
+                .await?;
+            indexed += 1;
+        }
+
+        Ok(indexed)
+    }
+
+    /// Insert an invite; Scylla drops it once it expires
+    async fn insert_invite(&self, invite: &Invite) -> Result<(), DbError> {
+        let row = InviteRow::from_invite(invite);
+        let ttl = (row.expires_at - row.created_at).num_seconds().max(1) as i32;
+        let query = self.client.statement(
+            crate::db::queries::INSERT_INVITE,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (conversation_id, shared_with))
+            .execute(
+                query,
+                (
+                    row.email,
+                    row.token,
+                    row.conversation_id,
+                    row.permission,
+                    row.invited_by,
+                    row.created_at,
+                    row.expires_at,
+                    ttl,
+                ),
+            )
             .await?;
 
         Ok(())
     }
 
+    /// Get a pending invite
+    async fn get_invite(&self, email: &str, token: &str) -> Result<Invite, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_INVITE,
+            StatementProfile::InteractiveRead,
+        );
+
+        let row: InviteRow = self.client.fetch_one(query, (email, token)).await?;
+
+        row.to_invite()
+    }
+
+    /// Delete an invite
+    async fn delete_invite(&self, email: &str, token: &str) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_INVITE,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client.execute(query, (email, token)).await?;
+
+        Ok(())
+    }
+
     /// Add or update user conversation activity
-    pub async fn upsert_user_conversation(
+    async fn upsert_user_conversation(
         &self,
         user_id: &str,
         conversation_id: Uuid,
         active_branch_id: Option<Uuid>,
     ) -> Result<(), DbError> {
         let now = Utc::now();
-        let query = Query::new(crate::db::queries::INSERT_USER_CONVERSATION);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_USER_CONVERSATION,
+            StatementProfile::InteractiveWrite,
+        );
 
         self.client
-            .session()
-            .query(query, (user_id, now, conversation_id, active_branch_id))
+            .execute(query, (user_id, now, conversation_id, active_branch_id))
             .await?;
 
         Ok(())
     }
 
     /// Get user's conversations (most recent first)
-    pub async fn get_user_conversations(
+    async fn get_user_conversations(
         &self,
         user_id: &str,
         limit: i32,
     ) -> Result<Vec<UserConversationRow>, DbError> {
-        let query = Query::new(crate::db::queries::SELECT_USER_CONVERSATIONS);
+        let query = self.client.statement(
+            crate::db::queries::SELECT_USER_CONVERSATIONS,
+            StatementProfile::InteractiveRead,
+        );
 
-        let result = self.client.session().query(query, (user_id, limit)).await?;
-
-        let rows = result.rows.unwrap_or_default();
-        let mut conversations = Vec::new();
-
-        for row in rows.into_typed::<UserConversationRow>() {
-            let row =
-                row.map_err(|e| DbError::InvalidData(format!("Failed to parse row: {}", e)))?;
-            conversations.push(row);
-        }
+        let conversations: Vec<UserConversationRow> =
+            self.client.fetch_all(query, (user_id, limit)).await?;
 
         Ok(conversations)
     }
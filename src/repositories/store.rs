@@ -0,0 +1,630 @@
+//! Backend-agnostic persistence traits. Services depend on these rather than on a
+//! concrete database so the storage backend can be chosen at startup.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::stream::{BoxStream, TryStreamExt};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::{
+    ActivityRow, AnalyticsRollupRow, ConversationTitleRow, DbClient, DbError, ForkLinkRow,
+    TrendingRow, UserConversationRow,
+};
+use crate::domain::{
+    AccessLogEntry, Branch, BranchSlug, Change, ConversationEvent, ConversationExport,
+    ConversationLock, ForkProgress, Invite, Job, LegalHold, Message, Notification, OutboxEntry,
+    Share, UserPreferences, WebhookSubscription,
+};
+
+use super::memory::{
+    MemoryAccessLogStore, MemoryAnalyticsStore, MemoryBranchStore, MemoryChangeStore,
+    MemoryEventLog, MemoryExportStore, MemoryImageStore, MemoryJobStore, MemoryLineageStore,
+    MemoryNotificationStore, MemoryPreferenceStore, MemoryShareStore, MemoryTrendingStore,
+    MemoryWebhookStore,
+};
+use super::{
+    AccessLogRepository, AnalyticsRepository, BranchRepository, ChangeRepository, ContentBlobs,
+    ContentCipher, ExportRepository, ImageRepository, JobRepository, LineageRepository,
+    NotificationRepository, PreferenceRepository, ShareRepository, TrendingRepository,
+    WebhookRepository,
+};
+
+/// Messages and checkpoints of conversation trees
+#[async_trait]
+pub trait LineageStore: Send + Sync {
+    async fn insert_message(&self, message: &Message) -> Result<(), DbError>;
+
+    async fn get_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<Message, DbError>;
+
+    async fn get_children(
+        &self,
+        conversation_id: Uuid,
+        parent_message_id: Uuid,
+    ) -> Result<Vec<Message>, DbError>;
+
+    /// Number of messages in a conversation, the root included, counted
+    /// over the whole partition. Size checks on writes read the maintained
+    /// counters instead.
+    async fn count_messages(&self, conversation_id: Uuid) -> Result<usize, DbError>;
+
+    /// Number of messages in a conversation, the root included, as kept by
+    /// its counter
+    async fn get_message_count(&self, conversation_id: Uuid) -> Result<usize, DbError>;
+
+    /// Number of direct replies to a message, as kept by its counter
+    async fn get_reply_count(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<usize, DbError>;
+
+    /// Add `messages` to the message counter of a conversation and each
+    /// `(message_id, replies)` to the reply counter of that message. Not
+    /// idempotent: writers call it once for the messages they add, move or
+    /// remove. Deleting the conversation drops the counters.
+    async fn add_message_counts(
+        &self,
+        conversation_id: Uuid,
+        messages: i64,
+        replies: &[(Uuid, i64)],
+    ) -> Result<(), DbError>;
+
+    /// Messages with the given IDs, sorted by lineage depth
+    async fn get_messages_by_ids(
+        &self,
+        conversation_id: Uuid,
+        message_ids: &[Uuid],
+    ) -> Result<Vec<Message>, DbError>;
+
+    /// Every message of a conversation, as the pages of the partition come
+    /// in; `page_size` rows at a time, or the configured page size. Only
+    /// callers that need the whole tree at once should collect it, e.g.
+    /// through `get_all_messages`.
+    async fn stream_all_messages(
+        &self,
+        conversation_id: Uuid,
+        page_size: Option<i32>,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError>;
+
+    /// Every message of a conversation, collected from `stream_all_messages`
+    async fn get_all_messages(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError> {
+        self.stream_all_messages(conversation_id, None)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    /// The root of a conversation. Stops paging once it is found, which for
+    /// conversations written by this service is on the first page.
+    async fn get_root_message(&self, conversation_id: Uuid) -> Result<Message, DbError> {
+        self.stream_all_messages(conversation_id, None)
+            .await?
+            .try_filter(|m| futures::future::ready(m.is_root()))
+            .try_next()
+            .await?
+            .ok_or(DbError::NotFound)
+    }
+
+    /// Stream every message of every conversation. Scans the whole table;
+    /// meant for one-off backfills, not request handling.
+    async fn scan_messages(
+        &self,
+        page_size: i32,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError>;
+
+    /// Delete all messages, checkpoints and message counters of a conversation
+    async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), DbError>;
+
+    async fn insert_checkpoint(&self, checkpoint: &Message) -> Result<(), DbError>;
+
+    async fn get_checkpoints(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError>;
+
+    async fn batch_insert_messages(&self, messages: &[Message]) -> Result<(), DbError>;
+
+    /// Insert messages and append events to the event log in one logged
+    /// batch, so neither is written without the other
+    async fn insert_messages_with_events(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError>;
+
+    /// Insert messages copied by a fork or duplicate with their events,
+    /// written the way `scylla.fork_batch_strategy` says. Unless it's
+    /// `logged`, a failed call may have written part of them.
+    async fn insert_copied_messages(
+        &self,
+        messages: &[Message],
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError>;
+
+    /// Events with a sequence number of at least `from_seq`, oldest first
+    async fn get_events(
+        &self,
+        conversation_id: Uuid,
+        from_seq: i64,
+        limit: i32,
+    ) -> Result<Vec<ConversationEvent>, DbError>;
+
+    /// Delete the whole event log of a conversation, numbering included.
+    /// Called after `delete_conversation`, so it also deletes the
+    /// conversation's data keys.
+    async fn delete_events(&self, conversation_id: Uuid) -> Result<(), DbError>;
+
+    /// Re-wrap every conversation's data keys under the active master key,
+    /// returning how many were re-wrapped. Fails unless encryption at rest
+    /// is enabled.
+    async fn rewrap_data_keys(&self) -> Result<usize, DbError>;
+
+    /// Add or retitle a conversation in its creator's title index
+    async fn upsert_conversation_title(&self, entry: &ConversationTitleRow) -> Result<(), DbError>;
+
+    async fn delete_conversation_title(
+        &self,
+        user_id: &str,
+        conversation_id: Uuid,
+    ) -> Result<(), DbError>;
+
+    /// All title index entries of the conversations a user created
+    async fn get_conversation_titles(
+        &self,
+        user_id: &str,
+    ) -> Result<Vec<ConversationTitleRow>, DbError>;
+
+    /// Record a fork in its source conversation's fork index
+    async fn upsert_fork_link(&self, link: &ForkLinkRow) -> Result<(), DbError>;
+
+    async fn delete_fork_link(
+        &self,
+        source_conversation_id: Uuid,
+        fork_conversation_id: Uuid,
+    ) -> Result<(), DbError>;
+
+    /// The direct forks of a conversation
+    async fn get_fork_links(
+        &self,
+        source_conversation_id: Uuid,
+    ) -> Result<Vec<ForkLinkRow>, DbError>;
+
+    async fn insert_lock(&self, lock: &ConversationLock) -> Result<(), DbError>;
+
+    /// The conversation's lock, `None` if it isn't locked
+    async fn get_lock(&self, conversation_id: Uuid) -> Result<Option<ConversationLock>, DbError>;
+
+    async fn delete_lock(&self, conversation_id: Uuid) -> Result<(), DbError>;
+
+    async fn insert_hold(&self, hold: &LegalHold) -> Result<(), DbError>;
+
+    /// The conversation's legal hold, `None` if it has none
+    async fn get_hold(&self, conversation_id: Uuid) -> Result<Option<LegalHold>, DbError>;
+
+    /// Every legal hold, in no particular order
+    async fn get_holds(&self) -> Result<Vec<LegalHold>, DbError>;
+
+    async fn delete_hold(&self, conversation_id: Uuid) -> Result<(), DbError>;
+
+    async fn upsert_fork_progress(&self, progress: &ForkProgress) -> Result<(), DbError>;
+
+    /// Every unfinished fork, in no particular order
+    async fn get_fork_progress(&self) -> Result<Vec<ForkProgress>, DbError>;
+
+    async fn delete_fork_progress(&self, conversation_id: Uuid) -> Result<(), DbError>;
+}
+
+/// Named branches and the leaf -> branch index
+#[async_trait]
+pub trait BranchStore: Send + Sync {
+    /// Insert a branch and claim its slug
+    async fn insert_branch(&self, branch: &Branch) -> Result<(), DbError>;
+
+    async fn get_branch(&self, conversation_id: Uuid, branch_id: Uuid) -> Result<Branch, DbError>;
+
+    async fn get_branches_by_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<Branch>, DbError>;
+
+    /// Move a branch to a new leaf, appending `events` to the conversation's
+    /// event log in the same batch
+    async fn update_branch_leaf(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        old_leaf_id: Uuid,
+        new_leaf_id: Uuid,
+        events: &[ConversationEvent],
+    ) -> Result<(), DbError>;
+
+    /// Rename a branch and claim `new_slug` for it. The branch's earlier
+    /// slugs stay claimed and keep resolving to it.
+    async fn update_branch_name(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        new_name: String,
+        new_slug: String,
+    ) -> Result<(), DbError>;
+
+    /// Delete a branch, releasing all of its slugs
+    async fn delete_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        leaf_message_id: Uuid,
+    ) -> Result<(), DbError>;
+
+    /// (conversation_id, branch_id) of the branch ending at the given leaf
+    async fn get_branch_by_leaf(&self, leaf_message_id: Uuid) -> Result<(Uuid, Uuid), DbError>;
+
+    /// Every slug claimed in the conversation, current or from earlier names
+    async fn get_branch_slugs(&self, conversation_id: Uuid) -> Result<Vec<BranchSlug>, DbError>;
+
+    async fn get_branch_slug(
+        &self,
+        conversation_id: Uuid,
+        slug: &str,
+    ) -> Result<BranchSlug, DbError>;
+}
+
+/// Shares, pending invites and per-user conversation activity
+#[async_trait]
+pub trait ShareStore: Send + Sync {
+    async fn insert_share(&self, share: &Share) -> Result<(), DbError>;
+
+    async fn get_share(&self, conversation_id: Uuid, shared_with: &str) -> Result<Share, DbError>;
+
+    async fn get_shares_by_conversation(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<Share>, DbError>;
+
+    async fn delete_share(&self, conversation_id: Uuid, shared_with: &str) -> Result<(), DbError>;
+
+    /// Grant (or change) and revoke several shares of one conversation at once
+    async fn apply_share_changes(
+        &self,
+        conversation_id: Uuid,
+        grants: &[Share],
+        revokes: &[String],
+    ) -> Result<(), DbError>;
+
+    /// Every share granted to a user, across conversations
+    async fn get_shares_for_user(&self, shared_with: &str) -> Result<Vec<Share>, DbError>;
+
+    /// Re-derive the by-user index from all shares; returns how many were indexed
+    async fn rebuild_user_index(&self) -> Result<usize, DbError>;
+
+    async fn insert_invite(&self, invite: &Invite) -> Result<(), DbError>;
+
+    async fn get_invite(&self, email: &str, token: &str) -> Result<Invite, DbError>;
+
+    async fn delete_invite(&self, email: &str, token: &str) -> Result<(), DbError>;
+
+    async fn upsert_user_conversation(
+        &self,
+        user_id: &str,
+        conversation_id: Uuid,
+        active_branch_id: Option<Uuid>,
+    ) -> Result<(), DbError>;
+
+    /// Most recent activity first
+    async fn get_user_conversations(
+        &self,
+        user_id: &str,
+        limit: i32,
+    ) -> Result<Vec<UserConversationRow>, DbError>;
+}
+
+/// Per-user defaults
+#[async_trait]
+pub trait PreferenceStore: Send + Sync {
+    /// `None` if the user never saved any
+    async fn get_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>, DbError>;
+
+    async fn upsert_preferences(&self, preferences: &UserPreferences) -> Result<(), DbError>;
+}
+
+/// Background jobs and their progress
+#[async_trait]
+pub trait JobStore: Send + Sync {
+    /// Insert or update a job. Never clears a cancel request.
+    async fn save_job(&self, job: &Job) -> Result<(), DbError>;
+
+    async fn get_job(&self, job_id: Uuid) -> Result<Job, DbError>;
+
+    /// Newest first
+    async fn get_jobs_for_user(&self, user_id: &str, limit: i32) -> Result<Vec<Job>, DbError>;
+
+    /// Jobs not yet finished, across all instances
+    async fn get_running_jobs(&self) -> Result<Vec<Job>, DbError>;
+
+    async fn request_cancel(&self, job_id: Uuid) -> Result<(), DbError>;
+}
+
+/// Conversation exports written to the object store
+#[async_trait]
+pub trait ExportStore: Send + Sync {
+    /// Record a requested export. Never clears a result already recorded.
+    async fn insert_export(&self, export: &ConversationExport) -> Result<(), DbError>;
+
+    async fn complete_export(
+        &self,
+        conversation_id: Uuid,
+        created_at: DateTime<Utc>,
+        export_id: Uuid,
+        object_key: &str,
+        size_bytes: i64,
+        completed_at: DateTime<Utc>,
+    ) -> Result<(), DbError>;
+
+    async fn get_export(
+        &self,
+        conversation_id: Uuid,
+        created_at: DateTime<Utc>,
+        export_id: Uuid,
+    ) -> Result<ConversationExport, DbError>;
+
+    /// Newest first
+    async fn get_exports(
+        &self,
+        conversation_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<ConversationExport>, DbError>;
+}
+
+/// Per-conversation change feed
+#[async_trait]
+pub trait ChangeStore: Send + Sync {
+    async fn insert_change(&self, change: &Change) -> Result<(), DbError>;
+
+    /// Changes strictly after the given position, oldest first
+    async fn get_changes_since(
+        &self,
+        conversation_id: Uuid,
+        since: DateTime<Utc>,
+        after_change_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<Change>, DbError>;
+
+    async fn delete_changes(&self, conversation_id: Uuid) -> Result<(), DbError>;
+
+    /// Rewrite the conversation's changes so they never expire. Returns how
+    /// many were rewritten.
+    async fn retain_changes(&self, conversation_id: Uuid) -> Result<usize, DbError>;
+}
+
+/// Daily activity counters and the rankings computed from them
+#[async_trait]
+pub trait TrendingStore: Send + Sync {
+    /// Add to a conversation's counters for a UTC day (`YYYY-MM-DD`)
+    async fn increment_activity(
+        &self,
+        day: &str,
+        conversation_id: Uuid,
+        views: i64,
+        forks: i64,
+    ) -> Result<(), DbError>;
+
+    async fn get_activity(&self, day: &str) -> Result<Vec<ActivityRow>, DbError>;
+
+    /// Replace a ranking with the given entries, ordered by rank
+    async fn replace_trending(&self, bucket: &str, entries: &[TrendingRow]) -> Result<(), DbError>;
+
+    /// Best ranked first
+    async fn get_trending(&self, bucket: &str, limit: i32) -> Result<Vec<TrendingRow>, DbError>;
+}
+
+/// Per-user notifications and how far each user has read them
+#[async_trait]
+pub trait NotificationStore: Send + Sync {
+    async fn insert_notification(&self, notification: &Notification) -> Result<(), DbError>;
+
+    /// Newest first
+    async fn get_notifications(
+        &self,
+        user_id: &str,
+        limit: i32,
+    ) -> Result<Vec<Notification>, DbError>;
+
+    async fn set_read_up_to(&self, user_id: &str, read_up_to: DateTime<Utc>)
+    -> Result<(), DbError>;
+
+    async fn get_read_up_to(&self, user_id: &str) -> Result<Option<DateTime<Utc>>, DbError>;
+}
+
+/// Sampled reads of each conversation
+#[async_trait]
+pub trait AccessLogStore: Send + Sync {
+    async fn insert_entry(&self, entry: &AccessLogEntry) -> Result<(), DbError>;
+
+    /// Entries older than `before`, newest first
+    async fn get_entries(
+        &self,
+        conversation_id: Uuid,
+        before: DateTime<Utc>,
+        limit: i32,
+    ) -> Result<Vec<AccessLogEntry>, DbError>;
+}
+
+/// Object storage keys referenced by each conversation, and keys no
+/// conversation references any more, waiting to be deleted
+#[async_trait]
+pub trait ImageStore: Send + Sync {
+    /// Record that a conversation references these keys
+    async fn add_image_refs(
+        &self,
+        conversation_id: Uuid,
+        storage_keys: &[String],
+    ) -> Result<(), DbError>;
+
+    async fn get_image_keys(&self, conversation_id: Uuid) -> Result<Vec<String>, DbError>;
+
+    /// Drop every reference a conversation holds; `storage_keys` are the keys
+    /// it references
+    async fn delete_image_refs(
+        &self,
+        conversation_id: Uuid,
+        storage_keys: &[String],
+    ) -> Result<(), DbError>;
+
+    /// Whether any conversation still references a key
+    async fn is_image_referenced(&self, storage_key: &str) -> Result<bool, DbError>;
+
+    async fn queue_image_deletions(&self, storage_keys: &[String]) -> Result<(), DbError>;
+
+    /// Up to `limit` keys waiting to be deleted, in no particular order
+    async fn get_pending_image_deletions(&self, limit: i32) -> Result<Vec<String>, DbError>;
+
+    async fn remove_pending_image_deletion(&self, storage_key: &str) -> Result<(), DbError>;
+}
+
+/// Daily rollups computed by the analytics job
+#[async_trait]
+pub trait AnalyticsStore: Send + Sync {
+    /// Write rollups, replacing those already stored for the same scope and day
+    async fn upsert_rollups(&self, rollups: &[AnalyticsRollupRow]) -> Result<(), DbError>;
+
+    /// Rollups of a scope between two UTC days (`YYYY-MM-DD`, both included),
+    /// oldest first
+    async fn get_rollups(
+        &self,
+        scope: &str,
+        from_day: &str,
+        to_day: &str,
+    ) -> Result<Vec<AnalyticsRollupRow>, DbError>;
+}
+
+/// The CDC log of the lineage table and the outbox it is copied into. Only
+/// the Scylla backend has one, so it isn't part of [`Storage`].
+#[async_trait]
+pub trait CdcStore: Send + Sync {
+    /// Turn on CDC for the lineage table
+    async fn enable_lineage_cdc(&self) -> Result<(), DbError>;
+
+    /// Changes to the lineage table made in `(from, to]`, oldest first
+    async fn get_lineage_changes(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEntry>, DbError>;
+
+    async fn insert_outbox_entries(&self, entries: &[OutboxEntry]) -> Result<(), DbError>;
+
+    /// How far a consumer got; `None` before its first run
+    async fn get_checkpoint(&self, consumer: &str) -> Result<Option<DateTime<Utc>>, DbError>;
+
+    async fn set_checkpoint(
+        &self,
+        consumer: &str,
+        processed_up_to: DateTime<Utc>,
+    ) -> Result<(), DbError>;
+}
+
+/// Webhook subscriptions of each conversation and how far their deliveries got
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    async fn insert_subscription(&self, subscription: &WebhookSubscription) -> Result<(), DbError>;
+
+    async fn get_subscriptions(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<WebhookSubscription>, DbError>;
+
+    /// Every subscription of every conversation
+    async fn get_all_subscriptions(&self) -> Result<Vec<WebhookSubscription>, DbError>;
+
+    /// Move a subscription's delivery position; a deleted subscription stays deleted
+    async fn set_delivered_up_to(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+        delivered_up_to: (DateTime<Utc>, Uuid),
+    ) -> Result<(), DbError>;
+
+    async fn delete_subscription(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+    ) -> Result<(), DbError>;
+}
+
+/// The set of stores backing the service
+#[derive(Clone)]
+pub struct Storage {
+    pub lineage: Arc<dyn LineageStore>,
+    pub branches: Arc<dyn BranchStore>,
+    pub shares: Arc<dyn ShareStore>,
+    pub changes: Arc<dyn ChangeStore>,
+    pub trending: Arc<dyn TrendingStore>,
+    pub notifications: Arc<dyn NotificationStore>,
+    pub preferences: Arc<dyn PreferenceStore>,
+    pub images: Arc<dyn ImageStore>,
+    pub analytics: Arc<dyn AnalyticsStore>,
+    pub jobs: Arc<dyn JobStore>,
+    pub exports: Arc<dyn ExportStore>,
+    pub webhooks: Arc<dyn WebhookStore>,
+    pub access_log: Arc<dyn AccessLogStore>,
+}
+
+impl Storage {
+    pub fn scylla(client: DbClient) -> Self {
+        Self {
+            lineage: Arc::new(LineageRepository::new(client.clone())),
+            branches: Arc::new(BranchRepository::new(client.clone())),
+            shares: Arc::new(ShareRepository::new(client.clone())),
+            changes: Arc::new(ChangeRepository::new(client.clone())),
+            trending: Arc::new(TrendingRepository::new(client.clone())),
+            notifications: Arc::new(NotificationRepository::new(client.clone())),
+            preferences: Arc::new(PreferenceRepository::new(client.clone())),
+            images: Arc::new(ImageRepository::new(client.clone())),
+            analytics: Arc::new(AnalyticsRepository::new(client.clone())),
+            jobs: Arc::new(JobRepository::new(client.clone())),
+            exports: Arc::new(ExportRepository::new(client.clone())),
+            webhooks: Arc::new(WebhookRepository::new(client.clone())),
+            access_log: Arc::new(AccessLogRepository::new(client)),
+        }
+    }
+
+    /// Scylla storage that keeps large message contents in `blobs` and, with
+    /// a `cipher`, encrypts message contents and event payloads
+    pub fn scylla_with_content(
+        client: DbClient,
+        blobs: Arc<ContentBlobs>,
+        cipher: Option<Arc<ContentCipher>>,
+    ) -> Self {
+        let mut lineage = LineageRepository::new(client.clone()).with_blobs(blobs);
+        if let Some(cipher) = cipher {
+            lineage = lineage.with_cipher(cipher);
+        }
+
+        Self {
+            lineage: Arc::new(lineage),
+            ..Self::scylla(client)
+        }
+    }
+
+    /// Process-local storage, for development, tests and benchmarks. Nothing is persisted.
+    pub fn memory() -> Self {
+        let events = MemoryEventLog::default();
+        Self {
+            lineage: Arc::new(MemoryLineageStore::with_events(events.clone())),
+            branches: Arc::new(MemoryBranchStore::with_events(events)),
+            shares: Arc::new(MemoryShareStore::default()),
+            changes: Arc::new(MemoryChangeStore::default()),
+            trending: Arc::new(MemoryTrendingStore::default()),
+            notifications: Arc::new(MemoryNotificationStore::default()),
+            preferences: Arc::new(MemoryPreferenceStore::default()),
+            images: Arc::new(MemoryImageStore::default()),
+            analytics: Arc::new(MemoryAnalyticsStore::default()),
+            jobs: Arc::new(MemoryJobStore::default()),
+            exports: Arc::new(MemoryExportStore::default()),
+            webhooks: Arc::new(MemoryWebhookStore::default()),
+            access_log: Arc::new(MemoryAccessLogStore::default()),
+        }
+    }
+}
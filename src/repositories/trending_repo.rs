@@ -0,0 +1,99 @@
+use async_trait::async_trait;
+use scylla::frame::value::Counter;
+use uuid::Uuid;
+
+use super::store::TrendingStore;
+use crate::db::{ActivityRow, DbClient, DbError, StatementProfile, TrendingRow};
+
+#[derive(Clone)]
+pub struct TrendingRepository {
+    client: DbClient,
+}
+
+impl TrendingRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl TrendingStore for TrendingRepository {
+    /// Add to the view and fork counters of a conversation for one day
+    async fn increment_activity(
+        &self,
+        day: &str,
+        conversation_id: Uuid,
+        views: i64,
+        forks: i64,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::INCREMENT_ACTIVITY,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (Counter(views), Counter(forks), day, conversation_id),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the counters of every conversation active on a day (one partition)
+    async fn get_activity(&self, day: &str) -> Result<Vec<ActivityRow>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_ACTIVITY_BY_DAY,
+            StatementProfile::BulkRead,
+        );
+
+        self.client.fetch_all(query, (day,)).await
+    }
+
+    /// Overwrite a ranking: write all ranks, then drop ranks left over from a longer one
+    async fn replace_trending(&self, bucket: &str, entries: &[TrendingRow]) -> Result<(), DbError> {
+        if !entries.is_empty() {
+            let mut batch = self.client.batch();
+            let mut values_list = Vec::new();
+
+            for entry in entries {
+                batch.append_statement(crate::db::queries::INSERT_TRENDING);
+                values_list.push((
+                    bucket,
+                    entry.rank,
+                    entry.conversation_id,
+                    &entry.title,
+                    entry.score,
+                    entry.view_count,
+                    entry.fork_count,
+                    entry.computed_at,
+                    &entry.language,
+                ));
+            }
+
+            self.client.execute_batch(&batch, values_list).await?;
+        }
+
+        let query = self.client.statement(
+            crate::db::queries::DELETE_TRENDING_FROM_RANK,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(query, (bucket, entries.len() as i32))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Get the top of a ranking, best first
+    async fn get_trending(&self, bucket: &str, limit: i32) -> Result<Vec<TrendingRow>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_TRENDING,
+            StatementProfile::InteractiveRead,
+        );
+
+        self.client.fetch_all(query, (bucket, limit)).await
+    }
+}
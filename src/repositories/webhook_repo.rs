@@ -0,0 +1,120 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use super::store::WebhookStore;
+use crate::db::{DbClient, DbError, StatementProfile, WebhookSubscriptionRow};
+use crate::domain::WebhookSubscription;
+
+#[derive(Clone)]
+pub struct WebhookRepository {
+    client: DbClient,
+}
+
+impl WebhookRepository {
+    pub fn new(client: DbClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl WebhookStore for WebhookRepository {
+    async fn insert_subscription(&self, subscription: &WebhookSubscription) -> Result<(), DbError> {
+        let row = WebhookSubscriptionRow::from_subscription(subscription);
+        let query = self.client.statement(
+            crate::db::queries::INSERT_WEBHOOK_SUBSCRIPTION,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    row.conversation_id,
+                    row.subscription_id,
+                    row.url,
+                    row.created_by,
+                    row.created_at,
+                    row.event_types,
+                    row.roles,
+                    row.content_types,
+                    row.secret,
+                    row.delivered_at,
+                    row.delivered_change_id,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_subscriptions(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<WebhookSubscription>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_WEBHOOK_SUBSCRIPTIONS,
+            StatementProfile::InteractiveRead,
+        );
+
+        let rows: Vec<WebhookSubscriptionRow> =
+            self.client.fetch_all(query, (conversation_id,)).await?;
+        rows.into_iter().map(|row| row.to_subscription()).collect()
+    }
+
+    /// Scans the whole table; subscriptions are few
+    async fn get_all_subscriptions(&self) -> Result<Vec<WebhookSubscription>, DbError> {
+        let query = self.client.statement(
+            crate::db::queries::SELECT_ALL_WEBHOOK_SUBSCRIPTIONS,
+            StatementProfile::BulkRead,
+        );
+
+        let rows: Vec<WebhookSubscriptionRow> = self.client.fetch_all(query, ()).await?;
+        rows.into_iter().map(|row| row.to_subscription()).collect()
+    }
+
+    /// A lightweight transaction, so that a delivery finishing after the
+    /// subscription was deleted doesn't bring back part of its row
+    async fn set_delivered_up_to(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+        (delivered_at, delivered_change_id): (DateTime<Utc>, Uuid),
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::UPDATE_WEBHOOK_DELIVERED,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(
+                query,
+                (
+                    delivered_at,
+                    delivered_change_id,
+                    conversation_id,
+                    subscription_id,
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_subscription(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+    ) -> Result<(), DbError> {
+        let query = self.client.statement(
+            crate::db::queries::DELETE_WEBHOOK_SUBSCRIPTION,
+            StatementProfile::InteractiveWrite,
+        );
+
+        self.client
+            .execute(query, (conversation_id, subscription_id))
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+//! Recurring background tasks. Services implement [`ScheduledTask`] and are
+//! registered with the [`Scheduler`], which runs each on its own interval,
+//! records how the runs went for the health check and stops them on shutdown.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::FutureExt;
+use std::collections::BTreeMap;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::SchedulerConfig;
+
+pub type TaskError = Box<dyn std::error::Error + Send + Sync>;
+
+#[async_trait]
+pub trait ScheduledTask: Send + Sync {
+    /// Unique name used in logs and health reports
+    fn name(&self) -> &'static str;
+
+    /// One run of the task. A run is abandoned on shutdown, so it must be
+    /// safe to interrupt and repeat.
+    async fn run(&self) -> Result<(), TaskError>;
+}
+
+/// How a registered task has been doing
+#[derive(Debug, Clone)]
+pub struct TaskHealth {
+    pub name: &'static str,
+    pub interval: Duration,
+    /// False when scheduling is disabled in this instance
+    pub enabled: bool,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    pub last_started_at: Option<DateTime<Utc>>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    /// Error of the latest run, cleared by the next success
+    pub last_error: Option<String>,
+}
+
+impl TaskHealth {
+    pub fn is_failing(&self) -> bool {
+        self.last_error.is_some()
+    }
+}
+
+pub struct Scheduler {
+    config: SchedulerConfig,
+    shutdown: CancellationToken,
+    health: Arc<Mutex<BTreeMap<&'static str, TaskHealth>>>,
+    handles: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Scheduler {
+    pub fn new(config: SchedulerConfig, shutdown: CancellationToken) -> Self {
+        Self {
+            config,
+            shutdown,
+            health: Arc::new(Mutex::new(BTreeMap::new())),
+            handles: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Run `task` now and then every `interval` until shutdown. A run that
+    /// overlaps the next tick delays it rather than running twice.
+    pub fn register(&self, task: Arc<dyn ScheduledTask>, interval: Duration) {
+        let name = task.name();
+        lock(&self.health).insert(
+            name,
+            TaskHealth {
+                name,
+                interval,
+                enabled: self.config.enabled,
+                running: false,
+                runs: 0,
+                failures: 0,
+                last_started_at: None,
+                last_success_at: None,
+                last_error: None,
+            },
+        );
+        if !self.config.enabled {
+            tracing::info!("Scheduling is disabled; not running task `{}`", name);
+            return;
+        }
+
+        let shutdown = self.shutdown.clone();
+        let health = self.health.clone();
+        let handle = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval(interval);
+            ticks.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    _ = ticks.tick() => {}
+                }
+
+                update(&health, name, |h| {
+                    h.running = true;
+                    h.last_started_at = Some(Utc::now());
+                });
+                let result = tokio::select! {
+                    _ = shutdown.cancelled() => break,
+                    result = AssertUnwindSafe(task.run()).catch_unwind() => result,
+                };
+                let error = match result {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some(e.to_string()),
+                    Err(_) => Some("task panicked".to_string()),
+                };
+
+                if let Some(e) = &error {
+                    tracing::warn!("Scheduled task `{}` failed: {}", name, e);
+                }
+                update(&health, name, |h| {
+                    h.running = false;
+                    h.runs += 1;
+                    match error {
+                        Some(e) => {
+                            h.failures += 1;
+                            h.last_error = Some(e);
+                        }
+                        None => {
+                            h.last_success_at = Some(Utc::now());
+                            h.last_error = None;
+                        }
+                    }
+                });
+            }
+        });
+        lock(&self.handles).push(handle);
+    }
+
+    /// Health of every registered task, by name
+    pub fn health(&self) -> Vec<TaskHealth> {
+        lock(&self.health).values().cloned().collect()
+    }
+
+    /// Wait for every task loop to stop; call after the shutdown token is cancelled
+    pub async fn join(&self) {
+        let handles = std::mem::take(&mut *lock(&self.handles));
+        for handle in handles {
+            if let Err(e) = handle.await {
+                tracing::warn!("Scheduled task ended abnormally: {}", e);
+            }
+        }
+    }
+}
+
+fn update(
+    health: &Mutex<BTreeMap<&'static str, TaskHealth>>,
+    name: &'static str,
+    f: impl FnOnce(&mut TaskHealth),
+) {
+    if let Some(task) = lock(health).get_mut(name) {
+        f(task);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().expect("scheduler lock poisoned")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    struct Counter {
+        name: &'static str,
+        runs: AtomicU64,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl ScheduledTask for Counter {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn run(&self) -> Result<(), TaskError> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            if self.fail {
+                return Err("boom".into());
+            }
+            Ok(())
+        }
+    }
+
+    fn counter(name: &'static str, fail: bool) -> Arc<Counter> {
+        Arc::new(Counter {
+            name,
+            runs: AtomicU64::new(0),
+            fail,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_tasks_run_report_health_and_stop_on_shutdown() {
+        let shutdown = CancellationToken::new();
+        let scheduler = Scheduler::new(SchedulerConfig { enabled: true }, shutdown.clone());
+        let ok = counter("ok", false);
+        let failing = counter("failing", true);
+        scheduler.register(ok.clone(), Duration::from_millis(5));
+        scheduler.register(failing.clone(), Duration::from_millis(5));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        shutdown.cancel();
+        scheduler.join().await;
+
+        let runs = ok.runs.load(Ordering::SeqCst);
+        assert!(runs >= 1);
+        let health = scheduler.health();
+        assert_eq!(health.len(), 2);
+        let (failing_health, ok_health) = (&health[0], &health[1]);
+        assert_eq!(ok_health.runs, runs);
+        assert!(ok_health.last_success_at.is_some() && !ok_health.is_failing());
+        assert_eq!(failing_health.failures, failing_health.runs);
+        assert_eq!(failing_health.last_error.as_deref(), Some("boom"));
+
+        // Nothing runs once stopped
+        tokio::time::sleep(Duration::from_millis(15)).await;
+        assert_eq!(ok.runs.load(Ordering::SeqCst), runs);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_scheduler_only_registers() {
+        let scheduler =
+            Scheduler::new(SchedulerConfig { enabled: false }, CancellationToken::new());
+        let task = counter("idle", false);
+        scheduler.register(task.clone(), Duration::from_millis(1));
+        scheduler.join().await;
+
+        assert_eq!(task.runs.load(Ordering::SeqCst), 0);
+        assert!(!scheduler.health()[0].enabled);
+    }
+}
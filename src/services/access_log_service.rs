@@ -0,0 +1,133 @@
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::config::AccessLogConfig;
+use crate::db::DbError;
+use crate::domain::AccessLogEntry;
+use crate::repositories::AccessLogStore;
+
+/// A page of a conversation's access log
+#[derive(Debug)]
+pub struct AccessLogPage {
+    pub entries: Vec<AccessLogEntry>,
+    /// Pass as `before` to read the next page; `None` at the end
+    pub next_before: Option<DateTime<Utc>>,
+}
+
+/// Records sampled reads of conversations and lists them for their owners
+pub struct AccessLogService {
+    store: Arc<dyn AccessLogStore>,
+    config: AccessLogConfig,
+}
+
+impl AccessLogService {
+    pub fn new(store: Arc<dyn AccessLogStore>, config: AccessLogConfig) -> Self {
+        Self { store, config }
+    }
+
+    /// Whether the next read should be recorded, per `access_log.sample_rate`
+    pub fn should_record(&self) -> bool {
+        self.config.enabled && rand::thread_rng().gen_bool(self.config.sample_rate)
+    }
+
+    /// Record a read. Failing to doesn't fail the read.
+    pub async fn record(&self, entry: &AccessLogEntry) {
+        if let Err(e) = self.store.insert_entry(entry).await {
+            tracing::warn!(
+                "Failed to record a read of conversation {}: {}",
+                entry.conversation_id,
+                e
+            );
+        }
+    }
+
+    /// Reads older than `before` (or all), newest first, leaving out the
+    /// owner's own. A page can hold fewer than `limit` entries and still have
+    /// a next one.
+    pub async fn get_entries(
+        &self,
+        conversation_id: Uuid,
+        owner: &str,
+        before: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<AccessLogPage, DbError> {
+        let entries = self
+            .store
+            .get_entries(
+                conversation_id,
+                before.unwrap_or(DateTime::<Utc>::MAX_UTC),
+                limit as i32,
+            )
+            .await?;
+        let next_before = (entries.len() == limit)
+            .then(|| entries.last().map(|entry| entry.accessed_at))
+            .flatten();
+
+        Ok(AccessLogPage {
+            entries: entries
+                .into_iter()
+                .filter(|entry| entry.viewer.as_deref() != Some(owner))
+                .collect(),
+            next_before,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::Storage;
+
+    #[tokio::test]
+    async fn test_reads_are_listed_newest_first_without_the_owner() {
+        let storage = Storage::memory();
+        let service = AccessLogService::new(
+            storage.access_log.clone(),
+            AccessLogConfig {
+                enabled: true,
+                sample_rate: 1.0,
+            },
+        );
+        assert!(service.should_record());
+        let cid = Uuid::new_v4();
+        let branch_id = Uuid::new_v4();
+
+        let mut entries = Vec::new();
+        for (i, (viewer, resource)) in [
+            (Some("owner"), "tree".to_string()),
+            (Some("user_b"), format!("branches/{}/messages", branch_id)),
+            (None, String::new()),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let mut entry = AccessLogEntry::new(cid, viewer.map(str::to_string), false, resource);
+            entry.accessed_at += chrono::Duration::seconds(i as i64);
+            service.record(&entry).await;
+            entries.push(entry);
+        }
+
+        let page = service.get_entries(cid, "owner", None, 2).await.unwrap();
+        assert_eq!(page.entries, vec![entries[2].clone(), entries[1].clone()]);
+        assert_eq!(page.entries[1].branch_id, Some(branch_id));
+        assert_eq!(page.next_before, Some(entries[1].accessed_at));
+
+        let page = service
+            .get_entries(cid, "owner", page.next_before, 2)
+            .await
+            .unwrap();
+        assert!(page.entries.is_empty());
+        assert_eq!(page.next_before, None);
+
+        let disabled = AccessLogService::new(
+            storage.access_log.clone(),
+            AccessLogConfig {
+                enabled: false,
+                sample_rate: 1.0,
+            },
+        );
+        assert!(!disabled.should_record());
+    }
+}
@@ -0,0 +1,424 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use futures::StreamExt;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::config::AnalyticsConfig;
+use crate::db::{AnalyticsRollupRow, DbError};
+use crate::domain::{ContentType, Message};
+use crate::repositories::{AnalyticsStore, LineageStore};
+use crate::scheduler::{ScheduledTask, TaskError};
+
+/// Messages read per page while scanning
+const SCAN_PAGE_SIZE: i32 = 1000;
+
+/// Scope of the rollups covering the whole deployment
+const GLOBAL_SCOPE: &str = "global";
+
+/// Content metadata holding the tokens a model read and wrote for a message
+const INPUT_TOKENS_KEY: &str = "input_tokens";
+const OUTPUT_TOKENS_KEY: &str = "output_tokens";
+
+/// Messages and tokens of one model over a range of days
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelUsage {
+    pub model: String,
+    pub message_count: i64,
+    pub input_tokens: i64,
+    pub output_tokens: i64,
+}
+
+/// Counts of one scope on one day
+#[derive(Debug, Default)]
+struct DayTotals {
+    messages: i64,
+    forks: i64,
+    by_role: HashMap<String, i64>,
+    by_model: HashMap<String, i64>,
+    input_tokens_by_model: HashMap<String, i64>,
+    output_tokens_by_model: HashMap<String, i64>,
+}
+
+/// A message counted by a rollup, kept until every fork root has been seen
+struct Counted {
+    conversation_id: Uuid,
+    created_at: DateTime<Utc>,
+    created_by: String,
+    role: String,
+    models: BTreeSet<String>,
+    input_tokens: i64,
+    output_tokens: i64,
+}
+
+/// Daily rollups of messages and forks, per user and per conversation, for
+/// product dashboards. A background job recomputes the last few days from the
+/// messages themselves; the API only reads the stored rollups.
+pub struct AnalyticsService {
+    analytics_repo: Arc<dyn AnalyticsStore>,
+    lineage_repo: Arc<dyn LineageStore>,
+    config: AnalyticsConfig,
+}
+
+impl AnalyticsService {
+    pub fn new(
+        analytics_repo: Arc<dyn AnalyticsStore>,
+        lineage_repo: Arc<dyn LineageStore>,
+        config: AnalyticsConfig,
+    ) -> Self {
+        Self {
+            analytics_repo,
+            lineage_repo,
+            config,
+        }
+    }
+
+    /// Rollups of the messages a user wrote and the forks they made
+    pub async fn get_user_rollups(
+        &self,
+        user_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AnalyticsRollupRow>, DbError> {
+        self.analytics_repo
+            .get_rollups(&user_scope(user_id), &from.to_string(), &to.to_string())
+            .await
+    }
+
+    /// Rollups of a conversation's messages and of the forks made from it
+    pub async fn get_conversation_rollups(
+        &self,
+        conversation_id: Uuid,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<AnalyticsRollupRow>, DbError> {
+        self.analytics_repo
+            .get_rollups(
+                &conversation_scope(conversation_id),
+                &from.to_string(),
+                &to.to_string(),
+            )
+            .await
+    }
+
+    /// Messages and tokens per model across the deployment, most used first
+    pub async fn get_usage_by_model(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<ModelUsage>, DbError> {
+        let rollups = self
+            .analytics_repo
+            .get_rollups(GLOBAL_SCOPE, &from.to_string(), &to.to_string())
+            .await?;
+
+        let mut usage: HashMap<String, ModelUsage> = HashMap::new();
+        for rollup in rollups {
+            for (model, count) in rollup.messages_by_model.unwrap_or_default() {
+                usage_of(&mut usage, model).message_count += count;
+            }
+            for (model, count) in rollup.input_tokens_by_model.unwrap_or_default() {
+                usage_of(&mut usage, model).input_tokens += count;
+            }
+            for (model, count) in rollup.output_tokens_by_model.unwrap_or_default() {
+                usage_of(&mut usage, model).output_tokens += count;
+            }
+        }
+
+        let mut usage: Vec<ModelUsage> = usage.into_values().collect();
+        usage.sort_by(|a, b| {
+            b.message_count
+                .cmp(&a.message_count)
+                .then_with(|| a.model.cmp(&b.model))
+        });
+
+        Ok(usage)
+    }
+
+    /// Recompute the rollups of the configured window of days up to `now`.
+    /// Scans every message. Messages a fork copied keep their original
+    /// timestamp, older than the fork's root, and are not counted again.
+    /// Returns the rollups written.
+    pub async fn rollup(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let today = now.date_naive();
+        let first_day = today - Duration::days(self.config.window_days as i64 - 1);
+        let in_window = |at: DateTime<Utc>| (first_day..=today).contains(&at.date_naive());
+
+        let mut totals: HashMap<(String, String), DayTotals> = HashMap::new();
+        let mut forked_at: HashMap<Uuid, DateTime<Utc>> = HashMap::new();
+        let mut counted = Vec::new();
+
+        let mut messages = self.lineage_repo.scan_messages(SCAN_PAGE_SIZE).await?;
+        while let Some(message) = messages.next().await {
+            let message = message?;
+            if let ContentType::Metadata(metadata) = &message.content {
+                let Some(source_conversation_id) = metadata.fork_from_conversation_id else {
+                    continue;
+                };
+                forked_at.insert(message.conversation_id, message.created_at);
+                if in_window(message.created_at) {
+                    let day = day(message.created_at);
+                    for scope in [
+                        GLOBAL_SCOPE.to_string(),
+                        conversation_scope(source_conversation_id),
+                        user_scope(&message.created_by),
+                    ] {
+                        totals.entry((scope, day.clone())).or_default().forks += 1;
+                    }
+                }
+            } else if in_window(message.created_at) {
+                counted.push(Counted {
+                    conversation_id: message.conversation_id,
+                    created_at: message.created_at,
+                    role: message.role.as_str().to_string(),
+                    models: models(&message),
+                    input_tokens: tokens(&message, INPUT_TOKENS_KEY),
+                    output_tokens: tokens(&message, OUTPUT_TOKENS_KEY),
+                    created_by: message.created_by,
+                });
+            }
+        }
+
+        for message in counted {
+            if forked_at
+                .get(&message.conversation_id)
+                .is_some_and(|forked_at| message.created_at < *forked_at)
+            {
+                continue;
+            }
+
+            let day = day(message.created_at);
+            for scope in [
+                GLOBAL_SCOPE.to_string(),
+                conversation_scope(message.conversation_id),
+                user_scope(&message.created_by),
+            ] {
+                let day_totals = totals.entry((scope, day.clone())).or_default();
+                day_totals.messages += 1;
+                *day_totals.by_role.entry(message.role.clone()).or_default() += 1;
+                for model in &message.models {
+                    *day_totals.by_model.entry(model.clone()).or_default() += 1;
+                    *day_totals
+                        .input_tokens_by_model
+                        .entry(model.clone())
+                        .or_default() += message.input_tokens;
+                    *day_totals
+                        .output_tokens_by_model
+                        .entry(model.clone())
+                        .or_default() += message.output_tokens;
+                }
+            }
+        }
+
+        let rollups: Vec<AnalyticsRollupRow> = totals
+            .into_iter()
+            .map(|((scope, day), totals)| AnalyticsRollupRow {
+                scope,
+                day,
+                message_count: totals.messages,
+                fork_count: totals.forks,
+                messages_by_role: Some(totals.by_role),
+                messages_by_model: Some(totals.by_model),
+                input_tokens_by_model: Some(totals.input_tokens_by_model),
+                output_tokens_by_model: Some(totals.output_tokens_by_model),
+                computed_at: now,
+            })
+            .collect();
+        self.analytics_repo.upsert_rollups(&rollups).await?;
+
+        Ok(rollups.len())
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for AnalyticsService {
+    fn name(&self) -> &'static str {
+        "analytics"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let written = self.rollup(Utc::now()).await?;
+        tracing::debug!("Analytics wrote {} daily rollups", written);
+        Ok(())
+    }
+}
+
+fn user_scope(user_id: &str) -> String {
+    format!("user:{}", user_id)
+}
+
+fn conversation_scope(conversation_id: Uuid) -> String {
+    format!("conversation:{}", conversation_id)
+}
+
+/// Rollup bucket for a point in time
+fn day(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d").to_string()
+}
+
+/// Models that produced a message: the `model` content metadata, or the
+/// models named by the items of an image batch
+fn models(message: &Message) -> BTreeSet<String> {
+    if let Some(model) = message.content_metadata.get("model") {
+        return BTreeSet::from([model.clone()]);
+    }
+
+    match &message.content {
+        ContentType::ImageBatch(batch) => batch
+            .images
+            .iter()
+            .filter_map(|item| item.model.clone())
+            .collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+fn usage_of(usage: &mut HashMap<String, ModelUsage>, model: String) -> &mut ModelUsage {
+    usage.entry(model.clone()).or_insert_with(|| ModelUsage {
+        model,
+        ..Default::default()
+    })
+}
+
+/// A token count from content metadata; missing or malformed counts are 0
+fn tokens(message: &Message, key: &str) -> i64 {
+    message
+        .content_metadata
+        .get(key)
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Conversation, MessageRole, TextContent};
+    use crate::repositories::Storage;
+    use crate::utils::{compute_lineage, new_message_id};
+
+    fn message(
+        conversation: &Conversation,
+        role: MessageRole,
+        model: Option<&str>,
+        created_at: DateTime<Utc>,
+    ) -> Message {
+        let root = &conversation.root_message;
+        let message_id = new_message_id();
+        Message {
+            conversation_id: conversation.conversation_id,
+            message_id,
+            parent_message_id: Some(root.message_id),
+            role,
+            content: ContentType::Text(TextContent {
+                text: "hi".to_string(),
+            }),
+            content_metadata: model
+                .map(|model| HashMap::from([("model".to_string(), model.to_string())]))
+                .unwrap_or_default(),
+            lineage: compute_lineage(&root.lineage, message_id),
+            created_at,
+            created_by: conversation.created_by().to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rollup_counts_recent_messages_and_forks() {
+        let storage = Storage::memory();
+        let service = AnalyticsService::new(
+            storage.analytics.clone(),
+            storage.lineage.clone(),
+            AnalyticsConfig {
+                enabled: true,
+                interval_secs: 60,
+                window_days: 2,
+            },
+        );
+        let now: DateTime<Utc> = "2026-03-10T12:00:00Z".parse().unwrap();
+        let today = now.date_naive();
+
+        let source = Conversation::new("Cats".to_string(), "alice".to_string());
+        let mut fork = Conversation::new("Cats, forked".to_string(), "bob".to_string());
+        fork.root_message.created_at = now - Duration::minutes(1);
+        if let ContentType::Metadata(metadata) = &mut fork.root_message.content {
+            metadata.fork_from_conversation_id = Some(source.conversation_id);
+        }
+        let copied = Message {
+            conversation_id: fork.conversation_id,
+            ..message(
+                &source,
+                MessageRole::Human,
+                None,
+                now - Duration::minutes(5),
+            )
+        };
+        let mut answer = message(&source, MessageRole::Assistant, Some("gpt-4o"), now);
+        answer
+            .content_metadata
+            .insert(INPUT_TOKENS_KEY.to_string(), "120".to_string());
+        answer
+            .content_metadata
+            .insert(OUTPUT_TOKENS_KEY.to_string(), "80".to_string());
+        let messages = vec![
+            source.root_message.clone(),
+            fork.root_message.clone(),
+            message(
+                &source,
+                MessageRole::Human,
+                None,
+                now - Duration::minutes(5),
+            ),
+            answer,
+            // Outside the window
+            message(&source, MessageRole::Human, None, now - Duration::days(3)),
+            copied,
+            message(&fork, MessageRole::Human, None, now),
+        ];
+        storage
+            .lineage
+            .batch_insert_messages(&messages)
+            .await
+            .unwrap();
+
+        service.rollup(now).await.unwrap();
+
+        let source_days = service
+            .get_conversation_rollups(source.conversation_id, today, today)
+            .await
+            .unwrap();
+        assert_eq!(source_days.len(), 1);
+        assert_eq!(source_days[0].message_count, 2);
+        assert_eq!(source_days[0].fork_count, 1);
+        assert_eq!(
+            source_days[0].messages_by_model,
+            Some(HashMap::from([("gpt-4o".to_string(), 1)]))
+        );
+
+        let alice = service
+            .get_user_rollups("alice", today - Duration::days(7), today)
+            .await
+            .unwrap();
+        assert_eq!(alice.len(), 1);
+        assert_eq!(
+            alice[0].messages_by_role,
+            Some(HashMap::from([
+                ("human".to_string(), 1),
+                ("assistant".to_string(), 1),
+            ]))
+        );
+
+        let bob = service.get_user_rollups("bob", today, today).await.unwrap();
+        assert_eq!((bob[0].message_count, bob[0].fork_count), (1, 1));
+
+        let usage = service.get_usage_by_model(today, today).await.unwrap();
+        assert_eq!(
+            usage,
+            vec![ModelUsage {
+                model: "gpt-4o".to_string(),
+                message_count: 1,
+                input_tokens: 120,
+                output_tokens: 80,
+            }]
+        );
+    }
+}
@@ -1,42 +1,261 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
+use crate::config::BranchCacheConfig;
 use crate::db::DbError;
-use crate::domain::{Branch, Message};
-use crate::repositories::{BranchRepository, LineageRepository};
+use crate::domain::{
+    Branch, BranchMoved, BranchNaming, ChangeKind, ContentType, ConversationEvent, EventKind,
+    Message, slugify,
+};
+use crate::repositories::{BranchStore, LineageStore};
+use crate::services::conversation_service::ensure_unlocked;
+use crate::services::{ChangeFeed, PreferenceService, PrivateMessages};
+
+/// Characters of the leaf's text kept in `leaf_excerpt` branch names
+const EXCERPT_CHARS: usize = 40;
+
+/// What a client holding some of a conversation's leaves is missing
+#[derive(Debug, Clone)]
+pub struct ConversationSync {
+    /// Messages on none of the known leaves' lineages, oldest first
+    pub messages: Vec<Message>,
+    /// Branches created or moved to a message the client doesn't have
+    pub branches: Vec<Branch>,
+    /// Every branch the conversation has now, so clients can drop deleted ones
+    pub branch_ids: Vec<Uuid>,
+    /// Known leaves no longer in the conversation, e.g. deleted or moved away
+    pub unknown_leaves: Vec<Uuid>,
+}
+
+/// Message lists of recently read branches. An entry is served only while
+/// the branch's `last_updated` and leaf are the ones it was read at, so a
+/// leaf moved by another instance is picked up on the next read.
+struct BranchMessageCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<HashMap<(Uuid, Uuid), CachedBranch>>,
+}
+
+struct CachedBranch {
+    last_updated: DateTime<Utc>,
+    leaf_message_id: Uuid,
+    cached_at: Instant,
+    messages: Arc<Vec<Message>>,
+}
+
+impl BranchMessageCache {
+    fn new(config: &BranchCacheConfig) -> Self {
+        Self {
+            capacity: config.capacity,
+            ttl: Duration::from_secs(config.ttl_secs),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, branch: &Branch) -> Option<Arc<Vec<Message>>> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&(branch.conversation_id, branch.branch_id))
+            .filter(|cached| {
+                cached.last_updated == branch.last_updated
+                    && cached.leaf_message_id == branch.leaf_message_id
+                    && cached.cached_at.elapsed() < self.ttl
+            })
+            .map(|cached| cached.messages.clone())
+    }
+
+    fn insert(&self, branch: &Branch, messages: Arc<Vec<Message>>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= self.capacity {
+            entries.retain(|_, cached| cached.cached_at.elapsed() < self.ttl);
+        }
+        if entries.len() >= self.capacity
+            && let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.cached_at)
+                .map(|(key, _)| *key)
+        {
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            (branch.conversation_id, branch.branch_id),
+            CachedBranch {
+                last_updated: branch.last_updated,
+                leaf_message_id: branch.leaf_message_id,
+                cached_at: Instant::now(),
+                messages,
+            },
+        );
+    }
+
+    fn invalidate_conversation(&self, conversation_id: Uuid) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|(cached_conversation_id, _), _| *cached_conversation_id != conversation_id);
+    }
+
+    fn invalidate(&self, conversation_id: Uuid, branch_id: Uuid) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(&(conversation_id, branch_id));
+    }
+}
 
 pub struct BranchService {
-    branch_repo: BranchRepository,
-    lineage_repo: LineageRepository,
+    branch_repo: Arc<dyn BranchStore>,
+    lineage_repo: Arc<dyn LineageStore>,
+    change_feed: ChangeFeed,
+    cache: BranchMessageCache,
+    /// Naming scheme of users who didn't pick one
+    default_naming: BranchNaming,
+    preferences: Arc<PreferenceService>,
 }
 
 impl BranchService {
-    pub fn new(branch_repo: BranchRepository, lineage_repo: LineageRepository) -> Self {
+    pub fn new(
+        branch_repo: Arc<dyn BranchStore>,
+        lineage_repo: Arc<dyn LineageStore>,
+        change_feed: ChangeFeed,
+        cache: BranchCacheConfig,
+        default_naming: BranchNaming,
+        preferences: Arc<PreferenceService>,
+    ) -> Self {
         Self {
             branch_repo,
             lineage_repo,
+            change_feed,
+            cache: BranchMessageCache::new(&cache),
+            default_naming,
+            preferences,
         }
     }
 
-    /// Create a new branch
+    /// Create a new branch. Without a name it is named the way the creator's
+    /// preferences, or the deployment default, say. Names are unique within
+    /// the conversation, ignoring case, and so are the slugs derived from them.
     pub async fn create_branch(
         &self,
         conversation_id: Uuid,
-        branch_name: String,
+        branch_name: Option<String>,
         leaf_message_id: Uuid,
         created_by: String,
     ) -> Result<Branch, DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+
         // Validate that the leaf message exists
-        self.lineage_repo
+        let leaf = self
+            .lineage_repo
             .get_message(conversation_id, leaf_message_id)
             .await?;
+        let taken = self.taken_names(conversation_id, None).await?;
+        let branch_name = match branch_name {
+            Some(branch_name) => unique_name(&branch_name, &taken)?,
+            None => self.default_branch_name(&leaf, &created_by, &taken).await?,
+        };
 
-        let branch = Branch::new(conversation_id, branch_name, leaf_message_id, created_by);
+        let mut branch = Branch::new(conversation_id, branch_name, leaf_message_id, created_by);
+        branch.slug = self
+            .free_slug(conversation_id, branch.branch_id, &branch.branch_name)
+            .await?;
 
         self.branch_repo.insert_branch(&branch).await?;
+        self.record_branch_change(&branch).await?;
 
         Ok(branch)
     }
 
+    /// Name for a branch ending at `leaf`, following the creator's naming
+    /// scheme and avoiding `taken` names. Leaves without text fall back to
+    /// numbering; a name already taken gets a ` (2)`, ` (3)`... suffix.
+    async fn default_branch_name(
+        &self,
+        leaf: &Message,
+        created_by: &str,
+        taken: &HashSet<String>,
+    ) -> Result<String, DbError> {
+        let naming = self
+            .preferences
+            .get(created_by)
+            .await?
+            .branch_naming
+            .unwrap_or(self.default_naming);
+        let name = match naming {
+            BranchNaming::Numbered => None,
+            BranchNaming::Timestamp => Some(Utc::now().format("%Y-%m-%d %H:%M UTC").to_string()),
+            BranchNaming::LeafExcerpt => leaf_excerpt(leaf),
+        };
+
+        let Some(name) = name else {
+            let number = (taken.len() + 1..)
+                .find(|n| !taken.contains(&format!("branch {}", n)))
+                .unwrap_or_default();
+            return Ok(format!("Branch {}", number));
+        };
+        Ok((1..)
+            .map(|n| match n {
+                1 => name.clone(),
+                n => format!("{} ({})", name, n),
+            })
+            .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+            .unwrap_or(name))
+    }
+
+    /// Lowercased names of the conversation's branches, except `except`
+    async fn taken_names(
+        &self,
+        conversation_id: Uuid,
+        except: Option<Uuid>,
+    ) -> Result<HashSet<String>, DbError> {
+        Ok(self
+            .branch_repo
+            .get_branches_by_conversation(conversation_id)
+            .await?
+            .into_iter()
+            .filter(|branch| Some(branch.branch_id) != except)
+            .map(|branch| branch.branch_name.to_lowercase())
+            .collect())
+    }
+
+    /// Slug for `branch_id` named `name`: the slugified name, with a `-2`,
+    /// `-3`... suffix while another branch holds it, now or from an earlier name
+    async fn free_slug(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+        name: &str,
+    ) -> Result<String, DbError> {
+        let claimed: HashMap<String, Uuid> = self
+            .branch_repo
+            .get_branch_slugs(conversation_id)
+            .await?
+            .into_iter()
+            .map(|slug| (slug.slug, slug.branch_id))
+            .collect();
+        let base = slugify(name);
+
+        Ok((1..)
+            .map(|n| match n {
+                1 => base.clone(),
+                n => format!("{}-{}", base, n),
+            })
+            .find(|candidate| {
+                claimed
+                    .get(candidate)
+                    .is_none_or(|owner| *owner == branch_id)
+            })
+            .unwrap_or(base))
+    }
+
     /// Get a specific branch
     pub async fn get_branch(
         &self,
@@ -48,6 +267,35 @@ impl BranchService {
             .await
     }
 
+    /// Get the branch a slug belongs to. Slugs of earlier names resolve too,
+    /// so links survive renames.
+    pub async fn get_branch_by_slug(
+        &self,
+        conversation_id: Uuid,
+        slug: &str,
+    ) -> Result<Branch, DbError> {
+        match self
+            .branch_repo
+            .get_branch_slug(conversation_id, slug)
+            .await
+        {
+            Ok(claimed) => {
+                self.branch_repo
+                    .get_branch(conversation_id, claimed.branch_id)
+                    .await
+            }
+            // Branches from before slugs were stored only have a derived one
+            Err(DbError::NotFound) => self
+                .branch_repo
+                .get_branches_by_conversation(conversation_id)
+                .await?
+                .into_iter()
+                .find(|branch| branch.slug == slug)
+                .ok_or(DbError::NotFound),
+            Err(e) => Err(e),
+        }
+    }
+
     /// Get all branches in a conversation
     pub async fn get_branches(&self, conversation_id: Uuid) -> Result<Vec<Branch>, DbError> {
         self.branch_repo
@@ -55,7 +303,70 @@ impl BranchService {
             .await
     }
 
-    /// Get all messages in a branch (from root to leaf)
+    /// The messages and branches a client is missing, given the leaves it
+    /// has. Having a leaf means having its whole lineage. What `private`
+    /// hides is left out, and counts as unknown when given as a leaf.
+    pub async fn sync(
+        &self,
+        conversation_id: Uuid,
+        known_leaves: &[Uuid],
+        private: &PrivateMessages,
+    ) -> Result<ConversationSync, DbError> {
+        // The leaves' lineages first, so the messages they cover can be
+        // skipped as the conversation streams by instead of collected
+        let known: HashSet<Uuid> = self
+            .lineage_repo
+            .get_messages_by_ids(conversation_id, known_leaves)
+            .await?
+            .iter()
+            .filter(|m| !private.hides(m))
+            .flat_map(|m| m.lineage.iter().copied())
+            .collect();
+
+        let mut found = false;
+        let mut present = HashSet::new();
+        let mut messages = Vec::new();
+        let mut stream = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, None)
+            .await?;
+        while let Some(message) = stream.try_next().await? {
+            found = true;
+            if private.hides(&message) {
+                continue;
+            }
+            present.insert(message.message_id);
+            if !known.contains(&message.message_id) {
+                messages.push(message);
+            }
+        }
+        if !found {
+            return Err(DbError::NotFound);
+        }
+        let unknown_leaves = known_leaves
+            .iter()
+            .filter(|id| !present.contains(id))
+            .copied()
+            .collect();
+
+        // Branches ending at a hidden message are left out like the message
+        let mut branches = self.get_branches(conversation_id).await?;
+        branches.retain(|b| present.contains(&b.leaf_message_id));
+        let branch_ids = branches.iter().map(|b| b.branch_id).collect();
+
+        Ok(ConversationSync {
+            messages,
+            branches: branches
+                .into_iter()
+                .filter(|b| !known.contains(&b.leaf_message_id))
+                .collect(),
+            branch_ids,
+            unknown_leaves,
+        })
+    }
+
+    /// Get all messages in a branch (from root to leaf). Only the branch row
+    /// is read while the branch's cached message list is still current.
     pub async fn get_branch_messages(
         &self,
         conversation_id: Uuid,
@@ -65,15 +376,67 @@ impl BranchService {
             .branch_repo
             .get_branch(conversation_id, branch_id)
             .await?;
+        if let Some(messages) = self.cache.get(&branch) {
+            return Ok(messages.to_vec());
+        }
 
         let leaf_message = self
             .lineage_repo
             .get_message(conversation_id, branch.leaf_message_id)
             .await?;
 
-        self.lineage_repo
+        let messages = self
+            .lineage_repo
             .get_messages_by_ids(conversation_id, &leaf_message.lineage)
-            .await
+            .await?;
+        self.cache.insert(&branch, Arc::new(messages.clone()));
+
+        Ok(messages)
+    }
+
+    /// Drop the cached message lists of a conversation's branches, after its
+    /// messages were rewritten in place
+    pub fn forget_conversation(&self, conversation_id: Uuid) {
+        self.cache.invalidate_conversation(conversation_id);
+    }
+
+    /// Get the branch messages compacted by the deepest checkpoint on its path:
+    /// the checkpoint summary followed by the messages after the covered range
+    pub async fn get_compacted_branch_messages(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Uuid,
+    ) -> Result<Vec<Message>, DbError> {
+        let messages = self.get_branch_messages(conversation_id, branch_id).await?;
+
+        let Some(leaf_message) = messages.last() else {
+            return Ok(messages);
+        };
+        let lineage = leaf_message.lineage.clone();
+
+        let checkpoints = self.lineage_repo.get_checkpoints(conversation_id).await?;
+
+        let latest = checkpoints
+            .into_iter()
+            .filter_map(|checkpoint| {
+                let ContentType::Summary(summary) = &checkpoint.content else {
+                    return None;
+                };
+                lineage
+                    .iter()
+                    .position(|id| *id == summary.to_message_id)
+                    .map(|position| (position, checkpoint))
+            })
+            .max_by_key(|(position, _)| *position);
+
+        let Some((position, checkpoint)) = latest else {
+            return Ok(messages);
+        };
+
+        let mut compacted = vec![checkpoint];
+        compacted.extend(messages.into_iter().filter(|m| m.depth() > position + 1));
+
+        Ok(compacted)
     }
 
     /// Update branch leaf (move branch pointer to a new message)
@@ -83,6 +446,8 @@ impl BranchService {
         branch_id: Uuid,
         new_leaf_id: Uuid,
     ) -> Result<(), DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+
         // Validate that the new leaf message exists
         self.lineage_repo
             .get_message(conversation_id, new_leaf_id)
@@ -93,14 +458,7 @@ impl BranchService {
             .get_branch(conversation_id, branch_id)
             .await?;
 
-        self.branch_repo
-            .update_branch_leaf(
-                conversation_id,
-                branch_id,
-                branch.leaf_message_id,
-                new_leaf_id,
-            )
-            .await
+        self.move_leaf(branch, new_leaf_id).await
     }
 
     /// Update branch name
@@ -110,9 +468,31 @@ impl BranchService {
         branch_id: Uuid,
         new_name: String,
     ) -> Result<(), DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        let mut branch = self
+            .branch_repo
+            .get_branch(conversation_id, branch_id)
+            .await?;
+        let taken = self.taken_names(conversation_id, Some(branch_id)).await?;
+        let new_name = unique_name(&new_name, &taken)?;
+        let new_slug = self
+            .free_slug(conversation_id, branch_id, &new_name)
+            .await?;
+
         self.branch_repo
-            .update_branch_name(conversation_id, branch_id, new_name)
-            .await
+            .update_branch_name(
+                conversation_id,
+                branch_id,
+                new_name.clone(),
+                new_slug.clone(),
+            )
+            .await?;
+        self.cache.invalidate(conversation_id, branch_id);
+
+        branch.branch_name = new_name;
+        branch.slug = new_slug;
+        branch.last_updated = chrono::Utc::now();
+        self.record_branch_change(&branch).await
     }
 
     /// Delete a branch (messages remain in the conversation)
@@ -121,6 +501,7 @@ impl BranchService {
         conversation_id: Uuid,
         branch_id: Uuid,
     ) -> Result<(), DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
         let branch = self
             .branch_repo
             .get_branch(conversation_id, branch_id)
@@ -128,6 +509,16 @@ impl BranchService {
 
         self.branch_repo
             .delete_branch(conversation_id, branch_id, branch.leaf_message_id)
+            .await?;
+        self.cache.invalidate(conversation_id, branch_id);
+
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::BranchDeleted,
+                branch_id,
+                &branch,
+            )
             .await
     }
 
@@ -143,13 +534,432 @@ impl BranchService {
             .get_branch(conversation_id, branch_id)
             .await?;
 
+        self.move_leaf(branch, new_message_id).await
+    }
+
+    /// Refresh branches whose leaf is one of the given messages (e.g. after a subtree move)
+    pub async fn touch_branches_with_leaves(
+        &self,
+        conversation_id: Uuid,
+        leaf_message_ids: &[Uuid],
+    ) -> Result<(), DbError> {
+        let branches = self
+            .branch_repo
+            .get_branches_by_conversation(conversation_id)
+            .await?;
+
+        for mut branch in branches
+            .into_iter()
+            .filter(|b| leaf_message_ids.contains(&b.leaf_message_id))
+        {
+            self.branch_repo
+                .update_branch_leaf(
+                    conversation_id,
+                    branch.branch_id,
+                    branch.leaf_message_id,
+                    branch.leaf_message_id,
+                    &[],
+                )
+                .await?;
+            self.cache.invalidate(conversation_id, branch.branch_id);
+
+            branch.update_leaf(branch.leaf_message_id);
+            self.record_branch_change(&branch).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Point a branch at a new leaf, with a `BranchMoved` event
+    async fn move_leaf(&self, mut branch: Branch, new_leaf_id: Uuid) -> Result<(), DbError> {
+        let event = ConversationEvent::new(
+            branch.conversation_id,
+            EventKind::BranchMoved,
+            &BranchMoved {
+                branch_id: branch.branch_id,
+                from_leaf_message_id: branch.leaf_message_id,
+                to_leaf_message_id: new_leaf_id,
+            },
+        )?;
+
         self.branch_repo
             .update_branch_leaf(
-                conversation_id,
-                branch_id,
+                branch.conversation_id,
+                branch.branch_id,
                 branch.leaf_message_id,
-                new_message_id,
+                new_leaf_id,
+                &[event],
+            )
+            .await?;
+        self.cache
+            .invalidate(branch.conversation_id, branch.branch_id);
+
+        branch.update_leaf(new_leaf_id);
+        self.record_branch_change(&branch).await
+    }
+
+    async fn record_branch_change(&self, branch: &Branch) -> Result<(), DbError> {
+        self.change_feed
+            .record(
+                branch.conversation_id,
+                ChangeKind::BranchUpdated,
+                branch.branch_id,
+                branch,
             )
             .await
     }
 }
+
+/// `name` without surrounding whitespace, unless it is empty or `taken`
+fn unique_name(name: &str, taken: &HashSet<String>) -> Result<String, DbError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(DbError::InvalidData(
+            "Branch name must not be empty".to_string(),
+        ));
+    }
+    if taken.contains(&name.to_lowercase()) {
+        return Err(DbError::Conflict(format!(
+            "A branch named `{}` already exists in this conversation",
+            name
+        )));
+    }
+    Ok(name.to_string())
+}
+
+/// The start of a message's text with whitespace collapsed; `None` when it
+/// has no text
+fn leaf_excerpt(message: &Message) -> Option<String> {
+    let ContentType::Text(content) = &message.content else {
+        return None;
+    };
+    let words: Vec<&str> = content.text.split_whitespace().collect();
+    if words.is_empty() {
+        return None;
+    }
+
+    let text = words.join(" ");
+    let mut excerpt: String = text.chars().take(EXCERPT_CHARS).collect();
+    if excerpt.len() < text.len() {
+        excerpt.push('…');
+    }
+    Some(excerpt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Conversation, MessageRole, TextContent};
+    use crate::repositories::Storage;
+    use crate::services::CollaborationHub;
+    use crate::utils::{compute_lineage, new_message_id};
+
+    fn reply(parent: &Message, text: &str) -> Message {
+        let message_id = new_message_id();
+        Message {
+            conversation_id: parent.conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role: MessageRole::Human,
+            content: ContentType::Text(TextContent {
+                text: text.to_string(),
+            }),
+            content_metadata: Default::default(),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: Utc::now(),
+            created_by: "user_a".to_string(),
+        }
+    }
+
+    fn text_of(message: &Message) -> &str {
+        match &message.content {
+            ContentType::Text(content) => &content.text,
+            _ => "",
+        }
+    }
+
+    #[tokio::test]
+    async fn test_branch_messages_are_cached_until_the_leaf_moves() {
+        let storage = Storage::memory();
+        let service = BranchService::new(
+            storage.branches.clone(),
+            storage.lineage.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            BranchCacheConfig {
+                capacity: 10,
+                ttl_secs: 3600,
+            },
+            BranchNaming::Numbered,
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        );
+        let conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        let a = reply(&conversation.root_message, "a");
+        let b = reply(&a, "b");
+        storage
+            .lineage
+            .batch_insert_messages(&[conversation.root_message.clone(), a.clone(), b.clone()])
+            .await
+            .unwrap();
+        let branch = service
+            .create_branch(
+                cid,
+                Some("main".to_string()),
+                a.message_id,
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            service
+                .get_branch_messages(cid, branch.branch_id)
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+
+        // Served from the cache: an edit behind the service's back isn't seen
+        let mut edited = a.clone();
+        edited.content = ContentType::Text(TextContent {
+            text: "edited".to_string(),
+        });
+        storage.lineage.insert_message(&edited).await.unwrap();
+        let messages = service
+            .get_branch_messages(cid, branch.branch_id)
+            .await
+            .unwrap();
+        assert_eq!(text_of(&messages[1]), "a");
+
+        service
+            .update_branch_leaf(cid, branch.branch_id, b.message_id)
+            .await
+            .unwrap();
+        let messages = service
+            .get_branch_messages(cid, branch.branch_id)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(text_of(&messages[1]), "edited");
+    }
+
+    #[tokio::test]
+    async fn test_sync_returns_what_the_known_leaves_dont_cover() {
+        let storage = Storage::memory();
+        let service = BranchService::new(
+            storage.branches.clone(),
+            storage.lineage.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            BranchCacheConfig {
+                capacity: 10,
+                ttl_secs: 3600,
+            },
+            BranchNaming::Numbered,
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        );
+        let conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        let a = reply(&conversation.root_message, "a");
+        let b = reply(&a, "b");
+        let c = reply(&a, "c");
+        let d = reply(&c, "d");
+        storage
+            .lineage
+            .batch_insert_messages(&[
+                conversation.root_message.clone(),
+                a.clone(),
+                b.clone(),
+                c.clone(),
+                d.clone(),
+            ])
+            .await
+            .unwrap();
+        let on_b = service
+            .create_branch(cid, None, b.message_id, "user_a".to_string())
+            .await
+            .unwrap();
+        let on_d = service
+            .create_branch(cid, None, d.message_id, "user_a".to_string())
+            .await
+            .unwrap();
+
+        let gone = Uuid::new_v4();
+        let sync = service
+            .sync(cid, &[b.message_id, gone], &PrivateMessages::default())
+            .await
+            .unwrap();
+        let ids: Vec<Uuid> = sync.messages.iter().map(|m| m.message_id).collect();
+        assert_eq!(ids, [c.message_id, d.message_id]);
+        assert_eq!(sync.branches.len(), 1);
+        assert_eq!(sync.branches[0].branch_id, on_d.branch_id);
+        assert_eq!(sync.branch_ids.len(), 2);
+        assert!(sync.branch_ids.contains(&on_b.branch_id));
+        assert_eq!(sync.unknown_leaves, [gone]);
+
+        let sync = service
+            .sync(
+                cid,
+                &[b.message_id, d.message_id],
+                &PrivateMessages::default(),
+            )
+            .await
+            .unwrap();
+        assert!(sync.messages.is_empty() && sync.branches.is_empty());
+        assert_eq!(
+            service
+                .sync(cid, &[], &PrivateMessages::default())
+                .await
+                .unwrap()
+                .messages
+                .len(),
+            5
+        );
+        assert!(matches!(
+            service
+                .sync(Uuid::new_v4(), &[], &PrivateMessages::default())
+                .await,
+            Err(DbError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unnamed_branches_follow_the_creators_naming_scheme() {
+        let storage = Storage::memory();
+        let preferences = Arc::new(PreferenceService::new(storage.preferences.clone()));
+        let service = BranchService::new(
+            storage.branches.clone(),
+            storage.lineage.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            BranchCacheConfig {
+                capacity: 10,
+                ttl_secs: 3600,
+            },
+            BranchNaming::Numbered,
+            preferences.clone(),
+        );
+        let conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        let a = reply(&conversation.root_message, &"long ".repeat(20));
+        storage
+            .lineage
+            .batch_insert_messages(&[conversation.root_message.clone(), a.clone()])
+            .await
+            .unwrap();
+
+        let branch = service
+            .create_branch(cid, None, a.message_id, "user_a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(branch.branch_name, "Branch 1");
+
+        let mut prefs = preferences.get("user_b").await.unwrap();
+        prefs.branch_naming = Some(BranchNaming::LeafExcerpt);
+        preferences.save(prefs).await.unwrap();
+        let branch = service
+            .create_branch(cid, None, a.message_id, "user_b".to_string())
+            .await
+            .unwrap();
+        assert_eq!(branch.branch_name.chars().count(), EXCERPT_CHARS + 1);
+        assert!(branch.branch_name.ends_with('…'));
+        let again = service
+            .create_branch(cid, None, a.message_id, "user_b".to_string())
+            .await
+            .unwrap();
+        assert_eq!(again.branch_name, format!("{} (2)", branch.branch_name));
+
+        // Names are unique per conversation, ignoring case
+        assert!(matches!(
+            service
+                .create_branch(
+                    cid,
+                    Some(" branch 1 ".to_string()),
+                    a.message_id,
+                    "user_a".to_string()
+                )
+                .await,
+            Err(DbError::Conflict(_))
+        ));
+        service
+            .create_branch(
+                cid,
+                Some("Branch 5".to_string()),
+                a.message_id,
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        let numbered = service
+            .create_branch(cid, None, a.message_id, "user_a".to_string())
+            .await
+            .unwrap();
+        assert_eq!(numbered.branch_name, "Branch 6");
+        assert!(matches!(
+            service
+                .update_branch_name(cid, numbered.branch_id, "BRANCH 5".to_string())
+                .await,
+            Err(DbError::Conflict(_))
+        ));
+        service
+            .update_branch_name(cid, numbered.branch_id, "Branch 6".to_string())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_slugs_keep_resolving_after_renames() {
+        let storage = Storage::memory();
+        let service = BranchService::new(
+            storage.branches.clone(),
+            storage.lineage.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            BranchCacheConfig {
+                capacity: 10,
+                ttl_secs: 3600,
+            },
+            BranchNaming::Numbered,
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        );
+        let conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        let a = reply(&conversation.root_message, "a");
+        storage
+            .lineage
+            .batch_insert_messages(&[conversation.root_message.clone(), a.clone()])
+            .await
+            .unwrap();
+        let create = |name: &str| {
+            service.create_branch(
+                cid,
+                Some(name.to_string()),
+                a.message_id,
+                "user_a".to_string(),
+            )
+        };
+
+        let trip = create("Trip to Paris!").await.unwrap();
+        assert_eq!(trip.slug, "trip-to-paris");
+        let other = create("trip-to-paris").await.unwrap();
+        assert_eq!(other.slug, "trip-to-paris-2");
+
+        service
+            .update_branch_name(cid, trip.branch_id, "Paris, final".to_string())
+            .await
+            .unwrap();
+        for slug in ["trip-to-paris", "paris-final"] {
+            let found = service.get_branch_by_slug(cid, slug).await.unwrap();
+            assert_eq!(found.branch_id, trip.branch_id);
+            assert_eq!(found.slug, "paris-final");
+        }
+
+        // The old slug stays with the renamed branch
+        let again = create("Trip to Paris").await.unwrap();
+        assert_eq!(again.slug, "trip-to-paris-3");
+
+        service.delete_branch(cid, trip.branch_id).await.unwrap();
+        assert!(matches!(
+            service.get_branch_by_slug(cid, "paris-final").await,
+            Err(DbError::NotFound)
+        ));
+    }
+}
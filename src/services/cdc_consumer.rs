@@ -0,0 +1,175 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+
+use crate::config::CdcConfig;
+use crate::db::DbError;
+use crate::repositories::CdcStore;
+use crate::scheduler::{ScheduledTask, TaskError};
+
+/// Name the consumer's position is stored under
+const CONSUMER: &str = "lineage_outbox";
+
+/// Copies changes to the lineage table from Scylla's CDC log into the
+/// outbox, so search indexers and the like can follow them without the
+/// write path knowing about them
+pub struct CdcConsumer {
+    store: Arc<dyn CdcStore>,
+    config: CdcConfig,
+    batch_size: usize,
+}
+
+impl CdcConsumer {
+    pub fn new(store: Arc<dyn CdcStore>, config: CdcConfig, batch_size: usize) -> Self {
+        Self {
+            store,
+            config,
+            batch_size,
+        }
+    }
+
+    /// Enable CDC on the lineage table. Rows written before this aren't in
+    /// the CDC log, so the outbox only covers later changes.
+    pub async fn enable(&self) -> Result<(), DbError> {
+        self.store.enable_lineage_cdc().await
+    }
+
+    /// Copy the changes after the stored position, up to `lag_secs` before
+    /// `now` and at most `max_window_secs` of them, then move the position.
+    /// Returns the number of outbox entries written.
+    pub async fn consume(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let max_window = Duration::seconds(self.config.max_window_secs as i64);
+        let until = now - Duration::seconds(self.config.lag_secs as i64);
+        let from = match self.store.get_checkpoint(CONSUMER).await? {
+            Some(from) => from,
+            // Start with the most recent window rather than the whole log
+            None => until - max_window,
+        };
+        let to = until.min(from + max_window);
+        if to <= from {
+            return Ok(0);
+        }
+
+        let entries = self.store.get_lineage_changes(from, to).await?;
+        for chunk in entries.chunks(self.batch_size) {
+            self.store.insert_outbox_entries(chunk).await?;
+        }
+        self.store.set_checkpoint(CONSUMER, to).await?;
+
+        Ok(entries.len())
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for CdcConsumer {
+    fn name(&self) -> &'static str {
+        "cdc"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let copied = self.consume(Utc::now()).await?;
+        tracing::debug!("CDC consumer copied {} changes to the outbox", copied);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{OutboxEntry, OutboxKind};
+    use std::sync::Mutex;
+    use uuid::Uuid;
+
+    /// A CDC log holding `changes`, recording what is written
+    #[derive(Default)]
+    struct FakeCdc {
+        changes: Vec<OutboxEntry>,
+        outbox: Mutex<Vec<OutboxEntry>>,
+        checkpoint: Mutex<Option<DateTime<Utc>>>,
+    }
+
+    #[async_trait]
+    impl CdcStore for FakeCdc {
+        async fn enable_lineage_cdc(&self) -> Result<(), DbError> {
+            Ok(())
+        }
+
+        async fn get_lineage_changes(
+            &self,
+            from: DateTime<Utc>,
+            to: DateTime<Utc>,
+        ) -> Result<Vec<OutboxEntry>, DbError> {
+            Ok(self
+                .changes
+                .iter()
+                .filter(|entry| entry.changed_at > from && entry.changed_at <= to)
+                .cloned()
+                .collect())
+        }
+
+        async fn insert_outbox_entries(&self, entries: &[OutboxEntry]) -> Result<(), DbError> {
+            self.outbox.lock().unwrap().extend_from_slice(entries);
+            Ok(())
+        }
+
+        async fn get_checkpoint(&self, _consumer: &str) -> Result<Option<DateTime<Utc>>, DbError> {
+            Ok(*self.checkpoint.lock().unwrap())
+        }
+
+        async fn set_checkpoint(
+            &self,
+            _consumer: &str,
+            processed_up_to: DateTime<Utc>,
+        ) -> Result<(), DbError> {
+            *self.checkpoint.lock().unwrap() = Some(processed_up_to);
+            Ok(())
+        }
+    }
+
+    fn change(changed_at: DateTime<Utc>) -> OutboxEntry {
+        OutboxEntry {
+            changed_at,
+            change_id: Uuid::new_v4(),
+            batch_seq_no: 0,
+            conversation_id: Uuid::new_v4(),
+            message_id: Some(Uuid::new_v4()),
+            kind: OutboxKind::MessageUpserted,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consumer_copies_each_change_once() {
+        let now = Utc::now();
+        let start = now - Duration::seconds(200);
+        let store = Arc::new(FakeCdc {
+            changes: vec![
+                change(start + Duration::seconds(10)),
+                change(start + Duration::seconds(90)),
+                change(now - Duration::seconds(5)),
+            ],
+            checkpoint: Mutex::new(Some(start)),
+            ..Default::default()
+        });
+        let consumer = CdcConsumer::new(
+            store.clone(),
+            CdcConfig {
+                enabled: true,
+                interval_secs: 5,
+                lag_secs: 30,
+                max_window_secs: 60,
+            },
+            100,
+        );
+
+        // One window per run, never closer to now than the lag
+        assert_eq!(consumer.consume(now).await.unwrap(), 1);
+        assert_eq!(consumer.consume(now).await.unwrap(), 1);
+        assert_eq!(consumer.consume(now).await.unwrap(), 0);
+        assert_eq!(consumer.consume(now).await.unwrap(), 0);
+        assert_eq!(
+            *store.checkpoint.lock().unwrap(),
+            Some(now - Duration::seconds(30))
+        );
+        assert_eq!(store.outbox.lock().unwrap().len(), 2);
+    }
+}
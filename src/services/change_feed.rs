@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{Change, ChangeKind};
+use crate::repositories::ChangeStore;
+
+use super::{CollaborationEvent, CollaborationHub};
+
+/// Records changes in the change store and forwards them to live subscribers
+#[derive(Clone)]
+pub struct ChangeFeed {
+    store: Arc<dyn ChangeStore>,
+    hub: Arc<CollaborationHub>,
+}
+
+impl ChangeFeed {
+    pub fn new(store: Arc<dyn ChangeStore>, hub: Arc<CollaborationHub>) -> Self {
+        Self { store, hub }
+    }
+
+    /// Append a change to the conversation's change feed and
+    /// forward it to live subscribers once persisted
+    pub async fn insert_change(&self, change: &Change) -> Result<(), DbError> {
+        self.store.insert_change(change).await?;
+
+        self.hub.publish(
+            change.conversation_id,
+            CollaborationEvent::Change(change.clone()),
+        );
+
+        Ok(())
+    }
+
+    /// Build and append a change entry, serializing the entity snapshot as payload
+    pub async fn record<T: Serialize>(
+        &self,
+        conversation_id: Uuid,
+        kind: ChangeKind,
+        entity_id: impl ToString,
+        entity: &T,
+    ) -> Result<(), DbError> {
+        let payload =
+            serde_json::to_value(entity).map_err(|e| DbError::SerializationError(e.to_string()))?;
+
+        let change = Change::new(conversation_id, kind, entity_id.to_string(), Some(payload));
+
+        self.insert_change(&change).await
+    }
+
+    /// Get changes strictly after the given position (oldest first)
+    pub async fn get_changes_since(
+        &self,
+        conversation_id: Uuid,
+        since: DateTime<Utc>,
+        after_change_id: Uuid,
+        limit: i32,
+    ) -> Result<Vec<Change>, DbError> {
+        self.store
+            .get_changes_since(conversation_id, since, after_change_id, limit)
+            .await
+    }
+
+    /// Delete the whole change feed of a conversation
+    pub async fn delete_changes(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        self.store.delete_changes(conversation_id).await
+    }
+}
@@ -0,0 +1,326 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::Job;
+use crate::repositories::{BranchStore, LineageStore, ShareStore};
+use crate::services::conversation_service::{ensure_not_held, ensure_unlocked};
+use crate::services::{ConversationService, JobContext, JobRunner};
+
+/// Parameters of a `user_cleanup` job
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserCleanupParams {
+    /// Only delete conversations created before this time
+    pub older_than: Option<DateTime<Utc>>,
+}
+
+/// Deletes a user's conversations in bulk, together with their branches and
+/// shares, as the runner of `user_cleanup` jobs. Progress is reported in the
+/// `matched`, `deleted`, `held` and `failed` counters. Re-running a job is safe.
+pub struct CleanupService {
+    lineage_repo: Arc<dyn LineageStore>,
+    branch_repo: Arc<dyn BranchStore>,
+    share_repo: Arc<dyn ShareStore>,
+    conversations: Arc<ConversationService>,
+}
+
+impl CleanupService {
+    pub fn new(
+        lineage_repo: Arc<dyn LineageStore>,
+        branch_repo: Arc<dyn BranchStore>,
+        share_repo: Arc<dyn ShareStore>,
+        conversations: Arc<ConversationService>,
+    ) -> Self {
+        Self {
+            lineage_repo,
+            branch_repo,
+            share_repo,
+            conversations,
+        }
+    }
+
+    /// Delete the conversations `user_id` created, optionally only those
+    /// created before `older_than`, until done or cancelled
+    async fn delete_user_conversations(
+        &self,
+        user_id: &str,
+        older_than: Option<DateTime<Utc>>,
+        ctx: &JobContext,
+    ) -> Result<(), DbError> {
+        // The title index lists every conversation the user created
+        let entries = self.lineage_repo.get_conversation_titles(user_id).await?;
+
+        for entry in entries {
+            if ctx.is_cancelled().await? {
+                return Ok(());
+            }
+            let conversation_id = entry.conversation_id;
+            let conversation = match self.conversations.get_conversation(conversation_id).await {
+                Ok(conversation) => conversation,
+                Err(DbError::NotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            if older_than.is_some_and(|cutoff| conversation.created_at() >= cutoff) {
+                continue;
+            }
+
+            ctx.add_progress("matched", 1).await?;
+            match self.delete_conversation(conversation_id).await {
+                Ok(()) => ctx.add_progress("deleted", 1).await?,
+                Err(DbError::LegalHold(_)) => ctx.add_progress("held", 1).await?,
+                Err(e) => {
+                    // Keep going; a later run picks up what is left
+                    tracing::warn!("Failed to delete conversation {}: {}", conversation_id, e);
+                    ctx.add_progress("failed", 1).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Delete a conversation along with its branches and shares. Locked and
+    /// held conversations are left whole.
+    async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        ensure_not_held(self.lineage_repo.as_ref(), conversation_id).await?;
+
+        for branch in self
+            .branch_repo
+            .get_branches_by_conversation(conversation_id)
+            .await?
+        {
+            self.branch_repo
+                .delete_branch(conversation_id, branch.branch_id, branch.leaf_message_id)
+                .await?;
+        }
+
+        let revokes: Vec<String> = self
+            .share_repo
+            .get_shares_by_conversation(conversation_id)
+            .await?
+            .into_iter()
+            .map(|share| share.shared_with)
+            .collect();
+        if !revokes.is_empty() {
+            self.share_repo
+                .apply_share_changes(conversation_id, &[], &revokes)
+                .await?;
+        }
+
+        // Last, so a failed attempt can still be found and retried
+        self.conversations
+            .delete_conversation(conversation_id)
+            .await
+    }
+}
+
+#[async_trait]
+impl JobRunner for CleanupService {
+    async fn run(&self, job: &Job, ctx: &JobContext) -> Result<(), DbError> {
+        let params: UserCleanupParams = serde_json::from_value(job.params.clone())
+            .map_err(|e| DbError::InvalidData(format!("Invalid cleanup parameters: {}", e)))?;
+
+        self.delete_user_conversations(&job.user_id, params.older_than, ctx)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use crate::config::{AppConfig, JobsConfig, PiiConfig};
+    use crate::domain::{Branch, JobKind, JobStatus, LegalHold, Permission, Share};
+    use crate::object_store::S3ObjectStore;
+    use crate::repositories::Storage;
+    use crate::services::{
+        ChangeFeed, CollaborationHub, ImageService, JobService, NotificationService,
+        PreferenceService, ShareService,
+    };
+    use crate::utils::pii::PiiScrubber;
+    use std::collections::HashMap;
+
+    async fn wait_for(jobs: &JobService, job_id: Uuid) -> Job {
+        loop {
+            let job = jobs.get_job(job_id).await.unwrap();
+            if job.is_finished() {
+                return job;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    fn counters(job: &Job) -> (i64, i64, i64) {
+        let counter = |name: &str| job.progress.get(name).copied().unwrap_or(0);
+        (counter("matched"), counter("deleted"), counter("failed"))
+    }
+
+    #[tokio::test]
+    async fn test_user_cleanup_deletes_conversations_branches_and_shares() {
+        let storage = Storage::memory();
+        let conversations = Arc::new(ConversationService::new(
+            storage.lineage.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            AppConfig {
+                max_lineage_depth: 1000,
+                max_batch_size: 100,
+                max_children_per_message: 1000,
+                max_messages_per_conversation: 50_000,
+                default_page_size: 50,
+                max_page_size: 200,
+            },
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(PiiScrubber::new(&PiiConfig {
+                scrub_on_write: false,
+            })),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+            Arc::new(ShareService::new(
+                storage.shares.clone(),
+                ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+                Arc::new(NotificationService::new(
+                    storage.notifications.clone(),
+                    Arc::new(PreferenceService::new(storage.preferences.clone())),
+                )),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+        ));
+        let service: Arc<dyn JobRunner> = Arc::new(CleanupService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            conversations.clone(),
+        ));
+        let jobs = JobService::new(
+            storage.jobs.clone(),
+            HashMap::from([(JobKind::UserCleanup, service)]),
+            JobsConfig {
+                stale_after_secs: 300,
+                recovery_interval_secs: 60,
+            },
+        );
+        let start = |older_than: Option<DateTime<Utc>>| {
+            jobs.start(
+                JobKind::UserCleanup,
+                "demo".to_string(),
+                serde_json::to_value(UserCleanupParams { older_than }).unwrap(),
+            )
+        };
+
+        let first = conversations
+            .create_conversation("First".to_string(), "demo".to_string())
+            .await
+            .unwrap();
+        let second = conversations
+            .create_conversation("Second".to_string(), "demo".to_string())
+            .await
+            .unwrap();
+        let kept = conversations
+            .create_conversation("Kept".to_string(), "someone_else".to_string())
+            .await
+            .unwrap();
+        let held = conversations
+            .create_conversation("Held".to_string(), "demo".to_string())
+            .await
+            .unwrap();
+        storage
+            .lineage
+            .insert_hold(&LegalHold {
+                conversation_id: held.conversation_id,
+                placed_by: "admin".to_string(),
+                placed_at: Utc::now(),
+                reason: None,
+            })
+            .await
+            .unwrap();
+        let held_branch = Branch::new(
+            held.conversation_id,
+            "main".to_string(),
+            held.root_message.message_id,
+            "demo".to_string(),
+        );
+        storage.branches.insert_branch(&held_branch).await.unwrap();
+        let branch = Branch::new(
+            first.conversation_id,
+            "main".to_string(),
+            first.root_message.message_id,
+            "demo".to_string(),
+        );
+        storage.branches.insert_branch(&branch).await.unwrap();
+        storage
+            .shares
+            .insert_share(&Share {
+                conversation_id: first.conversation_id,
+                shared_with: "viewer".to_string(),
+                permission: Permission::Read,
+                shared_at: Utc::now(),
+                shared_by: "demo".to_string(),
+            })
+            .await
+            .unwrap();
+
+        // Nothing was created before the cutoff
+        let cutoff = Utc::now() - chrono::Duration::hours(1);
+        let job = start(Some(cutoff)).await.unwrap();
+        let job = wait_for(&jobs, job.job_id).await;
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(counters(&job), (0, 0, 0));
+
+        let job = start(None).await.unwrap();
+        let job = wait_for(&jobs, job.job_id).await;
+        assert_eq!(job.status, JobStatus::Completed);
+        assert_eq!(counters(&job), (3, 2, 0));
+        assert_eq!(job.progress["held"], 1);
+
+        for conversation_id in [first.conversation_id, second.conversation_id] {
+            assert!(matches!(
+                conversations.get_conversation(conversation_id).await,
+                Err(DbError::NotFound)
+            ));
+        }
+        assert!(
+            storage
+                .branches
+                .get_branches_by_conversation(first.conversation_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(
+            storage
+                .shares
+                .get_shares_for_user("viewer")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        for conversation_id in [kept.conversation_id, held.conversation_id] {
+            assert!(
+                conversations
+                    .get_conversation(conversation_id)
+                    .await
+                    .is_ok()
+            );
+        }
+        assert_eq!(
+            storage
+                .branches
+                .get_branches_by_conversation(held.conversation_id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::domain::Change;
+use crate::services::PresenceSignal;
+
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Event broadcast to everyone following a conversation live
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CollaborationEvent {
+    Change(Change),
+    Presence(PresenceSignal),
+}
+
+/// Per-conversation broadcast registry. Channels are created on first
+/// subscription and dropped once the last subscriber goes away.
+#[derive(Default)]
+pub struct CollaborationHub {
+    channels: Mutex<HashMap<Uuid, broadcast::Sender<CollaborationEvent>>>,
+}
+
+impl CollaborationHub {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Subscribe to the live events of a conversation
+    pub fn subscribe(&self, conversation_id: Uuid) -> broadcast::Receiver<CollaborationEvent> {
+        let mut channels = self
+            .channels
+            .lock()
+            .expect("collaboration hub lock poisoned");
+        channels
+            .entry(conversation_id)
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+
+    /// Broadcast an event to the conversation's subscribers, if any
+    pub fn publish(&self, conversation_id: Uuid, event: CollaborationEvent) {
+        let mut channels = self
+            .channels
+            .lock()
+            .expect("collaboration hub lock poisoned");
+
+        if let Some(sender) = channels.get(&conversation_id)
+            && sender.send(event).is_err()
+        {
+            // No receivers left
+            channels.remove(&conversation_id);
+        }
+    }
+
+    /// Drop every channel so that all live streams end, e.g. on shutdown
+    pub fn close_all(&self) {
+        self.channels
+            .lock()
+            .expect("collaboration hub lock poisoned")
+            .clear();
+    }
+
+    /// Number of live subscribers of a conversation
+    pub fn subscriber_count(&self, conversation_id: Uuid) -> usize {
+        self.channels
+            .lock()
+            .expect("collaboration hub lock poisoned")
+            .get(&conversation_id)
+            .map(|sender| sender.receiver_count())
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::PresenceState;
+    use chrono::Utc;
+
+    #[tokio::test]
+    async fn test_publish_reaches_subscribers_and_drops_idle_channels() {
+        let hub = CollaborationHub::new();
+        let conversation_id = Uuid::new_v4();
+        let mut receiver = hub.subscribe(conversation_id);
+
+        hub.publish(
+            conversation_id,
+            CollaborationEvent::Presence(PresenceSignal {
+                user_id: "user_a".to_string(),
+                state: PresenceState::Typing,
+                branch_id: None,
+                at: Utc::now(),
+            }),
+        );
+
+        match receiver.recv().await.unwrap() {
+            CollaborationEvent::Presence(signal) => assert_eq!(signal.state, PresenceState::Typing),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        drop(receiver);
+        hub.publish(
+            conversation_id,
+            CollaborationEvent::Presence(PresenceSignal {
+                user_id: "user_a".to_string(),
+                state: PresenceState::Offline,
+                branch_id: None,
+                at: Utc::now(),
+            }),
+        );
+
+        assert_eq!(hub.subscriber_count(conversation_id), 0);
+        assert!(hub.channels.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{ContentType, Message, MessageRole};
+use crate::repositories::LineageStore;
+use crate::services::{ContextStrategy, PrivateMessages};
+
+/// Roughly how many characters one token covers in English text
+const CHARS_PER_TOKEN: usize = 4;
+
+/// A message of an assembled context with its estimated size
+#[derive(Debug, Clone)]
+pub struct ContextMessage {
+    pub message: Message,
+    pub tokens: usize,
+}
+
+/// The path to a leaf message shaped as a prompt: system messages first, then
+/// the checkpoint summary if one was used, then the turns up to the leaf
+#[derive(Debug, Clone)]
+pub struct ConversationContext {
+    pub messages: Vec<ContextMessage>,
+    pub tokens: usize,
+    /// Messages replaced by a checkpoint summary
+    pub summarized: usize,
+    /// Messages dropped to fit the token budget
+    pub truncated: usize,
+}
+
+/// Assembles the lineage of a message into LLM context, so generation
+/// services share one implementation of ordering and budget trimming
+pub struct ContextService {
+    lineage_repo: Arc<dyn LineageStore>,
+}
+
+impl ContextService {
+    pub fn new(lineage_repo: Arc<dyn LineageStore>) -> Self {
+        Self { lineage_repo }
+    }
+
+    /// Build the context ending at `leaf_message_id`. System messages and the
+    /// leaf are always kept; fails if they alone exceed `max_tokens`. A leaf
+    /// `private` hides is not found, and so are checkpoints it hides.
+    pub async fn build_context(
+        &self,
+        conversation_id: Uuid,
+        leaf_message_id: Uuid,
+        max_tokens: Option<usize>,
+        strategy: ContextStrategy,
+        private: &PrivateMessages,
+    ) -> Result<ConversationContext, DbError> {
+        let leaf = self
+            .lineage_repo
+            .get_message(conversation_id, leaf_message_id)
+            .await?;
+        if private.hides(&leaf) {
+            return Err(DbError::NotFound);
+        }
+        let mut path = self
+            .lineage_repo
+            .get_messages_by_ids(conversation_id, &leaf.lineage)
+            .await?;
+        // The root only carries conversation metadata
+        path.retain(|m| !matches!(m.content, ContentType::Metadata(_)));
+
+        let mut summary = None;
+        let mut summarized = 0;
+        if strategy == ContextStrategy::Summary
+            && let Some((range, checkpoint)) = self.deepest_checkpoint(&path, private).await?
+        {
+            summarized = range.len();
+            path.drain(range);
+            summary = Some(checkpoint);
+        }
+
+        let (system, turns): (Vec<Message>, Vec<Message>) = path
+            .into_iter()
+            .partition(|m| m.role == MessageRole::System);
+        let system: Vec<ContextMessage> = system.into_iter().map(sized).collect();
+        let mut summary = summary.map(sized);
+        let mut turns: Vec<ContextMessage> = turns.into_iter().map(sized).collect();
+
+        let mut tokens: usize = system
+            .iter()
+            .chain(&summary)
+            .chain(&turns)
+            .map(|m| m.tokens)
+            .sum();
+        let mut truncated = 0;
+        if let Some(max_tokens) = max_tokens {
+            // Oldest turns go first, then the summary; the leaf stays
+            let mut dropped = 0;
+            while tokens > max_tokens && dropped + 1 < turns.len() {
+                tokens -= turns[dropped].tokens;
+                dropped += 1;
+            }
+            turns.drain(..dropped);
+            truncated = dropped;
+            if tokens > max_tokens
+                && let Some(checkpoint) = summary.take()
+            {
+                tokens -= checkpoint.tokens;
+            }
+            if tokens > max_tokens {
+                return Err(DbError::InvalidData(format!(
+                    "max_tokens {} is below the {} tokens the system messages and the leaf \
+                     message need",
+                    max_tokens, tokens
+                )));
+            }
+        }
+
+        let messages = system.into_iter().chain(summary).chain(turns).collect();
+
+        Ok(ConversationContext {
+            messages,
+            tokens,
+            summarized,
+            truncated,
+        })
+    }
+
+    /// The checkpoint whose range ends deepest on `path`, with the positions
+    /// in `path` it covers. The leaf itself is never summarized away.
+    async fn deepest_checkpoint(
+        &self,
+        path: &[Message],
+        private: &PrivateMessages,
+    ) -> Result<Option<(std::ops::Range<usize>, Message)>, DbError> {
+        let Some(leaf) = path.last() else {
+            return Ok(None);
+        };
+        let position = |id: Uuid| path.iter().position(|m| m.message_id == id);
+
+        let checkpoints = self
+            .lineage_repo
+            .get_checkpoints(leaf.conversation_id)
+            .await?;
+
+        Ok(checkpoints
+            .into_iter()
+            .filter(|checkpoint| !private.hides(checkpoint))
+            .filter_map(|checkpoint| {
+                let ContentType::Summary(summary) = &checkpoint.content else {
+                    return None;
+                };
+                let from = position(summary.from_message_id)?;
+                let to = position(summary.to_message_id)?;
+                (from <= to && to + 1 < path.len()).then_some((from..to + 1, checkpoint))
+            })
+            .max_by_key(|(range, _)| (range.end, range.len())))
+    }
+}
+
+fn sized(message: Message) -> ContextMessage {
+    let tokens = estimate_tokens(&message.content);
+    ContextMessage { message, tokens }
+}
+
+/// Approximate token count; text is counted directly, other content by its
+/// JSON form
+pub fn estimate_tokens(content: &ContentType) -> usize {
+    let chars = match content {
+        ContentType::Text(text) => text.text.chars().count(),
+        ContentType::Summary(summary) => summary.text.chars().count(),
+        other => other
+            .to_json_string()
+            .map(|json| json.chars().count())
+            .unwrap_or_default(),
+    };
+    chars.div_ceil(CHARS_PER_TOKEN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Conversation, TextContent};
+    use crate::repositories::Storage;
+    use crate::utils::{compute_lineage, new_message_id};
+    use std::collections::HashMap;
+
+    fn reply(parent: &Message, role: MessageRole, text: &str) -> Message {
+        let message_id = new_message_id();
+        Message {
+            conversation_id: parent.conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role,
+            content: ContentType::Text(TextContent {
+                text: text.to_string(),
+            }),
+            content_metadata: HashMap::new(),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: chrono::Utc::now(),
+            created_by: "demo".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_context_orders_summarizes_and_trims() {
+        let storage = Storage::memory();
+        let service = ContextService::new(storage.lineage.clone());
+
+        let conversation = Conversation::new("Context".to_string(), "demo".to_string());
+        let root = conversation.root_message.clone();
+        let system = reply(&root, MessageRole::System, "Be brief.");
+        let first = reply(&system, MessageRole::Human, &"a".repeat(40));
+        let second = reply(&first, MessageRole::Assistant, &"b".repeat(40));
+        let third = reply(&second, MessageRole::Human, &"c".repeat(40));
+        let leaf = reply(&third, MessageRole::Assistant, &"d".repeat(40));
+        let path = [root, system, first, second, third, leaf.clone()];
+        storage.lineage.batch_insert_messages(&path).await.unwrap();
+
+        let ids = |context: &ConversationContext| -> Vec<Uuid> {
+            context
+                .messages
+                .iter()
+                .map(|m| m.message.message_id)
+                .collect()
+        };
+
+        let full = service
+            .build_context(
+                leaf.conversation_id,
+                leaf.message_id,
+                None,
+                ContextStrategy::TruncateOldest,
+                &PrivateMessages::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(&full),
+            path[1..].iter().map(|m| m.message_id).collect::<Vec<_>>()
+        );
+        assert_eq!(full.tokens, 3 + 4 * 10);
+
+        // Budget for the system message and two turns
+        let trimmed = service
+            .build_context(
+                leaf.conversation_id,
+                leaf.message_id,
+                Some(25),
+                ContextStrategy::TruncateOldest,
+                &PrivateMessages::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(&trimmed),
+            vec![path[1].message_id, path[4].message_id, leaf.message_id]
+        );
+        assert_eq!(trimmed.truncated, 2);
+
+        let checkpoint_id = new_message_id();
+        let checkpoint = Message {
+            message_id: checkpoint_id,
+            role: MessageRole::System,
+            content: ContentType::Summary(crate::domain::SummaryContent {
+                text: "ab".to_string(),
+                from_message_id: path[2].message_id,
+                to_message_id: path[3].message_id,
+                message_count: 2,
+            }),
+            lineage: compute_lineage(&path[3].lineage, checkpoint_id),
+            ..path[3].clone()
+        };
+        storage
+            .lineage
+            .insert_checkpoint(&checkpoint)
+            .await
+            .unwrap();
+
+        let summarized = service
+            .build_context(
+                leaf.conversation_id,
+                leaf.message_id,
+                Some(25),
+                ContextStrategy::Summary,
+                &PrivateMessages::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            ids(&summarized),
+            vec![
+                path[1].message_id,
+                checkpoint_id,
+                path[4].message_id,
+                leaf.message_id
+            ]
+        );
+        assert_eq!((summarized.summarized, summarized.truncated), (2, 0));
+        assert_eq!(summarized.tokens, 3 + 1 + 2 * 10);
+
+        let too_small = service
+            .build_context(
+                leaf.conversation_id,
+                leaf.message_id,
+                Some(5),
+                ContextStrategy::Summary,
+                &PrivateMessages::default(),
+            )
+            .await;
+        assert!(matches!(too_small, Err(DbError::InvalidData(_))));
+    }
+}
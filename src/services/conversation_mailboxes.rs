@@ -0,0 +1,230 @@
+use futures::FutureExt;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+use crate::config::MailboxesConfig;
+
+type Write = BoxFuture<'static, ()>;
+type Mailboxes = Arc<Mutex<HashMap<Uuid, mpsc::Sender<Write>>>>;
+
+/// Per-conversation mailboxes in front of conversation writes. Each
+/// conversation with writes in flight gets a task that runs them one at a
+/// time in arrival order, so concurrent clients can't interleave the reads
+/// and writes of e.g. an append and a move. Tasks are created on the first
+/// write and end once idle for `idle_secs`.
+///
+/// Writes run on the conversation's task, so a client hanging up doesn't
+/// cancel one halfway. When disabled, writes run in the caller's task.
+pub struct ConversationMailboxes {
+    enabled: bool,
+    capacity: usize,
+    idle: Duration,
+    mailboxes: Mailboxes,
+}
+
+impl ConversationMailboxes {
+    pub fn new(config: MailboxesConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            capacity: config.capacity,
+            idle: Duration::from_secs(config.idle_secs),
+            mailboxes: Mailboxes::default(),
+        }
+    }
+
+    /// Run `write` after the conversation's earlier writes, and return its
+    /// output. A panic in `write` resumes in the caller. `write` must not
+    /// itself wait on a write to the same conversation, which would never
+    /// start.
+    pub async fn run<T, F>(&self, conversation_id: Uuid, write: F) -> T
+    where
+        T: Send + 'static,
+        F: Future<Output = T> + Send + 'static,
+    {
+        if !self.enabled {
+            return write.await;
+        }
+
+        let (reply, output) = oneshot::channel();
+        let mut write: Write = Box::pin(async move {
+            let _ = reply.send(AssertUnwindSafe(write).catch_unwind().await);
+        });
+        loop {
+            let sender = self.sender(conversation_id);
+            match sender.send(write).await {
+                Ok(()) => break,
+                // The task ended after the sender was taken; start a new one
+                Err(mpsc::error::SendError(returned)) => {
+                    self.remove_closed(conversation_id);
+                    write = returned;
+                }
+            }
+        }
+
+        match output
+            .await
+            .expect("conversation mailbox dropped a queued write")
+        {
+            Ok(output) => output,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    }
+
+    /// Conversations with a running task
+    pub fn active(&self) -> usize {
+        self.mailboxes
+            .lock()
+            .expect("conversation mailboxes lock poisoned")
+            .len()
+    }
+
+    /// The conversation's mailbox, starting its task if there is none
+    fn sender(&self, conversation_id: Uuid) -> mpsc::Sender<Write> {
+        let mut mailboxes = self
+            .mailboxes
+            .lock()
+            .expect("conversation mailboxes lock poisoned");
+        mailboxes
+            .entry(conversation_id)
+            .or_insert_with(|| {
+                let (sender, receiver) = mpsc::channel(self.capacity);
+                tokio::spawn(run_mailbox(
+                    conversation_id,
+                    receiver,
+                    self.idle,
+                    self.mailboxes.clone(),
+                ));
+                sender
+            })
+            .clone()
+    }
+
+    fn remove_closed(&self, conversation_id: Uuid) {
+        let mut mailboxes = self
+            .mailboxes
+            .lock()
+            .expect("conversation mailboxes lock poisoned");
+        if mailboxes
+            .get(&conversation_id)
+            .is_some_and(|sender| sender.is_closed())
+        {
+            mailboxes.remove(&conversation_id);
+        }
+    }
+}
+
+/// Run the conversation's writes until none came for `idle`
+async fn run_mailbox(
+    conversation_id: Uuid,
+    mut receiver: mpsc::Receiver<Write>,
+    idle: Duration,
+    mailboxes: Mailboxes,
+) {
+    loop {
+        match tokio::time::timeout(idle, receiver.recv()).await {
+            Ok(Some(write)) => write.await,
+            Ok(None) => return,
+            Err(_) => {
+                let mut mailboxes = mailboxes
+                    .lock()
+                    .expect("conversation mailboxes lock poisoned");
+                // Senders are only handed out under the lock, so with just the
+                // registry's left and nothing queued no write can still arrive
+                let unused = mailboxes
+                    .get(&conversation_id)
+                    .is_none_or(|sender| sender.strong_count() == 1);
+                if unused && receiver.is_empty() {
+                    mailboxes.remove(&conversation_id);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn mailboxes(enabled: bool) -> Arc<ConversationMailboxes> {
+        Arc::new(ConversationMailboxes {
+            enabled,
+            capacity: 4,
+            idle: Duration::from_millis(20),
+            mailboxes: Mailboxes::default(),
+        })
+    }
+
+    /// Starts `writes` concurrent writes that each read a counter, yield,
+    /// then write it back incremented; returns the final count
+    async fn race(mailboxes: Arc<ConversationMailboxes>, writes: usize) -> usize {
+        let conversation_id = Uuid::new_v4();
+        let counter = Arc::new(AtomicUsize::new(0));
+        let tasks: Vec<_> = (0..writes)
+            .map(|_| {
+                let (mailboxes, counter) = (mailboxes.clone(), counter.clone());
+                tokio::spawn(async move {
+                    mailboxes
+                        .run(conversation_id, async move {
+                            let read = counter.load(Ordering::SeqCst);
+                            tokio::task::yield_now().await;
+                            counter.store(read + 1, Ordering::SeqCst);
+                        })
+                        .await
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        counter.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_writes_to_a_conversation_do_not_interleave() {
+        assert_eq!(race(mailboxes(true), 50).await, 50);
+        assert!(race(mailboxes(false), 50).await < 50);
+    }
+
+    #[tokio::test]
+    async fn test_idle_mailboxes_end_and_restart() {
+        let mailboxes = mailboxes(true);
+        let conversation_id = Uuid::new_v4();
+
+        assert_eq!(mailboxes.run(conversation_id, async { 1 }).await, 1);
+        assert_eq!(mailboxes.active(), 1);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(mailboxes.active(), 0);
+
+        assert_eq!(mailboxes.run(conversation_id, async { 2 }).await, 2);
+        assert_eq!(mailboxes.active(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_panics_reach_the_caller_and_spare_the_mailbox() {
+        let mailboxes = mailboxes(true);
+        let conversation_id = Uuid::new_v4();
+
+        let panicking = {
+            let mailboxes = mailboxes.clone();
+            tokio::spawn(async move {
+                mailboxes
+                    .run(conversation_id, async { panic!("write failed") })
+                    .await
+            })
+        };
+        assert!(panicking.await.unwrap_err().is_panic());
+        assert_eq!(
+            mailboxes.run(conversation_id, async { "next" }).await,
+            "next"
+        );
+    }
+}
@@ -1,26 +1,83 @@
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use std::collections::HashMap;
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::config::AppConfig;
-use crate::db::DbError;
-use crate::domain::{ContentType, Conversation, Message, MessageRole};
-use crate::repositories::LineageRepository;
-use crate::utils::{compute_lineage, validate_lineage_depth};
+use crate::db::{ConversationTitleRow, DbError, ForkLinkRow};
+use crate::domain::{
+    AuthorKind, Branch, Change, ChangeKind, ContentType, Conversation, ConversationEvent,
+    ConversationLock, EventKind, LANGUAGE_KEY, Message, MessageMoved, MessageRole,
+    NotificationKind, Permission, SummaryContent,
+};
+use crate::repositories::LineageStore;
+use crate::services::{
+    ChangeFeed, ImageService, MessageTree, NotificationService, PrivateMessages, ShareService,
+};
+use crate::utils::content_hash::content_hash;
+use crate::utils::content_pipeline::ContentPipeline;
+use crate::utils::language;
+use crate::utils::pii::PiiScrubber;
+use crate::utils::{
+    compute_lineage, is_ancestor, nest_messages, new_message_id, rebase_lineage,
+    validate_lineage_depth,
+};
+
+/// Most messages of a conversation that can be private at once; they are
+/// listed in the root message's metadata
+const MAX_PRIVATE_MESSAGES: usize = 1000;
+
+/// Messages of one conversation with identical content
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub content_hash: String,
+    pub content_type: String,
+    /// Oldest first
+    pub message_ids: Vec<Uuid>,
+}
 
 pub struct ConversationService {
-    lineage_repo: LineageRepository,
+    lineage_repo: Arc<dyn LineageStore>,
+    change_feed: ChangeFeed,
     app_config: AppConfig,
+    notifications: Arc<NotificationService>,
+    pii: Arc<PiiScrubber>,
+    /// Runs over new messages before they are stored
+    content_pipeline: ContentPipeline,
+    images: Arc<ImageService>,
+    shares: Arc<ShareService>,
 }
 
 impl ConversationService {
-    pub fn new(lineage_repo: LineageRepository, app_config: AppConfig) -> Self {
+    pub fn new(
+        lineage_repo: Arc<dyn LineageStore>,
+        change_feed: ChangeFeed,
+        app_config: AppConfig,
+        notifications: Arc<NotificationService>,
+        pii: Arc<PiiScrubber>,
+        images: Arc<ImageService>,
+        shares: Arc<ShareService>,
+    ) -> Self {
         Self {
             lineage_repo,
+            change_feed,
             app_config,
+            notifications,
+            pii,
+            content_pipeline: ContentPipeline::default(),
+            images,
+            shares,
         }
     }
 
-    /// Create a new conversation with a root message
+    pub fn with_content_pipeline(mut self, content_pipeline: ContentPipeline) -> Self {
+        self.content_pipeline = content_pipeline;
+        self
+    }
+
+    /// Create a new conversation with a root message, shared as the
+    /// creator's preferences say
     pub async fn create_conversation(
         &self,
         title: String,
@@ -29,8 +86,26 @@ impl ConversationService {
         let conversation = Conversation::new(title, created_by);
 
         // Insert the root message
-        self.lineage_repo
-            .insert_message(&conversation.root_message)
+        self.write_metadata(&conversation).await?;
+        count_new_messages(
+            self.lineage_repo.as_ref(),
+            conversation.conversation_id,
+            std::slice::from_ref(&conversation.root_message),
+        )
+        .await?;
+        self.index_conversation(&conversation).await?;
+
+        self.change_feed
+            .record(
+                conversation.conversation_id,
+                ChangeKind::ConversationUpdated,
+                conversation.root_message.message_id,
+                &conversation.root_message,
+            )
+            .await?;
+
+        self.shares
+            .apply_default_shares(conversation.conversation_id, conversation.created_by())
             .await?;
 
         Ok(conversation)
@@ -38,28 +113,29 @@ impl ConversationService {
 
     /// Get conversation metadata (root message)
     pub async fn get_conversation(&self, conversation_id: Uuid) -> Result<Conversation, DbError> {
-        // Find the root message (parent_message_id is NULL)
-        let all_messages = self.lineage_repo.get_all_messages(conversation_id).await?;
-
-        let root_message = all_messages
-            .into_iter()
-            .find(|m| m.is_root())
-            .ok_or(DbError::NotFound)?;
-
         Ok(Conversation {
             conversation_id,
-            root_message,
+            root_message: self.lineage_repo.get_root_message(conversation_id).await?,
         })
     }
 
-    /// Update conversation metadata (root message)
+    /// Update conversation metadata (root message). Publishing it, or
+    /// making it private again, updates the share everyone gets.
     pub async fn update_conversation(
         &self,
         conversation_id: Uuid,
         title: Option<String>,
         description: Option<String>,
+        is_public: Option<bool>,
+        language: Option<String>,
     ) -> Result<(), DbError> {
+        let language = language
+            .map(|tag| language::normalize(&tag))
+            .transpose()
+            .map_err(DbError::InvalidData)?;
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
         let mut conversation = self.get_conversation(conversation_id).await?;
+        let was_public = conversation.metadata().is_some_and(|m| m.is_public);
 
         // Update the root message content
         if let ContentType::Metadata(ref mut metadata) = conversation.root_message.content {
@@ -69,19 +145,291 @@ impl ConversationService {
             if let Some(new_desc) = description {
                 metadata.description = Some(new_desc);
             }
+            if let Some(is_public) = is_public {
+                metadata.is_public = is_public;
+            }
+            if let Some(language) = language {
+                metadata.language = Some(language);
+            }
         }
 
         // Re-insert the root message (upsert behavior)
-        self.lineage_repo
-            .insert_message(&conversation.root_message)
+        self.write_metadata(&conversation).await?;
+        self.index_conversation(&conversation).await?;
+
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::ConversationUpdated,
+                conversation.root_message.message_id,
+                &conversation.root_message,
+            )
             .await?;
 
+        let is_public = conversation.metadata().is_some_and(|m| m.is_public);
+        if is_public != was_public {
+            self.shares
+                .apply_visibility(conversation_id, conversation.created_by(), is_public)
+                .await?;
+        }
+
         Ok(())
     }
 
+    /// Whether `user_id` may read the conversation: its owner, anyone if it
+    /// is public, or a user with a share
+    pub async fn can_read(
+        &self,
+        conversation: &Conversation,
+        user_id: &str,
+    ) -> Result<bool, DbError> {
+        if conversation.created_by() == user_id
+            || conversation.metadata().is_some_and(|m| m.is_public)
+        {
+            return Ok(true);
+        }
+        self.shares
+            .check_permission(conversation.conversation_id, user_id, Permission::Read)
+            .await
+    }
+
+    /// Whether `user_id` may write to the conversation: its owner, or a user
+    /// with a `branch` share
+    pub async fn can_write(
+        &self,
+        conversation: &Conversation,
+        user_id: &str,
+    ) -> Result<bool, DbError> {
+        if conversation.created_by() == user_id {
+            return Ok(true);
+        }
+        self.shares
+            .check_permission(conversation.conversation_id, user_id, Permission::Branch)
+            .await
+    }
+
+    /// Publish one of the conversation's branches as the path viewers who
+    /// can't write see by default, or stop publishing one with `None`. The
+    /// caller checks that the branch exists.
+    pub async fn set_published_branch(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Option<Uuid>,
+    ) -> Result<Conversation, DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        let mut conversation = self.get_conversation(conversation_id).await?;
+        if let ContentType::Metadata(ref mut metadata) = conversation.root_message.content {
+            metadata.published_branch_id = branch_id;
+        }
+
+        self.write_metadata(&conversation).await?;
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::ConversationUpdated,
+                conversation.root_message.message_id,
+                &conversation.root_message,
+            )
+            .await?;
+
+        Ok(conversation)
+    }
+
+    /// What `viewer` may not read of the conversation
+    pub async fn private_messages(
+        &self,
+        conversation_id: Uuid,
+        viewer: Option<&str>,
+    ) -> Result<PrivateMessages, DbError> {
+        PrivateMessages::for_viewer(self.lineage_repo.as_ref(), conversation_id, viewer).await
+    }
+
+    /// Let `private` know which of `message_ids`, named by branches or events,
+    /// are hidden. See [`PrivateMessages::resolve`].
+    pub async fn resolve_private(
+        &self,
+        conversation_id: Uuid,
+        private: &mut PrivateMessages,
+        message_ids: Vec<Uuid>,
+    ) -> Result<(), DbError> {
+        if private.is_empty() || message_ids.is_empty() {
+            return Ok(());
+        }
+        let messages = self
+            .lineage_repo
+            .get_messages_by_ids(conversation_id, &message_ids)
+            .await?;
+        private.resolve(&messages);
+        Ok(())
+    }
+
+    /// `branches` of the conversation without those ending at a message
+    /// `private` hides
+    pub async fn visible_branches(
+        &self,
+        conversation_id: Uuid,
+        branches: Vec<Branch>,
+        private: &mut PrivateMessages,
+    ) -> Result<Vec<Branch>, DbError> {
+        let leaf_ids = branches.iter().map(|b| b.leaf_message_id).collect();
+        self.resolve_private(conversation_id, private, leaf_ids)
+            .await?;
+
+        Ok(branches
+            .into_iter()
+            .filter(|b| !private.hides_branch(b))
+            .collect())
+    }
+
+    /// Make a message, and the replies below it, readable by the owner only,
+    /// or readable again with `private` false. Returns the private messages.
+    pub async fn set_message_private(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        private: bool,
+    ) -> Result<Vec<Uuid>, DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        let message = self
+            .lineage_repo
+            .get_message(conversation_id, message_id)
+            .await?;
+        if message.is_root() {
+            return Err(DbError::InvalidData(
+                "The root message can't be private".to_string(),
+            ));
+        }
+
+        let mut conversation = self.get_conversation(conversation_id).await?;
+        let ContentType::Metadata(metadata) = &mut conversation.root_message.content else {
+            return Err(DbError::InvalidData(
+                "Invalid root message content".to_string(),
+            ));
+        };
+        let listed = metadata.private_message_ids.contains(&message_id);
+        match (private, listed) {
+            (true, false) => {
+                if metadata.private_message_ids.len() >= MAX_PRIVATE_MESSAGES {
+                    return Err(DbError::LimitExceeded(format!(
+                        "A conversation can have at most {} private messages",
+                        MAX_PRIVATE_MESSAGES
+                    )));
+                }
+                metadata.private_message_ids.push(message_id);
+            }
+            (false, true) => metadata.private_message_ids.retain(|id| *id != message_id),
+            _ => return Ok(metadata.private_message_ids.clone()),
+        }
+        let private_message_ids = metadata.private_message_ids.clone();
+
+        self.write_metadata(&conversation).await?;
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::ConversationUpdated,
+                conversation.root_message.message_id,
+                &conversation.root_message,
+            )
+            .await?;
+
+        Ok(private_message_ids)
+    }
+
     /// Delete an entire conversation
     pub async fn delete_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
-        self.lineage_repo.delete_conversation(conversation_id).await
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        ensure_not_held(self.lineage_repo.as_ref(), conversation_id).await?;
+        let conversation = match self.get_conversation(conversation_id).await {
+            Ok(conversation) => Some(conversation),
+            Err(DbError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+
+        self.lineage_repo
+            .delete_conversation(conversation_id)
+            .await?;
+        if let Some(conversation) = conversation {
+            self.lineage_repo
+                .delete_conversation_title(conversation.created_by(), conversation_id)
+                .await?;
+            if let Some(link) = ForkLinkRow::from_conversation(&conversation) {
+                self.lineage_repo
+                    .delete_fork_link(link.source_conversation_id, conversation_id)
+                    .await?;
+            }
+        }
+        self.images.release_conversation(conversation_id).await?;
+        self.lineage_repo.delete_events(conversation_id).await?;
+        self.change_feed.delete_changes(conversation_id).await
+    }
+
+    /// Conversations created by a user whose title contains `query` (case-insensitive),
+    /// in `language` or a variant of it if given. Titles starting with the query, or
+    /// with a word starting with it, rank first; ties go to the most recently updated.
+    pub async fn search_conversations(
+        &self,
+        user_id: &str,
+        query: &str,
+        language: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<ConversationTitleRow>, DbError> {
+        let query = query.trim().to_lowercase();
+        let language = language
+            .map(language::normalize)
+            .transpose()
+            .map_err(DbError::InvalidData)?;
+        let mut matches: Vec<(u8, ConversationTitleRow)> = self
+            .lineage_repo
+            .get_conversation_titles(user_id)
+            .await?
+            .into_iter()
+            .filter(|entry| {
+                language.as_deref().is_none_or(|filter| {
+                    entry
+                        .language
+                        .as_deref()
+                        .is_some_and(|language| language::matches(language, filter))
+                })
+            })
+            .filter_map(|entry| {
+                let title = entry.title.to_lowercase();
+                let rank = if title.starts_with(&query) {
+                    0
+                } else if title
+                    .split_whitespace()
+                    .any(|word| word.starts_with(&query))
+                {
+                    1
+                } else if title.contains(&query) {
+                    2
+                } else {
+                    return None;
+                };
+                Some((rank, entry))
+            })
+            .collect();
+
+        matches.sort_by(|(rank_a, a), (rank_b, b)| {
+            rank_a.cmp(rank_b).then(b.updated_at.cmp(&a.updated_at))
+        });
+
+        Ok(matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    /// Refresh the title index and, for forks, the source's fork index
+    async fn index_conversation(&self, conversation: &Conversation) -> Result<(), DbError> {
+        if let Some(entry) = ConversationTitleRow::from_conversation(conversation) {
+            self.lineage_repo.upsert_conversation_title(&entry).await?;
+        }
+        if let Some(link) = ForkLinkRow::from_conversation(conversation) {
+            self.lineage_repo.upsert_fork_link(&link).await?;
+        }
+
+        Ok(())
     }
 
     /// Append a new message to a conversation
@@ -94,6 +442,8 @@ impl ConversationService {
         content_metadata: std::collections::HashMap<String, String>,
         created_by: String,
     ) -> Result<Message, DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+
         // Get parent message to compute lineage
         let parent = self
             .lineage_repo
@@ -101,15 +451,23 @@ impl ConversationService {
             .await?;
 
         // Compute new lineage
-        let message_id = Uuid::new_v4();
+        let message_id = new_message_id();
         let lineage = compute_lineage(&parent.lineage, message_id);
 
-        // Validate lineage depth
-        validate_lineage_depth(&lineage, self.app_config.max_lineage_depth)
-            .map_err(DbError::InvalidData)?;
+        // Validate lineage depth, then the width and size limits
+        validate_lineage_depth(&lineage, self.app_config.max_lineage_depth)?;
+        self.ensure_room_for_reply(conversation_id, parent_message_id)
+            .await?;
+        let messages = self.lineage_repo.get_message_count(conversation_id).await?;
+        if messages >= self.app_config.max_messages_per_conversation {
+            return Err(DbError::LimitExceeded(format!(
+                "Conversation has {} messages, the maximum allowed",
+                messages
+            )));
+        }
 
         // Create new message
-        let message = Message {
+        let mut message = Message {
             conversation_id,
             message_id,
             parent_message_id: Some(parent_message_id),
@@ -120,13 +478,551 @@ impl ConversationService {
             created_at: Utc::now(),
             created_by,
         };
+        self.content_pipeline
+            .process(&mut message)
+            .map_err(DbError::InvalidData)?;
+        self.images.validate_urls(&message)?;
+        if self.pii.scrub_on_write() {
+            self.pii.scrub_message(&mut message);
+        }
 
         // Insert message
-        self.lineage_repo.insert_message(&message).await?;
+        self.images.track(std::slice::from_ref(&message)).await?;
+        let event = ConversationEvent::new(conversation_id, EventKind::MessageCreated, &message)?;
+        self.lineage_repo
+            .insert_messages_with_events(std::slice::from_ref(&message), &[event])
+            .await?;
+        count_new_messages(
+            self.lineage_repo.as_ref(),
+            conversation_id,
+            std::slice::from_ref(&message),
+        )
+        .await?;
+
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::MessageCreated,
+                message.message_id,
+                &message,
+            )
+            .await?;
+
+        // The first reply with a language gives the conversation its own
+        if parent.is_root()
+            && let Some(language) = message.language()
+            && matches!(&parent.content, ContentType::Metadata(m) if m.language.is_none())
+        {
+            self.update_conversation(conversation_id, None, None, None, Some(language.into()))
+                .await?;
+        }
+
+        // Replying to someone else's message notifies its author
+        self.notifications
+            .notify(
+                &parent.created_by,
+                NotificationKind::MessageReplied,
+                conversation_id,
+                &message.created_by,
+                Some(message.message_id),
+            )
+            .await;
 
         Ok(message)
     }
 
+    /// Reattach a message (and its whole subtree) to a different parent.
+    /// Returns the updated subtree ordered from the moved message downwards.
+    pub async fn move_message(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        new_parent_message_id: Uuid,
+    ) -> Result<Vec<Message>, DbError> {
+        if message_id == new_parent_message_id {
+            return Err(DbError::InvalidData(
+                "A message cannot be its own parent".to_string(),
+            ));
+        }
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+
+        let message = self
+            .lineage_repo
+            .get_message(conversation_id, message_id)
+            .await?;
+
+        if message.is_root() {
+            return Err(DbError::InvalidData(
+                "The root message cannot be moved".to_string(),
+            ));
+        }
+
+        let new_parent = self
+            .lineage_repo
+            .get_message(conversation_id, new_parent_message_id)
+            .await?;
+
+        // Moving below one of its own descendants would create a cycle
+        if is_ancestor(message_id, &new_parent.lineage) {
+            return Err(DbError::InvalidData(
+                "Cannot move a message below one of its descendants".to_string(),
+            ));
+        }
+
+        if message.parent_message_id != Some(new_parent_message_id) {
+            self.ensure_room_for_reply(conversation_id, new_parent_message_id)
+                .await?;
+        }
+
+        // Only the moved subtree is kept while paging through the conversation
+        let mut all_messages = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, None)
+            .await?;
+
+        let mut moved_messages = Vec::new();
+        while let Some(mut msg) = all_messages.try_next().await? {
+            let Some(lineage) = rebase_lineage(&msg.lineage, message_id, &new_parent.lineage)
+            else {
+                continue;
+            };
+
+            validate_lineage_depth(&lineage, self.app_config.max_lineage_depth)?;
+
+            if msg.message_id == message_id {
+                msg.parent_message_id = Some(new_parent_message_id);
+            }
+            msg.lineage = lineage;
+            moved_messages.push(msg);
+        }
+
+        moved_messages.sort_by_key(|m| m.lineage.len());
+
+        // Re-insert the subtree (upsert behavior), the move logged with its
+        // first batch
+        let mut events = Some(vec![ConversationEvent::new(
+            conversation_id,
+            EventKind::MessageMoved,
+            &MessageMoved {
+                message_id,
+                from_parent_message_id: message.parent_message_id,
+                to_parent_message_id: new_parent_message_id,
+            },
+        )?]);
+        for chunk in moved_messages.chunks(self.app_config.max_batch_size) {
+            let events = events.take().unwrap_or_default();
+            self.lineage_repo
+                .insert_messages_with_events(chunk, &events)
+                .await?;
+        }
+        if let Some(old_parent_id) = message
+            .parent_message_id
+            .filter(|&id| id != new_parent_message_id)
+        {
+            self.lineage_repo
+                .add_message_counts(
+                    conversation_id,
+                    0,
+                    &[(old_parent_id, -1), (new_parent_message_id, 1)],
+                )
+                .await?;
+        }
+
+        for msg in &moved_messages {
+            self.change_feed
+                .record(
+                    conversation_id,
+                    ChangeKind::MessageUpdated,
+                    msg.message_id,
+                    msg,
+                )
+                .await?;
+        }
+
+        Ok(moved_messages)
+    }
+
+    /// Write the root message with a `MetadataUpdated` event
+    async fn write_metadata(&self, conversation: &Conversation) -> Result<(), DbError> {
+        let event = ConversationEvent::new(
+            conversation.conversation_id,
+            EventKind::MetadataUpdated,
+            &conversation.metadata(),
+        )?;
+
+        self.lineage_repo
+            .insert_messages_with_events(std::slice::from_ref(&conversation.root_message), &[event])
+            .await
+    }
+
+    /// Rewrite stored messages in place with a `MessageUpdated` event each
+    async fn write_updated(&self, messages: &[Message]) -> Result<(), DbError> {
+        let events = messages
+            .iter()
+            .map(|message| {
+                ConversationEvent::new(message.conversation_id, EventKind::MessageUpdated, message)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.lineage_repo
+            .insert_messages_with_events(messages, &events)
+            .await
+    }
+
+    /// Fail if the message already has as many replies as allowed
+    async fn ensure_room_for_reply(
+        &self,
+        conversation_id: Uuid,
+        parent_message_id: Uuid,
+    ) -> Result<(), DbError> {
+        let children = self
+            .lineage_repo
+            .get_reply_count(conversation_id, parent_message_id)
+            .await?;
+        if children >= self.app_config.max_children_per_message {
+            return Err(DbError::LimitExceeded(format!(
+                "Message {} has {} replies, the maximum allowed",
+                parent_message_id, children
+            )));
+        }
+        Ok(())
+    }
+
+    /// Groups of messages whose content is identical, largest groups first.
+    /// The root and the messages `viewer` may not read are never part of a
+    /// group.
+    pub async fn find_duplicates(
+        &self,
+        conversation_id: Uuid,
+        viewer: Option<&str>,
+    ) -> Result<Vec<DuplicateGroup>, DbError> {
+        let private = self.private_messages(conversation_id, viewer).await?;
+        let mut messages = self.lineage_repo.get_all_messages(conversation_id).await?;
+        messages.retain(|m| !m.is_root() && !private.hides(m));
+        sort_chronologically(&mut messages);
+
+        let mut groups: std::collections::HashMap<(&str, String), Vec<Uuid>> =
+            std::collections::HashMap::new();
+        for msg in &messages {
+            let content_data = msg
+                .content
+                .to_json_string()
+                .map_err(|e| DbError::SerializationError(e.to_string()))?;
+            groups
+                .entry((msg.content.to_type_string(), content_data))
+                .or_default()
+                .push(msg.message_id);
+        }
+
+        let mut duplicates: Vec<DuplicateGroup> = groups
+            .into_iter()
+            .filter(|(_, ids)| ids.len() > 1)
+            .map(
+                |((content_type, content_data), message_ids)| DuplicateGroup {
+                    content_hash: content_hash(&content_data),
+                    content_type: content_type.to_string(),
+                    message_ids,
+                },
+            )
+            .collect();
+        duplicates.sort_by(|a, b| {
+            b.message_ids
+                .len()
+                .cmp(&a.message_ids.len())
+                .then(a.message_ids[0].cmp(&b.message_ids[0]))
+        });
+
+        Ok(duplicates)
+    }
+
+    /// Detect the language of every stored message that has none, and give
+    /// conversations without a language the one of their earliest reply to
+    /// the root. Idempotent; scans every message, so run it once to fill in
+    /// messages stored before language detection was enabled. Returns the
+    /// messages and conversations updated.
+    pub async fn backfill_languages(&self, page_size: i32) -> Result<(usize, usize), DbError> {
+        let mut messages = self.lineage_repo.scan_messages(page_size).await?;
+
+        let mut updated_messages = 0;
+        let mut first_replies: HashMap<Uuid, (DateTime<Utc>, Uuid, String)> = HashMap::new();
+        while let Some(mut message) = messages.try_next().await? {
+            if message.is_root() {
+                continue;
+            }
+            if message.language().is_none() {
+                let Some(detected) = message.language_sample().and_then(language::detect) else {
+                    continue;
+                };
+                message
+                    .content_metadata
+                    .insert(LANGUAGE_KEY.to_string(), detected.to_string());
+                self.write_updated(std::slice::from_ref(&message)).await?;
+                updated_messages += 1;
+            }
+
+            // Lineage starts at the root, so replies to it have two entries
+            if message.depth() == 2
+                && let Some(language) = message.language()
+            {
+                let reply = (message.created_at, message.lineage[0], language.to_string());
+                first_replies
+                    .entry(message.conversation_id)
+                    .and_modify(|first| {
+                        if reply.0 < first.0 {
+                            *first = reply.clone();
+                        }
+                    })
+                    .or_insert(reply);
+            }
+        }
+
+        let mut updated_conversations = 0;
+        for (conversation_id, (_, root_id, language)) in first_replies {
+            let mut root = self
+                .lineage_repo
+                .get_message(conversation_id, root_id)
+                .await?;
+            let ContentType::Metadata(metadata) = &mut root.content else {
+                continue;
+            };
+            if metadata.language.is_some() {
+                continue;
+            }
+            metadata.language = Some(language);
+            let conversation = Conversation {
+                conversation_id,
+                root_message: root,
+            };
+            self.write_metadata(&conversation).await?;
+            self.index_conversation(&conversation).await?;
+            updated_conversations += 1;
+        }
+
+        Ok((updated_messages, updated_conversations))
+    }
+
+    /// Re-wrap every conversation's data keys under the active master key,
+    /// e.g. after a master key was rotated. Message rows are left as they
+    /// are. Returns how many keys were re-wrapped.
+    pub async fn rewrap_data_keys(&self) -> Result<usize, DbError> {
+        self.lineage_repo.rewrap_data_keys().await
+    }
+
+    /// Bring the message and reply counters in line with the stored
+    /// messages, adding what they are missing. Idempotent while no messages
+    /// are being written; scans every message, so run it once to count
+    /// conversations stored before the counters existed. Returns the
+    /// conversations whose counters changed.
+    pub async fn backfill_message_counts(&self, page_size: i32) -> Result<usize, DbError> {
+        let mut messages = self.lineage_repo.scan_messages(page_size).await?;
+
+        let mut counts: HashMap<Uuid, (usize, HashMap<Uuid, usize>)> = HashMap::new();
+        while let Some(message) = messages.try_next().await? {
+            let (messages, replies) = counts.entry(message.conversation_id).or_default();
+            *messages += 1;
+            if let Some(parent_id) = message.parent_message_id {
+                *replies.entry(parent_id).or_default() += 1;
+            }
+        }
+
+        let mut updated = 0;
+        for (conversation_id, (messages, replies)) in counts {
+            let counted = self.lineage_repo.get_message_count(conversation_id).await?;
+            let mut reply_changes = Vec::new();
+            for (parent_id, replies) in replies {
+                let counted = self
+                    .lineage_repo
+                    .get_reply_count(conversation_id, parent_id)
+                    .await?;
+                if counted != replies {
+                    reply_changes.push((parent_id, replies as i64 - counted as i64));
+                }
+            }
+            if counted == messages && reply_changes.is_empty() {
+                continue;
+            }
+            self.lineage_repo
+                .add_message_counts(
+                    conversation_id,
+                    messages as i64 - counted as i64,
+                    &reply_changes,
+                )
+                .await?;
+            updated += 1;
+        }
+
+        Ok(updated)
+    }
+
+    /// Mask personal data in the text of every message of a conversation, in
+    /// place. The original text is not kept. Returns the scrubbed messages.
+    pub async fn scrub_conversation(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        let mut all_messages = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, None)
+            .await?;
+        let mut scrubbed = Vec::new();
+        while let Some(mut msg) = all_messages.try_next().await? {
+            if self.pii.scrub_message(&mut msg) {
+                scrubbed.push(msg);
+            }
+        }
+        scrubbed.sort_by_key(|m| (m.created_at, m.message_id));
+
+        // Re-insert the scrubbed messages (upsert behavior)
+        for chunk in scrubbed.chunks(self.app_config.max_batch_size) {
+            self.write_updated(chunk).await?;
+        }
+
+        for msg in &scrubbed {
+            self.change_feed
+                .record(
+                    conversation_id,
+                    ChangeKind::MessageUpdated,
+                    msg.message_id,
+                    msg,
+                )
+                .await?;
+        }
+
+        Ok(scrubbed)
+    }
+
+    /// Store a checkpoint summarizing the lineage range `from_message_id..=to_message_id`.
+    /// Defaults to everything after the root when `from_message_id` is omitted.
+    pub async fn create_checkpoint(
+        &self,
+        conversation_id: Uuid,
+        from_message_id: Option<Uuid>,
+        to_message_id: Uuid,
+        summary: String,
+        created_by: String,
+    ) -> Result<Message, DbError> {
+        ensure_unlocked(self.lineage_repo.as_ref(), conversation_id).await?;
+        let to_message = self
+            .lineage_repo
+            .get_message(conversation_id, to_message_id)
+            .await?;
+
+        let from_message_id = from_message_id
+            .or_else(|| to_message.lineage.get(1).copied())
+            .unwrap_or(to_message_id);
+
+        let from_position = to_message
+            .lineage
+            .iter()
+            .position(|id| *id == from_message_id)
+            .ok_or_else(|| {
+                DbError::InvalidData(format!(
+                    "Message {} is not an ancestor of {}",
+                    from_message_id, to_message_id
+                ))
+            })?;
+
+        let checkpoint_id = new_message_id();
+        let checkpoint = Message {
+            conversation_id,
+            message_id: checkpoint_id,
+            parent_message_id: Some(to_message_id),
+            role: MessageRole::System,
+            content: ContentType::Summary(SummaryContent {
+                text: summary,
+                from_message_id,
+                to_message_id,
+                message_count: to_message.lineage.len() - from_position,
+            }),
+            content_metadata: std::collections::HashMap::new(),
+            lineage: compute_lineage(&to_message.lineage, checkpoint_id),
+            created_at: Utc::now(),
+            created_by,
+        };
+
+        self.lineage_repo.insert_checkpoint(&checkpoint).await?;
+
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::CheckpointCreated,
+                checkpoint.message_id,
+                &checkpoint,
+            )
+            .await?;
+
+        Ok(checkpoint)
+    }
+
+    /// Get all checkpoints of a conversation
+    /// Make the conversation read-only until it is unlocked. Locking a locked
+    /// conversation keeps its original lock.
+    pub async fn lock_conversation(
+        &self,
+        conversation_id: Uuid,
+        locked_by: String,
+        reason: Option<String>,
+    ) -> Result<ConversationLock, DbError> {
+        if let Some(lock) = self.lineage_repo.get_lock(conversation_id).await? {
+            return Ok(lock);
+        }
+        self.get_conversation(conversation_id).await?;
+
+        let lock = ConversationLock {
+            conversation_id,
+            locked_by,
+            locked_at: Utc::now(),
+            reason,
+        };
+        self.lineage_repo.insert_lock(&lock).await?;
+
+        Ok(lock)
+    }
+
+    /// Accept writes to the conversation again
+    pub async fn unlock_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        if self.lineage_repo.get_lock(conversation_id).await?.is_none() {
+            return Err(DbError::NotFound);
+        }
+
+        self.lineage_repo.delete_lock(conversation_id).await
+    }
+
+    pub async fn get_lock(&self, conversation_id: Uuid) -> Result<ConversationLock, DbError> {
+        self.lineage_repo
+            .get_lock(conversation_id)
+            .await?
+            .ok_or(DbError::NotFound)
+    }
+
+    pub async fn get_checkpoints(&self, conversation_id: Uuid) -> Result<Vec<Message>, DbError> {
+        self.lineage_repo.get_checkpoints(conversation_id).await
+    }
+
+    /// Get the changes recorded after the given feed position
+    pub async fn get_changes(
+        &self,
+        conversation_id: Uuid,
+        since: Option<(DateTime<Utc>, Uuid)>,
+        limit: i32,
+    ) -> Result<Vec<Change>, DbError> {
+        let (since, after_change_id) = since.unwrap_or((DateTime::UNIX_EPOCH, Uuid::nil()));
+
+        self.change_feed
+            .get_changes_since(conversation_id, since, after_change_id, limit)
+            .await
+    }
+
+    /// Get up to `limit` events of the conversation's event log, starting at `from_seq`
+    pub async fn get_events(
+        &self,
+        conversation_id: Uuid,
+        from_seq: i64,
+        limit: i32,
+    ) -> Result<Vec<ConversationEvent>, DbError> {
+        self.lineage_repo
+            .get_events(conversation_id, from_seq, limit)
+            .await
+    }
+
     /// Get a specific message
     pub async fn get_message(
         &self,
@@ -144,9 +1040,13 @@ impl ConversationService {
         conversation_id: Uuid,
         parent_message_id: Uuid,
     ) -> Result<Vec<Message>, DbError> {
-        self.lineage_repo
+        let mut children = self
+            .lineage_repo
             .get_children(conversation_id, parent_message_id)
-            .await
+            .await?;
+        sort_chronologically(&mut children);
+
+        Ok(children)
     }
 
     /// Get the lineage path for a message (from root to this message)
@@ -165,11 +1065,860 @@ impl ConversationService {
             .await
     }
 
-    /// Get entire conversation tree
+    /// The last `last` ancestors of a message, oldest first, and how many
+    /// ancestors it has in total. Only the window is read from storage.
+    pub async fn get_ancestors(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        last: usize,
+    ) -> Result<(Vec<Message>, usize), DbError> {
+        let message = self
+            .lineage_repo
+            .get_message(conversation_id, message_id)
+            .await?;
+
+        // The lineage ends with the message itself
+        let ancestors = &message.lineage[..message.lineage.len().saturating_sub(1)];
+        let window = &ancestors[ancestors.len().saturating_sub(last)..];
+        let messages = self
+            .lineage_repo
+            .get_messages_by_ids(conversation_id, window)
+            .await?;
+
+        Ok((messages, ancestors.len()))
+    }
+
+    /// Get entire conversation tree, oldest message first
     pub async fn get_conversation_tree(
         &self,
         conversation_id: Uuid,
     ) -> Result<Vec<Message>, DbError> {
-        self.lineage_repo.get_all_messages(conversation_id).await
+        let mut messages = self.lineage_repo.get_all_messages(conversation_id).await?;
+        sort_chronologically(&mut messages);
+
+        Ok(messages)
+    }
+
+    /// The conversation's messages whose depth (lineage length, 1 for the
+    /// root) is within `min_depth..=max_depth`, and written by `author_kind`
+    /// if given, oldest first
+    pub async fn get_conversation_levels(
+        &self,
+        conversation_id: Uuid,
+        min_depth: Option<usize>,
+        max_depth: Option<usize>,
+        author_kind: Option<AuthorKind>,
+    ) -> Result<Vec<Message>, DbError> {
+        let in_levels = level_filter(min_depth, max_depth, author_kind)?;
+        let mut messages: Vec<Message> = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, None)
+            .await?
+            .try_filter(|m| futures::future::ready(in_levels(m)))
+            .try_collect()
+            .await?;
+        sort_chronologically(&mut messages);
+
+        Ok(messages)
+    }
+
+    /// The conversation's messages nested under their parents, from the root
+    pub async fn get_message_tree(&self, conversation_id: Uuid) -> Result<MessageTree, DbError> {
+        let messages = self.get_conversation_tree(conversation_id).await?;
+        nest_messages(messages).ok_or(DbError::NotFound)
+    }
+}
+
+/// Whether a message's depth (lineage length, 1 for the root) is within
+/// `min_depth..=max_depth`, and it was written by `author_kind` if given
+pub fn level_filter(
+    min_depth: Option<usize>,
+    max_depth: Option<usize>,
+    author_kind: Option<AuthorKind>,
+) -> Result<impl Fn(&Message) -> bool, DbError> {
+    if let (Some(min), Some(max)) = (min_depth, max_depth)
+        && min > max
+    {
+        return Err(DbError::InvalidData(
+            "`min_depth` must not be greater than `max_depth`".to_string(),
+        ));
+    }
+
+    Ok(move |m: &Message| {
+        min_depth.is_none_or(|min| m.depth() >= min)
+            && max_depth.is_none_or(|max| m.depth() <= max)
+            && author_kind.is_none_or(|kind| m.author_kind() == kind)
+    })
+}
+
+/// Fail with `Locked` if the conversation is read-only
+pub(crate) async fn ensure_unlocked(
+    lineage_repo: &dyn LineageStore,
+    conversation_id: Uuid,
+) -> Result<(), DbError> {
+    match lineage_repo.get_lock(conversation_id).await? {
+        Some(_) => Err(DbError::Locked(conversation_id)),
+        None => Ok(()),
+    }
+}
+
+/// Fail with `LegalHold` if the conversation must be kept
+pub(crate) async fn ensure_not_held(
+    lineage_repo: &dyn LineageStore,
+    conversation_id: Uuid,
+) -> Result<(), DbError> {
+    match lineage_repo.get_hold(conversation_id).await? {
+        Some(_) => Err(DbError::LegalHold(conversation_id)),
+        None => Ok(()),
+    }
+}
+
+/// Count `messages`, just written to the conversation for the first time,
+/// in its message counter and the reply counters of their parents
+pub(crate) async fn count_new_messages(
+    lineage_repo: &dyn LineageStore,
+    conversation_id: Uuid,
+    messages: &[Message],
+) -> Result<(), DbError> {
+    let mut replies: HashMap<Uuid, i64> = HashMap::new();
+    for parent_id in messages.iter().filter_map(|m| m.parent_message_id) {
+        *replies.entry(parent_id).or_default() += 1;
+    }
+    let replies: Vec<(Uuid, i64)> = replies.into_iter().collect();
+
+    lineage_repo
+        .add_message_counts(conversation_id, messages.len() as i64, &replies)
+        .await
+}
+
+/// Messages with time-ordered IDs already come back in creation order; this
+/// also orders messages created before IDs were time-ordered
+fn sort_chronologically(messages: &mut [Message]) {
+    messages.sort_by_key(|m| (m.created_at, m.message_id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use crate::config::PiiConfig;
+    use crate::domain::{EVERYONE, TextContent};
+    use crate::object_store::S3ObjectStore;
+    use crate::repositories::Storage;
+    use crate::services::{CollaborationHub, PreferenceService};
+
+    fn service() -> ConversationService {
+        service_with(AppConfig {
+            max_lineage_depth: 1000,
+            max_batch_size: 100,
+            max_children_per_message: 1000,
+            max_messages_per_conversation: 50_000,
+            default_page_size: 50,
+            max_page_size: 200,
+        })
+    }
+
+    fn service_with(app_config: AppConfig) -> ConversationService {
+        services_with(app_config).0
+    }
+
+    fn services_with(
+        app_config: AppConfig,
+    ) -> (
+        ConversationService,
+        Arc<ShareService>,
+        Arc<PreferenceService>,
+    ) {
+        let storage = Storage::memory();
+        let change_feed = ChangeFeed::new(storage.changes, Arc::new(CollaborationHub::new()));
+        let preferences = Arc::new(PreferenceService::new(storage.preferences));
+        let notifications = Arc::new(NotificationService::new(
+            storage.notifications,
+            preferences.clone(),
+        ));
+        let shares = Arc::new(ShareService::new(
+            storage.shares,
+            change_feed.clone(),
+            notifications.clone(),
+            preferences.clone(),
+        ));
+        let images = Arc::new(ImageService::new(
+            storage.images,
+            storage.lineage.clone(),
+            Arc::new(S3ObjectStore::new(Settings::default().s3)),
+            Settings::default().images,
+        ));
+
+        let service = ConversationService::new(
+            storage.lineage,
+            change_feed,
+            app_config,
+            notifications,
+            Arc::new(PiiScrubber::new(&PiiConfig {
+                scrub_on_write: false,
+            })),
+            images,
+            shares.clone(),
+        );
+        (service, shares, preferences)
+    }
+
+    fn text(text: &str) -> ContentType {
+        ContentType::Text(TextContent {
+            text: text.to_string(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_move_message_rebases_subtree_on_memory_backend() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let root = conversation.root_message.message_id;
+
+        let a = service
+            .append_message(
+                cid,
+                root,
+                MessageRole::Human,
+                text("a"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+        let b = service
+            .append_message(
+                cid,
+                a.message_id,
+                MessageRole::Assistant,
+                text("b"),
+                HashMap::new(),
+                "assistant".into(),
+            )
+            .await
+            .unwrap();
+        let c = service
+            .append_message(
+                cid,
+                root,
+                MessageRole::Human,
+                text("c"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+
+        let moved = service
+            .move_message(cid, a.message_id, c.message_id)
+            .await
+            .unwrap();
+
+        assert_eq!(moved.len(), 2);
+        let b = service.get_message(cid, b.message_id).await.unwrap();
+        assert_eq!(
+            b.lineage,
+            vec![root, c.message_id, a.message_id, b.message_id]
+        );
+
+        let changes = service.get_changes(cid, None, 100).await.unwrap();
+        assert!(changes.iter().any(|c| c.kind == ChangeKind::MessageUpdated));
+        let events = service.get_events(cid, 0, 100).await.unwrap();
+        let moved = events.last().unwrap();
+        assert_eq!(moved.kind, EventKind::MessageMoved);
+        assert_eq!(moved.payload["message_id"], a.message_id.to_string());
+        assert_eq!(
+            moved.payload["to_parent_message_id"],
+            c.message_id.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_conversations_ranks_prefix_matches_first() {
+        let service = service();
+        for title in [
+            "Brand guide: logo colors",
+            "Logo generation",
+            "Weekly notes",
+        ] {
+            service
+                .create_conversation(title.to_string(), "user_a".to_string())
+                .await
+                .unwrap();
+        }
+        service
+            .create_conversation("Logo for user b".to_string(), "user_b".to_string())
+            .await
+            .unwrap();
+
+        let matches = service
+            .search_conversations("user_a", "LOGO", None, 10)
+            .await
+            .unwrap();
+
+        let titles: Vec<&str> = matches.iter().map(|m| m.title.as_str()).collect();
+        assert_eq!(titles, vec!["Logo generation", "Brand guide: logo colors"]);
+    }
+
+    #[tokio::test]
+    async fn test_conversation_languages_come_from_first_replies() {
+        let service = service();
+        let german = service
+            .create_conversation("Soße".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        service
+            .append_message(
+                german.conversation_id,
+                german.root_message.message_id,
+                MessageRole::Human,
+                text("Wie kann ich die Soße andicken?"),
+                HashMap::from([(LANGUAGE_KEY.to_string(), "de_at".to_string())]),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+        let conversation = service
+            .get_conversation(german.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            conversation.metadata().unwrap().language.as_deref(),
+            Some("de-AT")
+        );
+
+        // Stored before detection was enabled
+        let english = service
+            .create_conversation("Sauce".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let reply = service
+            .append_message(
+                english.conversation_id,
+                english.root_message.message_id,
+                MessageRole::Human,
+                text("How do I make the sauce thicker?"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply.language(), None);
+
+        assert_eq!(service.backfill_languages(100).await.unwrap(), (1, 1));
+        assert_eq!(service.backfill_languages(100).await.unwrap(), (0, 0));
+        let reply = service
+            .get_message(english.conversation_id, reply.message_id)
+            .await
+            .unwrap();
+        assert_eq!(reply.language(), Some("en"));
+
+        for (filter, title) in [("de", "Soße"), ("EN", "Sauce")] {
+            let matches = service
+                .search_conversations("user_a", "s", Some(filter), 10)
+                .await
+                .unwrap();
+            let titles: Vec<&str> = matches.iter().map(|m| m.title.as_str()).collect();
+            assert_eq!(titles, vec![title]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrub_conversation_masks_stored_text() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+
+        let leaky = service
+            .append_message(
+                cid,
+                conversation.root_message.message_id,
+                MessageRole::Human,
+                text("Reach me at jane@example.com"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+        service
+            .append_message(
+                cid,
+                leaky.message_id,
+                MessageRole::Assistant,
+                text("Noted"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+
+        let scrubbed = service.scrub_conversation(cid).await.unwrap();
+        assert_eq!(scrubbed.len(), 1);
+        let stored = service.get_message(cid, leaky.message_id).await.unwrap();
+        assert_eq!(stored.content, text("Reach me at [EMAIL]"));
+        let events = service.get_events(cid, 0, 100).await.unwrap();
+        let updated = events.last().unwrap();
+        assert_eq!(updated.kind, EventKind::MessageUpdated);
+        let content = serde_json::to_value(&stored.content).unwrap();
+        assert_eq!(updated.payload["content"], content);
+        assert!(service.scrub_conversation(cid).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_message_tree_nests_replies_in_order() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Tree".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let append = |parent: Uuid, body: &'static str| {
+            service.append_message(
+                cid,
+                parent,
+                MessageRole::Human,
+                text(body),
+                HashMap::new(),
+                "user_a".into(),
+            )
+        };
+
+        let question = append(conversation.root_message.message_id, "q")
+            .await
+            .unwrap();
+        let first = append(question.message_id, "a1").await.unwrap();
+        let second = append(question.message_id, "a2").await.unwrap();
+        let follow_up = append(first.message_id, "q2").await.unwrap();
+
+        let tree = service.get_message_tree(cid).await.unwrap();
+        assert_eq!(
+            tree.message.message_id,
+            conversation.root_message.message_id
+        );
+        let question_node = &tree.children[0];
+        assert_eq!(question_node.message.message_id, question.message_id);
+        let replies: Vec<Uuid> = question_node
+            .children
+            .iter()
+            .map(|node| node.message.message_id)
+            .collect();
+        assert_eq!(replies, [first.message_id, second.message_id]);
+        assert_eq!(
+            question_node.children[0].children[0].message.message_id,
+            follow_up.message_id
+        );
+        assert!(question_node.children[1].children.is_empty());
+
+        let levels = |min, max| service.get_conversation_levels(cid, min, max, None);
+        let ids = |messages: Vec<Message>| -> Vec<Uuid> {
+            messages.into_iter().map(|m| m.message_id).collect()
+        };
+        assert_eq!(
+            ids(levels(None, Some(2)).await.unwrap()),
+            [conversation.root_message.message_id, question.message_id]
+        );
+        assert_eq!(
+            ids(levels(Some(3), Some(3)).await.unwrap()),
+            [first.message_id, second.message_id]
+        );
+        assert_eq!(
+            ids(levels(Some(4), None).await.unwrap()),
+            [follow_up.message_id]
+        );
+        assert!(matches!(
+            levels(Some(3), Some(2)).await,
+            Err(DbError::InvalidData(_))
+        ));
+
+        let generated = service
+            .append_message(
+                cid,
+                follow_up.message_id,
+                MessageRole::Assistant,
+                text("a3"),
+                HashMap::new(),
+                "service:generation-worker".into(),
+            )
+            .await
+            .unwrap();
+        let written_by = |kind| service.get_conversation_levels(cid, Some(2), None, Some(kind));
+        assert_eq!(
+            ids(written_by(AuthorKind::Service).await.unwrap()),
+            [generated.message_id]
+        );
+        assert_eq!(
+            ids(written_by(AuthorKind::Human).await.unwrap()),
+            [
+                question.message_id,
+                first.message_id,
+                second.message_id,
+                follow_up.message_id
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ancestors_are_the_nearest_window() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Deep".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+
+        let mut path = vec![conversation.root_message.message_id];
+        for i in 0..5 {
+            let message = service
+                .append_message(
+                    cid,
+                    *path.last().unwrap(),
+                    MessageRole::Human,
+                    text(&i.to_string()),
+                    HashMap::new(),
+                    "user_a".into(),
+                )
+                .await
+                .unwrap();
+            path.push(message.message_id);
+        }
+        let leaf = *path.last().unwrap();
+
+        let (ancestors, total) = service.get_ancestors(cid, leaf, 2).await.unwrap();
+        let ids: Vec<Uuid> = ancestors.iter().map(|m| m.message_id).collect();
+        assert_eq!(ids, path[3..5]);
+        assert_eq!(total, 5);
+
+        let (ancestors, _) = service.get_ancestors(cid, leaf, 100).await.unwrap();
+        assert_eq!(ancestors.len(), 5);
+        let (ancestors, total) = service.get_ancestors(cid, path[0], 3).await.unwrap();
+        assert!(ancestors.is_empty());
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_size_limits_are_reported_as_typed_errors() {
+        let service = service_with(AppConfig {
+            max_lineage_depth: 3,
+            max_batch_size: 100,
+            max_children_per_message: 2,
+            max_messages_per_conversation: 5,
+            default_page_size: 50,
+            max_page_size: 200,
+        });
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let root = conversation.root_message.message_id;
+        let append = |parent| {
+            service.append_message(
+                cid,
+                parent,
+                MessageRole::Human,
+                text("hi"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+        };
+
+        let a = append(root).await.unwrap();
+        let c = append(root).await.unwrap();
+        let err = append(root).await.unwrap_err();
+        assert!(matches!(err, DbError::LimitExceeded(_)), "{err}");
+
+        let b = append(a.message_id).await.unwrap();
+        let err = append(b.message_id).await.unwrap_err();
+        assert!(
+            matches!(err, DbError::LineageTooDeep { depth: 4, max: 3 }),
+            "{err}"
+        );
+
+        // The root counts too, so this fifth message fills the conversation
+        append(c.message_id).await.unwrap();
+        let err = append(c.message_id).await.unwrap_err();
+        assert!(
+            matches!(err, DbError::LimitExceeded(ref m) if m.contains("messages")),
+            "{err}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_message_counters_follow_appends_moves_and_the_backfill() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let root = conversation.root_message.message_id;
+        let append = |parent| {
+            service.append_message(
+                cid,
+                parent,
+                MessageRole::Human,
+                text("hi"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+        };
+        let a = append(root).await.unwrap();
+        let b = append(root).await.unwrap();
+        let c = append(a.message_id).await.unwrap();
+        let store = service.lineage_repo.as_ref();
+        let replies = |id| store.get_reply_count(cid, id);
+
+        assert_eq!(store.get_message_count(cid).await.unwrap(), 4);
+        assert_eq!(replies(root).await.unwrap(), 2);
+        assert_eq!(replies(a.message_id).await.unwrap(), 1);
+
+        service
+            .move_message(cid, c.message_id, b.message_id)
+            .await
+            .unwrap();
+        assert_eq!(replies(a.message_id).await.unwrap(), 0);
+        assert_eq!(replies(b.message_id).await.unwrap(), 1);
+        assert_eq!(store.get_message_count(cid).await.unwrap(), 4);
+
+        // Messages written without counting them are counted once, however
+        // often the backfill runs
+        let message_id = new_message_id();
+        let d = Message {
+            message_id,
+            parent_message_id: Some(b.message_id),
+            lineage: compute_lineage(&b.lineage, message_id),
+            ..c.clone()
+        };
+        store.batch_insert_messages(&[d]).await.unwrap();
+        assert_eq!(service.backfill_message_counts(100).await.unwrap(), 1);
+        assert_eq!(service.backfill_message_counts(100).await.unwrap(), 0);
+        assert_eq!(store.get_message_count(cid).await.unwrap(), 5);
+        assert_eq!(replies(b.message_id).await.unwrap(), 2);
+
+        service.delete_conversation(cid).await.unwrap();
+        assert_eq!(store.get_message_count(cid).await.unwrap(), 0);
+        assert_eq!(replies(root).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_locked_conversations_reject_writes_until_unlocked() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let root_id = conversation.root_message.message_id;
+        let append = || {
+            service.append_message(
+                cid,
+                root_id,
+                MessageRole::Human,
+                text("hi"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+        };
+
+        let lock = service
+            .lock_conversation(cid, "reviewer".into(), Some("Final".into()))
+            .await
+            .unwrap();
+        // Locking again keeps the first lock
+        let again = service
+            .lock_conversation(cid, "someone_else".into(), None)
+            .await
+            .unwrap();
+        assert_eq!(again.locked_by, lock.locked_by);
+        assert_eq!(
+            service.get_lock(cid).await.unwrap().reason.as_deref(),
+            Some("Final")
+        );
+
+        assert!(matches!(append().await, Err(DbError::Locked(id)) if id == cid));
+        assert!(matches!(
+            service
+                .update_conversation(cid, Some("New".into()), None, None, None)
+                .await,
+            Err(DbError::Locked(_))
+        ));
+        assert!(matches!(
+            service.delete_conversation(cid).await,
+            Err(DbError::Locked(_))
+        ));
+
+        service.unlock_conversation(cid).await.unwrap();
+        append().await.unwrap();
+        assert!(matches!(
+            service.get_lock(cid).await,
+            Err(DbError::NotFound)
+        ));
+        assert!(matches!(
+            service.unlock_conversation(cid).await,
+            Err(DbError::NotFound)
+        ));
+        assert!(matches!(
+            service
+                .lock_conversation(Uuid::new_v4(), "reviewer".into(), None)
+                .await,
+            Err(DbError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_mutations_are_recorded_in_the_event_log() {
+        let service = service();
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        let message = service
+            .append_message(
+                cid,
+                conversation.root_message.message_id,
+                MessageRole::Human,
+                text("hi"),
+                HashMap::new(),
+                "user_a".into(),
+            )
+            .await
+            .unwrap();
+        service
+            .update_conversation(cid, Some("Renamed".to_string()), None, None, None)
+            .await
+            .unwrap();
+
+        let events = service.get_events(cid, 0, 100).await.unwrap();
+        let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::MetadataUpdated,
+                EventKind::MessageCreated,
+                EventKind::MetadataUpdated
+            ]
+        );
+        assert_eq!(
+            events[1].payload["message_id"],
+            message.message_id.to_string()
+        );
+        assert_eq!(events[2].payload["title"], "Renamed");
+        // The store numbers events in the order they are written
+        let seqs: Vec<i64> = events.iter().map(|event| event.seq).collect();
+        assert_eq!(seqs, [1, 2, 3]);
+
+        let rest = service
+            .get_events(cid, events[1].seq + 1, 100)
+            .await
+            .unwrap();
+        assert_eq!(rest.len(), 1);
+
+        service.delete_conversation(cid).await.unwrap();
+        assert!(service.get_events(cid, 0, 100).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_creator_preferences_apply_on_creation_and_publishing() {
+        let (service, shares, preferences) = services_with(AppConfig {
+            max_lineage_depth: 1000,
+            max_batch_size: 100,
+            max_children_per_message: 1000,
+            max_messages_per_conversation: 50_000,
+            default_page_size: 50,
+            max_page_size: 200,
+        });
+        let mut prefs = preferences.get("user_a").await.unwrap();
+        prefs.default_shares = HashMap::from([("user_b".to_string(), Permission::Read)]);
+        prefs.allow_public_forks = false;
+        preferences.save(prefs).await.unwrap();
+
+        let cid = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap()
+            .conversation_id;
+        let granted = shares.get_conversation_shares(cid).await.unwrap();
+        assert_eq!(granted.len(), 1);
+        assert_eq!(granted[0].shared_with, "user_b");
+        assert_eq!(granted[0].permission, Permission::Read);
+
+        service
+            .update_conversation(cid, None, None, Some(true), None)
+            .await
+            .unwrap();
+        let public = shares.get_share(cid, EVERYONE).await.unwrap();
+        assert_eq!(public.permission, Permission::Read);
+
+        service
+            .update_conversation(cid, None, None, Some(false), None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            shares.get_share(cid, EVERYONE).await,
+            Err(DbError::NotFound)
+        ));
+
+        let other = service
+            .create_conversation("Other".to_string(), "user_b".to_string())
+            .await
+            .unwrap();
+        assert!(
+            shares
+                .get_conversation_shares(other.conversation_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_published_branch_is_kept_and_writers_are_told_apart() {
+        let (service, shares, _) = services_with(AppConfig {
+            max_lineage_depth: 1000,
+            max_batch_size: 100,
+            max_children_per_message: 1000,
+            max_messages_per_conversation: 50_000,
+            default_page_size: 50,
+            max_page_size: 200,
+        });
+        let conversation = service
+            .create_conversation("Test".to_string(), "user_a".to_string())
+            .await
+            .unwrap();
+        let cid = conversation.conversation_id;
+        shares
+            .share_conversation(cid, "user_b".into(), Permission::Branch, "user_a".into())
+            .await
+            .unwrap();
+        shares
+            .share_conversation(cid, "user_c".into(), Permission::Read, "user_a".into())
+            .await
+            .unwrap();
+
+        let branch_id = Uuid::new_v4();
+        service
+            .set_published_branch(cid, Some(branch_id))
+            .await
+            .unwrap();
+        let published = service.get_conversation(cid).await.unwrap();
+        assert_eq!(
+            published.metadata().unwrap().published_branch_id,
+            Some(branch_id)
+        );
+        for (user, writes) in [("user_a", true), ("user_b", true), ("user_c", false)] {
+            assert_eq!(service.can_write(&published, user).await.unwrap(), writes);
+        }
+
+        let cleared = service.set_published_branch(cid, None).await.unwrap();
+        assert_eq!(cleared.metadata().unwrap().published_branch_id, None);
     }
 }
@@ -0,0 +1,385 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{Branch, BranchMoved, ChangeKind, EventKind, Message, MessageMoved};
+use crate::repositories::{BranchStore, ChangeStore, LineageStore};
+
+/// Events and changes read per round trip while scanning a window
+const SCAN_PAGE: i32 = 500;
+
+/// What happened to a conversation between two instants
+#[derive(Debug, Clone)]
+pub struct ConversationDiff {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    /// Messages created in the window, oldest first
+    pub messages_added: Vec<Message>,
+    /// Messages that existed before the window and were edited in it, as
+    /// they are now
+    pub messages_changed: Vec<Message>,
+    /// Branches created in the window that still exist
+    pub branches_added: Vec<Branch>,
+    /// Branches that existed before the window and were moved or renamed in
+    /// it, as they are now
+    pub branches_changed: Vec<Branch>,
+    /// Branches that existed before the window and were deleted in it, as
+    /// they were when deleted
+    pub branches_removed: Vec<Branch>,
+}
+
+/// Compares a conversation at two points in time, from creation times, the
+/// event log and the change feed
+pub struct DiffService {
+    lineage_repo: Arc<dyn LineageStore>,
+    branch_repo: Arc<dyn BranchStore>,
+    changes: Arc<dyn ChangeStore>,
+}
+
+impl DiffService {
+    pub fn new(
+        lineage_repo: Arc<dyn LineageStore>,
+        branch_repo: Arc<dyn BranchStore>,
+        changes: Arc<dyn ChangeStore>,
+    ) -> Self {
+        Self {
+            lineage_repo,
+            branch_repo,
+            changes,
+        }
+    }
+
+    /// Diff the conversation between `from` (exclusive) and `to` (inclusive)
+    pub async fn diff(
+        &self,
+        conversation_id: Uuid,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<ConversationDiff, DbError> {
+        if from >= to {
+            return Err(DbError::InvalidData(
+                "`from` must be before `to`".to_string(),
+            ));
+        }
+        let in_window = |at: DateTime<Utc>| at > from && at <= to;
+
+        // Messages are read last, keeping only those the log and the feed
+        // name, instead of the whole conversation
+        let mut added_messages = HashSet::new();
+        let mut changed_messages = HashSet::new();
+        let mut metadata_updated = false;
+        let mut changed_branches = HashSet::new();
+        let mut removed_branches = HashMap::new();
+
+        // Sequence numbers don't follow the clock; read the log from the start
+        let mut from_seq = 0;
+        'events: loop {
+            let events = self
+                .lineage_repo
+                .get_events(conversation_id, from_seq, SCAN_PAGE)
+                .await?;
+            let Some(last) = events.last() else {
+                break;
+            };
+            from_seq = last.seq + 1;
+            let page_full = events.len() as i32 == SCAN_PAGE;
+
+            for event in events {
+                if event.occurred_at > to {
+                    break 'events;
+                }
+                if !in_window(event.occurred_at) {
+                    continue;
+                }
+                match event.kind {
+                    EventKind::MessageCreated => {
+                        if let Some(id) = event.payload.get("message_id")
+                            && let Ok(id) = serde_json::from_value::<Uuid>(id.clone())
+                        {
+                            added_messages.insert(id);
+                        }
+                    }
+                    EventKind::MessageUpdated => {
+                        if let Some(id) = event.payload.get("message_id")
+                            && let Ok(id) = serde_json::from_value::<Uuid>(id.clone())
+                        {
+                            changed_messages.insert(id);
+                        }
+                    }
+                    EventKind::MessageMoved => {
+                        if let Ok(moved) = serde_json::from_value::<MessageMoved>(event.payload) {
+                            changed_messages.insert(moved.message_id);
+                        }
+                    }
+                    EventKind::MetadataUpdated => metadata_updated = true,
+                    EventKind::BranchMoved => {
+                        if let Ok(moved) = serde_json::from_value::<BranchMoved>(event.payload) {
+                            changed_branches.insert(moved.branch_id);
+                        }
+                    }
+                    EventKind::Forked => {}
+                }
+            }
+            if !page_full {
+                break;
+            }
+        }
+
+        let (mut since, mut after_change_id) = (from, Uuid::nil());
+        'changes: loop {
+            let changes = self
+                .changes
+                .get_changes_since(conversation_id, since, after_change_id, SCAN_PAGE)
+                .await?;
+            let Some(last) = changes.last() else {
+                break;
+            };
+            (since, after_change_id) = (last.changed_at, last.change_id);
+            let page_full = changes.len() as i32 == SCAN_PAGE;
+
+            for change in changes {
+                if change.changed_at > to {
+                    break 'changes;
+                }
+                if !in_window(change.changed_at) {
+                    continue;
+                }
+                let Ok(entity_id) = Uuid::parse_str(&change.entity_id) else {
+                    continue;
+                };
+                match change.kind {
+                    ChangeKind::MessageUpdated => {
+                        changed_messages.insert(entity_id);
+                    }
+                    ChangeKind::BranchUpdated => {
+                        changed_branches.insert(entity_id);
+                    }
+                    ChangeKind::BranchDeleted => {
+                        if let Some(branch) = change
+                            .payload
+                            .and_then(|payload| serde_json::from_value::<Branch>(payload).ok())
+                        {
+                            removed_branches.insert(entity_id, branch);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            if !page_full {
+                break;
+            }
+        }
+
+        let mut found = false;
+        let mut messages_added = Vec::new();
+        let mut messages_changed = Vec::new();
+        let mut messages = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, None)
+            .await?;
+        while let Some(message) = messages.try_next().await? {
+            found = true;
+            if in_window(message.created_at) || added_messages.contains(&message.message_id) {
+                messages_added.push(message);
+            } else if changed_messages.contains(&message.message_id)
+                || (metadata_updated && message.parent_message_id.is_none())
+            {
+                messages_changed.push(message);
+            }
+        }
+        if !found {
+            return Err(DbError::NotFound);
+        }
+        messages_added.sort_by_key(|message| message.created_at);
+        messages_changed.sort_by_key(|message| message.created_at);
+
+        let mut branches_added = Vec::new();
+        let mut branches_changed = Vec::new();
+        for branch in self
+            .branch_repo
+            .get_branches_by_conversation(conversation_id)
+            .await?
+        {
+            if in_window(branch.created_at) {
+                branches_added.push(branch);
+            } else if changed_branches.contains(&branch.branch_id) {
+                branches_changed.push(branch);
+            }
+        }
+        branches_added.sort_by_key(|branch| branch.created_at);
+        branches_changed.sort_by_key(|branch| branch.created_at);
+        // Branches both created and deleted within the window never show up
+        let mut branches_removed: Vec<Branch> = removed_branches
+            .into_values()
+            .filter(|branch| branch.created_at <= from)
+            .collect();
+        branches_removed.sort_by_key(|branch| branch.created_at);
+
+        Ok(ConversationDiff {
+            from,
+            to,
+            messages_added,
+            messages_changed,
+            branches_added,
+            branches_changed,
+            branches_removed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        Change, ContentType, Conversation, ConversationEvent, MessageRole, TextContent,
+    };
+    use crate::repositories::Storage;
+    use crate::utils::{compute_lineage, new_message_id};
+    use chrono::Duration;
+
+    fn reply(parent: &Message, at: DateTime<Utc>) -> Message {
+        let message_id = new_message_id();
+        Message {
+            conversation_id: parent.conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role: MessageRole::Human,
+            content: ContentType::Text(TextContent {
+                text: "hi".to_string(),
+            }),
+            content_metadata: Default::default(),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: at,
+            created_by: "user_a".to_string(),
+        }
+    }
+
+    fn event_at<T: serde::Serialize>(
+        conversation_id: Uuid,
+        kind: EventKind,
+        payload: &T,
+        at: DateTime<Utc>,
+    ) -> ConversationEvent {
+        let mut event = ConversationEvent::new(conversation_id, kind, payload).unwrap();
+        event.occurred_at = at;
+        event
+    }
+
+    fn branch_at(conversation_id: Uuid, name: &str, leaf: Uuid, at: DateTime<Utc>) -> Branch {
+        let mut branch = Branch::new(conversation_id, name.to_string(), leaf, "user_a".into());
+        branch.created_at = at;
+        branch
+    }
+
+    #[tokio::test]
+    async fn test_diff_reports_what_happened_in_the_window() {
+        let storage = Storage::memory();
+        let service = DiffService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.changes.clone(),
+        );
+        let now = Utc::now();
+        let (before, from, to) = (
+            now - Duration::hours(3),
+            now - Duration::hours(2),
+            now - Duration::hours(1),
+        );
+        let during = from + Duration::minutes(10);
+
+        let mut conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        conversation.root_message.created_at = before;
+        let cid = conversation.conversation_id;
+        let old = reply(&conversation.root_message, before);
+        let new = reply(&old, during);
+        let late = reply(&new, now);
+        storage
+            .lineage
+            .batch_insert_messages(&[conversation.root_message.clone(), old.clone(), late])
+            .await
+            .unwrap();
+        storage
+            .lineage
+            .insert_messages_with_events(
+                std::slice::from_ref(&new),
+                &[event_at(cid, EventKind::MessageCreated, &new, during)],
+            )
+            .await
+            .unwrap();
+
+        let main = branch_at(cid, "main", new.message_id, before);
+        let added = branch_at(cid, "added", new.message_id, during);
+        let deleted = branch_at(cid, "deleted", old.message_id, before);
+        let fleeting = branch_at(cid, "fleeting", old.message_id, during);
+        storage.branches.insert_branch(&main).await.unwrap();
+        storage.branches.insert_branch(&added).await.unwrap();
+        storage
+            .branches
+            .update_branch_leaf(
+                cid,
+                main.branch_id,
+                old.message_id,
+                new.message_id,
+                &[event_at(
+                    cid,
+                    EventKind::BranchMoved,
+                    &BranchMoved {
+                        branch_id: main.branch_id,
+                        from_leaf_message_id: old.message_id,
+                        to_leaf_message_id: new.message_id,
+                    },
+                    during,
+                )],
+            )
+            .await
+            .unwrap();
+
+        let mut changes = vec![Change::new(
+            cid,
+            ChangeKind::MessageUpdated,
+            old.message_id.to_string(),
+            None,
+        )];
+        for branch in [&deleted, &fleeting] {
+            changes.push(Change::new(
+                cid,
+                ChangeKind::BranchDeleted,
+                branch.branch_id.to_string(),
+                Some(serde_json::to_value(branch).unwrap()),
+            ));
+        }
+        for change in &mut changes {
+            change.changed_at = during;
+            storage.changes.insert_change(change).await.unwrap();
+        }
+
+        let diff = service.diff(cid, from, to).await.unwrap();
+        let ids = |messages: &[Message]| messages.iter().map(|m| m.message_id).collect::<Vec<_>>();
+        let branch_ids =
+            |branches: &[Branch]| branches.iter().map(|b| b.branch_id).collect::<Vec<_>>();
+        assert_eq!(ids(&diff.messages_added), vec![new.message_id]);
+        assert_eq!(ids(&diff.messages_changed), vec![old.message_id]);
+        assert_eq!(branch_ids(&diff.branches_added), vec![added.branch_id]);
+        assert_eq!(branch_ids(&diff.branches_changed), vec![main.branch_id]);
+        assert_eq!(branch_ids(&diff.branches_removed), vec![deleted.branch_id]);
+
+        // Nothing happened after the window
+        let diff = service
+            .diff(cid, to, now - Duration::seconds(1))
+            .await
+            .unwrap();
+        assert!(diff.messages_added.is_empty() && diff.branches_changed.is_empty());
+
+        assert!(matches!(
+            service.diff(cid, to, from).await,
+            Err(DbError::InvalidData(_))
+        ));
+        assert!(matches!(
+            service.diff(Uuid::new_v4(), from, to).await,
+            Err(DbError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,516 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::future;
+use futures::stream::{BoxStream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::ExportsConfig;
+use crate::db::DbError;
+use crate::domain::{
+    ConversationExport, Job, JobStatus, Message, NATIVE_EXPORT_VERSION, NativeExport,
+};
+use crate::object_store::ObjectStore;
+use crate::repositories::{BranchStore, ExportStore, JobStore, LineageStore, ShareStore};
+use crate::services::{ExportFormat, JobContext, JobRunner, PrivateMessages};
+use crate::utils::transcript::{render_anthropic, render_html, render_markdown};
+
+/// Rows fetched per Scylla page when streaming exports
+const EXPORT_PAGE_SIZE: i32 = 500;
+
+/// Parameters of a `conversation_export` job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationExportParams {
+    pub conversation_id: Uuid,
+    pub format: ExportFormat,
+    pub branch_id: Option<Uuid>,
+    pub leaf_message_id: Option<Uuid>,
+    #[serde(default)]
+    pub include_shares: bool,
+}
+
+/// A stored export together with the state of the job writing it
+#[derive(Debug, Clone)]
+pub struct ExportStatus {
+    pub export: ConversationExport,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    /// Where to download a completed export from, until `download_expires_at`
+    pub download_url: Option<String>,
+    pub download_expires_at: Option<DateTime<Utc>>,
+}
+
+/// Renders conversations for download, either within the request or, as the
+/// runner of `conversation_export` jobs, into the object store
+pub struct ExportService {
+    lineage_repo: Arc<dyn LineageStore>,
+    branch_repo: Arc<dyn BranchStore>,
+    export_repo: Arc<dyn ExportStore>,
+    job_repo: Arc<dyn JobStore>,
+    share_repo: Arc<dyn ShareStore>,
+    object_store: Arc<dyn ObjectStore>,
+    config: ExportsConfig,
+}
+
+impl ExportService {
+    pub fn new(
+        lineage_repo: Arc<dyn LineageStore>,
+        branch_repo: Arc<dyn BranchStore>,
+        export_repo: Arc<dyn ExportStore>,
+        job_repo: Arc<dyn JobStore>,
+        share_repo: Arc<dyn ShareStore>,
+        object_store: Arc<dyn ObjectStore>,
+        config: ExportsConfig,
+    ) -> Self {
+        Self {
+            lineage_repo,
+            branch_repo,
+            export_repo,
+            job_repo,
+            share_repo,
+            object_store,
+            config,
+        }
+    }
+
+    /// Check that a conversation can be exported before a job is started for it
+    pub async fn ensure_exportable(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        if self.lineage_repo.count_messages(conversation_id).await? == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Whether `viewer` owns the conversation, and so may export its shares
+    pub async fn is_owner(
+        &self,
+        conversation_id: Uuid,
+        viewer: Option<&str>,
+    ) -> Result<bool, DbError> {
+        let root = self.lineage_repo.get_root_message(conversation_id).await?;
+        Ok(viewer == Some(root.created_by.as_str()))
+    }
+
+    /// Record the export a `conversation_export` job writes, so it is listed
+    /// with the conversation's exports
+    pub async fn record_export(&self, job: &Job) -> Result<ExportStatus, DbError> {
+        let params = export_params(job)?;
+        let export = ConversationExport {
+            export_id: job.job_id,
+            conversation_id: params.conversation_id,
+            format: params.format.as_str().to_string(),
+            requested_by: job.user_id.clone(),
+            created_at: job.started_at,
+            object_key: None,
+            size_bytes: None,
+            completed_at: None,
+        };
+        self.export_repo.insert_export(&export).await?;
+
+        Ok(self.with_status(export, Some(job.clone())))
+    }
+
+    /// A conversation's most recent exports, newest first
+    pub async fn get_exports(
+        &self,
+        conversation_id: Uuid,
+        limit: usize,
+    ) -> Result<Vec<ExportStatus>, DbError> {
+        let exports = self
+            .export_repo
+            .get_exports(conversation_id, limit as i32)
+            .await?;
+
+        let mut statuses = Vec::with_capacity(exports.len());
+        for export in exports {
+            let job = self.find_job(export.export_id).await?;
+            statuses.push(self.with_status(export, job));
+        }
+
+        Ok(statuses)
+    }
+
+    pub async fn get_export(
+        &self,
+        conversation_id: Uuid,
+        export_id: Uuid,
+    ) -> Result<ExportStatus, DbError> {
+        // The export is keyed by its job's start time
+        let job = self.job_repo.get_job(export_id).await?;
+        let export = self
+            .export_repo
+            .get_export(conversation_id, job.started_at, export_id)
+            .await?;
+
+        Ok(self.with_status(export, Some(job)))
+    }
+
+    async fn find_job(&self, job_id: Uuid) -> Result<Option<Job>, DbError> {
+        match self.job_repo.get_job(job_id).await {
+            Ok(job) => Ok(Some(job)),
+            Err(DbError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// `export` with its status and, once complete, a fresh download URL
+    fn with_status(&self, export: ConversationExport, job: Option<Job>) -> ExportStatus {
+        let (status, error) = match job {
+            Some(job) => (job.status, job.error),
+            // The job expired first; the export either got written or not
+            None if export.object_key.is_some() => (JobStatus::Completed, None),
+            None => (JobStatus::Failed, None),
+        };
+
+        let (download_url, download_expires_at) = match &export.object_key {
+            Some(key) if status == JobStatus::Completed => {
+                let expires_in = Duration::from_secs(self.config.download_expiry_secs);
+                (
+                    Some(self.object_store.presigned_get_url(key, expires_in)),
+                    Some(Utc::now() + chrono::Duration::seconds(expires_in.as_secs() as i64)),
+                )
+            }
+            _ => (None, None),
+        };
+
+        ExportStatus {
+            export,
+            status,
+            error,
+            download_url,
+            download_expires_at,
+        }
+    }
+
+    /// Render a branch (or the path to a specific leaf) as a readable transcript.
+    /// Without an explicit branch or leaf, the most recently updated active branch is used.
+    /// Messages `viewer` may not read are left out.
+    pub async fn export_transcript(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Option<Uuid>,
+        leaf_message_id: Option<Uuid>,
+        format: ExportFormat,
+        viewer: Option<&str>,
+    ) -> Result<String, DbError> {
+        let private =
+            PrivateMessages::for_viewer(self.lineage_repo.as_ref(), conversation_id, viewer)
+                .await?;
+        let messages: Vec<Message> = self
+            .resolve_path(conversation_id, branch_id, leaf_message_id)
+            .await?
+            .into_iter()
+            .filter_map(|message| private.redact(message))
+            .collect();
+
+        match format {
+            ExportFormat::Markdown => Ok(render_markdown(&messages)),
+            ExportFormat::Html => Ok(render_html(&messages)),
+            ExportFormat::Anthropic => serde_json::to_string_pretty(&render_anthropic(&messages))
+                .map_err(|e| DbError::SerializationError(e.to_string())),
+            ExportFormat::Ndjson => Err(DbError::InvalidData(
+                "NDJSON exports cover the whole conversation and are streamed".to_string(),
+            )),
+            ExportFormat::Native => Err(DbError::InvalidData(
+                "Native exports cover the whole conversation".to_string(),
+            )),
+        }
+    }
+
+    /// The whole conversation in the native format, of what `viewer` may
+    /// read: branches ending at a message left out are left out too. Shares
+    /// are included if `include_shares`; callers check that `viewer` may see
+    /// them.
+    pub async fn export_native(
+        &self,
+        conversation_id: Uuid,
+        viewer: Option<&str>,
+        include_shares: bool,
+    ) -> Result<NativeExport, DbError> {
+        let messages: Vec<Message> = self
+            .stream_messages(conversation_id, viewer)
+            .await?
+            .try_collect()
+            .await?;
+        let ids: HashSet<Uuid> = messages.iter().map(|m| m.message_id).collect();
+        let branches = self
+            .branch_repo
+            .get_branches_by_conversation(conversation_id)
+            .await?
+            .into_iter()
+            .filter(|b| ids.contains(&b.leaf_message_id))
+            .collect();
+        let shares = if include_shares {
+            Some(
+                self.share_repo
+                    .get_shares_by_conversation(conversation_id)
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        Ok(NativeExport {
+            version: NATIVE_EXPORT_VERSION,
+            exported_at: Utc::now(),
+            conversation_id,
+            messages,
+            branches,
+            shares,
+        })
+    }
+
+    /// Stream every message of the conversation `viewer` may read as it is
+    /// paged from the database
+    pub async fn stream_messages(
+        &self,
+        conversation_id: Uuid,
+        viewer: Option<&str>,
+    ) -> Result<BoxStream<'static, Result<Message, DbError>>, DbError> {
+        let private =
+            PrivateMessages::for_viewer(self.lineage_repo.as_ref(), conversation_id, viewer)
+                .await?;
+        let messages = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, Some(EXPORT_PAGE_SIZE))
+            .await?;
+
+        Ok(messages
+            .try_filter_map(move |message| future::ready(Ok(private.redact(message))))
+            .boxed())
+    }
+
+    /// The export as a file, of what `viewer` may read
+    async fn render(
+        &self,
+        params: &ConversationExportParams,
+        viewer: &str,
+    ) -> Result<Vec<u8>, DbError> {
+        if params.format == ExportFormat::Native {
+            let export = self
+                .export_native(params.conversation_id, Some(viewer), params.include_shares)
+                .await?;
+            return serde_json::to_vec(&export)
+                .map_err(|e| DbError::SerializationError(e.to_string()));
+        }
+        if !params.format.is_streamed() {
+            let transcript = self
+                .export_transcript(
+                    params.conversation_id,
+                    params.branch_id,
+                    params.leaf_message_id,
+                    params.format,
+                    Some(viewer),
+                )
+                .await?;
+            return Ok(transcript.into_bytes());
+        }
+
+        let mut messages = self
+            .stream_messages(params.conversation_id, Some(viewer))
+            .await?;
+        let mut body = Vec::new();
+        while let Some(message) = messages.try_next().await? {
+            body.extend(ndjson_line(&message)?);
+        }
+
+        Ok(body)
+    }
+
+    /// Resolve the lineage path (root first) selected for export
+    async fn resolve_path(
+        &self,
+        conversation_id: Uuid,
+        branch_id: Option<Uuid>,
+        leaf_message_id: Option<Uuid>,
+    ) -> Result<Vec<Message>, DbError> {
+        let leaf_message_id = match (leaf_message_id, branch_id) {
+            (Some(leaf_message_id), _) => leaf_message_id,
+            (None, Some(branch_id)) => {
+                self.branch_repo
+                    .get_branch(conversation_id, branch_id)
+                    .await?
+                    .leaf_message_id
+            }
+            (None, None) => {
+                self.branch_repo
+                    .get_branches_by_conversation(conversation_id)
+                    .await?
+                    .into_iter()
+                    .filter(|b| b.is_active)
+                    .max_by_key(|b| b.last_updated)
+                    .ok_or_else(|| {
+                        DbError::InvalidData(
+                            "Conversation has no branches; specify branch_id or leaf_message_id"
+                                .to_string(),
+                        )
+                    })?
+                    .leaf_message_id
+            }
+        };
+
+        let leaf_message = self
+            .lineage_repo
+            .get_message(conversation_id, leaf_message_id)
+            .await?;
+
+        self.lineage_repo
+            .get_messages_by_ids(conversation_id, &leaf_message.lineage)
+            .await
+    }
+}
+
+#[async_trait]
+impl JobRunner for ExportService {
+    async fn run(&self, job: &Job, ctx: &JobContext) -> Result<(), DbError> {
+        let params = export_params(job)?;
+        let body = self.render(&params, &job.user_id).await?;
+        if ctx.is_cancelled().await? {
+            return Ok(());
+        }
+
+        let size_bytes = body.len() as i64;
+        let key = format!(
+            "exports/{}/{}.{}",
+            params.conversation_id,
+            job.job_id,
+            params.format.file_extension()
+        );
+        self.object_store
+            .put_object(&key, params.format.content_type(), body)
+            .await?;
+
+        self.export_repo
+            .complete_export(
+                params.conversation_id,
+                job.started_at,
+                job.job_id,
+                &key,
+                size_bytes,
+                Utc::now(),
+            )
+            .await
+    }
+}
+
+fn export_params(job: &Job) -> Result<ConversationExportParams, DbError> {
+    serde_json::from_value(job.params.clone())
+        .map_err(|e| DbError::InvalidData(format!("Invalid export parameters: {}", e)))
+}
+
+/// A message as one NDJSON line, in the shape of the API's message responses
+fn ndjson_line(message: &Message) -> Result<Vec<u8>, DbError> {
+    let mut value =
+        serde_json::to_value(message).map_err(|e| DbError::SerializationError(e.to_string()))?;
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("depth".to_string(), message.depth().into());
+    }
+
+    let mut line =
+        serde_json::to_vec(&value).map_err(|e| DbError::SerializationError(e.to_string()))?;
+    line.push(b'\n');
+
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JobsConfig;
+    use crate::domain::{Conversation, JobKind};
+    use crate::object_store::memory::MemoryObjectStore;
+    use crate::repositories::Storage;
+    use crate::services::JobService;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn test_export_jobs_write_to_the_object_store() {
+        let storage = Storage::memory();
+        let object_store = Arc::new(MemoryObjectStore::default());
+        let service = Arc::new(ExportService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.exports.clone(),
+            storage.jobs.clone(),
+            storage.shares.clone(),
+            object_store.clone(),
+            ExportsConfig {
+                download_expiry_secs: 600,
+            },
+        ));
+        let jobs = JobService::new(
+            storage.jobs.clone(),
+            HashMap::from([(
+                JobKind::ConversationExport,
+                service.clone() as Arc<dyn JobRunner>,
+            )]),
+            JobsConfig {
+                stale_after_secs: 300,
+                recovery_interval_secs: 60,
+            },
+        );
+        let conversation = Conversation::new("Logo".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        storage
+            .lineage
+            .insert_message(&conversation.root_message)
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service.ensure_exportable(Uuid::new_v4()).await,
+            Err(DbError::NotFound)
+        ));
+        service.ensure_exportable(cid).await.unwrap();
+
+        let params = ConversationExportParams {
+            conversation_id: cid,
+            format: ExportFormat::Ndjson,
+            branch_id: None,
+            leaf_message_id: None,
+            include_shares: false,
+        };
+        let job = jobs
+            .start(
+                JobKind::ConversationExport,
+                "user_a".to_string(),
+                serde_json::to_value(&params).unwrap(),
+            )
+            .await
+            .unwrap();
+        service.record_export(&job).await.unwrap();
+
+        let export = loop {
+            let export = service.get_export(cid, job.job_id).await.unwrap();
+            if export.status != JobStatus::Running {
+                break export;
+            }
+            tokio::task::yield_now().await;
+        };
+        assert_eq!(export.status, JobStatus::Completed);
+        assert_eq!(export.export.format, "ndjson");
+        let key = format!("exports/{}/{}.ndjson", cid, job.job_id);
+        assert_eq!(export.export.object_key.as_deref(), Some(key.as_str()));
+        assert_eq!(
+            export.download_url.as_deref(),
+            Some(format!("https://signed/{}?expires=600", key).as_str())
+        );
+
+        let body = object_store.objects.lock().unwrap()[&key].clone();
+        assert_eq!(export.export.size_bytes, Some(body.len() as i64));
+        let line: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            line["message_id"],
+            conversation.root_message.message_id.to_string()
+        );
+        assert_eq!(line["depth"], conversation.root_message.depth());
+
+        let listed = service.get_exports(cid, 10).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].export.export_id, job.job_id);
+    }
+}
@@ -1,26 +1,139 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use futures::future::{self, FutureExt};
+use futures::stream::{self, BoxStream, StreamExt, TryChunksError, TryStreamExt};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use uuid::Uuid;
 
-use crate::config::AppConfig;
-use crate::db::DbError;
-use crate::domain::{ContentType, Conversation, Message, MetadataContent};
-use crate::repositories::{BranchRepository, LineageRepository};
+use crate::config::{AppConfig, ForkConfig};
+use crate::db::startup::is_transient;
+use crate::db::{ConversationTitleRow, DbError, ForkLinkRow, ForkRejection};
+use crate::domain::{
+    Branch, ContentType, Conversation, ConversationEvent, EVERYONE, EventKind, ForkProgress,
+    Forked, Message, MetadataContent, NotificationKind, Share,
+};
+use crate::repositories::{BranchStore, LineageStore, ShareStore};
+use crate::scheduler::{ScheduledTask, TaskError};
+use crate::services::conversation_service::count_new_messages;
+use crate::services::{ImageService, NotificationService, PrivateMessages};
+use crate::utils::new_message_id;
+
+/// What a fork carries over from its source besides the messages
+#[derive(Debug, Clone, Copy)]
+pub struct ForkOptions {
+    /// Give copied messages fresh IDs, rewriting parents and lineage to match
+    pub remap_ids: bool,
+    /// Recreate source branches whose leaf was copied; requires `remap_ids`
+    pub copy_branches: bool,
+    /// Grant the forker the same share on the fork they had on the source
+    pub copy_share: bool,
+}
+
+impl Default for ForkOptions {
+    fn default() -> Self {
+        Self {
+            remap_ids: true,
+            copy_branches: true,
+            copy_share: false,
+        }
+    }
+}
+
+/// How many fork hops the graph follows upwards from the requested conversation
+const MAX_FORK_ANCESTORS: usize = 20;
+/// Upper bound on the conversations in one fork graph
+const MAX_FORK_GRAPH_NODES: usize = 500;
+
+/// A conversation in a fork graph
+#[derive(Debug, Clone)]
+pub struct ForkGraphNode {
+    pub conversation_id: Uuid,
+    pub title: String,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub fork_from_conversation_id: Option<Uuid>,
+    pub fork_from_message_id: Option<Uuid>,
+}
+
+impl ForkGraphNode {
+    fn from_conversation(conversation: &Conversation) -> Self {
+        let (fork_from_conversation_id, fork_from_message_id) =
+            match &conversation.root_message.content {
+                ContentType::Metadata(metadata) => (
+                    metadata.fork_from_conversation_id,
+                    metadata.fork_from_message_id,
+                ),
+                _ => (None, None),
+            };
+
+        ForkGraphNode {
+            conversation_id: conversation.conversation_id,
+            title: conversation.title().unwrap_or_default(),
+            created_by: conversation.created_by().to_string(),
+            created_at: conversation.created_at(),
+            fork_from_conversation_id,
+            fork_from_message_id,
+        }
+    }
+
+    fn from_link(link: ForkLinkRow) -> Self {
+        ForkGraphNode {
+            conversation_id: link.fork_conversation_id,
+            title: link.title,
+            created_by: link.created_by,
+            created_at: link.created_at,
+            fork_from_conversation_id: Some(link.source_conversation_id),
+            fork_from_message_id: link.fork_from_message_id,
+        }
+    }
+}
+
+/// The fork family of a conversation: its ancestors up to the original and
+/// everything forked from that original, directly or indirectly
+#[derive(Debug, Clone)]
+pub struct ForkGraph {
+    pub conversation_id: Uuid,
+    /// The topmost ancestor that still exists
+    pub origin_conversation_id: Uuid,
+    /// Ordered by creation time
+    pub nodes: Vec<ForkGraphNode>,
+    /// Set when the walk stopped at a depth or size limit
+    pub truncated: bool,
+}
 
 pub struct ForkService {
-    lineage_repo: LineageRepository,
-    branch_repo: BranchRepository,
+    lineage_repo: Arc<dyn LineageStore>,
+    branch_repo: Arc<dyn BranchStore>,
+    share_repo: Arc<dyn ShareStore>,
     app_config: AppConfig,
+    fork_config: ForkConfig,
+    notifications: Arc<NotificationService>,
+    images: Arc<ImageService>,
+    /// When each user forked within the last hour, oldest first
+    recent_forks: Mutex<HashMap<String, VecDeque<DateTime<Utc>>>>,
 }
 
 impl ForkService {
     pub fn new(
-        lineage_repo: LineageRepository,
-        branch_repo: BranchRepository,
+        lineage_repo: Arc<dyn LineageStore>,
+        branch_repo: Arc<dyn BranchStore>,
+        share_repo: Arc<dyn ShareStore>,
         app_config: AppConfig,
+        fork_config: ForkConfig,
+        notifications: Arc<NotificationService>,
+        images: Arc<ImageService>,
     ) -> Self {
         Self {
             lineage_repo,
             branch_repo,
+            share_repo,
             app_config,
+            fork_config,
+            notifications,
+            images,
+            recent_forks: Mutex::new(HashMap::new()),
         }
     }
 
@@ -30,56 +143,19 @@ impl ForkService {
         source_conversation_id: Uuid,
         title: String,
         created_by: String,
+        options: ForkOptions,
     ) -> Result<Conversation, DbError> {
-        // Get all messages from source conversation
-        let source_messages = self
-            .lineage_repo
-            .get_all_messages(source_conversation_id)
-            .await?;
-
-        // Create new conversation with fork metadata
-        let new_conversation_id = Uuid::new_v4();
-        let new_root_id = Uuid::new_v4();
-
-        let root_message = Message {
-            conversation_id: new_conversation_id,
-            message_id: new_root_id,
-            parent_message_id: None,
-            role: crate::domain::MessageRole::Root,
-            content: ContentType::Metadata(MetadataContent {
-                title: title.clone(),
-                description: Some(format!(
-                    "Forked from conversation {}",
-                    source_conversation_id
-                )),
-                is_public: false,
-                fork_from_conversation_id: Some(source_conversation_id),
-                fork_from_message_id: None,
-            }),
-            content_metadata: std::collections::HashMap::new(),
-            lineage: vec![new_root_id],
-            created_at: chrono::Utc::now(),
-            created_by: created_by.clone(),
-        };
+        let source = self.whole_source(source_conversation_id).await?;
 
-        // Copy all non-root messages with new conversation_id
-        let mut forked_messages = vec![root_message.clone()];
-
-        for msg in source_messages.iter() {
-            if !msg.is_root() {
-                let mut forked_msg = msg.clone();
-                forked_msg.conversation_id = new_conversation_id;
-                forked_messages.push(forked_msg);
-            }
-        }
-
-        // Batch insert all messages
-        self.batch_insert_with_limit(&forked_messages).await?;
-
-        Ok(Conversation {
-            conversation_id: new_conversation_id,
-            root_message,
-        })
+        self.fork_messages(
+            source_conversation_id,
+            source,
+            None,
+            title,
+            created_by,
+            options,
+        )
+        .await
     }
 
     /// Fork a specific branch to a new conversation
@@ -89,6 +165,7 @@ impl ForkService {
         source_branch_id: Uuid,
         title: String,
         created_by: String,
+        options: ForkOptions,
     ) -> Result<Conversation, DbError> {
         // Get the branch
         let branch = self
@@ -109,8 +186,15 @@ impl ForkService {
             .await?;
 
         // Fork from the specific branch point
-        self.fork_messages(source_conversation_id, &source_messages, title, created_by)
-            .await
+        self.fork_messages(
+            source_conversation_id,
+            ForkSource::from_messages(source_messages),
+            Some(leaf_message.message_id),
+            title,
+            created_by,
+            options,
+        )
+        .await
     }
 
     /// Fork from a specific message (copies lineage up to that point)
@@ -120,6 +204,7 @@ impl ForkService {
         source_message_id: Uuid,
         title: String,
         created_by: String,
+        options: ForkOptions,
     ) -> Result<Conversation, DbError> {
         // Get the message to get its lineage
         let message = self
@@ -134,71 +219,1373 @@ impl ForkService {
             .await?;
 
         // Fork from this point
-        self.fork_messages(source_conversation_id, &source_messages, title, created_by)
-            .await
+        self.fork_messages(
+            source_conversation_id,
+            ForkSource::from_messages(source_messages),
+            Some(source_message_id),
+            title,
+            created_by,
+            options,
+        )
+        .await
     }
 
-    /// Helper function to fork a list of messages
+    /// Copy messages into a new conversation under a fresh root.
+    ///
+    /// With `remap_ids` every copied message gets a new ID and parent and
+    /// lineage references are rewritten to match. Without it messages keep
+    /// their IDs (only references to the source root are rewritten) and no
+    /// branches are copied, since a leaf ID may belong to a single branch.
     async fn fork_messages(
         &self,
         source_conversation_id: Uuid,
-        source_messages: &[Message],
+        source: ForkSource<'_>,
+        fork_from_message_id: Option<Uuid>,
         title: String,
         created_by: String,
+        options: ForkOptions,
     ) -> Result<Conversation, DbError> {
+        self.ensure_fits(source.count)?;
+        self.ensure_can_fork(source_conversation_id, source.root.as_ref(), &created_by)
+            .await?;
+        let source = source.visible_to(None);
+        self.record_fork(&created_by, Utc::now())?;
+
         // Create new conversation with fork metadata
         let new_conversation_id = Uuid::new_v4();
-        let new_root_id = Uuid::new_v4();
+        let new_root_id = new_message_id();
 
+        let metadata = MetadataContent {
+            title: title.clone(),
+            description: Some(format!(
+                "Forked from conversation {}",
+                source_conversation_id
+            )),
+            is_public: false,
+            fork_from_conversation_id: Some(source_conversation_id),
+            fork_from_message_id,
+            language: source.root.as_ref().and_then(|root| match &root.content {
+                ContentType::Metadata(metadata) => metadata.language.clone(),
+                _ => None,
+            }),
+            published_branch_id: None,
+            private_message_ids: Vec::new(),
+        };
         let root_message = Message {
             conversation_id: new_conversation_id,
             message_id: new_root_id,
             parent_message_id: None,
             role: crate::domain::MessageRole::Root,
-            content: ContentType::Metadata(MetadataContent {
-                title: title.clone(),
-                description: Some(format!(
-                    "Forked from conversation {}",
-                    source_conversation_id
-                )),
-                is_public: false,
-                fork_from_conversation_id: Some(source_conversation_id),
-                fork_from_message_id: source_messages.last().map(|m| m.message_id),
-            }),
+            content: ContentType::Metadata(metadata.clone()),
             content_metadata: std::collections::HashMap::new(),
             lineage: vec![new_root_id],
             created_at: chrono::Utc::now(),
             created_by: created_by.clone(),
         };
 
-        // Copy messages with new conversation_id
-        let mut forked_messages = vec![root_message.clone()];
+        let mut id_map = IdMap::new(source.root.as_ref(), &root_message, options.remap_ids);
+
+        // Batch insert all messages, recording the fork in its event log
+        let fork_events = vec![
+            ConversationEvent::new(
+                new_conversation_id,
+                EventKind::Forked,
+                &Forked {
+                    source_conversation_id,
+                    source_message_id: fork_from_message_id,
+                    created_by: created_by.clone(),
+                },
+            )?,
+            ConversationEvent::new(new_conversation_id, EventKind::MetadataUpdated, &metadata)?,
+        ];
+        let mut progress = self
+            .start_copy(
+                new_conversation_id,
+                source_conversation_id,
+                &created_by,
+                source.count,
+            )
+            .await?;
+        let copied = async {
+            let copies = id_map.copy_stream(&root_message, source.messages);
+            self.batch_insert_with_limit(&mut progress, copies, fork_events)
+                .await?;
 
-        for msg in source_messages.iter() {
-            if !msg.is_root() {
-                let mut forked_msg = msg.clone();
-                forked_msg.conversation_id = new_conversation_id;
-                forked_messages.push(forked_msg);
+            if options.remap_ids && options.copy_branches {
+                self.copy_branches(
+                    source_conversation_id,
+                    new_conversation_id,
+                    &id_map.ids,
+                    &created_by,
+                )
+                .await?;
+            }
+            if options.copy_share {
+                self.copy_share(source_conversation_id, new_conversation_id, &created_by)
+                    .await?;
+            }
+
+            let conversation = Conversation {
+                conversation_id: new_conversation_id,
+                root_message,
+            };
+            if let Some(entry) = ConversationTitleRow::from_conversation(&conversation) {
+                self.lineage_repo.upsert_conversation_title(&entry).await?;
+            }
+            if let Some(link) = ForkLinkRow::from_conversation(&conversation) {
+                self.lineage_repo.upsert_fork_link(&link).await?;
             }
+            self.lineage_repo
+                .delete_fork_progress(new_conversation_id)
+                .await?;
+
+            Ok(conversation)
         }
+        .await;
+        let conversation = self.discard_on_failure(&progress, copied).await?;
+        self.notify_owner(source_conversation_id, source.root.as_ref(), &conversation)
+            .await;
 
-        // Batch insert all messages
-        self.batch_insert_with_limit(&forked_messages).await?;
+        Ok(conversation)
+    }
 
-        Ok(Conversation {
+    /// Copy a conversation for `caller`, with fresh IDs and its branches,
+    /// titled "<title> (copy)". Messages the caller can't see are left out;
+    /// the owner's private messages stay private in the owner's copy. Unlike
+    /// a fork, the copy doesn't record where it came from, so it is not part
+    /// of the fork graph, and it doesn't count towards the fork rate limit.
+    pub async fn duplicate_conversation(
+        &self,
+        source_conversation_id: Uuid,
+        caller: &str,
+    ) -> Result<Conversation, DbError> {
+        let source = self
+            .whole_source(source_conversation_id)
+            .await?
+            .visible_to(Some(caller));
+        let source_root = source.root.as_ref().ok_or(DbError::NotFound)?;
+        let ContentType::Metadata(source_metadata) = &source_root.content else {
+            return Err(DbError::InvalidData(
+                "Invalid root message content".to_string(),
+            ));
+        };
+
+        let new_conversation_id = Uuid::new_v4();
+        let new_root_id = new_message_id();
+        let mut metadata = MetadataContent {
+            title: format!("{} (copy)", source_metadata.title),
+            description: source_metadata.description.clone(),
+            is_public: false,
+            fork_from_conversation_id: None,
+            fork_from_message_id: None,
+            language: source_metadata.language.clone(),
+            // Copied branches get fresh IDs
+            published_branch_id: None,
+            private_message_ids: Vec::new(),
+        };
+        let mut root_message = Message {
             conversation_id: new_conversation_id,
+            message_id: new_root_id,
+            parent_message_id: None,
+            role: crate::domain::MessageRole::Root,
+            content: ContentType::Metadata(metadata.clone()),
+            content_metadata: source_root.content_metadata.clone(),
+            lineage: vec![new_root_id],
+            created_at: Utc::now(),
+            created_by: source_root.created_by.clone(),
+        };
+
+        let mut id_map = IdMap::new(Some(source_root), &root_message, true);
+        // Only the owner sees private messages, and copies them along
+        if caller == source_root.created_by {
+            metadata.private_message_ids = source_metadata
+                .private_message_ids
+                .iter()
+                .map(|&id| id_map.get(id))
+                .collect();
+            root_message.content = ContentType::Metadata(metadata.clone());
+        }
+        let events = vec![ConversationEvent::new(
+            new_conversation_id,
+            EventKind::MetadataUpdated,
+            &metadata,
+        )?];
+        let created_by = root_message.created_by.clone();
+        let mut progress = self
+            .start_copy(
+                new_conversation_id,
+                source_conversation_id,
+                &created_by,
+                source.count,
+            )
+            .await?;
+        let copied = async {
+            let copies = id_map.copy_stream(&root_message, source.messages);
+            self.batch_insert_with_limit(&mut progress, copies, events)
+                .await?;
+            self.copy_branches(
+                source_conversation_id,
+                new_conversation_id,
+                &id_map.ids,
+                &created_by,
+            )
+            .await?;
+
+            let conversation = Conversation {
+                conversation_id: new_conversation_id,
+                root_message,
+            };
+            if let Some(entry) = ConversationTitleRow::from_conversation(&conversation) {
+                self.lineage_repo.upsert_conversation_title(&entry).await?;
+            }
+            self.lineage_repo
+                .delete_fork_progress(new_conversation_id)
+                .await?;
+
+            Ok(conversation)
+        }
+        .await;
+
+        self.discard_on_failure(&progress, copied).await
+    }
+
+    /// A whole conversation to copy, read a page at a time as it is inserted
+    async fn whole_source(&self, conversation_id: Uuid) -> Result<ForkSource<'_>, DbError> {
+        // Refuse oversized sources before reading them
+        let count = self.lineage_repo.count_messages(conversation_id).await?;
+        self.ensure_fits(count)?;
+
+        let root = match self.lineage_repo.get_root_message(conversation_id).await {
+            Ok(root) => Some(root),
+            Err(DbError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        let messages = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, Some(self.app_config.max_batch_size as i32))
+            .await?;
+
+        Ok(ForkSource {
+            root,
+            messages,
+            count,
+        })
+    }
+
+    /// Delete the copies whose progress record hasn't moved for
+    /// `fork.stale_after_secs`: the instance making them failed to clean up
+    /// or stopped midway. Returns how many were deleted.
+    pub async fn discard_abandoned(&self) -> Result<usize, DbError> {
+        let stale_before = Utc::now() - Duration::seconds(self.fork_config.stale_after_secs as i64);
+        let mut discarded = 0;
+
+        for progress in self.lineage_repo.get_fork_progress().await? {
+            if progress.updated_at >= stale_before {
+                continue;
+            }
+
+            tracing::warn!(
+                "Deleting abandoned copy {} of {} ({} of {} batches inserted)",
+                progress.conversation_id,
+                progress.source_conversation_id,
+                progress.completed_chunks,
+                progress.total_chunks
+            );
+            self.discard_copy(&progress).await?;
+            discarded += 1;
+        }
+
+        Ok(discarded)
+    }
+
+    /// Record that a copy of `messages` messages into `conversation_id` has
+    /// started, before anything of it is written
+    async fn start_copy(
+        &self,
+        conversation_id: Uuid,
+        source_conversation_id: Uuid,
+        created_by: &str,
+        messages: usize,
+    ) -> Result<ForkProgress, DbError> {
+        let progress = ForkProgress::new(
+            conversation_id,
+            source_conversation_id,
+            created_by.to_string(),
+            messages.div_ceil(self.app_config.max_batch_size),
+        );
+        self.lineage_repo.upsert_fork_progress(&progress).await?;
+
+        Ok(progress)
+    }
+
+    /// Pass on the result of a copy, deleting what it wrote if it failed.
+    /// If that fails too, the progress record stays for `discard_abandoned`.
+    async fn discard_on_failure<T>(
+        &self,
+        progress: &ForkProgress,
+        copied: Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        let err = match copied {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        tracing::warn!(
+            "Copy {} of {} failed after {} of {} batches: {}",
+            progress.conversation_id,
+            progress.source_conversation_id,
+            progress.completed_chunks,
+            progress.total_chunks,
+            err
+        );
+        if let Err(cleanup_err) = self.discard_copy(progress).await {
+            tracing::error!(
+                "Failed to delete partial copy {}, left for recovery: {}",
+                progress.conversation_id,
+                cleanup_err
+            );
+        }
+
+        Err(err)
+    }
+
+    /// Delete everything a copy may have written, then its progress record
+    async fn discard_copy(&self, progress: &ForkProgress) -> Result<(), DbError> {
+        let conversation_id = progress.conversation_id;
+
+        for branch in self
+            .branch_repo
+            .get_branches_by_conversation(conversation_id)
+            .await?
+        {
+            self.branch_repo
+                .delete_branch(conversation_id, branch.branch_id, branch.leaf_message_id)
+                .await?;
+        }
+        self.share_repo
+            .delete_share(conversation_id, &progress.created_by)
+            .await?;
+        self.lineage_repo
+            .delete_conversation_title(&progress.created_by, conversation_id)
+            .await?;
+        self.lineage_repo
+            .delete_fork_link(progress.source_conversation_id, conversation_id)
+            .await?;
+        self.lineage_repo
+            .delete_conversation(conversation_id)
+            .await?;
+        self.images.release_conversation(conversation_id).await?;
+        self.lineage_repo.delete_events(conversation_id).await?;
+
+        self.lineage_repo
+            .delete_fork_progress(conversation_id)
+            .await
+    }
+
+    /// Build the fork graph around a conversation. Ancestors come from the fork
+    /// metadata in each root message; descendants from the fork index, so forks
+    /// whose source was deleted cut the walk off there.
+    pub async fn get_fork_graph(&self, conversation_id: Uuid) -> Result<ForkGraph, DbError> {
+        let mut current = self.get_root(conversation_id).await?;
+        let mut truncated = false;
+
+        // Walk up to the original conversation
+        let mut nodes = vec![ForkGraphNode::from_conversation(&current)];
+        while let Some(source_id) = nodes.last().and_then(|n| n.fork_from_conversation_id) {
+            if nodes.len() > MAX_FORK_ANCESTORS {
+                truncated = true;
+                break;
+            }
+            if nodes.iter().any(|n| n.conversation_id == source_id) {
+                break;
+            }
+            current = match self.get_root(source_id).await {
+                Ok(conversation) => conversation,
+                Err(DbError::NotFound) => break,
+                Err(e) => return Err(e),
+            };
+            nodes.push(ForkGraphNode::from_conversation(&current));
+        }
+        let origin_conversation_id = current.conversation_id;
+
+        // Walk down through every fork of the original
+        let mut seen: HashSet<Uuid> = nodes.iter().map(|n| n.conversation_id).collect();
+        let mut queue = VecDeque::from([origin_conversation_id]);
+        'walk: while let Some(source_id) = queue.pop_front() {
+            for link in self.lineage_repo.get_fork_links(source_id).await? {
+                if seen.contains(&link.fork_conversation_id) {
+                    continue;
+                }
+                if nodes.len() >= MAX_FORK_GRAPH_NODES {
+                    truncated = true;
+                    break 'walk;
+                }
+                seen.insert(link.fork_conversation_id);
+                queue.push_back(link.fork_conversation_id);
+                nodes.push(ForkGraphNode::from_link(link));
+            }
+        }
+
+        nodes.sort_by_key(|n| (n.created_at, n.conversation_id));
+
+        Ok(ForkGraph {
+            conversation_id,
+            origin_conversation_id,
+            nodes,
+            truncated,
+        })
+    }
+
+    async fn get_root(&self, conversation_id: Uuid) -> Result<Conversation, DbError> {
+        let root_message = self.lineage_repo.get_root_message(conversation_id).await?;
+
+        Ok(Conversation {
+            conversation_id,
             root_message,
         })
     }
 
-    /// Helper to batch insert with size limits
-    async fn batch_insert_with_limit(&self, messages: &[Message]) -> Result<(), DbError> {
-        let batch_size = self.app_config.max_batch_size;
+    /// Recreate the source branches whose leaf was copied into the fork
+    async fn copy_branches(
+        &self,
+        source_conversation_id: Uuid,
+        new_conversation_id: Uuid,
+        id_map: &HashMap<Uuid, Uuid>,
+        created_by: &str,
+    ) -> Result<(), DbError> {
+        let branches = self
+            .branch_repo
+            .get_branches_by_conversation(source_conversation_id)
+            .await?;
+
+        for branch in branches {
+            let Some(&leaf_message_id) = id_map.get(&branch.leaf_message_id) else {
+                continue;
+            };
+
+            let mut forked = Branch::new(
+                new_conversation_id,
+                branch.branch_name,
+                leaf_message_id,
+                created_by.to_string(),
+            );
+            forked.slug = branch.slug;
+            forked.is_active = branch.is_active;
+            self.branch_repo.insert_branch(&forked).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Forks are copied within the request, so sources are capped in size
+    fn ensure_fits(&self, messages: usize) -> Result<(), ForkRejection> {
+        let max = self.fork_config.max_source_messages;
+        if messages > max {
+            return Err(ForkRejection::SourceTooLarge { messages, max });
+        }
+        Ok(())
+    }
+
+    /// The owner may always fork. Others need a `fork` share of their own
+    /// or, on a public conversation, the `fork` share everyone gets.
+    async fn ensure_can_fork(
+        &self,
+        source_conversation_id: Uuid,
+        source_root: Option<&Message>,
+        created_by: &str,
+    ) -> Result<(), DbError> {
+        let Some(root) = source_root else {
+            return Ok(());
+        };
+        if root.created_by == created_by {
+            return Ok(());
+        }
+        let is_public =
+            matches!(&root.content, ContentType::Metadata(metadata) if metadata.is_public);
+
+        for shared_with in [EVERYONE, created_by] {
+            if shared_with == EVERYONE && !is_public {
+                continue;
+            }
+            match self
+                .share_repo
+                .get_share(source_conversation_id, shared_with)
+                .await
+            {
+                Ok(share) if share.permission.can_fork() => return Ok(()),
+                Ok(_) => {}
+                // Published before sharing policies existed
+                Err(DbError::NotFound) if shared_with == EVERYONE => return Ok(()),
+                Err(DbError::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        let reason = if is_public {
+            "The owner doesn't allow forking this conversation"
+        } else {
+            "Forking a private conversation needs a fork share"
+        };
+        Err(ForkRejection::NotAllowed(reason.to_string()).into())
+    }
+
+    /// Count a fork by `created_by`, unless they reached the hourly limit
+    fn record_fork(&self, created_by: &str, now: DateTime<Utc>) -> Result<(), ForkRejection> {
+        let window_start = now - Duration::hours(1);
+        let max = self.fork_config.max_per_user_per_hour;
+        let mut recent_forks = self.recent_forks.lock().unwrap();
+        recent_forks.retain(|_, forks| {
+            while forks.front().is_some_and(|at| *at <= window_start) {
+                forks.pop_front();
+            }
+            !forks.is_empty()
+        });
+
+        let forks = recent_forks.entry(created_by.to_string()).or_default();
+        if let Some(oldest) = forks.front()
+            && forks.len() >= max
+        {
+            let retry_after_secs = (*oldest - window_start).num_seconds().max(1) as u64;
+            return Err(ForkRejection::RateLimited {
+                max,
+                retry_after_secs,
+            });
+        }
+        forks.push_back(now);
+        Ok(())
+    }
+
+    /// Give the forker the share they hold on the source, if any
+    async fn copy_share(
+        &self,
+        source_conversation_id: Uuid,
+        new_conversation_id: Uuid,
+        created_by: &str,
+    ) -> Result<(), DbError> {
+        let share = match self
+            .share_repo
+            .get_share(source_conversation_id, created_by)
+            .await
+        {
+            Ok(share) => share,
+            Err(DbError::NotFound) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        self.share_repo
+            .insert_share(&Share {
+                conversation_id: new_conversation_id,
+                shared_at: chrono::Utc::now(),
+                ..share
+            })
+            .await
+    }
+
+    /// Let the owner of a public source conversation know it was forked
+    async fn notify_owner(
+        &self,
+        source_conversation_id: Uuid,
+        source_root: Option<&Message>,
+        fork: &Conversation,
+    ) {
+        let Some(root) = source_root else {
+            return;
+        };
+        if !matches!(&root.content, ContentType::Metadata(metadata) if metadata.is_public) {
+            return;
+        }
+
+        self.notifications
+            .notify(
+                &root.created_by,
+                NotificationKind::ConversationForked,
+                source_conversation_id,
+                &fork.root_message.created_by,
+                Some(fork.conversation_id),
+            )
+            .await;
+    }
+
+    /// Helper to batch insert with size limits, `fork.chunk_parallelism`
+    /// batches at a time, reading `messages` only as far as the batches in
+    /// flight need. Every batch carries a `MessageCreated` event per
+    /// non-root message; the first one also carries `first_events`. Events
+    /// are numbered as batches are cut, so the log keeps the message order
+    /// whichever batch lands first. Batches failing with transient errors are
+    /// retried, and each one inserted is counted in `progress`.
+    async fn batch_insert_with_limit(
+        &self,
+        progress: &mut ForkProgress,
+        messages: BoxStream<'_, Result<Message, DbError>>,
+        first_events: Vec<ConversationEvent>,
+    ) -> Result<(), DbError> {
+        let mut first_events = Some(first_events);
+        let mut inserts = messages
+            .try_chunks(self.app_config.max_batch_size)
+            .map_err(|TryChunksError(_, err)| err)
+            .and_then(|chunk| {
+                let mut events = first_events.take().unwrap_or_default();
+                let batch = chunk
+                    .iter()
+                    .filter(|m| !m.is_root())
+                    .try_for_each(|message| {
+                        events.push(ConversationEvent::new(
+                            message.conversation_id,
+                            EventKind::MessageCreated,
+                            message,
+                        )?);
+                        Ok(())
+                    })
+                    .map(|()| self.insert_batch(chunk, events).boxed());
+                future::ready(batch)
+            })
+            .try_buffer_unordered(self.fork_config.chunk_parallelism)
+            .boxed();
+
+        while let Some(()) = inserts.try_next().await? {
+            progress.completed_chunks += 1;
+            progress.updated_at = Utc::now();
+            self.lineage_repo.upsert_fork_progress(progress).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn insert_batch(
+        &self,
+        messages: Vec<Message>,
+        events: Vec<ConversationEvent>,
+    ) -> Result<(), DbError> {
+        self.images.track(&messages).await?;
+
+        // Messages and events keep their keys across attempts, so a batch
+        // that was written before failing is merely overwritten
+        retry_chunk(&self.fork_config, || {
+            self.lineage_repo.insert_copied_messages(&messages, &events)
+        })
+        .await?;
+
+        // Counters aren't idempotent, so they are added once the batch is in
+        let Some(first) = messages.first() else {
+            return Ok(());
+        };
+        count_new_messages(self.lineage_repo.as_ref(), first.conversation_id, &messages).await
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for ForkService {
+    fn name(&self) -> &'static str {
+        "fork_recovery"
+    }
 
-        for chunk in messages.chunks(batch_size) {
-            self.lineage_repo.batch_insert_messages(chunk).await?;
+    async fn run(&self) -> Result<(), TaskError> {
+        let discarded = self.discard_abandoned().await?;
+        if discarded > 0 {
+            tracing::info!("Deleted {} abandoned forks", discarded);
         }
 
         Ok(())
     }
 }
+
+/// Run `attempt` until it succeeds, fails with an error that isn't
+/// transient, or `fork.chunk_retries` retries have failed. Waits between
+/// attempts start at `fork.chunk_retry_backoff_ms` and double.
+async fn retry_chunk<F, Fut>(config: &ForkConfig, mut attempt: F) -> Result<(), DbError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), DbError>>,
+{
+    let mut backoff = std::time::Duration::from_millis(config.chunk_retry_backoff_ms);
+
+    for retries in 0.. {
+        let err = match attempt().await {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        if retries >= config.chunk_retries || !is_transient(&err) {
+            return Err(err);
+        }
+
+        tracing::warn!(
+            "Inserting fork batch failed (attempt {}): {}; retrying in {} ms",
+            retries + 1,
+            err,
+            backoff.as_millis()
+        );
+        tokio::time::sleep(backoff).await;
+        backoff *= 2;
+    }
+
+    unreachable!("the retries range is unbounded")
+}
+
+/// The messages a fork copies from its source
+struct ForkSource<'a> {
+    root: Option<Message>,
+    messages: BoxStream<'a, Result<Message, DbError>>,
+    count: usize,
+}
+
+impl ForkSource<'_> {
+    /// The source without the messages `viewer` can't see; with `None`,
+    /// without any of those its owner made private
+    fn visible_to(self, viewer: Option<&str>) -> Self {
+        let Some(root) = &self.root else {
+            return self;
+        };
+        let private = PrivateMessages::of(root, viewer);
+
+        Self {
+            messages: self
+                .messages
+                .try_filter(move |m| future::ready(!private.hides(m)))
+                .boxed(),
+            ..self
+        }
+    }
+}
+
+impl ForkSource<'static> {
+    /// A part of the source, e.g. a branch's lineage, already read
+    fn from_messages(messages: Vec<Message>) -> Self {
+        Self {
+            root: messages.iter().find(|m| m.is_root()).cloned(),
+            count: messages.len(),
+            messages: stream::iter(messages.into_iter().map(Ok)).boxed(),
+        }
+    }
+}
+
+/// Source to copied message IDs, filled in as the source is read. The source
+/// root maps to the copy's root; with `remap_ids` every other message gets a
+/// fresh ID the first time it is referenced.
+struct IdMap {
+    ids: HashMap<Uuid, Uuid>,
+    remap_ids: bool,
+}
+
+impl IdMap {
+    fn new(source_root: Option<&Message>, root_message: &Message, remap_ids: bool) -> Self {
+        Self {
+            ids: source_root
+                .map(|root| (root.message_id, root_message.message_id))
+                .into_iter()
+                .collect(),
+            remap_ids,
+        }
+    }
+
+    /// Copies of `source_messages` under `root_message`, root first. The
+    /// source is read in ID order, which for time-ordered IDs is the order
+    /// messages were written, so fresh IDs keep the source order.
+    fn copy_stream<'a>(
+        &'a mut self,
+        root_message: &Message,
+        source_messages: BoxStream<'a, Result<Message, DbError>>,
+    ) -> BoxStream<'a, Result<Message, DbError>> {
+        let conversation_id = root_message.conversation_id;
+        stream::once(future::ready(Ok(root_message.clone())))
+            .chain(
+                source_messages
+                    .try_filter(|m| future::ready(!m.is_root()))
+                    .map_ok(move |msg| self.copy(&msg, conversation_id)),
+            )
+            .boxed()
+    }
+
+    /// The copy of a message referenced from the source
+    fn get(&mut self, id: Uuid) -> Uuid {
+        let remap_ids = self.remap_ids;
+        *self
+            .ids
+            .entry(id)
+            .or_insert_with(|| if remap_ids { new_message_id() } else { id })
+    }
+
+    /// A copy of a source message in the fork, with every message reference
+    /// translated
+    fn copy(&mut self, msg: &Message, conversation_id: Uuid) -> Message {
+        let mut forked = msg.clone();
+        forked.conversation_id = conversation_id;
+        // Lineage runs root first, so ancestors are mapped before descendants
+        forked.lineage = msg.lineage.iter().map(|&id| self.get(id)).collect();
+        forked.message_id = self.get(msg.message_id);
+        forked.parent_message_id = msg.parent_message_id.map(|id| self.get(id));
+        if let ContentType::Summary(summary) = &mut forked.content {
+            summary.from_message_id = self.get(summary.from_message_id);
+            summary.to_message_id = self.get(summary.to_message_id);
+        }
+
+        forked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use crate::domain::{MessageRole, Permission, TextContent};
+    use crate::object_store::S3ObjectStore;
+    use crate::repositories::Storage;
+    use crate::services::PreferenceService;
+    use crate::utils::compute_lineage;
+    use scylla::transport::errors::QueryError;
+    use std::cell::Cell;
+
+    fn reply(parent: &Message, text: &str) -> Message {
+        let message_id = new_message_id();
+        Message {
+            conversation_id: parent.conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role: MessageRole::Human,
+            content: ContentType::Text(TextContent {
+                text: text.to_string(),
+            }),
+            content_metadata: HashMap::new(),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: chrono::Utc::now(),
+            created_by: "user_a".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fork_remaps_ids_and_copies_branches() {
+        let storage = Storage::memory();
+        let service = ForkService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            AppConfig {
+                max_lineage_depth: 1000,
+                max_batch_size: 2,
+                max_children_per_message: 1000,
+                max_messages_per_conversation: 50_000,
+                default_page_size: 50,
+                max_page_size: 200,
+            },
+            Settings::default().fork,
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+        );
+
+        let source = Conversation::new("Source".to_string(), "user_a".to_string());
+        let a = reply(&source.root_message, "a");
+        let b = reply(&a, "b");
+        storage
+            .lineage
+            .batch_insert_messages(&[source.root_message.clone(), a.clone(), b.clone()])
+            .await
+            .unwrap();
+        let branch = Branch::new(
+            source.conversation_id,
+            "main".to_string(),
+            b.message_id,
+            "user_a".to_string(),
+        );
+        storage.branches.insert_branch(&branch).await.unwrap();
+        storage
+            .shares
+            .insert_share(&Share {
+                conversation_id: source.conversation_id,
+                shared_with: "user_b".to_string(),
+                permission: crate::domain::Permission::Fork,
+                shared_at: chrono::Utc::now(),
+                shared_by: "user_a".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let fork = service
+            .fork_conversation(
+                source.conversation_id,
+                "Fork".to_string(),
+                "user_b".to_string(),
+                ForkOptions::default(),
+            )
+            .await
+            .unwrap();
+        let messages = storage
+            .lineage
+            .get_all_messages(fork.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(messages.len(), 3);
+        // Each batch is counted once
+        let counted = storage.lineage.get_message_count(fork.conversation_id);
+        assert_eq!(counted.await.unwrap(), 3);
+        assert!(
+            storage
+                .lineage
+                .get_fork_progress()
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        // Two batches, inserted concurrently, still log in message order
+        let kinds: Vec<EventKind> = storage
+            .lineage
+            .get_events(fork.conversation_id, 0, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Forked,
+                EventKind::MetadataUpdated,
+                EventKind::MessageCreated,
+                EventKind::MessageCreated
+            ]
+        );
+
+        let ids: Vec<Uuid> = messages.iter().map(|m| m.message_id).collect();
+        for message in &messages {
+            assert!(![a.message_id, b.message_id].contains(&message.message_id));
+            assert!(message.lineage.iter().all(|id| ids.contains(id)));
+            assert_eq!(message.lineage[0], fork.root_message.message_id);
+            if let Some(parent) = message.parent_message_id {
+                assert!(ids.contains(&parent));
+            }
+        }
+
+        let branches = storage
+            .branches
+            .get_branches_by_conversation(fork.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches[0].branch_name, "main");
+        assert!(ids.contains(&branches[0].leaf_message_id));
+        assert_eq!(
+            storage
+                .branches
+                .get_branch_by_leaf(b.message_id)
+                .await
+                .unwrap(),
+            (source.conversation_id, branch.branch_id)
+        );
+
+        let kept = service
+            .fork_from_message(
+                source.conversation_id,
+                b.message_id,
+                "Kept".to_string(),
+                "user_b".to_string(),
+                ForkOptions {
+                    remap_ids: false,
+                    copy_branches: true,
+                    copy_share: true,
+                },
+            )
+            .await
+            .unwrap();
+        let kept_b = storage
+            .lineage
+            .get_message(kept.conversation_id, b.message_id)
+            .await
+            .unwrap();
+        assert_eq!(kept_b.lineage[0], kept.root_message.message_id);
+        assert!(
+            storage
+                .branches
+                .get_branches_by_conversation(kept.conversation_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            storage
+                .shares
+                .get_share(kept.conversation_id, "user_b")
+                .await
+                .unwrap()
+                .permission,
+            crate::domain::Permission::Fork
+        );
+
+        let copy = service
+            .duplicate_conversation(source.conversation_id, "user_a")
+            .await
+            .unwrap();
+        assert_eq!(copy.title().as_deref(), Some("Source (copy)"));
+        assert_eq!(copy.created_by(), "user_a");
+        let copied = storage
+            .lineage
+            .get_all_messages(copy.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(copied.len(), 3);
+        assert!(
+            copied
+                .iter()
+                .all(|m| ![a.message_id, b.message_id].contains(&m.message_id))
+        );
+        assert_eq!(
+            storage
+                .branches
+                .get_branches_by_conversation(copy.conversation_id)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+        // Not recorded as a fork of the source
+        let links = storage
+            .lineage
+            .get_fork_links(source.conversation_id)
+            .await
+            .unwrap();
+        assert!(
+            links
+                .iter()
+                .all(|link| link.fork_conversation_id != copy.conversation_id)
+        );
+        assert!(
+            storage
+                .lineage
+                .get_conversation_titles("user_a")
+                .await
+                .unwrap()
+                .iter()
+                .any(|entry| entry.conversation_id == copy.conversation_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fork_guards_are_enforced() {
+        let storage = Storage::memory();
+        let service = ForkService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            Settings::default().app,
+            ForkConfig {
+                max_source_messages: 2,
+                max_per_user_per_hour: 3,
+                ..Settings::default().fork
+            },
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+        );
+
+        let mut source = Conversation::new("Source".to_string(), "user_a".to_string());
+        if let ContentType::Metadata(metadata) = &mut source.root_message.content {
+            metadata.is_public = true;
+        }
+        storage
+            .lineage
+            .insert_message(&source.root_message)
+            .await
+            .unwrap();
+        let fork = |created_by: &str| {
+            service.fork_conversation(
+                source.conversation_id,
+                "Fork".to_string(),
+                created_by.to_string(),
+                ForkOptions::default(),
+            )
+        };
+
+        // Published before policies existed
+        assert!(fork("user_b").await.is_ok());
+
+        let share = |shared_with: &str, permission| Share {
+            conversation_id: source.conversation_id,
+            shared_with: shared_with.to_string(),
+            permission,
+            shared_at: Utc::now(),
+            shared_by: "user_a".to_string(),
+        };
+        storage
+            .shares
+            .insert_share(&share(EVERYONE, Permission::Read))
+            .await
+            .unwrap();
+        assert!(matches!(
+            fork("user_b").await,
+            Err(DbError::ForkRejected(ForkRejection::NotAllowed(_)))
+        ));
+        assert!(fork("user_a").await.is_ok());
+
+        storage
+            .shares
+            .insert_share(&share("user_b", Permission::Fork))
+            .await
+            .unwrap();
+        assert!(fork("user_b").await.is_ok());
+
+        // Private again: only personal fork shares count
+        if let ContentType::Metadata(metadata) = &mut source.root_message.content {
+            metadata.is_public = false;
+        }
+        storage
+            .lineage
+            .insert_message(&source.root_message)
+            .await
+            .unwrap();
+        storage
+            .shares
+            .insert_share(&share(EVERYONE, Permission::Fork))
+            .await
+            .unwrap();
+        assert!(matches!(
+            fork("user_c").await,
+            Err(DbError::ForkRejected(ForkRejection::NotAllowed(_)))
+        ));
+
+        // Rejected forks don't count towards the hourly limit
+        assert!(fork("user_b").await.is_ok());
+        assert!(matches!(
+            fork("user_b").await,
+            Err(DbError::ForkRejected(ForkRejection::RateLimited {
+                max: 3,
+                retry_after_secs
+            })) if retry_after_secs > 3500
+        ));
+
+        let first = reply(&source.root_message, "first");
+        let second = reply(&first, "second");
+        storage
+            .lineage
+            .batch_insert_messages(&[first, second])
+            .await
+            .unwrap();
+        assert!(matches!(
+            fork("user_a").await,
+            Err(DbError::ForkRejected(ForkRejection::SourceTooLarge {
+                messages: 3,
+                max: 2
+            }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_fork_graph_spans_ancestors_and_descendants() {
+        let storage = Storage::memory();
+        let service = ForkService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            AppConfig {
+                max_lineage_depth: 1000,
+                max_batch_size: 10,
+                max_children_per_message: 1000,
+                max_messages_per_conversation: 50_000,
+                default_page_size: 50,
+                max_page_size: 200,
+            },
+            Settings::default().fork,
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+        );
+
+        let mut origin = Conversation::new("Origin".to_string(), "user_a".to_string());
+        if let ContentType::Metadata(metadata) = &mut origin.root_message.content {
+            metadata.is_public = true;
+        }
+        storage
+            .lineage
+            .insert_message(&origin.root_message)
+            .await
+            .unwrap();
+        let fork = |source: Uuid, title: &str| {
+            service.fork_conversation(
+                source,
+                title.to_string(),
+                "user_b".to_string(),
+                ForkOptions::default(),
+            )
+        };
+        let child = fork(origin.conversation_id, "Child").await.unwrap();
+        let sibling = fork(origin.conversation_id, "Sibling").await.unwrap();
+        let grandchild = fork(child.conversation_id, "Grandchild").await.unwrap();
+
+        let graph = service
+            .get_fork_graph(grandchild.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(graph.origin_conversation_id, origin.conversation_id);
+        assert!(!graph.truncated);
+        let mut titles: Vec<&str> = graph.nodes.iter().map(|n| n.title.as_str()).collect();
+        titles.sort();
+        assert_eq!(titles, ["Child", "Grandchild", "Origin", "Sibling"]);
+        let sibling_node = graph
+            .nodes
+            .iter()
+            .find(|n| n.conversation_id == sibling.conversation_id)
+            .unwrap();
+        assert_eq!(
+            sibling_node.fork_from_conversation_id,
+            Some(origin.conversation_id)
+        );
+
+        // A deleted source stops the walk upwards
+        storage
+            .lineage
+            .delete_conversation(origin.conversation_id)
+            .await
+            .unwrap();
+        let graph = service
+            .get_fork_graph(grandchild.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(graph.origin_conversation_id, child.conversation_id);
+        assert_eq!(graph.nodes.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_only_transient_chunk_failures_are_retried() {
+        let config = ForkConfig {
+            chunk_retries: 2,
+            chunk_retry_backoff_ms: 1,
+            ..Settings::default().fork
+        };
+        let calls = Cell::new(0);
+        let flaky = || {
+            calls.set(calls.get() + 1);
+            let calls = calls.get();
+            async move {
+                match calls {
+                    1 | 2 => Err(DbError::QueryError(QueryError::TimeoutError)),
+                    _ => Ok(()),
+                }
+            }
+        };
+        assert!(retry_chunk(&config, flaky).await.is_ok());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let down = || {
+            calls.set(calls.get() + 1);
+            async { Err(DbError::QueryError(QueryError::TimeoutError)) }
+        };
+        assert!(retry_chunk(&config, down).await.is_err());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let invalid = || {
+            calls.set(calls.get() + 1);
+            async { Err(DbError::InvalidData("bad".to_string())) }
+        };
+        assert!(retry_chunk(&config, invalid).await.is_err());
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_forks_are_deleted() {
+        let storage = Storage::memory();
+        let service = ForkService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            Settings::default().app,
+            ForkConfig {
+                stale_after_secs: 60,
+                ..Settings::default().fork
+            },
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+        );
+
+        // A fork whose instance stopped after the first of two batches, and
+        // one still being copied
+        let source_id = Uuid::new_v4();
+        let abandoned = Conversation::new("Fork".to_string(), "user_b".to_string());
+        let a = reply(&abandoned.root_message, "a");
+        storage
+            .lineage
+            .batch_insert_messages(&[abandoned.root_message.clone(), a.clone()])
+            .await
+            .unwrap();
+        let branch = Branch::new(
+            abandoned.conversation_id,
+            "main".to_string(),
+            a.message_id,
+            "user_b".to_string(),
+        );
+        storage.branches.insert_branch(&branch).await.unwrap();
+        let mut progress = ForkProgress::new(
+            abandoned.conversation_id,
+            source_id,
+            "user_b".to_string(),
+            2,
+        );
+        progress.completed_chunks = 1;
+        progress.updated_at = Utc::now() - Duration::minutes(5);
+        storage
+            .lineage
+            .upsert_fork_progress(&progress)
+            .await
+            .unwrap();
+        let running = ForkProgress::new(Uuid::new_v4(), source_id, "user_c".to_string(), 2);
+        storage
+            .lineage
+            .upsert_fork_progress(&running)
+            .await
+            .unwrap();
+
+        assert_eq!(service.discard_abandoned().await.unwrap(), 1);
+        assert_eq!(
+            storage
+                .lineage
+                .count_messages(abandoned.conversation_id)
+                .await
+                .unwrap(),
+            0
+        );
+        assert!(
+            storage
+                .branches
+                .get_branches_by_conversation(abandoned.conversation_id)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let left: Vec<Uuid> = storage
+            .lineage
+            .get_fork_progress()
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.conversation_id)
+            .collect();
+        assert_eq!(left, [running.conversation_id]);
+    }
+}
@@ -0,0 +1,482 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::{FetchConfig, ImagesConfig};
+use crate::db::DbError;
+use crate::domain::{ContentType, Message};
+use crate::object_store::ObjectStore;
+use crate::repositories::{ImageStore, LineageStore};
+use crate::scheduler::{ScheduledTask, TaskError};
+use crate::services::conversation_service::ensure_not_held;
+use crate::utils::url;
+
+/// The image and thumbnail URLs of a message
+fn image_urls(message: &Message) -> Vec<&str> {
+    match &message.content {
+        ContentType::Image(image) => std::iter::once(image.image_url.as_str())
+            .chain(image.thumbnail_url.as_deref())
+            .collect(),
+        ContentType::ImageBatch(batch) => batch
+            .images
+            .iter()
+            .map(|item| item.image_url.as_str())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Outcome of one garbage collection run
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageGcReport {
+    pub deleted: usize,
+    /// Queued images a conversation referenced again; they are kept
+    pub kept: usize,
+    /// Deletions that failed; they stay queued for the next run
+    pub failed: usize,
+}
+
+/// Tracks which conversations reference which images in the bucket, and
+/// deletes images once no conversation references them. Forks copy messages,
+/// so one image can be referenced by several conversations.
+pub struct ImageService {
+    image_repo: Arc<dyn ImageStore>,
+    lineage_repo: Arc<dyn LineageStore>,
+    object_store: Arc<dyn ObjectStore>,
+    config: ImagesConfig,
+    allow_private_networks: bool,
+}
+
+impl ImageService {
+    pub fn new(
+        image_repo: Arc<dyn ImageStore>,
+        lineage_repo: Arc<dyn LineageStore>,
+        object_store: Arc<dyn ObjectStore>,
+        config: ImagesConfig,
+    ) -> Self {
+        Self {
+            image_repo,
+            lineage_repo,
+            object_store,
+            config,
+            allow_private_networks: false,
+        }
+    }
+
+    /// Accept image URLs on internal hosts if `fetch.allow_private_networks`
+    pub fn with_fetch_config(mut self, fetch: &FetchConfig) -> Self {
+        self.allow_private_networks = fetch.allow_private_networks;
+        self
+    }
+
+    /// Keys of the images in the bucket a message links to; images hosted
+    /// elsewhere are not ours to delete and are left out
+    pub fn storage_keys(&self, message: &Message) -> BTreeSet<String> {
+        image_urls(message)
+            .into_iter()
+            .filter_map(|url| self.object_store.key_for_url(url))
+            .collect()
+    }
+
+    /// Check the image URLs of a new message: links into the bucket pass,
+    /// others need an allowed scheme, a bounded length and a host that
+    /// isn't internal, so that nothing fetching them later is pointed at
+    /// internal services
+    pub fn validate_urls(&self, message: &Message) -> Result<(), DbError> {
+        for url in image_urls(message) {
+            if self.object_store.key_for_url(url).is_some() {
+                continue;
+            }
+            url::check_url(
+                url,
+                &self.config.allowed_url_schemes,
+                self.config.max_url_length,
+                self.allow_private_networks,
+            )
+            .map_err(|e| DbError::InvalidData(format!("Invalid image URL: {}", e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// `message` with its links to images in the bucket replaced by presigned
+    /// URLs, if presigning is enabled. Meant for responses; never store the
+    /// result, as the URLs expire.
+    pub fn presign(&self, mut message: Message) -> Message {
+        if !self.config.presign_urls {
+            return message;
+        }
+
+        let expires_in = Duration::from_secs(self.config.presign_expiry_secs);
+        let sign = |url: &mut String| {
+            if let Some(key) = self.object_store.key_for_url(url) {
+                *url = self.object_store.presigned_get_url(&key, expires_in);
+            }
+        };
+        match &mut message.content {
+            ContentType::Image(image) => {
+                sign(&mut image.image_url);
+                if let Some(thumbnail_url) = &mut image.thumbnail_url {
+                    sign(thumbnail_url);
+                }
+            }
+            ContentType::ImageBatch(batch) => {
+                for item in &mut batch.images {
+                    sign(&mut item.image_url);
+                }
+            }
+            _ => {}
+        }
+
+        message
+    }
+
+    /// Record the images that messages about to be stored reference. Call it
+    /// before storing them, so no stored message links to an image that is
+    /// not referenced.
+    pub async fn track(&self, messages: &[Message]) -> Result<(), DbError> {
+        let mut keys: BTreeMap<Uuid, BTreeSet<String>> = BTreeMap::new();
+        for message in messages {
+            let message_keys = self.storage_keys(message);
+            if !message_keys.is_empty() {
+                keys.entry(message.conversation_id)
+                    .or_default()
+                    .extend(message_keys);
+            }
+        }
+
+        for (conversation_id, keys) in keys {
+            let keys: Vec<String> = keys.into_iter().collect();
+            self.image_repo
+                .add_image_refs(conversation_id, &keys)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Release the images of a deleted conversation. They are queued for
+    /// deletion before the references go, so an interrupted call leaks
+    /// nothing; garbage collection skips the ones still referenced elsewhere.
+    /// The images of a conversation under legal hold are never released.
+    pub async fn release_conversation(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        ensure_not_held(self.lineage_repo.as_ref(), conversation_id).await?;
+        let keys = self.image_repo.get_image_keys(conversation_id).await?;
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        self.image_repo.queue_image_deletions(&keys).await?;
+        self.image_repo
+            .delete_image_refs(conversation_id, &keys)
+            .await
+    }
+
+    /// Delete queued images that no conversation references, up to the
+    /// configured batch size
+    pub async fn collect_garbage(&self) -> Result<ImageGcReport, DbError> {
+        let mut report = ImageGcReport::default();
+        let queued = self
+            .image_repo
+            .get_pending_image_deletions(self.config.gc_batch_size as i32)
+            .await?;
+
+        for key in queued {
+            if self.image_repo.is_image_referenced(&key).await? {
+                report.kept += 1;
+            } else if let Err(e) = self.object_store.delete_object(&key).await {
+                tracing::warn!("Failed to delete image {}: {}", key, e);
+                report.failed += 1;
+                continue;
+            } else {
+                report.deleted += 1;
+            }
+            self.image_repo.remove_pending_image_deletion(&key).await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Reference the images of every stored message. Idempotent; scans every
+    /// message, so run it once before enabling garbage collection on a
+    /// database that predates image tracking. Returns the references written.
+    pub async fn backfill_refs(&self, page_size: i32) -> Result<usize, DbError> {
+        let mut messages = self.lineage_repo.scan_messages(page_size).await?;
+
+        let mut indexed = 0;
+        while let Some(message) = messages.next().await {
+            let message = message?;
+            let keys: Vec<String> = self.storage_keys(&message).into_iter().collect();
+            if !keys.is_empty() {
+                self.image_repo
+                    .add_image_refs(message.conversation_id, &keys)
+                    .await?;
+                indexed += keys.len();
+            }
+        }
+
+        Ok(indexed)
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for ImageService {
+    fn name(&self) -> &'static str {
+        "image_gc"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let report = self.collect_garbage().await?;
+        tracing::debug!(
+            "Image GC deleted {}, kept {}, failed {}",
+            report.deleted,
+            report.kept,
+            report.failed
+        );
+        if report.failed > 0 {
+            return Err(format!("failed to delete {} images", report.failed).into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        Conversation, ImageBatchContent, ImageBatchItem, ImageContent, MessageRole,
+    };
+    use crate::object_store::ObjectStoreError;
+    use crate::repositories::Storage;
+    use crate::utils::{compute_lineage, new_message_id};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    /// Serves `s3://bucket/...` URLs and records deletions
+    #[derive(Default)]
+    struct RecordingStore {
+        deleted: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl ObjectStore for RecordingStore {
+        fn key_for_url(&self, url: &str) -> Option<String> {
+            url.strip_prefix("s3://bucket/").map(str::to_string)
+        }
+
+        fn presigned_get_url(&self, key: &str, expires_in: Duration) -> String {
+            format!("https://signed/{}?expires={}", key, expires_in.as_secs())
+        }
+
+        async fn put_object(
+            &self,
+            _key: &str,
+            _content_type: &str,
+            _body: Vec<u8>,
+        ) -> Result<(), ObjectStoreError> {
+            Ok(())
+        }
+
+        async fn get_object(&self, key: &str) -> Result<Vec<u8>, ObjectStoreError> {
+            Err(ObjectStoreError::Request(format!("{} not found", key)))
+        }
+
+        async fn delete_object(&self, key: &str) -> Result<(), ObjectStoreError> {
+            self.deleted.lock().unwrap().push(key.to_string());
+            Ok(())
+        }
+
+        async fn check(&self) -> Result<(), ObjectStoreError> {
+            Ok(())
+        }
+    }
+
+    fn config(presign_urls: bool) -> ImagesConfig {
+        ImagesConfig {
+            gc_enabled: true,
+            gc_interval_secs: 60,
+            gc_batch_size: 100,
+            presign_urls,
+            presign_expiry_secs: 600,
+            allowed_url_schemes: vec!["https".to_string()],
+            max_url_length: 128,
+        }
+    }
+
+    fn image_message(conversation: &Conversation, content: ContentType) -> Message {
+        let root = &conversation.root_message;
+        let message_id = new_message_id();
+        Message {
+            conversation_id: conversation.conversation_id,
+            message_id,
+            parent_message_id: Some(root.message_id),
+            role: MessageRole::Assistant,
+            content,
+            content_metadata: HashMap::new(),
+            lineage: compute_lineage(&root.lineage, message_id),
+            created_at: chrono::Utc::now(),
+            created_by: "demo".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_images_are_deleted_once_unreferenced() {
+        let storage = Storage::memory();
+        let object_store = Arc::new(RecordingStore::default());
+        let service = ImageService::new(
+            storage.images.clone(),
+            storage.lineage.clone(),
+            object_store.clone(),
+            config(false),
+        );
+
+        let source = Conversation::new("Cats".to_string(), "demo".to_string());
+        let fork = Conversation::new("Cats, forked".to_string(), "demo".to_string());
+        let cat = ContentType::Image(ImageContent {
+            image_url: "s3://bucket/cat.png".to_string(),
+            thumbnail_url: Some("s3://bucket/cat_thumb.png".to_string()),
+            width: None,
+            height: None,
+            mime_type: None,
+            size_bytes: None,
+        });
+        let batch = ContentType::ImageBatch(ImageBatchContent {
+            images: vec![
+                ImageBatchItem {
+                    image_url: "s3://bucket/dog.png".to_string(),
+                    prompt: None,
+                    model: None,
+                },
+                ImageBatchItem {
+                    image_url: "https://elsewhere.example/fox.png".to_string(),
+                    prompt: None,
+                    model: None,
+                },
+            ],
+        });
+        service
+            .track(&[
+                image_message(&source, cat.clone()),
+                image_message(&source, batch),
+                // The fork copied the cat
+                image_message(&fork, cat),
+            ])
+            .await
+            .unwrap();
+
+        service
+            .release_conversation(source.conversation_id)
+            .await
+            .unwrap();
+        let report = service.collect_garbage().await.unwrap();
+        assert_eq!(
+            report,
+            ImageGcReport {
+                deleted: 1,
+                kept: 2,
+                failed: 0
+            }
+        );
+        assert_eq!(*object_store.deleted.lock().unwrap(), vec!["dog.png"]);
+
+        service
+            .release_conversation(fork.conversation_id)
+            .await
+            .unwrap();
+        service.collect_garbage().await.unwrap();
+        let mut deleted = object_store.deleted.lock().unwrap().clone();
+        deleted.sort();
+        assert_eq!(deleted, vec!["cat.png", "cat_thumb.png", "dog.png"]);
+        assert!(
+            storage
+                .images
+                .get_pending_image_deletions(10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_presign_replaces_bucket_links_only() {
+        let storage = Storage::memory();
+        let service = ImageService::new(
+            storage.images.clone(),
+            storage.lineage.clone(),
+            Arc::new(RecordingStore::default()),
+            config(true),
+        );
+        let conversation = Conversation::new("Cats".to_string(), "demo".to_string());
+        let message = image_message(
+            &conversation,
+            ContentType::Image(ImageContent {
+                image_url: "s3://bucket/cat.png".to_string(),
+                thumbnail_url: Some("https://elsewhere.example/cat.png".to_string()),
+                width: None,
+                height: None,
+                mime_type: None,
+                size_bytes: None,
+            }),
+        );
+
+        let ContentType::Image(image) = service.presign(message).content else {
+            panic!("not an image");
+        };
+        assert_eq!(image.image_url, "https://signed/cat.png?expires=600");
+        assert_eq!(
+            image.thumbnail_url.as_deref(),
+            Some("https://elsewhere.example/cat.png")
+        );
+    }
+
+    #[test]
+    fn test_image_urls_are_validated() {
+        let storage = Storage::memory();
+        let service = ImageService::new(
+            storage.images.clone(),
+            storage.lineage.clone(),
+            Arc::new(RecordingStore::default()),
+            config(false),
+        );
+        let conversation = Conversation::new("Cats".to_string(), "demo".to_string());
+        let check = |service: &ImageService, url: &str| {
+            let message = image_message(
+                &conversation,
+                ContentType::ImageBatch(ImageBatchContent {
+                    images: vec![ImageBatchItem {
+                        image_url: url.to_string(),
+                        prompt: None,
+                        model: None,
+                    }],
+                }),
+            );
+            service.validate_urls(&message)
+        };
+
+        // Links into the bucket pass whatever their scheme
+        assert!(check(&service, "s3://bucket/cat.png").is_ok());
+        assert!(check(&service, "https://elsewhere.example/cat.png").is_ok());
+        for refused in [
+            "http://elsewhere.example/cat.png",
+            "https://169.254.169.254/latest/meta-data/",
+            "https://[::1]/cat.png",
+            "https://localhost:8080/cat.png",
+            &format!("https://elsewhere.example/{}.png", "a".repeat(128)),
+        ] {
+            assert!(
+                matches!(check(&service, refused), Err(DbError::InvalidData(_))),
+                "{}",
+                refused
+            );
+        }
+
+        let service = service.with_fetch_config(&FetchConfig {
+            allow_private_networks: true,
+            max_response_bytes: 1024,
+        });
+        assert!(check(&service, "https://localhost:8080/cat.png").is_ok());
+    }
+}
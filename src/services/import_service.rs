@@ -0,0 +1,481 @@
+use crate::config::AppConfig;
+use crate::db::{ConversationTitleRow, DbError};
+use crate::domain::{Branch, ConversationEvent, EventKind, NativeExport, Share};
+use crate::repositories::{BranchStore, LineageStore, ShareStore};
+use crate::services::ImageService;
+use crate::services::conversation_service::count_new_messages;
+use crate::utils::chatgpt::{ChatGptConversation, ImportedConversation, convert_conversation};
+use crate::utils::content_hash::dedup_identical_siblings;
+use crate::utils::native::convert_export;
+use crate::utils::pii::PiiScrubber;
+use crate::utils::validate_lineage_depth;
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+pub struct ImportService {
+    lineage_repo: Arc<dyn LineageStore>,
+    branch_repo: Arc<dyn BranchStore>,
+    share_repo: Arc<dyn ShareStore>,
+    app_config: AppConfig,
+    pii: Arc<PiiScrubber>,
+    images: Arc<ImageService>,
+}
+
+impl ImportService {
+    pub fn new(
+        lineage_repo: Arc<dyn LineageStore>,
+        branch_repo: Arc<dyn BranchStore>,
+        share_repo: Arc<dyn ShareStore>,
+        app_config: AppConfig,
+        pii: Arc<PiiScrubber>,
+        images: Arc<ImageService>,
+    ) -> Self {
+        Self {
+            lineage_repo,
+            branch_repo,
+            share_repo,
+            app_config,
+            pii,
+            images,
+        }
+    }
+
+    /// Import the conversations of a ChatGPT `conversations.json` export.
+    /// Every conversation is converted and validated before anything is written.
+    /// With `dedup`, identical sibling messages are stored once.
+    pub async fn import_chatgpt(
+        &self,
+        conversations: &[ChatGptConversation],
+        created_by: String,
+        dedup: bool,
+    ) -> Result<Vec<ImportedConversation>, DbError> {
+        let mut imported: Vec<ImportedConversation> = conversations
+            .iter()
+            .map(|source| convert_conversation(source, &created_by))
+            .collect();
+        if self.pii.scrub_on_write() {
+            for conversation in &mut imported {
+                for message in &mut conversation.messages {
+                    self.pii.scrub_message(message);
+                }
+            }
+        }
+        if dedup {
+            imported.iter_mut().for_each(dedup_conversation);
+        }
+
+        for conversation in &imported {
+            for message in &conversation.messages {
+                validate_lineage_depth(&message.lineage, self.app_config.max_lineage_depth)?;
+            }
+            self.ensure_within_limits(conversation)?;
+        }
+
+        for conversation in &imported {
+            self.store(conversation).await?;
+        }
+
+        Ok(imported)
+    }
+
+    /// Import a native export as a new conversation owned by `created_by`,
+    /// equivalent to the exported one. Shares in the export are granted
+    /// again, by `created_by`.
+    pub async fn import_native(
+        &self,
+        export: &NativeExport,
+        created_by: String,
+    ) -> Result<ImportedConversation, DbError> {
+        if export.messages.len() > self.app_config.max_messages_per_conversation {
+            return Err(DbError::LimitExceeded(format!(
+                "Export has {} messages, more than the {} allowed",
+                export.messages.len(),
+                self.app_config.max_messages_per_conversation
+            )));
+        }
+
+        let mut imported = convert_export(export, &created_by, self.app_config.max_lineage_depth)?;
+        self.ensure_within_limits(&imported)?;
+        for message in &mut imported.messages {
+            self.images.validate_urls(message)?;
+            if self.pii.scrub_on_write() {
+                self.pii.scrub_message(message);
+            }
+        }
+
+        self.store(&imported).await?;
+        let shares = export.shares.iter().flatten();
+        for share in shares.filter(|share| share.shared_with != created_by) {
+            let share = Share {
+                conversation_id: imported.conversation.conversation_id,
+                shared_by: created_by.clone(),
+                ..share.clone()
+            };
+            self.share_repo.insert_share(&share).await?;
+        }
+
+        Ok(imported)
+    }
+
+    /// Fail if the conversation has more messages, or a message more
+    /// replies, than appending to it would allow
+    fn ensure_within_limits(&self, conversation: &ImportedConversation) -> Result<(), DbError> {
+        let messages = conversation.messages.len();
+        if messages > self.app_config.max_messages_per_conversation {
+            return Err(DbError::LimitExceeded(format!(
+                "Conversation {:?} has {} messages, more than the {} allowed",
+                conversation.conversation.title().unwrap_or_default(),
+                messages,
+                self.app_config.max_messages_per_conversation
+            )));
+        }
+
+        let mut replies: HashMap<Uuid, usize> = HashMap::new();
+        for parent_id in conversation
+            .messages
+            .iter()
+            .filter_map(|m| m.parent_message_id)
+        {
+            *replies.entry(parent_id).or_default() += 1;
+        }
+        if let Some((parent_id, replies)) = replies
+            .into_iter()
+            .find(|&(_, replies)| replies > self.app_config.max_children_per_message)
+        {
+            return Err(DbError::LimitExceeded(format!(
+                "Message {} has {} replies, more than the {} allowed",
+                parent_id, replies, self.app_config.max_children_per_message
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn store(&self, conversation: &ImportedConversation) -> Result<(), DbError> {
+        self.images.track(&conversation.messages).await?;
+        let metadata = conversation.conversation.metadata();
+        for chunk in conversation.messages.chunks(self.app_config.max_batch_size) {
+            let events = chunk
+                .iter()
+                .map(|message| {
+                    let cid = message.conversation_id;
+                    if message.is_root() {
+                        ConversationEvent::new(cid, EventKind::MetadataUpdated, &metadata)
+                    } else {
+                        ConversationEvent::new(cid, EventKind::MessageCreated, message)
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.lineage_repo
+                .insert_messages_with_events(chunk, &events)
+                .await?;
+        }
+        count_new_messages(
+            self.lineage_repo.as_ref(),
+            conversation.conversation.conversation_id,
+            &conversation.messages,
+        )
+        .await?;
+        for branch in &conversation.branches {
+            self.branch_repo.insert_branch(branch).await?;
+        }
+        if let Some(entry) = ConversationTitleRow::from_conversation(&conversation.conversation) {
+            self.lineage_repo.upsert_conversation_title(&entry).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Store identical sibling messages once. Branches ending at a dropped
+/// duplicate end at the message it was merged into instead; of several
+/// branches left ending at the same message, one is kept, "main" if present.
+fn dedup_conversation(conversation: &mut ImportedConversation) {
+    let (messages, merged) = dedup_identical_siblings(std::mem::take(&mut conversation.messages));
+    conversation.messages = messages;
+    if merged.is_empty() {
+        return;
+    }
+
+    let mut branches: Vec<Branch> = Vec::new();
+    for mut branch in std::mem::take(&mut conversation.branches) {
+        if let Some(&leaf) = merged.get(&branch.leaf_message_id) {
+            branch.leaf_message_id = leaf;
+        }
+        match branches
+            .iter_mut()
+            .find(|b| b.leaf_message_id == branch.leaf_message_id)
+        {
+            Some(existing) if branch.branch_name == "main" => *existing = branch,
+            Some(_) => {}
+            None => branches.push(branch),
+        }
+    }
+    conversation.branches = branches;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use crate::config::ExportsConfig;
+    use crate::domain::{ContentType, Message, Permission};
+    use crate::object_store::S3ObjectStore;
+    use crate::object_store::memory::MemoryObjectStore;
+    use crate::repositories::Storage;
+    use crate::services::ExportService;
+    use crate::test_support::ConversationBuilder;
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn test_import_of_a_native_export_reproduces_the_tree() {
+        let storage = Storage::memory();
+        let images = Arc::new(ImageService::new(
+            storage.images.clone(),
+            storage.lineage.clone(),
+            Arc::new(S3ObjectStore::new(Settings::default().s3)),
+            Settings::default().images,
+        ));
+        let exports = ExportService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.exports.clone(),
+            storage.jobs.clone(),
+            storage.shares.clone(),
+            Arc::new(MemoryObjectStore::default()),
+            ExportsConfig {
+                download_expiry_secs: 600,
+            },
+        );
+        let imports = ImportService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            Settings::default().app,
+            Arc::new(PiiScrubber::new(&Settings::default().pii)),
+            images,
+        );
+
+        let mut fixture = ConversationBuilder::new("Logo")
+            .with_messages(2, 2)
+            .with_branch("main", &[0, 1])
+            .with_branch("alt", &[1])
+            .build();
+        let private = fixture.message(&[1]).message_id;
+        let main = fixture.branch("main").branch_id;
+        if let ContentType::Metadata(metadata) = &mut fixture.messages[0].content {
+            metadata.published_branch_id = Some(main);
+            metadata.private_message_ids = vec![private];
+        }
+        fixture.persist(&storage).await.unwrap();
+        let cid = fixture.conversation_id();
+        storage
+            .shares
+            .insert_share(&Share {
+                conversation_id: cid,
+                shared_with: "user_c".to_string(),
+                permission: Permission::Branch,
+                shared_at: fixture.root().created_at,
+                shared_by: "user_a".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let exported = exports
+            .export_native(cid, Some("user_a"), true)
+            .await
+            .unwrap();
+        let body = serde_json::to_string(&exported).unwrap();
+        let imported = imports
+            .import_native(&serde_json::from_str(&body).unwrap(), "user_a".to_string())
+            .await
+            .unwrap();
+        let new_cid = imported.conversation.conversation_id;
+        assert_ne!(new_cid, cid);
+
+        let reexported = exports
+            .export_native(new_cid, Some("user_a"), true)
+            .await
+            .unwrap();
+        assert_eq!(reexported.messages.len(), fixture.messages.len());
+        assert_eq!(shape(&reexported), shape(&exported));
+        // The import is logged like messages written one by one
+        let events = storage.lineage.get_events(new_cid, 0, 100).await.unwrap();
+        assert_eq!(events.len(), fixture.messages.len());
+        assert_eq!(events[0].kind, EventKind::MetadataUpdated);
+        assert!(
+            events[1..]
+                .iter()
+                .all(|e| e.kind == EventKind::MessageCreated)
+        );
+
+        // Others get neither the private message, its replies nor the branch
+        // ending below it
+        let shared = exports
+            .export_native(cid, Some("user_c"), false)
+            .await
+            .unwrap();
+        assert_eq!(shared.messages.len(), fixture.messages.len() - 3);
+        assert_eq!(shared.branches.len(), 1);
+        assert!(shared.shares.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_chatgpt_imports_are_held_to_the_conversation_limits() {
+        let storage = Storage::memory();
+        let imports = ImportService::new(
+            storage.lineage.clone(),
+            storage.branches.clone(),
+            storage.shares.clone(),
+            AppConfig {
+                max_children_per_message: 2,
+                max_messages_per_conversation: 5,
+                ..Settings::default().app
+            },
+            Arc::new(PiiScrubber::new(&Settings::default().pii)),
+            Arc::new(ImageService::new(
+                storage.images.clone(),
+                storage.lineage.clone(),
+                Arc::new(S3ObjectStore::new(Settings::default().s3)),
+                Settings::default().images,
+            )),
+        );
+        // A question followed by `parents.len()` answers, each replying to
+        // the question or, with `Some(i)`, to the i-th answer
+        let conversation = |title: &str, parents: &[Option<usize>]| -> ChatGptConversation {
+            let mut mapping = json!({
+                "root": { "message": null, "parent": null, "children": ["q"] },
+                "q": {
+                    "message": {
+                        "id": "q",
+                        "author": { "role": "user" },
+                        "content": { "content_type": "text", "parts": ["Draw a fox"] }
+                    },
+                    "parent": "root",
+                    "children": []
+                }
+            });
+            for (i, parent) in parents.iter().enumerate() {
+                let (id, parent) = (
+                    format!("a{}", i),
+                    parent.map_or("q".to_string(), |p| format!("a{}", p)),
+                );
+                mapping[&id] = json!({
+                    "message": {
+                        "id": id,
+                        "author": { "role": "assistant" },
+                        "content": { "content_type": "text", "parts": [format!("Fox {}", i)] }
+                    },
+                    "parent": parent,
+                    "children": []
+                });
+                mapping[&parent]["children"]
+                    .as_array_mut()
+                    .unwrap()
+                    .push(json!(id));
+            }
+            serde_json::from_value(json!({ "title": title, "mapping": mapping })).unwrap()
+        };
+
+        let fits = conversation("Fits", &[None, None]);
+        let wide = conversation("Wide", &[None, None, None]);
+        let long = conversation("Long", &[None, Some(0), Some(1), Some(2)]);
+        for (too_large, reason) in [(wide, "3 replies"), (long, "6 messages")] {
+            let err = imports
+                .import_chatgpt(&[fits.clone(), too_large], "user_a".to_string(), false)
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(&err, DbError::LimitExceeded(message) if message.contains(reason)),
+                "{}",
+                err
+            );
+        }
+        // Nothing is written when one conversation is refused
+        assert!(
+            storage
+                .lineage
+                .get_conversation_titles("user_a")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        imports
+            .import_chatgpt(&[fits], "user_a".to_string(), false)
+            .await
+            .unwrap();
+    }
+
+    /// An export with every message named by the texts on its path from the
+    /// root, so exports of equivalent trees under different IDs compare equal
+    fn shape(export: &NativeExport) -> Value {
+        let by_id: HashMap<Uuid, &Message> =
+            export.messages.iter().map(|m| (m.message_id, m)).collect();
+        let path = |id: &Uuid| -> Vec<String> {
+            by_id[id]
+                .lineage
+                .iter()
+                .map(|id| match &by_id[id].content {
+                    ContentType::Text(content) => content.text.clone(),
+                    ContentType::Metadata(metadata) => metadata.title.clone(),
+                    other => panic!("unexpected content {:?}", other),
+                })
+                .collect()
+        };
+
+        let mut messages: Vec<Value> = export
+            .messages
+            .iter()
+            .map(|m| {
+                json!({
+                    "path": path(&m.message_id),
+                    "role": m.role,
+                    "content_metadata": m.content_metadata,
+                    "created_at": m.created_at,
+                    "created_by": m.created_by,
+                })
+            })
+            .collect();
+        messages.sort_by_key(Value::to_string);
+        let mut branches: Vec<Value> = export
+            .branches
+            .iter()
+            .map(|b| {
+                json!({
+                    "name": b.branch_name,
+                    "slug": b.slug,
+                    "leaf": path(&b.leaf_message_id),
+                    "created_at": b.created_at,
+                    "is_active": b.is_active,
+                })
+            })
+            .collect();
+        branches.sort_by_key(Value::to_string);
+        let root = export.messages.iter().find(|m| m.is_root()).unwrap();
+        let ContentType::Metadata(metadata) = &root.content else {
+            panic!("root without metadata");
+        };
+        let published = metadata.published_branch_id.map(|id| {
+            let branch = export.branches.iter().find(|b| b.branch_id == id).unwrap();
+            branch.branch_name.clone()
+        });
+        let private: Vec<Vec<String>> = metadata.private_message_ids.iter().map(path).collect();
+        let shares: Vec<Value> = export
+            .shares
+            .iter()
+            .flatten()
+            .map(|s| json!({"shared_with": s.shared_with, "permission": s.permission}))
+            .collect();
+
+        json!({
+            "messages": messages,
+            "branches": branches,
+            "published": published,
+            "private": private,
+            "shares": shares,
+        })
+    }
+}
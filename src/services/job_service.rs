@@ -0,0 +1,381 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+use crate::config::JobsConfig;
+use crate::db::DbError;
+use crate::domain::{Job, JobKind, JobStatus};
+use crate::repositories::JobStore;
+use crate::scheduler::{ScheduledTask, TaskError};
+
+/// The work behind one kind of job
+#[async_trait]
+pub trait JobRunner: Send + Sync {
+    /// Do the work `job` asks for, reporting progress and checking for
+    /// cancellation through `ctx`. An interrupted job is run again with its
+    /// saved progress, so this must be safe to repeat.
+    async fn run(&self, job: &Job, ctx: &JobContext) -> Result<(), DbError>;
+}
+
+/// A running job's link back to its persisted state
+pub struct JobContext {
+    job_id: Uuid,
+    store: Arc<dyn JobStore>,
+    job: Mutex<Job>,
+    token: CancellationToken,
+    cancelled: AtomicBool,
+}
+
+impl JobContext {
+    /// Add `delta` to a progress counter and persist it
+    pub async fn add_progress(&self, counter: &str, delta: i64) -> Result<(), DbError> {
+        let job = self.touch(|job| *job.progress.entry(counter.to_string()).or_default() += delta);
+
+        self.store.save_job(&job).await
+    }
+
+    /// Whether the job was cancelled, through this instance or another.
+    /// Runners check it between items and return `Ok` once it is true.
+    pub async fn is_cancelled(&self) -> Result<bool, DbError> {
+        if self.token.is_cancelled() || self.store.get_job(self.job_id).await?.cancel_requested {
+            self.cancelled.store(true, Ordering::Release);
+        }
+
+        Ok(self.cancelled.load(Ordering::Acquire))
+    }
+
+    /// Refresh `updated_at`, so the job isn't taken for interrupted
+    async fn heartbeat(&self) -> Result<(), DbError> {
+        let job = self.touch(|_| {});
+
+        self.store.save_job(&job).await
+    }
+
+    fn touch(&self, f: impl FnOnce(&mut Job)) -> Job {
+        let mut job = self.job.lock().expect("job lock poisoned");
+        f(&mut job);
+        job.updated_at = Utc::now();
+        job.clone()
+    }
+}
+
+/// Starts, tracks and cancels background jobs. Job state lives in the job
+/// store, so any instance can report on a job; jobs whose instance stopped
+/// updating them are resumed by the recovery task.
+pub struct JobService {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    store: Arc<dyn JobStore>,
+    runners: HashMap<JobKind, Arc<dyn JobRunner>>,
+    config: JobsConfig,
+    /// Cancellation handles of the jobs running in this instance
+    local: Mutex<HashMap<Uuid, CancellationToken>>,
+}
+
+impl JobService {
+    pub fn new(
+        store: Arc<dyn JobStore>,
+        runners: HashMap<JobKind, Arc<dyn JobRunner>>,
+        config: JobsConfig,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                store,
+                runners,
+                config,
+                local: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Record a new job and start it in the background. Returns the job as
+    /// first saved.
+    pub async fn start(
+        &self,
+        kind: JobKind,
+        user_id: String,
+        params: serde_json::Value,
+    ) -> Result<Job, DbError> {
+        if !self.inner.runners.contains_key(&kind) {
+            return Err(DbError::InvalidData(format!(
+                "No runner for {} jobs",
+                kind.as_str()
+            )));
+        }
+
+        let job = Job::new(kind, user_id, params);
+        self.inner.store.save_job(&job).await?;
+        Inner::spawn(&self.inner, job.clone());
+
+        Ok(job)
+    }
+
+    pub async fn get_job(&self, job_id: Uuid) -> Result<Job, DbError> {
+        self.inner.store.get_job(job_id).await
+    }
+
+    /// A user's most recent jobs, newest first
+    pub async fn get_jobs_for_user(
+        &self,
+        user_id: &str,
+        limit: usize,
+    ) -> Result<Vec<Job>, DbError> {
+        self.inner
+            .store
+            .get_jobs_for_user(user_id, limit as i32)
+            .await
+    }
+
+    /// Ask a running job to stop. It becomes `cancelled` once its runner
+    /// notices, at its next check.
+    pub async fn cancel(&self, job_id: Uuid) -> Result<Job, DbError> {
+        let mut job = self.inner.store.get_job(job_id).await?;
+        if job.is_finished() {
+            return Err(DbError::Conflict(format!(
+                "Job {} is already {}",
+                job_id,
+                job.status.as_str()
+            )));
+        }
+
+        self.inner.store.request_cancel(job_id).await?;
+        if let Some(token) = self.inner.local().get(&job_id) {
+            token.cancel();
+        }
+        job.cancel_requested = true;
+
+        Ok(job)
+    }
+
+    /// Resume running jobs that haven't been updated for `stale_after_secs`
+    /// because the instance running them stopped. Returns how many were resumed.
+    pub async fn recover(&self) -> Result<usize, DbError> {
+        let stale_after = chrono::Duration::seconds(self.inner.config.stale_after_secs as i64);
+        let stale_before = Utc::now() - stale_after;
+        let mut resumed = 0;
+
+        for mut job in self.inner.store.get_running_jobs().await? {
+            if job.updated_at >= stale_before || self.inner.local().contains_key(&job.job_id) {
+                continue;
+            }
+
+            tracing::info!(
+                "Resuming interrupted {} job {}",
+                job.kind.as_str(),
+                job.job_id
+            );
+            // Claim it, so other instances' recovery leaves it alone
+            job.updated_at = Utc::now();
+            self.inner.store.save_job(&job).await?;
+            Inner::spawn(&self.inner, job);
+            resumed += 1;
+        }
+
+        Ok(resumed)
+    }
+}
+
+impl Inner {
+    fn local(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, CancellationToken>> {
+        self.local.lock().expect("jobs lock poisoned")
+    }
+
+    fn spawn(inner: &Arc<Inner>, job: Job) {
+        let token = CancellationToken::new();
+        inner.local().insert(job.job_id, token.clone());
+
+        let inner = inner.clone();
+        tokio::spawn(async move {
+            let job_id = job.job_id;
+            inner.execute(job, token).await;
+            inner.local().remove(&job_id);
+        });
+    }
+
+    async fn execute(&self, job: Job, token: CancellationToken) {
+        let ctx = Arc::new(JobContext {
+            job_id: job.job_id,
+            store: self.store.clone(),
+            job: Mutex::new(job.clone()),
+            token,
+            cancelled: AtomicBool::new(false),
+        });
+
+        let result = match ctx.is_cancelled().await {
+            // Cancelled while it waited to be resumed
+            Ok(true) => Ok(()),
+            Ok(false) => {
+                let heartbeat = tokio::spawn({
+                    let ctx = ctx.clone();
+                    let every = Duration::from_secs((self.config.stale_after_secs / 3).max(1));
+                    async move {
+                        loop {
+                            tokio::time::sleep(every).await;
+                            if let Err(e) = ctx.heartbeat().await {
+                                tracing::warn!("Failed to refresh job {}: {}", ctx.job_id, e);
+                            }
+                        }
+                    }
+                });
+                let result = match self.runners.get(&job.kind) {
+                    Some(runner) => runner.run(&job, &ctx).await,
+                    None => Err(DbError::InvalidData(format!(
+                        "No runner for {} jobs",
+                        job.kind.as_str()
+                    ))),
+                };
+                heartbeat.abort();
+                result
+            }
+            Err(e) => Err(e),
+        };
+
+        let mut finished = ctx.touch(|_| {});
+        finished.finished_at = Some(finished.updated_at);
+        match result {
+            Ok(()) if ctx.cancelled.load(Ordering::Acquire) => {
+                finished.status = JobStatus::Cancelled;
+            }
+            Ok(()) => finished.status = JobStatus::Completed,
+            Err(e) => {
+                tracing::warn!(
+                    "{} job {} for {} failed: {}",
+                    finished.kind.as_str(),
+                    finished.job_id,
+                    finished.user_id,
+                    e
+                );
+                finished.status = JobStatus::Failed;
+                finished.error = Some(e.to_string());
+            }
+        }
+        if let Err(e) = self.store.save_job(&finished).await {
+            // Still marked as running, so recovery runs it again
+            tracing::warn!("Failed to record the end of job {}: {}", finished.job_id, e);
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for JobService {
+    fn name(&self) -> &'static str {
+        "job_recovery"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let resumed = self.recover().await?;
+        if resumed > 0 {
+            tracing::info!("Resumed {} interrupted jobs", resumed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::Storage;
+
+    /// Counts up until cancelled
+    struct Ticker;
+
+    #[async_trait]
+    impl JobRunner for Ticker {
+        async fn run(&self, _job: &Job, ctx: &JobContext) -> Result<(), DbError> {
+            while !ctx.is_cancelled().await? {
+                ctx.add_progress("ticks", 1).await?;
+                tokio::task::yield_now().await;
+            }
+            Ok(())
+        }
+    }
+
+    fn service(storage: &Storage) -> JobService {
+        let ticker: Arc<dyn JobRunner> = Arc::new(Ticker);
+        JobService::new(
+            storage.jobs.clone(),
+            HashMap::from([(JobKind::UserCleanup, ticker)]),
+            JobsConfig {
+                stale_after_secs: 300,
+                recovery_interval_secs: 60,
+            },
+        )
+    }
+
+    async fn wait_for(jobs: &JobService, job_id: Uuid) -> Job {
+        loop {
+            let job = jobs.get_job(job_id).await.unwrap();
+            if job.is_finished() {
+                return job;
+            }
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_jobs_stop_and_keep_their_progress() {
+        let storage = Storage::memory();
+        let jobs = service(&storage);
+
+        let job = jobs
+            .start(
+                JobKind::UserCleanup,
+                "user_a".to_string(),
+                serde_json::json!({}),
+            )
+            .await
+            .unwrap();
+        assert!(jobs.cancel(job.job_id).await.unwrap().cancel_requested);
+
+        let job = wait_for(&jobs, job.job_id).await;
+        assert_eq!(job.status, JobStatus::Cancelled);
+        assert!(job.finished_at.is_some());
+        assert_eq!(
+            jobs.get_jobs_for_user("user_a", 10).await.unwrap()[0].job_id,
+            job.job_id
+        );
+        assert!(matches!(
+            jobs.cancel(job.job_id).await,
+            Err(DbError::Conflict(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_recovery_resumes_only_stale_jobs() {
+        let storage = Storage::memory();
+        let jobs = service(&storage);
+
+        let mut stale = Job::new(
+            JobKind::UserCleanup,
+            "user_a".to_string(),
+            serde_json::json!({}),
+        );
+        stale.updated_at = Utc::now() - chrono::Duration::hours(1);
+        stale.progress.insert("ticks".to_string(), 5);
+        let fresh = Job::new(
+            JobKind::UserCleanup,
+            "user_a".to_string(),
+            serde_json::json!({}),
+        );
+        storage.jobs.save_job(&stale).await.unwrap();
+        storage.jobs.save_job(&fresh).await.unwrap();
+
+        assert_eq!(jobs.recover().await.unwrap(), 1);
+        // Already running here, so not resumed twice
+        assert_eq!(jobs.recover().await.unwrap(), 0);
+
+        jobs.cancel(stale.job_id).await.unwrap();
+        let resumed = wait_for(&jobs, stale.job_id).await;
+        assert_eq!(resumed.status, JobStatus::Cancelled);
+        assert!(resumed.progress["ticks"] >= 5);
+        assert!(!jobs.get_job(fresh.job_id).await.unwrap().is_finished());
+    }
+}
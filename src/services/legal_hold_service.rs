@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::LegalHold;
+use crate::repositories::{ChangeStore, LineageStore};
+use crate::scheduler::{ScheduledTask, TaskError};
+
+/// Places and releases legal holds. A held conversation can't be deleted,
+/// neither on its own nor with its creator's conversations, its images stay
+/// in the bucket, and its change feed is kept past the table's TTL.
+pub struct LegalHoldService {
+    lineage_repo: Arc<dyn LineageStore>,
+    changes: Arc<dyn ChangeStore>,
+}
+
+impl LegalHoldService {
+    pub fn new(lineage_repo: Arc<dyn LineageStore>, changes: Arc<dyn ChangeStore>) -> Self {
+        Self {
+            lineage_repo,
+            changes,
+        }
+    }
+
+    /// Put the conversation on hold. Holding a held conversation keeps its
+    /// original hold.
+    pub async fn place_hold(
+        &self,
+        conversation_id: Uuid,
+        placed_by: String,
+        reason: Option<String>,
+    ) -> Result<LegalHold, DbError> {
+        if let Some(hold) = self.lineage_repo.get_hold(conversation_id).await? {
+            return Ok(hold);
+        }
+        if self.lineage_repo.count_messages(conversation_id).await? == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        let hold = LegalHold {
+            conversation_id,
+            placed_by,
+            placed_at: Utc::now(),
+            reason,
+        };
+        self.lineage_repo.insert_hold(&hold).await?;
+        self.changes.retain_changes(conversation_id).await?;
+
+        Ok(hold)
+    }
+
+    /// Release the hold. Changes kept while it was held stay kept.
+    pub async fn release_hold(&self, conversation_id: Uuid) -> Result<(), DbError> {
+        if self.lineage_repo.get_hold(conversation_id).await?.is_none() {
+            return Err(DbError::NotFound);
+        }
+
+        self.lineage_repo.delete_hold(conversation_id).await
+    }
+
+    /// Every hold, most recent first
+    pub async fn get_holds(&self) -> Result<Vec<LegalHold>, DbError> {
+        let mut holds = self.lineage_repo.get_holds().await?;
+        holds.sort_by_key(|hold| std::cmp::Reverse((hold.placed_at, hold.conversation_id)));
+
+        Ok(holds)
+    }
+
+    /// Keep the changes recorded since the last run from expiring. Returns
+    /// how many changes were rewritten.
+    pub async fn retain_held(&self) -> Result<usize, DbError> {
+        let mut retained = 0;
+        for hold in self.lineage_repo.get_holds().await? {
+            retained += self.changes.retain_changes(hold.conversation_id).await?;
+        }
+
+        Ok(retained)
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for LegalHoldService {
+    fn name(&self) -> &'static str {
+        "legal_hold_retention"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let retained = self.retain_held().await?;
+        tracing::debug!("Kept {} changes of held conversations", retained);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Change, ChangeKind, Conversation};
+    use crate::repositories::Storage;
+    use crate::services::conversation_service::ensure_not_held;
+
+    #[tokio::test]
+    async fn test_holds_are_placed_once_listed_and_released() {
+        let storage = Storage::memory();
+        let service = LegalHoldService::new(storage.lineage.clone(), storage.changes.clone());
+        let conversation = Conversation::new("Case".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        storage
+            .lineage
+            .batch_insert_messages(std::slice::from_ref(&conversation.root_message))
+            .await
+            .unwrap();
+        storage
+            .changes
+            .insert_change(&Change::new(
+                cid,
+                ChangeKind::ConversationUpdated,
+                cid.to_string(),
+                None,
+            ))
+            .await
+            .unwrap();
+
+        let hold = service
+            .place_hold(cid, "admin".to_string(), Some("Matter 7".to_string()))
+            .await
+            .unwrap();
+        let again = service
+            .place_hold(cid, "other_admin".to_string(), None)
+            .await
+            .unwrap();
+        assert_eq!(again.placed_by, hold.placed_by);
+        assert_eq!(service.retain_held().await.unwrap(), 1);
+        assert!(matches!(
+            ensure_not_held(storage.lineage.as_ref(), cid).await,
+            Err(DbError::LegalHold(id)) if id == cid
+        ));
+
+        let holds = service.get_holds().await.unwrap();
+        assert_eq!(holds.len(), 1);
+        assert_eq!(holds[0].reason.as_deref(), Some("Matter 7"));
+
+        service.release_hold(cid).await.unwrap();
+        assert!(service.get_holds().await.unwrap().is_empty());
+        assert!(ensure_not_held(storage.lineage.as_ref(), cid).await.is_ok());
+        assert!(matches!(
+            service.release_hold(cid).await,
+            Err(DbError::NotFound)
+        ));
+        assert!(matches!(
+            service
+                .place_hold(Uuid::new_v4(), "admin".to_string(), None)
+                .await,
+            Err(DbError::NotFound)
+        ));
+    }
+}
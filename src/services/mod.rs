@@ -1,9 +1,53 @@
+pub mod access_log_service;
+pub mod analytics_service;
 pub mod branch_service;
+pub mod cdc_consumer;
+pub mod change_feed;
+pub mod cleanup_service;
+pub mod collaboration_hub;
+pub mod context_service;
+pub mod conversation_mailboxes;
 pub mod conversation_service;
+pub mod diff_service;
+pub mod export_service;
 pub mod fork_service;
+pub mod image_service;
+pub mod import_service;
+pub mod job_service;
+pub mod legal_hold_service;
+pub mod notification_service;
+pub mod preference_service;
+pub mod private_messages;
+pub mod search_service;
+pub mod seed;
 pub mod share_service;
+pub mod trending_service;
+pub mod webhook_service;
 
-pub use branch_service::BranchService;
-pub use conversation_service::ConversationService;
-pub use fork_service::ForkService;
+pub use access_log_service::{AccessLogPage, AccessLogService};
+pub use aigc_history_types::tree::MessageTree;
+pub use aigc_history_types::{ContextStrategy, ExportFormat, PresenceSignal, PresenceState};
+pub use analytics_service::{AnalyticsService, ModelUsage};
+pub use branch_service::{BranchService, ConversationSync};
+pub use cdc_consumer::CdcConsumer;
+pub use change_feed::ChangeFeed;
+pub use cleanup_service::{CleanupService, UserCleanupParams};
+pub use collaboration_hub::{CollaborationEvent, CollaborationHub};
+pub use context_service::{ContextMessage, ContextService, ConversationContext};
+pub use conversation_mailboxes::ConversationMailboxes;
+pub use conversation_service::{ConversationService, DuplicateGroup, level_filter};
+pub use diff_service::{ConversationDiff, DiffService};
+pub use export_service::{ConversationExportParams, ExportService, ExportStatus};
+pub use fork_service::{ForkGraph, ForkGraphNode, ForkOptions, ForkService};
+pub use image_service::{ImageGcReport, ImageService};
+pub use import_service::ImportService;
+pub use job_service::{JobContext, JobRunner, JobService};
+pub use legal_hold_service::LegalHoldService;
+pub use notification_service::NotificationService;
+pub use preference_service::PreferenceService;
+pub use private_messages::PrivateMessages;
+pub use search_service::{MessageMatch, SearchResults, SearchService};
+pub use seed::{DemoSeeder, SeedReport};
 pub use share_service::ShareService;
+pub use trending_service::TrendingService;
+pub use webhook_service::WebhookService;
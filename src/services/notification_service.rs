@@ -0,0 +1,168 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{Notification, NotificationKind};
+use crate::repositories::NotificationStore;
+use crate::services::PreferenceService;
+
+pub struct NotificationService {
+    notification_repo: Arc<dyn NotificationStore>,
+    preferences: Arc<PreferenceService>,
+}
+
+impl NotificationService {
+    pub fn new(
+        notification_repo: Arc<dyn NotificationStore>,
+        preferences: Arc<PreferenceService>,
+    ) -> Self {
+        Self {
+            notification_repo,
+            preferences,
+        }
+    }
+
+    /// Notify a user of another user's action. Nobody is notified of their own
+    /// actions or of kinds they opted out of, and failures are logged rather
+    /// than failing the action itself.
+    pub async fn notify(
+        &self,
+        user_id: &str,
+        kind: NotificationKind,
+        conversation_id: Uuid,
+        actor: &str,
+        entity_id: Option<Uuid>,
+    ) {
+        if user_id == actor {
+            return;
+        }
+        match self.preferences.get(user_id).await {
+            Ok(preferences) if !preferences.wants_notification(kind) => return,
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Failed to read preferences of user {}: {}", user_id, e),
+        }
+
+        let notification = Notification::new(
+            user_id.to_string(),
+            kind,
+            conversation_id,
+            actor.to_string(),
+            entity_id,
+        );
+        if let Err(e) = self
+            .notification_repo
+            .insert_notification(&notification)
+            .await
+        {
+            tracing::warn!("Failed to notify user {}: {}", user_id, e);
+        }
+    }
+
+    /// A user's notifications, newest first, each paired with whether it has been read
+    pub async fn get_notifications(
+        &self,
+        user_id: &str,
+        limit: i32,
+    ) -> Result<Vec<(Notification, bool)>, DbError> {
+        let read_up_to = self.notification_repo.get_read_up_to(user_id).await?;
+        let notifications = self
+            .notification_repo
+            .get_notifications(user_id, limit)
+            .await?;
+
+        Ok(notifications
+            .into_iter()
+            .map(|n| {
+                let read = read_up_to.is_some_and(|at| n.created_at <= at);
+                (n, read)
+            })
+            .collect())
+    }
+
+    /// Mark everything created up to `up_to` as read. The read marker never moves back.
+    pub async fn mark_read(&self, user_id: &str, up_to: DateTime<Utc>) -> Result<(), DbError> {
+        let current = self.notification_repo.get_read_up_to(user_id).await?;
+        if current.is_some_and(|at| at >= up_to) {
+            return Ok(());
+        }
+
+        self.notification_repo.set_read_up_to(user_id, up_to).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::Storage;
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_mark_read_only_moves_forward() {
+        let storage = Storage::memory();
+        let service = NotificationService::new(
+            storage.notifications,
+            Arc::new(PreferenceService::new(storage.preferences)),
+        );
+        let conversation_id = Uuid::new_v4();
+
+        service
+            .notify(
+                "owner",
+                NotificationKind::ConversationShared,
+                conversation_id,
+                "owner",
+                None,
+            )
+            .await;
+        service
+            .notify(
+                "owner",
+                NotificationKind::ConversationForked,
+                conversation_id,
+                "user_b",
+                Some(Uuid::new_v4()),
+            )
+            .await;
+
+        let notifications = service.get_notifications("owner", 10).await.unwrap();
+        assert_eq!(notifications.len(), 1);
+        assert!(!notifications[0].1);
+
+        let now = Utc::now();
+        service.mark_read("owner", now).await.unwrap();
+        service
+            .mark_read("owner", now - Duration::hours(1))
+            .await
+            .unwrap();
+
+        let notifications = service.get_notifications("owner", 10).await.unwrap();
+        assert!(notifications[0].1);
+    }
+
+    #[tokio::test]
+    async fn test_muted_kinds_are_not_delivered() {
+        let storage = Storage::memory();
+        let preferences = Arc::new(PreferenceService::new(storage.preferences));
+        let service = NotificationService::new(storage.notifications, preferences.clone());
+        let mut prefs = preferences.get("owner").await.unwrap();
+        prefs
+            .muted_notifications
+            .insert(NotificationKind::ConversationForked);
+        preferences.save(prefs).await.unwrap();
+
+        for kind in NotificationKind::ALL {
+            service
+                .notify("owner", kind, Uuid::new_v4(), "user_b", None)
+                .await;
+        }
+
+        let notifications = service.get_notifications("owner", 10).await.unwrap();
+        assert_eq!(notifications.len(), NotificationKind::ALL.len() - 1);
+        assert!(
+            notifications
+                .iter()
+                .all(|(n, _)| n.kind != NotificationKind::ConversationForked)
+        );
+    }
+}
@@ -0,0 +1,51 @@
+use chrono::Utc;
+use std::sync::Arc;
+
+use crate::db::DbError;
+use crate::domain::{EVERYONE, UserPreferences};
+use crate::repositories::PreferenceStore;
+
+/// Per-user defaults other services fall back on
+pub struct PreferenceService {
+    preference_repo: Arc<dyn PreferenceStore>,
+}
+
+impl PreferenceService {
+    pub fn new(preference_repo: Arc<dyn PreferenceStore>) -> Self {
+        Self { preference_repo }
+    }
+
+    /// A user's preferences, or the built-in defaults if they never saved any
+    pub async fn get(&self, user_id: &str) -> Result<UserPreferences, DbError> {
+        Ok(self
+            .preference_repo
+            .get_preferences(user_id)
+            .await?
+            .unwrap_or_else(|| UserPreferences::defaults(user_id)))
+    }
+
+    /// Store a user's preferences. They apply to what happens afterwards;
+    /// existing conversations, shares and branches are left alone.
+    pub async fn save(&self, mut preferences: UserPreferences) -> Result<UserPreferences, DbError> {
+        if let Some(recipient) = preferences.default_shares.keys().find(|recipient| {
+            recipient.as_str() == preferences.user_id || recipient.as_str() == EVERYONE
+        }) {
+            return Err(DbError::InvalidData(format!(
+                "{} can't be a default share recipient",
+                recipient
+            )));
+        }
+        if preferences.default_page_size == Some(0) {
+            return Err(DbError::InvalidData(
+                "default_page_size must be positive".to_string(),
+            ));
+        }
+
+        preferences.updated_at = Utc::now();
+        self.preference_repo
+            .upsert_preferences(&preferences)
+            .await?;
+
+        Ok(preferences)
+    }
+}
@@ -0,0 +1,245 @@
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{
+    Branch, BranchMoved, Change, ChangeKind, ContentType, ConversationEvent, EventKind, Message,
+    MessageMoved,
+};
+use crate::repositories::LineageStore;
+
+/// The messages of a conversation hidden from a viewer: those its owner made
+/// private, along with their replies, which continue from them. Nothing is
+/// hidden from the owner.
+///
+/// Branches and events name messages by ID only, so a reply below a private
+/// message is only known to be hidden once [`PrivateMessages::resolve`] has
+/// seen it.
+#[derive(Debug, Clone, Default)]
+pub struct PrivateMessages {
+    hidden: HashSet<Uuid>,
+    /// Replies found to be below a private message
+    below: HashSet<Uuid>,
+}
+
+impl PrivateMessages {
+    /// What `viewer` may not see of the conversation; `None` for anonymous
+    /// and embed viewers
+    pub async fn for_viewer(
+        lineage_repo: &dyn LineageStore,
+        conversation_id: Uuid,
+        viewer: Option<&str>,
+    ) -> Result<Self, DbError> {
+        let root = lineage_repo.get_root_message(conversation_id).await?;
+        Ok(Self::of(&root, viewer))
+    }
+
+    /// What `viewer` may not see of the conversation `root` belongs to
+    pub fn of(root: &Message, viewer: Option<&str>) -> Self {
+        if viewer == Some(root.created_by.as_str()) {
+            return Self::default();
+        }
+
+        match &root.content {
+            ContentType::Metadata(metadata) => Self {
+                hidden: metadata.private_message_ids.iter().copied().collect(),
+                below: HashSet::new(),
+            },
+            _ => Self::default(),
+        }
+    }
+
+    /// Whether nothing is hidden, as for the owner
+    pub fn is_empty(&self) -> bool {
+        self.hidden.is_empty()
+    }
+
+    pub fn hides(&self, message: &Message) -> bool {
+        message.lineage.iter().any(|id| self.hidden.contains(id))
+    }
+
+    /// Remember which of `messages` are hidden, so that branches and events
+    /// naming them by ID are hidden too
+    pub fn resolve(&mut self, messages: &[Message]) {
+        for message in messages {
+            if self.hides(message) {
+                self.below.insert(message.message_id);
+            }
+        }
+    }
+
+    /// Whether the message with this ID is hidden: private messages always,
+    /// their replies once resolved
+    pub fn hides_id(&self, message_id: Uuid) -> bool {
+        self.hidden.contains(&message_id) || self.below.contains(&message_id)
+    }
+
+    /// Whether the branch ends at a hidden message
+    pub fn hides_branch(&self, branch: &Branch) -> bool {
+        self.hides_id(branch.leaf_message_id)
+    }
+
+    /// The message a branch change names by ID, to resolve before redacting it
+    pub fn named_by_change(change: &Change) -> Option<Uuid> {
+        match change.kind {
+            ChangeKind::BranchUpdated | ChangeKind::BranchDeleted => change
+                .payload
+                .clone()
+                .and_then(|payload| serde_json::from_value::<Branch>(payload).ok())
+                .map(|branch| branch.leaf_message_id),
+            _ => None,
+        }
+    }
+
+    /// The messages an event names by ID, to resolve before redacting it
+    pub fn named_by_event(event: &ConversationEvent) -> Vec<Uuid> {
+        match event.kind {
+            EventKind::BranchMoved => serde_json::from_value::<BranchMoved>(event.payload.clone())
+                .map(|moved| vec![moved.from_leaf_message_id, moved.to_leaf_message_id])
+                .unwrap_or_default(),
+            EventKind::MessageMoved => {
+                serde_json::from_value::<MessageMoved>(event.payload.clone())
+                    .map(|moved| {
+                        let mut ids = vec![moved.message_id, moved.to_parent_message_id];
+                        ids.extend(moved.from_parent_message_id);
+                        ids
+                    })
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// `message` as the viewer may see it: `None` if it is hidden, and the
+    /// root without the list of private messages
+    pub fn redact(&self, mut message: Message) -> Option<Message> {
+        if self.hides(&message) {
+            return None;
+        }
+        if !self.hidden.is_empty()
+            && let ContentType::Metadata(metadata) = &mut message.content
+        {
+            metadata.private_message_ids.clear();
+        }
+
+        Some(message)
+    }
+
+    /// `change` as the viewer may see it: `None` if it carries or names a
+    /// hidden message, or a branch ending at one
+    pub fn redact_change(&self, mut change: Change) -> Option<Change> {
+        if self.hidden.is_empty() {
+            return Some(change);
+        }
+        if let Some(leaf_id) = Self::named_by_change(&change)
+            && self.hides_id(leaf_id)
+        {
+            return None;
+        }
+        match change.payload.take() {
+            Some(payload) => match serde_json::from_value::<Message>(payload.clone()) {
+                Ok(message) => {
+                    let message = self.redact(message)?;
+                    change.payload = serde_json::to_value(message).ok();
+                }
+                Err(_) => change.payload = Some(payload),
+            },
+            None => {
+                if let Ok(id) = Uuid::parse_str(&change.entity_id)
+                    && self.hides_id(id)
+                {
+                    return None;
+                }
+            }
+        }
+
+        Some(change)
+    }
+
+    /// `event` as the viewer may see it: `None` for the creation, update or
+    /// move of a hidden message, a move from or to one, or a branch moving
+    /// from or to one, and metadata without the list of private messages
+    pub fn redact_event(&self, mut event: ConversationEvent) -> Option<ConversationEvent> {
+        if self.hidden.is_empty() {
+            return Some(event);
+        }
+        match event.kind {
+            EventKind::MessageCreated | EventKind::MessageUpdated => {
+                let message = serde_json::from_value::<Message>(event.payload.clone()).ok()?;
+                event.payload = serde_json::to_value(self.redact(message)?).ok()?;
+            }
+            EventKind::MetadataUpdated => {
+                if let Some(ids) = event.payload.get_mut("private_message_ids") {
+                    *ids = serde_json::Value::Array(Vec::new());
+                }
+            }
+            EventKind::BranchMoved | EventKind::MessageMoved => {
+                if Self::named_by_event(&event)
+                    .into_iter()
+                    .any(|id| self.hides_id(id))
+                {
+                    return None;
+                }
+            }
+            EventKind::Forked => {}
+        }
+
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{Conversation, MetadataContent};
+    use crate::utils::compute_lineage;
+
+    #[test]
+    fn test_private_messages_hide_their_replies_from_everyone_but_the_owner() {
+        let mut conversation = Conversation::new("Test".to_string(), "user_a".to_string());
+        let root = &conversation.root_message;
+        let reply = |parent: &Message| {
+            let message_id = Uuid::now_v7();
+            Message {
+                message_id,
+                parent_message_id: Some(parent.message_id),
+                lineage: compute_lineage(&parent.lineage, message_id),
+                content: ContentType::Text(crate::domain::TextContent {
+                    text: "note".to_string(),
+                }),
+                ..root.clone()
+            }
+        };
+        let visible = reply(root);
+        let private = reply(&visible);
+        let below = reply(&private);
+        if let ContentType::Metadata(MetadataContent {
+            private_message_ids,
+            ..
+        }) = &mut conversation.root_message.content
+        {
+            private_message_ids.push(private.message_id);
+        }
+        let root = &conversation.root_message;
+
+        let owner = PrivateMessages::of(root, Some("user_a"));
+        assert!(![&visible, &private, &below].iter().any(|m| owner.hides(m)));
+        assert_eq!(listed(&owner.redact(root.clone()).unwrap()), 1);
+
+        for viewer in [None, Some("user_b")] {
+            let private_messages = PrivateMessages::of(root, viewer);
+            assert!(!private_messages.hides(&visible));
+            assert!(private_messages.hides(&private));
+            assert!(private_messages.hides(&below));
+            assert_eq!(listed(&private_messages.redact(root.clone()).unwrap()), 0);
+        }
+    }
+
+    /// How many private messages the root's metadata lists
+    fn listed(root: &Message) -> usize {
+        match &root.content {
+            ContentType::Metadata(metadata) => metadata.private_message_ids.len(),
+            _ => 0,
+        }
+    }
+}
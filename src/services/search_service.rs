@@ -0,0 +1,296 @@
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{ContentType, Message, MessageRole};
+use crate::repositories::LineageStore;
+use crate::services::PrivateMessages;
+use crate::utils::language;
+
+/// Messages read per round trip while scanning a conversation
+const SCAN_PAGE_SIZE: i32 = 1000;
+/// Characters of context kept on each side of the first hit in a snippet
+const SNIPPET_CONTEXT: usize = 40;
+/// Longest accepted query, in characters
+const MAX_QUERY_CHARS: usize = 200;
+
+/// A message field containing the searched phrase
+#[derive(Debug, Clone)]
+pub struct MessageMatch {
+    pub message_id: Uuid,
+    pub role: MessageRole,
+    pub created_at: DateTime<Utc>,
+    /// `text`, `summary`, `tool_name` or `prompt`
+    pub field: &'static str,
+    /// The field around its first hit, `…` marking cut text
+    pub snippet: String,
+    /// `[start, end)` character offsets of every hit within `snippet`
+    pub highlights: Vec<(usize, usize)>,
+}
+
+/// Result of searching one conversation
+#[derive(Debug, Clone)]
+pub struct SearchResults {
+    /// Oldest message first, at most the requested number
+    pub matches: Vec<MessageMatch>,
+    /// Matches found before the limit was applied
+    pub total: usize,
+}
+
+/// Finds a phrase in the text of one conversation's messages. Scans the
+/// conversation on every request rather than keeping an index, which is fine
+/// for trees of a few thousand messages.
+pub struct SearchService {
+    lineage_repo: Arc<dyn LineageStore>,
+}
+
+impl SearchService {
+    pub fn new(lineage_repo: Arc<dyn LineageStore>) -> Self {
+        Self { lineage_repo }
+    }
+
+    /// Message texts, summaries, tool names and image prompts of the
+    /// conversation containing `query`, ignoring case. With `language`, only
+    /// messages in it or a variant of it are searched. Messages `private`
+    /// hides are never searched.
+    pub async fn search(
+        &self,
+        conversation_id: Uuid,
+        query: &str,
+        language: Option<&str>,
+        private: &PrivateMessages,
+        limit: usize,
+    ) -> Result<SearchResults, DbError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Err(DbError::InvalidData("`q` must not be empty".to_string()));
+        }
+        if query.chars().count() > MAX_QUERY_CHARS {
+            return Err(DbError::InvalidData(format!(
+                "`q` must be at most {} characters",
+                MAX_QUERY_CHARS
+            )));
+        }
+        let needle = fold(query);
+        let language = language
+            .map(language::normalize)
+            .transpose()
+            .map_err(DbError::InvalidData)?;
+
+        let mut messages = self
+            .lineage_repo
+            .stream_all_messages(conversation_id, Some(SCAN_PAGE_SIZE))
+            .await?;
+        let mut matches = Vec::new();
+        while let Some(message) = messages.try_next().await? {
+            if private.hides(&message) {
+                continue;
+            }
+            if let Some(filter) = &language
+                && !message
+                    .language()
+                    .is_some_and(|language| language::matches(language, filter))
+            {
+                continue;
+            }
+            for (field, value) in searchable_fields(&message) {
+                if let Some((snippet, highlights)) = find_in(value, &needle) {
+                    matches.push(MessageMatch {
+                        message_id: message.message_id,
+                        role: message.role.clone(),
+                        created_at: message.created_at,
+                        field,
+                        snippet,
+                        highlights,
+                    });
+                }
+            }
+        }
+
+        matches.sort_by_key(|m| (m.created_at, m.message_id));
+        let total = matches.len();
+        matches.truncate(limit);
+
+        Ok(SearchResults { matches, total })
+    }
+}
+
+/// The searchable text of a message, by field name
+fn searchable_fields(message: &Message) -> Vec<(&'static str, &str)> {
+    match &message.content {
+        ContentType::Text(content) => vec![("text", content.text.as_str())],
+        ContentType::Summary(content) => vec![("summary", content.text.as_str())],
+        ContentType::ToolCall(content) => vec![("tool_name", content.tool_name.as_str())],
+        ContentType::ImageBatch(content) => content
+            .images
+            .iter()
+            .filter_map(|image| image.prompt.as_deref())
+            .map(|prompt| ("prompt", prompt))
+            .collect(),
+        ContentType::Image(_) | ContentType::ToolResult(_) | ContentType::Metadata(_) => Vec::new(),
+    }
+}
+
+/// Characters of `s` with case folded one to one, so offsets into the result
+/// are offsets into `s`
+fn fold(s: &str) -> Vec<char> {
+    s.chars()
+        .map(|c| c.to_lowercase().next().unwrap_or(c))
+        .collect()
+}
+
+/// Snippet of `haystack` around the first occurrence of `needle`, with the
+/// occurrences inside it; `None` if there is none
+fn find_in(haystack: &str, needle: &[char]) -> Option<(String, Vec<(usize, usize)>)> {
+    let chars: Vec<char> = haystack.chars().collect();
+    let folded = fold(haystack);
+    if needle.is_empty() || folded.len() < needle.len() {
+        return None;
+    }
+
+    let mut hits = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= folded.len() {
+        if folded[i..i + needle.len()] == *needle {
+            hits.push(i);
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    let first = *hits.first()?;
+
+    let start = first.saturating_sub(SNIPPET_CONTEXT);
+    let end = (first + needle.len() + SNIPPET_CONTEXT).min(chars.len());
+    let mut snippet = String::new();
+    let offset = if start > 0 {
+        snippet.push('…');
+        1
+    } else {
+        0
+    };
+    snippet.extend(&chars[start..end]);
+    if end < chars.len() {
+        snippet.push('…');
+    }
+
+    let highlights = hits
+        .into_iter()
+        .filter(|&hit| hit + needle.len() <= end)
+        .map(|hit| (hit - start + offset, hit - start + offset + needle.len()))
+        .collect();
+
+    Some((snippet, highlights))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{
+        Conversation, ImageBatchContent, ImageBatchItem, LANGUAGE_KEY, TextContent,
+    };
+    use crate::repositories::Storage;
+    use crate::utils::{compute_lineage, new_message_id};
+
+    fn reply(parent: &Message, content: ContentType) -> Message {
+        let message_id = new_message_id();
+        Message {
+            conversation_id: parent.conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role: MessageRole::Human,
+            content,
+            content_metadata: Default::default(),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: Utc::now(),
+            created_by: "user_a".to_string(),
+        }
+    }
+
+    fn text(text: &str) -> ContentType {
+        ContentType::Text(TextContent {
+            text: text.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_snippets_highlight_every_hit_around_the_first() {
+        let haystack = format!("{}Blue fox, blue FOX", "x".repeat(50));
+        let (snippet, highlights) = find_in(&haystack, &fold("fox")).unwrap();
+
+        assert!(snippet.starts_with('…') && !snippet.ends_with('…'));
+        let hits: Vec<String> = highlights
+            .iter()
+            .map(|&(start, end)| snippet.chars().skip(start).take(end - start).collect())
+            .collect();
+        assert_eq!(hits, vec!["fox", "FOX"]);
+        assert!(find_in("Straße", &fold("STRASSE")).is_none());
+        assert!(find_in("ÄRGER", &fold("ärger")).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_search_finds_text_and_prompts_oldest_first() {
+        let storage = Storage::memory();
+        let service = SearchService::new(storage.lineage.clone());
+        let conversation = Conversation::new("Logo".to_string(), "user_a".to_string());
+        let cid = conversation.conversation_id;
+        let mut a = reply(&conversation.root_message, text("Draw a blue fox"));
+        a.content_metadata
+            .insert(LANGUAGE_KEY.to_string(), "en-GB".to_string());
+        let b = reply(
+            &a,
+            ContentType::ImageBatch(ImageBatchContent {
+                images: vec![ImageBatchItem {
+                    image_url: "https://example.com/fox.png".to_string(),
+                    prompt: Some("a Blue Fox, flat style".to_string()),
+                    model: None,
+                }],
+            }),
+        );
+        let c = reply(&b, text("Make it red"));
+        storage
+            .lineage
+            .batch_insert_messages(&[conversation.root_message.clone(), a.clone(), b.clone(), c])
+            .await
+            .unwrap();
+
+        let everything = PrivateMessages::default();
+        let results = service
+            .search(cid, " blue fox ", None, &everything, 10)
+            .await
+            .unwrap();
+        let found: Vec<(Uuid, &str)> = results
+            .matches
+            .iter()
+            .map(|m| (m.message_id, m.field))
+            .collect();
+        assert_eq!(
+            found,
+            vec![(a.message_id, "text"), (b.message_id, "prompt")]
+        );
+        assert_eq!(results.matches[1].highlights, vec![(2, 10)]);
+
+        let results = service
+            .search(cid, "BLUE", None, &everything, 1)
+            .await
+            .unwrap();
+        assert_eq!((results.matches.len(), results.total), (1, 2));
+
+        // Messages without a language are left out once one is asked for
+        let results = service
+            .search(cid, "blue", Some("en"), &everything, 10)
+            .await
+            .unwrap();
+        assert_eq!(results.total, 1);
+        assert_eq!(results.matches[0].message_id, a.message_id);
+
+        for (query, language) in [("  ", None), ("blue", Some("english"))] {
+            assert!(matches!(
+                service.search(cid, query, language, &everything, 10).await,
+                Err(DbError::InvalidData(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{ContentType, Message, MessageRole, Permission, TextContent};
+use crate::services::{BranchService, ConversationService, ForkOptions, ForkService, ShareService};
+
+/// Owner of the demo conversations
+pub const DEMO_USER: &str = "demo_user";
+
+/// User the demo conversations are shared with, who also forks the public one
+pub const DEMO_COLLABORATOR: &str = "demo_collaborator";
+
+/// Model named in the content metadata of demo answers
+const DEMO_MODEL: &str = "demo-model";
+
+/// What `seed` created, for printing
+#[derive(Debug, Clone)]
+pub struct SeedReport {
+    pub conversation_id: Uuid,
+    pub branch_ids: Vec<Uuid>,
+    pub public_conversation_id: Uuid,
+    pub fork_conversation_id: Uuid,
+    pub messages: usize,
+}
+
+/// Creates demo data through the same services the API uses, so it looks
+/// exactly like data created by clients. Every run creates new conversations.
+pub struct DemoSeeder {
+    conversations: Arc<ConversationService>,
+    branches: Arc<BranchService>,
+    shares: Arc<ShareService>,
+    forks: Arc<ForkService>,
+}
+
+impl DemoSeeder {
+    pub fn new(
+        conversations: Arc<ConversationService>,
+        branches: Arc<BranchService>,
+        shares: Arc<ShareService>,
+        forks: Arc<ForkService>,
+    ) -> Self {
+        Self {
+            conversations,
+            branches,
+            shares,
+            forks,
+        }
+    }
+
+    /// Create a deep conversation with three branches shared with the
+    /// collaborator, and a public conversation the collaborator forked
+    pub async fn seed(&self) -> Result<SeedReport, DbError> {
+        let mut messages = 0;
+
+        let conversation = self
+            .conversations
+            .create_conversation("Planning two weeks in Japan".to_string(), DEMO_USER.into())
+            .await?;
+        let conversation_id = conversation.conversation_id;
+        let root_id = conversation.root_message.message_id;
+
+        let main = self.exchange(conversation_id, root_id, TRIP_MAIN).await?;
+        messages += main.len();
+        // Both alternatives branch off the assistant's first answer
+        let budget = self.exchange(conversation_id, main[1], TRIP_BUDGET).await?;
+        messages += budget.len();
+        let food = self.exchange(conversation_id, main[1], TRIP_FOOD).await?;
+        messages += food.len();
+
+        let mut branch_ids = Vec::new();
+        for (name, leaf) in [("main", &main), ("budget", &budget), ("food", &food)] {
+            let branch = self
+                .branches
+                .create_branch(
+                    conversation_id,
+                    Some(name.to_string()),
+                    *leaf.last().expect("demo exchanges are not empty"),
+                    DEMO_USER.to_string(),
+                )
+                .await?;
+            branch_ids.push(branch.branch_id);
+        }
+
+        self.shares
+            .share_conversation(
+                conversation_id,
+                DEMO_COLLABORATOR.to_string(),
+                Permission::Branch,
+                DEMO_USER.to_string(),
+            )
+            .await?;
+
+        let public = self
+            .conversations
+            .create_conversation("Prompt writing tips".to_string(), DEMO_USER.into())
+            .await?;
+        messages += self
+            .exchange(
+                public.conversation_id,
+                public.root_message.message_id,
+                PROMPT_TIPS,
+            )
+            .await?
+            .len();
+        self.conversations
+            .update_conversation(
+                public.conversation_id,
+                None,
+                Some("A short public conversation anyone can fork".to_string()),
+                Some(true),
+                None,
+            )
+            .await?;
+
+        let fork = self
+            .forks
+            .fork_conversation(
+                public.conversation_id,
+                "Prompt writing tips (my notes)".to_string(),
+                DEMO_COLLABORATOR.to_string(),
+                ForkOptions::default(),
+            )
+            .await?;
+
+        Ok(SeedReport {
+            conversation_id,
+            branch_ids,
+            public_conversation_id: public.conversation_id,
+            fork_conversation_id: fork.conversation_id,
+            messages,
+        })
+    }
+
+    /// Append alternating human and assistant turns under `parent_id`,
+    /// returning their IDs in order
+    async fn exchange(
+        &self,
+        conversation_id: Uuid,
+        parent_id: Uuid,
+        turns: &[&str],
+    ) -> Result<Vec<Uuid>, DbError> {
+        let mut ids = Vec::with_capacity(turns.len());
+        let mut parent_id = parent_id;
+        for (i, text) in turns.iter().enumerate() {
+            let (role, content_metadata) = if i % 2 == 0 {
+                (MessageRole::Human, HashMap::new())
+            } else {
+                (MessageRole::Assistant, answer_metadata(text))
+            };
+            let message: Message = self
+                .conversations
+                .append_message(
+                    conversation_id,
+                    parent_id,
+                    role,
+                    ContentType::Text(TextContent {
+                        text: text.to_string(),
+                    }),
+                    content_metadata,
+                    DEMO_USER.to_string(),
+                )
+                .await?;
+            parent_id = message.message_id;
+            ids.push(message.message_id);
+        }
+
+        Ok(ids)
+    }
+}
+
+/// Model and rough token counts, so analytics have something to show
+fn answer_metadata(text: &str) -> HashMap<String, String> {
+    let words = text.split_whitespace().count();
+    HashMap::from([
+        ("model".to_string(), DEMO_MODEL.to_string()),
+        ("input_tokens".to_string(), (words * 3).to_string()),
+        ("output_tokens".to_string(), (words * 4 / 3).to_string()),
+    ])
+}
+
+const TRIP_MAIN: &[&str] = &[
+    "I have two weeks in Japan in April. Where should I go?",
+    "April is cherry blossom season. A classic route is Tokyo, Hakone, Kyoto, Nara and Osaka, \
+     with a few days in each.",
+    "How many days should I spend in Tokyo?",
+    "Four days covers the main districts: Asakusa, Shibuya, Shinjuku and a day trip to Nikko \
+     or Kamakura.",
+    "Is the Japan Rail Pass worth it for this route?",
+    "Since the 2023 price increase it only pays off with several long trips. For Tokyo to \
+     Kyoto and back, individual tickets are usually cheaper.",
+    "What should I book in advance?",
+    "Ryokan stays in Hakone, the Ghibli Museum, teamLab and popular restaurants. Shinkansen \
+     seats are easy to get outside Golden Week.",
+    "Can you summarize the itinerary day by day?",
+    "Days 1-4 Tokyo, 5-6 Hakone, 7-10 Kyoto with a day in Nara, 11-13 Osaka, 14 fly home \
+     from Kansai.",
+];
+
+const TRIP_BUDGET: &[&str] = &[
+    "What if I need to keep it under $2,000 excluding flights?",
+    "That is about $140 a day. Stay in business hotels or hostels, use IC cards for local \
+     transit, and eat at shokudo and convenience stores for some meals.",
+    "Which cities are cheapest?",
+    "Osaka is generally cheaper than Tokyo and Kyoto for both rooms and food, so consider \
+     basing yourself there for the Kansai part.",
+];
+
+const TRIP_FOOD: &[&str] = &[
+    "Actually I mostly care about food. How should I change the plan?",
+    "Spend longer in Osaka, the kitchen of Japan: Dotonbori street food, Kuromon Market and \
+     a kushikatsu crawl in Shinshekai.",
+    "Any dishes I must try in Kyoto?",
+    "Yudofu near Nanzen-ji, a kaiseki dinner if the budget allows, and matcha sweets in Uji.",
+];
+
+const PROMPT_TIPS: &[&str] = &[
+    "What makes a good prompt?",
+    "State the task, give the context the model needs, show the format you want, and say \
+     what to avoid. Examples help more than adjectives.",
+    "Should prompts be long or short?",
+    "As long as needed and no longer. Remove anything the model would assume anyway.",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Settings;
+    use crate::object_store::S3ObjectStore;
+    use crate::repositories::Storage;
+    use crate::services::{
+        ChangeFeed, CollaborationHub, ImageService, NotificationService, PreferenceService,
+    };
+    use crate::utils::pii::PiiScrubber;
+
+    #[tokio::test]
+    async fn test_seed_creates_browsable_data() {
+        let storage = Storage::memory();
+        let settings = Settings::default();
+        let change_feed =
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new()));
+        let notifications = Arc::new(NotificationService::new(
+            storage.notifications.clone(),
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        ));
+        let images = Arc::new(ImageService::new(
+            storage.images.clone(),
+            storage.lineage.clone(),
+            Arc::new(S3ObjectStore::new(settings.s3.clone())),
+            settings.images.clone(),
+        ));
+        let shares = Arc::new(ShareService::new(
+            storage.shares.clone(),
+            change_feed.clone(),
+            notifications.clone(),
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        ));
+        let conversations = Arc::new(ConversationService::new(
+            storage.lineage.clone(),
+            change_feed.clone(),
+            settings.app.clone(),
+            notifications.clone(),
+            Arc::new(PiiScrubber::new(&settings.pii)),
+            images.clone(),
+            shares.clone(),
+        ));
+        let seeder = DemoSeeder::new(
+            conversations.clone(),
+            Arc::new(BranchService::new(
+                storage.branches.clone(),
+                storage.lineage.clone(),
+                change_feed.clone(),
+                settings.branch_cache.clone(),
+                settings.branches.default_naming,
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            shares,
+            Arc::new(ForkService::new(
+                storage.lineage.clone(),
+                storage.branches.clone(),
+                storage.shares.clone(),
+                settings.app.clone(),
+                settings.fork.clone(),
+                notifications,
+                images,
+            )),
+        );
+
+        let report = seeder.seed().await.unwrap();
+
+        assert_eq!(report.branch_ids.len(), 3);
+        assert_eq!(
+            report.messages,
+            TRIP_MAIN.len() + TRIP_BUDGET.len() + TRIP_FOOD.len() + PROMPT_TIPS.len()
+        );
+        let tree = conversations
+            .get_conversation_tree(report.conversation_id)
+            .await
+            .unwrap();
+        assert_eq!(
+            tree.len(),
+            1 + TRIP_MAIN.len() + TRIP_BUDGET.len() + TRIP_FOOD.len()
+        );
+        let public = conversations
+            .get_conversation(report.public_conversation_id)
+            .await
+            .unwrap();
+        assert!(matches!(
+            public.root_message.content,
+            ContentType::Metadata(metadata) if metadata.is_public
+        ));
+    }
+}
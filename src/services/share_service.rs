@@ -1,17 +1,40 @@
-use chrono::Utc;
+use chrono::{Duration, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use std::collections::HashSet;
+use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::db::DbError;
-use crate::domain::{Permission, Share};
-use crate::repositories::ShareRepository;
+use crate::domain::{ChangeKind, EVERYONE, Invite, NotificationKind, Permission, Share};
+use crate::repositories::ShareStore;
+use crate::services::{ChangeFeed, NotificationService, PreferenceService};
+
+/// How long an invite can be accepted
+const INVITE_TTL_DAYS: i64 = 14;
+
+/// Length of the random token an invitee must present
+const INVITE_TOKEN_LEN: usize = 32;
 
 pub struct ShareService {
-    share_repo: ShareRepository,
+    share_repo: Arc<dyn ShareStore>,
+    change_feed: ChangeFeed,
+    notifications: Arc<NotificationService>,
+    preferences: Arc<PreferenceService>,
 }
 
 impl ShareService {
-    pub fn new(share_repo: ShareRepository) -> Self {
-        Self { share_repo }
+    pub fn new(
+        share_repo: Arc<dyn ShareStore>,
+        change_feed: ChangeFeed,
+        notifications: Arc<NotificationService>,
+        preferences: Arc<PreferenceService>,
+    ) -> Self {
+        Self {
+            share_repo,
+            change_feed,
+            notifications,
+            preferences,
+        }
     }
 
     /// Share a conversation with a user
@@ -32,6 +55,25 @@ impl ShareService {
 
         self.share_repo.insert_share(&share).await?;
 
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::ShareUpdated,
+                &share.shared_with,
+                &share,
+            )
+            .await?;
+
+        self.notifications
+            .notify(
+                &share.shared_with,
+                NotificationKind::ConversationShared,
+                conversation_id,
+                &share.shared_by,
+                None,
+            )
+            .await;
+
         Ok(share)
     }
 
@@ -64,9 +106,166 @@ impl ShareService {
     ) -> Result<(), DbError> {
         self.share_repo
             .delete_share(conversation_id, shared_with)
+            .await?;
+
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::ShareRevoked,
+                shared_with,
+                &serde_json::json!({ "shared_with": shared_with }),
+            )
             .await
     }
 
+    /// Grant or change and revoke several shares of a conversation in a single
+    /// write. Each user may appear only once across grants and revokes.
+    pub async fn batch_update_shares(
+        &self,
+        conversation_id: Uuid,
+        grants: Vec<(String, Permission)>,
+        revokes: Vec<String>,
+        shared_by: String,
+    ) -> Result<(Vec<Share>, Vec<String>), DbError> {
+        let mut seen = HashSet::new();
+        for user_id in grants.iter().map(|(user_id, _)| user_id).chain(&revokes) {
+            if !seen.insert(user_id.as_str()) {
+                return Err(DbError::InvalidData(format!(
+                    "User {} appears more than once",
+                    user_id
+                )));
+            }
+        }
+
+        let existing: HashSet<String> = self
+            .share_repo
+            .get_shares_by_conversation(conversation_id)
+            .await?
+            .into_iter()
+            .map(|share| share.shared_with)
+            .collect();
+
+        let now = Utc::now();
+        let shares: Vec<Share> = grants
+            .into_iter()
+            .map(|(shared_with, permission)| Share {
+                conversation_id,
+                shared_with,
+                permission,
+                shared_at: now,
+                shared_by: shared_by.clone(),
+            })
+            .collect();
+
+        self.share_repo
+            .apply_share_changes(conversation_id, &shares, &revokes)
+            .await?;
+
+        for share in &shares {
+            self.change_feed
+                .record(
+                    conversation_id,
+                    ChangeKind::ShareUpdated,
+                    &share.shared_with,
+                    share,
+                )
+                .await?;
+
+            if !existing.contains(&share.shared_with) {
+                self.notifications
+                    .notify(
+                        &share.shared_with,
+                        NotificationKind::ConversationShared,
+                        conversation_id,
+                        &share.shared_by,
+                        None,
+                    )
+                    .await;
+            }
+        }
+        for shared_with in &revokes {
+            self.change_feed
+                .record(
+                    conversation_id,
+                    ChangeKind::ShareRevoked,
+                    shared_with,
+                    &serde_json::json!({ "shared_with": shared_with }),
+                )
+                .await?;
+        }
+
+        Ok((shares, revokes))
+    }
+
+    /// Shares granted to a user, most recent first
+    pub async fn get_shared_with_user(
+        &self,
+        user_id: &str,
+        limit: usize,
+    ) -> Result<Vec<Share>, DbError> {
+        let mut shares = self.share_repo.get_shares_for_user(user_id).await?;
+        shares.sort_by_key(|share| std::cmp::Reverse(share.shared_at));
+        shares.truncate(limit);
+
+        Ok(shares)
+    }
+
+    /// Invite someone without an account by email. The returned token must
+    /// reach the invitee, who presents it together with the email to accept.
+    pub async fn create_invite(
+        &self,
+        conversation_id: Uuid,
+        email: String,
+        permission: Permission,
+        invited_by: String,
+    ) -> Result<Invite, DbError> {
+        let now = Utc::now();
+        let invite = Invite {
+            email,
+            token: rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(INVITE_TOKEN_LEN)
+                .map(char::from)
+                .collect(),
+            conversation_id,
+            permission,
+            invited_by,
+            created_at: now,
+            expires_at: now + Duration::days(INVITE_TTL_DAYS),
+        };
+
+        self.share_repo.insert_invite(&invite).await?;
+
+        Ok(invite)
+    }
+
+    /// Turn a pending invite into a share for the authenticated user.
+    /// Unknown and expired invites are both `NotFound`; an invite can be used once.
+    pub async fn accept_invite(
+        &self,
+        email: &str,
+        token: &str,
+        user_id: String,
+    ) -> Result<Share, DbError> {
+        let invite = self.share_repo.get_invite(email, token).await?;
+        if invite.expires_at <= Utc::now() {
+            self.share_repo.delete_invite(email, token).await?;
+            return Err(DbError::NotFound);
+        }
+
+        let share = self
+            .share_conversation(
+                invite.conversation_id,
+                user_id,
+                invite.permission,
+                invite.invited_by,
+            )
+            .await?;
+        self.share_repo.delete_invite(email, token).await?;
+
+        Ok(share)
+    }
+
     /// Check if a user has permission to access a conversation
     pub async fn check_permission(
         &self,
@@ -82,6 +281,7 @@ impl ShareService {
                     Permission::Read => share.permission.can_read(),
                     Permission::Branch => share.permission.can_branch(),
                     Permission::Fork => share.permission.can_fork(),
+                    Permission::Admin => share.permission.can_manage(),
                 };
                 Ok(has_permission)
             }
@@ -90,6 +290,61 @@ impl ShareService {
         }
     }
 
+    /// Grant the owner's default shares on a conversation they just created
+    pub async fn apply_default_shares(
+        &self,
+        conversation_id: Uuid,
+        owner: &str,
+    ) -> Result<Vec<Share>, DbError> {
+        let preferences = self.preferences.get(owner).await?;
+        if preferences.default_shares.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let grants = preferences.default_shares.into_iter().collect();
+        let (shares, _) = self
+            .batch_update_shares(conversation_id, grants, Vec::new(), owner.to_string())
+            .await?;
+
+        Ok(shares)
+    }
+
+    /// Give everyone the access the owner's policy allows on a conversation
+    /// that was just published, or take it back when it stops being public
+    pub async fn apply_visibility(
+        &self,
+        conversation_id: Uuid,
+        owner: &str,
+        is_public: bool,
+    ) -> Result<(), DbError> {
+        if !is_public {
+            return self.revoke_share(conversation_id, EVERYONE).await;
+        }
+
+        let preferences = self.preferences.get(owner).await?;
+        let share = Share {
+            conversation_id,
+            shared_with: EVERYONE.to_string(),
+            permission: if preferences.allow_public_forks {
+                Permission::Fork
+            } else {
+                Permission::Read
+            },
+            shared_at: Utc::now(),
+            shared_by: owner.to_string(),
+        };
+        self.share_repo.insert_share(&share).await?;
+
+        self.change_feed
+            .record(
+                conversation_id,
+                ChangeKind::ShareUpdated,
+                &share.shared_with,
+                &share,
+            )
+            .await
+    }
+
     /// Update user's conversation activity
     pub async fn update_user_activity(
         &self,
@@ -115,3 +370,131 @@ impl ShareService {
         Ok(rows.into_iter().map(|r| r.conversation_id).collect())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repositories::Storage;
+    use crate::services::CollaborationHub;
+
+    #[tokio::test]
+    async fn test_accepted_invite_becomes_share_once() {
+        let storage = Storage::memory();
+        let service = ShareService::new(
+            storage.shares.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        );
+        let conversation_id = Uuid::new_v4();
+
+        let invite = service
+            .create_invite(
+                conversation_id,
+                "new@example.com".to_string(),
+                Permission::Branch,
+                "owner".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service
+                .accept_invite("other@example.com", &invite.token, "user_b".to_string())
+                .await,
+            Err(DbError::NotFound)
+        ));
+
+        let share = service
+            .accept_invite("new@example.com", &invite.token, "user_b".to_string())
+            .await
+            .unwrap();
+        assert_eq!(share.shared_with, "user_b");
+        assert_eq!(share.shared_by, "owner");
+        assert!(
+            service
+                .check_permission(conversation_id, "user_b", Permission::Branch)
+                .await
+                .unwrap()
+        );
+
+        assert!(matches!(
+            service
+                .accept_invite("new@example.com", &invite.token, "user_c".to_string())
+                .await,
+            Err(DbError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_batch_update_shares_grants_and_revokes_together() {
+        let storage = Storage::memory();
+        let service = ShareService::new(
+            storage.shares.clone(),
+            ChangeFeed::new(storage.changes.clone(), Arc::new(CollaborationHub::new())),
+            Arc::new(NotificationService::new(
+                storage.notifications.clone(),
+                Arc::new(PreferenceService::new(storage.preferences.clone())),
+            )),
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        );
+        let conversation_id = Uuid::new_v4();
+        service
+            .share_conversation(
+                conversation_id,
+                "user_b".to_string(),
+                Permission::Read,
+                "owner".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            service
+                .batch_update_shares(
+                    conversation_id,
+                    vec![("user_c".to_string(), Permission::Read)],
+                    vec!["user_c".to_string()],
+                    "owner".to_string(),
+                )
+                .await,
+            Err(DbError::InvalidData(_))
+        ));
+
+        service
+            .batch_update_shares(
+                conversation_id,
+                vec![
+                    ("user_c".to_string(), Permission::Fork),
+                    ("user_d".to_string(), Permission::Read),
+                ],
+                vec!["user_b".to_string()],
+                "owner".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let shared_with: Vec<String> = service
+            .get_conversation_shares(conversation_id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|share| share.shared_with)
+            .collect();
+        assert_eq!(shared_with, vec!["user_c", "user_d"]);
+
+        let shared_with_c = service.get_shared_with_user("user_c", 10).await.unwrap();
+        assert_eq!(shared_with_c.len(), 1);
+        assert_eq!(shared_with_c[0].permission, Permission::Fork);
+        assert!(
+            service
+                .get_shared_with_user("user_b", 10)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::config::TrendingConfig;
+use crate::db::{DbError, TrendingRow};
+use crate::domain::ContentType;
+use crate::repositories::{LineageStore, TrendingStore};
+use crate::scheduler::{ScheduledTask, TaskError};
+use crate::utils::language;
+
+/// Ranking bucket served by the explore API
+const GLOBAL_BUCKET: &str = "global";
+
+/// A fork signals far more interest than a view
+const FORK_WEIGHT: f64 = 5.0;
+
+/// Candidates examined per ranking slot; private conversations are skipped
+const CANDIDATES_PER_SLOT: usize = 4;
+
+pub struct TrendingService {
+    trending_repo: Arc<dyn TrendingStore>,
+    lineage_repo: Arc<dyn LineageStore>,
+    config: TrendingConfig,
+}
+
+impl TrendingService {
+    pub fn new(
+        trending_repo: Arc<dyn TrendingStore>,
+        lineage_repo: Arc<dyn LineageStore>,
+        config: TrendingConfig,
+    ) -> Self {
+        Self {
+            trending_repo,
+            lineage_repo,
+            config,
+        }
+    }
+
+    /// Count a view of a conversation. Failures are logged, never surfaced to the viewer.
+    pub async fn record_view(&self, conversation_id: Uuid) {
+        self.record(conversation_id, 1, 0).await
+    }
+
+    /// Count a fork of a conversation
+    pub async fn record_fork(&self, source_conversation_id: Uuid) {
+        self.record(source_conversation_id, 0, 1).await
+    }
+
+    async fn record(&self, conversation_id: Uuid, views: i64, forks: i64) {
+        if let Err(e) = self
+            .trending_repo
+            .increment_activity(&day(Utc::now()), conversation_id, views, forks)
+            .await
+        {
+            tracing::warn!(
+                "Failed to record activity for conversation {}: {}",
+                conversation_id,
+                e
+            );
+        }
+    }
+
+    /// The current trending ranking, best first, keeping only conversations
+    /// in `language` or a variant of it if given
+    pub async fn get_trending(
+        &self,
+        limit: usize,
+        language: Option<&str>,
+    ) -> Result<Vec<TrendingRow>, DbError> {
+        let Some(filter) = language
+            .map(language::normalize)
+            .transpose()
+            .map_err(DbError::InvalidData)?
+        else {
+            return self
+                .trending_repo
+                .get_trending(GLOBAL_BUCKET, limit as i32)
+                .await;
+        };
+
+        // The ranking is short, so filter all of it
+        let mut ranking = self
+            .trending_repo
+            .get_trending(GLOBAL_BUCKET, self.config.size as i32)
+            .await?;
+        ranking.retain(|entry| {
+            entry
+                .language
+                .as_deref()
+                .is_some_and(|language| language::matches(language, &filter))
+        });
+        ranking.truncate(limit);
+
+        Ok(ranking)
+    }
+
+    /// Recompute the ranking from the daily counters of the configured window.
+    /// Each day's activity is weighted half as much as the day after it, so the
+    /// ranking favours what is popular now. Only public conversations are ranked.
+    pub async fn recompute(&self, now: DateTime<Utc>) -> Result<usize, DbError> {
+        let mut totals: HashMap<Uuid, (f64, i64, i64)> = HashMap::new();
+
+        for age in 0..self.config.window_days {
+            let decay = 0.5f64.powi(age as i32);
+            let rows = self
+                .trending_repo
+                .get_activity(&day(now - Duration::days(age as i64)))
+                .await?;
+
+            for row in rows {
+                let (score, views, forks) = totals.entry(row.conversation_id).or_default();
+                *score += (row.views() as f64 + FORK_WEIGHT * row.forks() as f64) * decay;
+                *views += row.views();
+                *forks += row.forks();
+            }
+        }
+
+        let mut candidates: Vec<(Uuid, (f64, i64, i64))> = totals.into_iter().collect();
+        candidates.sort_by(|(_, (a, _, _)), (_, (b, _, _))| b.total_cmp(a));
+
+        let mut ranking = Vec::with_capacity(self.config.size);
+        for (conversation_id, (score, view_count, fork_count)) in candidates
+            .into_iter()
+            .take(self.config.size * CANDIDATES_PER_SLOT)
+        {
+            if ranking.len() == self.config.size {
+                break;
+            }
+
+            let Some((title, language)) = self.public_metadata(conversation_id).await? else {
+                continue;
+            };
+            ranking.push(TrendingRow {
+                bucket: GLOBAL_BUCKET.to_string(),
+                rank: ranking.len() as i32,
+                conversation_id,
+                title,
+                score,
+                view_count,
+                fork_count,
+                computed_at: now,
+                language,
+            });
+        }
+
+        self.trending_repo
+            .replace_trending(GLOBAL_BUCKET, &ranking)
+            .await?;
+
+        Ok(ranking.len())
+    }
+
+    /// Title and language of a conversation if it still exists and is public
+    async fn public_metadata(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Option<(String, Option<String>)>, DbError> {
+        let root = match self.lineage_repo.get_root_message(conversation_id).await {
+            Ok(root) => root,
+            Err(DbError::NotFound) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(match root.content {
+            ContentType::Metadata(metadata) if metadata.is_public => {
+                Some((metadata.title, metadata.language))
+            }
+            _ => None,
+        })
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for TrendingService {
+    fn name(&self) -> &'static str {
+        "trending"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let ranked = self.recompute(Utc::now()).await?;
+        tracing::debug!("Trending ranking updated with {} entries", ranked);
+        Ok(())
+    }
+}
+
+/// Counter bucket for a point in time
+fn day(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Conversation;
+    use crate::repositories::Storage;
+
+    #[tokio::test]
+    async fn test_recompute_ranks_recent_public_activity() {
+        let storage = Storage::memory();
+        let service = TrendingService::new(
+            storage.trending.clone(),
+            storage.lineage.clone(),
+            TrendingConfig {
+                interval_secs: 60,
+                window_days: 7,
+                size: 10,
+            },
+        );
+        let now = Utc::now();
+
+        let mut ids = Vec::new();
+        for (title, is_public, language) in [
+            ("Old hit", true, None),
+            ("Rising", true, Some("pt-BR")),
+            ("Private", false, Some("pt")),
+        ] {
+            let mut conversation = Conversation::new(title.to_string(), "user_a".to_string());
+            if let ContentType::Metadata(metadata) = &mut conversation.root_message.content {
+                metadata.is_public = is_public;
+                metadata.language = language.map(String::from);
+            }
+            storage
+                .lineage
+                .insert_message(&conversation.root_message)
+                .await
+                .unwrap();
+            ids.push(conversation.conversation_id);
+        }
+
+        let trending = &storage.trending;
+        // 40 views three days ago decay to 5; 2 forks today are worth 10
+        trending
+            .increment_activity(&day(now - Duration::days(3)), ids[0], 40, 0)
+            .await
+            .unwrap();
+        trending
+            .increment_activity(&day(now), ids[1], 0, 2)
+            .await
+            .unwrap();
+        trending
+            .increment_activity(&day(now), ids[2], 100, 10)
+            .await
+            .unwrap();
+
+        assert_eq!(service.recompute(now).await.unwrap(), 2);
+
+        let ranking = service.get_trending(10, None).await.unwrap();
+        assert_eq!(ranking[0].title, "Rising");
+        assert_eq!(ranking[0].fork_count, 2);
+        assert_eq!(ranking[1].title, "Old hit");
+        assert_eq!(ranking[1].score, 5.0);
+
+        let ranking = service.get_trending(10, Some("pt")).await.unwrap();
+        assert_eq!(ranking.len(), 1);
+        assert_eq!(ranking[0].language.as_deref(), Some("pt-BR"));
+    }
+}
@@ -0,0 +1,679 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::{Rng, distributions::Alphanumeric};
+use ring::hmac;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::config::{FetchConfig, WebhooksConfig};
+use crate::db::DbError;
+use crate::domain::{CONTENT_TYPES, Change, WebhookFilter, WebhookSubscription};
+use crate::repositories::{ChangeStore, LineageStore, WebhookStore};
+use crate::scheduler::{ScheduledTask, TaskError};
+use crate::services::PrivateMessages;
+use crate::utils::hex::hex;
+use crate::utils::http::{HttpClient, HttpError};
+
+/// Subscriptions one conversation may have
+const MAX_SUBSCRIPTIONS_PER_CONVERSATION: usize = 10;
+
+/// Prefix of signing secrets, so they're recognizable in receivers' configuration
+const SECRET_PREFIX: &str = "whsec_";
+
+/// Random characters of a signing secret
+const SECRET_LEN: usize = 32;
+
+/// Body of a delivery
+#[derive(Serialize)]
+struct Delivery<'a> {
+    /// The change's ID: the same for retries and redeliveries
+    delivery_id: Uuid,
+    subscription_id: Uuid,
+    change: &'a Change,
+}
+
+/// `X-Webhook-Signature` of a delivery: HMAC-SHA256 of
+/// `<timestamp>.<body>` keyed with the subscription's secret
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut signed = format!("{}.", timestamp).into_bytes();
+    signed.extend_from_slice(body);
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    format!("v1={}", hex(hmac::sign(&key, &signed)))
+}
+
+/// Manages webhook subscriptions and delivers the changes of subscribed
+/// conversations to them, in order and at least once. A subscriber that
+/// fails a delivery gets it again on the next run, before anything newer.
+/// Changes go out as the subscription's creator may see them, so messages
+/// the owner keeps private reach only the owner's own subscriptions.
+pub struct WebhookService {
+    store: Arc<dyn WebhookStore>,
+    changes: Arc<dyn ChangeStore>,
+    lineage: Arc<dyn LineageStore>,
+    config: WebhooksConfig,
+    client: HttpClient,
+}
+
+impl WebhookService {
+    pub fn new(
+        store: Arc<dyn WebhookStore>,
+        changes: Arc<dyn ChangeStore>,
+        lineage: Arc<dyn LineageStore>,
+        config: WebhooksConfig,
+        fetch: FetchConfig,
+    ) -> Self {
+        Self {
+            store,
+            changes,
+            lineage,
+            config,
+            client: HttpClient::for_fetch(&fetch),
+        }
+    }
+
+    /// Subscribe `url` to the conversation's changes matching `filter`,
+    /// starting with the next one
+    pub async fn subscribe(
+        &self,
+        conversation_id: Uuid,
+        url: String,
+        filter: WebhookFilter,
+        created_by: String,
+    ) -> Result<WebhookSubscription, DbError> {
+        self.client.check_url(&url).map_err(|e| match e {
+            HttpError::InvalidUrl(_) => {
+                DbError::InvalidData("Webhook URLs must be http:// or https:// URLs".to_string())
+            }
+            e => DbError::InvalidData(format!("Webhook URL refused: {}", e)),
+        })?;
+        if let Some(unknown) = filter
+            .content_types
+            .iter()
+            .find(|content_type| !CONTENT_TYPES.contains(&content_type.as_str()))
+        {
+            return Err(DbError::InvalidData(format!(
+                "Unknown content type `{}`; expected one of {}",
+                unknown,
+                CONTENT_TYPES.join(", ")
+            )));
+        }
+        if self.store.get_subscriptions(conversation_id).await?.len()
+            >= MAX_SUBSCRIPTIONS_PER_CONVERSATION
+        {
+            return Err(DbError::InvalidData(format!(
+                "A conversation can have at most {} webhook subscriptions",
+                MAX_SUBSCRIPTIONS_PER_CONVERSATION
+            )));
+        }
+
+        let created_at = Utc::now();
+        let secret: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LEN)
+            .map(char::from)
+            .collect();
+        let subscription = WebhookSubscription {
+            conversation_id,
+            subscription_id: Uuid::new_v4(),
+            url,
+            created_by,
+            created_at,
+            filter,
+            secret: Some(format!("{}{}", SECRET_PREFIX, secret)),
+            delivered_up_to: (created_at, Uuid::nil()),
+        };
+        self.store.insert_subscription(&subscription).await?;
+
+        Ok(subscription)
+    }
+
+    pub async fn get_subscriptions(
+        &self,
+        conversation_id: Uuid,
+    ) -> Result<Vec<WebhookSubscription>, DbError> {
+        self.store.get_subscriptions(conversation_id).await
+    }
+
+    async fn get_subscription(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+    ) -> Result<WebhookSubscription, DbError> {
+        self.store
+            .get_subscriptions(conversation_id)
+            .await?
+            .into_iter()
+            .find(|s| s.subscription_id == subscription_id)
+            .ok_or(DbError::NotFound)
+    }
+
+    pub async fn unsubscribe(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+    ) -> Result<(), DbError> {
+        self.get_subscription(conversation_id, subscription_id)
+            .await?;
+
+        self.store
+            .delete_subscription(conversation_id, subscription_id)
+            .await
+    }
+
+    /// Deliver the changes after `since` again, e.g. after the receiver
+    /// lost them. Only moves the subscription back.
+    pub async fn redeliver(
+        &self,
+        conversation_id: Uuid,
+        subscription_id: Uuid,
+        since: (DateTime<Utc>, Uuid),
+    ) -> Result<WebhookSubscription, DbError> {
+        let mut subscription = self
+            .get_subscription(conversation_id, subscription_id)
+            .await?;
+        if since > subscription.delivered_up_to {
+            return Err(DbError::InvalidData(
+                "`since` is after what was already delivered".to_string(),
+            ));
+        }
+
+        self.store
+            .set_delivered_up_to(conversation_id, subscription_id, since)
+            .await?;
+        subscription.delivered_up_to = since;
+
+        Ok(subscription)
+    }
+
+    /// Deliver what every subscription hasn't received yet, up to
+    /// `batch_size` changes each. Returns how many deliveries were made.
+    pub async fn deliver(&self) -> Result<usize, DbError> {
+        let mut delivered = 0;
+        for subscription in self.store.get_all_subscriptions().await? {
+            delivered += self.deliver_to(&subscription).await?;
+        }
+
+        Ok(delivered)
+    }
+
+    async fn deliver_to(&self, subscription: &WebhookSubscription) -> Result<usize, DbError> {
+        let conversation_id = subscription.conversation_id;
+        let (since, after_change_id) = subscription.delivered_up_to;
+        let changes = self
+            .changes
+            .get_changes_since(
+                conversation_id,
+                since,
+                after_change_id,
+                self.config.batch_size as i32,
+            )
+            .await?;
+        if changes.is_empty() {
+            return Ok(0);
+        }
+
+        let mut private = match PrivateMessages::for_viewer(
+            self.lineage.as_ref(),
+            conversation_id,
+            Some(&subscription.created_by),
+        )
+        .await
+        {
+            Ok(private) => private,
+            // Without the conversation, there's no telling what is private
+            Err(DbError::NotFound) => return Ok(0),
+            Err(e) => return Err(e),
+        };
+        let named: Vec<Uuid> = changes
+            .iter()
+            .filter_map(PrivateMessages::named_by_change)
+            .collect();
+        if !private.is_empty() && !named.is_empty() {
+            let messages = self
+                .lineage
+                .get_messages_by_ids(conversation_id, &named)
+                .await?;
+            private.resolve(&messages);
+        }
+
+        let mut delivered = 0;
+        let mut position = subscription.delivered_up_to;
+        for change in changes {
+            let changed = (change.changed_at, change.change_id);
+            // Hidden changes are passed over like filtered out ones
+            if let Some(change) = private.redact_change(change)
+                && subscription.filter.matches(&change)
+            {
+                if let Err(e) = self.post(subscription, &change).await {
+                    tracing::warn!(
+                        "Webhook delivery to subscription {} failed: {}",
+                        subscription.subscription_id,
+                        e
+                    );
+                    break;
+                }
+                delivered += 1;
+            }
+            position = changed;
+        }
+
+        if position != subscription.delivered_up_to {
+            self.store
+                .set_delivered_up_to(
+                    subscription.conversation_id,
+                    subscription.subscription_id,
+                    position,
+                )
+                .await?;
+        }
+
+        Ok(delivered)
+    }
+
+    async fn post(
+        &self,
+        subscription: &WebhookSubscription,
+        change: &Change,
+    ) -> Result<(), String> {
+        let body = serde_json::to_vec(&Delivery {
+            delivery_id: change.change_id,
+            subscription_id: subscription.subscription_id,
+            change,
+        })
+        .map_err(|e| e.to_string())?;
+
+        let delivery_id = change.change_id.to_string();
+        let timestamp = Utc::now().timestamp();
+        let timestamp_header = timestamp.to_string();
+        let mut headers = vec![
+            ("X-Webhook-Event", change.kind.as_str()),
+            ("X-Webhook-Id", delivery_id.as_str()),
+            ("X-Webhook-Timestamp", timestamp_header.as_str()),
+        ];
+        let signature = subscription
+            .secret
+            .as_deref()
+            .map(|secret| signature(secret, timestamp, &body));
+        if let Some(signature) = &signature {
+            headers.push(("X-Webhook-Signature", signature));
+        }
+
+        headers.push(("content-type", "application/json"));
+
+        let response = self
+            .client
+            .send(
+                "POST",
+                &subscription.url,
+                &headers,
+                body,
+                Duration::from_secs(self.config.timeout_secs),
+            )
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if response.is_success() {
+            Ok(())
+        } else {
+            Err(format!("{} answered {}", subscription.url, response.status))
+        }
+    }
+}
+
+#[async_trait]
+impl ScheduledTask for WebhookService {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn run(&self) -> Result<(), TaskError> {
+        let delivered = self.deliver().await?;
+        tracing::debug!("Delivered {} webhook events", delivered);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Settings;
+    use crate::domain::{ChangeKind, ContentType, Message, MessageRole};
+    use crate::repositories::Storage;
+    use crate::test_support::{ConversationBuilder, Fixture};
+    use std::collections::HashMap;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// A request as the receiver saw it
+    struct Received {
+        /// Lowercased names
+        headers: HashMap<String, String>,
+        raw_body: String,
+        body: serde_json::Value,
+    }
+
+    /// Receiver answering every request with `status`, forwarding them
+    async fn receiver(status: u16) -> (String, mpsc::UnboundedReceiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = Vec::new();
+                let mut buf = [0; 4096];
+                // The body ends the request; it's complete once it parses
+                let received = loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request);
+                    if let Some((head, raw_body)) = text.split_once("\r\n\r\n")
+                        && let Ok(body) = serde_json::from_str(raw_body)
+                    {
+                        let headers = head
+                            .lines()
+                            .skip(1)
+                            .filter_map(|line| line.split_once(':'))
+                            .map(|(name, value)| {
+                                (name.trim().to_lowercase(), value.trim().to_string())
+                            })
+                            .collect();
+                        break Received {
+                            headers,
+                            raw_body: raw_body.to_string(),
+                            body,
+                        };
+                    }
+                };
+                let _ = tx.send(received);
+                let response = format!("HTTP/1.0 {} OK\r\n\r\n", status);
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn service(storage: &Storage) -> WebhookService {
+        WebhookService::new(
+            storage.webhooks.clone(),
+            storage.changes.clone(),
+            storage.lineage.clone(),
+            WebhooksConfig {
+                interval_secs: 1,
+                batch_size: 100,
+                timeout_secs: 5,
+            },
+            FetchConfig {
+                allow_private_networks: true,
+                max_response_bytes: 1024,
+            },
+        )
+    }
+
+    /// A conversation of `user_a`'s: a reply to the root and a reply to it
+    async fn conversation(storage: &Storage) -> Fixture {
+        let fixture = ConversationBuilder::new("Test").with_messages(2, 1).build();
+        fixture.persist(storage).await.unwrap();
+        fixture
+    }
+
+    async fn record_change(storage: &Storage, kind: ChangeKind, message: &Message) -> Change {
+        let change = Change::new(
+            message.conversation_id,
+            kind,
+            message.message_id.to_string(),
+            Some(serde_json::to_value(message).unwrap()),
+        );
+        storage.changes.insert_change(&change).await.unwrap();
+        change
+    }
+
+    async fn record(storage: &Storage, conversation_id: Uuid, role: MessageRole) -> Change {
+        let mut message = Message::new_root(
+            conversation_id,
+            Uuid::new_v4(),
+            "Test".to_string(),
+            "user_a".to_string(),
+        );
+        message.role = role;
+        record_change(storage, ChangeKind::MessageCreated, &message).await
+    }
+
+    #[tokio::test]
+    async fn test_matching_changes_are_delivered_once_in_order() {
+        let storage = Storage::memory();
+        let service = service(&storage);
+        let cid = conversation(&storage).await.conversation_id();
+        let (url, mut received) = receiver(200).await;
+        let subscription = service
+            .subscribe(
+                cid,
+                url,
+                WebhookFilter {
+                    roles: vec![MessageRole::Assistant],
+                    ..Default::default()
+                },
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+
+        record(&storage, cid, MessageRole::Human).await;
+        let first = record(&storage, cid, MessageRole::Assistant).await;
+        let second = record(&storage, cid, MessageRole::Assistant).await;
+
+        assert_eq!(service.deliver().await.unwrap(), 2);
+        for expected in [&first, &second] {
+            let Received { body, .. } = received.recv().await.unwrap();
+            assert_eq!(
+                body["subscription_id"],
+                subscription.subscription_id.to_string()
+            );
+            assert_eq!(body["change"]["change_id"], expected.change_id.to_string());
+        }
+        assert_eq!(service.deliver().await.unwrap(), 0);
+
+        // Rewinding delivers the second change again, with the same ID
+        service
+            .redeliver(
+                cid,
+                subscription.subscription_id,
+                (first.changed_at, first.change_id),
+            )
+            .await
+            .unwrap();
+        assert_eq!(service.deliver().await.unwrap(), 1);
+        let Received { body, .. } = received.recv().await.unwrap();
+        assert_eq!(body["delivery_id"], second.change_id.to_string());
+        assert!(matches!(
+            service
+                .redeliver(
+                    cid,
+                    subscription.subscription_id,
+                    (Utc::now() + chrono::Duration::hours(1), Uuid::nil())
+                )
+                .await,
+            Err(DbError::InvalidData(_))
+        ));
+
+        service
+            .unsubscribe(cid, subscription.subscription_id)
+            .await
+            .unwrap();
+        record(&storage, cid, MessageRole::Assistant).await;
+        assert_eq!(service.deliver().await.unwrap(), 0);
+        assert!(matches!(
+            service.unsubscribe(cid, subscription.subscription_id).await,
+            Err(DbError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_private_messages_reach_only_the_owners_subscriptions() {
+        let storage = Storage::memory();
+        let service = service(&storage);
+        let fixture = conversation(&storage).await;
+        let cid = fixture.conversation_id();
+        let (private, below) = (fixture.message(&[0]), fixture.message(&[0, 0]));
+        let mut root = fixture.root().clone();
+        if let ContentType::Metadata(metadata) = &mut root.content {
+            metadata.private_message_ids = vec![private.message_id];
+        }
+        storage.lineage.insert_message(&root).await.unwrap();
+
+        let (owner_url, mut owner_received) = receiver(200).await;
+        let (admin_url, mut admin_received) = receiver(200).await;
+        service
+            .subscribe(
+                cid,
+                owner_url,
+                WebhookFilter::default(),
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap();
+        service
+            .subscribe(
+                cid,
+                admin_url,
+                WebhookFilter::default(),
+                "user_b".to_string(),
+            )
+            .await
+            .unwrap();
+        record_change(&storage, ChangeKind::MessageCreated, below).await;
+        let updated = record_change(&storage, ChangeKind::ConversationUpdated, &root).await;
+
+        assert_eq!(service.deliver().await.unwrap(), 3);
+        for _ in 0..2 {
+            let Received { raw_body, .. } = owner_received.recv().await.unwrap();
+            assert!(raw_body.contains(&private.message_id.to_string()));
+        }
+        let Received { raw_body, body, .. } = admin_received.recv().await.unwrap();
+        assert_eq!(body["change"]["change_id"], updated.change_id.to_string());
+        assert!(
+            !raw_body.contains(&private.message_id.to_string())
+                && !raw_body.contains(&below.message_id.to_string()),
+            "{}",
+            raw_body
+        );
+        // The hidden change is passed over, not retried
+        assert_eq!(service.deliver().await.unwrap(), 0);
+        assert!(admin_received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_internal_receivers_are_refused() {
+        let storage = Storage::memory();
+        let strict = WebhookService::new(
+            storage.webhooks.clone(),
+            storage.changes.clone(),
+            storage.lineage.clone(),
+            service(&storage).config,
+            Settings::default().fetch,
+        );
+        let cid = conversation(&storage).await.conversation_id();
+        let (url, mut received) = receiver(200).await;
+
+        let err = strict
+            .subscribe(
+                cid,
+                url.clone(),
+                WebhookFilter::default(),
+                "user_a".to_string(),
+            )
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("internal address"), "{}", err);
+
+        // Subscriptions stored before are checked again when delivering
+        service(&storage)
+            .subscribe(cid, url, WebhookFilter::default(), "user_a".to_string())
+            .await
+            .unwrap();
+        record(&storage, cid, MessageRole::Assistant).await;
+        assert_eq!(strict.deliver().await.unwrap(), 0);
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried() {
+        let storage = Storage::memory();
+        let service = service(&storage);
+        let cid = conversation(&storage).await.conversation_id();
+        let (url, mut received) = receiver(503).await;
+        service
+            .subscribe(cid, url, WebhookFilter::default(), "user_a".to_string())
+            .await
+            .unwrap();
+        let change = record(&storage, cid, MessageRole::Assistant).await;
+
+        assert_eq!(service.deliver().await.unwrap(), 0);
+        assert_eq!(service.deliver().await.unwrap(), 0);
+        for _ in 0..2 {
+            let Received { body, .. } = received.recv().await.unwrap();
+            assert_eq!(body["change"]["change_id"], change.change_id.to_string());
+        }
+
+        assert!(matches!(
+            service
+                .subscribe(
+                    cid,
+                    "ftp://example.com/hook".to_string(),
+                    WebhookFilter::default(),
+                    "user_a".to_string()
+                )
+                .await,
+            Err(DbError::InvalidData(_))
+        ));
+        assert!(matches!(
+            service
+                .subscribe(
+                    cid,
+                    "http://example.com/hook".to_string(),
+                    WebhookFilter {
+                        content_types: vec!["video".to_string()],
+                        ..Default::default()
+                    },
+                    "user_a".to_string()
+                )
+                .await,
+            Err(DbError::InvalidData(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_deliveries_are_signed_with_the_subscription_secret() {
+        let storage = Storage::memory();
+        let service = service(&storage);
+        let cid = conversation(&storage).await.conversation_id();
+        let (url, mut received) = receiver(200).await;
+        let subscription = service
+            .subscribe(cid, url, WebhookFilter::default(), "user_a".to_string())
+            .await
+            .unwrap();
+        let secret = subscription.secret.clone().unwrap();
+        assert!(secret.starts_with(SECRET_PREFIX));
+        let change = record(&storage, cid, MessageRole::Assistant).await;
+
+        assert_eq!(service.deliver().await.unwrap(), 1);
+        let Received {
+            headers, raw_body, ..
+        } = received.recv().await.unwrap();
+        assert_eq!(headers["x-webhook-id"], change.change_id.to_string());
+        let timestamp: i64 = headers["x-webhook-timestamp"].parse().unwrap();
+        assert!((Utc::now().timestamp() - timestamp).abs() < 60);
+        assert_eq!(
+            headers["x-webhook-signature"],
+            signature(&secret, timestamp, raw_body.as_bytes())
+        );
+        assert_ne!(
+            headers["x-webhook-signature"],
+            signature("whsec_other", timestamp, raw_body.as_bytes())
+        );
+    }
+}
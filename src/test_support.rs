@@ -0,0 +1,386 @@
+//! Deterministic fixtures for tests: conversations with message trees and
+//! branches built in one expression, optionally written to a [`Storage`].
+//!
+//! Compiled for this crate's tests and, with the `test_support` feature,
+//! for other crates' tests. The same builder and seed always give the same
+//! IDs, timestamps and contents.
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use uuid::{Builder, Uuid};
+
+use crate::api::AppState;
+use crate::config::Settings;
+use crate::db::DbError;
+use crate::domain::{
+    Branch, ContentMetadata, ContentType, Conversation, Message, MessageRole, TextContent, slugify,
+};
+use crate::object_store::memory::MemoryObjectStore;
+use crate::repositories::Storage;
+use crate::services::conversation_service::count_new_messages;
+use crate::utils::compute_lineage;
+
+/// Builds a [`Fixture`]: a conversation whose root has `width` replies,
+/// each with `width` replies of its own, `depth` levels down, and branches
+/// ending at chosen messages
+#[derive(Debug, Clone)]
+pub struct ConversationBuilder {
+    title: String,
+    owner: String,
+    seed: u64,
+    start: DateTime<Utc>,
+    depth: usize,
+    width: usize,
+    branches: Vec<(String, Vec<usize>)>,
+}
+
+impl ConversationBuilder {
+    /// A conversation with only its root, owned by `user_a`, created at
+    /// 2025-01-01 00:00 UTC
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.to_string(),
+            owner: "user_a".to_string(),
+            seed: 0,
+            start: Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap(),
+            depth: 0,
+            width: 0,
+            branches: Vec::new(),
+        }
+    }
+
+    /// Create the conversation, its messages and branches as `owner`
+    pub fn with_owner(mut self, owner: &str) -> Self {
+        self.owner = owner.to_string();
+        self
+    }
+
+    /// Derive IDs from `seed`, so fixtures built in the same test don't
+    /// collide
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Create the root at `start`; each further message comes a second
+    /// after the previous one
+    pub fn with_start(mut self, start: DateTime<Utc>) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// A full tree below the root, `depth` levels of `width` replies each.
+    /// Replies alternate between human and assistant, humans first.
+    pub fn with_messages(mut self, depth: usize, width: usize) -> Self {
+        self.depth = depth;
+        self.width = width;
+        self
+    }
+
+    /// A branch named `name` ending at the message reached by following
+    /// `path` from the root, e.g. `&[0, 1]` for the second reply to the
+    /// first reply. An empty path ends at the root.
+    pub fn with_branch(mut self, name: &str, path: &[usize]) -> Self {
+        self.branches.push((name.to_string(), path.to_vec()));
+        self
+    }
+
+    /// Build the fixture.
+    ///
+    /// # Panics
+    ///
+    /// If a branch path leaves the tree.
+    pub fn build(self) -> Fixture {
+        let mut ids = Ids::new(self.seed);
+        let mut at = self.start;
+        let conversation_id = ids.random();
+
+        let root_id = ids.time_ordered(at);
+        let mut root = Message::new_root(
+            conversation_id,
+            root_id,
+            self.title.clone(),
+            self.owner.clone(),
+        );
+        root.created_at = at;
+
+        // Preorder, so every parent is created before its replies
+        let mut messages = vec![(Vec::new(), root)];
+        let mut stack: Vec<usize> = vec![0];
+        while let Some(parent) = stack.pop() {
+            let (path, parent_message) = &messages[parent];
+            if path.len() == self.depth {
+                continue;
+            }
+            let (path, parent_message) = (path.clone(), parent_message.clone());
+            let first_child = messages.len();
+            for i in 0..self.width {
+                at += Duration::seconds(1);
+                let mut child_path = path.clone();
+                child_path.push(i);
+                let message_id = ids.time_ordered(at);
+                let role = match child_path.len() % 2 {
+                    1 => MessageRole::Human,
+                    _ => MessageRole::Assistant,
+                };
+                let message = Message {
+                    conversation_id,
+                    message_id,
+                    parent_message_id: Some(parent_message.message_id),
+                    role,
+                    content: ContentType::Text(TextContent {
+                        text: format!("Message {}", path_label(&child_path)),
+                    }),
+                    content_metadata: ContentMetadata::new(),
+                    lineage: compute_lineage(&parent_message.lineage, message_id),
+                    created_at: at,
+                    created_by: self.owner.clone(),
+                };
+                messages.push((child_path, message));
+            }
+            stack.extend((first_child..messages.len()).rev());
+        }
+
+        let (paths, messages): (Vec<_>, Vec<_>) = messages.into_iter().unzip();
+        let mut fixture = Fixture {
+            conversation: Conversation {
+                conversation_id,
+                root_message: messages[0].clone(),
+            },
+            messages,
+            paths,
+            branches: Vec::new(),
+        };
+        for (name, path) in self.branches {
+            at += Duration::seconds(1);
+            let leaf = fixture.message(&path).message_id;
+            fixture.branches.push(Branch {
+                conversation_id,
+                branch_id: ids.random(),
+                slug: slugify(&name),
+                branch_name: name,
+                leaf_message_id: leaf,
+                created_at: at,
+                last_updated: at,
+                created_by: self.owner.clone(),
+                is_active: true,
+            });
+        }
+
+        fixture
+    }
+}
+
+/// A conversation built by a [`ConversationBuilder`]
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub conversation: Conversation,
+    /// Every message, the root first, each parent before its replies and
+    /// replies in creation order
+    pub messages: Vec<Message>,
+    paths: Vec<Vec<usize>>,
+    pub branches: Vec<Branch>,
+}
+
+impl Fixture {
+    pub fn conversation_id(&self) -> Uuid {
+        self.conversation.conversation_id
+    }
+
+    pub fn root(&self) -> &Message {
+        &self.conversation.root_message
+    }
+
+    /// The message reached by following `path` from the root, as in
+    /// [`ConversationBuilder::with_branch`]
+    ///
+    /// # Panics
+    ///
+    /// If `path` leaves the tree.
+    pub fn message(&self, path: &[usize]) -> &Message {
+        let i = self
+            .paths
+            .iter()
+            .position(|p| p == path)
+            .unwrap_or_else(|| panic!("no message at {:?}", path));
+        &self.messages[i]
+    }
+
+    /// Messages without replies, in tree order
+    pub fn leaves(&self) -> Vec<&Message> {
+        self.messages
+            .iter()
+            .filter(|m| {
+                !self
+                    .messages
+                    .iter()
+                    .any(|other| other.parent_message_id == Some(m.message_id))
+            })
+            .collect()
+    }
+
+    /// The branch named `name`
+    ///
+    /// # Panics
+    ///
+    /// If there is none.
+    pub fn branch(&self, name: &str) -> &Branch {
+        self.branches
+            .iter()
+            .find(|b| b.branch_name == name)
+            .unwrap_or_else(|| panic!("no branch named {}", name))
+    }
+
+    /// Write the messages, counted, and branches through the stores of `storage`
+    pub async fn persist(&self, storage: &Storage) -> Result<(), DbError> {
+        storage
+            .lineage
+            .batch_insert_messages(&self.messages)
+            .await?;
+        count_new_messages(
+            storage.lineage.as_ref(),
+            self.conversation.conversation_id,
+            &self.messages,
+        )
+        .await?;
+        for branch in &self.branches {
+            storage.branches.insert_branch(branch).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Everything the router needs, wired as the server wires it over `storage`
+/// and default settings, with objects kept in memory
+pub fn app_state(storage: &Storage) -> AppState {
+    AppState::build(
+        storage,
+        &Settings::default(),
+        Arc::new(MemoryObjectStore::default()),
+        None,
+        CancellationToken::new(),
+    )
+}
+
+/// `0.1` for the second reply to the first reply
+fn path_label(path: &[usize]) -> String {
+    path.iter()
+        .map(usize::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// IDs derived from a seed and a counter (splitmix64)
+struct Ids {
+    state: u64,
+}
+
+impl Ids {
+    fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn bytes(&mut self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.next().to_le_bytes());
+        bytes[8..].copy_from_slice(&self.next().to_le_bytes());
+        bytes
+    }
+
+    /// A version 4 UUID, like conversation and branch IDs
+    fn random(&mut self) -> Uuid {
+        Builder::from_random_bytes(self.bytes()).into_uuid()
+    }
+
+    /// A version 7 UUID for `at`, like message IDs
+    fn time_ordered(&mut self, at: DateTime<Utc>) -> Uuid {
+        let bytes = self.bytes();
+        Builder::from_unix_timestamp_millis(
+            at.timestamp_millis() as u64,
+            &bytes[..10].try_into().unwrap(),
+        )
+        .into_uuid()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builds_full_deterministic_trees() {
+        let build = || {
+            ConversationBuilder::new("Sauces")
+                .with_messages(3, 2)
+                .with_branch("main", &[0, 1, 0])
+                .build()
+        };
+        let fixture = build();
+
+        assert_eq!(fixture.messages.len(), 1 + 2 + 4 + 8);
+        assert_eq!(fixture.leaves().len(), 8);
+        let leaf = fixture.message(&[0, 1, 0]);
+        assert_eq!(leaf.depth(), 4);
+        assert_eq!(leaf.role, MessageRole::Human);
+        assert_eq!(
+            leaf.content,
+            ContentType::Text(TextContent {
+                text: "Message 0.1.0".to_string()
+            })
+        );
+        assert_eq!(fixture.branch("main").leaf_message_id, leaf.message_id);
+        assert!(
+            fixture
+                .messages
+                .windows(2)
+                .all(|w| w[0].created_at < w[1].created_at && w[0].message_id < w[1].message_id)
+        );
+
+        let again = build();
+        let ids = |f: &Fixture| f.messages.iter().map(|m| m.message_id).collect::<Vec<_>>();
+        assert_eq!(ids(&fixture), ids(&again));
+        assert_eq!(fixture.branches[0].branch_id, again.branches[0].branch_id);
+        let other = ConversationBuilder::new("Sauces").with_seed(1).build();
+        assert_ne!(other.conversation_id(), fixture.conversation_id());
+    }
+
+    #[tokio::test]
+    async fn test_fixtures_persist_through_the_stores() {
+        let storage = Storage::memory();
+        let fixture = ConversationBuilder::new("Sauces")
+            .with_owner("user_b")
+            .with_messages(2, 3)
+            .with_branch("retry", &[2])
+            .build();
+
+        fixture.persist(&storage).await.unwrap();
+
+        let stored = storage
+            .lineage
+            .get_all_messages(fixture.conversation_id())
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), fixture.messages.len());
+        let branches = storage
+            .branches
+            .get_branches_by_conversation(fixture.conversation_id())
+            .await
+            .unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(
+            branches[0].leaf_message_id,
+            fixture.message(&[2]).message_id
+        );
+        assert_eq!(branches[0].created_by, "user_b");
+    }
+}
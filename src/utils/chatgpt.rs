@@ -0,0 +1,433 @@
+//! Conversion of the official ChatGPT data-export (`conversations.json`) into our tree model
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::domain::content::ImageBatchItem;
+use crate::domain::{
+    Branch, ContentType, Conversation, ImageBatchContent, ImageContent, Message, MessageRole,
+    TextContent, ToolCallContent, ToolResultContent,
+};
+
+use super::{compute_lineage, message_id_at};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatGptConversation {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub create_time: Option<f64>,
+    #[serde(default)]
+    pub mapping: HashMap<String, ChatGptNode>,
+    #[serde(default)]
+    pub current_node: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatGptNode {
+    #[serde(default)]
+    pub message: Option<ChatGptMessage>,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub children: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatGptMessage {
+    pub id: String,
+    pub author: ChatGptAuthor,
+    #[serde(default)]
+    pub create_time: Option<f64>,
+    pub content: ChatGptContent,
+    #[serde(default)]
+    pub recipient: Option<String>,
+    #[serde(default)]
+    pub metadata: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatGptAuthor {
+    pub role: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatGptContent {
+    pub content_type: String,
+    #[serde(default)]
+    pub parts: Vec<serde_json::Value>,
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// A ChatGPT conversation converted into our model, ready to be persisted
+#[derive(Debug, Clone)]
+pub struct ImportedConversation {
+    pub conversation: Conversation,
+    /// All messages including the root, parents before children
+    pub messages: Vec<Message>,
+    pub branches: Vec<Branch>,
+}
+
+/// Convert one exported ChatGPT conversation. Every visible node becomes a message;
+/// hidden or empty nodes are skipped and their children attached to the nearest kept ancestor,
+/// so the original branch points are preserved. One branch is created per leaf, the leaf the
+/// user was last on being named "main".
+pub fn convert_conversation(
+    source: &ChatGptConversation,
+    created_by: &str,
+) -> ImportedConversation {
+    let mut conversation = Conversation::new(
+        source
+            .title
+            .clone()
+            .filter(|t| !t.trim().is_empty())
+            .unwrap_or_else(|| "Imported conversation".to_string()),
+        created_by.to_string(),
+    );
+    if let Some(created_at) = source.create_time.and_then(timestamp) {
+        conversation.root_message.created_at = created_at;
+    }
+    conversation
+        .root_message
+        .content_metadata
+        .insert("source".to_string(), "chatgpt".to_string());
+
+    let conversation_id = conversation.conversation_id;
+    let root = conversation.root_message.clone();
+
+    let mut messages = vec![root.clone()];
+    // Original node id -> id of the message it maps to (itself or nearest kept ancestor)
+    let mut resolved: HashMap<&str, Uuid> = HashMap::new();
+    let mut has_children: HashMap<Uuid, bool> = HashMap::new();
+
+    let mut roots: Vec<&str> = source
+        .mapping
+        .iter()
+        .filter(|(_, node)| {
+            node.parent
+                .as_deref()
+                .is_none_or(|parent| !source.mapping.contains_key(parent))
+        })
+        .map(|(id, _)| id.as_str())
+        .collect();
+    roots.sort();
+
+    // Messages are created depth-first; each pending node carries the index of its parent in
+    // `kept` (usize::MAX for the root) and the original id of that parent
+    let mut kept: Vec<Message> = Vec::new();
+    let mut pending: Vec<(&str, usize, Option<&str>)> = roots
+        .into_iter()
+        .rev()
+        .map(|id| (id, usize::MAX, None))
+        .collect();
+
+    while let Some((node_id, parent_index, parent_original)) = pending.pop() {
+        let Some(node) = source.mapping.get(node_id) else {
+            continue;
+        };
+        let parent = if parent_index == usize::MAX {
+            &root
+        } else {
+            &kept[parent_index]
+        };
+
+        let converted = node
+            .message
+            .as_ref()
+            .filter(|m| !is_hidden(m))
+            .and_then(|m| {
+                let role = convert_role(&m.author.role)?;
+                let content = convert_content(m, parent_original)?;
+                Some((m, role, content))
+            });
+
+        let (next_parent_index, next_parent_original) = match converted {
+            Some((source_message, role, content)) => {
+                let created_at = source_message
+                    .create_time
+                    .and_then(timestamp)
+                    .unwrap_or(root.created_at);
+                let message_id = message_id_at(created_at);
+                let mut content_metadata = HashMap::new();
+                content_metadata.insert("source_message_id".to_string(), source_message.id.clone());
+                if let Some(model) = source_message
+                    .metadata
+                    .get("model_slug")
+                    .and_then(|v| v.as_str())
+                {
+                    content_metadata.insert("model".to_string(), model.to_string());
+                }
+
+                let message = Message {
+                    conversation_id,
+                    message_id,
+                    parent_message_id: Some(parent.message_id),
+                    created_by: author_name(&role, source_message, created_by),
+                    role,
+                    content,
+                    content_metadata,
+                    lineage: compute_lineage(&parent.lineage, message_id),
+                    created_at,
+                };
+
+                has_children.insert(parent.message_id, true);
+                resolved.insert(node_id, message_id);
+                kept.push(message);
+                (kept.len() - 1, Some(source_message.id.as_str()))
+            }
+            None => {
+                resolved.insert(node_id, parent.message_id);
+                (parent_index, parent_original)
+            }
+        };
+
+        for child in node.children.iter().rev() {
+            pending.push((child.as_str(), next_parent_index, next_parent_original));
+        }
+    }
+
+    let current_leaf = source
+        .current_node
+        .as_deref()
+        .and_then(|id| resolved.get(id))
+        .copied();
+
+    let mut branches = Vec::new();
+    let mut alternate = 0;
+    for message in kept
+        .iter()
+        .filter(|m| !has_children.contains_key(&m.message_id))
+    {
+        let name = if Some(message.message_id) == current_leaf {
+            "main".to_string()
+        } else {
+            alternate += 1;
+            format!("Alternate {}", alternate)
+        };
+        branches.push(Branch::new(
+            conversation_id,
+            name,
+            message.message_id,
+            created_by.to_string(),
+        ));
+    }
+
+    messages.extend(kept);
+
+    ImportedConversation {
+        conversation,
+        messages,
+        branches,
+    }
+}
+
+fn timestamp(seconds: f64) -> Option<DateTime<Utc>> {
+    Utc.timestamp_millis_opt((seconds * 1000.0) as i64).single()
+}
+
+fn is_hidden(message: &ChatGptMessage) -> bool {
+    message
+        .metadata
+        .get("is_visually_hidden_from_conversation")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn convert_role(role: &str) -> Option<MessageRole> {
+    match role {
+        "user" => Some(MessageRole::Human),
+        "assistant" => Some(MessageRole::Assistant),
+        "system" => Some(MessageRole::System),
+        "tool" => Some(MessageRole::Tool),
+        _ => None,
+    }
+}
+
+fn author_name(role: &MessageRole, message: &ChatGptMessage, importer: &str) -> String {
+    match role {
+        MessageRole::Human => importer.to_string(),
+        MessageRole::Assistant => message
+            .metadata
+            .get("model_slug")
+            .and_then(|v| v.as_str())
+            .unwrap_or("assistant")
+            .to_string(),
+        MessageRole::Tool => message
+            .author
+            .name
+            .clone()
+            .unwrap_or_else(|| "tool".to_string()),
+        _ => role.as_str().to_string(),
+    }
+}
+
+fn convert_content(message: &ChatGptMessage, parent_original: Option<&str>) -> Option<ContentType> {
+    let content = &message.content;
+    let text = content
+        .text
+        .clone()
+        .unwrap_or_else(|| text_parts(&content.parts).join("\n"));
+
+    match content.content_type.as_str() {
+        "code" => match message.recipient.as_deref() {
+            Some(recipient) if recipient != "all" => Some(ContentType::ToolCall(ToolCallContent {
+                tool_name: recipient.to_string(),
+                arguments: serde_json::json!({ "code": text }),
+                tool_call_id: message.id.clone(),
+            })),
+            _ => non_empty_text(format!("```\n{}\n```", text)),
+        },
+        "execution_output" => Some(ContentType::ToolResult(ToolResultContent {
+            tool_call_id: parent_original.unwrap_or_default().to_string(),
+            result: serde_json::json!({ "output": text }),
+            success: true,
+        })),
+        "multimodal_text" => {
+            let images: Vec<String> = content
+                .parts
+                .iter()
+                .filter_map(|part| part.get("asset_pointer").and_then(|v| v.as_str()))
+                .map(str::to_string)
+                .collect();
+            let text = text_parts(&content.parts).join("\n");
+
+            if !text.trim().is_empty() {
+                let links: Vec<String> = images
+                    .iter()
+                    .map(|url| format!("[image]({})", url))
+                    .collect();
+                let mut text = text;
+                if !links.is_empty() {
+                    text.push_str("\n\n");
+                    text.push_str(&links.join("\n"));
+                }
+                non_empty_text(text)
+            } else if images.len() == 1 {
+                Some(ContentType::Image(ImageContent {
+                    image_url: images[0].clone(),
+                    thumbnail_url: None,
+                    width: None,
+                    height: None,
+                    mime_type: None,
+                    size_bytes: None,
+                }))
+            } else if !images.is_empty() {
+                Some(ContentType::ImageBatch(ImageBatchContent {
+                    images: images
+                        .into_iter()
+                        .map(|image_url| ImageBatchItem {
+                            image_url,
+                            prompt: None,
+                            model: None,
+                        })
+                        .collect(),
+                }))
+            } else {
+                None
+            }
+        }
+        _ => non_empty_text(text),
+    }
+}
+
+fn text_parts(parts: &[serde_json::Value]) -> Vec<String> {
+    parts
+        .iter()
+        .filter_map(|part| part.as_str())
+        .map(str::to_string)
+        .collect()
+}
+
+fn non_empty_text(text: String) -> Option<ContentType> {
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(ContentType::Text(TextContent { text }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_preserves_branch_points() {
+        let export = serde_json::json!({
+            "title": "Logo generation",
+            "create_time": 1700000000.0,
+            "current_node": "b2",
+            "mapping": {
+                "root": { "message": null, "parent": null, "children": ["sys"] },
+                "sys": {
+                    "message": {
+                        "id": "sys",
+                        "author": { "role": "system" },
+                        "content": { "content_type": "text", "parts": [""] },
+                        "metadata": { "is_visually_hidden_from_conversation": true }
+                    },
+                    "parent": "root",
+                    "children": ["q"]
+                },
+                "q": {
+                    "message": {
+                        "id": "q",
+                        "author": { "role": "user" },
+                        "content": { "content_type": "text", "parts": ["Draw a fox"] }
+                    },
+                    "parent": "sys",
+                    "children": ["b1", "b2"]
+                },
+                "b1": {
+                    "message": {
+                        "id": "b1",
+                        "author": { "role": "assistant" },
+                        "content": { "content_type": "text", "parts": ["A red fox"] },
+                        "metadata": { "model_slug": "gpt-4o" }
+                    },
+                    "parent": "q",
+                    "children": []
+                },
+                "b2": {
+                    "message": {
+                        "id": "b2",
+                        "author": { "role": "assistant" },
+                        "content": { "content_type": "text", "parts": ["A blue fox"] }
+                    },
+                    "parent": "q",
+                    "children": []
+                }
+            }
+        });
+        let source: ChatGptConversation = serde_json::from_value(export).unwrap();
+
+        let imported = convert_conversation(&source, "user_a");
+
+        assert_eq!(imported.conversation.title().unwrap(), "Logo generation");
+        assert_eq!(imported.messages.len(), 4);
+
+        let question = &imported.messages[1];
+        assert_eq!(question.role, MessageRole::Human);
+        assert_eq!(question.lineage.len(), 2);
+
+        let answers: Vec<&Message> = imported.messages[2..].iter().collect();
+        assert!(
+            answers
+                .iter()
+                .all(|m| m.parent_message_id == Some(question.message_id))
+        );
+        assert_eq!(answers[0].content_metadata.get("model").unwrap(), "gpt-4o");
+
+        assert_eq!(imported.branches.len(), 2);
+        let main = imported
+            .branches
+            .iter()
+            .find(|b| b.branch_name == "main")
+            .unwrap();
+        assert_eq!(main.leaf_message_id, answers[1].message_id);
+    }
+}
@@ -0,0 +1,115 @@
+use ring::digest;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::domain::Message;
+
+use super::hex::hex;
+
+/// Stable hash of serialized message content (SHA-256, hex encoded). Rows
+/// written before it was SHA-256 keep their 16-digit FNV-1a hash; nothing
+/// compares stored hashes, so they needn't be rewritten.
+pub fn content_hash(content_data: &str) -> String {
+    hex(digest::digest(&digest::SHA256, content_data.as_bytes()))
+}
+
+/// Collapse sibling messages with the same role and identical content into
+/// the first of them, e.g. regenerations that produced the same output.
+/// Children of a dropped duplicate move under the message it was merged into.
+/// `messages` must list parents before children. Returns the remaining
+/// messages and a map from each dropped message to the one it was merged into.
+pub fn dedup_identical_siblings(messages: Vec<Message>) -> (Vec<Message>, HashMap<Uuid, Uuid>) {
+    let mut merged: HashMap<Uuid, Uuid> = HashMap::new();
+    let mut seen: HashMap<(Option<Uuid>, String, String), Uuid> = HashMap::new();
+    let mut kept = Vec::with_capacity(messages.len());
+
+    for mut message in messages {
+        let remap = |id: Uuid| merged.get(&id).copied().unwrap_or(id);
+        message.parent_message_id = message.parent_message_id.map(remap);
+        message.lineage = message.lineage.iter().copied().map(remap).collect();
+
+        // Content that can't be serialized can't be compared; keep it as is
+        if let Ok(content_data) = message.content.to_json_string() {
+            let key = (
+                message.parent_message_id,
+                message.role.as_str().to_string(),
+                content_data,
+            );
+            if let Some(&first) = seen.get(&key) {
+                merged.insert(message.message_id, first);
+                continue;
+            }
+            seen.insert(key, message.message_id);
+        }
+        kept.push(message);
+    }
+
+    (kept, merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ContentType, Conversation, MessageRole, TextContent};
+    use crate::utils::{compute_lineage, new_message_id};
+
+    fn reply(parent: &Message, role: MessageRole, text: &str) -> Message {
+        let message_id = new_message_id();
+        Message {
+            conversation_id: parent.conversation_id,
+            message_id,
+            parent_message_id: Some(parent.message_id),
+            role,
+            content: ContentType::Text(TextContent {
+                text: text.to_string(),
+            }),
+            content_metadata: HashMap::new(),
+            lineage: compute_lineage(&parent.lineage, message_id),
+            created_at: chrono::Utc::now(),
+            created_by: "user_a".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_content_hash_is_stable() {
+        assert_eq!(
+            content_hash(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            content_hash("a"),
+            "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb"
+        );
+    }
+
+    #[test]
+    fn test_dedup_merges_identical_siblings_and_moves_children() {
+        let root = Conversation::new("Test".to_string(), "user_a".to_string()).root_message;
+        let question = reply(&root, MessageRole::Human, "Draw a cat");
+        let first = reply(&question, MessageRole::Assistant, "cat.png");
+        let repeat = reply(&question, MessageRole::Assistant, "cat.png");
+        let other = reply(&question, MessageRole::Assistant, "dog.png");
+        let follow_up = reply(&repeat, MessageRole::Human, "Bigger");
+
+        let (kept, merged) = dedup_identical_siblings(vec![
+            root,
+            question,
+            first.clone(),
+            repeat.clone(),
+            other,
+            follow_up.clone(),
+        ]);
+
+        assert_eq!(kept.len(), 5);
+        assert_eq!(
+            merged,
+            HashMap::from([(repeat.message_id, first.message_id)])
+        );
+        let moved = kept
+            .iter()
+            .find(|m| m.message_id == follow_up.message_id)
+            .unwrap();
+        assert_eq!(moved.parent_message_id, Some(first.message_id));
+        assert_eq!(moved.lineage[moved.lineage.len() - 2], first.message_id);
+    }
+}
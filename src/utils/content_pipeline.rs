@@ -0,0 +1,393 @@
+//! Processing of message content before it is stored: cleanups and checks
+//! every new message goes through, chosen per deployment
+
+use crate::config::{ContentConfig, ContentProcessorKind};
+use crate::domain::{ContentType, LANGUAGE_KEY, Message};
+use crate::utils::language;
+use crate::utils::url::scheme;
+
+/// Query parameters dropped from image URLs besides `utm_*` ones
+const TRACKING_PARAMS: &[&str] = &[
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid", "mc_cid",
+    "mc_eid", "_ga", "_gl",
+];
+
+/// One step of the pipeline. Implement it to plug in a processor of your
+/// own next to the built-in ones.
+pub trait ContentProcessor: Send + Sync {
+    /// Rewrite `content` in place, or refuse it with the reason why
+    fn process(&self, content: &mut ContentType) -> Result<(), String>;
+}
+
+/// Trims whitespace around text and summaries
+pub struct TrimWhitespace;
+
+impl ContentProcessor for TrimWhitespace {
+    fn process(&self, content: &mut ContentType) -> Result<(), String> {
+        let text = match content {
+            ContentType::Text(content) => &mut content.text,
+            ContentType::Summary(content) => &mut content.text,
+            _ => return Ok(()),
+        };
+        if text.trim().len() != text.len() {
+            *text = text.trim().to_string();
+        }
+
+        Ok(())
+    }
+}
+
+/// Normalizes the Markdown of text: Unix line endings, no trailing
+/// whitespace except the two spaces of a hard break, and at most one blank
+/// line in a row. Fenced code blocks keep their whitespace.
+pub struct NormalizeMarkdown;
+
+impl ContentProcessor for NormalizeMarkdown {
+    fn process(&self, content: &mut ContentType) -> Result<(), String> {
+        if let ContentType::Text(content) = content {
+            content.text = normalize_markdown(&content.text);
+        }
+
+        Ok(())
+    }
+}
+
+fn normalize_markdown(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let mut normalized = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut blank_run = 0;
+
+    for line in text.split('\n') {
+        let marker = line.trim_start();
+        let marker = ["```", "~~~"]
+            .into_iter()
+            .find(|fence| marker.starts_with(fence));
+        if let Some(open) = fence {
+            normalized.push(line.to_string());
+            if marker == Some(open) {
+                fence = None;
+            }
+            continue;
+        }
+        fence = marker;
+
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() {
+            blank_run += 1;
+            if blank_run == 1 {
+                normalized.push(String::new());
+            }
+            continue;
+        }
+        blank_run = 0;
+        if line.ends_with("  ") && fence.is_none() {
+            normalized.push(format!("{}  ", trimmed));
+        } else {
+            normalized.push(trimmed.to_string());
+        }
+    }
+
+    normalized.join("\n")
+}
+
+/// Drops `utm_*`, `fbclid` and other tracking parameters from image URLs
+pub struct StripTrackingParams;
+
+impl ContentProcessor for StripTrackingParams {
+    fn process(&self, content: &mut ContentType) -> Result<(), String> {
+        for url in image_urls(content) {
+            *url = strip_tracking_params(url);
+        }
+
+        Ok(())
+    }
+}
+
+fn strip_tracking_params(url: &str) -> String {
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let Some((base, query)) = url.split_once('?') else {
+        return match fragment {
+            Some(fragment) => format!("{}#{}", url, fragment),
+            None => url.to_string(),
+        };
+    };
+
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+            let name = param
+                .split('=')
+                .next()
+                .unwrap_or_default()
+                .to_ascii_lowercase();
+            !name.is_empty() && !name.starts_with("utm_") && !TRACKING_PARAMS.contains(&&*name)
+        })
+        .collect();
+
+    let mut stripped = base.to_string();
+    if !kept.is_empty() {
+        stripped.push('?');
+        stripped.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        stripped.push('#');
+        stripped.push_str(fragment);
+    }
+
+    stripped
+}
+
+/// Refuses image URLs and Markdown link targets with a scheme that isn't
+/// allowed. Relative links have no scheme and pass.
+pub struct UrlSchemes {
+    allowed: Vec<String>,
+}
+
+impl UrlSchemes {
+    pub fn new(allowed: &[String]) -> Self {
+        Self {
+            allowed: allowed.iter().map(|s| s.to_ascii_lowercase()).collect(),
+        }
+    }
+
+    fn check(&self, url: &str) -> Result<(), String> {
+        match scheme(url) {
+            Some(scheme) if !self.allowed.contains(&scheme.to_ascii_lowercase()) => Err(format!(
+                "URL scheme `{}` is not allowed (allowed: {})",
+                scheme,
+                self.allowed.join(", ")
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl ContentProcessor for UrlSchemes {
+    fn process(&self, content: &mut ContentType) -> Result<(), String> {
+        if let ContentType::Text(content) = content {
+            for target in markdown_link_targets(&content.text) {
+                self.check(target)?;
+            }
+        }
+        for url in image_urls(content) {
+            self.check(url)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Targets of inline links and images, `[text](target "title")`
+fn markdown_link_targets(text: &str) -> Vec<&str> {
+    text.match_indices("](")
+        .filter_map(|(i, _)| {
+            let rest = &text[i + 2..];
+            let rest = rest.trim_start();
+            let rest = rest.strip_prefix('<').unwrap_or(rest);
+            let end = rest.find(|c: char| c == ')' || c == '>' || c.is_whitespace())?;
+            Some(&rest[..end])
+        })
+        .filter(|target| !target.is_empty())
+        .collect()
+}
+
+/// The image URLs of image and image batch content
+fn image_urls(content: &mut ContentType) -> Vec<&mut String> {
+    match content {
+        ContentType::Image(image) => std::iter::once(&mut image.image_url)
+            .chain(image.thumbnail_url.as_mut())
+            .collect(),
+        ContentType::ImageBatch(batch) => batch
+            .images
+            .iter_mut()
+            .map(|item| &mut item.image_url)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Runs the processors it was built with over new messages, in order
+#[derive(Default)]
+pub struct ContentPipeline {
+    processors: Vec<Box<dyn ContentProcessor>>,
+    detect_language: bool,
+}
+
+impl ContentPipeline {
+    /// A pipeline of the built-in processors in `content.processors`
+    pub fn new(config: &ContentConfig) -> Self {
+        let mut pipeline = Self {
+            detect_language: config.detect_language,
+            ..Self::default()
+        };
+        for kind in &config.processors {
+            pipeline.processors.push(match kind {
+                ContentProcessorKind::TrimWhitespace => Box::new(TrimWhitespace),
+                ContentProcessorKind::NormalizeMarkdown => Box::new(NormalizeMarkdown),
+                ContentProcessorKind::StripTrackingParams => Box::new(StripTrackingParams),
+                ContentProcessorKind::UrlSchemes => {
+                    Box::new(UrlSchemes::new(&config.allowed_url_schemes))
+                }
+            });
+        }
+
+        pipeline
+    }
+
+    /// Also run `processor`, after the others
+    pub fn with_processor(mut self, processor: impl ContentProcessor + 'static) -> Self {
+        self.processors.push(Box::new(processor));
+        self
+    }
+
+    /// Run every processor over the content of `message`. Stops at the
+    /// first refusal and returns its reason.
+    ///
+    /// A language tag given in `content_metadata` is normalized, or refused
+    /// if it isn't one. Without it, the language is detected from the
+    /// processed text if `content.detect_language` is set.
+    pub fn process(&self, message: &mut Message) -> Result<(), String> {
+        if let Some(tag) = message.language() {
+            let tag = language::normalize(tag)?;
+            message
+                .content_metadata
+                .insert(LANGUAGE_KEY.to_string(), tag);
+        }
+        for processor in &self.processors {
+            processor.process(&mut message.content)?;
+        }
+        if self.detect_language
+            && message.language().is_none()
+            && let Some(detected) = message.language_sample().and_then(language::detect)
+        {
+            message
+                .content_metadata
+                .insert(LANGUAGE_KEY.to_string(), detected.to_string());
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{ImageContent, TextContent};
+
+    fn text(text: &str) -> ContentType {
+        ContentType::Text(TextContent {
+            text: text.to_string(),
+        })
+    }
+
+    fn image(url: &str) -> ContentType {
+        ContentType::Image(ImageContent {
+            image_url: url.to_string(),
+            thumbnail_url: None,
+            width: None,
+            height: None,
+            mime_type: None,
+            size_bytes: None,
+        })
+    }
+
+    #[test]
+    fn test_markdown_is_normalized_outside_code_blocks() {
+        let mut content = text(
+            "# Title \r\n\r\n\r\n\r\nLine with break  \r\nNext\t\n\n\n```\nkeep   \n\n\n\nthis\n```\nend",
+        );
+        TrimWhitespace.process(&mut content).unwrap();
+        NormalizeMarkdown.process(&mut content).unwrap();
+
+        assert_eq!(
+            content,
+            text("# Title\n\nLine with break  \nNext\n\n```\nkeep   \n\n\n\nthis\n```\nend")
+        );
+    }
+
+    #[test]
+    fn test_tracking_params_are_stripped() {
+        for (url, stripped) in [
+            (
+                "https://cdn.example.com/cat.png?utm_source=x&w=200&fbclid=abc#top",
+                "https://cdn.example.com/cat.png?w=200#top",
+            ),
+            (
+                "https://cdn.example.com/cat.png?UTM_Campaign=y&gclid=1",
+                "https://cdn.example.com/cat.png",
+            ),
+            (
+                "http://minio:9000/aigc-images/cat.png?X-Amz-Signature=abc",
+                "http://minio:9000/aigc-images/cat.png?X-Amz-Signature=abc",
+            ),
+        ] {
+            let mut content = image(url);
+            StripTrackingParams.process(&mut content).unwrap();
+            assert_eq!(content, image(stripped), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_only_allowed_url_schemes_pass() {
+        let pipeline = ContentPipeline::new(&ContentConfig {
+            processors: vec![ContentProcessorKind::UrlSchemes],
+            allowed_url_schemes: vec!["https".to_string(), "s3".to_string()],
+            detect_language: false,
+        });
+        let check = |content: ContentType| {
+            let mut message =
+                crate::domain::Conversation::new("t".to_string(), "u".to_string()).root_message;
+            message.content = content;
+            pipeline.process(&mut message)
+        };
+
+        assert!(check(text("See [docs](https://example.com) and [below](#notes)")).is_ok());
+        assert!(check(image("s3://aigc-images/cat.png")).is_ok());
+        assert!(check(image("/relative/cat.png")).is_ok());
+
+        let err = check(text("Click [here](javascript:alert(1))")).unwrap_err();
+        assert!(err.contains("`javascript`"), "{}", err);
+        assert!(check(text("![x]( <ftp://host/cat.png> )")).is_err());
+        assert!(check(image("data:image/png;base64,AAAA")).is_err());
+    }
+
+    #[test]
+    fn test_languages_are_normalized_or_detected() {
+        let pipeline = ContentPipeline::new(&ContentConfig {
+            processors: Vec::new(),
+            allowed_url_schemes: Vec::new(),
+            detect_language: true,
+        });
+        let message = |content: ContentType, language: Option<&str>| {
+            let mut message =
+                crate::domain::Conversation::new("t".to_string(), "u".to_string()).root_message;
+            message.content = content;
+            if let Some(language) = language {
+                message
+                    .content_metadata
+                    .insert(LANGUAGE_KEY.to_string(), language.to_string());
+            }
+            message
+        };
+
+        let mut given = message(text("Obrigado"), Some("PT_br"));
+        pipeline.process(&mut given).unwrap();
+        assert_eq!(given.language(), Some("pt-BR"));
+        assert!(
+            pipeline
+                .process(&mut message(text("Hi"), Some("Portuguese")))
+                .is_err()
+        );
+
+        let mut detected = message(text("Wie kann ich die Soße andicken?"), None);
+        pipeline.process(&mut detected).unwrap();
+        assert_eq!(detected.language(), Some("de"));
+        let mut unknown = message(image("s3://aigc-images/cat.png"), None);
+        pipeline.process(&mut unknown).unwrap();
+        assert_eq!(unknown.language(), None);
+    }
+}
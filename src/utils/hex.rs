@@ -0,0 +1,9 @@
+//! Lowercase hex encoding, for digests and signatures
+
+pub fn hex(bytes: impl AsRef<[u8]>) -> String {
+    bytes
+        .as_ref()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
@@ -0,0 +1,262 @@
+//! Client for outgoing HTTP requests: webhook deliveries, secrets agents and
+//! object stores. Built on reqwest with rustls, so both `http://` and
+//! `https://` URLs are reachable. Redirects aren't followed and proxies
+//! aren't used, so a request goes to the host it names and nowhere else.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::error::Error as _;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use crate::config::FetchConfig;
+use crate::utils::url::{self, ParsedUrl, UrlError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("{0} is not an http:// or https:// URL")]
+    InvalidUrl(String),
+    #[error(transparent)]
+    Refused(#[from] UrlError),
+    #[error("{0} timed out")]
+    Timeout(String),
+    #[error("{0}")]
+    Request(String),
+    #[error("{0} returned {1}")]
+    Status(String, u16),
+    #[error("invalid response from {0}: {1}")]
+    InvalidResponse(String, String),
+}
+
+/// Status and body of a response
+#[derive(Debug)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+#[derive(Clone)]
+pub struct HttpClient {
+    client: reqwest::Client,
+    /// Refuse hosts that are or resolve to internal addresses
+    public_only: bool,
+    max_response_bytes: usize,
+}
+
+impl HttpClient {
+    /// Client for the endpoints of the configuration (object stores,
+    /// secrets agents), which may well be on the local network
+    pub fn configured() -> &'static HttpClient {
+        static CLIENT: OnceLock<HttpClient> = OnceLock::new();
+        CLIENT.get_or_init(|| HttpClient {
+            client: builder()
+                .build()
+                .expect("the TLS backend could not be initialized"),
+            public_only: false,
+            max_response_bytes: usize::MAX,
+        })
+    }
+
+    /// Client for URLs given by users, such as webhooks: hosts must resolve
+    /// to public addresses only, unless `fetch.allow_private_networks`, and
+    /// at most `fetch.max_response_bytes` of a response are read
+    pub fn for_fetch(fetch: &FetchConfig) -> HttpClient {
+        let mut builder = builder();
+        if !fetch.allow_private_networks {
+            builder = builder.dns_resolver(Arc::new(PublicResolver));
+        }
+
+        HttpClient {
+            client: builder
+                .build()
+                .expect("the TLS backend could not be initialized"),
+            public_only: !fetch.allow_private_networks,
+            max_response_bytes: fetch.max_response_bytes,
+        }
+    }
+
+    /// Check that requests may be sent to `url`: an `http://` or `https://`
+    /// URL whose host isn't internal, for a client refusing those. Names are
+    /// checked again once resolved, when sending.
+    pub fn check_url(&self, url: &str) -> Result<(), HttpError> {
+        let parsed = parse_url(url)?;
+        if self.public_only && url::is_internal_host(parsed.host) {
+            return Err(UrlError::InternalAddress(parsed.host.to_string()).into());
+        }
+
+        Ok(())
+    }
+
+    /// Send a request with the given headers and body, and return the
+    /// response's status and body whatever the status
+    pub async fn send(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<HttpResponse, HttpError> {
+        self.check_url(url)?;
+        let target = without_query(url);
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|_| HttpError::Request(format!("invalid method {}", method)))?;
+
+        let mut request = self.client.request(method, url).timeout(timeout);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        if !body.is_empty() {
+            request = request.body(body);
+        }
+
+        let mut response = request.send().await.map_err(|e| request_error(target, e))?;
+        let status = response.status().as_u16();
+        let mut body = Vec::new();
+        while body.len() < self.max_response_bytes
+            && let Some(chunk) = response
+                .chunk()
+                .await
+                .map_err(|e| request_error(target, e))?
+        {
+            let room = self.max_response_bytes - body.len();
+            body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+        }
+
+        Ok(HttpResponse { status, body })
+    }
+
+    /// GET a JSON document, which must come with a `2xx` status
+    pub async fn get_json(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<serde_json::Value, HttpError> {
+        let mut headers = headers.to_vec();
+        headers.push(("accept", "application/json"));
+        let response = self.send("GET", url, &headers, Vec::new(), timeout).await?;
+        if !response.is_success() {
+            return Err(HttpError::Status(
+                without_query(url).to_string(),
+                response.status,
+            ));
+        }
+
+        serde_json::from_slice(&response.body)
+            .map_err(|e| HttpError::InvalidResponse(without_query(url).to_string(), e.to_string()))
+    }
+}
+
+fn builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .no_proxy()
+}
+
+fn parse_url(url: &str) -> Result<ParsedUrl<'_>, HttpError> {
+    match url::parse(url) {
+        Ok(parsed) if parsed.scheme == "http" || parsed.scheme == "https" => Ok(parsed),
+        _ => Err(HttpError::InvalidUrl(url.to_string())),
+    }
+}
+
+/// The URL without its query, which may carry credentials, for errors
+fn without_query(url: &str) -> &str {
+    url.split(['?', '#']).next().unwrap_or(url)
+}
+
+fn request_error(target: &str, error: reqwest::Error) -> HttpError {
+    if error.is_timeout() {
+        return HttpError::Timeout(target.to_string());
+    }
+    let mut source = error.source();
+    while let Some(cause) = source {
+        if let Some(refused) = cause.downcast_ref::<UrlError>() {
+            return HttpError::Refused(refused.clone());
+        }
+        source = cause.source();
+    }
+
+    HttpError::Request(format!("{}: {}", target, error))
+}
+
+/// Resolves names to a single address, refused if any of the name's
+/// addresses is internal, so a second lookup can't be steered elsewhere
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let address = url::resolve(name.as_str(), 0, false).await?;
+            Ok(Box::new(std::iter::once(address)) as Addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_internal_hosts_are_refused_unless_allowed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut buf = [0; 4096];
+                let _ = stream.read(&mut buf).await;
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 11\r\n\r\nhello world")
+                    .await;
+            }
+        });
+
+        let public = HttpClient::for_fetch(&FetchConfig {
+            allow_private_networks: false,
+            max_response_bytes: 5,
+        });
+        assert!(matches!(
+            public.check_url("ftp://example.com/hook"),
+            Err(HttpError::InvalidUrl(_))
+        ));
+        assert!(public.check_url("https://example.com/hook").is_ok());
+        assert!(matches!(
+            public
+                .send("POST", &url, &[], b"{}".to_vec(), Duration::from_secs(5))
+                .await,
+            Err(HttpError::Refused(UrlError::InternalAddress(_)))
+        ));
+        let localhost = url.replace("127.0.0.1", "localhost");
+        assert!(matches!(
+            public
+                .send(
+                    "POST",
+                    &localhost,
+                    &[],
+                    b"{}".to_vec(),
+                    Duration::from_secs(5)
+                )
+                .await,
+            Err(HttpError::Refused(UrlError::InternalAddress(_)))
+        ));
+
+        let private = HttpClient::for_fetch(&FetchConfig {
+            allow_private_networks: true,
+            max_response_bytes: 5,
+        });
+        let response = private
+            .send("POST", &url, &[], b"{}".to_vec(), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body, b"hello");
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Formats each event as one JSON object per line:
+/// `{"timestamp", "level", "target", "message", "fields", "span_id", "trace_id", "spans"}`.
+/// `trace_id` is the ID of the outermost span the event happened in (the request span),
+/// so all events of one request share it.
+pub struct JsonFormat;
+
+/// Records span fields as a JSON object, so they can be embedded in event lines
+pub struct JsonFields;
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        let mut visitor = JsonVisitor::default();
+        event.record(&mut visitor);
+        let message = visitor.fields.remove("message");
+
+        let mut line = serde_json::Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            chrono::Utc::now()
+                .to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+                .into(),
+        );
+        line.insert("level".to_string(), metadata.level().as_str().into());
+        line.insert("target".to_string(), metadata.target().into());
+        if let Some(message) = message {
+            line.insert("message".to_string(), message);
+        }
+        if !visitor.fields.is_empty() {
+            line.insert("fields".to_string(), visitor.fields.into());
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let mut spans = Vec::new();
+            let mut trace_id = None;
+            for (i, span) in scope.enumerate() {
+                if i == 0 {
+                    line.insert("span_id".to_string(), span_id(span.id()).into());
+                }
+                trace_id = Some(span.id());
+
+                let mut entry = serde_json::Map::new();
+                entry.insert("name".to_string(), span.name().into());
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>()
+                    && let Ok(serde_json::Value::Object(fields)) =
+                        serde_json::from_str(fields.as_str())
+                {
+                    entry.extend(fields);
+                }
+                spans.push(serde_json::Value::Object(entry));
+            }
+            if let Some(trace_id) = trace_id {
+                line.insert("trace_id".to_string(), span_id(trace_id).into());
+            }
+            // Outermost span first
+            spans.reverse();
+            line.insert("spans".to_string(), spans.into());
+        }
+
+        writeln!(writer, "{}", serde_json::Value::Object(line))
+    }
+}
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: tracing_subscriber::field::RecordFields>(
+        &self,
+        mut writer: Writer<'writer>,
+        fields: R,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        fields.record(&mut visitor);
+        write!(writer, "{}", serde_json::Value::Object(visitor.fields))
+    }
+
+    fn add_fields(
+        &self,
+        current: &'writer mut FormattedFields<Self>,
+        fields: &tracing::span::Record<'_>,
+    ) -> fmt::Result {
+        let mut visitor = JsonVisitor::default();
+        if let Ok(serde_json::Value::Object(existing)) = serde_json::from_str(current.as_str()) {
+            visitor.fields = existing;
+        }
+        fields.record(&mut visitor);
+        current.fields = serde_json::Value::Object(visitor.fields).to_string();
+        Ok(())
+    }
+}
+
+fn span_id(id: tracing::span::Id) -> String {
+    format!("{:016x}", id.into_u64())
+}
+
+#[derive(Default)]
+struct JsonVisitor {
+    fields: serde_json::Map<String, serde_json::Value>,
+}
+
+impl Visit for JsonVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.fields.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.fields
+            .insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_events_carry_span_and_trace_ids() {
+        let buffer = Buffer::default();
+        let subscriber = tracing_subscriber::fmt()
+            .event_format(JsonFormat)
+            .fmt_fields(JsonFields)
+            .with_writer(buffer.clone())
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", route = "/api/v1/conversations");
+            let _request = request.enter();
+            let query = tracing::info_span!("query", table = "messages");
+            let _query = query.enter();
+            tracing::info!(rows = 3, "fetched \"rows\"");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "fetched \"rows\"");
+        assert_eq!(line["fields"]["rows"], 3);
+        assert_eq!(line["spans"][0]["name"], "request");
+        assert_eq!(line["spans"][0]["route"], "/api/v1/conversations");
+        assert_eq!(line["spans"][1]["table"], "messages");
+        assert_ne!(line["span_id"], line["trace_id"]);
+    }
+}
@@ -0,0 +1,250 @@
+//! Language tags of conversations and messages: a lightweight detector for
+//! message text, and normalization and matching of BCP 47 style tags such
+//! as `en` or `pt-BR`
+
+/// Fewer letters than this are too little text to tell the language
+const MIN_LETTERS: usize = 12;
+
+/// Common short words of the Latin-script languages told apart by counting
+/// them. Words shared between languages count for each.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "of", "to", "in", "that", "it", "with", "for", "you",
+            "this", "was", "have", "not", "what", "how",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "de", "que", "y", "en", "es", "por", "para", "una", "con",
+            "no", "del", "está", "cómo", "qué",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "des", "est", "et", "que", "une", "pour", "dans", "pas", "du",
+            "vous", "je", "il", "avec", "ce", "sont",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "mit", "ein", "eine", "zu", "den",
+            "von", "sie", "auf", "es", "wie", "was",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "a", "os", "as", "de", "que", "e", "é", "um", "uma", "não", "para", "com", "em",
+            "do", "da", "você", "como",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "la", "di", "che", "e", "è", "un", "una", "per", "non", "con", "sono", "del",
+            "della", "gli", "lo", "come",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "van", "is", "dat", "niet", "ik", "je", "op", "te", "met",
+            "zijn", "voor", "hoe", "wat",
+        ],
+    ),
+];
+
+/// Scripts told apart by character ranges, with the language they imply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    Kana,
+    Han,
+}
+
+fn script(c: char) -> Option<Script> {
+    Some(match c {
+        'a'..='z' | 'A'..='Z' | '\u{c0}'..='\u{24f}' if c.is_alphabetic() => Script::Latin,
+        '\u{400}'..='\u{4ff}' => Script::Cyrillic,
+        '\u{370}'..='\u{3ff}' => Script::Greek,
+        '\u{600}'..='\u{6ff}' => Script::Arabic,
+        '\u{590}'..='\u{5ff}' => Script::Hebrew,
+        '\u{900}'..='\u{97f}' => Script::Devanagari,
+        '\u{e00}'..='\u{e7f}' => Script::Thai,
+        '\u{1100}'..='\u{11ff}' | '\u{ac00}'..='\u{d7af}' => Script::Hangul,
+        '\u{3040}'..='\u{30ff}' => Script::Kana,
+        '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' => Script::Han,
+        _ => return None,
+    })
+}
+
+/// Best guess at the language of `text`, as an ISO 639-1 code. `None` if
+/// the text is too short or the guess too uncertain.
+///
+/// Non-Latin scripts mostly give the language away; Latin-script text is
+/// told apart by its common words, which covers English, Spanish, French,
+/// German, Portuguese, Italian and Dutch.
+pub fn detect(text: &str) -> Option<&'static str> {
+    let mut counts: Vec<(Script, usize)> = Vec::new();
+    let mut letters = 0;
+    for script in text.chars().filter_map(script) {
+        letters += 1;
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    if letters < MIN_LETTERS {
+        return None;
+    }
+    let count = |script: Script| {
+        counts
+            .iter()
+            .find(|(s, _)| *s == script)
+            .map_or(0, |(_, count)| *count)
+    };
+
+    // Japanese mixes kana with kanji; any real share of kana settles it
+    if count(Script::Kana) * 10 >= letters {
+        return Some("ja");
+    }
+    let (dominant, _) = counts.iter().copied().max_by_key(|(_, count)| *count)?;
+    match dominant {
+        Script::Latin => detect_latin(text),
+        Script::Cyrillic if text.contains(['і', 'ї', 'є', 'ґ']) => Some("uk"),
+        Script::Cyrillic => Some("ru"),
+        Script::Greek => Some("el"),
+        Script::Arabic => Some("ar"),
+        Script::Hebrew => Some("he"),
+        Script::Devanagari => Some("hi"),
+        Script::Thai => Some("th"),
+        Script::Hangul => Some("ko"),
+        Script::Kana => Some("ja"),
+        Script::Han => Some("zh"),
+    }
+}
+
+fn detect_latin(text: &str) -> Option<&'static str> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|word| stopwords.contains(&word.as_str()))
+                .count();
+            (*language, hits)
+        })
+        .collect();
+    scores.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    match scores.as_slice() {
+        [(language, best), (_, second), ..] if *best >= 2 && best > second => Some(language),
+        _ => None,
+    }
+}
+
+/// `tag` in canonical case (`en`, `pt-BR`, `zh-Hant`), or why it isn't a
+/// language tag. Underscores are accepted as separators.
+pub fn normalize(tag: &str) -> Result<String, String> {
+    let invalid = || {
+        format!(
+            "`{}` is not a language tag such as `en` or `pt-BR`",
+            tag.trim()
+        )
+    };
+    let mut subtags = tag.trim().split(['-', '_']);
+    let primary = subtags.next().unwrap_or_default();
+    if !(2..=3).contains(&primary.len()) || !primary.chars().all(|c| c.is_ascii_alphabetic()) {
+        return Err(invalid());
+    }
+
+    let mut normalized = primary.to_ascii_lowercase();
+    for subtag in subtags {
+        if !(2..=8).contains(&subtag.len()) || !subtag.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+        normalized.push('-');
+        match subtag.len() {
+            2 => normalized.push_str(&subtag.to_ascii_uppercase()),
+            4 if subtag.chars().all(|c| c.is_ascii_alphabetic()) => {
+                normalized.push_str(&subtag[..1].to_ascii_uppercase());
+                normalized.push_str(&subtag[1..].to_ascii_lowercase());
+            }
+            _ => normalized.push_str(&subtag.to_ascii_lowercase()),
+        }
+    }
+
+    Ok(normalized)
+}
+
+/// Whether `language` is `filter` or a variant of it: `pt` matches `pt-BR`,
+/// but `pt-BR` doesn't match `pt`. Case is ignored.
+pub fn matches(language: &str, filter: &str) -> bool {
+    let language = language.to_ascii_lowercase();
+    let filter = filter.to_ascii_lowercase();
+
+    language == filter
+        || language
+            .strip_prefix(&filter)
+            .is_some_and(|rest| rest.starts_with('-'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        for (text, language) in [
+            ("How do I make the sauce thicker without flour?", Some("en")),
+            ("¿Cómo puedo hacer que la salsa sea más espesa?", Some("es")),
+            ("Je voudrais savoir pourquoi le gâteau est sec", Some("fr")),
+            (
+                "Wie kann ich die Soße ohne Mehl andicken und was ist",
+                Some("de"),
+            ),
+            ("Você pode me ajudar com uma receita de bolo?", Some("pt")),
+            ("Как сделать соус гуще без муки?", Some("ru")),
+            ("小麦粉を使わずにソースを濃くする方法は？", Some("ja")),
+            ("如何在不用面粉的情况下让酱汁变稠？", Some("zh")),
+            ("밀가루 없이 소스를 걸쭉하게 만드는 방법은?", Some("ko")),
+            ("Thanks!", None),
+            ("fn main() { println!(\"{}\", x + y); }", None),
+        ] {
+            assert_eq!(detect(text), language, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_normalize_and_match() {
+        assert_eq!(normalize(" EN ").unwrap(), "en");
+        assert_eq!(normalize("pt_br").unwrap(), "pt-BR");
+        assert_eq!(normalize("zh-hant-tw").unwrap(), "zh-Hant-TW");
+        assert_eq!(normalize("es-419").unwrap(), "es-419");
+        for invalid in ["", "e", "english", "en-", "en--US", "12"] {
+            assert!(normalize(invalid).is_err(), "{}", invalid);
+        }
+
+        assert!(matches("pt-BR", "pt"));
+        assert!(matches("pt-BR", "PT-br"));
+        assert!(!matches("pt", "pt-BR"));
+        assert!(!matches("pta", "pt"));
+    }
+}
@@ -1,5 +1,15 @@
-pub mod lineage_utils;
+pub mod chatgpt;
+pub mod content_hash;
+pub mod content_pipeline;
+pub mod hex;
+pub mod http;
+pub mod json_log;
+pub mod language;
+pub mod native;
+pub mod pii;
+pub mod transcript;
+pub mod url;
 pub mod uuid_utils;
 
-pub use lineage_utils::*;
+pub use aigc_history_types::tree::*;
 pub use uuid_utils::*;
@@ -0,0 +1,278 @@
+//! Conversion of a native export back into our tree model, under fresh IDs
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::db::DbError;
+use crate::domain::{
+    Branch, ContentType, Conversation, Message, NATIVE_EXPORT_VERSION, NativeExport,
+};
+
+use super::chatgpt::ImportedConversation;
+use super::{LineageTooDeep, compute_lineage, new_message_id, validate_lineage_depth};
+
+/// Convert a native export into a new conversation owned by `created_by`.
+/// Messages and branches get fresh IDs, every reference to them is
+/// translated, and lineages are rebuilt from the parents, so the tree is the
+/// one exported whatever the order of the messages. Contents and timestamps
+/// are kept, but, as in a ChatGPT import, messages and branches are the
+/// caller's; a message written by someone else keeps its author under
+/// `original_created_by` in its content metadata. Like a duplicate, the
+/// conversation starts private and is no fork of anything.
+pub fn convert_export(
+    export: &NativeExport,
+    created_by: &str,
+    max_lineage_depth: usize,
+) -> Result<ImportedConversation, DbError> {
+    if export.version != NATIVE_EXPORT_VERSION {
+        return Err(DbError::InvalidData(format!(
+            "Unsupported export version {}, expected {}",
+            export.version, NATIVE_EXPORT_VERSION
+        )));
+    }
+
+    // Fresh IDs in export order, which is the order the messages were written
+    let mut ids = HashMap::with_capacity(export.messages.len());
+    let mut sources = HashMap::with_capacity(export.messages.len());
+    for message in &export.messages {
+        if ids.insert(message.message_id, new_message_id()).is_some() {
+            return Err(DbError::InvalidData(format!(
+                "Message {} appears twice",
+                message.message_id
+            )));
+        }
+        sources.insert(message.message_id, message);
+        if message.is_root() != message.parent_message_id.is_none() {
+            return Err(DbError::InvalidData(format!(
+                "Message {} must be the root if and only if it has no parent",
+                message.message_id
+            )));
+        }
+    }
+    let roots = export.messages.iter().filter(|m| m.is_root()).count();
+    if roots != 1 {
+        return Err(DbError::InvalidData(format!(
+            "Expected one root message, found {}",
+            roots
+        )));
+    }
+
+    let conversation_id = Uuid::new_v4();
+    let mut branch_ids = HashMap::with_capacity(export.branches.len());
+    let mut slugs = HashSet::with_capacity(export.branches.len());
+    let mut branches = Vec::with_capacity(export.branches.len());
+    for source in &export.branches {
+        if !slugs.insert(source.slug.as_str()) {
+            return Err(DbError::InvalidData(format!(
+                "Branch slug {} appears twice",
+                source.slug
+            )));
+        }
+        let branch = Branch {
+            conversation_id,
+            branch_id: Uuid::new_v4(),
+            leaf_message_id: translate(&ids, source.leaf_message_id)?,
+            created_by: created_by.to_string(),
+            ..source.clone()
+        };
+        branch_ids.insert(source.branch_id, branch.branch_id);
+        branches.push(branch);
+    }
+
+    let mut lineages = HashMap::with_capacity(export.messages.len());
+    let mut messages = Vec::with_capacity(export.messages.len());
+    for source in &export.messages {
+        let mut message = source.clone();
+        message.conversation_id = conversation_id;
+        message.message_id = ids[&source.message_id];
+        if message.created_by != created_by {
+            let original = std::mem::replace(&mut message.created_by, created_by.to_string());
+            message
+                .content_metadata
+                .insert("original_created_by".to_string(), original);
+        }
+        message.parent_message_id = source
+            .parent_message_id
+            .map(|id| translate(&ids, id))
+            .transpose()?;
+        message.lineage = lineage(
+            source.message_id,
+            &sources,
+            &ids,
+            &mut lineages,
+            max_lineage_depth,
+        )?;
+        match &mut message.content {
+            ContentType::Summary(summary) => {
+                summary.from_message_id = translate(&ids, summary.from_message_id)?;
+                summary.to_message_id = translate(&ids, summary.to_message_id)?;
+            }
+            ContentType::Metadata(metadata) if message.parent_message_id.is_none() => {
+                metadata.is_public = false;
+                metadata.fork_from_conversation_id = None;
+                metadata.fork_from_message_id = None;
+                metadata.published_branch_id = metadata
+                    .published_branch_id
+                    .and_then(|id| branch_ids.get(&id).copied());
+                metadata.private_message_ids = metadata
+                    .private_message_ids
+                    .iter()
+                    .filter_map(|id| ids.get(id).copied())
+                    .collect();
+            }
+            ContentType::Metadata(_) => {
+                return Err(DbError::InvalidData(format!(
+                    "Message {} holds metadata but isn't the root",
+                    source.message_id
+                )));
+            }
+            _ if message.parent_message_id.is_none() => {
+                return Err(DbError::InvalidData(
+                    "The root message must hold the conversation metadata".to_string(),
+                ));
+            }
+            _ => {}
+        }
+        messages.push(message);
+    }
+    // Parents before children; the sort is stable, so siblings keep their order
+    messages.sort_by_key(Message::depth);
+
+    Ok(ImportedConversation {
+        conversation: Conversation {
+            conversation_id,
+            root_message: messages[0].clone(),
+        },
+        messages,
+        branches,
+    })
+}
+
+/// The fresh ID of an exported message
+fn translate(ids: &HashMap<Uuid, Uuid>, id: Uuid) -> Result<Uuid, DbError> {
+    ids.get(&id)
+        .copied()
+        .ok_or_else(|| DbError::InvalidData(format!("Message {} is not in the export", id)))
+}
+
+/// The lineage of an exported message in fresh IDs, following parents up to
+/// the root or to a message whose lineage is already known. A chain deeper
+/// than `max_depth`, as a cycle would be, is refused.
+fn lineage(
+    message_id: Uuid,
+    sources: &HashMap<Uuid, &Message>,
+    ids: &HashMap<Uuid, Uuid>,
+    lineages: &mut HashMap<Uuid, Vec<Uuid>>,
+    max_depth: usize,
+) -> Result<Vec<Uuid>, DbError> {
+    let mut chain = Vec::new();
+    let mut current = message_id;
+    let mut lineage = loop {
+        if let Some(lineage) = lineages.get(&current) {
+            break lineage.clone();
+        }
+        chain.push(current);
+        if chain.len() > max_depth {
+            return Err(LineageTooDeep {
+                depth: chain.len(),
+                max: max_depth,
+            }
+            .into());
+        }
+        match sources[&current].parent_message_id {
+            Some(parent) if sources.contains_key(&parent) => current = parent,
+            Some(parent) => {
+                return Err(DbError::InvalidData(format!(
+                    "Message {} is not in the export",
+                    parent
+                )));
+            }
+            None => break Vec::new(),
+        }
+    };
+
+    for id in chain.into_iter().rev() {
+        lineage = compute_lineage(&lineage, ids[&id]);
+        lineages.insert(id, lineage.clone());
+    }
+    validate_lineage_depth(&lineage, max_depth)?;
+
+    Ok(lineage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ConversationBuilder;
+    use chrono::Utc;
+
+    fn export_of(messages: Vec<Message>, branches: Vec<Branch>) -> NativeExport {
+        NativeExport {
+            version: NATIVE_EXPORT_VERSION,
+            exported_at: Utc::now(),
+            conversation_id: messages[0].conversation_id,
+            messages,
+            branches,
+            shares: None,
+        }
+    }
+
+    #[test]
+    fn test_convert_rebuilds_the_tree_under_fresh_ids() {
+        let fixture = ConversationBuilder::new("Logo")
+            .with_messages(2, 2)
+            .with_branch("main", &[1, 0])
+            .build();
+        // Children listed before their parents still land under them
+        let mut messages = fixture.messages.clone();
+        messages.reverse();
+        let export = export_of(messages, fixture.branches.clone());
+
+        let imported = convert_export(&export, "user_b", 10).unwrap();
+        assert_ne!(
+            imported.conversation.conversation_id,
+            fixture.conversation_id()
+        );
+        assert_eq!(imported.conversation.created_by(), "user_b");
+        assert_eq!(imported.messages.len(), fixture.messages.len());
+        assert!(
+            imported
+                .messages
+                .windows(2)
+                .all(|pair| pair[0].depth() <= pair[1].depth())
+        );
+
+        let by_id: HashMap<Uuid, &Message> = imported
+            .messages
+            .iter()
+            .map(|m| (m.message_id, m))
+            .collect();
+        let leaf = by_id[&imported.branches[0].leaf_message_id];
+        let texts: Vec<String> = leaf
+            .lineage
+            .iter()
+            .skip(1)
+            .map(|id| match &by_id[id].content {
+                ContentType::Text(content) => content.text.clone(),
+                other => panic!("unexpected content {:?}", other),
+            })
+            .collect();
+        assert_eq!(texts, ["Message 1", "Message 1.0"]);
+        assert!(imported.messages.iter().all(|m| m.created_by == "user_b"));
+        assert!(imported.branches.iter().all(|b| b.created_by == "user_b"));
+        assert_eq!(leaf.content_metadata["original_created_by"], "user_a");
+        assert_eq!(leaf.created_at, fixture.message(&[1, 0]).created_at);
+
+        assert!(matches!(
+            convert_export(&export, "user_b", 2),
+            Err(DbError::LineageTooDeep { depth: 3, max: 2 })
+        ));
+        let orphaned = export_of(fixture.messages[1..].to_vec(), Vec::new());
+        assert!(convert_export(&orphaned, "user_b", 10).is_err());
+        let future = NativeExport {
+            version: NATIVE_EXPORT_VERSION + 1,
+            ..export
+        };
+        assert!(convert_export(&future, "user_b", 10).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+//! Detection and masking of personal data (PII) in message text
+
+use crate::config::PiiConfig;
+use crate::domain::{ContentType, Message};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    Phone,
+    CreditCard,
+}
+
+impl PiiKind {
+    /// Text that replaces a match
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            PiiKind::Email => "[EMAIL]",
+            PiiKind::Phone => "[PHONE]",
+            PiiKind::CreditCard => "[CREDIT_CARD]",
+        }
+    }
+}
+
+/// A byte range of text holding personal data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PiiMatch {
+    pub start: usize,
+    pub end: usize,
+    pub kind: PiiKind,
+}
+
+/// Finds personal data in text. Implement it to plug in another detection
+/// provider next to, or instead of, the built-in patterns.
+pub trait PiiDetector: Send + Sync {
+    fn detect(&self, text: &str) -> Vec<PiiMatch>;
+}
+
+/// Pattern-based detection of emails, phone numbers and credit card numbers.
+/// Errs on the side of fewer false positives: a phone number needs 10-15
+/// digits, or a leading `+` and at least 8, and a card number must pass the
+/// Luhn check.
+pub struct BuiltinPiiDetector;
+
+impl PiiDetector for BuiltinPiiDetector {
+    fn detect(&self, text: &str) -> Vec<PiiMatch> {
+        let mut matches = find_emails(text);
+        matches.extend(find_numbers(text));
+        matches
+    }
+}
+
+/// Masks personal data with the detectors it was built with
+pub struct PiiScrubber {
+    detectors: Vec<Box<dyn PiiDetector>>,
+    scrub_on_write: bool,
+}
+
+impl PiiScrubber {
+    /// A scrubber using the built-in patterns
+    pub fn new(config: &PiiConfig) -> Self {
+        Self {
+            detectors: vec![Box::new(BuiltinPiiDetector)],
+            scrub_on_write: config.scrub_on_write,
+        }
+    }
+
+    /// Also mask what `detector` finds
+    pub fn with_detector(mut self, detector: impl PiiDetector + 'static) -> Self {
+        self.detectors.push(Box::new(detector));
+        self
+    }
+
+    /// Whether new messages are scrubbed before they are stored
+    pub fn scrub_on_write(&self) -> bool {
+        self.scrub_on_write
+    }
+
+    /// `text` with every match replaced by its placeholder, or `None` if
+    /// nothing was found
+    pub fn scrub(&self, text: &str) -> Option<String> {
+        let mut matches: Vec<PiiMatch> = self
+            .detectors
+            .iter()
+            .flat_map(|detector| detector.detect(text))
+            .collect();
+        if matches.is_empty() {
+            return None;
+        }
+        // Earliest first, the longer of two matches starting together first
+        matches.sort_by_key(|m| (m.start, std::cmp::Reverse(m.end)));
+
+        let mut scrubbed = String::with_capacity(text.len());
+        let mut copied_up_to = 0;
+        for m in matches {
+            if m.start < copied_up_to {
+                continue;
+            }
+            scrubbed.push_str(&text[copied_up_to..m.start]);
+            scrubbed.push_str(m.kind.placeholder());
+            copied_up_to = m.end;
+        }
+        scrubbed.push_str(&text[copied_up_to..]);
+
+        Some(scrubbed)
+    }
+
+    /// Scrub the text of a message in place; returns whether anything changed.
+    /// Only text content is scrubbed.
+    pub fn scrub_message(&self, message: &mut Message) -> bool {
+        let ContentType::Text(content) = &mut message.content else {
+            return false;
+        };
+        match self.scrub(&content.text) {
+            Some(scrubbed) => {
+                content.text = scrubbed;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn find_emails(text: &str) -> Vec<PiiMatch> {
+    let bytes = text.as_bytes();
+    let is_local = |b: u8| b.is_ascii_alphanumeric() || b"._%+-".contains(&b);
+    let is_domain = |b: u8| b.is_ascii_alphanumeric() || b".-".contains(&b);
+
+    let mut matches = Vec::new();
+    let mut after_last = 0;
+    for (at, _) in text.match_indices('@') {
+        if at < after_last {
+            continue;
+        }
+        let mut start = at;
+        while start > after_last && is_local(bytes[start - 1]) {
+            start -= 1;
+        }
+        let mut end = at + 1;
+        while end < bytes.len() && is_domain(bytes[end]) {
+            end += 1;
+        }
+        // A sentence may end right after the address
+        while end > at + 1 && matches!(bytes[end - 1], b'.' | b'-') {
+            end -= 1;
+        }
+
+        let domain = &text[at + 1..end];
+        let valid_tld = domain.rsplit_once('.').is_some_and(|(host, tld)| {
+            !host.is_empty() && tld.len() >= 2 && tld.bytes().all(|b| b.is_ascii_alphabetic())
+        });
+        if start < at && valid_tld {
+            matches.push(PiiMatch {
+                start,
+                end,
+                kind: PiiKind::Email,
+            });
+            after_last = end;
+        }
+    }
+
+    matches
+}
+
+/// Runs of digits, optionally split by single spaces, dashes, dots or
+/// parentheses, classified as card or phone numbers
+fn find_numbers(text: &str) -> Vec<PiiMatch> {
+    let bytes = text.as_bytes();
+    let is_separator = |b: u8| b" -.()".contains(&b);
+    let starts_digits = |i: usize| i < bytes.len() && bytes[i].is_ascii_digit();
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let starts_run =
+            bytes[i].is_ascii_digit() || (matches!(bytes[i], b'+' | b'(') && starts_digits(i + 1));
+        if !starts_run || (i > 0 && bytes[i - 1].is_ascii_alphanumeric()) {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        let mut end = i;
+        let mut j = i + 1;
+        if bytes[start].is_ascii_digit() {
+            end = j;
+        }
+        while j < bytes.len() {
+            if bytes[j].is_ascii_digit() {
+                j += 1;
+                end = j;
+            } else if is_separator(bytes[j]) && starts_digits(j + 1) {
+                j += 1;
+            } else if is_separator(bytes[j])
+                && j + 1 < bytes.len()
+                && is_separator(bytes[j + 1])
+                && starts_digits(j + 2)
+            {
+                // e.g. ") " in "(555) 123-4567"
+                j += 2;
+            } else {
+                break;
+            }
+        }
+
+        let followed_by_word = end < bytes.len() && bytes[end].is_ascii_alphanumeric();
+        if !followed_by_word {
+            let run = &text[start..end];
+            let digits: Vec<u32> = run.chars().filter_map(|c| c.to_digit(10)).collect();
+            let kind = if (13..=19).contains(&digits.len()) && luhn_valid(&digits) {
+                Some(PiiKind::CreditCard)
+            } else if (10..=15).contains(&digits.len())
+                || (run.starts_with('+') && (8..=15).contains(&digits.len()))
+            {
+                Some(PiiKind::Phone)
+            } else {
+                None
+            };
+            if let Some(kind) = kind {
+                matches.push(PiiMatch { start, end, kind });
+            }
+        }
+        i = end.max(i + 1);
+    }
+
+    matches
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if i % 2 == 1 {
+                let doubled = d * 2;
+                if doubled > 9 { doubled - 9 } else { doubled }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrubber() -> PiiScrubber {
+        PiiScrubber::new(&PiiConfig {
+            scrub_on_write: true,
+        })
+    }
+
+    #[test]
+    fn test_scrub_masks_emails_phones_and_cards() {
+        let scrubbed = scrubber()
+            .scrub(
+                "Mail jane.doe+work@example.co.uk, call +1 (555) 123-4567 \
+                 or pay with 4111 1111 1111 1111.",
+            )
+            .unwrap();
+        assert_eq!(
+            scrubbed,
+            "Mail [EMAIL], call [PHONE] or pay with [CREDIT_CARD]."
+        );
+    }
+
+    #[test]
+    fn test_scrub_leaves_ordinary_numbers_alone() {
+        let scrubber = scrubber();
+        for text in [
+            "Released on 2024-01-15 as v1.2.3",
+            "It costs 1,299.99 dollars",
+            "Order 4111111111111112 was cancelled",
+            "Ping me @here or at user@localhost",
+            "Build abc1234567890 failed",
+        ] {
+            assert_eq!(scrubber.scrub(text), None, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use crate::domain::{ContentType, Message, MessageRole};
+
+/// Render a lineage path (root first) as a Markdown transcript
+pub fn render_markdown(messages: &[Message]) -> String {
+    let mut out = String::new();
+
+    for message in messages {
+        match &message.content {
+            ContentType::Metadata(metadata) => {
+                out.push_str(&format!("# {}\n\n", metadata.title));
+                if let Some(description) = &metadata.description {
+                    out.push_str(&format!("_{}_\n\n", description));
+                }
+                continue;
+            }
+            ContentType::Summary(summary) => {
+                out.push_str("## Summary\n\n");
+                for line in summary.text.lines() {
+                    out.push_str(&format!("> {}\n", line));
+                }
+                out.push('\n');
+                continue;
+            }
+            _ => {}
+        }
+
+        out.push_str(&format!("## {}\n\n", role_title(&message.role)));
+
+        match &message.content {
+            ContentType::Text(text) => {
+                out.push_str(text.text.trim_end());
+                out.push_str("\n\n");
+            }
+            ContentType::Image(image) => {
+                out.push_str(&format!("![image]({})\n\n", image.image_url));
+            }
+            ContentType::ImageBatch(batch) => {
+                for image in &batch.images {
+                    let alt = image.prompt.as_deref().unwrap_or("image");
+                    out.push_str(&format!("![{}]({})\n\n", alt, image.image_url));
+                }
+            }
+            ContentType::ToolCall(call) => {
+                out.push_str(&format!(
+                    "Tool call `{}` (`{}`):\n\n```json\n{}\n```\n\n",
+                    call.tool_name,
+                    call.tool_call_id,
+                    pretty_json(&call.arguments)
+                ));
+            }
+            ContentType::ToolResult(result) => {
+                let status = if result.success {
+                    "succeeded"
+                } else {
+                    "failed"
+                };
+                out.push_str(&format!(
+                    "Tool result for `{}` ({}):\n\n```json\n{}\n```\n\n",
+                    result.tool_call_id,
+                    status,
+                    pretty_json(&result.result)
+                ));
+            }
+            ContentType::Metadata(_) | ContentType::Summary(_) => {}
+        }
+    }
+
+    out
+}
+
+/// Render a lineage path (root first) as a standalone HTML document
+pub fn render_html(messages: &[Message]) -> String {
+    let title = messages
+        .iter()
+        .find_map(|m| match &m.content {
+            ContentType::Metadata(metadata) => Some(metadata.title.as_str()),
+            _ => None,
+        })
+        .unwrap_or("Conversation");
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", escape_html(title)));
+    out.push_str("</head>\n<body>\n<article>\n");
+
+    for message in messages {
+        match &message.content {
+            ContentType::Metadata(metadata) => {
+                out.push_str(&format!("<h1>{}</h1>\n", escape_html(&metadata.title)));
+                if let Some(description) = &metadata.description {
+                    out.push_str(&format!("<p><em>{}</em></p>\n", escape_html(description)));
+                }
+                continue;
+            }
+            ContentType::Summary(summary) => {
+                out.push_str("<section class=\"summary\">\n<h2>Summary</h2>\n");
+                out.push_str(&format!(
+                    "<blockquote>{}</blockquote>\n</section>\n",
+                    paragraphs(&summary.text)
+                ));
+                continue;
+            }
+            _ => {}
+        }
+
+        out.push_str(&format!(
+            "<section class=\"message role-{}\">\n<h2>{}</h2>\n",
+            message.role.as_str(),
+            role_title(&message.role)
+        ));
+
+        match &message.content {
+            ContentType::Text(text) => {
+                out.push_str(&paragraphs(&text.text));
+                out.push('\n');
+            }
+            ContentType::Image(image) => {
+                let url = escape_html(&image.image_url);
+                out.push_str(&format!("<p><a href=\"{}\">{}</a></p>\n", url, url));
+            }
+            ContentType::ImageBatch(batch) => {
+                out.push_str("<ul>\n");
+                for image in &batch.images {
+                    let url = escape_html(&image.image_url);
+                    let label = image
+                        .prompt
+                        .as_deref()
+                        .map(escape_html)
+                        .unwrap_or_else(|| url.clone());
+                    out.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", url, label));
+                }
+                out.push_str("</ul>\n");
+            }
+            ContentType::ToolCall(call) => {
+                out.push_str(&format!(
+                    "<p>Tool call <code>{}</code></p>\n<pre><code>{}</code></pre>\n",
+                    escape_html(&call.tool_name),
+                    escape_html(&pretty_json(&call.arguments))
+                ));
+            }
+            ContentType::ToolResult(result) => {
+                let status = if result.success {
+                    "succeeded"
+                } else {
+                    "failed"
+                };
+                out.push_str(&format!(
+                    "<p>Tool result for <code>{}</code> ({})</p>\n<pre><code>{}</code></pre>\n",
+                    escape_html(&result.tool_call_id),
+                    status,
+                    escape_html(&pretty_json(&result.result))
+                ));
+            }
+            ContentType::Metadata(_) | ContentType::Summary(_) => {}
+        }
+
+        out.push_str("</section>\n");
+    }
+
+    out.push_str("</article>\n</body>\n</html>\n");
+    out
+}
+
+/// Render a lineage path (root first) as an Anthropic Messages API request body.
+/// System messages and checkpoint summaries are folded into the top-level `system` prompt,
+/// tool calls become `tool_use` blocks on assistant turns and tool results become
+/// `tool_result` blocks on user turns. Consecutive messages of the same side are merged
+/// so that user and assistant turns alternate.
+pub fn render_anthropic(messages: &[Message]) -> serde_json::Value {
+    let mut system = Vec::new();
+    let mut turns: Vec<(&'static str, Vec<serde_json::Value>)> = Vec::new();
+
+    for message in messages {
+        let (role, blocks) = match &message.content {
+            ContentType::Metadata(_) => continue,
+            ContentType::Summary(summary) => {
+                system.push(summary.text.clone());
+                continue;
+            }
+            ContentType::Text(text) if message.role == MessageRole::System => {
+                system.push(text.text.clone());
+                continue;
+            }
+            ContentType::Text(text) => (
+                anthropic_role(&message.role),
+                vec![serde_json::json!({ "type": "text", "text": text.text })],
+            ),
+            ContentType::Image(image) => (
+                anthropic_role(&message.role),
+                vec![image_block(&image.image_url)],
+            ),
+            ContentType::ImageBatch(batch) => (
+                anthropic_role(&message.role),
+                batch
+                    .images
+                    .iter()
+                    .map(|image| image_block(&image.image_url))
+                    .collect(),
+            ),
+            ContentType::ToolCall(call) => (
+                "assistant",
+                vec![serde_json::json!({
+                    "type": "tool_use",
+                    "id": call.tool_call_id,
+                    "name": call.tool_name,
+                    "input": call.arguments,
+                })],
+            ),
+            ContentType::ToolResult(result) => {
+                let content = match &result.result {
+                    serde_json::Value::String(text) => text.clone(),
+                    other => other.to_string(),
+                };
+                (
+                    "user",
+                    vec![serde_json::json!({
+                        "type": "tool_result",
+                        "tool_use_id": result.tool_call_id,
+                        "content": content,
+                        "is_error": !result.success,
+                    })],
+                )
+            }
+        };
+
+        match turns.last_mut() {
+            Some((last_role, last_blocks)) if *last_role == role => last_blocks.extend(blocks),
+            _ => turns.push((role, blocks)),
+        }
+    }
+
+    let mut body = serde_json::json!({
+        "messages": turns
+            .into_iter()
+            .map(|(role, content)| serde_json::json!({ "role": role, "content": content }))
+            .collect::<Vec<_>>(),
+    });
+    if !system.is_empty() {
+        body["system"] = serde_json::Value::String(system.join("\n\n"));
+    }
+    body
+}
+
+fn anthropic_role(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::Assistant => "assistant",
+        _ => "user",
+    }
+}
+
+fn image_block(url: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "image",
+        "source": { "type": "url", "url": url },
+    })
+}
+
+fn role_title(role: &MessageRole) -> &'static str {
+    match role {
+        MessageRole::Root => "Root",
+        MessageRole::Human => "Human",
+        MessageRole::Assistant => "Assistant",
+        MessageRole::System => "System",
+        MessageRole::Tool => "Tool",
+    }
+}
+
+fn pretty_json(value: &serde_json::Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+}
+
+fn paragraphs(text: &str) -> String {
+    text.split("\n\n")
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| format!("<p>{}</p>", escape_html(p.trim()).replace('\n', "<br>")))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Escape text for inclusion in HTML element content or attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{TextContent, ToolCallContent, ToolResultContent};
+    use chrono::Utc;
+    use std::collections::HashMap;
+    use uuid::Uuid;
+
+    fn path() -> Vec<Message> {
+        let conversation_id = Uuid::new_v4();
+        let root = Message::new_root(
+            conversation_id,
+            Uuid::new_v4(),
+            "Logo ideas".to_string(),
+            "user_a".to_string(),
+        );
+        let human = Message {
+            conversation_id,
+            message_id: Uuid::new_v4(),
+            parent_message_id: Some(root.message_id),
+            role: MessageRole::Human,
+            content: ContentType::Text(TextContent {
+                text: "Draw a <fox>".to_string(),
+            }),
+            content_metadata: HashMap::new(),
+            lineage: vec![root.message_id],
+            created_at: Utc::now(),
+            created_by: "user_a".to_string(),
+        };
+        let tool = Message {
+            role: MessageRole::Assistant,
+            content: ContentType::ToolCall(ToolCallContent {
+                tool_name: "draw".to_string(),
+                arguments: serde_json::json!({ "subject": "fox" }),
+                tool_call_id: "call_1".to_string(),
+            }),
+            ..human.clone()
+        };
+        vec![root, human, tool]
+    }
+
+    #[test]
+    fn test_render_markdown() {
+        let markdown = render_markdown(&path());
+
+        assert!(markdown.starts_with("# Logo ideas\n\n"));
+        assert!(markdown.contains("## Human\n\nDraw a <fox>\n\n"));
+        assert!(markdown.contains("## Assistant\n\nTool call `draw`"));
+        assert!(markdown.contains("```json\n{\n  \"subject\": \"fox\"\n}\n```"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_content() {
+        let html = render_html(&path());
+
+        assert!(html.contains("<title>Logo ideas</title>"));
+        assert!(html.contains("<p>Draw a &lt;fox&gt;</p>"));
+        assert!(html.contains("<section class=\"message role-assistant\">"));
+    }
+
+    #[test]
+    fn test_render_anthropic_alternates_turns() {
+        let mut messages = path();
+        let result = Message {
+            role: MessageRole::Tool,
+            content: ContentType::ToolResult(ToolResultContent {
+                tool_call_id: "call_1".to_string(),
+                result: serde_json::json!("done"),
+                success: true,
+            }),
+            ..messages[1].clone()
+        };
+        let answer = Message {
+            role: MessageRole::Assistant,
+            content: ContentType::Text(TextContent {
+                text: "Here is your fox".to_string(),
+            }),
+            ..messages[1].clone()
+        };
+        messages.push(result);
+        messages.push(answer);
+
+        let body = render_anthropic(&messages);
+        let turns = body["messages"].as_array().unwrap();
+
+        assert_eq!(turns.len(), 4);
+        assert_eq!(turns[0]["role"], "user");
+        assert_eq!(turns[1]["role"], "assistant");
+        assert_eq!(turns[1]["content"][0]["type"], "tool_use");
+        assert_eq!(turns[1]["content"][0]["id"], "call_1");
+        assert_eq!(turns[2]["content"][0]["type"], "tool_result");
+        assert_eq!(turns[2]["content"][0]["tool_use_id"], "call_1");
+        assert_eq!(turns[3]["content"][0]["text"], "Here is your fox");
+        assert!(body.get("system").is_none());
+    }
+}
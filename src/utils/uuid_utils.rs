@@ -1,4 +1,21 @@
-use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use uuid::{NoContext, Timestamp, Uuid};
+
+/// A new, time-ordered (UUIDv7) message ID. IDs generated later sort after
+/// earlier ones, so a conversation's messages cluster in creation order.
+pub fn new_message_id() -> Uuid {
+    Uuid::now_v7()
+}
+
+/// A time-ordered message ID for a message created at the given time,
+/// e.g. when importing history
+pub fn message_id_at(created_at: DateTime<Utc>) -> Uuid {
+    Uuid::new_v7(Timestamp::from_unix(
+        NoContext,
+        created_at.timestamp().max(0) as u64,
+        created_at.timestamp_subsec_nanos(),
+    ))
+}
 
 /// Validates that a string is a valid UUID
 pub fn validate_uuid(uuid_str: &str) -> Result<Uuid, String> {
@@ -12,3 +29,19 @@ pub fn validate_uuid_list(uuids: &[Uuid]) -> Result<(), String> {
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_message_ids_sort_by_creation_time() {
+        let first = new_message_id();
+        let second = new_message_id();
+        assert!(first < second);
+
+        let now = Utc::now();
+        assert!(message_id_at(now - Duration::seconds(1)) < message_id_at(now));
+    }
+}
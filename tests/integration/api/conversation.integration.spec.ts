@@ -271,41 +271,59 @@ function uniqueName(prefix: string): string {
   return `${prefix}-${randomUUID()}`;
 }
 
+/** Request options identifying the caller; the server attributes writes to them */
+function asUser(userId: string): AxiosRequestConfig {
+  return { headers: { "X-User-ID": userId } };
+}
+
 async function createConversation(
   options: CreateConversationOptions = {}
 ): Promise<ConversationResponse> {
+  const createdBy = options.created_by ?? "ts-integration-user";
   const payload = {
     title: options.title ?? `Conversation ${uniqueName("ts")}`,
-    created_by: options.created_by ?? "ts-integration-user"
+    created_by: createdBy
   };
 
   const response = await httpClient.post<ConversationResponse>(
     "/api/v1/conversations",
-    payload
+    payload,
+    asUser(createdBy)
   );
 
   expect(response.status).toBe(200);
   return response.data;
 }
 
-async function getConversation(conversationId: string): Promise<ConversationResponse> {
+async function getConversation(
+  conversationId: string,
+  userId: string
+): Promise<ConversationResponse> {
   const response = await httpClient.get<ConversationResponse>(
-    `/api/v1/conversations/${conversationId}`
+    `/api/v1/conversations/${conversationId}`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
 }
 
-async function getConversationTree(conversationId: string): Promise<TreeResponse> {
+async function getConversationTree(
+  conversationId: string,
+  userId: string
+): Promise<TreeResponse> {
   const response = await httpClient.get<TreeResponse>(
-    `/api/v1/conversations/${conversationId}/tree`
+    `/api/v1/conversations/${conversationId}/tree`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
 }
 
-async function getRootMessage(conversationId: string): Promise<MessageResponse> {
-  const tree = await getConversationTree(conversationId);
+async function getRootMessage(
+  conversationId: string,
+  userId: string
+): Promise<MessageResponse> {
+  const tree = await getConversationTree(conversationId, userId);
   const root = tree.messages.find((msg) => msg.parent_message_id === null);
   if (!root) {
     throw new Error("Root message missing from conversation tree response");
@@ -328,7 +346,8 @@ async function appendMessage(
 
   const response = await httpClient.post<MessageResponse>(
     `/api/v1/conversations/${conversationId}/messages`,
-    requestBody
+    requestBody,
+    asUser(payload.created_by)
   );
 
   expect(response.status).toBe(200);
@@ -337,10 +356,12 @@ async function appendMessage(
 
 async function getMessage(
   conversationId: string,
-  messageId: string
+  messageId: string,
+  userId: string
 ): Promise<MessageResponse> {
   const response = await httpClient.get<MessageResponse>(
-    `/api/v1/conversations/${conversationId}/messages/${messageId}`
+    `/api/v1/conversations/${conversationId}/messages/${messageId}`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
@@ -348,10 +369,12 @@ async function getMessage(
 
 async function getMessageChildren(
   conversationId: string,
-  messageId: string
+  messageId: string,
+  userId: string
 ): Promise<MessageResponse[]> {
   const response = await httpClient.get<MessageResponse[]>(
-    `/api/v1/conversations/${conversationId}/messages/${messageId}/children`
+    `/api/v1/conversations/${conversationId}/messages/${messageId}/children`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
@@ -359,10 +382,12 @@ async function getMessageChildren(
 
 async function getMessageLineage(
   conversationId: string,
-  messageId: string
+  messageId: string,
+  userId: string
 ): Promise<MessageResponse[]> {
   const response = await httpClient.get<MessageResponse[]>(
-    `/api/v1/conversations/${conversationId}/messages/${messageId}/lineage`
+    `/api/v1/conversations/${conversationId}/messages/${messageId}/lineage`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
@@ -372,13 +397,15 @@ async function createBranch(
   conversationId: string,
   options: { branch_name?: string; leaf_message_id: string; created_by?: string }
 ): Promise<BranchResponse> {
+  const createdBy = options.created_by ?? "ts-branch-user";
   const response = await httpClient.post<BranchResponse>(
     `/api/v1/conversations/${conversationId}/branches`,
     {
       branch_name: options.branch_name ?? `branch-${uniqueName("ts")}`,
       leaf_message_id: options.leaf_message_id,
-      created_by: options.created_by ?? "ts-branch-user"
-    }
+      created_by: createdBy
+    },
+    asUser(createdBy)
   );
 
   expect(response.status).toBe(200);
@@ -387,10 +414,12 @@ async function createBranch(
 
 async function getBranch(
   conversationId: string,
-  branchId: string
+  branchId: string,
+  userId: string
 ): Promise<BranchResponse> {
   const response = await httpClient.get<BranchResponse>(
-    `/api/v1/conversations/${conversationId}/branches/${branchId}`
+    `/api/v1/conversations/${conversationId}/branches/${branchId}`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
@@ -398,10 +427,12 @@ async function getBranch(
 
 async function getBranchMessages(
   conversationId: string,
-  branchId: string
+  branchId: string,
+  userId: string
 ): Promise<MessageResponse[]> {
   const response = await httpClient.get<MessageResponse[]>(
-    `/api/v1/conversations/${conversationId}/branches/${branchId}/messages`
+    `/api/v1/conversations/${conversationId}/branches/${branchId}/messages`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
   return response.data;
@@ -409,15 +440,16 @@ async function getBranchMessages(
 
 async function shareConversationWithUser(
   conversationId: string,
-  options: { shared_with: string; permission: "read" | "branch" | "fork"; shared_by?: string }
+  options: { shared_with: string; permission: "read" | "branch" | "fork"; shared_by: string }
 ): Promise<ShareResponse> {
   const response = await httpClient.post<ShareResponse>(
     `/api/v1/conversations/${conversationId}/share`,
     {
       shared_with: options.shared_with,
       permission: options.permission,
-      shared_by: options.shared_by ?? "ts-share-user"
-    }
+      shared_by: options.shared_by
+    },
+    asUser(options.shared_by)
   );
 
   expect(response.status).toBe(200);
@@ -426,21 +458,25 @@ async function shareConversationWithUser(
 
 async function revokeShareStrict(
   conversationId: string,
-  sharedWith: string
+  sharedWith: string,
+  userId: string
 ): Promise<void> {
   const response = await httpClient.delete(
-    `/api/v1/conversations/${conversationId}/shares/${sharedWith}`
+    `/api/v1/conversations/${conversationId}/shares/${sharedWith}`,
+    asUser(userId)
   );
   expect(response.status).toBe(200);
 }
 
 async function revokeShareIfExists(
   conversationId: string,
-  sharedWith: string
+  sharedWith: string,
+  userId: string
 ): Promise<void> {
   try {
     const response = await httpClient.delete(
-      `/api/v1/conversations/${conversationId}/shares/${sharedWith}`
+      `/api/v1/conversations/${conversationId}/shares/${sharedWith}`,
+      asUser(userId)
     );
 
     if (response.status !== 200 && response.status !== 404) {
@@ -453,10 +489,14 @@ async function revokeShareIfExists(
   }
 }
 
-async function deleteConversationIfExists(conversationId: string): Promise<void> {
+async function deleteConversationIfExists(
+  conversationId: string,
+  userId: string
+): Promise<void> {
   try {
     const response = await httpClient.delete(
-      `/api/v1/conversations/${conversationId}`
+      `/api/v1/conversations/${conversationId}`,
+      asUser(userId)
     );
 
     if (response.status !== 200 && response.status !== 404) {
@@ -471,9 +511,10 @@ async function deleteConversationIfExists(conversationId: string): Promise<void>
 
 describe.sequential("Conversations API", () => {
   it("manages conversation lifecycle metadata", async () => {
+    const owner = "ts-lifecycle-owner";
     const conversation = await createConversation({
       title: `Lifecycle ${uniqueName("conv")}`,
-      created_by: "ts-lifecycle-owner"
+      created_by: owner
     });
 
     const conversationId = conversation.conversation_id;
@@ -484,7 +525,7 @@ describe.sequential("Conversations API", () => {
       expect(conversation.fork_from_conversation_id).toBeNull();
       expect(conversation.fork_from_message_id).toBeNull();
 
-      const fetched = await getConversation(conversationId);
+      const fetched = await getConversation(conversationId, owner);
       expect(fetched.conversation_id).toBe(conversationId);
       expect(fetched.title).toBe(conversation.title);
 
@@ -495,17 +536,18 @@ describe.sequential("Conversations API", () => {
 
       const updateResponse = await httpClient.put<ConversationResponse>(
         `/api/v1/conversations/${conversationId}`,
-        updatePayload
+        updatePayload,
+        asUser(owner)
       );
       expect(updateResponse.status).toBe(200);
       expect(updateResponse.data.title).toBe(updatePayload.title);
       expect(updateResponse.data.description).toBe(updatePayload.description);
 
-      const updated = await getConversation(conversationId);
+      const updated = await getConversation(conversationId, owner);
       expect(updated.title).toBe(updatePayload.title);
       expect(updated.description).toBe(updatePayload.description);
 
-      const tree = await getConversationTree(conversationId);
+      const tree = await getConversationTree(conversationId, owner);
       expect(tree.conversation_id).toBe(conversationId);
       expect(tree.total_messages).toBe(1);
       expect(tree.messages).toHaveLength(1);
@@ -515,7 +557,7 @@ describe.sequential("Conversations API", () => {
       expect(rootMessage.lineage).toHaveLength(1);
       expect(rootMessage.parent_message_id).toBeNull();
     } finally {
-      await deleteConversationIfExists(conversationId);
+      await deleteConversationIfExists(conversationId, owner);
     }
   });
 
@@ -528,7 +570,15 @@ describe.sequential("Conversations API", () => {
     const conversationId = conversation.conversation_id;
 
     try {
-      const rootMessage = await getRootMessage(conversationId);
+      const rootMessage = await getRootMessage(conversationId, owner);
+      // Appending takes write access, which a branch share grants
+      for (const writer of ["ts-image-bot", "ts-tool-executor"]) {
+        await shareConversationWithUser(conversationId, {
+          shared_with: writer,
+          permission: "branch",
+          shared_by: owner
+        });
+      }
 
       const humanMessage = await appendMessage(conversationId, {
         parent_message_id: rootMessage.message_id,
@@ -605,7 +655,8 @@ describe.sequential("Conversations API", () => {
 
       const fetchedToolResult = await getMessage(
         conversationId,
-        toolResultMessage.message_id
+        toolResultMessage.message_id,
+        owner
       );
       expect(fetchedToolResult.depth).toBe(fetchedToolResult.lineage.length);
       expect(fetchedToolResult.content.type).toBe("tool_result");
@@ -618,7 +669,8 @@ describe.sequential("Conversations API", () => {
 
       const lineagePath = await getMessageLineage(
         conversationId,
-        toolResultMessage.message_id
+        toolResultMessage.message_id,
+        owner
       );
       expect(lineagePath.map((msg) => msg.message_id)).toEqual(
         fetchedToolResult.lineage
@@ -626,13 +678,14 @@ describe.sequential("Conversations API", () => {
 
       const rootChildren = await getMessageChildren(
         conversationId,
-        rootMessage.message_id
+        rootMessage.message_id,
+        owner
       );
       const rootChildIds = rootChildren.map((msg) => msg.message_id);
       expect(rootChildIds).toContain(humanMessage.message_id);
       expect(rootChildIds).toContain(imageMessage.message_id);
 
-      const tree = await getConversationTree(conversationId);
+      const tree = await getConversationTree(conversationId, owner);
       expect(tree.total_messages).toBe(6);
       expect(tree.messages.map((msg) => msg.content.type)).toEqual(
         expect.arrayContaining([
@@ -645,7 +698,7 @@ describe.sequential("Conversations API", () => {
         ])
       );
     } finally {
-      await deleteConversationIfExists(conversationId);
+      await deleteConversationIfExists(conversationId, owner);
     }
   });
 
@@ -658,7 +711,7 @@ describe.sequential("Conversations API", () => {
     const conversationId = conversation.conversation_id;
 
     try {
-      const rootMessage = await getRootMessage(conversationId);
+      const rootMessage = await getRootMessage(conversationId, owner);
       const seedMessage = await appendMessage(conversationId, {
         parent_message_id: rootMessage.message_id,
         role: "human",
@@ -678,13 +731,19 @@ describe.sequential("Conversations API", () => {
 
       const branchMessages = await getBranchMessages(
         conversationId,
-        branch.branch_id
+        branch.branch_id,
+        owner
       );
       expect(branchMessages.map((msg) => msg.message_id)).toEqual([
         rootMessage.message_id,
         seedMessage.message_id
       ]);
 
+      await shareConversationWithUser(conversationId, {
+        shared_with: "ts-branch-bot",
+        permission: "branch",
+        shared_by: owner
+      });
       const followUp = await appendMessage(conversationId, {
         parent_message_id: seedMessage.message_id,
         role: "assistant",
@@ -698,7 +757,8 @@ describe.sequential("Conversations API", () => {
 
       const updatedBranch = await getBranch(
         conversationId,
-        branch.branch_id
+        branch.branch_id,
+        owner
       );
       expect(updatedBranch.leaf_message_id).toBe(followUp.message_id);
 
@@ -706,22 +766,25 @@ describe.sequential("Conversations API", () => {
         `/api/v1/conversations/${conversationId}/branches/${branch.branch_id}`,
         {
           branch_name: `Renamed ${uniqueName("branch")}`
-        }
+        },
+        asUser(owner)
       );
       expect(renameResponse.status).toBe(200);
       expect(renameResponse.data.branch_name).toContain("Renamed");
 
       const deleteResponse = await httpClient.delete(
-        `/api/v1/conversations/${conversationId}/branches/${branch.branch_id}`
+        `/api/v1/conversations/${conversationId}/branches/${branch.branch_id}`,
+        asUser(owner)
       );
       expect(deleteResponse.status).toBe(200);
     } finally {
-      await deleteConversationIfExists(conversationId);
+      await deleteConversationIfExists(conversationId, owner);
     }
   });
 
   it("forks conversations, branches, and messages with provenance metadata", async () => {
     const owner = "ts-fork-owner";
+    const forker = "ts-fork-user";
     const conversation = await createConversation({
       title: `Fork Source ${uniqueName("conv")}`,
       created_by: owner
@@ -730,7 +793,7 @@ describe.sequential("Conversations API", () => {
     const forkedConversationIds: string[] = [];
 
     try {
-      const rootMessage = await getRootMessage(conversationId);
+      const rootMessage = await getRootMessage(conversationId, owner);
       const firstChild = await appendMessage(conversationId, {
         parent_message_id: rootMessage.message_id,
         role: "human",
@@ -741,6 +804,11 @@ describe.sequential("Conversations API", () => {
         created_by: owner
       });
 
+      await shareConversationWithUser(conversationId, {
+        shared_with: "ts-fork-helper",
+        permission: "branch",
+        shared_by: owner
+      });
       const branchLeaf = await appendMessage(conversationId, {
         parent_message_id: firstChild.message_id,
         role: "assistant",
@@ -757,12 +825,20 @@ describe.sequential("Conversations API", () => {
         created_by: owner
       });
 
+      // Others fork a private conversation only with a share of their own
+      await shareConversationWithUser(conversationId, {
+        shared_with: forker,
+        permission: "fork",
+        shared_by: owner
+      });
+
       const forkConversationResponse = await httpClient.post<ConversationResponse>(
         `/api/v1/conversations/${conversationId}/fork`,
         {
           title: `Forked Conversation ${uniqueName("conv")}`,
-          created_by: "ts-fork-user"
-        }
+          created_by: forker
+        },
+        asUser(forker)
       );
       expect(forkConversationResponse.status).toBe(200);
       expect(forkConversationResponse.data.fork_from_conversation_id).toBe(
@@ -775,8 +851,9 @@ describe.sequential("Conversations API", () => {
         `/api/v1/conversations/${conversationId}/branches/${branch.branch_id}/fork`,
         {
           title: `Forked Branch ${uniqueName("conv")}`,
-          created_by: "ts-fork-user"
-        }
+          created_by: forker
+        },
+        asUser(forker)
       );
       expect(forkBranchResponse.status).toBe(200);
       expect(forkBranchResponse.data.fork_from_conversation_id).toBe(
@@ -791,8 +868,9 @@ describe.sequential("Conversations API", () => {
         `/api/v1/conversations/${conversationId}/messages/${firstChild.message_id}/fork`,
         {
           title: `Forked Message ${uniqueName("conv")}`,
-          created_by: "ts-fork-user"
-        }
+          created_by: forker
+        },
+        asUser(forker)
       );
       expect(forkFromMessageResponse.status).toBe(200);
       expect(forkFromMessageResponse.data.fork_from_conversation_id).toBe(
@@ -804,9 +882,9 @@ describe.sequential("Conversations API", () => {
       forkedConversationIds.push(forkFromMessageResponse.data.conversation_id);
     } finally {
       for (const forkId of forkedConversationIds) {
-        await deleteConversationIfExists(forkId);
+        await deleteConversationIfExists(forkId, forker);
       }
-      await deleteConversationIfExists(conversationId);
+      await deleteConversationIfExists(conversationId, owner);
     }
   });
 
@@ -835,7 +913,8 @@ describe.sequential("Conversations API", () => {
       }
 
       const sharesResponse = await httpClient.get<ShareResponse[]>(
-        `/api/v1/conversations/${conversationId}/shares`
+        `/api/v1/conversations/${conversationId}/shares`,
+        asUser(owner)
       );
       expect(sharesResponse.status).toBe(200);
       expect(sharesResponse.data).toHaveLength(3);
@@ -847,10 +926,11 @@ describe.sequential("Conversations API", () => {
         expect(permissionMap.get(userId)).toBe(permission);
       }
 
-      await revokeShareStrict(conversationId, shareUsers[1].userId);
+      await revokeShareStrict(conversationId, shareUsers[1].userId, owner);
 
       const postRevokeResponse = await httpClient.get<ShareResponse[]>(
-        `/api/v1/conversations/${conversationId}/shares`
+        `/api/v1/conversations/${conversationId}/shares`,
+        asUser(owner)
       );
       expect(postRevokeResponse.status).toBe(200);
       expect(postRevokeResponse.data).toHaveLength(2);
@@ -859,10 +939,10 @@ describe.sequential("Conversations API", () => {
       ).not.toContain(shareUsers[1].userId);
 
       // Clean up any remaining shares to avoid cross-test interference.
-      await revokeShareIfExists(conversationId, shareUsers[0].userId);
-      await revokeShareIfExists(conversationId, shareUsers[2].userId);
+      await revokeShareIfExists(conversationId, shareUsers[0].userId, owner);
+      await revokeShareIfExists(conversationId, shareUsers[2].userId, owner);
     } finally {
-      await deleteConversationIfExists(conversationId);
+      await deleteConversationIfExists(conversationId, owner);
     }
   });
 });
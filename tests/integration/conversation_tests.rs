@@ -4,12 +4,19 @@
 #[cfg(test)]
 mod tests {
     use aigc_history::{
-        config::{AppConfig, ScyllaConfig},
+        Settings,
+        config::{AppConfig, PiiConfig, ScyllaConfig},
         db::DbClient,
         domain::{ContentType, MessageRole, TextContent},
-        repositories::LineageRepository,
-        services::ConversationService,
+        object_store::S3ObjectStore,
+        repositories::Storage,
+        services::{
+            ChangeFeed, CollaborationHub, ConversationService, ImageService, NotificationService,
+            PreferenceService, ShareService,
+        },
+        utils::pii::PiiScrubber,
     };
+    use std::sync::Arc;
 
     async fn setup_test_service() -> ConversationService {
         let scylla_config = ScyllaConfig {
@@ -17,20 +24,60 @@ mod tests {
             keyspace: "aigc_history_test".to_string(),
             username: None,
             password: None,
+            local_dc: None,
+            consistency: "one".to_string(),
+            request_timeout_ms: 5_000,
+            bulk_timeout_ms: 60_000,
+            page_size: 1000,
+            connections_per_shard: 1,
+            ..Settings::default().scylla
         };
 
         let app_config = AppConfig {
             max_lineage_depth: 1000,
             max_batch_size: 100,
+            ..Settings::default().app
         };
 
         let db_client = DbClient::new(&scylla_config)
             .await
             .expect("Failed to connect to test database");
 
-        let lineage_repo = LineageRepository::new(db_client);
-
-        ConversationService::new(lineage_repo, app_config)
+        let storage = Storage::scylla(db_client);
+        let change_feed = ChangeFeed::new(storage.changes, Arc::new(CollaborationHub::new()));
+
+        let notifications = Arc::new(NotificationService::new(
+            storage.notifications,
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        ));
+
+        let pii = Arc::new(PiiScrubber::new(&PiiConfig {
+            scrub_on_write: false,
+        }));
+
+        let images = Arc::new(ImageService::new(
+            storage.images,
+            storage.lineage.clone(),
+            Arc::new(S3ObjectStore::new(Settings::default().s3)),
+            Settings::default().images,
+        ));
+
+        let shares = Arc::new(ShareService::new(
+            storage.shares,
+            change_feed.clone(),
+            notifications.clone(),
+            Arc::new(PreferenceService::new(storage.preferences.clone())),
+        ));
+
+        ConversationService::new(
+            storage.lineage,
+            change_feed,
+            app_config,
+            notifications,
+            pii,
+            images,
+            shares,
+        )
     }
 
     #[tokio::test]